@@ -0,0 +1,132 @@
+//! The arena linked list against the pointer-based ones.
+//!
+//! Run with `cargo bench --bench arena_list`. Three workloads:
+//!
+//! - push to the back, pop from the front: one allocation and free per element for the
+//!   boxed and `Rc` lists, none once the arena has grown;
+//! - walk the whole list: the arena's nodes sit in one `Vec`, the others wherever the
+//!   allocator put them. The `Rc<RefCell>` deque has no borrowing iterator, so it sits
+//!   this one and the next out;
+//! - a cursor walking the list, inserting after every node and removing every third.
+
+use rust_practice::arena_list::ArenaList;
+use rust_practice::deque;
+use rust_practice::linked_list::LinkedList;
+use std::time::{Duration, Instant};
+
+const ELEMS: u64 = 1_000_000;
+const CURSOR_ELEMS: u64 = 100_000;
+
+fn time(f: impl FnOnce()) -> Duration {
+    let start = Instant::now();
+    f();
+    start.elapsed()
+}
+
+fn report(workload: &str, arena: Duration, boxed: Duration, rc: Option<Duration>) {
+    print!(
+        "{:<16} arena {:>8.1} ms   boxed {:>8.1} ms",
+        workload,
+        arena.as_secs_f64() * 1e3,
+        boxed.as_secs_f64() * 1e3
+    );
+    match rc {
+        Some(rc) => println!("   rc {:>8.1} ms", rc.as_secs_f64() * 1e3),
+        None => println!(),
+    }
+}
+
+fn push_pop() {
+    let arena = time(|| {
+        let mut list = ArenaList::new();
+        for i in 0..ELEMS {
+            list.push_back(i);
+        }
+        while list.pop_front().is_some() {}
+    });
+    let boxed = time(|| {
+        let mut list = LinkedList::new();
+        for i in 0..ELEMS {
+            list.push_back(i);
+        }
+        while list.pop_front().is_some() {}
+    });
+    let rc = time(|| {
+        let mut list = deque::List::new();
+        for i in 0..ELEMS {
+            list.push_back(i);
+        }
+        while list.pop_front().is_some() {}
+    });
+    report("push, pop", arena, boxed, Some(rc));
+}
+
+fn iterate() {
+    // Build both from the two ends alternately, so neighbours in the list weren't pushed
+    // one after another
+    let mut arena_list = ArenaList::new();
+    let mut boxed_list = LinkedList::new();
+    for i in 0..ELEMS {
+        if i % 2 == 0 {
+            arena_list.push_back(i);
+            boxed_list.push_back(i);
+        } else {
+            arena_list.push_front(i);
+            boxed_list.push_front(i);
+        }
+    }
+    let mut sums = (0, 0);
+    let arena = time(|| sums.0 = arena_list.iter().sum());
+    let boxed = time(|| sums.1 = boxed_list.iter().sum());
+    assert_eq!(sums.0, sums.1);
+    report("iterate", arena, boxed, None);
+}
+
+fn cursor_edits() {
+    let mut arena_list: ArenaList<u64> = ArenaList::new();
+    let mut boxed_list: LinkedList<u64> = LinkedList::new();
+    for i in 0..CURSOR_ELEMS {
+        arena_list.push_back(i);
+        boxed_list.push_back(i);
+    }
+
+    let arena = time(|| {
+        let mut cursor = arena_list.cursor_mut();
+        cursor.move_next();
+        let mut i = 0;
+        while let Some(&mut elem) = cursor.current() {
+            if i % 3 == 0 {
+                cursor.remove_current();
+            } else {
+                cursor.insert_after(elem * 2);
+                cursor.move_next();
+                cursor.move_next();
+            }
+            i += 1;
+        }
+    });
+    let boxed = time(|| {
+        let mut cursor = boxed_list.cursor_mut();
+        cursor.move_next();
+        let mut i = 0;
+        while let Some(&mut elem) = cursor.current() {
+            if i % 3 == 0 {
+                cursor.remove_current();
+            } else {
+                cursor.insert_after(elem * 2);
+                cursor.move_next();
+                cursor.move_next();
+            }
+            i += 1;
+        }
+    });
+    assert!(arena_list.iter().eq(boxed_list.iter()));
+    report("cursor edits", arena, boxed, None);
+}
+
+fn main() {
+    println!("{} elements ({} for cursor edits):", ELEMS, CURSOR_ELEMS);
+    push_pop();
+    iterate();
+    cursor_edits();
+}
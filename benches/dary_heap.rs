@@ -0,0 +1,78 @@
+//! How the arity of a `DaryHeap` trades tree depth against comparisons.
+//!
+//! Run with `cargo bench --bench dary_heap`. Each heap takes the same random pushes and
+//! is then popped empty. Wider nodes make push cheaper, since it compares once per level,
+//! while pop has to pick the largest of `D` children at each of its fewer levels.
+
+use rust_practice::dary_heap::DaryHeap;
+use std::cell::Cell;
+use std::cmp::Ordering;
+use std::time::{Duration, Instant};
+
+const ELEMS: usize = 1_000_000;
+
+thread_local! {
+    static COMPARES: Cell<u64> = const { Cell::new(0) };
+}
+
+/// A `u64` that counts how often it gets compared.
+#[derive(PartialEq, Eq)]
+struct Counted(u64);
+
+impl PartialOrd for Counted {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Counted {
+    fn cmp(&self, other: &Self) -> Ordering {
+        COMPARES.with(|c| c.set(c.get() + 1));
+        self.0.cmp(&other.0)
+    }
+}
+
+/// Runs `f` and returns how long it took and how many comparisons it made.
+fn measure(f: impl FnOnce()) -> (Duration, u64) {
+    COMPARES.with(|c| c.set(0));
+    let start = Instant::now();
+    f();
+    (start.elapsed(), COMPARES.with(|c| c.get()))
+}
+
+fn run<const D: usize>(keys: &[u64]) {
+    let mut heap: DaryHeap<Counted, D> = DaryHeap::with_capacity(keys.len());
+    let (push_time, push_compares) = measure(|| {
+        for &key in keys {
+            heap.push(Counted(key));
+        }
+    });
+    let height = heap.height();
+    let (pop_time, pop_compares) = measure(|| while heap.pop().is_some() {});
+
+    let n = keys.len() as f64;
+    println!(
+        "d = {:<2}  height {:>2}   push {:>5.2} cmp {:>6.1} ns   pop {:>5.2} cmp {:>6.1} ns",
+        D,
+        height,
+        push_compares as f64 / n,
+        push_time.as_nanos() as f64 / n,
+        pop_compares as f64 / n,
+        pop_time.as_nanos() as f64 / n,
+    );
+}
+
+fn main() {
+    let mut x: u64 = 1;
+    let keys: Vec<u64> = (0..ELEMS)
+        .map(|_| {
+            x = x.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1);
+            x >> 16
+        })
+        .collect();
+
+    println!("{} random keys, per operation:", ELEMS);
+    run::<2>(&keys);
+    run::<4>(&keys);
+    run::<8>(&keys);
+}
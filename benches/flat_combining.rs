@@ -0,0 +1,75 @@
+//! Throughput of the flat-combining queue against a plain `Mutex<VecDeque>`.
+//!
+//! Run with `cargo bench --bench flat_combining`. Every thread alternates enqueues and
+//! dequeues, which is the worst case for a lock: every single operation contends.
+
+use rust_practice::flat_combining::FcQueue;
+use std::collections::VecDeque;
+use std::sync::{Arc, Barrier, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const OPS_PER_THREAD: usize = 200_000;
+
+/// Runs `op(thread, i)` `OPS_PER_THREAD` times on each of `threads` threads, all starting
+/// together, and returns how long the slowest one took.
+fn run<F>(threads: usize, op: F) -> Duration
+where
+    F: Fn(usize, usize) + Send + Sync + 'static,
+{
+    let op = Arc::new(op);
+    let barrier = Arc::new(Barrier::new(threads + 1));
+    let handles: Vec<_> = (0..threads)
+        .map(|t| {
+            let op = op.clone();
+            let barrier = barrier.clone();
+            thread::spawn(move || {
+                barrier.wait();
+                for i in 0..OPS_PER_THREAD {
+                    op(t, i);
+                }
+            })
+        })
+        .collect();
+
+    barrier.wait();
+    let start = Instant::now();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    start.elapsed()
+}
+
+fn report(name: &str, threads: usize, elapsed: Duration) {
+    let ops = (threads * OPS_PER_THREAD) as f64;
+    println!(
+        "{:<18} {:>2} threads  {:>8.2} Mops/s",
+        name,
+        threads,
+        ops / elapsed.as_secs_f64() / 1e6
+    );
+}
+
+fn main() {
+    for threads in [1, 2, 4, 8] {
+        let queue = Arc::new(FcQueue::new());
+        let elapsed = run(threads, move |t, i| {
+            if i % 2 == 0 {
+                queue.enqueue(t + i);
+            } else {
+                queue.dequeue();
+            }
+        });
+        report("flat combining", threads, elapsed);
+
+        let queue = Arc::new(Mutex::new(VecDeque::new()));
+        let elapsed = run(threads, move |t, i| {
+            if i % 2 == 0 {
+                queue.lock().unwrap().push_back(t + i);
+            } else {
+                queue.lock().unwrap().pop_front();
+            }
+        });
+        report("Mutex<VecDeque>", threads, elapsed);
+    }
+}
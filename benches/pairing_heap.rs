@@ -0,0 +1,157 @@
+//! The pairing heap against the array-backed binary heap.
+//!
+//! Run with `cargo bench --bench pairing_heap`. The binary heap is a max-heap, so it
+//! holds `Reverse` keys to come out in the same order. Three workloads:
+//!
+//! - push everything, then pop everything: the binary heap's home ground, with its
+//!   contiguous array and no allocation per element;
+//! - merge many small heaps into one: O(1) each for the pairing heap, while the binary heap
+//!   has to push every element of the smaller one;
+//! - a Dijkstra-like mix of pops and key decreases: the pairing heap cuts and melds, the
+//!   binary heap pushes a duplicate with the lower key and skips stale ones as they pop.
+
+use rust_practice::heap::BinaryHeap;
+use rust_practice::pairing_heap::PairingHeap;
+use std::cmp::Reverse;
+use std::time::{Duration, Instant};
+
+const ELEMS: usize = 1_000_000;
+const SMALL_HEAPS: usize = 10_000;
+
+fn time(f: impl FnOnce()) -> Duration {
+    let start = Instant::now();
+    f();
+    start.elapsed()
+}
+
+fn report(workload: &str, pairing: Duration, binary: Duration) {
+    println!(
+        "{:<16} pairing {:>8.1} ms   binary {:>8.1} ms",
+        workload,
+        pairing.as_secs_f64() * 1e3,
+        binary.as_secs_f64() * 1e3
+    );
+}
+
+fn push_pop(keys: &[u64]) {
+    let pairing = time(|| {
+        let mut heap = PairingHeap::new();
+        for &key in keys {
+            heap.push(key);
+        }
+        while heap.pop().is_some() {}
+    });
+    let binary = time(|| {
+        let mut heap = BinaryHeap::new();
+        for &key in keys {
+            heap.push(Reverse(key));
+        }
+        while heap.pop().is_some() {}
+    });
+    report("push, pop", pairing, binary);
+}
+
+fn merge(keys: &[u64]) {
+    let per_heap = keys.len() / SMALL_HEAPS;
+    let (mut pairings, mut binaries): (Vec<PairingHeap<u64>>, Vec<BinaryHeap<Reverse<u64>>>) = keys
+        .chunks(per_heap)
+        .map(|chunk| {
+            (
+                chunk.iter().copied().collect(),
+                chunk.iter().copied().map(Reverse).collect(),
+            )
+        })
+        .unzip();
+
+    // Freeing the merged heaps isn't part of merging, so it happens outside the timings
+    let mut all = PairingHeap::new();
+    let pairing = time(|| {
+        for heap in pairings.drain(..) {
+            all.merge(heap);
+        }
+    });
+    assert_eq!(all.len(), keys.len());
+    let mut all = BinaryHeap::new();
+    let binary = time(|| {
+        for heap in binaries.drain(..) {
+            all.extend(heap.into_vec());
+        }
+    });
+    assert_eq!(all.len(), keys.len());
+    report("merge", pairing, binary);
+}
+
+/// Three pseudo-random neighbours of `i`, with edge weights.
+fn neighbours(i: usize, n: usize) -> impl Iterator<Item = (usize, u64)> {
+    (1..=3).map(move |k| {
+        let j = (i.wrapping_mul(2_654_435_761) + k * 40_503) % n;
+        (j, ((i ^ j) % 1000) as u64)
+    })
+}
+
+fn decrease_key(keys: &[u64]) {
+    // Every element starts at its random key, and popping one offers its neighbours a
+    // lower one, as Dijkstra's algorithm does with distances
+    let n = keys.len();
+    let mut pairing_dist = keys.to_vec();
+    let pairing = time(|| {
+        let dist = &mut pairing_dist;
+        let mut heap = PairingHeap::new();
+        let handles: Vec<_> = keys
+            .iter()
+            .enumerate()
+            .map(|(i, &key)| heap.push((key, i)))
+            .collect();
+        let mut done = vec![false; n];
+        while let Some((d, i)) = heap.pop() {
+            done[i] = true;
+            for (j, weight) in neighbours(i, n) {
+                if !done[j] && d + weight < dist[j] {
+                    dist[j] = d + weight;
+                    // The handle is live: `j` hasn't been popped
+                    unsafe { heap.decrease_key(handles[j], (dist[j], j)) };
+                }
+            }
+        }
+    });
+
+    let mut binary_dist = keys.to_vec();
+    let binary = time(|| {
+        let dist = &mut binary_dist;
+        let mut heap: BinaryHeap<_> = keys
+            .iter()
+            .enumerate()
+            .map(|(i, &key)| Reverse((key, i)))
+            .collect();
+        let mut done = vec![false; n];
+        while let Some(Reverse((d, i))) = heap.pop() {
+            if done[i] {
+                continue;
+            }
+            done[i] = true;
+            for (j, weight) in neighbours(i, n) {
+                if !done[j] && d + weight < dist[j] {
+                    dist[j] = d + weight;
+                    heap.push(Reverse((dist[j], j)));
+                }
+            }
+        }
+    });
+    assert_eq!(pairing_dist, binary_dist);
+    report("decrease key", pairing, binary);
+}
+
+fn main() {
+    let mut x: u64 = 1;
+    let keys: Vec<u64> = (0..ELEMS)
+        .map(|_| {
+            x = x.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1);
+            x >> 16
+        })
+        .collect();
+
+    println!("{} random keys:", ELEMS);
+    push_pop(&keys);
+    merge(&keys);
+    decrease_key(&keys);
+}
@@ -0,0 +1,368 @@
+//! # Aho–Corasick
+//!
+//! Finds every occurrence of many patterns in one pass over the input (Aho and Corasick,
+//! 1975). The patterns go into a trie, and each trie state also gets a *failure link* to
+//! the state for the longest proper suffix of its path that is also in the trie. When the
+//! next byte has no edge, following failure links keeps as much of the match so far as
+//! could still be useful, so the input never has to be read twice:
+//!
+//! ```text
+//! patterns: he, she, his, hers
+//!
+//!   root ─h─ 1 ─e─ 2* ─r─ 8 ─s─ 9*        failure links:  4 → 1   (s-h     → h)
+//!     │      └─i─ 6 ─s─ 7*                                 5 → 2   (s-h-e   → h-e)
+//!     └─s─ 3 ─h─ 4 ─e─ 5*                                  7 → 3   (h-i-s   → s)
+//!                                                          9 → 3   (h-e-r-s → s)
+//! ```
+//!
+//! Reading "shers" walks root, 3, 4, 5 and reports "she", then sees that 5's failure link
+//! is a match too and reports "he". The 'r' has no edge out of 5, so it falls back to 2 and
+//! carries on to 8 and 9 for "hers". Each state also keeps a *dictionary link*: the
+//! nearest state down its failure chain that ends a pattern, so reporting skips the ones
+//! that don't.
+//!
+//! Building takes time linear in the total pattern length, times the log of the alphabet
+//! for the sorted edges. Searching takes O(n + matches), since each byte moves at most one
+//! step deeper and every failure link followed moves back up.
+//!
+//! [`AhoCorasick::find_overlapping_iter`] reports every match. [`AhoCorasick::find_iter`]
+//! only reports matches that don't overlap: as soon as it sees one it goes back to the
+//! root, so it picks the match that ends first, and the longest of those ending together.
+//! Both read their input as a stream of bytes, so it can come from anywhere.
+
+use std::collections::{BTreeMap, VecDeque};
+
+const ROOT: usize = 0;
+
+pub struct AhoCorasick {
+    states: Vec<State>,
+    // Length of each pattern, by index
+    lengths: Vec<usize>,
+}
+
+struct State {
+    next: BTreeMap<u8, usize>,
+    // State for the longest proper suffix of this path that is also in the trie
+    fail: usize,
+    // Nearest state down the failure chain that ends a pattern
+    dict: Option<usize>,
+    // Pattern ending exactly here
+    pattern: Option<usize>,
+}
+
+impl State {
+    fn new() -> Self {
+        State {
+            next: BTreeMap::new(),
+            fail: ROOT,
+            dict: None,
+            pattern: None,
+        }
+    }
+}
+
+/// One occurrence of a pattern: its index, and where in the input it starts and ends.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Match {
+    pub pattern: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl AhoCorasick {
+    /// Builds an automaton for `patterns`, which matches report by their index. If a
+    /// pattern is given more than once, only its first index is reported.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a pattern is empty.
+    pub fn new<I, P>(patterns: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<[u8]>,
+    {
+        let mut states = vec![State::new()];
+        let mut lengths = Vec::new();
+
+        for (index, pattern) in patterns.into_iter().enumerate() {
+            let pattern = pattern.as_ref();
+            assert!(!pattern.is_empty(), "patterns must not be empty");
+            let mut state = ROOT;
+            for &byte in pattern {
+                state = match states[state].next.get(&byte) {
+                    Some(&next) => next,
+                    None => {
+                        states.push(State::new());
+                        let next = states.len() - 1;
+                        states[state].next.insert(byte, next);
+                        next
+                    }
+                };
+            }
+            states[state].pattern.get_or_insert(index);
+            lengths.push(pattern.len());
+        }
+
+        // Breadth first, so the failure link of every shallower state is already known
+        let mut queue: VecDeque<usize> = states[ROOT].next.values().copied().collect();
+        while let Some(state) = queue.pop_front() {
+            let edges: Vec<(u8, usize)> =
+                states[state].next.iter().map(|(&b, &s)| (b, s)).collect();
+            for (byte, child) in edges {
+                let mut fallback = states[state].fail;
+                let fail = loop {
+                    if let Some(&next) = states[fallback].next.get(&byte) {
+                        break next;
+                    }
+                    if fallback == ROOT {
+                        break ROOT;
+                    }
+                    fallback = states[fallback].fail;
+                };
+                states[child].fail = fail;
+                states[child].dict = match states[fail].pattern {
+                    Some(_) => Some(fail),
+                    None => states[fail].dict,
+                };
+                queue.push_back(child);
+            }
+        }
+
+        AhoCorasick { states, lengths }
+    }
+
+    pub fn pattern_count(&self) -> usize {
+        self.lengths.len()
+    }
+
+    /// Returns how many states the automaton has, the root included.
+    pub fn state_count(&self) -> usize {
+        self.states.len()
+    }
+
+    /// Follows the edge for `byte`, falling back along failure links until there is one.
+    fn step(&self, mut state: usize, byte: u8) -> usize {
+        loop {
+            if let Some(&next) = self.states[state].next.get(&byte) {
+                return next;
+            }
+            if state == ROOT {
+                return ROOT;
+            }
+            state = self.states[state].fail;
+        }
+    }
+
+    /// Returns whether any pattern occurs in the input, stopping at the first one.
+    pub fn is_match<I>(&self, input: I) -> bool
+    where
+        I: IntoIterator<Item = u8>,
+    {
+        self.find_iter(input).next().is_some()
+    }
+
+    /// Iterates over matches that don't overlap, in the order they end.
+    pub fn find_iter<I>(&self, input: I) -> FindIter<'_, I::IntoIter>
+    where
+        I: IntoIterator<Item = u8>,
+    {
+        FindIter::new(self, input.into_iter(), false)
+    }
+
+    /// Iterates over every match, in the order they end, longest first among those
+    /// ending together.
+    pub fn find_overlapping_iter<I>(&self, input: I) -> FindIter<'_, I::IntoIter>
+    where
+        I: IntoIterator<Item = u8>,
+    {
+        FindIter::new(self, input.into_iter(), true)
+    }
+}
+
+/// Iterator over matches, reading its input lazily, one byte at a time.
+pub struct FindIter<'a, I> {
+    automaton: &'a AhoCorasick,
+    input: I,
+    overlapping: bool,
+    state: usize,
+    // Bytes read so far
+    position: usize,
+    // Next state whose pattern ends at `position` and hasn't been reported yet
+    pending: Option<usize>,
+}
+
+impl<'a, I> FindIter<'a, I> {
+    fn new(automaton: &'a AhoCorasick, input: I, overlapping: bool) -> Self {
+        FindIter {
+            automaton,
+            input,
+            overlapping,
+            state: ROOT,
+            position: 0,
+            pending: None,
+        }
+    }
+}
+
+impl<'a, I: Iterator<Item = u8>> Iterator for FindIter<'a, I> {
+    type Item = Match;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let states = &self.automaton.states;
+        loop {
+            if let Some(found) = self.pending.take() {
+                if self.overlapping {
+                    self.pending = states[found].dict;
+                }
+                let pattern = states[found].pattern.unwrap();
+                return Some(Match {
+                    pattern,
+                    start: self.position - self.automaton.lengths[pattern],
+                    end: self.position,
+                });
+            }
+
+            let byte = self.input.next()?;
+            self.position += 1;
+            self.state = self.automaton.step(self.state, byte);
+            self.pending = match states[self.state].pattern {
+                Some(_) => Some(self.state),
+                None => states[self.state].dict,
+            };
+            if self.pending.is_some() && !self.overlapping {
+                // Whatever comes next must start after this match
+                self.state = ROOT;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AhoCorasick, Match};
+
+    fn found(iter: impl Iterator<Item = Match>) -> Vec<(usize, usize, usize)> {
+        iter.map(|m| (m.pattern, m.start, m.end)).collect()
+    }
+
+    #[test]
+    fn basics() {
+        let ac = AhoCorasick::new(["he", "she", "his", "hers"]);
+        assert_eq!(ac.pattern_count(), 4);
+        assert_eq!(ac.state_count(), 10);
+
+        let text = "ushers";
+        assert_eq!(
+            found(ac.find_overlapping_iter(text.bytes())),
+            vec![(1, 1, 4), (0, 2, 4), (3, 2, 6)]
+        );
+        // "she" ends first, which rules out both the others
+        assert_eq!(found(ac.find_iter(text.bytes())), vec![(1, 1, 4)]);
+        assert_eq!(
+            found(ac.find_iter("he said his hers".bytes())),
+            vec![(0, 0, 2), (2, 8, 11), (0, 12, 14)]
+        );
+
+        assert!(ac.is_match("this".bytes()));
+        assert!(!ac.is_match("hush".bytes()));
+        assert!(!ac.is_match("".bytes()));
+
+        // Check an automaton without patterns behaves right
+        let none = AhoCorasick::new(Vec::<&str>::new());
+        assert_eq!(none.find_overlapping_iter("abc".bytes()).count(), 0);
+    }
+
+    #[test]
+    fn nested_and_repeated_patterns() {
+        // Every suffix of a match that is a pattern gets reported, longest first
+        let ac = AhoCorasick::new(["a", "aa", "aaa", "aa"]);
+        assert_eq!(
+            found(ac.find_overlapping_iter("aaa".bytes())),
+            vec![
+                (0, 0, 1),
+                (1, 0, 2),
+                (0, 1, 2),
+                (2, 0, 3),
+                (1, 1, 3),
+                (0, 2, 3)
+            ]
+        );
+        assert_eq!(
+            found(ac.find_iter("aaa".bytes())),
+            vec![(0, 0, 1), (0, 1, 2), (0, 2, 3)]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "must not be empty")]
+    fn empty_pattern() {
+        AhoCorasick::new(["a", ""]);
+    }
+
+    #[test]
+    fn streaming_input() {
+        use std::io::{BufReader, Read};
+
+        // A match straddling two reads is found like any other
+        let ac = AhoCorasick::new(["needle"]);
+        let haystack = "hay".repeat(1000) + "needle" + &"hay".repeat(1000);
+        let reader = BufReader::with_capacity(16, haystack.as_bytes());
+        let bytes = reader.bytes().map(|b| b.unwrap());
+        assert_eq!(found(ac.find_iter(bytes)), vec![(0, 3000, 3006)]);
+
+        // Nothing past the first match is read
+        let mut read = 0;
+        let counting = haystack.bytes().inspect(|_| read += 1);
+        assert!(ac.is_match(counting));
+        assert_eq!(read, 3006);
+    }
+
+    #[test]
+    fn against_brute_force() {
+        let mut x: u32 = 5;
+        let mut random = |n: u32| {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (x >> 16) % n
+        };
+        for _ in 0..50 {
+            let mut patterns: Vec<Vec<u8>> = (0..random(8) + 1)
+                .map(|_| {
+                    (0..random(4) + 1)
+                        .map(|_| b"ab"[random(2) as usize])
+                        .collect()
+                })
+                .collect();
+            patterns.sort();
+            patterns.dedup();
+            let text: Vec<u8> = (0..200).map(|_| b"abc"[random(3) as usize]).collect();
+            let ac = AhoCorasick::new(&patterns);
+
+            let mut overlapping = Vec::new();
+            let mut non_overlapping = Vec::new();
+            let mut free_from = 0;
+            for end in 1..=text.len() {
+                // Longest first, like the automaton
+                let mut ending: Vec<usize> = (0..patterns.len())
+                    .filter(|&i| text[..end].ends_with(&patterns[i]))
+                    .collect();
+                ending.sort_by_key(|&i| std::cmp::Reverse(patterns[i].len()));
+                for &i in &ending {
+                    overlapping.push((i, end - patterns[i].len(), end));
+                }
+                if let Some(&i) = ending
+                    .iter()
+                    .find(|&&i| end - patterns[i].len() >= free_from)
+                {
+                    non_overlapping.push((i, end - patterns[i].len(), end));
+                    free_from = end;
+                }
+            }
+
+            assert_eq!(
+                found(ac.find_overlapping_iter(text.iter().copied())),
+                overlapping
+            );
+            assert_eq!(found(ac.find_iter(text.iter().copied())), non_overlapping);
+        }
+    }
+}
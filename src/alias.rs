@@ -0,0 +1,257 @@
+//! # Alias method
+//!
+//! Draws random indices with probabilities in proportion to a list of weights, in O(1)
+//! per draw after O(n) setup. The obvious way, a binary search over the running totals of
+//! the weights, is O(log n) a draw.
+//!
+//! The trick (Walker, 1974) is to even the weights out into `n` columns of equal height,
+//! scaled so the average weight is 1. Each column holds at most two outcomes: the part of
+//! its own index's weight that fits, and a top-up taken from one index with weight to
+//! spare, its *alias*:
+//!
+//! ```text
+//!   weights 1 2 5 0, scaled to 0.5 1 2.5 0
+//!
+//!   column     0     1     2     3
+//!            +---+ +---+ +---+ +---+
+//!            | 2 | |   | |   | |   |       prob  0.5   1   1   0
+//!            |---| | 1 | | 2 | | 2 |       alias   2   -   -   2
+//!            | 0 | |   | |   | |   |
+//!            +---+ +---+ +---+ +---+
+//! ```
+//!
+//! A draw picks a column uniformly, then flips a coin weighted by the column's `prob` to
+//! choose between its own index and its alias. Together, 2's three parts add up to 2.5 of
+//! the 4 columns' height, so it comes up 5 / 8 of the time, as its weight says.
+//!
+//! Building the columns is Vose's (1991) version: sort the scaled weights into those
+//! below 1 and those at or above, and repeatedly fill a small one's column with some of a
+//! large one, which leaves the large one smaller and maybe now small. Each step finishes
+//! one column, so it's O(n), and it's careful enough with rounding that floating point
+//! leftovers can't send a draw out of bounds.
+//!
+//! The table can't be updated in place when weights change, since one weight moving
+//! shuffles many columns, but [`AliasTable::rebuild`] starts over reusing the allocations.
+
+use crate::rng::XorShift;
+
+#[derive(Clone, Debug)]
+pub struct AliasTable {
+    // Chance a draw landing in column i keeps i rather than taking alias[i]
+    prob: Vec<f64>,
+    alias: Vec<usize>,
+    // Work lists for building, kept to save reallocating them on a rebuild
+    small: Vec<usize>,
+    large: Vec<usize>,
+    rng: XorShift,
+}
+
+impl AliasTable {
+    /// Builds a table that draws index `i` with probability `weights[i] / sum(weights)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are no weights, any is negative, infinite or NaN, or all are zero.
+    pub fn new(weights: &[f64]) -> Self {
+        Self::with_rng(weights, XorShift::from_entropy())
+    }
+
+    /// Like `new`, but the same seed always makes the same sequence of draws.
+    pub fn with_seed(weights: &[f64], seed: u64) -> Self {
+        Self::with_rng(weights, XorShift::new(seed))
+    }
+
+    fn with_rng(weights: &[f64], rng: XorShift) -> Self {
+        let mut table = AliasTable {
+            prob: Vec::new(),
+            alias: Vec::new(),
+            small: Vec::new(),
+            large: Vec::new(),
+            rng,
+        };
+        table.rebuild(weights);
+        table
+    }
+
+    /// Returns the number of weights, and so of possible indices.
+    pub fn len(&self) -> usize {
+        self.prob.len()
+    }
+
+    /// Always `false`, since a table needs at least one weight. Here for symmetry with
+    /// `len`.
+    pub fn is_empty(&self) -> bool {
+        self.prob.is_empty()
+    }
+
+    /// Replaces the weights, as if building a new table, but keeps the allocations and
+    /// the random number generator.
+    ///
+    /// # Panics
+    ///
+    /// Panics on the same weights `new` does.
+    pub fn rebuild(&mut self, weights: &[f64]) {
+        assert!(!weights.is_empty(), "need at least one weight");
+        assert!(
+            weights.iter().all(|w| *w >= 0.0 && w.is_finite()),
+            "weights must be finite and not negative"
+        );
+        let total: f64 = weights.iter().sum();
+        assert!(total > 0.0, "weights must not all be zero");
+
+        let n = weights.len();
+        self.prob.clear();
+        self.prob
+            .extend(weights.iter().map(|w| w * n as f64 / total));
+        self.alias.clear();
+        self.alias.extend(0..n);
+        self.small.clear();
+        self.large.clear();
+        for (i, &p) in self.prob.iter().enumerate() {
+            if p < 1.0 {
+                self.small.push(i);
+            } else {
+                self.large.push(i);
+            }
+        }
+
+        // Top up a small column from a large one, which shrinks by what it gave
+        while let (Some(&less), Some(&more)) = (self.small.last(), self.large.last()) {
+            self.small.pop();
+            self.alias[less] = more;
+            self.prob[more] -= 1.0 - self.prob[less];
+            if self.prob[more] < 1.0 {
+                self.large.pop();
+                self.small.push(more);
+            }
+        }
+        // Whatever's left is 1 but for rounding, and must not point at an alias
+        for &i in self.small.iter().chain(&self.large) {
+            self.prob[i] = 1.0;
+        }
+    }
+
+    /// Draws a random index.
+    pub fn sample(&mut self) -> usize {
+        let column = self.rng.below(self.prob.len() as u64) as usize;
+        if self.rng.next_f64() < self.prob[column] {
+            column
+        } else {
+            self.alias[column]
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::AliasTable;
+
+    /// Adds up each index's share of the columns, which is its exact probability of being
+    /// drawn.
+    fn probabilities(table: &AliasTable) -> Vec<f64> {
+        let n = table.len() as f64;
+        let mut p = vec![0.0; table.len()];
+        for (i, (&prob, &alias)) in table.prob.iter().zip(&table.alias).enumerate() {
+            p[i] += prob / n;
+            p[alias] += (1.0 - prob) / n;
+        }
+        p
+    }
+
+    fn assert_close(a: &[f64], b: &[f64]) {
+        assert_eq!(a.len(), b.len());
+        for (x, y) in a.iter().zip(b) {
+            assert!((x - y).abs() < 1e-9, "{:?} vs {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn basics() {
+        let mut table = AliasTable::with_seed(&[1.0, 2.0, 5.0, 0.0], 42);
+        assert_eq!(table.len(), 4);
+        assert!(!table.is_empty());
+        assert_close(&probabilities(&table), &[0.125, 0.25, 0.625, 0.0]);
+        // The only draws left are the ones with weight
+        assert!((0..1000).all(|_| table.sample() != 3));
+
+        // A single weight always wins
+        let mut table = AliasTable::new(&[0.3]);
+        assert!((0..100).all(|_| table.sample() == 0));
+    }
+
+    #[test]
+    #[should_panic(expected = "need at least one weight")]
+    fn no_weights() {
+        AliasTable::new(&[]);
+    }
+
+    #[test]
+    #[should_panic(expected = "weights must not all be zero")]
+    fn zero_weights() {
+        AliasTable::new(&[0.0, 0.0]);
+    }
+
+    #[test]
+    #[should_panic(expected = "weights must be finite and not negative")]
+    fn nan_weight() {
+        AliasTable::new(&[1.0, f64::NAN]);
+    }
+
+    #[test]
+    fn rebuild() {
+        let mut table = AliasTable::with_seed(&[1.0, 1.0], 5);
+        table.rebuild(&[0.0, 3.0, 1.0]);
+        assert_eq!(table.len(), 3);
+        assert_close(&probabilities(&table), &[0.0, 0.75, 0.25]);
+        assert!((0..1000).all(|_| table.sample() != 0));
+    }
+
+    #[test]
+    fn random_weights() {
+        // Exact probabilities from the table, for weights of wildly different sizes
+        let mut x: u32 = 1;
+        for n in 1..50 {
+            let weights: Vec<f64> = (0..n)
+                .map(|_| {
+                    x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                    let w = (x >> 8) as f64;
+                    if x >> 28 == 0 {
+                        0.0
+                    } else {
+                        w * w
+                    }
+                })
+                .collect();
+            let total: f64 = weights.iter().sum();
+            if total == 0.0 {
+                continue;
+            }
+            let table = AliasTable::new(&weights);
+            let expected: Vec<f64> = weights.iter().map(|w| w / total).collect();
+            assert_close(&probabilities(&table), &expected);
+        }
+    }
+
+    #[test]
+    fn draws_follow_the_weights() {
+        let weights = [1.0, 2.0, 3.0, 4.0];
+        let draws = if cfg!(miri) { 4_000 } else { 400_000 };
+        let mut table = AliasTable::with_seed(&weights, 0x9e37_79b9_7f4a_7c15);
+        let mut counts = [0u32; 4];
+        for _ in 0..draws {
+            counts[table.sample()] += 1;
+        }
+        let tolerance = if cfg!(miri) { 0.15 } else { 0.02 };
+        for (i, &count) in counts.iter().enumerate() {
+            let expected = draws as f64 * weights[i] / 10.0;
+            let off = (count as f64 - expected).abs() / expected;
+            assert!(
+                off < tolerance,
+                "{} drew {} times, expected {}",
+                i,
+                count,
+                expected
+            );
+        }
+    }
+}
@@ -0,0 +1,592 @@
+//! # Arena linked list
+//!
+//! The doubly-linked list again, with the same push, pop and cursor API as
+//! [`crate::linked_list`], but with every node in one `Vec` and links that are `u32`
+//! indices into it:
+//!
+//! ```text
+//!   head = 2, tail = 1, free = 3
+//!
+//!   nodes:    0              1              2              3
+//!          [ b  prev 2     [ c  prev 0     [ a  prev -     [ -  next -
+//!                next 1 ]       next - ]       next 0 ]  ]
+//!
+//!   reads as  a <-> b <-> c, with slot 3 waiting to be reused
+//! ```
+//!
+//! Against `Box` or `Rc` nodes, that means:
+//!
+//! - No allocation per element. The arena grows like a `Vec`, and removed nodes go on a
+//!   free list threaded through the `next` links, to be reused by the next push.
+//! - Nodes sit together in one buffer, and ones pushed one after another are neighbours
+//!   in it. A walk still pays a bounds check per hop, though, so it only wins once the
+//!   boxed nodes are scattered enough to miss the cache.
+//! - A link is 4 bytes rather than 8, and "no link" is the reserved index `u32::MAX`.
+//! - No `unsafe` at all. The borrow checker only ever sees one `Vec`.
+//!
+//! What it gives up is freedom to move nodes between lists. Splitting or splicing two
+//! arenas would mean copying nodes from one to the other, so the cursor here doesn't do
+//! that. `benches/arena_list.rs` times it against the pointer-based lists, [`crate::deque`]
+//! included.
+
+use std::fmt;
+use std::iter::FromIterator;
+
+/// The "no node" link.
+const NIL: u32 = u32::MAX;
+
+#[derive(Clone)]
+pub struct ArenaList<T> {
+    nodes: Vec<Node<T>>,
+    head: u32,
+    tail: u32,
+    // Head of the free list, linked through `next`
+    free: u32,
+    len: usize,
+}
+
+#[derive(Clone)]
+struct Node<T> {
+    // None while the node is on the free list
+    elem: Option<T>,
+    prev: u32,
+    next: u32,
+}
+
+impl<T> ArenaList<T> {
+    /// Creates an empty ArenaList.
+    pub fn new() -> Self {
+        ArenaList {
+            nodes: Vec::new(),
+            head: NIL,
+            tail: NIL,
+            free: NIL,
+            len: 0,
+        }
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        ArenaList {
+            nodes: Vec::with_capacity(capacity),
+            ..ArenaList::new()
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Removes every element, and frees the arena too.
+    pub fn clear(&mut self) {
+        *self = ArenaList::new();
+    }
+
+    fn node(&self, i: u32) -> &Node<T> {
+        &self.nodes[i as usize]
+    }
+
+    fn node_mut(&mut self, i: u32) -> &mut Node<T> {
+        &mut self.nodes[i as usize]
+    }
+
+    /// Stores `elem` in a free node, or a new one, linked between `prev` and `next`.
+    fn alloc(&mut self, elem: T, prev: u32, next: u32) -> u32 {
+        let node = Node {
+            elem: Some(elem),
+            prev,
+            next,
+        };
+        if self.free != NIL {
+            let i = self.free;
+            self.free = self.node(i).next;
+            *self.node_mut(i) = node;
+            i
+        } else {
+            assert!(self.nodes.len() < NIL as usize, "arena is full");
+            self.nodes.push(node);
+            (self.nodes.len() - 1) as u32
+        }
+    }
+
+    /// Links a new node between `prev` and `next`, which must be neighbours (or NIL at
+    /// the ends), and returns it.
+    fn link_between(&mut self, elem: T, prev: u32, next: u32) -> u32 {
+        let i = self.alloc(elem, prev, next);
+        match prev {
+            NIL => self.head = i,
+            prev => self.node_mut(prev).next = i,
+        }
+        match next {
+            NIL => self.tail = i,
+            next => self.node_mut(next).prev = i,
+        }
+        self.len += 1;
+        i
+    }
+
+    /// Unlinks node `i`, puts it on the free list and returns its element.
+    fn unlink(&mut self, i: u32) -> T {
+        let (prev, next) = (self.node(i).prev, self.node(i).next);
+        match prev {
+            NIL => self.head = next,
+            prev => self.node_mut(prev).next = next,
+        }
+        match next {
+            NIL => self.tail = prev,
+            next => self.node_mut(next).prev = prev,
+        }
+        let free = self.free;
+        let node = self.node_mut(i);
+        node.next = free;
+        let elem = node.elem.take().unwrap();
+        self.free = i;
+        self.len -= 1;
+        elem
+    }
+
+    pub fn push_front(&mut self, elem: T) {
+        self.link_between(elem, NIL, self.head);
+    }
+
+    pub fn push_back(&mut self, elem: T) {
+        self.link_between(elem, self.tail, NIL);
+    }
+
+    pub fn pop_front(&mut self) -> Option<T> {
+        (self.head != NIL).then(|| self.unlink(self.head))
+    }
+
+    pub fn pop_back(&mut self) -> Option<T> {
+        (self.tail != NIL).then(|| self.unlink(self.tail))
+    }
+
+    fn elem(&self, i: u32) -> Option<&T> {
+        (i != NIL).then(|| self.node(i).elem.as_ref().unwrap())
+    }
+
+    fn elem_mut(&mut self, i: u32) -> Option<&mut T> {
+        if i == NIL {
+            return None;
+        }
+        self.node_mut(i).elem.as_mut()
+    }
+
+    pub fn front(&self) -> Option<&T> {
+        self.elem(self.head)
+    }
+
+    pub fn front_mut(&mut self) -> Option<&mut T> {
+        self.elem_mut(self.head)
+    }
+
+    pub fn back(&self) -> Option<&T> {
+        self.elem(self.tail)
+    }
+
+    pub fn back_mut(&mut self) -> Option<&mut T> {
+        self.elem_mut(self.tail)
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            list: self,
+            front: self.head,
+            back: self.tail,
+            len: self.len,
+        }
+    }
+
+    /// Returns a cursor that starts at the ghost position, before the front element.
+    pub fn cursor_mut(&mut self) -> CursorMut<'_, T> {
+        CursorMut {
+            list: self,
+            cur: NIL,
+            index: None,
+        }
+    }
+}
+
+impl<T> Default for ArenaList<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Extend<T> for ArenaList<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for elem in iter {
+            self.push_back(elem);
+        }
+    }
+}
+
+impl<T> FromIterator<T> for ArenaList<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut list = ArenaList::new();
+        list.extend(iter);
+        list
+    }
+}
+
+impl<T: PartialEq> PartialEq for ArenaList<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.iter().eq(other)
+    }
+}
+
+impl<T: Eq> Eq for ArenaList<T> {}
+
+impl<T: fmt::Debug> fmt::Debug for ArenaList<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// Iterator over the elements of an [`ArenaList`], front to back.
+pub struct Iter<'a, T> {
+    list: &'a ArenaList<T>,
+    front: u32,
+    back: u32,
+    len: usize,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        let list = self.list;
+        let node = list.node(self.front);
+        self.front = node.next;
+        self.len -= 1;
+        node.elem.as_ref()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for Iter<'a, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        let list = self.list;
+        let node = list.node(self.back);
+        self.back = node.prev;
+        self.len -= 1;
+        node.elem.as_ref()
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Iter<'a, T> {}
+
+impl<'a, T> IntoIterator for &'a ArenaList<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Owning iterator, front to back.
+pub struct IntoIter<T>(ArenaList<T>);
+
+impl<T> IntoIterator for ArenaList<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter(self)
+    }
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.0.pop_front()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.0.len, Some(self.0.len))
+    }
+}
+
+impl<T> DoubleEndedIterator for IntoIter<T> {
+    fn next_back(&mut self) -> Option<T> {
+        self.0.pop_back()
+    }
+}
+
+impl<T> ExactSizeIterator for IntoIter<T> {}
+
+/// A cursor points at an element, or at the ghost position that sits between the back and
+/// the front, just like the one in [`crate::linked_list`].
+pub struct CursorMut<'a, T> {
+    list: &'a mut ArenaList<T>,
+    cur: u32,
+    index: Option<usize>,
+}
+
+impl<'a, T> CursorMut<'a, T> {
+    /// Returns the index of the current element, or `None` on the ghost.
+    pub fn index(&self) -> Option<usize> {
+        self.index
+    }
+
+    pub fn move_next(&mut self) {
+        if self.cur == NIL {
+            // From the ghost to the front, if there is one
+            self.cur = self.list.head;
+            self.index = (self.cur != NIL).then_some(0);
+        } else {
+            self.cur = self.list.node(self.cur).next;
+            self.index = if self.cur == NIL {
+                None
+            } else {
+                self.index.map(|i| i + 1)
+            };
+        }
+    }
+
+    pub fn move_prev(&mut self) {
+        if self.cur == NIL {
+            self.cur = self.list.tail;
+            self.index = (self.cur != NIL).then(|| self.list.len - 1);
+        } else {
+            self.cur = self.list.node(self.cur).prev;
+            self.index = if self.cur == NIL {
+                None
+            } else {
+                self.index.map(|i| i - 1)
+            };
+        }
+    }
+
+    pub fn current(&mut self) -> Option<&mut T> {
+        self.list.elem_mut(self.cur)
+    }
+
+    fn next_node(&self) -> u32 {
+        match self.cur {
+            NIL => self.list.head,
+            cur => self.list.node(cur).next,
+        }
+    }
+
+    fn prev_node(&self) -> u32 {
+        match self.cur {
+            NIL => self.list.tail,
+            cur => self.list.node(cur).prev,
+        }
+    }
+
+    pub fn peek_next(&mut self) -> Option<&mut T> {
+        let next = self.next_node();
+        self.list.elem_mut(next)
+    }
+
+    pub fn peek_prev(&mut self) -> Option<&mut T> {
+        let prev = self.prev_node();
+        self.list.elem_mut(prev)
+    }
+
+    /// Inserts an element just before the current one. On the ghost, that means at the back.
+    pub fn insert_before(&mut self, elem: T) {
+        let prev = self.prev_node();
+        self.list.link_between(elem, prev, self.cur);
+        if let Some(i) = self.index.as_mut() {
+            *i += 1;
+        }
+    }
+
+    /// Inserts an element just after the current one. On the ghost, that means at the front.
+    pub fn insert_after(&mut self, elem: T) {
+        let next = self.next_node();
+        self.list.link_between(elem, self.cur, next);
+    }
+
+    /// Unlinks and returns the current element. The cursor moves on to the next element, or
+    /// the ghost if there is none. Does nothing on the ghost.
+    pub fn remove_current(&mut self) -> Option<T> {
+        if self.cur == NIL {
+            return None;
+        }
+        let next = self.list.node(self.cur).next;
+        let elem = self.list.unlink(self.cur);
+        self.cur = next;
+        if next == NIL {
+            self.index = None;
+        }
+        Some(elem)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ArenaList, NIL};
+    use std::collections::VecDeque;
+
+    /// Checks the links agree in both directions and the free list holds the rest.
+    fn check<T>(list: &ArenaList<T>) {
+        let mut prev = NIL;
+        let mut i = list.head;
+        let mut len = 0;
+        while i != NIL {
+            let node = list.node(i);
+            assert_eq!(node.prev, prev);
+            assert!(node.elem.is_some());
+            prev = i;
+            i = node.next;
+            len += 1;
+        }
+        assert_eq!(list.tail, prev);
+        assert_eq!(len, list.len);
+        let mut free = 0;
+        let mut i = list.free;
+        while i != NIL {
+            assert!(list.node(i).elem.is_none());
+            i = list.node(i).next;
+            free += 1;
+        }
+        assert_eq!(len + free, list.nodes.len());
+    }
+
+    #[test]
+    fn basics() {
+        let mut list = ArenaList::new();
+
+        // Check empty list behaves right
+        assert_eq!(list.pop_front(), None);
+        assert_eq!(list.pop_back(), None);
+        assert_eq!(list.front(), None);
+
+        // Populate list
+        list.push_back(2);
+        list.push_back(3);
+        list.push_front(1);
+        assert_eq!(format!("{:?}", list), "[1, 2, 3]");
+        check(&list);
+
+        // Check normal removal
+        assert_eq!(list.pop_front(), Some(1));
+        assert_eq!(list.pop_back(), Some(3));
+        check(&list);
+
+        // Push some more just to make sure nothing's corrupted, and that freed nodes are
+        // reused
+        list.push_front(0);
+        list.push_back(4);
+        assert_eq!(list.nodes.len(), 3);
+        *list.back_mut().unwrap() += 10;
+        assert!(list.iter().eq(&[0, 2, 14]));
+        assert!(list.iter().rev().eq(&[14, 2, 0]));
+        check(&list);
+
+        // Check exhaustion
+        assert_eq!(list.pop_back(), Some(14));
+        assert_eq!(list.pop_front(), Some(0));
+        assert_eq!(list.pop_front(), Some(2));
+        assert_eq!(list.pop_front(), None);
+        assert!(list.is_empty());
+        check(&list);
+    }
+
+    #[test]
+    fn cursor() {
+        let mut list: ArenaList<u32> = (1..=3).collect();
+        let mut cursor = list.cursor_mut();
+        assert_eq!(cursor.index(), None);
+        assert_eq!(cursor.peek_next(), Some(&mut 1));
+        assert_eq!(cursor.peek_prev(), Some(&mut 3));
+
+        cursor.move_next();
+        cursor.move_next();
+        assert_eq!(cursor.current(), Some(&mut 2));
+        assert_eq!(cursor.index(), Some(1));
+        cursor.insert_before(10);
+        cursor.insert_after(20);
+        assert_eq!(cursor.index(), Some(2));
+        assert_eq!(cursor.current(), Some(&mut 2));
+
+        // Removing moves on to the next element
+        assert_eq!(cursor.remove_current(), Some(2));
+        assert_eq!(cursor.current(), Some(&mut 20));
+        cursor.move_prev();
+        assert_eq!(cursor.current(), Some(&mut 10));
+
+        // Off either end is the ghost, and moving past it wraps around
+        cursor.move_prev();
+        cursor.move_prev();
+        assert_eq!(cursor.index(), None);
+        cursor.move_prev();
+        assert_eq!(cursor.current(), Some(&mut 3));
+        assert_eq!(cursor.index(), Some(3));
+        assert_eq!(cursor.remove_current(), Some(3));
+        assert_eq!(cursor.index(), None);
+
+        // On the ghost, inserting before and after means the back and the front
+        cursor.insert_before(99);
+        cursor.insert_after(0);
+        assert_eq!(cursor.remove_current(), None);
+        check(&list);
+        assert!(list.iter().eq(&[0, 1, 10, 20, 99]));
+    }
+
+    #[test]
+    fn against_vec_deque() {
+        // The same random operations on both, including cursor edits in the middle
+        let mut list = ArenaList::new();
+        let mut model = VecDeque::new();
+        let mut x: u32 = 1;
+        for i in 0..5_000 {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            match x >> 29 {
+                0 => assert_eq!(list.pop_front(), model.pop_front()),
+                1 => assert_eq!(list.pop_back(), model.pop_back()),
+                2 | 3 => {
+                    list.push_front(x);
+                    model.push_front(x);
+                }
+                4 if !model.is_empty() => {
+                    let at = (x >> 8) as usize % model.len();
+                    let mut cursor = list.cursor_mut();
+                    for _ in 0..=at {
+                        cursor.move_next();
+                    }
+                    assert_eq!(cursor.index(), Some(at));
+                    assert_eq!(cursor.remove_current(), model.remove(at));
+                }
+                5 => {
+                    let at = (x >> 8) as usize % (model.len() + 1);
+                    let mut cursor = list.cursor_mut();
+                    for _ in 0..at {
+                        cursor.move_next();
+                    }
+                    cursor.insert_after(x);
+                    model.insert(at, x);
+                }
+                _ => {
+                    list.push_back(x);
+                    model.push_back(x);
+                }
+            }
+            assert_eq!(list.len(), model.len());
+            if i % 100 == 0 {
+                check(&list);
+                assert!(list.iter().eq(model.iter()));
+            }
+        }
+        let drained: Vec<_> = list.into_iter().rev().collect();
+        assert!(drained.iter().eq(model.iter().rev()));
+    }
+}
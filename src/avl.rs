@@ -0,0 +1,554 @@
+//! # AVL tree map
+//!
+//! The first self-balancing binary search tree (Adelson-Velsky and Landis, 1962). It is the
+//! [`crate::bst`] map plus a height stored in every node, and one rule: the heights of a
+//! node's two subtrees may differ by at most one. The difference is the node's *balance
+//! factor*. That rule alone keeps the height under about 1.44 log2(n), so every operation is
+//! O(log n) even on the sorted input that turns a plain BST into a list.
+//!
+//! After an insert or a remove, the nodes on the way back up to the root recompute their
+//! heights. Any node whose balance factor has reached +2 or -2 is fixed with a rotation.
+//! Which rotation depends on which grandchild grew too tall:
+//!
+//! ```text
+//! left-left: rotate right          left-right: rotate the child left, then this node right
+//!
+//!         c                b               c              c              b
+//!        /                / \             /              /              / \
+//!       b       -->      a   c           a      -->     b      -->     a   c
+//!      /                                  \            /
+//!     a                                    b          a
+//! ```
+//!
+//! The right-right and right-left cases are mirror images. A rotation keeps the keys in
+//! order and moves only a few pointers, so it costs O(1).
+//!
+//! The tree is never deeper than O(log n), so unlike the plain BST these operations can
+//! recurse.
+
+use std::borrow::Borrow;
+use std::cmp::{self, Ordering};
+use std::fmt;
+use std::iter::FromIterator;
+use std::mem;
+
+pub struct Map<K, V> {
+    root: Link<K, V>,
+    len: usize,
+}
+
+type Link<K, V> = Option<Box<Node<K, V>>>;
+
+struct Node<K, V> {
+    key: K,
+    value: V,
+    // Height of the subtree rooted here; a leaf has height 1
+    height: usize,
+    left: Link<K, V>,
+    right: Link<K, V>,
+}
+
+fn height<K, V>(link: &Link<K, V>) -> usize {
+    link.as_ref().map_or(0, |node| node.height)
+}
+
+impl<K, V> Node<K, V> {
+    fn update_height(&mut self) {
+        self.height = 1 + cmp::max(height(&self.left), height(&self.right));
+    }
+
+    /// Left height minus right height.
+    fn balance_factor(&self) -> isize {
+        height(&self.left) as isize - height(&self.right) as isize
+    }
+}
+
+fn rotate_right<K, V>(mut node: Box<Node<K, V>>) -> Box<Node<K, V>> {
+    let mut left = node.left.take().expect("rotate_right needs a left child");
+    node.left = left.right.take();
+    node.update_height();
+    left.right = Some(node);
+    left.update_height();
+    left
+}
+
+fn rotate_left<K, V>(mut node: Box<Node<K, V>>) -> Box<Node<K, V>> {
+    let mut right = node.right.take().expect("rotate_left needs a right child");
+    node.right = right.left.take();
+    node.update_height();
+    right.left = Some(node);
+    right.update_height();
+    right
+}
+
+/// Recomputes the height of `node`, whose children are balanced, and rotates it back into
+/// balance if needed. Returns the new root of the subtree.
+fn rebalance<K, V>(mut node: Box<Node<K, V>>) -> Box<Node<K, V>> {
+    node.update_height();
+    let balance = node.balance_factor();
+    if balance > 1 {
+        if node.left.as_ref().unwrap().balance_factor() < 0 {
+            // Left-right: turn it into left-left first
+            node.left = node.left.take().map(rotate_left);
+        }
+        rotate_right(node)
+    } else if balance < -1 {
+        if node.right.as_ref().unwrap().balance_factor() > 0 {
+            // Right-left: turn it into right-right first
+            node.right = node.right.take().map(rotate_right);
+        }
+        rotate_left(node)
+    } else {
+        node
+    }
+}
+
+fn insert<K: Ord, V>(link: &mut Link<K, V>, key: K, value: V) -> Option<V> {
+    let node = match link {
+        Some(node) => node,
+        None => {
+            *link = Some(Box::new(Node {
+                key,
+                value,
+                height: 1,
+                left: None,
+                right: None,
+            }));
+            return None;
+        }
+    };
+    let old = match key.cmp(&node.key) {
+        Ordering::Less => insert(&mut node.left, key, value),
+        Ordering::Greater => insert(&mut node.right, key, value),
+        // Replacing a value doesn't change the shape
+        Ordering::Equal => return Some(mem::replace(&mut node.value, value)),
+    };
+    *link = link.take().map(rebalance);
+    old
+}
+
+fn remove<K, V, Q>(link: &mut Link<K, V>, key: &Q) -> Option<(K, V)>
+where
+    K: Borrow<Q>,
+    Q: Ord + ?Sized,
+{
+    let node = link.as_mut()?;
+    let removed = match key.cmp(node.key.borrow()) {
+        Ordering::Less => remove(&mut node.left, key),
+        Ordering::Greater => remove(&mut node.right, key),
+        Ordering::Equal => {
+            let mut node = link.take().unwrap();
+            *link = match (node.left.take(), node.right.take()) {
+                (None, right) => right,
+                (left, None) => left,
+                (Some(left), Some(right)) => {
+                    // Two children: the successor takes this node's place
+                    let (mut successor, rest) = take_min(right);
+                    successor.left = Some(left);
+                    successor.right = rest;
+                    Some(rebalance(successor))
+                }
+            };
+            return Some((node.key, node.value));
+        }
+    };
+    if removed.is_some() {
+        *link = link.take().map(rebalance);
+    }
+    removed
+}
+
+/// Splits the leftmost node off the subtree, returning it and the rebalanced remainder.
+fn take_min<K, V>(mut node: Box<Node<K, V>>) -> (Box<Node<K, V>>, Link<K, V>) {
+    match node.left.take() {
+        None => {
+            let rest = node.right.take();
+            (node, rest)
+        }
+        Some(left) => {
+            let (min, rest) = take_min(left);
+            node.left = rest;
+            (min, Some(rebalance(node)))
+        }
+    }
+}
+
+impl<K, V> Map<K, V> {
+    /// Creates an empty Map.
+    pub fn new() -> Self {
+        Map { root: None, len: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    /// Returns the number of nodes on the longest path from the root, so zero when empty.
+    pub fn height(&self) -> usize {
+        height(&self.root)
+    }
+
+    /// Returns the entry with the smallest key.
+    pub fn min(&self) -> Option<(&K, &V)> {
+        let mut node = self.root.as_deref()?;
+        while let Some(left) = node.left.as_deref() {
+            node = left;
+        }
+        Some((&node.key, &node.value))
+    }
+
+    /// Returns the entry with the largest key.
+    pub fn max(&self) -> Option<(&K, &V)> {
+        let mut node = self.root.as_deref()?;
+        while let Some(right) = node.right.as_deref() {
+            node = right;
+        }
+        Some((&node.key, &node.value))
+    }
+
+    pub fn clear(&mut self) {
+        self.root = None;
+        self.len = 0;
+    }
+
+    /// In-order iteration, so keys come out sorted.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        let mut iter = Iter {
+            stack: Vec::new(),
+            remaining: self.len,
+        };
+        iter.push_left_spine(self.root.as_deref());
+        iter
+    }
+}
+
+impl<K: Ord, V> Map<K, V> {
+    fn find<Q>(&self, key: &Q) -> Option<&Node<K, V>>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut link = self.root.as_deref();
+        while let Some(node) = link {
+            link = match key.cmp(node.key.borrow()) {
+                Ordering::Less => node.left.as_deref(),
+                Ordering::Greater => node.right.as_deref(),
+                Ordering::Equal => return Some(node),
+            };
+        }
+        None
+    }
+
+    /// Inserts a key-value pair, returning the old value if the key was already there.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let old = insert(&mut self.root, key, value);
+        if old.is_none() {
+            self.len += 1;
+        }
+        old
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.find(key).map(|node| &node.value)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut link = self.root.as_deref_mut();
+        while let Some(node) = link {
+            link = match key.cmp(node.key.borrow()) {
+                Ordering::Less => node.left.as_deref_mut(),
+                Ordering::Greater => node.right.as_deref_mut(),
+                Ordering::Equal => return Some(&mut node.value),
+            };
+        }
+        None
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.find(key).is_some()
+    }
+
+    /// Removes a key, returning its value if it was there.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.remove_entry(key).map(|(_, v)| v)
+    }
+
+    /// Removes a key, returning the stored key and value if it was there.
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let removed = remove(&mut self.root, key);
+        if removed.is_some() {
+            self.len -= 1;
+        }
+        removed
+    }
+
+    /// Walks the whole tree and panics if any AVL invariant is broken: keys out of order,
+    /// a stale stored height, a balance factor outside -1..=1, or a wrong `len`. It's a
+    /// hook for tests, and costs O(n).
+    pub fn check_invariants(&self) {
+        fn check<K: Ord, V>(link: &Link<K, V>, lower: Option<&K>, upper: Option<&K>) -> usize {
+            let node = match link {
+                Some(node) => node,
+                None => return 0,
+            };
+            assert!(
+                lower.is_none_or(|lower| *lower < node.key),
+                "keys out of order"
+            );
+            assert!(
+                upper.is_none_or(|upper| node.key < *upper),
+                "keys out of order"
+            );
+            assert_eq!(
+                node.height,
+                1 + cmp::max(height(&node.left), height(&node.right)),
+                "stale height"
+            );
+            assert!(
+                node.balance_factor().abs() <= 1,
+                "balance factor {} out of range",
+                node.balance_factor()
+            );
+            1 + check(&node.left, lower, Some(&node.key))
+                + check(&node.right, Some(&node.key), upper)
+        }
+        assert_eq!(check(&self.root, None, None), self.len, "wrong len");
+    }
+}
+
+impl<K, V> Default for Map<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord, V> FromIterator<(K, V)> for Map<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = Map::new();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K: Ord, V> Extend<(K, V)> for Map<K, V> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug> fmt::Debug for Map<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// In-order iterator. The stack holds the nodes whose left subtree we've started but who
+/// haven't been yielded yet.
+pub struct Iter<'a, K, V> {
+    stack: Vec<&'a Node<K, V>>,
+    remaining: usize,
+}
+
+impl<'a, K, V> Iter<'a, K, V> {
+    fn push_left_spine(&mut self, mut link: Option<&'a Node<K, V>>) {
+        while let Some(node) = link {
+            self.stack.push(node);
+            link = node.left.as_deref();
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        self.push_left_spine(node.right.as_deref());
+        self.remaining -= 1;
+        Some((&node.key, &node.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+
+impl<'a, K, V> IntoIterator for &'a Map<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Map;
+
+    fn root(map: &Map<i32, ()>) -> i32 {
+        map.root.as_ref().unwrap().key
+    }
+
+    fn keys(map: &Map<i32, ()>) -> Vec<i32> {
+        map.iter().map(|(k, _)| *k).collect()
+    }
+
+    fn build(keys: &[i32]) -> Map<i32, ()> {
+        let map: Map<_, _> = keys.iter().map(|&k| (k, ())).collect();
+        map.check_invariants();
+        map
+    }
+
+    #[test]
+    fn basics() {
+        let mut map = Map::new();
+
+        // Check empty map behaves right
+        assert!(map.is_empty());
+        assert_eq!(map.get(&1), None);
+        assert_eq!(map.remove(&1), None);
+        assert_eq!(map.height(), 0);
+        map.check_invariants();
+
+        // Populate map
+        for k in 0..10 {
+            assert_eq!(map.insert(k, k * 10), None);
+        }
+        assert_eq!(map.len(), 10);
+        assert_eq!(map.get(&6), Some(&60));
+        assert!(map.contains_key(&9));
+        assert!(!map.contains_key(&10));
+        assert_eq!(map.min(), Some((&0, &0)));
+        assert_eq!(map.max(), Some((&9, &90)));
+
+        // Replacing hands back the old value
+        assert_eq!(map.insert(6, 0), Some(60));
+        if let Some(value) = map.get_mut(&6) {
+            *value = 66;
+        }
+        assert_eq!(map.get(&6), Some(&66));
+        assert_eq!(map.len(), 10);
+
+        assert_eq!(map.remove(&3), Some(30));
+        assert_eq!(map.remove_entry(&4), Some((4, 40)));
+        assert_eq!(map.len(), 8);
+        map.check_invariants();
+        assert_eq!(format!("{:?}", map.iter().next()), "Some((0, 0))");
+    }
+
+    #[test]
+    fn four_rotations() {
+        let left_left = build(&[3, 2, 1]);
+        let right_right = build(&[1, 2, 3]);
+        let left_right = build(&[3, 1, 2]);
+        let right_left = build(&[1, 3, 2]);
+        for map in &[left_left, right_right, left_right, right_left] {
+            assert_eq!(root(map), 2);
+            assert_eq!(map.height(), 2);
+            assert_eq!(keys(map), vec![1, 2, 3]);
+        }
+    }
+
+    #[test]
+    fn removal_rebalances() {
+        //       4
+        //     /   \
+        //    2     6
+        //   / \   / \
+        //  1   3 5   7
+        //             \
+        //              8
+        let mut map = build(&[4, 2, 6, 1, 3, 5, 7, 8]);
+        assert_eq!(map.height(), 4);
+
+        // Emptying the left side leaves the root right-heavy
+        for k in [1, 3, 2] {
+            map.remove(&k);
+            map.check_invariants();
+        }
+        assert_eq!(root(&map), 6);
+        assert_eq!(keys(&map), vec![4, 5, 6, 7, 8]);
+
+        // Removing a node with two children
+        map.remove(&6);
+        map.check_invariants();
+        assert_eq!(keys(&map), vec![4, 5, 7, 8]);
+    }
+
+    #[test]
+    fn sorted_input_stays_shallow() {
+        let n = if cfg!(miri) { 255 } else { (1 << 16) - 1 };
+        let mut map: Map<_, _> = (0..n).map(|i| (i, ())).collect();
+        map.check_invariants();
+        // Inserting in order builds a perfect tree
+        assert_eq!(1 << map.height(), n + 1);
+
+        for i in (0..n).step_by(2) {
+            map.remove(&i);
+        }
+        map.check_invariants();
+        assert_eq!(map.len(), n as usize / 2);
+        assert!(map.height() <= 16);
+    }
+
+    #[test]
+    fn borrowed_keys() {
+        let mut map = Map::new();
+        map.insert("b".to_string(), 2);
+        map.insert("a".to_string(), 1);
+        assert_eq!(map.get("a"), Some(&1));
+        assert_eq!(map.remove("b"), Some(2));
+        assert_eq!(format!("{:?}", map), r#"{"a": 1}"#);
+    }
+
+    #[test]
+    fn against_btreemap() {
+        use std::collections::BTreeMap;
+        let mut map = Map::new();
+        let mut model = BTreeMap::new();
+        let mut x: u32 = 2024;
+        for i in 0..5_000 {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let key = (x >> 16) % 500;
+            if x.is_multiple_of(3) {
+                assert_eq!(map.remove(&key), model.remove(&key));
+            } else {
+                assert_eq!(map.insert(key, x), model.insert(key, x));
+            }
+            if i % 100 == 0 {
+                map.check_invariants();
+            }
+        }
+        map.check_invariants();
+        assert!(map.iter().eq(model.iter()));
+    }
+}
@@ -0,0 +1,347 @@
+//! # Bidirectional map
+//!
+//! A one-to-one map between left values and right values, which looks up just as well
+//! either way round: the right value for a left one, or the left value for a right one.
+//! It's two [`RobinHoodMap`]s kept in step, left to right and right to left, and both sides
+//! must be `Clone` since each value is stored once in each.
+//!
+//! The interesting part is keeping it one-to-one. Inserting a pair whose left value or
+//! right value is already paired with something else has to break those pairs up, or one
+//! index would disagree with the other:
+//!
+//! ```text
+//!   before:   1 <-> a     2 <-> b
+//!
+//!   insert(1, b)   evicts both 1 <-> a and 2 <-> b
+//!
+//!   after:    1 <-> b
+//! ```
+//!
+//! `insert` reports what it evicted as an [`Overwritten`], so nothing goes missing without
+//! the caller knowing. `insert_no_overwrite` refuses instead.
+
+use crate::robin_hood::RobinHoodMap;
+use std::borrow::Borrow;
+use std::fmt;
+use std::hash::Hash;
+use std::iter::FromIterator;
+
+/// The pairs an insert into a [`BiMap`] pushed out to keep it one-to-one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Overwritten<L, R> {
+    /// Neither value was paired before.
+    Neither,
+    /// The very same pair was already there.
+    Pair(L, R),
+    /// The left value's old pair.
+    Left(L, R),
+    /// The right value's old pair.
+    Right(L, R),
+    /// Both values were paired with others: the left value's old pair, then the right's.
+    Both((L, R), (L, R)),
+}
+
+impl<L, R> Overwritten<L, R> {
+    /// Returns whether the insert evicted anything, other than the same pair.
+    pub fn did_overwrite(&self) -> bool {
+        !matches!(self, Overwritten::Neither | Overwritten::Pair(..))
+    }
+}
+
+#[derive(Clone)]
+pub struct BiMap<L, R> {
+    left: RobinHoodMap<L, R>,
+    right: RobinHoodMap<R, L>,
+}
+
+impl<L, R> BiMap<L, R>
+where
+    L: Eq + Hash + Clone,
+    R: Eq + Hash + Clone,
+{
+    /// Creates an empty BiMap.
+    pub fn new() -> Self {
+        BiMap {
+            left: RobinHoodMap::new(),
+            right: RobinHoodMap::new(),
+        }
+    }
+
+    /// Returns the number of pairs.
+    pub fn len(&self) -> usize {
+        self.left.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.left.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.left.clear();
+        self.right.clear();
+    }
+
+    /// Pairs `left` with `right`, first taking out whatever either was paired with, and
+    /// returns the pairs taken out.
+    pub fn insert(&mut self, left: L, right: R) -> Overwritten<L, R> {
+        let by_left = self.remove_by_left(&left);
+        let by_right = self.remove_by_right(&right);
+        // Taking out the left value's pair took the right value's too if they're the same
+        let same = by_left.as_ref().is_some_and(|(_, r)| *r == right);
+        self.left.insert(left.clone(), right.clone());
+        self.right.insert(right, left);
+        match (by_left, by_right) {
+            (None, None) => Overwritten::Neither,
+            (Some((l, r)), None) if same => Overwritten::Pair(l, r),
+            (Some((l, r)), None) => Overwritten::Left(l, r),
+            (None, Some((l, r))) => Overwritten::Right(l, r),
+            (Some(left), Some(right)) => Overwritten::Both(left, right),
+        }
+    }
+
+    /// Pairs `left` with `right` only if neither is paired already, else hands them back.
+    pub fn insert_no_overwrite(&mut self, left: L, right: R) -> Result<(), (L, R)> {
+        if self.left.contains_key(&left) || self.right.contains_key(&right) {
+            return Err((left, right));
+        }
+        self.left.insert(left.clone(), right.clone());
+        self.right.insert(right, left);
+        Ok(())
+    }
+
+    /// Returns the right value paired with `left`.
+    pub fn get_by_left<Q>(&self, left: &Q) -> Option<&R>
+    where
+        L: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.left.get(left)
+    }
+
+    /// Returns the left value paired with `right`.
+    pub fn get_by_right<Q>(&self, right: &Q) -> Option<&L>
+    where
+        R: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.right.get(right)
+    }
+
+    pub fn contains_left<Q>(&self, left: &Q) -> bool
+    where
+        L: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.left.contains_key(left)
+    }
+
+    pub fn contains_right<Q>(&self, right: &Q) -> bool
+    where
+        R: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.right.contains_key(right)
+    }
+
+    /// Removes the pair with this left value, and returns it.
+    pub fn remove_by_left<Q>(&mut self, left: &Q) -> Option<(L, R)>
+    where
+        L: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let (left, right) = self.left.remove_entry(left)?;
+        self.right.remove(&right);
+        Some((left, right))
+    }
+
+    /// Removes the pair with this right value, and returns it.
+    pub fn remove_by_right<Q>(&mut self, right: &Q) -> Option<(L, R)>
+    where
+        R: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let (right, left) = self.right.remove_entry(right)?;
+        self.left.remove(&left);
+        Some((left, right))
+    }
+
+    /// Iterates over the pairs, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (&L, &R)> {
+        self.left.iter()
+    }
+
+    pub fn left_values(&self) -> impl Iterator<Item = &L> {
+        self.left.keys()
+    }
+
+    pub fn right_values(&self) -> impl Iterator<Item = &R> {
+        self.right.keys()
+    }
+}
+
+impl<L, R> Default for BiMap<L, R>
+where
+    L: Eq + Hash + Clone,
+    R: Eq + Hash + Clone,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<L, R> PartialEq for BiMap<L, R>
+where
+    L: Eq + Hash + Clone,
+    R: Eq + Hash + Clone,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.left == other.left
+    }
+}
+
+impl<L, R> Eq for BiMap<L, R>
+where
+    L: Eq + Hash + Clone,
+    R: Eq + Hash + Clone,
+{
+}
+
+impl<L: fmt::Debug, R: fmt::Debug> fmt::Debug for BiMap<L, R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.left.iter()).finish()
+    }
+}
+
+impl<L, R> Extend<(L, R)> for BiMap<L, R>
+where
+    L: Eq + Hash + Clone,
+    R: Eq + Hash + Clone,
+{
+    fn extend<I: IntoIterator<Item = (L, R)>>(&mut self, iter: I) {
+        for (left, right) in iter {
+            self.insert(left, right);
+        }
+    }
+}
+
+impl<L, R> FromIterator<(L, R)> for BiMap<L, R>
+where
+    L: Eq + Hash + Clone,
+    R: Eq + Hash + Clone,
+{
+    fn from_iter<I: IntoIterator<Item = (L, R)>>(iter: I) -> Self {
+        let mut map = Self::new();
+        map.extend(iter);
+        map
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{BiMap, Overwritten};
+
+    /// Checks the two indexes agree.
+    fn check(map: &BiMap<u32, u32>) {
+        assert_eq!(map.left.len(), map.right.len());
+        for (l, r) in map.iter() {
+            assert_eq!(map.get_by_right(r), Some(l));
+        }
+    }
+
+    #[test]
+    fn basics() {
+        let mut map = BiMap::new();
+
+        // Check empty bimap behaves right
+        assert_eq!(map.get_by_left(&1), None);
+        assert_eq!(map.remove_by_right("a"), None);
+
+        // Populate bimap
+        assert_eq!(map.insert(1, "a".to_string()), Overwritten::Neither);
+        assert_eq!(map.insert(2, "b".to_string()), Overwritten::Neither);
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get_by_left(&1).map(String::as_str), Some("a"));
+        assert_eq!(map.get_by_right("b"), Some(&2));
+        assert!(map.contains_left(&2) && map.contains_right("a"));
+
+        // Check normal removal
+        assert_eq!(map.remove_by_right("a"), Some((1, "a".to_string())));
+        assert!(!map.contains_left(&1));
+        assert_eq!(map.remove_by_left(&2), Some((2, "b".to_string())));
+        assert!(map.is_empty());
+
+        // Push some more just to make sure nothing's corrupted
+        map.extend(vec![(3, "c".to_string())]);
+        assert_eq!(format!("{:?}", map), r#"{3: "c"}"#);
+        map.insert(4, "d".to_string());
+        assert_eq!(map, map.clone());
+
+        // Check exhaustion
+        map.clear();
+        assert!(map.is_empty());
+        assert_eq!(map.get_by_right("c"), None);
+    }
+
+    #[test]
+    fn overwriting() {
+        let mut map: BiMap<u32, char> = vec![(1, 'a'), (2, 'b'), (3, 'c')].into_iter().collect();
+        assert_eq!(map.insert(1, 'a'), Overwritten::Pair(1, 'a'));
+        assert_eq!(map.insert(1, 'z'), Overwritten::Left(1, 'a'));
+        assert_eq!(map.insert(9, 'z'), Overwritten::Right(1, 'z'));
+        assert_eq!(map.insert(2, 'c'), Overwritten::Both((2, 'b'), (3, 'c')));
+        assert!(!Overwritten::<u32, char>::Neither.did_overwrite());
+        assert!(Overwritten::Left(1, 'a').did_overwrite());
+
+        let mut pairs: Vec<_> = map.iter().map(|(&l, &r)| (l, r)).collect();
+        pairs.sort_unstable();
+        assert_eq!(pairs, [(2, 'c'), (9, 'z')]);
+
+        assert_eq!(map.insert_no_overwrite(2, 'q'), Err((2, 'q')));
+        assert_eq!(map.insert_no_overwrite(7, 'c'), Err((7, 'c')));
+        assert_eq!(map.insert_no_overwrite(7, 'q'), Ok(()));
+        assert_eq!(map.len(), 3);
+    }
+
+    #[test]
+    fn against_pairs() {
+        // A list of pairs, searched both ways, is a slow but obvious model
+        let mut map = BiMap::new();
+        let mut model: Vec<(u32, u32)> = Vec::new();
+        let n = if cfg!(miri) { 1_000 } else { 20_000 };
+        let mut x: u32 = 1;
+        for _ in 0..n {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let l = (x >> 8) % 40;
+            let r = (x >> 16) % 40;
+            match x >> 30 {
+                0 => {
+                    let at = model.iter().position(|p| p.0 == l);
+                    assert_eq!(map.remove_by_left(&l), at.map(|at| model.remove(at)));
+                }
+                1 => {
+                    let at = model.iter().position(|p| p.1 == r);
+                    assert_eq!(map.remove_by_right(&r), at.map(|at| model.remove(at)));
+                }
+                _ => {
+                    let by_left = model.iter().position(|p| p.0 == l).map(|at| model[at]);
+                    let by_right = model.iter().position(|p| p.1 == r).map(|at| model[at]);
+                    model.retain(|p| p.0 != l && p.1 != r);
+                    model.push((l, r));
+                    let expected = match (by_left, by_right) {
+                        (None, None) => Overwritten::Neither,
+                        (Some(p), Some(q)) if p == q => Overwritten::Pair(p.0, p.1),
+                        (Some(p), None) => Overwritten::Left(p.0, p.1),
+                        (None, Some(q)) => Overwritten::Right(q.0, q.1),
+                        (Some(p), Some(q)) => Overwritten::Both(p, q),
+                    };
+                    assert_eq!(map.insert(l, r), expected);
+                }
+            }
+            assert_eq!(map.len(), model.len());
+        }
+        check(&map);
+        for &(l, r) in &model {
+            assert_eq!(map.get_by_left(&l), Some(&r));
+        }
+    }
+}
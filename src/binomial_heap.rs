@@ -0,0 +1,324 @@
+//! # Binomial heap
+//!
+//! A heap kept as a forest of *binomial trees* (Vuillemin, 1978). A tree of order `k` is
+//! two trees of order `k - 1`, one hung under the other's root, so it holds exactly `2^k`
+//! elements and its root has `k` children, of orders `k - 1` down to 0:
+//!
+//! ```text
+//!   order 0    order 1    order 2          order 3
+//!
+//!     o          o          o                 o
+//!                |         / \             /  |  \
+//!                o        o   o           o   o   o
+//!                         |              / \  |
+//!                         o             o   o o
+//!                                       |
+//!                                       o
+//! ```
+//!
+//! The forest has at most one tree of each order, so which orders are present is just the
+//! binary representation of the length: 13 elements are trees of order 3, 2 and 0. Merging
+//! two heaps is binary addition. Going up the orders, two trees of the same order *link*,
+//! the larger root becoming the first child of the smaller. The result carries into the
+//! next order. That's O(log n), against O(n) for array-backed heaps.
+//!
+//! Push merges in a one-element heap, which is amortized O(1) just as incrementing a
+//! binary counter is. Pop finds the smallest root among the O(log n) trees and removes
+//! it. Its children are a binomial forest of their own, which then merges back in.
+//!
+//! Each node links to its first child and its next sibling, so children cost no `Vec` of
+//! their own. Like the other mergeable heaps, this is a min-heap.
+
+use std::fmt;
+use std::iter::FromIterator;
+
+#[derive(Clone)]
+pub struct BinomialHeap<T> {
+    // trees[k] is the tree of order k, if there is one
+    trees: Vec<Link<T>>,
+    len: usize,
+}
+
+type Link<T> = Option<Box<Node<T>>>;
+
+#[derive(Clone)]
+struct Node<T> {
+    elem: T,
+    // The child of highest order; the other children follow it, each one order lower
+    child: Link<T>,
+    sibling: Link<T>,
+}
+
+/// Makes two trees of the same order into one of the next order.
+fn link<T: Ord>(mut a: Box<Node<T>>, mut b: Box<Node<T>>) -> Box<Node<T>> {
+    if b.elem < a.elem {
+        std::mem::swap(&mut a, &mut b);
+    }
+    b.sibling = a.child.take();
+    a.child = Some(b);
+    a
+}
+
+impl<T: Ord> BinomialHeap<T> {
+    /// Creates an empty BinomialHeap.
+    pub fn new() -> Self {
+        BinomialHeap {
+            trees: Vec::new(),
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        *self = BinomialHeap::new();
+    }
+
+    pub fn push(&mut self, elem: T) {
+        let node = Box::new(Node {
+            elem,
+            child: None,
+            sibling: None,
+        });
+        self.merge_trees(vec![Some(node)]);
+        self.len += 1;
+    }
+
+    /// Index of the tree with the smallest root.
+    fn min_tree(&self) -> Option<usize> {
+        let roots = self.trees.iter().enumerate();
+        let roots = roots.filter_map(|(k, tree)| tree.as_ref().map(|node| (k, node)));
+        roots.min_by(|a, b| a.1.elem.cmp(&b.1.elem)).map(|(k, _)| k)
+    }
+
+    /// Returns the smallest element, in O(log n).
+    pub fn peek(&self) -> Option<&T> {
+        let k = self.min_tree()?;
+        self.trees[k].as_ref().map(|node| &node.elem)
+    }
+
+    /// Removes and returns the smallest element.
+    pub fn pop(&mut self) -> Option<T> {
+        let k = self.min_tree()?;
+        let mut root = self.trees[k].take().unwrap();
+
+        // The children, highest order first, are a forest of orders k - 1 down to 0
+        let mut children: Vec<Link<T>> = Vec::with_capacity(k);
+        let mut next = root.child.take();
+        while let Some(mut child) = next {
+            next = child.sibling.take();
+            children.push(Some(child));
+        }
+        children.reverse();
+
+        self.merge_trees(children);
+        self.len -= 1;
+        Some(root.elem)
+    }
+
+    /// Moves every element of `other` into this heap, in O(log n).
+    pub fn merge(&mut self, mut other: BinomialHeap<T>) {
+        self.len += other.len;
+        self.merge_trees(std::mem::take(&mut other.trees));
+    }
+
+    /// Adds two forests like binary numbers, one order at a time.
+    fn merge_trees(&mut self, mut other: Vec<Link<T>>) {
+        let orders = self.trees.len().max(other.len());
+        self.trees.resize_with(orders, || None);
+        let mut carry = None;
+        for k in 0..orders {
+            let theirs = other.get_mut(k).and_then(Option::take);
+            let mut present =
+                IntoIterator::into_iter([self.trees[k].take(), theirs, carry.take()]).flatten();
+            let (x, y, z) = (present.next(), present.next(), present.next());
+            let (slot, next_carry) = match (x, y, z) {
+                // Two or three trees of this order: two of them link and carry
+                (Some(x), Some(y), z) => (z, Some(link(x, y))),
+                (x, _, _) => (x, None),
+            };
+            self.trees[k] = slot;
+            carry = next_carry;
+        }
+        if carry.is_some() {
+            self.trees.push(carry);
+        }
+        while let Some(None) = self.trees.last() {
+            self.trees.pop();
+        }
+    }
+
+    /// Iterates over the elements in no particular order.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            stack: self.trees.iter().flatten().map(|node| &**node).collect(),
+        }
+    }
+}
+
+impl<T: Ord> Default for BinomialHeap<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Ord> FromIterator<T> for BinomialHeap<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut heap = BinomialHeap::new();
+        heap.extend(iter);
+        heap
+    }
+}
+
+impl<T: Ord> Extend<T> for BinomialHeap<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for elem in iter {
+            self.push(elem);
+        }
+    }
+}
+
+impl<T: Ord + fmt::Debug> fmt::Debug for BinomialHeap<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// Iterator over the elements of a [`BinomialHeap`], in no particular order.
+pub struct Iter<'a, T> {
+    stack: Vec<&'a Node<T>>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.stack.pop().map(|node| {
+            self.stack.extend(node.child.as_deref());
+            self.stack.extend(node.sibling.as_deref());
+            &node.elem
+        })
+    }
+}
+
+impl<'a, T: Ord> IntoIterator for &'a BinomialHeap<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{BinomialHeap, Node};
+
+    /// Checks a tree is heap-ordered and binomial of order `k`, and returns its size.
+    fn check_tree<T: Ord>(node: &Node<T>, k: usize) -> usize {
+        let mut size = 1;
+        let mut child = node.child.as_deref();
+        for order in (0..k).rev() {
+            let c = child.expect("too few children");
+            assert!(node.elem <= c.elem);
+            size += check_tree(c, order);
+            child = c.sibling.as_deref();
+        }
+        assert!(child.is_none(), "too many children");
+        size
+    }
+
+    fn check<T: Ord>(heap: &BinomialHeap<T>) {
+        let mut len = 0;
+        for (k, tree) in heap.trees.iter().enumerate() {
+            assert_eq!(heap.len >> k & 1 == 1, tree.is_some());
+            if let Some(root) = tree {
+                assert!(root.sibling.is_none());
+                len += check_tree(root, k);
+            }
+        }
+        assert_eq!(len, heap.len);
+        assert!(heap.trees.last().is_none_or(|tree| tree.is_some()));
+    }
+
+    #[test]
+    fn basics() {
+        let mut heap = BinomialHeap::new();
+
+        // Check empty heap behaves right
+        assert_eq!(heap.pop(), None);
+        assert_eq!(heap.peek(), None);
+
+        // Populate heap
+        heap.extend([5, 3, 8, 1, 9, 2, 7, 4, 6, 0, 12, 11, 10]);
+        check(&heap);
+        assert_eq!(heap.len(), 13);
+        // 13 = 0b1101
+        let shape: Vec<_> = heap.trees.iter().map(Option::is_some).collect();
+        assert_eq!(shape, vec![true, false, true, true]);
+        assert_eq!(heap.peek(), Some(&0));
+
+        // Check normal removal
+        assert_eq!(heap.pop(), Some(0));
+        assert_eq!(heap.pop(), Some(1));
+        check(&heap);
+
+        // Check exhaustion
+        let rest: Vec<_> = std::iter::from_fn(|| heap.pop()).collect();
+        assert_eq!(rest, (2..=12).collect::<Vec<_>>());
+        assert!(heap.is_empty());
+        assert!(heap.trees.is_empty());
+    }
+
+    #[test]
+    fn merge() {
+        // 7 + 9 = 16: every order carries, ending in a single tree
+        let mut a: BinomialHeap<_> = (0..7).collect();
+        let b: BinomialHeap<_> = (7..16).collect();
+        a.merge(b);
+        check(&a);
+        assert_eq!(a.trees.len(), 5);
+        assert_eq!(a.trees.iter().flatten().count(), 1);
+
+        a.merge(BinomialHeap::new());
+        let mut empty = BinomialHeap::new();
+        empty.merge(a);
+        let popped: Vec<_> = std::iter::from_fn(|| empty.pop()).collect();
+        assert_eq!(popped, (0..16).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn against_std() {
+        use std::cmp::Reverse;
+        let mut heap = BinomialHeap::new();
+        let mut model = std::collections::BinaryHeap::new();
+        let mut x: u32 = 1;
+        for i in 0..10_000 {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            match x >> 30 {
+                0 => assert_eq!(heap.pop(), model.pop().map(|Reverse(v)| v)),
+                1 => {
+                    // Merge in a small heap
+                    let other: Vec<u32> = (0..(x >> 8) % 20).map(|j| (x >> 4) % 1000 + j).collect();
+                    model.extend(other.iter().copied().map(Reverse));
+                    heap.merge(other.into_iter().collect());
+                }
+                _ => {
+                    heap.push(x % 1000);
+                    model.push(Reverse(x % 1000));
+                }
+            }
+            assert_eq!(heap.len(), model.len());
+            assert_eq!(heap.peek(), model.peek().map(|Reverse(v)| v));
+            if i % 100 == 0 {
+                check(&heap);
+            }
+        }
+    }
+}
@@ -0,0 +1,417 @@
+//! # Bit set
+//!
+//! A set of small non-negative integers, one bit each, in a `Vec<u64>` that grows to fit
+//! the largest element:
+//!
+//! ```text
+//!   {1, 3, 64, 70}
+//!
+//!   words[0] = ...0000_1010      bits 1 and 3
+//!   words[1] = ...0100_0001      bits 64 + 0 and 64 + 6
+//! ```
+//!
+//! Membership is a shift and a mask. Union, intersection and difference work a whole word,
+//! 64 elements, at a time with `|`, `&` and `& !`, and counting is a `count_ones` per word.
+//! Iterating skips empty words, and within a word jumps from set bit to set bit with
+//! `trailing_zeros`.
+//!
+//! The cost is memory proportional to the largest element rather than to the number of
+//! elements, so it wants dense sets over a known range: visited flags in a graph search,
+//! or the sieve of Eratosthenes, which one of the tests runs.
+//!
+//! The set never shrinks on its own. Two sets are equal if they hold the same elements,
+//! however many words each happens to have.
+
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::iter::FromIterator;
+use std::ops::{BitAnd, BitOr, BitXor, Sub};
+
+const BITS: usize = 64;
+
+#[derive(Clone, Default)]
+pub struct BitSet {
+    words: Vec<u64>,
+}
+
+impl BitSet {
+    /// Creates an empty BitSet.
+    pub fn new() -> Self {
+        BitSet { words: Vec::new() }
+    }
+
+    /// Returns an empty set with room for the elements `0..bits` without growing.
+    pub fn with_capacity(bits: usize) -> Self {
+        BitSet {
+            words: vec![0; bits.div_ceil(BITS)],
+        }
+    }
+
+    /// Returns how many elements fit without growing.
+    pub fn capacity(&self) -> usize {
+        self.words.len() * BITS
+    }
+
+    /// Returns the number of elements. This counts the bits, in O(capacity / 64).
+    pub fn len(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|&w| w == 0)
+    }
+
+    pub fn clear(&mut self) {
+        self.words.clear();
+    }
+
+    /// Drops the trailing words that hold no elements.
+    pub fn shrink_to_fit(&mut self) {
+        let used = self.used_words();
+        self.words.truncate(used);
+        self.words.shrink_to_fit();
+    }
+
+    /// Number of words up to and including the last nonzero one.
+    fn used_words(&self) -> usize {
+        self.words
+            .iter()
+            .rposition(|&w| w != 0)
+            .map_or(0, |i| i + 1)
+    }
+
+    pub fn contains(&self, elem: usize) -> bool {
+        self.words
+            .get(elem / BITS)
+            .is_some_and(|w| w & (1 << (elem % BITS)) != 0)
+    }
+
+    /// Adds an element, growing the set if needed. Returns whether it was new.
+    pub fn insert(&mut self, elem: usize) -> bool {
+        let word = elem / BITS;
+        if word >= self.words.len() {
+            self.words.resize(word + 1, 0);
+        }
+        let mask = 1 << (elem % BITS);
+        let new = self.words[word] & mask == 0;
+        self.words[word] |= mask;
+        new
+    }
+
+    /// Removes an element. Returns whether it was there.
+    pub fn remove(&mut self, elem: usize) -> bool {
+        match self.words.get_mut(elem / BITS) {
+            Some(w) => {
+                let mask = 1 << (elem % BITS);
+                let was = *w & mask != 0;
+                *w &= !mask;
+                was
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the smallest element.
+    pub fn first(&self) -> Option<usize> {
+        let (i, w) = self.words.iter().enumerate().find(|(_, &w)| w != 0)?;
+        Some(i * BITS + w.trailing_zeros() as usize)
+    }
+
+    /// Returns the largest element.
+    pub fn last(&self) -> Option<usize> {
+        let i = self.used_words().checked_sub(1)?;
+        Some(i * BITS + (BITS - 1 - self.words[i].leading_zeros() as usize))
+    }
+
+    /// Adds every element of `other` to this set.
+    pub fn union_with(&mut self, other: &BitSet) {
+        if other.words.len() > self.words.len() {
+            self.words.resize(other.words.len(), 0);
+        }
+        for (a, b) in self.words.iter_mut().zip(&other.words) {
+            *a |= b;
+        }
+    }
+
+    /// Keeps only the elements that are also in `other`.
+    pub fn intersect_with(&mut self, other: &BitSet) {
+        self.words.truncate(other.words.len());
+        for (a, b) in self.words.iter_mut().zip(&other.words) {
+            *a &= b;
+        }
+    }
+
+    /// Removes every element of `other` from this set.
+    pub fn difference_with(&mut self, other: &BitSet) {
+        for (a, b) in self.words.iter_mut().zip(&other.words) {
+            *a &= !b;
+        }
+    }
+
+    /// Keeps the elements in exactly one of the two sets.
+    pub fn symmetric_difference_with(&mut self, other: &BitSet) {
+        if other.words.len() > self.words.len() {
+            self.words.resize(other.words.len(), 0);
+        }
+        for (a, b) in self.words.iter_mut().zip(&other.words) {
+            *a ^= b;
+        }
+    }
+
+    pub fn is_subset(&self, other: &BitSet) -> bool {
+        self.words.iter().enumerate().all(|(i, &a)| {
+            let b = other.words.get(i).copied().unwrap_or(0);
+            a & !b == 0
+        })
+    }
+
+    pub fn is_superset(&self, other: &BitSet) -> bool {
+        other.is_subset(self)
+    }
+
+    pub fn is_disjoint(&self, other: &BitSet) -> bool {
+        self.words.iter().zip(&other.words).all(|(a, b)| a & b == 0)
+    }
+
+    /// Iterates over the elements in increasing order.
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            words: &self.words,
+            base: 0,
+            word: self.words.first().copied().unwrap_or(0),
+        }
+    }
+}
+
+impl PartialEq for BitSet {
+    fn eq(&self, other: &Self) -> bool {
+        let n = self.used_words();
+        n == other.used_words() && self.words[..n] == other.words[..n]
+    }
+}
+
+impl Eq for BitSet {}
+
+impl Hash for BitSet {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // Consistent with `eq`: trailing empty words don't count
+        self.words[..self.used_words()].hash(state);
+    }
+}
+
+impl fmt::Debug for BitSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl Extend<usize> for BitSet {
+    fn extend<I: IntoIterator<Item = usize>>(&mut self, iter: I) {
+        for elem in iter {
+            self.insert(elem);
+        }
+    }
+}
+
+impl FromIterator<usize> for BitSet {
+    fn from_iter<I: IntoIterator<Item = usize>>(iter: I) -> Self {
+        let mut set = BitSet::new();
+        set.extend(iter);
+        set
+    }
+}
+
+impl BitOr for &BitSet {
+    type Output = BitSet;
+
+    fn bitor(self, other: &BitSet) -> BitSet {
+        let mut set = self.clone();
+        set.union_with(other);
+        set
+    }
+}
+
+impl BitAnd for &BitSet {
+    type Output = BitSet;
+
+    fn bitand(self, other: &BitSet) -> BitSet {
+        let mut set = self.clone();
+        set.intersect_with(other);
+        set
+    }
+}
+
+impl Sub for &BitSet {
+    type Output = BitSet;
+
+    fn sub(self, other: &BitSet) -> BitSet {
+        let mut set = self.clone();
+        set.difference_with(other);
+        set
+    }
+}
+
+impl BitXor for &BitSet {
+    type Output = BitSet;
+
+    fn bitxor(self, other: &BitSet) -> BitSet {
+        let mut set = self.clone();
+        set.symmetric_difference_with(other);
+        set
+    }
+}
+
+/// Iterator over the elements of a [`BitSet`], in increasing order.
+pub struct Iter<'a> {
+    words: &'a [u64],
+    // Element number of bit 0 of `words[0]`
+    base: usize,
+    // What's left of `words[0]`, with the bits already yielded cleared
+    word: u64,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        while self.word == 0 {
+            if self.words.len() <= 1 {
+                return None;
+            }
+            self.words = &self.words[1..];
+            self.base += BITS;
+            self.word = self.words[0];
+        }
+        let bit = self.word.trailing_zeros() as usize;
+        // Clear the lowest set bit
+        self.word &= self.word - 1;
+        Some(self.base + bit)
+    }
+}
+
+impl<'a> IntoIterator for &'a BitSet {
+    type Item = usize;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::BitSet;
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn basics() {
+        let mut set = BitSet::new();
+
+        // Check empty set behaves right
+        assert!(set.is_empty());
+        assert!(!set.contains(0));
+        assert!(!set.remove(1000));
+        assert_eq!(set.iter().next(), None);
+        assert_eq!((set.first(), set.last()), (None, None));
+
+        // Populate set
+        assert!(set.insert(3));
+        assert!(set.insert(64));
+        assert!(set.insert(1));
+        assert!(!set.insert(3));
+        assert!(set.insert(200));
+        assert_eq!(set.len(), 4);
+        assert_eq!(set.capacity(), 256);
+        assert_eq!(format!("{:?}", set), "{1, 3, 64, 200}");
+        assert_eq!((set.first(), set.last()), (Some(1), Some(200)));
+
+        // Check normal removal
+        assert!(set.remove(200));
+        assert!(!set.remove(200));
+        assert!(!set.contains(200));
+        assert_eq!(set.last(), Some(64));
+
+        // Trailing empty words don't change equality, and can be dropped
+        assert_eq!(set, [1, 3, 64].iter().copied().collect());
+        set.shrink_to_fit();
+        assert_eq!(set.capacity(), 128);
+
+        // Check exhaustion
+        for elem in [1, 3, 64] {
+            assert!(set.remove(elem));
+        }
+        assert!(set.is_empty());
+        assert_eq!(set, BitSet::with_capacity(1000));
+        set.shrink_to_fit();
+        assert_eq!(set.capacity(), 0);
+    }
+
+    #[test]
+    fn sieve() {
+        // The sieve of Eratosthenes, marking composites
+        let n = 10_000;
+        let mut composite = BitSet::with_capacity(n);
+        composite.insert(0);
+        composite.insert(1);
+        let mut p = 2;
+        while p * p < n {
+            if !composite.contains(p) {
+                for multiple in (p * p..n).step_by(p) {
+                    composite.insert(multiple);
+                }
+            }
+            p += 1;
+        }
+        let mut everything = BitSet::new();
+        everything.extend(0..n);
+        let primes = &everything - &composite;
+        assert_eq!(primes.len(), 1229);
+        assert!(primes
+            .iter()
+            .take(10)
+            .eq([2, 3, 5, 7, 11, 13, 17, 19, 23, 29]));
+        assert_eq!(primes.last(), Some(9973));
+        assert!(primes.is_disjoint(&composite));
+        assert_eq!(&primes | &composite, everything);
+    }
+
+    #[test]
+    fn against_btree_set() {
+        let mut x: u32 = 1;
+        let mut random_set = |range: usize| {
+            let mut set = BitSet::new();
+            let mut model = BTreeSet::new();
+            for _ in 0..100 {
+                x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                let elem = (x >> 8) as usize % range;
+                assert_eq!(set.insert(elem), model.insert(elem));
+                x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                let elem = (x >> 8) as usize % range;
+                assert_eq!(set.remove(elem), model.remove(&elem));
+            }
+            (set, model)
+        };
+
+        for round in 0..200 {
+            // Sets of different sizes, so the word counts don't line up
+            let (a, a_model) = random_set(64 + round * 7);
+            let (b, b_model) = random_set(64 + (200 - round) * 7);
+            assert_eq!(a.len(), a_model.len());
+            assert!(a.iter().eq(a_model.iter().copied()));
+            assert_eq!(a.first(), a_model.iter().next().copied());
+            assert_eq!(a.last(), a_model.iter().next_back().copied());
+
+            assert!((&a | &b).iter().eq(a_model.union(&b_model).copied()));
+            assert!((&a & &b).iter().eq(a_model.intersection(&b_model).copied()));
+            assert!((&a - &b).iter().eq(a_model.difference(&b_model).copied()));
+            let symmetric = a_model.symmetric_difference(&b_model).copied();
+            assert!((&a ^ &b).iter().eq(symmetric));
+
+            assert_eq!(a.is_subset(&b), a_model.is_subset(&b_model));
+            assert_eq!(a.is_disjoint(&b), a_model.is_disjoint(&b_model));
+            let union = &a | &b;
+            assert!(union.is_superset(&a) && union.is_superset(&b));
+            assert!((&a & &b).is_subset(&a));
+        }
+    }
+}
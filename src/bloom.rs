@@ -0,0 +1,646 @@
+//! # Bloom filters
+//!
+//! A Bloom filter (Bloom, 1970) is a set that may lie, but only one way: it can say an
+//! item is in it when it isn't, but never the reverse. In return it takes a handful of bits
+//! per item, however big the items are, so it makes a cheap guard in front of an expensive
+//! lookup: "definitely not on disk, don't bother reading".
+//!
+//! It's an array of `m` bits and `k` hash functions. Inserting sets the `k` bits the item
+//! hashes to, and a lookup checks that all `k` are set:
+//!
+//! ```text
+//!   insert "cat"  ->  bits 1, 6, 9
+//!   insert "dog"  ->  bits 3, 6, 12
+//!
+//!   [ 0 1 0 1 0 0 1 0 0 1 0 0 1 0 0 0 ]
+//!
+//!   "cat"?  1, 6, 9 all set          -> maybe
+//!   "eel"?  3, 9, 14, and 14 is 0    -> no
+//!   "fox"?  1, 3, 12 all set         -> maybe, a false positive
+//! ```
+//!
+//! For `n` items and a false positive rate `p`, the best sizes are `m = -n ln p / (ln 2)²`
+//! bits and `k = (m / n) ln 2` hashes, about 10 bits and 7 hashes per item for 1%.
+//! `BloomFilter::new` works them out. The `k` positions come from one 64-bit hash with
+//! enhanced double hashing (Dillinger and Manolios, 2004). Kirsch and Mitzenmacher (2006)
+//! showed that deriving them this way costs next to nothing in accuracy over `k`
+//! independent hashes.
+//!
+//! A plain filter can't forget: clearing an item's bits might clear bits other items
+//! share. [`CountingBloomFilter`] (Fan, Cao, Almeida and Broder, 2000) swaps each bit for
+//! a small counter, which inserting increments and removing decrements, at 8 times the
+//! space. Removing something that was never inserted breaks it, by zeroing counters that
+//! real items count on, so only remove items you know went in.
+//!
+//! ## Cuckoo filters
+//!
+//! [`CuckooFilter`] (Fan, Andersen, Kaminsky and Mitzenmacher, 2014) gets deletion
+//! without the counters. It keeps a 16-bit fingerprint of each item in a cuckoo hash
+//! table of 4-slot buckets, where each fingerprint may live in one of two buckets. The
+//! second bucket is the first XOR a hash of the fingerprint, so either bucket leads to
+//! the other knowing only the fingerprint:
+//!
+//! ```text
+//!   i1 = hash(x)           i2 = i1 ^ hash(fp(x))          i1 = i2 ^ hash(fp(x))
+//! ```
+//!
+//! An insert with both buckets full kicks a fingerprint out of one and moves it to its
+//! other bucket, which may kick out another, and so on. Lookups and removals check two
+//! buckets, 8 slots. Removal just clears a matching fingerprint, so like the counting
+//! filter it must only be asked to remove what went in.
+//!
+//! Tables fill to about 95% before a chain of kicks runs too long, and at 16 bits a
+//! fingerprint the false positive rate is about 8 in 65536, 0.012%. A Bloom filter
+//! needs nearly 19 bits an item for that, against the cuckoo filter's 16.8.
+
+use crate::bit_set::BitSet;
+use crate::rng::XorShift;
+use std::collections::hash_map::RandomState;
+use std::f64::consts::LN_2;
+use std::hash::{BuildHasher, Hash};
+
+/// The hashing machinery the filters share: the sizes, and how an item picks its cells.
+#[derive(Clone, Debug)]
+struct Probes<S> {
+    hasher: S,
+    cells: usize,
+    hashes: u32,
+}
+
+impl<S: BuildHasher> Probes<S> {
+    /// Sizes for `items` items at a false positive rate of `rate`.
+    fn new(items: usize, rate: f64, hasher: S) -> Self {
+        assert!(
+            rate > 0.0 && rate < 1.0,
+            "false positive rate must be between 0 and 1"
+        );
+        let n = items.max(1) as f64;
+        let cells = (-n * rate.ln() / (LN_2 * LN_2)).ceil().max(1.0);
+        let hashes = (cells / n * LN_2).round().max(1.0);
+        Probes {
+            hasher,
+            cells: cells as usize,
+            hashes: hashes as u32,
+        }
+    }
+
+    /// The cells `item` maps to, one per hash. They can repeat.
+    fn cells<Q: Hash + ?Sized>(&self, item: &Q) -> impl Iterator<Item = usize> {
+        let hash = self.hasher.hash_one(item);
+        // A second hash from the first, by a round of a 64-bit finalizer
+        let mut step = (hash ^ (hash >> 33)).wrapping_mul(0xff51_afd7_ed55_8ccd);
+        let mut x = hash;
+        let cells = self.cells as u64;
+        (0..self.hashes).map(move |i| {
+            let cell = (x % cells) as usize;
+            x = x.wrapping_add(step);
+            step = step.wrapping_add(i as u64);
+            cell
+        })
+    }
+
+    /// The false positive rate if a fraction `full` of the cells are set.
+    fn rate(&self, full: f64) -> f64 {
+        full.powi(self.hashes as i32)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BloomFilter<S = RandomState> {
+    probes: Probes<S>,
+    bits: BitSet,
+}
+
+impl BloomFilter<RandomState> {
+    /// Creates an empty filter sized for `items` items at a false positive rate of `rate`.
+    ///
+    /// # Panics
+    ///
+    /// Panics unless `0 < rate < 1`.
+    pub fn new(items: usize, rate: f64) -> Self {
+        Self::with_hasher(items, rate, RandomState::new())
+    }
+}
+
+impl<S: BuildHasher> BloomFilter<S> {
+    /// Creates an empty filter like `new` that hashes items with `hasher`.
+    pub fn with_hasher(items: usize, rate: f64, hasher: S) -> Self {
+        let probes = Probes::new(items, rate, hasher);
+        BloomFilter {
+            bits: BitSet::with_capacity(probes.cells),
+            probes,
+        }
+    }
+
+    /// Returns the number of bits, `m`.
+    pub fn bit_count(&self) -> usize {
+        self.probes.cells
+    }
+
+    /// Returns the number of hashes per item, `k`.
+    pub fn hash_count(&self) -> u32 {
+        self.probes.hashes
+    }
+
+    /// Adds an item. Returns `false` if it seemed to be in already, which may be wrong.
+    pub fn insert<Q: Hash + ?Sized>(&mut self, item: &Q) -> bool {
+        let mut new = false;
+        for cell in self.probes.cells(item) {
+            new |= self.bits.insert(cell);
+        }
+        new
+    }
+
+    /// Returns `false` if the item was never inserted, and `true` if it probably was.
+    pub fn contains<Q: Hash + ?Sized>(&self, item: &Q) -> bool {
+        self.probes.cells(item).all(|cell| self.bits.contains(cell))
+    }
+
+    /// Estimates the current false positive rate from how full the bits are.
+    pub fn false_positive_rate(&self) -> f64 {
+        self.probes
+            .rate(self.bits.len() as f64 / self.probes.cells as f64)
+    }
+
+    pub fn clear(&mut self) {
+        self.bits = BitSet::with_capacity(self.probes.cells);
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CountingBloomFilter<S = RandomState> {
+    probes: Probes<S>,
+    // Stuck at u8::MAX once they get there, since the true count is lost
+    counters: Vec<u8>,
+}
+
+impl CountingBloomFilter<RandomState> {
+    /// Creates an empty filter sized for `items` items at a false positive rate of `rate`.
+    ///
+    /// # Panics
+    ///
+    /// Panics unless `0 < rate < 1`.
+    pub fn new(items: usize, rate: f64) -> Self {
+        Self::with_hasher(items, rate, RandomState::new())
+    }
+}
+
+impl<S: BuildHasher> CountingBloomFilter<S> {
+    /// Creates an empty filter like `new` that hashes items with `hasher`.
+    pub fn with_hasher(items: usize, rate: f64, hasher: S) -> Self {
+        let probes = Probes::new(items, rate, hasher);
+        CountingBloomFilter {
+            counters: vec![0; probes.cells],
+            probes,
+        }
+    }
+
+    /// Returns the number of counters, `m`.
+    pub fn counter_count(&self) -> usize {
+        self.probes.cells
+    }
+
+    /// Returns the number of hashes per item, `k`.
+    pub fn hash_count(&self) -> u32 {
+        self.probes.hashes
+    }
+
+    /// Adds an item. Inserting the same item twice counts it twice.
+    pub fn insert<Q: Hash + ?Sized>(&mut self, item: &Q) {
+        for cell in self.probes.cells(item) {
+            let counter = &mut self.counters[cell];
+            *counter = counter.saturating_add(1);
+        }
+    }
+
+    /// Returns `false` if the item was never inserted, and `true` if it probably was.
+    pub fn contains<Q: Hash + ?Sized>(&self, item: &Q) -> bool {
+        self.count(item) > 0
+    }
+
+    /// Returns an upper bound on how many times the item was inserted, the smallest of its
+    /// counters.
+    pub fn count<Q: Hash + ?Sized>(&self, item: &Q) -> u8 {
+        self.probes
+            .cells(item)
+            .map(|cell| self.counters[cell])
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// Takes one insert of an item back out. Returns `false`, and changes nothing, if the
+    /// item is certainly not in the filter.
+    ///
+    /// Removing an item that was never inserted, but looks like it was, leaves the filter
+    /// with false negatives.
+    pub fn remove<Q: Hash + ?Sized>(&mut self, item: &Q) -> bool {
+        if !self.contains(item) {
+            return false;
+        }
+        for cell in self.probes.cells(item) {
+            let counter = &mut self.counters[cell];
+            if *counter != u8::MAX {
+                *counter -= 1;
+            }
+        }
+        true
+    }
+
+    /// Estimates the current false positive rate from how many counters are nonzero.
+    pub fn false_positive_rate(&self) -> f64 {
+        let full = self.counters.iter().filter(|&&c| c > 0).count();
+        self.probes.rate(full as f64 / self.probes.cells as f64)
+    }
+
+    pub fn clear(&mut self) {
+        self.counters.iter_mut().for_each(|c| *c = 0);
+    }
+}
+
+const BUCKET_SLOTS: usize = 4;
+// How long a chain of kicks may run before an insert gives up
+const MAX_KICKS: usize = 500;
+
+/// Counters for how hard a [`CuckooFilter`] is working.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CuckooStats {
+    /// Fingerprints moved to their other bucket to make room.
+    pub kicks: u64,
+    /// Inserts turned away because the filter was full.
+    pub failures: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct CuckooFilter<S = RandomState> {
+    hasher: S,
+    // 0 marks an empty slot, so fingerprints are never 0
+    buckets: Vec<[u16; BUCKET_SLOTS]>,
+    // Where the last fingerprint of a failed chain of kicks waits. While it's taken, the
+    // filter counts as full.
+    victim: Option<(usize, u16)>,
+    len: usize,
+    rng: XorShift,
+    stats: CuckooStats,
+}
+
+impl CuckooFilter<RandomState> {
+    /// Creates an empty filter with room for at least `items` items.
+    pub fn new(items: usize) -> Self {
+        Self::with_hasher(items, RandomState::new())
+    }
+}
+
+impl<S: BuildHasher> CuckooFilter<S> {
+    /// Creates an empty filter like `new` that hashes items with `hasher`.
+    pub fn with_hasher(items: usize, hasher: S) -> Self {
+        // Aim for 95% full at `items`, with a power of two buckets for the XOR to work
+        let buckets = ((items as f64 / 0.95).ceil() as usize)
+            .div_ceil(BUCKET_SLOTS)
+            .next_power_of_two();
+        CuckooFilter {
+            hasher,
+            buckets: vec![[0; BUCKET_SLOTS]; buckets],
+            victim: None,
+            len: 0,
+            rng: XorShift::from_entropy(),
+            stats: CuckooStats::default(),
+        }
+    }
+
+    /// Returns the number of fingerprints stored.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of slots.
+    pub fn capacity(&self) -> usize {
+        self.buckets.len() * BUCKET_SLOTS
+    }
+
+    /// Returns the fraction of slots in use.
+    pub fn load_factor(&self) -> f64 {
+        self.len as f64 / self.capacity() as f64
+    }
+
+    pub fn stats(&self) -> CuckooStats {
+        self.stats
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = CuckooStats::default();
+    }
+
+    /// The item's first bucket and fingerprint.
+    fn locate<Q: Hash + ?Sized>(&self, item: &Q) -> (usize, u16) {
+        let hash = self.hasher.hash_one(item);
+        let fingerprint = ((hash >> 48) as u16).max(1);
+        (hash as usize & (self.buckets.len() - 1), fingerprint)
+    }
+
+    /// The other bucket a fingerprint in bucket `i` may live in.
+    fn alternate(&self, i: usize, fingerprint: u16) -> usize {
+        let hash = (fingerprint as u64).wrapping_mul(0xc6a4_a793_5bd1_e995);
+        (i ^ (hash >> 32) as usize) & (self.buckets.len() - 1)
+    }
+
+    fn put_in(&mut self, i: usize, fingerprint: u16) -> bool {
+        match self.buckets[i].iter_mut().find(|slot| **slot == 0) {
+            Some(slot) => {
+                *slot = fingerprint;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Adds an item. Returns `false` if the filter is too full to take it.
+    ///
+    /// Inserting an item twice stores it twice, up to 8 times, and it then takes as many
+    /// removals to forget.
+    pub fn insert<Q: Hash + ?Sized>(&mut self, item: &Q) -> bool {
+        if self.victim.is_some() {
+            self.stats.failures += 1;
+            return false;
+        }
+        let (i1, fingerprint) = self.locate(item);
+        let i2 = self.alternate(i1, fingerprint);
+        self.len += 1;
+        if self.put_in(i1, fingerprint) || self.put_in(i2, fingerprint) {
+            return true;
+        }
+
+        // Both full: kick out a random fingerprint, and move it to its other bucket
+        let mut i = if self.rng.next_u64() & 1 == 0 { i1 } else { i2 };
+        let mut fingerprint = fingerprint;
+        for _ in 0..MAX_KICKS {
+            let slot = (self.rng.next_u64() % BUCKET_SLOTS as u64) as usize;
+            std::mem::swap(&mut fingerprint, &mut self.buckets[i][slot]);
+            self.stats.kicks += 1;
+            i = self.alternate(i, fingerprint);
+            if self.put_in(i, fingerprint) {
+                return true;
+            }
+        }
+        // The item is in, but someone else's fingerprint is left without a slot. Park it,
+        // rather than lose it and give that item a false negative.
+        self.victim = Some((i, fingerprint));
+        true
+    }
+
+    /// Returns `false` if the item was never inserted, and `true` if it probably was.
+    pub fn contains<Q: Hash + ?Sized>(&self, item: &Q) -> bool {
+        let (i1, fingerprint) = self.locate(item);
+        let i2 = self.alternate(i1, fingerprint);
+        self.buckets[i1].contains(&fingerprint)
+            || self.buckets[i2].contains(&fingerprint)
+            || self
+                .victim
+                .is_some_and(|(i, f)| f == fingerprint && (i == i1 || i == i2))
+    }
+
+    /// Removes one copy of an item. Returns `false`, and changes nothing, if the item is
+    /// certainly not in the filter.
+    ///
+    /// Removing an item that was never inserted, but looks like it was, removes some other
+    /// item's fingerprint instead.
+    pub fn remove<Q: Hash + ?Sized>(&mut self, item: &Q) -> bool {
+        let (i1, fingerprint) = self.locate(item);
+        let i2 = self.alternate(i1, fingerprint);
+        if let Some((i, f)) = self.victim {
+            if f == fingerprint && (i == i1 || i == i2) {
+                self.victim = None;
+                self.len -= 1;
+                return true;
+            }
+        }
+        for i in [i1, i2] {
+            if let Some(slot) = self.buckets[i].iter_mut().find(|f| **f == fingerprint) {
+                *slot = 0;
+                self.len -= 1;
+                // There's room now, so give a parked fingerprint another go
+                if let Some((i, f)) = self.victim.take() {
+                    let alternate = self.alternate(i, f);
+                    if !self.put_in(i, f) && !self.put_in(alternate, f) {
+                        self.victim = Some((i, f));
+                    }
+                }
+                return true;
+            }
+        }
+        false
+    }
+
+    pub fn clear(&mut self) {
+        self.buckets.iter_mut().for_each(|b| *b = [0; BUCKET_SLOTS]);
+        self.victim = None;
+        self.len = 0;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{BloomFilter, CountingBloomFilter, CuckooFilter};
+
+    #[test]
+    fn sizes() {
+        // About 9.6 bits and 7 hashes per item for 1%
+        let filter = BloomFilter::new(1000, 0.01);
+        assert_eq!(filter.bit_count(), 9586);
+        assert_eq!(filter.hash_count(), 7);
+        let filter = CountingBloomFilter::new(1000, 0.001);
+        assert_eq!(filter.hash_count(), 10);
+    }
+
+    #[test]
+    #[should_panic(expected = "false positive rate must be between 0 and 1")]
+    fn rate_out_of_range() {
+        BloomFilter::new(10, 1.0);
+    }
+
+    #[test]
+    fn basics() {
+        let mut filter = BloomFilter::new(100, 0.01);
+
+        // Check empty filter behaves right
+        assert!(!filter.contains("cat"));
+        assert_eq!(filter.false_positive_rate(), 0.0);
+
+        // Populate filter
+        assert!(filter.insert("cat"));
+        assert!(filter.insert("dog"));
+        assert!(!filter.insert("cat"));
+        assert!(filter.contains("cat"));
+        assert!(filter.contains("dog"));
+        assert!(!filter.contains("eel"));
+        assert!(filter.false_positive_rate() > 0.0);
+
+        filter.clear();
+        assert!(!filter.contains("cat"));
+    }
+
+    #[test]
+    fn false_positive_rate() {
+        let n = if cfg!(miri) { 200 } else { 10_000 };
+        let mut filter = BloomFilter::new(n, 0.01);
+        for i in 0..n {
+            filter.insert(&i);
+        }
+
+        // No false negatives, and false positives near the target
+        assert!((0..n).all(|i| filter.contains(&i)));
+        let tries = 10 * n;
+        let false_positives = (n..n + tries).filter(|i| filter.contains(i)).count();
+        let rate = false_positives as f64 / tries as f64;
+        assert!(rate < 0.02, "false positive rate {}", rate);
+        let estimate = filter.false_positive_rate();
+        assert!(estimate > 0.005 && estimate < 0.02, "estimate {}", estimate);
+    }
+
+    #[test]
+    fn counting() {
+        let mut filter = CountingBloomFilter::new(100, 0.01);
+
+        // Check empty filter behaves right
+        assert!(!filter.contains("cat"));
+        assert!(!filter.remove("cat"));
+
+        // Populate filter, one item twice
+        filter.insert("cat");
+        filter.insert("cat");
+        filter.insert("dog");
+        assert_eq!(filter.count("cat"), 2);
+        assert!(filter.count("dog") >= 1);
+
+        // Check normal removal
+        assert!(filter.remove("cat"));
+        assert!(filter.contains("cat"));
+        assert!(filter.remove("cat"));
+        assert!(!filter.contains("cat"));
+        assert!(filter.contains("dog"));
+
+        // Check exhaustion
+        assert!(filter.remove("dog"));
+        assert!(!filter.contains("dog"));
+        assert_eq!(filter.false_positive_rate(), 0.0);
+    }
+
+    #[test]
+    fn counting_saturates() {
+        let mut filter = CountingBloomFilter::new(10, 0.01);
+        for _ in 0..300 {
+            filter.insert(&7);
+        }
+        assert_eq!(filter.count(&7), u8::MAX);
+
+        // The true count is lost, so removing never brings it back down
+        for _ in 0..300 {
+            assert!(filter.remove(&7));
+        }
+        assert!(filter.contains(&7));
+        filter.clear();
+        assert!(!filter.contains(&7));
+    }
+
+    #[test]
+    fn counting_remove_keeps_the_rest() {
+        let n = if cfg!(miri) { 100 } else { 5_000 };
+        let mut filter = CountingBloomFilter::new(n, 0.01);
+        for i in 0..n {
+            filter.insert(&i);
+        }
+        // Take out the evens, and every odd must still be there
+        for i in (0..n).step_by(2) {
+            assert!(filter.remove(&i));
+        }
+        assert!((1..n).step_by(2).all(|i| filter.contains(&i)));
+        let lingering = (0..n).step_by(2).filter(|i| filter.contains(i)).count();
+        assert!(lingering < n / 20, "{} evens linger", lingering);
+    }
+
+    #[test]
+    fn cuckoo_basics() {
+        let mut filter = CuckooFilter::new(100);
+        assert_eq!(filter.capacity(), 128);
+
+        // Check empty filter behaves right
+        assert!(filter.is_empty());
+        assert!(!filter.contains("cat"));
+        assert!(!filter.remove("cat"));
+
+        // Populate filter, one item twice
+        assert!(filter.insert("cat"));
+        assert!(filter.insert("cat"));
+        assert!(filter.insert("dog"));
+        assert_eq!(filter.len(), 3);
+        assert!(filter.contains("dog"));
+        assert!(!filter.contains("eel"));
+
+        // Check normal removal
+        assert!(filter.remove("cat"));
+        assert!(filter.contains("cat"));
+        assert!(filter.remove("cat"));
+        assert!(!filter.contains("cat"));
+
+        // Check exhaustion
+        assert!(filter.remove("dog"));
+        assert!(filter.is_empty());
+        assert!(!filter.contains("dog"));
+    }
+
+    #[test]
+    fn cuckoo_fills_up() {
+        let n = if cfg!(miri) { 500 } else { 1 << 16 };
+        let mut filter = CuckooFilter::new(n);
+        let capacity = filter.capacity();
+
+        // Insert until it turns one away. That should be well past 90% full.
+        let mut inserted = 0;
+        while filter.insert(&inserted) {
+            inserted += 1;
+        }
+        assert_eq!(filter.len(), inserted);
+        assert!(
+            filter.load_factor() > 0.9,
+            "full at {}",
+            filter.load_factor()
+        );
+        let stats = filter.stats();
+        assert_eq!(stats.failures, 1);
+        assert!(stats.kicks > 0);
+        assert!(!filter.insert(&capacity));
+        assert_eq!(filter.stats().failures, 2);
+
+        // Everything that went in is still found, the parked fingerprint included
+        assert!((0..inserted).all(|i| filter.contains(&i)));
+
+        // Making room lets it take inserts again
+        for i in 0..inserted / 2 {
+            assert!(filter.remove(&i));
+        }
+        assert!((inserted / 2..inserted).all(|i| filter.contains(&i)));
+        assert!(filter.insert(&capacity));
+        filter.reset_stats();
+        assert_eq!(filter.stats().kicks, 0);
+        filter.clear();
+        assert!(filter.is_empty());
+        assert!(!filter.contains(&(inserted - 1)));
+    }
+
+    #[test]
+    fn cuckoo_false_positive_rate() {
+        let n = if cfg!(miri) { 200 } else { 20_000 };
+        let mut filter = CuckooFilter::new(n);
+        for i in 0..n {
+            assert!(filter.insert(&i));
+        }
+        let tries = 50 * n;
+        let false_positives = (n..n + tries).filter(|i| filter.contains(i)).count();
+        // About 8 in 65536 at full load, and it's not quite full
+        let rate = false_positives as f64 / tries as f64;
+        assert!(rate < 0.0003, "false positive rate {}", rate);
+    }
+}
@@ -0,0 +1,502 @@
+//! # Binary search tree map
+//!
+//! The plain, unbalanced binary search tree: every node's left subtree holds smaller keys and
+//! its right subtree larger ones. It's the baseline the balanced trees build on. All the
+//! operations here carry over to them unchanged; they only add bookkeeping to stop the tree
+//! from degenerating.
+//!
+//! ```text
+//!         8
+//!       /   \
+//!      3     10
+//!     / \      \
+//!    1   6      14
+//!       / \    /
+//!      4   7  13
+//! ```
+//!
+//! Nothing stops it from degenerating, though: insert keys in sorted order and every node
+//! only has a right child, so the "tree" is a linked list and every operation is O(n).
+//! Because of that, everything here walks the tree with loops rather than recursion, so a
+//! degenerate tree costs time but can't overflow the stack.
+//!
+//! The interesting operation is removal. A node with at most one child is simply replaced by
+//! that child. A node with two children is replaced by its *successor*, the smallest key in
+//! its right subtree, which has no left child and so is easy to take out.
+
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::fmt;
+use std::iter::FromIterator;
+use std::mem;
+
+pub struct Map<K, V> {
+    root: Link<K, V>,
+    len: usize,
+}
+
+type Link<K, V> = Option<Box<Node<K, V>>>;
+
+struct Node<K, V> {
+    key: K,
+    value: V,
+    left: Link<K, V>,
+    right: Link<K, V>,
+}
+
+impl<K, V> Map<K, V> {
+    /// Creates an empty Map.
+    pub fn new() -> Self {
+        Map { root: None, len: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    /// Returns the number of nodes on the longest path from the root, so zero when empty.
+    pub fn height(&self) -> usize {
+        let mut height = 0;
+        let mut level: Vec<&Node<K, V>> = self.root.as_deref().into_iter().collect();
+        while !level.is_empty() {
+            height += 1;
+            level = level
+                .iter()
+                .flat_map(|node| {
+                    node.left
+                        .as_deref()
+                        .into_iter()
+                        .chain(node.right.as_deref())
+                })
+                .collect();
+        }
+        height
+    }
+
+    /// Returns the entry with the smallest key.
+    pub fn min(&self) -> Option<(&K, &V)> {
+        let mut node = self.root.as_deref()?;
+        while let Some(left) = node.left.as_deref() {
+            node = left;
+        }
+        Some((&node.key, &node.value))
+    }
+
+    /// Returns the entry with the largest key.
+    pub fn max(&self) -> Option<(&K, &V)> {
+        let mut node = self.root.as_deref()?;
+        while let Some(right) = node.right.as_deref() {
+            node = right;
+        }
+        Some((&node.key, &node.value))
+    }
+
+    pub fn clear(&mut self) {
+        drop(mem::take(self));
+    }
+
+    /// In-order iteration, so keys come out sorted.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        let mut iter = Iter {
+            stack: Vec::new(),
+            remaining: self.len,
+        };
+        iter.push_left_spine(self.root.as_deref());
+        iter
+    }
+}
+
+impl<K: Ord, V> Map<K, V> {
+    /// Follows the search path for `key` and returns the link where it is, or where it
+    /// would go.
+    fn find_link<'a, Q>(link: &'a mut Link<K, V>, key: &Q) -> &'a mut Link<K, V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut link = link;
+        loop {
+            // Decide with a shared borrow first; the borrow checker won't let us keep the
+            // `&mut` to the node in one branch and return `link` in another
+            let ordering = match link {
+                Some(node) => key.cmp(node.key.borrow()),
+                None => Ordering::Equal,
+            };
+            if ordering == Ordering::Equal {
+                return link;
+            }
+            let node = link.as_mut().unwrap();
+            link = if ordering == Ordering::Less {
+                &mut node.left
+            } else {
+                &mut node.right
+            };
+        }
+    }
+
+    fn find<Q>(&self, key: &Q) -> Option<&Node<K, V>>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut link = self.root.as_deref();
+        while let Some(node) = link {
+            link = match key.cmp(node.key.borrow()) {
+                Ordering::Less => node.left.as_deref(),
+                Ordering::Greater => node.right.as_deref(),
+                Ordering::Equal => return Some(node),
+            };
+        }
+        None
+    }
+
+    /// Inserts a key-value pair, returning the old value if the key was already there.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let link = Self::find_link(&mut self.root, &key);
+        match link {
+            Some(node) => Some(mem::replace(&mut node.value, value)),
+            None => {
+                *link = Some(Box::new(Node {
+                    key,
+                    value,
+                    left: None,
+                    right: None,
+                }));
+                self.len += 1;
+                None
+            }
+        }
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.find(key).map(|node| &node.value)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        Self::find_link(&mut self.root, key)
+            .as_mut()
+            .map(|node| &mut node.value)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.find(key).is_some()
+    }
+
+    /// Removes a key, returning its value if it was there.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.remove_entry(key).map(|(_, v)| v)
+    }
+
+    /// Removes a key, returning the stored key and value if it was there.
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let link = Self::find_link(&mut self.root, key);
+        let mut node = link.take()?;
+
+        *link = match (node.left.take(), node.right.take()) {
+            (None, right) => right,
+            (left, None) => left,
+            (Some(left), Some(right)) => {
+                // Two children: the successor takes this node's place
+                let mut right = Some(right);
+                let mut successor = take_min(&mut right);
+                successor.left = Some(left);
+                successor.right = right;
+                Some(successor)
+            }
+        };
+        self.len -= 1;
+        Some((node.key, node.value))
+    }
+
+    /// Returns the entry with the largest key less than or equal to `key`.
+    pub fn floor<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut best = None;
+        let mut link = self.root.as_deref();
+        while let Some(node) = link {
+            match key.cmp(node.key.borrow()) {
+                Ordering::Less => link = node.left.as_deref(),
+                Ordering::Equal => return Some((&node.key, &node.value)),
+                Ordering::Greater => {
+                    // A candidate, but there may be a closer one to the right
+                    best = Some((&node.key, &node.value));
+                    link = node.right.as_deref();
+                }
+            }
+        }
+        best
+    }
+
+    /// Returns the entry with the smallest key greater than or equal to `key`.
+    pub fn ceiling<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut best = None;
+        let mut link = self.root.as_deref();
+        while let Some(node) = link {
+            match key.cmp(node.key.borrow()) {
+                Ordering::Greater => link = node.right.as_deref(),
+                Ordering::Equal => return Some((&node.key, &node.value)),
+                Ordering::Less => {
+                    best = Some((&node.key, &node.value));
+                    link = node.left.as_deref();
+                }
+            }
+        }
+        best
+    }
+}
+
+/// Unhooks the leftmost node under `link`, putting its right child in its place.
+fn take_min<K, V>(link: &mut Link<K, V>) -> Box<Node<K, V>> {
+    let mut link = link;
+    while link.as_ref().unwrap().left.is_some() {
+        link = &mut link.as_mut().unwrap().left;
+    }
+    let mut min = link.take().unwrap();
+    *link = min.right.take();
+    min
+}
+
+impl<K, V> Default for Map<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V> Drop for Map<K, V> {
+    fn drop(&mut self) {
+        // A degenerate tree is as deep as it is long; don't let the default recursive drop
+        // blow the stack on it
+        let mut stack: Vec<_> = self.root.take().into_iter().collect();
+        while let Some(mut node) = stack.pop() {
+            stack.extend(node.left.take());
+            stack.extend(node.right.take());
+        }
+    }
+}
+
+impl<K: Ord, V> FromIterator<(K, V)> for Map<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = Map::new();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K: Ord, V> Extend<(K, V)> for Map<K, V> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug> fmt::Debug for Map<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// In-order iterator. The stack holds the nodes whose left subtree we've started but who
+/// haven't been yielded yet.
+pub struct Iter<'a, K, V> {
+    stack: Vec<&'a Node<K, V>>,
+    remaining: usize,
+}
+
+impl<'a, K, V> Iter<'a, K, V> {
+    fn push_left_spine(&mut self, mut link: Option<&'a Node<K, V>>) {
+        while let Some(node) = link {
+            self.stack.push(node);
+            link = node.left.as_deref();
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        self.push_left_spine(node.right.as_deref());
+        self.remaining -= 1;
+        Some((&node.key, &node.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+
+impl<'a, K, V> IntoIterator for &'a Map<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Map;
+
+    fn keys(map: &Map<i32, i32>) -> Vec<i32> {
+        map.iter().map(|(k, _)| *k).collect()
+    }
+
+    #[test]
+    fn basics() {
+        let mut map = Map::new();
+
+        // Check empty map behaves right
+        assert!(map.is_empty());
+        assert_eq!(map.get(&1), None);
+        assert_eq!(map.remove(&1), None);
+        assert_eq!(map.min(), None);
+        assert_eq!(map.height(), 0);
+
+        // Populate map
+        for k in [8, 3, 10, 1, 6, 14, 4, 7, 13] {
+            assert_eq!(map.insert(k, k * 10), None);
+        }
+        assert_eq!(map.len(), 9);
+        assert_eq!(map.height(), 4);
+        assert_eq!(map.get(&6), Some(&60));
+        assert!(map.contains_key(&13));
+        assert!(!map.contains_key(&2));
+
+        // Replacing hands back the old value
+        assert_eq!(map.insert(6, 0), Some(60));
+        if let Some(value) = map.get_mut(&6) {
+            *value = 66;
+        }
+        assert_eq!(map.get(&6), Some(&66));
+        assert_eq!(map.len(), 9);
+
+        assert_eq!(keys(&map), vec![1, 3, 4, 6, 7, 8, 10, 13, 14]);
+        assert_eq!(map.iter().len(), 9);
+    }
+
+    #[test]
+    fn remove_every_shape() {
+        let mut map: Map<_, _> = [8, 3, 10, 1, 6, 14, 4, 7, 13]
+            .iter()
+            .map(|&k| (k, k))
+            .collect();
+
+        // A leaf
+        assert_eq!(map.remove(&4), Some(4));
+        assert_eq!(keys(&map), vec![1, 3, 6, 7, 8, 10, 13, 14]);
+
+        // One child (10 only has 14 on its right)
+        assert_eq!(map.remove(&10), Some(10));
+        assert_eq!(keys(&map), vec![1, 3, 6, 7, 8, 13, 14]);
+
+        // Two children, whose successor (6) has a right child of its own
+        assert_eq!(map.remove_entry(&3), Some((3, 3)));
+        assert_eq!(keys(&map), vec![1, 6, 7, 8, 13, 14]);
+
+        // The root, with two children
+        assert_eq!(map.remove(&8), Some(8));
+        assert_eq!(keys(&map), vec![1, 6, 7, 13, 14]);
+
+        for k in [1, 6, 7, 13, 14] {
+            assert_eq!(map.remove(&k), Some(k));
+        }
+        assert!(map.is_empty());
+        assert_eq!(map.len(), 0);
+    }
+
+    #[test]
+    fn min_max_floor_ceiling() {
+        let map: Map<_, _> = (0..10).map(|i| (i * 10, i)).collect();
+        assert_eq!(map.min(), Some((&0, &0)));
+        assert_eq!(map.max(), Some((&90, &9)));
+
+        assert_eq!(map.floor(&35), Some((&30, &3)));
+        assert_eq!(map.floor(&30), Some((&30, &3)));
+        assert_eq!(map.floor(&-1), None);
+        assert_eq!(map.floor(&1000), Some((&90, &9)));
+
+        assert_eq!(map.ceiling(&35), Some((&40, &4)));
+        assert_eq!(map.ceiling(&40), Some((&40, &4)));
+        assert_eq!(map.ceiling(&91), None);
+        assert_eq!(map.ceiling(&-5), Some((&0, &0)));
+    }
+
+    #[test]
+    fn borrowed_keys() {
+        let mut map = Map::new();
+        map.insert("b".to_string(), 2);
+        map.insert("a".to_string(), 1);
+        assert_eq!(map.get("a"), Some(&1));
+        assert_eq!(map.floor("az"), Some((&"a".to_string(), &1)));
+        assert_eq!(map.remove("b"), Some(2));
+        assert_eq!(format!("{:?}", map), r#"{"a": 1}"#);
+    }
+
+    #[test]
+    fn degenerate_tree() {
+        // Sorted inserts make a list; it should still work and drop without recursion
+        let n = if cfg!(miri) { 200 } else { 10_000 };
+        let mut map: Map<_, _> = (0..n).map(|i| (i, ())).collect();
+        assert_eq!(map.height(), n as usize);
+        assert_eq!(map.max(), Some((&(n - 1), &())));
+        assert_eq!(map.remove(&(n / 2)), Some(()));
+        assert_eq!(map.len(), n as usize - 1);
+        map.clear();
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn against_btreemap() {
+        use std::collections::BTreeMap;
+        let mut map = Map::new();
+        let mut model = BTreeMap::new();
+        let mut x: u32 = 4242;
+        for _ in 0..5_000 {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let key = (x >> 16) % 500;
+            if x.is_multiple_of(3) {
+                assert_eq!(map.remove(&key), model.remove(&key));
+            } else {
+                assert_eq!(map.insert(key, x), model.insert(key, x));
+            }
+        }
+        assert_eq!(map.len(), model.len());
+        assert!(map.iter().eq(model.iter()));
+    }
+}
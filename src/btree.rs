@@ -0,0 +1,635 @@
+//! # B-tree map
+//!
+//! The structure behind `std::collections::BTreeMap`, written out from scratch. A B-tree
+//! node holds many sorted keys instead of one, and has one more child than it has keys.
+//! The keys split the range covered by the node, and each child covers one of the gaps:
+//!
+//! ```text
+//!                      [ 10 | 20 ]
+//!                     /     |     \
+//!          [ 2 | 5 | 7 ] [ 13 | 17 ] [ 24 | 30 | 31 ]
+//! ```
+//!
+//! The tree is parametrized by its *minimum degree* `t` (in CLRS's terms). Every node but
+//! the root holds between `t - 1` and `2t - 1` keys, and all leaves sit at the same depth.
+//! Wide nodes keep the tree very shallow. They also put many keys per cache line, which is
+//! why std uses a B-tree (with `t = 6`) rather than a binary tree.
+//!
+//! Both insertion and removal are done in a single pass down the tree, fixing nodes up
+//! *before* descending into them:
+//!
+//! - **Insert** splits any full node on the way down. When the new key reaches a leaf,
+//!   that leaf is guaranteed to have room. The median of a split node moves up into the
+//!   parent, and the root splitting is the only way the tree gets taller.
+//! - **Remove** makes sure any child it descends into has at least `t` keys, one more than
+//!   the minimum, so that taking a key out can't leave it underfull. Such a child borrows
+//!   a key from a sibling through the parent, or else merges with a sibling. A key found
+//!   in an internal node is replaced by its predecessor or successor from a leaf. The root
+//!   emptying out is the only way the tree gets shorter.
+
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::fmt;
+use std::iter::FromIterator;
+use std::mem;
+
+/// The minimum degree std's `BTreeMap` uses, too.
+const DEFAULT_MIN_DEGREE: usize = 6;
+
+pub struct Map<K, V> {
+    root: Node<K, V>,
+    len: usize,
+    min_degree: usize,
+}
+
+struct Node<K, V> {
+    keys: Vec<K>,
+    values: Vec<V>,
+    // Empty for leaves, otherwise always `keys.len() + 1` long
+    children: Vec<Node<K, V>>,
+}
+
+impl<K, V> Node<K, V> {
+    fn new() -> Self {
+        Node {
+            keys: Vec::new(),
+            values: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    fn is_leaf(&self) -> bool {
+        self.children.is_empty()
+    }
+
+    /// Splits the full child `i` around its median, which moves up into this node.
+    fn split_child(&mut self, i: usize, t: usize) {
+        let child = &mut self.children[i];
+        let right = Node {
+            keys: child.keys.split_off(t),
+            values: child.values.split_off(t),
+            children: if child.is_leaf() {
+                Vec::new()
+            } else {
+                child.children.split_off(t)
+            },
+        };
+        let key = child.keys.pop().unwrap();
+        let value = child.values.pop().unwrap();
+        self.keys.insert(i, key);
+        self.values.insert(i, value);
+        self.children.insert(i + 1, right);
+    }
+
+    /// Merges child `i + 1` and the key between them into child `i`.
+    fn merge_children(&mut self, i: usize) {
+        let right = self.children.remove(i + 1);
+        let key = self.keys.remove(i);
+        let value = self.values.remove(i);
+        let left = &mut self.children[i];
+        left.keys.push(key);
+        left.values.push(value);
+        left.keys.extend(right.keys);
+        left.values.extend(right.values);
+        left.children.extend(right.children);
+    }
+
+    /// Makes sure child `i` has at least `t` keys before we descend into it, by borrowing
+    /// from a sibling or merging with one. Returns the index of the child that now covers
+    /// the same range, which moves left after merging with the left sibling.
+    fn fill_child(&mut self, i: usize, t: usize) -> usize {
+        if self.children[i].keys.len() >= t {
+            return i;
+        }
+
+        if i > 0 && self.children[i - 1].keys.len() >= t {
+            // Rotate right: the separator comes down, the left sibling's last key goes up
+            let (left, right) = self.children.split_at_mut(i);
+            let (left, child) = (&mut left[i - 1], &mut right[0]);
+            let key = mem::replace(&mut self.keys[i - 1], left.keys.pop().unwrap());
+            let value = mem::replace(&mut self.values[i - 1], left.values.pop().unwrap());
+            child.keys.insert(0, key);
+            child.values.insert(0, value);
+            if let Some(grandchild) = left.children.pop() {
+                child.children.insert(0, grandchild);
+            }
+            i
+        } else if i + 1 < self.children.len() && self.children[i + 1].keys.len() >= t {
+            // Rotate left: the mirror image
+            let (left, right) = self.children.split_at_mut(i + 1);
+            let (child, right) = (&mut left[i], &mut right[0]);
+            let key = mem::replace(&mut self.keys[i], right.keys.remove(0));
+            let value = mem::replace(&mut self.values[i], right.values.remove(0));
+            child.keys.push(key);
+            child.values.push(value);
+            if !right.is_leaf() {
+                child.children.push(right.children.remove(0));
+            }
+            i
+        } else if i + 1 < self.children.len() {
+            self.merge_children(i);
+            i
+        } else {
+            self.merge_children(i - 1);
+            i - 1
+        }
+    }
+
+    fn remove<Q>(&mut self, key: &Q, t: usize) -> Option<(K, V)>
+    where
+        K: Borrow<Q> + Ord,
+        Q: Ord + ?Sized,
+    {
+        match self.keys.binary_search_by(|k| k.borrow().cmp(key)) {
+            Ok(i) if self.is_leaf() => Some((self.keys.remove(i), self.values.remove(i))),
+            Ok(i) => {
+                // Swap in the predecessor or successor, whichever side can spare a key
+                if self.children[i].keys.len() >= t {
+                    let (k, v) = self.children[i].remove_max(t);
+                    Some(self.replace(i, k, v))
+                } else if self.children[i + 1].keys.len() >= t {
+                    let (k, v) = self.children[i + 1].remove_min(t);
+                    Some(self.replace(i, k, v))
+                } else {
+                    // Neither can: merge them around the key and remove it from there
+                    self.merge_children(i);
+                    self.children[i].remove(key, t)
+                }
+            }
+            Err(_) if self.is_leaf() => None,
+            Err(i) => {
+                let i = self.fill_child(i, t);
+                self.children[i].remove(key, t)
+            }
+        }
+    }
+
+    fn replace(&mut self, i: usize, key: K, value: V) -> (K, V) {
+        (
+            mem::replace(&mut self.keys[i], key),
+            mem::replace(&mut self.values[i], value),
+        )
+    }
+
+    fn remove_max(&mut self, t: usize) -> (K, V) {
+        if self.is_leaf() {
+            return (self.keys.pop().unwrap(), self.values.pop().unwrap());
+        }
+        let i = self.fill_child(self.children.len() - 1, t);
+        self.children[i].remove_max(t)
+    }
+
+    fn remove_min(&mut self, t: usize) -> (K, V) {
+        if self.is_leaf() {
+            return (self.keys.remove(0), self.values.remove(0));
+        }
+        let i = self.fill_child(0, t);
+        self.children[i].remove_min(t)
+    }
+}
+
+impl<K, V> Map<K, V> {
+    /// Creates an empty Map.
+    pub fn new() -> Self {
+        Self::with_min_degree(DEFAULT_MIN_DEGREE)
+    }
+
+    /// Creates an empty map whose nodes hold between `t - 1` and `2t - 1` keys, so have up
+    /// to `2t` children.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `t` is less than 2.
+    pub fn with_min_degree(t: usize) -> Self {
+        assert!(t >= 2, "a B-tree needs a minimum degree of at least 2");
+        Map {
+            root: Node::new(),
+            len: 0,
+            min_degree: t,
+        }
+    }
+
+    pub fn min_degree(&self) -> usize {
+        self.min_degree
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of levels, so zero when empty. All leaves are at this depth.
+    pub fn height(&self) -> usize {
+        if self.is_empty() {
+            return 0;
+        }
+        let mut height = 1;
+        let mut node = &self.root;
+        while let Some(child) = node.children.first() {
+            height += 1;
+            node = child;
+        }
+        height
+    }
+
+    pub fn clear(&mut self) {
+        self.root = Node::new();
+        self.len = 0;
+    }
+
+    /// Returns the entry with the smallest key.
+    pub fn first_key_value(&self) -> Option<(&K, &V)> {
+        let mut node = &self.root;
+        while let Some(child) = node.children.first() {
+            node = child;
+        }
+        Some((node.keys.first()?, node.values.first()?))
+    }
+
+    /// Returns the entry with the largest key.
+    pub fn last_key_value(&self) -> Option<(&K, &V)> {
+        let mut node = &self.root;
+        while let Some(child) = node.children.last() {
+            node = child;
+        }
+        Some((node.keys.last()?, node.values.last()?))
+    }
+
+    /// In-order iteration, so keys come out sorted.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        let mut iter = Iter {
+            stack: Vec::new(),
+            remaining: self.len,
+        };
+        iter.push_leftmost(&self.root);
+        iter
+    }
+}
+
+impl<K: Ord, V> Map<K, V> {
+    /// Returns the node and position holding `key`.
+    fn find<Q>(&self, key: &Q) -> Option<(&Node<K, V>, usize)>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut node = &self.root;
+        loop {
+            match node.keys.binary_search_by(|k| k.borrow().cmp(key)) {
+                Ok(i) => return Some((node, i)),
+                Err(i) => node = node.children.get(i)?,
+            }
+        }
+    }
+
+    /// Inserts a key-value pair, returning the old value if the key was already there.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let t = self.min_degree;
+        if self.root.keys.len() == 2 * t - 1 {
+            // Split the full root, which is how the tree grows
+            let old_root = mem::replace(&mut self.root, Node::new());
+            self.root.children.push(old_root);
+            self.root.split_child(0, t);
+        }
+
+        let mut node = &mut self.root;
+        loop {
+            let mut i = match node.keys.binary_search(&key) {
+                Ok(i) => return Some(mem::replace(&mut node.values[i], value)),
+                Err(i) => i,
+            };
+            if node.is_leaf() {
+                node.keys.insert(i, key);
+                node.values.insert(i, value);
+                self.len += 1;
+                return None;
+            }
+            if node.children[i].keys.len() == 2 * t - 1 {
+                node.split_child(i, t);
+                // The median that came up might be the key, or send it to the new sibling
+                match key.cmp(&node.keys[i]) {
+                    Ordering::Less => {}
+                    Ordering::Equal => {
+                        return Some(mem::replace(&mut node.values[i], value));
+                    }
+                    Ordering::Greater => i += 1,
+                }
+            }
+            node = &mut node.children[i];
+        }
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.find(key).map(|(node, i)| &node.values[i])
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut node = &mut self.root;
+        loop {
+            match node.keys.binary_search_by(|k| k.borrow().cmp(key)) {
+                Ok(i) => return Some(&mut node.values[i]),
+                Err(i) => node = node.children.get_mut(i)?,
+            }
+        }
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.find(key).is_some()
+    }
+
+    /// Removes a key, returning its value if it was there.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.remove_entry(key).map(|(_, v)| v)
+    }
+
+    /// Removes a key, returning the stored key and value if it was there.
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let removed = self.root.remove(key, self.min_degree);
+        if self.root.keys.is_empty() && !self.root.is_leaf() {
+            // The root's last key was merged down, which is how the tree shrinks
+            self.root = self.root.children.pop().unwrap();
+        }
+        if removed.is_some() {
+            self.len -= 1;
+        }
+        removed
+    }
+}
+
+impl<K, V> Default for Map<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord, V> FromIterator<(K, V)> for Map<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = Map::new();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K: Ord, V> Extend<(K, V)> for Map<K, V> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug> fmt::Debug for Map<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// In-order iterator. The stack holds the path from the root, along with the position of
+/// the next key to yield in each node.
+pub struct Iter<'a, K, V> {
+    stack: Vec<(&'a Node<K, V>, usize)>,
+    remaining: usize,
+}
+
+impl<'a, K, V> Iter<'a, K, V> {
+    fn push_leftmost(&mut self, mut node: &'a Node<K, V>) {
+        loop {
+            self.stack.push((node, 0));
+            match node.children.first() {
+                Some(child) => node = child,
+                None => break,
+            }
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (node, i) = self.stack.last_mut()?;
+            let (node, i) = (*node, mem::replace(i, *i + 1));
+            if i == node.keys.len() {
+                self.stack.pop();
+                continue;
+            }
+            // Everything in the child after this key comes next
+            if let Some(child) = node.children.get(i + 1) {
+                self.push_leftmost(child);
+            }
+            self.remaining -= 1;
+            return Some((&node.keys[i], &node.values[i]));
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+
+impl<'a, K, V> IntoIterator for &'a Map<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Map, Node};
+
+    // Checks key counts, ordering and that every leaf is at the same depth
+    fn check<V>(map: &Map<i32, V>) {
+        fn walk<V>(
+            node: &Node<i32, V>,
+            t: usize,
+            is_root: bool,
+            depth: usize,
+            leaf_depth: &mut Option<usize>,
+        ) -> usize {
+            assert!(node.keys.len() < 2 * t);
+            assert!(is_root || node.keys.len() >= t - 1, "underfull node");
+            assert_eq!(node.keys.len(), node.values.len());
+            assert!(node.keys.windows(2).all(|w| w[0] < w[1]));
+            if node.is_leaf() {
+                assert_eq!(*leaf_depth.get_or_insert(depth), depth, "uneven leaves");
+                return node.keys.len();
+            }
+            assert_eq!(node.children.len(), node.keys.len() + 1);
+            for (i, child) in node.children.iter().enumerate() {
+                if i > 0 {
+                    assert!(child.keys[0] > node.keys[i - 1]);
+                }
+                if i < node.keys.len() {
+                    assert!(*child.keys.last().unwrap() < node.keys[i]);
+                }
+            }
+            node.keys.len()
+                + node
+                    .children
+                    .iter()
+                    .map(|child| walk(child, t, false, depth + 1, leaf_depth))
+                    .sum::<usize>()
+        }
+        let count = walk(&map.root, map.min_degree, true, 1, &mut None);
+        assert_eq!(count, map.len());
+    }
+
+    fn keys<V>(map: &Map<i32, V>) -> Vec<i32> {
+        map.iter().map(|(k, _)| *k).collect()
+    }
+
+    #[test]
+    fn basics() {
+        let mut map = Map::new();
+
+        // Check empty map behaves right
+        assert!(map.is_empty());
+        assert_eq!(map.get(&1), None);
+        assert_eq!(map.remove(&1), None);
+        assert_eq!(map.first_key_value(), None);
+        assert_eq!(map.height(), 0);
+        assert_eq!(map.min_degree(), 6);
+
+        // Populate map
+        for k in [5, 2, 8, 1, 9, 3] {
+            assert_eq!(map.insert(k, k * 10), None);
+        }
+        assert_eq!(map.len(), 6);
+        assert_eq!(map.insert(3, 0), Some(30));
+        assert_eq!(map.len(), 6);
+        if let Some(value) = map.get_mut(&3) {
+            *value = 33;
+        }
+        assert_eq!(map.get(&3), Some(&33));
+        assert!(map.contains_key(&9));
+        assert!(!map.contains_key(&4));
+        assert_eq!(map.first_key_value(), Some((&1, &10)));
+        assert_eq!(map.last_key_value(), Some((&9, &90)));
+
+        assert_eq!(map.remove(&5), Some(50));
+        assert_eq!(map.remove_entry(&1), Some((1, 10)));
+        assert_eq!(keys(&map), vec![2, 3, 8, 9]);
+        check(&map);
+    }
+
+    #[test]
+    fn splits_grow_the_root() {
+        let mut map = Map::with_min_degree(2);
+        // A node holds at most 3 keys, so the fourth insert splits the root
+        for k in 1..=3 {
+            map.insert(k, ());
+        }
+        assert_eq!(map.height(), 1);
+        map.insert(4, ());
+        assert_eq!(map.height(), 2);
+        assert_eq!(map.root.keys, vec![2]);
+        check(&map);
+
+        for k in 5..=100 {
+            map.insert(k, ());
+            check(&map);
+        }
+        assert_eq!(keys(&map), (1..=100).collect::<Vec<_>>());
+        assert_eq!(map.iter().len(), 100);
+        // With at least 2 children per node, and at most 4
+        assert!((4..=7).contains(&map.height()));
+    }
+
+    #[test]
+    fn removals_borrow_and_merge() {
+        let mut map = Map::with_min_degree(2);
+        map.extend((0..50).map(|i| (i, i)));
+        check(&map);
+
+        // Internal keys, leaf keys, missing keys: every path through removal
+        for k in [25, 0, 49, 24, 26, 100, 12, 37] {
+            assert_eq!(map.remove(&k), if k < 50 { Some(k) } else { None });
+            check(&map);
+        }
+        let height = map.height();
+        for k in 0..50 {
+            map.remove(&k);
+            check(&map);
+        }
+        assert!(map.is_empty());
+        assert_eq!(map.height(), 0);
+        assert!(height > 1);
+        assert_eq!(map.root.children.len(), 0);
+    }
+
+    #[test]
+    fn wide_nodes_stay_shallow() {
+        let n = if cfg!(miri) { 1_000 } else { 100_000 };
+        let mut map = Map::with_min_degree(32);
+        map.extend((0..n).map(|i| (i, ())));
+        check(&map);
+        assert!(map.height() <= 4);
+        assert_eq!(map.iter().count(), n as usize);
+    }
+
+    #[test]
+    fn borrowed_keys() {
+        let mut map = Map::new();
+        map.insert("b".to_string(), 2);
+        map.insert("a".to_string(), 1);
+        assert_eq!(map.get("a"), Some(&1));
+        assert_eq!(map.remove("b"), Some(2));
+        assert_eq!(format!("{:?}", map), r#"{"a": 1}"#);
+    }
+
+    #[test]
+    #[should_panic(expected = "at least 2")]
+    fn degree_one_is_rejected() {
+        Map::<i32, ()>::with_min_degree(1);
+    }
+
+    #[test]
+    fn against_btreemap() {
+        use std::collections::BTreeMap;
+        for t in 2..=5 {
+            let mut map = Map::with_min_degree(t);
+            let mut model = BTreeMap::new();
+            let mut x: u32 = t as u32;
+            for _ in 0..3_000 {
+                x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                let key = ((x >> 16) % 300) as i32;
+                if x.is_multiple_of(3) {
+                    assert_eq!(map.remove(&key), model.remove(&key));
+                } else {
+                    assert_eq!(map.insert(key, x), model.insert(key, x));
+                }
+            }
+            check(&map);
+            assert!(map.iter().eq(model.iter()));
+        }
+    }
+}
@@ -0,0 +1,753 @@
+//! # Caches
+//!
+//! [`LruCache`] is a map of bounded size that, when full, evicts the entry used least
+//! recently. It's two
+//! structures over the same entries. A [`LinkedList`] keeps them in order of use, most
+//! recent at the front, and a `HashMap` points each key straight at its node:
+//!
+//! ```text
+//!   map:   "b" ──┐      "d" ──┐      "a" ──┐
+//!                v            v            v
+//!   list:  front ("b", 2) <-> ("d", 4) <-> ("a", 1) back   <- evicted next
+//! ```
+//!
+//! A lookup finds the node through the map and moves it to the front, and an insert into a
+//! full cache drops the node at the back and its key from the map. Because the list is
+//! doubly linked, a node can be unlinked from the middle without a walk, so `get`, `put`
+//! and eviction are all O(1). This is the job a doubly-linked list is best at.
+//!
+//! Every key is stored twice, once in the map and once in its node, so keys must be
+//! `Clone`. The nodes are the list's own, reached through raw pointers, so run the tests
+//! under Miri too:
+//!
+//! ```text
+//! cargo +nightly miri test cache
+//! ```
+//!
+//! ## Adaptive replacement
+//!
+//! LRU has one well-known weakness: a single scan over more keys than fit flushes out
+//! everything, however often it was used before. [`AdaptiveCache`] is ARC (Megiddo and
+//! Modha, 2003), which keeps resident entries in two LRU lists. `recent` holds keys seen
+//! once lately and `frequent` holds keys seen at least twice, so a scan only churns
+//! `recent`. Each list also has a *ghost* list of keys it evicted recently, without their
+//! values:
+//!
+//! ```text
+//!   recent ghosts  <-  recent   |   frequent  ->  frequent ghosts
+//!                     <-- target -->
+//! ```
+//!
+//! The split between the two lists is a `target` size for `recent` that adapts with the
+//! workload. A miss on a recent ghost means `recent` was too small, so the target grows.
+//! A miss on a frequent ghost shrinks it. Both lists together hold at most `capacity`
+//! entries, and the ghosts at most as many again.
+//!
+//! Both caches count hits and misses in `get`, so the two can be run over the same trace
+//! and their [`Stats`] compared.
+
+use crate::linked_list::{LinkedList, Node};
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::ptr::NonNull;
+
+/// Hit and miss counts of a cache's lookups.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl Stats {
+    pub fn lookups(&self) -> u64 {
+        self.hits + self.misses
+    }
+
+    /// Fraction of lookups that hit, or 0 if there were none.
+    pub fn hit_rate(&self) -> f64 {
+        if self.lookups() == 0 {
+            0.0
+        } else {
+            self.hits as f64 / self.lookups() as f64
+        }
+    }
+
+    fn record(&mut self, hit: bool) {
+        if hit {
+            self.hits += 1;
+        } else {
+            self.misses += 1;
+        }
+    }
+}
+
+pub struct LruCache<K, V> {
+    map: HashMap<K, NonNull<Node<(K, V)>>>,
+    // Most recently used at the front
+    order: LinkedList<(K, V)>,
+    capacity: usize,
+    stats: Stats,
+}
+
+// The pointers are into `order`, which the cache owns outright
+unsafe impl<K: Send, V: Send> Send for LruCache<K, V> {}
+unsafe impl<K: Sync, V: Sync> Sync for LruCache<K, V> {}
+
+impl<K: Hash + Eq + Clone, V> LruCache<K, V> {
+    /// Creates an empty LruCache holding at most `capacity`
+    /// entries.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "capacity must be at least 1");
+        LruCache {
+            map: HashMap::with_capacity(capacity),
+            order: LinkedList::new(),
+            capacity,
+            stats: Stats::default(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn clear(&mut self) {
+        self.map.clear();
+        self.order.clear();
+    }
+
+    /// Hits and misses of `get` and `get_mut` so far.
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = Stats::default();
+    }
+
+    /// Inserts an entry as the most recently used. Returns the entry it pushed out: the
+    /// old value under the same key, or else the least recently used entry if the cache
+    /// was full.
+    pub fn put(&mut self, key: K, value: V) -> Option<(K, V)> {
+        if let Some(&node) = self.map.get(&key) {
+            unsafe {
+                self.order.move_to_front(node);
+                let old = std::mem::replace(&mut (*node.as_ptr()).elem.1, value);
+                return Some((key, old));
+            }
+        }
+        let evicted = if self.map.len() == self.capacity {
+            self.pop_lru()
+        } else {
+            None
+        };
+        let node = self.order.push_front_node((key.clone(), value));
+        self.map.insert(key, node);
+        evicted
+    }
+
+    /// Returns the value under `key` and marks it the most recently used.
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        Q: Hash + Eq + ?Sized,
+        K: Borrow<Q>,
+    {
+        self.get_mut(key).map(|value| &*value)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        Q: Hash + Eq + ?Sized,
+        K: Borrow<Q>,
+    {
+        let node = self.map.get(key).copied();
+        self.stats.record(node.is_some());
+        let node = node?;
+        unsafe {
+            self.order.move_to_front(node);
+            Some(&mut (*node.as_ptr()).elem.1)
+        }
+    }
+
+    /// Returns the value under `key` without counting it as a use.
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+    where
+        Q: Hash + Eq + ?Sized,
+        K: Borrow<Q>,
+    {
+        let node = self.map.get(key)?;
+        unsafe { Some(&(*node.as_ptr()).elem.1) }
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        Q: Hash + Eq + ?Sized,
+        K: Borrow<Q>,
+    {
+        self.map.contains_key(key)
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        Q: Hash + Eq + ?Sized,
+        K: Borrow<Q>,
+    {
+        let node = self.map.remove(key)?;
+        unsafe { Some(self.order.unlink(node).1) }
+    }
+
+    /// Returns the entry that would be evicted next.
+    pub fn peek_lru(&self) -> Option<(&K, &V)> {
+        self.order.back().map(|(key, value)| (key, value))
+    }
+
+    /// Removes and returns the least recently used entry.
+    pub fn pop_lru(&mut self) -> Option<(K, V)> {
+        let (key, value) = self.order.pop_back()?;
+        self.map.remove(&key);
+        Some((key, value))
+    }
+
+    /// Iterates over the entries from most to least recently used, without counting any
+    /// of them as a use.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> + '_ {
+        self.order.iter().map(|(key, value)| (key, value))
+    }
+}
+
+impl<K: Hash + Eq + Clone + fmt::Debug, V: fmt::Debug> fmt::Debug for LruCache<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// Which of the four lists a key is in, and its node there.
+enum Slot<K, V> {
+    Recent(NonNull<Node<(K, V)>>),
+    Frequent(NonNull<Node<(K, V)>>),
+    RecentGhost(NonNull<Node<K>>),
+    FrequentGhost(NonNull<Node<K>>),
+}
+
+impl<K, V> Clone for Slot<K, V> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<K, V> Copy for Slot<K, V> {}
+
+pub struct AdaptiveCache<K, V> {
+    map: HashMap<K, Slot<K, V>>,
+    // Each list has its most recently used at the front
+    recent: LinkedList<(K, V)>,
+    frequent: LinkedList<(K, V)>,
+    recent_ghosts: LinkedList<K>,
+    frequent_ghosts: LinkedList<K>,
+    // How many of the resident entries `recent` should hold
+    target: usize,
+    capacity: usize,
+    stats: Stats,
+}
+
+// The pointers are into the lists, which the cache owns outright
+unsafe impl<K: Send, V: Send> Send for AdaptiveCache<K, V> {}
+unsafe impl<K: Sync, V: Sync> Sync for AdaptiveCache<K, V> {}
+
+impl<K: Hash + Eq + Clone, V> AdaptiveCache<K, V> {
+    /// Creates an empty AdaptiveCache holding at most
+    /// `capacity` entries.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "capacity must be at least 1");
+        AdaptiveCache {
+            map: HashMap::with_capacity(2 * capacity),
+            recent: LinkedList::new(),
+            frequent: LinkedList::new(),
+            recent_ghosts: LinkedList::new(),
+            frequent_ghosts: LinkedList::new(),
+            target: 0,
+            capacity,
+            stats: Stats::default(),
+        }
+    }
+
+    /// Number of entries held, not counting ghosts.
+    pub fn len(&self) -> usize {
+        self.recent.len() + self.frequent.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Empties the cache and forgets its ghosts and what it has learned.
+    pub fn clear(&mut self) {
+        self.map.clear();
+        self.recent.clear();
+        self.frequent.clear();
+        self.recent_ghosts.clear();
+        self.frequent_ghosts.clear();
+        self.target = 0;
+    }
+
+    /// Hits and misses of `get` and `get_mut` so far.
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = Stats::default();
+    }
+
+    /// Inserts an entry. Returns the entry it pushed out: the old value under the same
+    /// key, or else the entry evicted to make room, if any.
+    pub fn put(&mut self, key: K, value: V) -> Option<(K, V)> {
+        let slot = self.map.get(&key).copied();
+        unsafe {
+            match slot {
+                Some(Slot::Recent(node)) | Some(Slot::Frequent(node)) => {
+                    let old = std::mem::replace(&mut (*node.as_ptr()).elem.1, value);
+                    self.promote(node);
+                    Some((key, old))
+                }
+                Some(Slot::RecentGhost(ghost)) => {
+                    // Evicted from `recent` too soon, so give it more room
+                    let step = (self.frequent_ghosts.len() / self.recent_ghosts.len()).max(1);
+                    self.target = (self.target + step).min(self.capacity);
+                    self.recent_ghosts.unlink(ghost);
+                    let evicted = self.replace(false);
+                    self.insert_frequent(key, value);
+                    evicted
+                }
+                Some(Slot::FrequentGhost(ghost)) => {
+                    let step = (self.recent_ghosts.len() / self.frequent_ghosts.len()).max(1);
+                    self.target = self.target.saturating_sub(step);
+                    self.frequent_ghosts.unlink(ghost);
+                    let evicted = self.replace(true);
+                    self.insert_frequent(key, value);
+                    evicted
+                }
+                None => {
+                    let evicted = self.make_room();
+                    let node = self.recent.push_front_node((key.clone(), value));
+                    self.map.insert(key, Slot::Recent(node));
+                    evicted
+                }
+            }
+        }
+    }
+
+    /// Makes room for a key seen for the first time, keeping `recent` and its ghosts to
+    /// `capacity` between them, and all four lists to twice that.
+    fn make_room(&mut self) -> Option<(K, V)> {
+        let (recent, frequent) = (self.recent.len(), self.frequent.len());
+        let ghosts = self.recent_ghosts.len() + self.frequent_ghosts.len();
+        if recent + self.recent_ghosts.len() >= self.capacity {
+            if recent < self.capacity {
+                self.forget_oldest(false);
+                self.replace(false)
+            } else {
+                // No ghosts to spare, so this one leaves without one
+                let (key, value) = self.recent.pop_back()?;
+                self.map.remove(&key);
+                Some((key, value))
+            }
+        } else if recent + frequent + ghosts >= self.capacity {
+            if recent + frequent + ghosts >= 2 * self.capacity {
+                self.forget_oldest(true);
+            }
+            self.replace(false)
+        } else {
+            None
+        }
+    }
+
+    /// Drops the oldest ghost of `recent`, or of `frequent`.
+    fn forget_oldest(&mut self, frequent: bool) {
+        let ghosts = if frequent {
+            &mut self.frequent_ghosts
+        } else {
+            &mut self.recent_ghosts
+        };
+        if let Some(key) = ghosts.pop_back() {
+            self.map.remove(&key);
+        }
+    }
+
+    /// If the cache is full, evicts the least recently used entry of `recent` or
+    /// `frequent`, whichever is over its share, leaving a ghost behind.
+    fn replace(&mut self, hit_frequent_ghost: bool) -> Option<(K, V)> {
+        let recent = self.recent.len();
+        if recent + self.frequent.len() < self.capacity {
+            return None;
+        }
+        let over = recent > self.target || (hit_frequent_ghost && recent == self.target);
+        let from_recent = recent > 0 && (over || self.frequent.is_empty());
+        let (key, value) = if from_recent {
+            self.recent.pop_back()?
+        } else {
+            self.frequent.pop_back()?
+        };
+        let slot = if from_recent {
+            Slot::RecentGhost(self.recent_ghosts.push_front_node(key.clone()))
+        } else {
+            Slot::FrequentGhost(self.frequent_ghosts.push_front_node(key.clone()))
+        };
+        self.map.insert(key.clone(), slot);
+        Some((key, value))
+    }
+
+    fn insert_frequent(&mut self, key: K, value: V) {
+        let node = self.frequent.push_front_node((key.clone(), value));
+        self.map.insert(key, Slot::Frequent(node));
+    }
+
+    /// Moves a resident entry to the front of `frequent`, and returns its new node.
+    ///
+    /// # Safety
+    ///
+    /// `node` must be the live node of a resident entry.
+    unsafe fn promote(&mut self, node: NonNull<Node<(K, V)>>) -> NonNull<Node<(K, V)>> {
+        let slot = self.map.get_mut(&(*node.as_ptr()).elem.0).unwrap();
+        match *slot {
+            Slot::Frequent(_) => {
+                self.frequent.move_to_front(node);
+                node
+            }
+            _ => {
+                let entry = self.recent.unlink(node);
+                let moved = self.frequent.push_front_node(entry);
+                *slot = Slot::Frequent(moved);
+                moved
+            }
+        }
+    }
+
+    /// Returns the value under `key` and counts it as a use, which moves it into
+    /// `frequent`.
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        Q: Hash + Eq + ?Sized,
+        K: Borrow<Q>,
+    {
+        self.get_mut(key).map(|value| &*value)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        Q: Hash + Eq + ?Sized,
+        K: Borrow<Q>,
+    {
+        let node = match self.map.get(key) {
+            Some(Slot::Recent(node)) | Some(Slot::Frequent(node)) => Some(*node),
+            _ => None,
+        };
+        self.stats.record(node.is_some());
+        unsafe {
+            let node = self.promote(node?);
+            Some(&mut (*node.as_ptr()).elem.1)
+        }
+    }
+
+    /// Returns the value under `key` without counting it as a use.
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+    where
+        Q: Hash + Eq + ?Sized,
+        K: Borrow<Q>,
+    {
+        match self.map.get(key)? {
+            Slot::Recent(node) | Slot::Frequent(node) => unsafe { Some(&(*node.as_ptr()).elem.1) },
+            _ => None,
+        }
+    }
+
+    /// Whether `key` is held. Ghosts don't count.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        Q: Hash + Eq + ?Sized,
+        K: Borrow<Q>,
+    {
+        self.peek(key).is_some()
+    }
+
+    /// Removes an entry, without leaving a ghost.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        Q: Hash + Eq + ?Sized,
+        K: Borrow<Q>,
+    {
+        let entry = match *self.map.get(key)? {
+            Slot::Recent(node) => unsafe { self.recent.unlink(node) },
+            Slot::Frequent(node) => unsafe { self.frequent.unlink(node) },
+            _ => return None,
+        };
+        self.map.remove(key);
+        Some(entry.1)
+    }
+
+    /// Iterates over the entries held, `frequent` ones first, without counting any of
+    /// them as a use.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> + '_ {
+        let entries = self.frequent.iter().chain(self.recent.iter());
+        entries.map(|(key, value)| (key, value))
+    }
+}
+
+impl<K: Hash + Eq + Clone + fmt::Debug, V: fmt::Debug> fmt::Debug for AdaptiveCache<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AdaptiveCache, LruCache, Slot, Stats};
+    use std::collections::HashMap;
+
+    fn check<K: std::hash::Hash + Eq + Clone, V>(cache: &LruCache<K, V>) {
+        assert_eq!(cache.map.len(), cache.order.len());
+        assert!(cache.len() <= cache.capacity());
+        for (key, _) in cache.iter() {
+            let node = cache.map[key];
+            unsafe { assert!((*node.as_ptr()).elem.0 == *key) };
+        }
+    }
+
+    #[test]
+    fn basics() {
+        let mut cache = LruCache::new(3);
+
+        // Check empty cache behaves right
+        assert_eq!(cache.get("a"), None);
+        assert_eq!(cache.pop_lru(), None);
+        assert!(cache.is_empty());
+
+        // Populate cache
+        assert_eq!(cache.put("a".to_string(), 1), None);
+        assert_eq!(cache.put("b".to_string(), 2), None);
+        assert_eq!(cache.put("c".to_string(), 3), None);
+        check(&cache);
+        assert_eq!(cache.len(), 3);
+
+        // A use saves "a" from eviction, so "b" goes
+        assert_eq!(cache.get("a"), Some(&1));
+        assert_eq!(cache.put("d".to_string(), 4), Some(("b".to_string(), 2)));
+        assert!(!cache.contains_key("b"));
+        assert_eq!(format!("{:?}", cache), r#"{"d": 4, "a": 1, "c": 3}"#);
+
+        // Peeking doesn't count as a use
+        assert_eq!(cache.peek("c"), Some(&3));
+        assert_eq!(cache.peek_lru(), Some((&"c".to_string(), &3)));
+
+        // Overwriting does, and hands back the old value
+        assert_eq!(cache.put("c".to_string(), 30), Some(("c".to_string(), 3)));
+        assert_eq!(cache.peek_lru(), Some((&"a".to_string(), &1)));
+        *cache.get_mut("a").unwrap() += 10;
+        check(&cache);
+
+        // Check exhaustion
+        assert_eq!(cache.remove("c"), Some(30));
+        assert_eq!(cache.remove("c"), None);
+        assert_eq!(cache.pop_lru(), Some(("d".to_string(), 4)));
+        assert_eq!(cache.pop_lru(), Some(("a".to_string(), 11)));
+        assert_eq!(cache.pop_lru(), None);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "capacity must be at least 1")]
+    fn zero_capacity() {
+        LruCache::<u32, u32>::new(0);
+    }
+
+    #[test]
+    fn against_model() {
+        // A plain vector in recency order, most recent first
+        let capacity = 16;
+        let mut cache = LruCache::new(capacity);
+        let mut model: Vec<(u32, u32)> = Vec::new();
+        let mut x: u32 = 1;
+        for i in 0..10_000 {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let key = (x >> 8) % 32;
+            let found = model.iter().position(|&(k, _)| k == key);
+            match x >> 30 {
+                0 => {
+                    assert_eq!(cache.remove(&key), found.map(|at| model.remove(at).1));
+                }
+                1 => {
+                    let expected = found.map(|at| {
+                        let entry = model.remove(at);
+                        model.insert(0, entry);
+                        entry.1
+                    });
+                    assert_eq!(cache.get(&key).copied(), expected);
+                }
+                _ => {
+                    let pushed_out = match found {
+                        Some(at) => Some(model.remove(at)),
+                        None if model.len() == capacity => model.pop(),
+                        None => None,
+                    };
+                    model.insert(0, (key, i));
+                    assert_eq!(cache.put(key, i), pushed_out);
+                }
+            }
+            assert!(cache
+                .iter()
+                .map(|(&k, &v)| (k, v))
+                .eq(model.iter().copied()));
+            if i % 100 == 0 {
+                check(&cache);
+            }
+        }
+    }
+
+    fn check_adaptive<K: std::hash::Hash + Eq + Clone, V>(cache: &AdaptiveCache<K, V>) {
+        let (recent, frequent) = (cache.recent.len(), cache.frequent.len());
+        let ghosts = (cache.recent_ghosts.len(), cache.frequent_ghosts.len());
+        assert!(recent + frequent <= cache.capacity);
+        assert!(recent + ghosts.0 <= cache.capacity);
+        assert!(recent + frequent + ghosts.0 + ghosts.1 <= 2 * cache.capacity);
+        assert!(cache.target <= cache.capacity);
+        assert_eq!(cache.map.len(), recent + frequent + ghosts.0 + ghosts.1);
+        for (key, slot) in &cache.map {
+            let found = unsafe {
+                match *slot {
+                    Slot::Recent(node) | Slot::Frequent(node) => &(*node.as_ptr()).elem.0,
+                    Slot::RecentGhost(node) | Slot::FrequentGhost(node) => &(*node.as_ptr()).elem,
+                }
+            };
+            assert!(found == key);
+        }
+    }
+
+    #[test]
+    fn adaptive_basics() {
+        let mut cache = AdaptiveCache::new(2);
+
+        // Check empty cache behaves right
+        assert_eq!(cache.get(&'a'), None);
+        assert!(cache.is_empty());
+
+        // Populate cache, and use 'a' so it moves to `frequent`
+        assert_eq!(cache.put('a', 1), None);
+        assert_eq!(cache.put('b', 2), None);
+        assert_eq!(cache.get(&'a'), Some(&1));
+        assert_eq!(format!("{:?}", cache), "{'a': 1, 'b': 2}");
+
+        // A new key evicts from `recent`, leaving a ghost of 'b'
+        assert_eq!(cache.put('c', 3), Some(('b', 2)));
+        assert!(!cache.contains_key(&'b'));
+        assert_eq!(cache.recent_ghosts.len(), 1);
+        check_adaptive(&cache);
+
+        // Missing on that ghost grows `recent`'s share, so this time `frequent` gives way
+        assert_eq!(cache.put('b', 20), Some(('a', 1)));
+        assert_eq!(cache.target, 1);
+        assert_eq!(cache.peek(&'b'), Some(&20));
+        check_adaptive(&cache);
+
+        // And missing on a ghost of `frequent` shrinks it again
+        assert_eq!(cache.put('a', 10), Some(('c', 3)));
+        assert_eq!(cache.target, 0);
+        assert_eq!(cache.frequent.len(), 2);
+        check_adaptive(&cache);
+
+        // Overwriting hands back the old value
+        assert_eq!(cache.put('a', 11), Some(('a', 10)));
+        assert_eq!(cache.stats(), Stats { hits: 1, misses: 1 });
+
+        // Check exhaustion
+        assert_eq!(cache.remove(&'a'), Some(11));
+        assert_eq!(cache.remove(&'b'), Some(20));
+        assert_eq!(cache.remove(&'c'), None);
+        assert!(cache.is_empty());
+        check_adaptive(&cache);
+    }
+
+    #[test]
+    fn adaptive_against_model() {
+        // The model only knows what's held, and learns of evictions from `put`
+        let capacity = 16;
+        let mut cache = AdaptiveCache::new(capacity);
+        let mut model = HashMap::new();
+        let mut x: u32 = 1;
+        for i in 0..10_000 {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            // Skewed keys, so some are hot and some come back as ghosts
+            let key = ((x >> 8) % 64) * ((x >> 16) % 64) / 64;
+            match x >> 30 {
+                0 => assert_eq!(cache.remove(&key), model.remove(&key)),
+                1 => assert_eq!(cache.get(&key), model.get(&key)),
+                _ => {
+                    if let Some((out, value)) = cache.put(key, i) {
+                        assert_eq!(model.remove(&out), Some(value));
+                    }
+                    model.insert(key, i);
+                }
+            }
+            assert_eq!(cache.len(), model.len());
+            if i % 100 == 0 {
+                check_adaptive(&cache);
+                assert!(model
+                    .iter()
+                    .all(|(key, value)| cache.peek(key) == Some(value)));
+            }
+        }
+    }
+
+    #[test]
+    fn scan_resistance() {
+        // A small hot set, interrupted now and then by a scan bigger than the cache
+        let capacity = 16;
+        let mut lru = LruCache::new(capacity);
+        let mut adaptive = AdaptiveCache::new(capacity);
+        let mut trace = Vec::new();
+        let mut x: u32 = 1;
+        for round in 0..20 {
+            for _ in 0..100 {
+                x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                trace.push((x >> 8) % 8);
+            }
+            trace.extend((0..40).map(|i| 1000 + 40 * round + i));
+        }
+        for &key in &trace {
+            if lru.get(&key).is_none() {
+                lru.put(key, key);
+            }
+            if adaptive.get(&key).is_none() {
+                adaptive.put(key, key);
+            }
+        }
+
+        // LRU misses the whole hot set again after every scan; ARC kept it in `frequent`
+        let (lru, adaptive) = (lru.stats(), adaptive.stats());
+        assert_eq!(lru.lookups(), trace.len() as u64);
+        assert_eq!(adaptive.lookups(), trace.len() as u64);
+        assert_eq!(lru.misses, 20 * 40 + 20 * 8);
+        assert_eq!(adaptive.misses, 20 * 40 + 8);
+        assert!(adaptive.hit_rate() > lru.hit_rate());
+    }
+}
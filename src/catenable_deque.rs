@@ -0,0 +1,726 @@
+//! # Catenable persistent deque
+//!
+//! A persistent double-ended queue that also concatenates in O(1): Okasaki's implicit
+//! catenable deque (*Purely Functional Data Structures*, 1998, section 11.2), which rounds
+//! out the persistent sequences here. The [list](crate::persistent) only has one end, the
+//! [finger tree](crate::finger_tree) concatenates in O(log n), and this one does push and
+//! pop at both ends plus joining two deques, each in O(1).
+//!
+//! A deque is either *shallow*, one plain deque, or *deep*, five parts strung together:
+//!
+//! ```text
+//!   front     a              middle     b              rear
+//!   [1 2 3]   deque of       [4 5]      deque of       [9 10 11]
+//!   (>= 3)    compound       (>= 2)     compound       (>= 3)
+//!             elements                  elements
+//! ```
+//!
+//! The plain parts are [`Seq`]s, so their ends are cheap. `a` and `b` are catenable deques
+//! themselves, one level down, of *compound elements*: either a plain deque of at least two
+//! elements, or a front, a catenable deque and a rear again. Joining two deep deques never
+//! looks inside either: it takes the last element of one rear and the first of the other
+//! front for the new middle, and wraps what's left of each side up as one compound element
+//! pushed onto the inner `a` or `b`. That's a constant number of pushes, whatever the sizes.
+//!
+//! The work comes back when popping runs an end's plain part down to its minimum and a
+//! compound element has to be unpacked from the level below, which may in turn have to
+//! unpack one from further down. That cascade is rare enough that pops are amortized O(1),
+//! with the same caveat as the [banker's queue](crate::persistent::Queue): the bound
+//! assumes each version is popped from once, since popping one unlucky version over and
+//! over pays for the same cascade every time. Pushes and concatenation never cascade: they're
+//! always just a few pushes on the plain parts.
+
+use crate::finger_tree::Seq;
+use std::fmt;
+use std::iter::FromIterator;
+use std::sync::Arc;
+
+// The plain deques at either end and in the middle
+type Plain<T> = Seq<Elem<T>>;
+
+#[derive(Clone)]
+enum Elem<T> {
+    // At the top level
+    Item(T),
+    // At the levels below
+    Simple(Arc<Plain<T>>),
+    Compound(Arc<Plain<T>>, Arc<Cat<T>>, Arc<Plain<T>>),
+}
+
+#[derive(Clone)]
+enum Cat<T> {
+    Shallow(Plain<T>),
+    Deep {
+        front: Plain<T>,
+        a: Arc<Cat<T>>,
+        middle: Plain<T>,
+        b: Arc<Cat<T>>,
+        rear: Plain<T>,
+    },
+}
+
+/// Moves the elements of small `left` onto the front of `right`.
+fn append_left<T: Clone>(left: &Plain<T>, right: &Plain<T>) -> Plain<T> {
+    left.iter()
+        .collect::<Vec<_>>()
+        .into_iter()
+        .rev()
+        .fold(right.clone(), |seq, elem| seq.push_front(elem.clone()))
+}
+
+/// Moves the elements of small `right` onto the back of `left`.
+fn append_right<T: Clone>(left: &Plain<T>, right: &Plain<T>) -> Plain<T> {
+    right
+        .iter()
+        .fold(left.clone(), |seq, elem| seq.push_back(elem.clone()))
+}
+
+/// Splits a rear and a front of at least three elements each into what's left of them and
+/// a middle of the two elements at the seam.
+fn share<T: Clone>(rear: &Plain<T>, front: &Plain<T>) -> (Plain<T>, Plain<T>, Plain<T>) {
+    let middle = Seq::new()
+        .push_back(rear.back().unwrap().clone())
+        .push_back(front.front().unwrap().clone());
+    (rear.pop_back(), middle, front.pop_front())
+}
+
+impl<T: Clone> Cat<T> {
+    fn empty() -> Arc<Self> {
+        Arc::new(Cat::Shallow(Seq::new()))
+    }
+
+    fn is_empty(&self) -> bool {
+        matches!(self, Cat::Shallow(seq) if seq.is_empty())
+    }
+
+    fn front(&self) -> Option<&Elem<T>> {
+        match self {
+            Cat::Shallow(seq) => seq.front(),
+            Cat::Deep { front, .. } => front.front(),
+        }
+    }
+
+    fn back(&self) -> Option<&Elem<T>> {
+        match self {
+            Cat::Shallow(seq) => seq.back(),
+            Cat::Deep { rear, .. } => rear.back(),
+        }
+    }
+
+    /// The same deque with its front plain part replaced by `f`.
+    fn with_front(&self, f: Plain<T>) -> Self {
+        match self {
+            Cat::Shallow(_) => Cat::Shallow(f),
+            Cat::Deep {
+                a, middle, b, rear, ..
+            } => Cat::Deep {
+                front: f,
+                a: a.clone(),
+                middle: middle.clone(),
+                b: b.clone(),
+                rear: rear.clone(),
+            },
+        }
+    }
+
+    /// The same deque with its rear plain part replaced by `r`.
+    fn with_rear(&self, r: Plain<T>) -> Self {
+        match self {
+            Cat::Shallow(_) => Cat::Shallow(r),
+            Cat::Deep {
+                front,
+                a,
+                middle,
+                b,
+                ..
+            } => Cat::Deep {
+                front: front.clone(),
+                a: a.clone(),
+                middle: middle.clone(),
+                b: b.clone(),
+                rear: r,
+            },
+        }
+    }
+
+    fn push_front(&self, elem: Elem<T>) -> Self {
+        match self {
+            Cat::Shallow(seq) => Cat::Shallow(seq.push_front(elem)),
+            Cat::Deep { front, .. } => self.with_front(front.push_front(elem)),
+        }
+    }
+
+    fn push_back(&self, elem: Elem<T>) -> Self {
+        match self {
+            Cat::Shallow(seq) => Cat::Shallow(seq.push_back(elem)),
+            Cat::Deep { rear, .. } => self.with_rear(rear.push_back(elem)),
+        }
+    }
+
+    fn concat(&self, other: &Self) -> Self {
+        match (self, other) {
+            (Cat::Shallow(left), Cat::Shallow(right)) => {
+                if left.len() < 4 {
+                    Cat::Shallow(append_left(left, right))
+                } else if right.len() < 4 {
+                    Cat::Shallow(append_right(left, right))
+                } else {
+                    let (front, middle, rear) = share(left, right);
+                    Cat::Deep {
+                        front,
+                        a: Cat::empty(),
+                        middle,
+                        b: Cat::empty(),
+                        rear,
+                    }
+                }
+            }
+            (
+                Cat::Shallow(left),
+                Cat::Deep {
+                    front,
+                    a,
+                    middle,
+                    b,
+                    rear,
+                },
+            ) => {
+                if left.len() < 4 {
+                    return other.with_front(append_left(left, front));
+                }
+                Cat::Deep {
+                    front: left.clone(),
+                    a: Arc::new(a.push_front(Elem::Simple(Arc::new(front.clone())))),
+                    middle: middle.clone(),
+                    b: b.clone(),
+                    rear: rear.clone(),
+                }
+            }
+            (
+                Cat::Deep {
+                    front,
+                    a,
+                    middle,
+                    b,
+                    rear,
+                },
+                Cat::Shallow(right),
+            ) => {
+                if right.len() < 4 {
+                    return self.with_rear(append_right(rear, right));
+                }
+                Cat::Deep {
+                    front: front.clone(),
+                    a: a.clone(),
+                    middle: middle.clone(),
+                    b: Arc::new(b.push_back(Elem::Simple(Arc::new(rear.clone())))),
+                    rear: right.clone(),
+                }
+            }
+            (
+                Cat::Deep {
+                    front: front1,
+                    a: a1,
+                    middle: middle1,
+                    b: b1,
+                    rear: rear1,
+                },
+                Cat::Deep {
+                    front: front2,
+                    a: a2,
+                    middle: middle2,
+                    b: b2,
+                    rear: rear2,
+                },
+            ) => {
+                // Each side's inner half gets wrapped up whole as one compound element
+                let (rear1, middle, front2) = share(rear1, front2);
+                let left = Elem::Compound(Arc::new(middle1.clone()), b1.clone(), Arc::new(rear1));
+                let right = Elem::Compound(Arc::new(front2), a2.clone(), Arc::new(middle2.clone()));
+                Cat::Deep {
+                    front: front1.clone(),
+                    a: Arc::new(a1.push_back(left)),
+                    middle,
+                    b: Arc::new(b2.push_front(right)),
+                    rear: rear2.clone(),
+                }
+            }
+        }
+    }
+
+    fn pop_front(&self) -> Self {
+        let (front, a, middle, b, rear) = match self {
+            Cat::Shallow(seq) => return Cat::Shallow(seq.pop_front()),
+            Cat::Deep {
+                front,
+                a,
+                middle,
+                b,
+                rear,
+            } => (front, a, middle, b, rear),
+        };
+        if front.len() > 3 {
+            return self.with_front(front.pop_front());
+        }
+        // The front is at its minimum, so refill it from the level below
+        let rest = front.pop_front();
+        if let Some(elem) = a.front() {
+            let (front, a) = match elem {
+                Elem::Simple(seq) => (append_left(&rest, seq), a.pop_front()),
+                Elem::Compound(f, inner, r) => {
+                    let unpacked = a.pop_front().push_front(Elem::Simple(r.clone()));
+                    (append_left(&rest, f), inner.concat(&unpacked))
+                }
+                Elem::Item(_) => unreachable!("items are at the top level"),
+            };
+            return Cat::Deep {
+                front,
+                a: Arc::new(a),
+                middle: middle.clone(),
+                b: b.clone(),
+                rear: rear.clone(),
+            };
+        }
+        if let Some(elem) = b.front() {
+            // With `a` empty, the middle joins the front and `b` supplies a new one
+            let front = append_left(&rest, middle);
+            let (a, middle) = match elem {
+                Elem::Simple(seq) => (Cat::empty(), Seq::clone(seq)),
+                Elem::Compound(f, inner, r) => (
+                    Arc::new(inner.push_front(Elem::Simple(f.clone()))),
+                    Seq::clone(r),
+                ),
+                Elem::Item(_) => unreachable!("items are at the top level"),
+            };
+            return Cat::Deep {
+                front,
+                a,
+                middle,
+                b: Arc::new(b.pop_front()),
+                rear: rear.clone(),
+            };
+        }
+        Cat::Shallow(append_left(&rest, middle)).concat(&Cat::Shallow(rear.clone()))
+    }
+
+    fn pop_back(&self) -> Self {
+        let (front, a, middle, b, rear) = match self {
+            Cat::Shallow(seq) => return Cat::Shallow(seq.pop_back()),
+            Cat::Deep {
+                front,
+                a,
+                middle,
+                b,
+                rear,
+            } => (front, a, middle, b, rear),
+        };
+        if rear.len() > 3 {
+            return self.with_rear(rear.pop_back());
+        }
+        let rest = rear.pop_back();
+        if let Some(elem) = b.back() {
+            let (b, rear) = match elem {
+                Elem::Simple(seq) => (b.pop_back(), append_right(seq, &rest)),
+                Elem::Compound(f, inner, r) => {
+                    let unpacked = b.pop_back().push_back(Elem::Simple(f.clone()));
+                    (unpacked.concat(inner), append_right(r, &rest))
+                }
+                Elem::Item(_) => unreachable!("items are at the top level"),
+            };
+            return Cat::Deep {
+                front: front.clone(),
+                a: a.clone(),
+                middle: middle.clone(),
+                b: Arc::new(b),
+                rear,
+            };
+        }
+        if let Some(elem) = a.back() {
+            let rear = append_right(middle, &rest);
+            let (middle, b) = match elem {
+                Elem::Simple(seq) => (Seq::clone(seq), Cat::empty()),
+                Elem::Compound(f, inner, r) => (
+                    Seq::clone(f),
+                    Arc::new(inner.push_back(Elem::Simple(r.clone()))),
+                ),
+                Elem::Item(_) => unreachable!("items are at the top level"),
+            };
+            return Cat::Deep {
+                front: front.clone(),
+                a: Arc::new(a.pop_back()),
+                middle,
+                b,
+                rear,
+            };
+        }
+        Cat::Shallow(front.clone()).concat(&Cat::Shallow(append_right(middle, &rest)))
+    }
+}
+
+fn item<T>(elem: &Elem<T>) -> &T {
+    match elem {
+        Elem::Item(item) => item,
+        _ => unreachable!("the top level holds items"),
+    }
+}
+
+pub struct CatDeque<T> {
+    cat: Cat<T>,
+    len: usize,
+}
+
+impl<T: Clone> CatDeque<T> {
+    /// Creates an empty CatDeque.
+    pub fn new() -> Self {
+        CatDeque {
+            cat: Cat::Shallow(Seq::new()),
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn front(&self) -> Option<&T> {
+        self.cat.front().map(item)
+    }
+
+    pub fn back(&self) -> Option<&T> {
+        self.cat.back().map(item)
+    }
+
+    /// Returns a new deque with `elem` in front.
+    pub fn push_front(&self, elem: T) -> Self {
+        CatDeque {
+            cat: self.cat.push_front(Elem::Item(elem)),
+            len: self.len + 1,
+        }
+    }
+
+    /// Returns a new deque with `elem` at the back.
+    pub fn push_back(&self, elem: T) -> Self {
+        CatDeque {
+            cat: self.cat.push_back(Elem::Item(elem)),
+            len: self.len + 1,
+        }
+    }
+
+    /// Returns a new deque without the front element. Popping an empty deque gives an
+    /// empty deque.
+    pub fn pop_front(&self) -> Self {
+        if self.cat.is_empty() {
+            return self.clone();
+        }
+        CatDeque {
+            cat: self.cat.pop_front(),
+            len: self.len - 1,
+        }
+    }
+
+    /// Returns a new deque without the back element.
+    pub fn pop_back(&self) -> Self {
+        if self.cat.is_empty() {
+            return self.clone();
+        }
+        CatDeque {
+            cat: self.cat.pop_back(),
+            len: self.len - 1,
+        }
+    }
+
+    /// Returns a new deque with the elements of this one followed by those of `other`,
+    /// in O(1).
+    pub fn concat(&self, other: &Self) -> Self {
+        CatDeque {
+            cat: self.cat.concat(&other.cat),
+            len: self.len + other.len,
+        }
+    }
+}
+
+impl<T> CatDeque<T> {
+    /// Iterates over the elements, front to back.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            stack: vec![Work::Cat(&self.cat)],
+            left: self.len,
+        }
+    }
+}
+
+impl<T: Clone> Clone for CatDeque<T> {
+    fn clone(&self) -> Self {
+        CatDeque {
+            cat: self.cat.clone(),
+            len: self.len,
+        }
+    }
+}
+
+impl<T: Clone> Default for CatDeque<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Clone> FromIterator<T> for CatDeque<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut deque = CatDeque::new();
+        for elem in iter {
+            deque = deque.push_back(elem);
+        }
+        deque
+    }
+}
+
+impl<T: PartialEq> PartialEq for CatDeque<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.iter().eq(other.iter())
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for CatDeque<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+enum Work<'a, T> {
+    Elem(&'a Elem<T>),
+    Cat(&'a Cat<T>),
+}
+
+/// Iterator over the elements of a [`CatDeque`], front to back.
+pub struct Iter<'a, T> {
+    // What's still to visit, the next thing on top
+    stack: Vec<Work<'a, T>>,
+    left: usize,
+}
+
+impl<'a, T> Iter<'a, T> {
+    fn push_seq(&mut self, seq: &'a Plain<T>) {
+        let elems: Vec<_> = seq.iter().collect();
+        self.stack.extend(elems.into_iter().rev().map(Work::Elem));
+    }
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.stack.pop()? {
+                Work::Elem(Elem::Item(item)) => {
+                    self.left -= 1;
+                    return Some(item);
+                }
+                Work::Elem(Elem::Simple(seq)) => self.push_seq(seq),
+                Work::Elem(Elem::Compound(front, inner, rear)) => {
+                    self.push_seq(rear);
+                    self.stack.push(Work::Cat(inner));
+                    self.push_seq(front);
+                }
+                Work::Cat(Cat::Shallow(seq)) => self.push_seq(seq),
+                Work::Cat(Cat::Deep {
+                    front,
+                    a,
+                    middle,
+                    b,
+                    rear,
+                }) => {
+                    self.push_seq(rear);
+                    self.stack.push(Work::Cat(b));
+                    self.push_seq(middle);
+                    self.stack.push(Work::Cat(a));
+                    self.push_seq(front);
+                }
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.left, Some(self.left))
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Iter<'a, T> {}
+
+impl<'a, T> IntoIterator for &'a CatDeque<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Cat, CatDeque, Elem};
+
+    /// Checks the size of every part, and that items are only at the top, returning the
+    /// number of items. Elements at `depth` 0 are items; deeper ones are compound, with
+    /// plain parts one level up and a catenable deque at the same depth.
+    fn check<T>(cat: &Cat<T>, depth: usize) -> usize {
+        fn check_elems<'a, T: 'a>(elems: impl Iterator<Item = &'a Elem<T>>, depth: usize) -> usize {
+            elems
+                .map(|elem| match elem {
+                    Elem::Item(_) => {
+                        assert_eq!(depth, 0);
+                        1
+                    }
+                    Elem::Simple(seq) => {
+                        assert!(depth > 0 && seq.len() >= 2);
+                        check_elems(seq.iter(), depth - 1)
+                    }
+                    Elem::Compound(front, inner, rear) => {
+                        assert!(depth > 0 && front.len() >= 2 && rear.len() >= 2);
+                        check_elems(front.iter(), depth - 1)
+                            + check(inner, depth)
+                            + check_elems(rear.iter(), depth - 1)
+                    }
+                })
+                .sum()
+        }
+        match cat {
+            Cat::Shallow(seq) => check_elems(seq.iter(), depth),
+            Cat::Deep {
+                front,
+                a,
+                middle,
+                b,
+                rear,
+            } => {
+                assert!(front.len() >= 3 && rear.len() >= 3 && middle.len() >= 2);
+                check_elems(front.iter(), depth)
+                    + check(a, depth + 1)
+                    + check_elems(middle.iter(), depth)
+                    + check(b, depth + 1)
+                    + check_elems(rear.iter(), depth)
+            }
+        }
+    }
+
+    #[test]
+    fn basics() {
+        let deque = CatDeque::new();
+
+        // Check empty deque behaves right
+        assert_eq!(deque.front(), None);
+        assert!(deque.pop_front().is_empty());
+        assert!(deque.pop_back().is_empty());
+
+        // Populate deque
+        let deque = deque.push_back(2).push_back(3).push_front(1);
+        assert_eq!(deque.len(), 3);
+        assert_eq!((deque.front(), deque.back()), (Some(&1), Some(&3)));
+
+        // Check normal removal
+        let deque = deque.pop_front();
+        assert_eq!(deque.front(), Some(&2));
+        let deque = deque.pop_back();
+        assert_eq!(format!("{:?}", deque), "[2]");
+
+        // Push some more just to make sure nothing's corrupted
+        let deque = (3..100).fold(deque, |deque, i| deque.push_back(i));
+        assert!(deque.iter().copied().eq(2..100));
+        assert_eq!(deque.iter().len(), 98);
+
+        // Check exhaustion
+        let deque = (0..98).fold(deque, |deque, _| deque.pop_back());
+        assert!(deque.is_empty());
+        assert_eq!(deque.back(), None);
+    }
+
+    #[test]
+    fn concat() {
+        // Join lots of small deques into deep nesting, then take it apart from both ends
+        let mut deque = CatDeque::new();
+        let mut next = 0;
+        for size in (0..200).map(|i| i % 9) {
+            let part: CatDeque<u32> = (next..next + size).collect();
+            next += size;
+            deque = if size % 2 == 0 {
+                deque.concat(&part)
+            } else {
+                // Right-nested joins too
+                let tail: CatDeque<u32> = (next..next + 5).collect();
+                next += 5;
+                deque.concat(&part.concat(&tail))
+            };
+        }
+        assert_eq!(check(&deque.cat, 0), deque.len());
+        assert!(deque.iter().copied().eq(0..next));
+
+        let mut rest = deque.clone();
+        let (mut low, mut high) = (0, next);
+        while !rest.is_empty() {
+            if (low + high) % 3 == 0 {
+                assert_eq!(rest.back(), Some(&(high - 1)));
+                rest = rest.pop_back();
+                high -= 1;
+            } else {
+                assert_eq!(rest.front(), Some(&low));
+                rest = rest.pop_front();
+                low += 1;
+            }
+            assert_eq!(check(&rest.cat, 0), rest.len());
+        }
+        assert_eq!(low, high);
+
+        // The original is untouched
+        assert_eq!(deque.len(), next as usize);
+        assert_eq!(deque.back(), Some(&(next - 1)));
+    }
+
+    #[test]
+    fn against_vec() {
+        // Random operations on random versions, each checked against its own model
+        let mut versions = vec![(CatDeque::new(), Vec::new())];
+        let mut x: u32 = 1;
+        let n = if cfg!(miri) { 300 } else { 5_000 };
+        for _ in 0..n {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let (deque, mut model) = versions[(x >> 4) as usize % versions.len()].clone();
+            let other = &versions[(x >> 12) as usize % versions.len()];
+            let next = match x >> 29 {
+                0 | 1 => {
+                    model.pop();
+                    deque.pop_back()
+                }
+                2 | 3 => {
+                    if !model.is_empty() {
+                        model.remove(0);
+                    }
+                    deque.pop_front()
+                }
+                // Concatenating versions doubles sizes fast, so cap them
+                4 if model.len() + other.1.len() < 1000 => {
+                    model.extend(other.1.iter().copied());
+                    deque.concat(&other.0)
+                }
+                5 => {
+                    model.insert(0, x);
+                    deque.push_front(x)
+                }
+                _ => {
+                    model.push(x);
+                    deque.push_back(x)
+                }
+            };
+            assert_eq!(next.len(), model.len());
+            assert_eq!(next.front(), model.first());
+            assert_eq!(next.back(), model.last());
+            versions.push((next, model));
+        }
+        for (deque, model) in versions.iter().step_by(50) {
+            assert_eq!(check(&deque.cat, 0), model.len());
+            assert!(deque.iter().eq(model.iter()));
+        }
+    }
+}
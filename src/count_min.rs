@@ -0,0 +1,302 @@
+//! # Count-min sketch
+//!
+//! Counts how often each item turns up in a stream, approximately, in a fixed amount of
+//! memory however many distinct items there are (Cormode and Muthukrishnan, 2005). It's a
+//! table of `depth` rows of `width` counters, each row with its own hash. Adding an item
+//! bumps one counter per row, and its estimate is the smallest of those counters:
+//!
+//! ```text
+//!             width
+//!   row 0  [ 0  3  0  0  5  0  1 ]      add "cat" bumps row 0 col 1, row 1 col 4,
+//!   row 1  [ 1  0  0  0  3  4  0 ]      and row 2 col 2
+//!   row 2  [ 0  0  3  2  0  0  4 ]
+//!                                       estimate("cat") = min(3, 3, 3) = 3
+//! ```
+//!
+//! Other items landing on the same counter only ever add to it, so an estimate is never
+//! below the true count. With `width = ⌈e / ε⌉` and `depth = ⌈ln(1 / δ)⌉`, it's within
+//! `ε` times the total of everything counted above it, except with probability `δ`.
+//! `CountMin::with_error` works the sizes out. That suits heavy hitters well and rare
+//! items badly: an item seen 3 times in a stream of millions gets an estimate that's all
+//! noise.
+//!
+//! **Conservative update** (Estan and Varghese, 2002) tightens the estimates for free.
+//! Adding `c` to an item only raises each of its counters as far as the new estimate,
+//! `min + c`, instead of adding `c` to all of them. Counters that were already higher
+//! than that are carrying other items' counts, and raising them is what makes estimates
+//! too high.
+//!
+//! Two sketches with the same sizes and hashes merge by adding their counters, which
+//! gives the sketch of both streams together. That lets workers count part of a stream
+//! each and combine the results. [`CountMin::new_like`] makes an empty sketch to match.
+
+use std::collections::hash_map::RandomState;
+use std::f64::consts::E;
+use std::hash::{BuildHasher, Hash};
+
+#[derive(Clone, Debug)]
+pub struct CountMin<S = RandomState> {
+    hasher: S,
+    width: usize,
+    depth: usize,
+    // depth rows of width counters, one after another
+    counters: Vec<u64>,
+    total: u64,
+    conservative: bool,
+}
+
+impl CountMin<RandomState> {
+    /// Creates an empty sketch with `depth` rows of `width` counters.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either is 0.
+    pub fn new(width: usize, depth: usize) -> Self {
+        Self::with_hasher(width, depth, RandomState::new())
+    }
+
+    /// Creates an empty sketch whose estimates are at most `epsilon` times the total count
+    /// too high, except with probability `delta`.
+    ///
+    /// # Panics
+    ///
+    /// Panics unless both are between 0 and 1.
+    pub fn with_error(epsilon: f64, delta: f64) -> Self {
+        assert!(
+            epsilon > 0.0 && epsilon < 1.0 && delta > 0.0 && delta < 1.0,
+            "epsilon and delta must be between 0 and 1"
+        );
+        let width = (E / epsilon).ceil() as usize;
+        let depth = (1.0 / delta).ln().ceil().max(1.0) as usize;
+        Self::new(width, depth)
+    }
+}
+
+impl<S: BuildHasher> CountMin<S> {
+    /// Creates an empty sketch like `new` that hashes items with `hasher`.
+    pub fn with_hasher(width: usize, depth: usize, hasher: S) -> Self {
+        assert!(width > 0 && depth > 0, "width and depth must be at least 1");
+        CountMin {
+            hasher,
+            width,
+            depth,
+            counters: vec![0; width * depth],
+            total: 0,
+            conservative: false,
+        }
+    }
+
+    /// Switches the sketch to conservative update.
+    pub fn conservative(mut self) -> Self {
+        self.conservative = true;
+        self
+    }
+
+    /// Returns an empty sketch with the same sizes, hashes and update mode, which can be
+    /// merged with this one.
+    pub fn new_like(&self) -> Self
+    where
+        S: Clone,
+    {
+        CountMin {
+            counters: vec![0; self.counters.len()],
+            total: 0,
+            hasher: self.hasher.clone(),
+            ..*self
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn is_conservative(&self) -> bool {
+        self.conservative
+    }
+
+    /// Returns the sum of all the counts added.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// The counter the item maps to in each row.
+    fn cells<Q: Hash + ?Sized>(&self, item: &Q) -> impl Iterator<Item = usize> {
+        // Row hashes by double hashing from one 64-bit hash
+        let hash = self.hasher.hash_one(item);
+        let step = (hash ^ (hash >> 33)).wrapping_mul(0xff51_afd7_ed55_8ccd) | 1;
+        let width = self.width;
+        (0..self.depth).map(move |row| {
+            let h = hash.wrapping_add((row as u64).wrapping_mul(step));
+            row * width + (h % width as u64) as usize
+        })
+    }
+
+    pub fn increment<Q: Hash + ?Sized>(&mut self, item: &Q) {
+        self.add(item, 1);
+    }
+
+    /// Counts `count` more of the item.
+    pub fn add<Q: Hash + ?Sized>(&mut self, item: &Q, count: u64) {
+        self.total += count;
+        if self.conservative {
+            // Only as far as the new estimate, and no further
+            let target = self.estimate(item) + count;
+            for cell in self.cells(item) {
+                let counter = &mut self.counters[cell];
+                *counter = (*counter).max(target);
+            }
+        } else {
+            for cell in self.cells(item) {
+                self.counters[cell] += count;
+            }
+        }
+    }
+
+    /// Returns the estimated count of the item, which is never below the true one.
+    pub fn estimate<Q: Hash + ?Sized>(&self, item: &Q) -> u64 {
+        self.cells(item)
+            .map(|cell| self.counters[cell])
+            .min()
+            .unwrap()
+    }
+
+    /// Adds everything `other` counted to this sketch.
+    ///
+    /// The two must share sizes and hashes, which is what [`CountMin::new_like`] is for.
+    /// Sketches with different hashers merge into nonsense, and there's no telling.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the sizes differ.
+    pub fn merge(&mut self, other: &CountMin<S>) {
+        assert!(
+            self.width == other.width && self.depth == other.depth,
+            "can't merge sketches of different sizes"
+        );
+        for (a, b) in self.counters.iter_mut().zip(&other.counters) {
+            *a += b;
+        }
+        self.total += other.total;
+    }
+
+    pub fn clear(&mut self) {
+        self.counters.iter_mut().for_each(|c| *c = 0);
+        self.total = 0;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::CountMin;
+    use std::collections::HashMap;
+
+    /// A skewed stream: item `i` turns up about `1 / (i + 1)` as often as item 0.
+    fn stream(len: usize) -> Vec<u32> {
+        let mut x: u32 = 1;
+        (0..len)
+            .map(|_| {
+                x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                let u = ((x >> 8) as f64 + 1.0) / (1 << 24) as f64;
+                // Inverse of a roughly Zipfian distribution over 10,000 items
+                (10_000f64.powf(u) - 1.0) as u32
+            })
+            .collect()
+    }
+
+    fn exact(stream: &[u32]) -> HashMap<u32, u64> {
+        let mut counts = HashMap::new();
+        for &item in stream {
+            *counts.entry(item).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    #[test]
+    fn basics() {
+        let mut sketch = CountMin::with_error(0.01, 0.01);
+        assert_eq!((sketch.width(), sketch.depth()), (272, 5));
+
+        // Check empty sketch behaves right
+        assert_eq!(sketch.estimate("cat"), 0);
+        assert_eq!(sketch.total(), 0);
+
+        // Populate sketch
+        sketch.increment("cat");
+        sketch.increment("cat");
+        sketch.add("dog", 10);
+        assert!(sketch.estimate("cat") >= 2);
+        assert!(sketch.estimate("dog") >= 10);
+        assert_eq!(sketch.total(), 12);
+
+        sketch.clear();
+        assert_eq!(sketch.estimate("dog"), 0);
+        assert_eq!(sketch.total(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "width and depth must be at least 1")]
+    fn zero_width() {
+        CountMin::new(0, 4);
+    }
+
+    #[test]
+    fn error_bound() {
+        let n = if cfg!(miri) { 2_000 } else { 200_000 };
+        let stream = stream(n);
+        let counts = exact(&stream);
+        let epsilon = 0.001;
+        let mut plain = CountMin::with_error(epsilon, 0.01);
+        let mut conservative = plain.new_like().conservative();
+        assert!(conservative.is_conservative());
+        for item in &stream {
+            plain.increment(item);
+            conservative.increment(item);
+        }
+
+        let bound = (epsilon * n as f64) as u64;
+        let mut over = 0;
+        for (item, &count) in &counts {
+            let estimate = plain.estimate(item);
+            let tighter = conservative.estimate(item);
+            // Never below the truth, and conservative update never does worse
+            assert!(count <= tighter && tighter <= estimate);
+            if estimate > count + bound {
+                over += 1;
+            }
+        }
+        // Allowed to miss the bound with probability 0.01, so a few may
+        assert!(over <= counts.len() / 50, "{} over the bound", over);
+    }
+
+    #[test]
+    fn merge() {
+        let stream = stream(if cfg!(miri) { 1_000 } else { 50_000 });
+        let (left, right) = stream.split_at(stream.len() / 3);
+        let mut whole = CountMin::new(100, 4);
+        let mut a = whole.new_like();
+        let mut b = whole.new_like();
+        for item in &stream {
+            whole.increment(item);
+        }
+        left.iter().for_each(|item| a.increment(item));
+        right.iter().for_each(|item| b.increment(item));
+
+        a.merge(&b);
+        assert_eq!(a.total(), whole.total());
+        for item in exact(&stream).keys() {
+            assert_eq!(a.estimate(item), whole.estimate(item));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "can't merge sketches of different sizes")]
+    fn merge_different_sizes() {
+        let mut a = CountMin::new(100, 4);
+        let b = CountMin::new(100, 5);
+        a.merge(&b);
+    }
+}
@@ -0,0 +1,389 @@
+//! # Counter
+//!
+//! A multiset: a set that knows how many times each element is in it. It's a
+//! [`RobinHoodMap`] from each element to its count, holding only counts of at least one, so
+//! an element counted down to zero is gone rather than lingering with a zero.
+//!
+//! The set operations work on counts the way they do for multisets:
+//!
+//! ```text
+//!   a = {x: 3, y: 1}     b = {x: 1, z: 2}
+//!
+//!   a | b  union         {x: 3, y: 1, z: 2}    the larger count of each
+//!   a & b  intersection  {x: 1}                the smaller, dropping zeros
+//! ```
+//!
+//! `most_common(k)` picks the `k` largest counts with a partial sort, O(n + k log k), rather
+//! than sorting everything.
+
+use crate::robin_hood::RobinHoodMap;
+use std::borrow::Borrow;
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+use std::iter::FromIterator;
+use std::ops::{BitAnd, BitOr};
+
+#[derive(Clone)]
+pub struct Counter<T, S = RandomState> {
+    counts: RobinHoodMap<T, usize, S>,
+    // Sum of the counts
+    total: usize,
+}
+
+impl<T> Counter<T, RandomState> {
+    /// Creates an empty Counter.
+    pub fn new() -> Self {
+        Self::with_hasher(RandomState::new())
+    }
+}
+
+impl<T, S> Counter<T, S> {
+    /// Creates an empty counter that hashes elements with `hasher`.
+    pub fn with_hasher(hasher: S) -> Self {
+        Counter {
+            counts: RobinHoodMap::with_hasher(hasher),
+            total: 0,
+        }
+    }
+
+    /// Returns the number of distinct elements.
+    pub fn len(&self) -> usize {
+        self.counts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+
+    /// Returns the sum of all the counts.
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    pub fn clear(&mut self) {
+        self.counts.clear();
+        self.total = 0;
+    }
+
+    /// Iterates over the distinct elements and their counts, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (&T, usize)> {
+        self.counts.iter().map(|(item, &count)| (item, count))
+    }
+
+    /// Returns the `k` elements with the largest counts, largest first. Elements with the
+    /// same count come out in no particular order.
+    pub fn most_common(&self, k: usize) -> Vec<(&T, usize)> {
+        let mut all: Vec<_> = self.iter().collect();
+        let by_count = |a: &(&T, usize), b: &(&T, usize)| b.1.cmp(&a.1);
+        if k < all.len() {
+            if k > 0 {
+                all.select_nth_unstable_by(k - 1, by_count);
+            }
+            all.truncate(k);
+        }
+        all.sort_unstable_by(by_count);
+        all
+    }
+}
+
+impl<T, S> Counter<T, S>
+where
+    T: Eq + Hash,
+    S: BuildHasher,
+{
+    /// Counts one more of `item`.
+    pub fn add(&mut self, item: T) {
+        self.add_many(item, 1);
+    }
+
+    /// Counts `n` more of `item`.
+    pub fn add_many(&mut self, item: T, n: usize) {
+        if n > 0 {
+            *self.counts.entry(item).or_insert(0) += n;
+            self.total += n;
+        }
+    }
+
+    /// Counts one less of `item`, returning whether there was one to take.
+    pub fn remove<Q>(&mut self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.remove_many(item, 1) == 1
+    }
+
+    /// Counts up to `n` less of `item`, and returns how many it took.
+    pub fn remove_many<Q>(&mut self, item: &Q, n: usize) -> usize
+    where
+        T: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let count = match self.counts.get_mut(item) {
+            Some(count) => count,
+            None => return 0,
+        };
+        let taken = n.min(*count);
+        *count -= taken;
+        if *count == 0 {
+            self.counts.remove(item);
+        }
+        self.total -= taken;
+        taken
+    }
+
+    /// Forgets `item` entirely, and returns how many there were.
+    pub fn remove_all<Q>(&mut self, item: &Q) -> usize
+    where
+        T: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let count = self.counts.remove(item).unwrap_or(0);
+        self.total -= count;
+        count
+    }
+
+    /// Returns how many of `item` there are, 0 if none.
+    pub fn count<Q>(&self, item: &Q) -> usize
+    where
+        T: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.counts.get(item).copied().unwrap_or(0)
+    }
+
+    pub fn contains<Q>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.counts.contains_key(item)
+    }
+
+    /// Returns whether every element is in `other` at least as many times.
+    pub fn is_subset(&self, other: &Self) -> bool {
+        self.iter().all(|(item, count)| count <= other.count(item))
+    }
+
+    /// Returns the multiset union, with the larger of the two counts of each element.
+    pub fn union(&self, other: &Self) -> Self
+    where
+        T: Clone,
+        S: Clone,
+    {
+        let mut union = self.clone();
+        for (item, count) in other.iter() {
+            let extra = count.saturating_sub(self.count(item));
+            union.add_many(item.clone(), extra);
+        }
+        union
+    }
+
+    /// Returns the multiset intersection, with the smaller of the two counts of each
+    /// element.
+    pub fn intersection(&self, other: &Self) -> Self
+    where
+        T: Clone,
+        S: Clone,
+    {
+        let mut intersection = Counter::with_hasher(self.counts.hasher().clone());
+        for (item, count) in self.iter() {
+            intersection.add_many(item.clone(), count.min(other.count(item)));
+        }
+        intersection
+    }
+}
+
+impl<T, S: Default> Default for Counter<T, S> {
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+impl<T, S> PartialEq for Counter<T, S>
+where
+    T: Eq + Hash,
+    S: BuildHasher,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.counts == other.counts
+    }
+}
+
+impl<T: Eq + Hash, S: BuildHasher> Eq for Counter<T, S> {}
+
+impl<T: fmt::Debug, S> fmt::Debug for Counter<T, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<T, S> Extend<T> for Counter<T, S>
+where
+    T: Eq + Hash,
+    S: BuildHasher,
+{
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for item in iter {
+            self.add(item);
+        }
+    }
+}
+
+impl<T, S> FromIterator<T> for Counter<T, S>
+where
+    T: Eq + Hash,
+    S: BuildHasher + Default,
+{
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut counter = Self::default();
+        counter.extend(iter);
+        counter
+    }
+}
+
+impl<T, S> BitOr for &Counter<T, S>
+where
+    T: Eq + Hash + Clone,
+    S: BuildHasher + Clone,
+{
+    type Output = Counter<T, S>;
+
+    fn bitor(self, other: Self) -> Counter<T, S> {
+        self.union(other)
+    }
+}
+
+impl<T, S> BitAnd for &Counter<T, S>
+where
+    T: Eq + Hash + Clone,
+    S: BuildHasher + Clone,
+{
+    type Output = Counter<T, S>;
+
+    fn bitand(self, other: Self) -> Counter<T, S> {
+        self.intersection(other)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Counter;
+    use std::collections::HashMap;
+
+    #[test]
+    fn basics() {
+        let mut counter = Counter::new();
+
+        // Check empty counter behaves right
+        assert_eq!(counter.count("a"), 0);
+        assert!(!counter.remove("a"));
+        assert!(counter.most_common(3).is_empty());
+
+        // Populate counter
+        counter.extend("abracadabra".chars().map(String::from));
+        assert_eq!((counter.len(), counter.total()), (5, 11));
+        assert_eq!(counter.count("a"), 5);
+        assert_eq!(counter.count("z"), 0);
+        // b and r tie for second
+        let top = counter.most_common(2);
+        assert_eq!(top[0], (&"a".to_string(), 5));
+        assert_eq!(top[1].1, 2);
+
+        // Check normal removal
+        assert!(counter.remove("c"));
+        assert!(!counter.contains("c"));
+        assert_eq!(counter.remove_many("a", 2), 2);
+        assert_eq!(counter.remove_many("a", 10), 3);
+        assert_eq!(counter.remove_all("b"), 2);
+        assert_eq!((counter.len(), counter.total()), (2, 3));
+
+        // Push some more just to make sure nothing's corrupted
+        counter.add_many("d".to_string(), 4);
+        counter.add_many("e".to_string(), 0);
+        assert!(!counter.contains("e"));
+        assert_eq!(format!("{:?}", counter.most_common(1)), r#"[("d", 5)]"#);
+
+        // Check exhaustion
+        counter.clear();
+        assert!(counter.is_empty());
+        assert_eq!(counter.total(), 0);
+    }
+
+    #[test]
+    fn union_and_intersection() {
+        let a: Counter<char> = "xxxy".chars().collect();
+        let b: Counter<char> = "xzz".chars().collect();
+
+        let union = &a | &b;
+        assert_eq!(
+            (union.count(&'x'), union.count(&'y'), union.count(&'z')),
+            (3, 1, 2)
+        );
+        assert_eq!(union.total(), 6);
+
+        let intersection = &a & &b;
+        assert_eq!(intersection, "x".chars().collect());
+        assert_eq!(intersection.total(), 1);
+        assert!(!intersection.contains(&'y'));
+
+        assert!(intersection.is_subset(&a) && intersection.is_subset(&b));
+        assert!(a.is_subset(&union) && !a.is_subset(&b));
+        assert_eq!(&a & &Counter::new(), Counter::new());
+    }
+
+    #[test]
+    fn most_common() {
+        // Counts 1 to 50, so the order is unambiguous
+        let mut counter = Counter::new();
+        for i in 1..=50usize {
+            counter.add_many(i, i);
+        }
+        let top: Vec<_> = counter
+            .most_common(5)
+            .into_iter()
+            .map(|(&i, n)| (i, n))
+            .collect();
+        assert_eq!(top, [(50, 50), (49, 49), (48, 48), (47, 47), (46, 46)]);
+        assert!(counter.most_common(0).is_empty());
+        let all = counter.most_common(100);
+        assert_eq!(all.len(), 50);
+        assert!(all.windows(2).all(|w| w[0].1 > w[1].1));
+    }
+
+    #[test]
+    fn against_hash_map() {
+        let mut counter = Counter::new();
+        let mut model: HashMap<u32, usize> = HashMap::new();
+        let n = if cfg!(miri) { 1_000 } else { 20_000 };
+        let mut x: u32 = 1;
+        for _ in 0..n {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let item = (x >> 8) % 30;
+            let many = (x >> 16) as usize % 5;
+            match x >> 30 {
+                0 => {
+                    let count = model.entry(item).or_insert(0);
+                    let taken = many.min(*count);
+                    *count -= taken;
+                    assert_eq!(counter.remove_many(&item, many), taken);
+                }
+                1 if many == 0 => {
+                    let count = model.remove(&item).unwrap_or(0);
+                    assert_eq!(counter.remove_all(&item), count);
+                }
+                _ => {
+                    *model.entry(item).or_insert(0) += many;
+                    counter.add_many(item, many);
+                }
+            }
+            model.retain(|_, count| *count > 0);
+            assert_eq!(counter.len(), model.len());
+            assert_eq!(counter.total(), model.values().sum::<usize>());
+        }
+        for (&item, &count) in &model {
+            assert_eq!(counter.count(&item), count);
+        }
+    }
+}
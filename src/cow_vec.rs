@@ -0,0 +1,288 @@
+//! # Copy-on-write vector
+//!
+//! A vector whose clones share one buffer until one of them is changed. Cloning is O(1):
+//! it only bumps a reference count. The first change to a clone whose buffer is shared
+//! copies the whole buffer, so that the clone has one of its own, and changes after that
+//! are as cheap as a plain `Vec`'s until the next clone. That's `Arc::make_mut`:
+//!
+//! ```text
+//!   a = [1 2 3]      b = a.clone()     b.push(4)
+//!
+//!   a --> [1 2 3]    a --> [1 2 3]     a --> [1 2 3]
+//!                    b ----^           b --> [1 2 3 4]
+//! ```
+//!
+//! This makes snapshots of mutable state cheap when they're taken a lot more often than
+//! the state is changed in between. Each snapshot that's still around when a change comes
+//! costs a full copy, though. When the state is big and changes are small, a persistent
+//! structure like [`crate::persistent_vec::Vector`], which only copies the path to what
+//! changed, is the better fit.
+
+use std::fmt;
+use std::iter::FromIterator;
+use std::ops::{Deref, Index, IndexMut};
+use std::slice::SliceIndex;
+use std::sync::Arc;
+
+pub struct CowVec<T> {
+    vec: Arc<Vec<T>>,
+}
+
+impl<T> CowVec<T> {
+    /// Creates an empty CowVec.
+    pub fn new() -> Self {
+        CowVec {
+            vec: Arc::new(Vec::new()),
+        }
+    }
+
+    /// Returns whether another clone shares this one's buffer, so that the next change
+    /// will copy it.
+    pub fn is_shared(&self) -> bool {
+        Arc::strong_count(&self.vec) > 1
+    }
+
+    /// Returns whether the two share a buffer.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.vec, &other.vec)
+    }
+}
+
+impl<T: Clone> CowVec<T> {
+    /// Returns the vector to change as it likes, copying the buffer first if it's shared.
+    pub fn make_mut(&mut self) -> &mut Vec<T> {
+        Arc::make_mut(&mut self.vec)
+    }
+
+    pub fn push(&mut self, elem: T) {
+        self.make_mut().push(elem);
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        if self.is_empty() {
+            return None;
+        }
+        self.make_mut().pop()
+    }
+
+    /// Inserts `elem` at `index`, shifting everything after it along.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index > len`.
+    pub fn insert(&mut self, index: usize, elem: T) {
+        self.make_mut().insert(index, elem);
+    }
+
+    /// Removes and returns the element at `index`, shifting everything after it back.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn remove(&mut self, index: usize) -> T {
+        self.make_mut().remove(index)
+    }
+
+    /// Keeps the first `len` elements. A shared buffer only has those copied.
+    pub fn truncate(&mut self, len: usize) {
+        if len >= self.len() {
+            return;
+        }
+        match Arc::get_mut(&mut self.vec) {
+            Some(vec) => vec.truncate(len),
+            None => self.vec = Arc::new(self.vec[..len].to_vec()),
+        }
+    }
+
+    /// Removes everything. A shared buffer is just let go of, not copied.
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, T> {
+        self.make_mut().iter_mut()
+    }
+
+    /// Returns the elements as a `Vec`, copying them only if the buffer is shared.
+    pub fn into_vec(self) -> Vec<T> {
+        Arc::try_unwrap(self.vec).unwrap_or_else(|vec| (*vec).clone())
+    }
+}
+
+impl<T> Deref for CowVec<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        &self.vec
+    }
+}
+
+impl<T, I: SliceIndex<[T]>> Index<I> for CowVec<T> {
+    type Output = I::Output;
+
+    fn index(&self, index: I) -> &I::Output {
+        &self.vec[index]
+    }
+}
+
+impl<T: Clone, I: SliceIndex<[T]>> IndexMut<I> for CowVec<T> {
+    fn index_mut(&mut self, index: I) -> &mut I::Output {
+        &mut self.make_mut()[index]
+    }
+}
+
+impl<T> Clone for CowVec<T> {
+    fn clone(&self) -> Self {
+        CowVec {
+            vec: self.vec.clone(),
+        }
+    }
+}
+
+impl<T> Default for CowVec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> From<Vec<T>> for CowVec<T> {
+    fn from(vec: Vec<T>) -> Self {
+        CowVec { vec: Arc::new(vec) }
+    }
+}
+
+impl<T> FromIterator<T> for CowVec<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        CowVec::from(iter.into_iter().collect::<Vec<_>>())
+    }
+}
+
+impl<T: Clone> Extend<T> for CowVec<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        self.make_mut().extend(iter);
+    }
+}
+
+impl<'a, T> IntoIterator for &'a CowVec<T> {
+    type Item = &'a T;
+    type IntoIter = std::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T: PartialEq> PartialEq for CowVec<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.ptr_eq(other) || self.vec == other.vec
+    }
+}
+
+impl<T: Eq> Eq for CowVec<T> {}
+
+impl<T: fmt::Debug> fmt::Debug for CowVec<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::CowVec;
+
+    #[test]
+    fn basics() {
+        let mut vec = CowVec::new();
+
+        // Check empty vector behaves right
+        assert!(vec.is_empty());
+        assert_eq!(vec.pop(), None);
+
+        // Populate vector
+        vec.push(1);
+        vec.push(2);
+        vec.push(3);
+        assert_eq!(vec.len(), 3);
+        assert_eq!(vec[1], 2);
+
+        // Check normal removal
+        assert_eq!(vec.pop(), Some(3));
+        assert_eq!(vec.remove(0), 1);
+        assert_eq!(*vec, [2]);
+
+        // Push some more just to make sure nothing's corrupted
+        vec.insert(0, 1);
+        vec.extend(3..6);
+        vec[4] = 50;
+        assert_eq!(format!("{:?}", vec), "[1, 2, 3, 4, 50]");
+
+        // Check exhaustion
+        vec.clear();
+        assert!(vec.is_empty());
+        assert_eq!(vec.pop(), None);
+    }
+
+    #[test]
+    fn copies_on_first_write() {
+        let mut a: CowVec<u32> = (0..10).collect();
+        let b = a.clone();
+        assert!(a.ptr_eq(&b) && a.is_shared());
+
+        // Reading doesn't copy
+        assert_eq!(a.iter().sum::<u32>(), 45);
+        assert!(a.ptr_eq(&b));
+
+        // The first write does, and leaves the clone alone
+        a[0] = 100;
+        assert!(!a.ptr_eq(&b) && !a.is_shared() && !b.is_shared());
+        assert_eq!(b[0], 0);
+
+        // Later writes go straight to the copy
+        let buffer = a.as_ptr();
+        a[1] = 101;
+        for x in a.iter_mut() {
+            *x += 1;
+        }
+        assert_eq!(a.as_ptr(), buffer);
+        assert_eq!(a[..3], [101, 102, 3]);
+    }
+
+    #[test]
+    fn shrinking_shared() {
+        let mut a: CowVec<String> = (0..10).map(|i| i.to_string()).collect();
+        let b = a.clone();
+        a.truncate(3);
+        assert_eq!(*a, ["0", "1", "2"]);
+        assert_eq!(b.len(), 10);
+        a.clear();
+        assert!(a.is_empty() && !b.is_shared());
+
+        // The last owner gets the buffer back without a copy
+        let buffer = b.as_ptr();
+        let vec = b.into_vec();
+        assert_eq!(vec.as_ptr(), buffer);
+    }
+
+    #[test]
+    fn snapshots() {
+        // A snapshot after every step costs nothing until the next write
+        let mut state = CowVec::new();
+        let mut snapshots = Vec::new();
+        for i in 0..100 {
+            snapshots.push(state.clone());
+            state.push(i);
+            if i % 3 == 0 {
+                state[i / 2] *= 2;
+            }
+        }
+        let mut model = Vec::new();
+        for (i, snapshot) in snapshots.iter().enumerate() {
+            assert_eq!(snapshot.to_vec(), model);
+            model.push(i);
+            if i % 3 == 0 {
+                model[i / 2] *= 2;
+            }
+        }
+        assert_eq!(state.into_vec(), model);
+    }
+}
@@ -0,0 +1,445 @@
+//! # Cuckoo hash map
+//!
+//! A hash map where every key can only ever be in one of two slots, so a lookup checks two
+//! slots and is done: O(1) in the worst case, not just on average. Compare
+//! [`crate::robin_hood`], whose probe runs are short on average but have no fixed bound.
+//!
+//! The price is paid on insert (Pagh and Rodler, 2001). There are two tables, each with
+//! its own hash function, and a key's two slots are one in each. When both are taken, the
+//! new key takes its first slot anyway and kicks the resident out, like a cuckoo chick; the
+//! evicted key moves to its slot in the other table, maybe kicking out another, and so on:
+//!
+//! ```text
+//!   table 0   [ a ][   ][ c ][   ]      insert x: slot 2 in table 0, slot 0 in table 1
+//!   table 1   [ b ][   ][   ][ d ]
+//!
+//!   x kicks c out of table 0 slot 2, c's other slot is table 1 slot 3
+//!   c kicks d out of table 1, d's other slot is table 0 slot 1, which is free
+//!
+//!   table 0   [ a ][ d ][ x ][   ]
+//!   table 1   [ b ][   ][   ][ c ]
+//! ```
+//!
+//! Usually that settles in a few moves. Sometimes the evictions go round in a cycle, which
+//! shows up as a chain of kicks that's too long. Then the map picks two new hash functions
+//! and rehashes everything, which with the tables at most half full is rare enough that
+//! inserts stay O(1) amortized and expected. A map that keeps failing to rehash at one
+//! size grows instead.
+//!
+//! The two hash functions are the map's hasher with a random seed mixed in ahead of the
+//! key, fresh seeds for every rehash.
+
+use crate::rng::XorShift;
+use std::borrow::Borrow;
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+use std::iter::FromIterator;
+use std::mem;
+
+const MIN_SLOTS: usize = 8;
+
+/// How many evictions one insert may cause before it counts as a cycle.
+const MAX_KICKS: usize = 100;
+
+/// How many times to try new hash functions at one size before growing.
+const REHASHES_PER_SIZE: usize = 3;
+
+#[derive(Clone)]
+pub struct CuckooMap<K, V, S = RandomState> {
+    tables: [Vec<Option<(K, V)>>; 2],
+    seeds: [u64; 2],
+    len: usize,
+    // Rehashes because of a cycle, not counting ones to grow
+    rehashes: usize,
+    hasher: S,
+    rng: XorShift,
+}
+
+impl<K, V> CuckooMap<K, V, RandomState> {
+    /// Creates an empty CuckooMap.
+    pub fn new() -> Self {
+        Self::with_hasher(RandomState::new())
+    }
+
+    /// Creates an empty map with room for at least `capacity` entries before it grows.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_and_hasher(capacity, RandomState::new())
+    }
+}
+
+impl<K, V, S> CuckooMap<K, V, S> {
+    /// Creates an empty map that hashes keys with `hasher`. It allocates nothing until the
+    /// first insert.
+    pub fn with_hasher(hasher: S) -> Self {
+        let mut rng = XorShift::from_entropy();
+        CuckooMap {
+            tables: [Vec::new(), Vec::new()],
+            seeds: [rng.next_u64(), rng.next_u64()],
+            len: 0,
+            rehashes: 0,
+            hasher,
+            rng,
+        }
+    }
+
+    /// Creates an empty map with room for at least `capacity` entries, hashing keys with
+    /// `hasher`.
+    pub fn with_capacity_and_hasher(capacity: usize, hasher: S) -> Self {
+        let mut map = Self::with_hasher(hasher);
+        if capacity > 0 {
+            let slots = capacity.next_power_of_two().max(MIN_SLOTS);
+            map.tables = [empty_slots(slots), empty_slots(slots)];
+        }
+        map
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns how many entries fit before the next resize: half the slots, the load at
+    /// which cuckoo hashing with two tables stops working well.
+    pub fn capacity(&self) -> usize {
+        self.tables[0].len()
+    }
+
+    /// Returns how many times an insert ran into a cycle and the map had to rehash.
+    pub fn rehashes(&self) -> usize {
+        self.rehashes
+    }
+
+    /// Removes every entry, but keeps the slots.
+    pub fn clear(&mut self) {
+        for table in &mut self.tables {
+            table.iter_mut().for_each(|slot| *slot = None);
+        }
+        self.len = 0;
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.tables.iter().flatten().flatten().map(|(k, v)| (k, v))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&K, &mut V)> {
+        self.tables
+            .iter_mut()
+            .flatten()
+            .flatten()
+            .map(|(k, v)| (&*k, v))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.iter().map(|(k, _)| k)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.iter().map(|(_, v)| v)
+    }
+}
+
+fn empty_slots<K, V>(n: usize) -> Vec<Option<(K, V)>> {
+    (0..n).map(|_| None).collect()
+}
+
+impl<K, V, S> CuckooMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    /// The slot for `key` in table `t`.
+    fn slot<Q: Hash + ?Sized>(&self, t: usize, key: &Q) -> usize {
+        self.hasher.hash_one((self.seeds[t], key)) as usize & (self.tables[t].len() - 1)
+    }
+
+    /// Returns the table and slot holding `key`, if it's there.
+    fn find<Q>(&self, key: &Q) -> Option<(usize, usize)>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        if self.len == 0 {
+            return None;
+        }
+        (0..2).map(|t| (t, self.slot(t, key))).find(|&(t, i)| {
+            self.tables[t][i]
+                .as_ref()
+                .is_some_and(|(k, _)| k.borrow() == key)
+        })
+    }
+
+    /// Puts an entry known not to be in the map in one of its slots, kicking residents
+    /// along as needed. Gives back whichever entry is left without a slot after too many
+    /// kicks.
+    fn place(&mut self, mut entry: (K, V)) -> Result<(), (K, V)> {
+        let mut t = 0;
+        for _ in 0..MAX_KICKS {
+            let i = self.slot(t, &entry.0);
+            match self.tables[t][i].replace(entry) {
+                None => return Ok(()),
+                // The evicted key's other slot is in the other table
+                Some(evicted) => entry = evicted,
+            }
+            t = 1 - t;
+        }
+        Err(entry)
+    }
+
+    /// Picks new hash functions and puts every entry, plus the ones in `pending`, back in
+    /// tables of `slots` slots each, growing if that keeps running into cycles.
+    fn rehash(&mut self, mut pending: Vec<(K, V)>, mut slots: usize) {
+        let mut attempts = 0;
+        'retry: loop {
+            for table in &mut self.tables {
+                pending.extend(mem::take(table).into_iter().flatten());
+            }
+            self.tables = [empty_slots(slots), empty_slots(slots)];
+            self.seeds = [self.rng.next_u64(), self.rng.next_u64()];
+            while let Some(entry) = pending.pop() {
+                if let Err(homeless) = self.place(entry) {
+                    pending.push(homeless);
+                    self.rehashes += 1;
+                    attempts += 1;
+                    if attempts % REHASHES_PER_SIZE == 0 {
+                        slots *= 2;
+                    }
+                    continue 'retry;
+                }
+            }
+            return;
+        }
+    }
+
+    /// Inserts a key-value pair, returning the old value if the key was already there.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        if let Some(old) = self.get_mut(&key) {
+            return Some(mem::replace(old, value));
+        }
+        if self.len >= self.capacity() {
+            let slots = (self.capacity() * 2).max(MIN_SLOTS);
+            self.rehash(Vec::new(), slots);
+        }
+        if let Err(homeless) = self.place((key, value)) {
+            self.rehashes += 1;
+            let slots = self.capacity();
+            self.rehash(vec![homeless], slots);
+        }
+        self.len += 1;
+        None
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let (t, i) = self.find(key)?;
+        self.tables[t][i].as_ref().map(|(_, v)| v)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let (t, i) = self.find(key)?;
+        self.tables[t][i].as_mut().map(|(_, v)| v)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.find(key).is_some()
+    }
+
+    /// Removes a key, returning its value if it was there.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let (t, i) = self.find(key)?;
+        self.len -= 1;
+        self.tables[t][i].take().map(|(_, v)| v)
+    }
+}
+
+impl<K, V, S: Default> Default for CuckooMap<K, V, S> {
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+impl<K, V, S> fmt::Debug for CuckooMap<K, V, S>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K, V, S> Extend<(K, V)> for CuckooMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+impl<K, V, S> FromIterator<(K, V)> for CuckooMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher + Default,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = Self::default();
+        map.extend(iter);
+        map
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::CuckooMap;
+    use std::collections::HashMap;
+    use std::hash::{BuildHasher, Hash};
+    use std::rc::Rc;
+
+    /// Checks every entry sits in one of its two slots, and the count adds up.
+    fn check<K, V, S>(map: &CuckooMap<K, V, S>)
+    where
+        K: Eq + Hash,
+        S: BuildHasher,
+    {
+        let mut len = 0;
+        for (t, table) in map.tables.iter().enumerate() {
+            for (i, slot) in table.iter().enumerate() {
+                if let Some((k, _)) = slot {
+                    assert_eq!(map.slot(t, k), i);
+                    len += 1;
+                }
+            }
+        }
+        assert_eq!(len, map.len());
+        assert!(map.len() <= map.capacity());
+    }
+
+    #[test]
+    fn basics() {
+        let mut map = CuckooMap::new();
+
+        // Check empty map behaves right
+        assert_eq!(map.get("a"), None);
+        assert_eq!(map.remove("a"), None);
+        assert_eq!(map.capacity(), 0);
+
+        // Populate map
+        assert_eq!(map.insert("a".to_string(), 1), None);
+        assert_eq!(map.insert("b".to_string(), 2), None);
+        assert_eq!(map.insert("a".to_string(), 3), Some(1));
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get("a"), Some(&3));
+        *map.get_mut("b").unwrap() += 10;
+        check(&map);
+
+        // Check normal removal
+        assert_eq!(map.remove("a"), Some(3));
+        assert_eq!(map.remove("a"), None);
+        assert!(!map.contains_key("a"));
+        assert_eq!(format!("{:?}", map), r#"{"b": 12}"#);
+
+        // Push some more just to make sure nothing's corrupted, through a few resizes
+        for i in 0..100 {
+            map.insert(i.to_string(), i);
+        }
+        assert_eq!(map.len(), 101);
+        check(&map);
+
+        // Check exhaustion
+        map.clear();
+        assert!(map.is_empty());
+        assert_eq!(map.get("b"), None);
+        assert!(map.capacity() >= 101);
+    }
+
+    #[test]
+    fn cycles_rehash() {
+        // Filling a small table right up to half full runs into cycles now and then,
+        // and every entry must survive the rehash
+        let mut map = CuckooMap::with_capacity(64);
+        let mut total = 0;
+        for round in 0..if cfg!(miri) { 10 } else { 200 } {
+            map.clear();
+            let rehashes = map.rehashes();
+            for i in 0..64u32 {
+                map.insert(round * 1000 + i, i);
+            }
+            total += map.rehashes() - rehashes;
+            check(&map);
+            assert!((0..64).all(|i| map.get(&(round * 1000 + i)) == Some(&i)));
+        }
+        if !cfg!(miri) {
+            assert!(total > 0, "never rehashed");
+        }
+    }
+
+    #[test]
+    fn drops_what_it_holds() {
+        let token = Rc::new(());
+        {
+            let mut map = CuckooMap::new();
+            for i in 0..20 {
+                map.insert(i, token.clone());
+            }
+            map.insert(0, token.clone());
+            map.remove(&1);
+            assert_eq!(Rc::strong_count(&token), 20);
+            let copy = map.clone();
+            assert_eq!(copy.len(), 19);
+            assert_eq!(Rc::strong_count(&token), 39);
+        }
+        assert_eq!(Rc::strong_count(&token), 1);
+    }
+
+    #[test]
+    fn against_hash_map() {
+        let mut map = CuckooMap::new();
+        let mut model = HashMap::new();
+        let n = if cfg!(miri) { 1_000 } else { 50_000 };
+        let mut x: u32 = 1;
+        for i in 0..n {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let key = (x >> 8) % 2_000;
+            match x >> 30 {
+                0 => assert_eq!(map.remove(&key), model.remove(&key)),
+                1 => assert_eq!(map.get(&key), model.get(&key)),
+                _ => assert_eq!(map.insert(key, i), model.insert(key, i)),
+            }
+            assert_eq!(map.len(), model.len());
+            if i % 1000 == 0 {
+                check(&map);
+            }
+        }
+        check(&map);
+        for (k, v) in map.iter_mut() {
+            *v += k;
+        }
+        let mut entries: Vec<_> = map.iter().map(|(&k, &v)| (k, v)).collect();
+        entries.sort_unstable();
+        let mut expected: Vec<_> = model.into_iter().map(|(k, v)| (k, v + k)).collect();
+        expected.sort_unstable();
+        assert_eq!(entries, expected);
+    }
+}
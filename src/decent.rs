@@ -17,7 +17,7 @@ impl<T> List<T> {
 
     pub fn push(&mut self, elem: T) {
         let new_node = Box::new(Node {
-            elem: elem,
+            elem,
             next: self.head.take(),
         });
 
@@ -40,6 +40,12 @@ impl<T> List<T> {
     }
 }
 
+impl<T> Default for List<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// We can't drop the contents of the `Box` _after_ deallocating, so there's no way to drop in a tail-recursive manner.
 /// Instead we're going to have to manually write an iterative drop for `List` that hoists nodes out of their boxes.
 impl<T> Drop for List<T> {
@@ -59,8 +65,11 @@ impl<T> Drop for List<T> {
 
 pub struct IntoIter<T>(List<T>);
 
-impl<T> List<T> {
-    pub fn into_iter(self) -> IntoIter<T> {
+impl<T> IntoIterator for List<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
         IntoIter(self)
     }
 }
@@ -80,13 +89,13 @@ pub struct Iter<'a, T> {
 
 // No lifetime here, List doesn't have any associated lifetimes
 impl<T> List<T> {
-    pub fn iter(&self) -> Iter<T> {
+    pub fn iter(&self) -> Iter<'_, T> {
         // We declare a fresh lifetime here for the *exact* borrow that
         // creates the iter. Now &self needs to be valid as long as the
         // Iter is around.
         Iter {
-            next: self.head.as_ref().map(|node| &**node),
-        } // that &** thing is really janky. Normally Rust is very good at
+            next: self.head.as_deref(),
+        } // `as_deref` replaces the janky `.map(|node| &**node)`. Normally Rust is very good at
           // doing this kind of conversion implicitly, through a process called deref coercion
     }
 }
@@ -100,7 +109,7 @@ impl<'a, T> Iterator for Iter<'a, T> {
     // Self continues to be incredibly hype and amazing
     fn next(&mut self) -> Option<Self::Item> {
         self.next.map(|node| {
-            self.next = node.next.as_ref().map(|node| &**node);
+            self.next = node.next.as_deref();
             &node.elem
         })
     }
@@ -123,9 +132,9 @@ pub struct IterMut<'a, T> {
 }
 
 impl<T> List<T> {
-    pub fn iter_mut(&mut self) -> IterMut<T> {
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
         IterMut {
-            next: self.head.as_mut().map(|node| &mut **node),
+            next: self.head.as_deref_mut(),
         }
     }
 }
@@ -135,7 +144,7 @@ impl<'a, T> Iterator for IterMut<'a, T> {
 
     fn next(&mut self) -> Option<Self::Item> {
         self.next.take().map(|node| {
-            self.next = node.next.as_mut().map(|node| &mut **node);
+            self.next = node.next.as_deref_mut();
             &mut node.elem
         })
     }
@@ -188,7 +197,9 @@ mod test {
         assert_eq!(list.peek_mut(), Some(&mut 3));
 
         // Check to see if we could mutate that peek_mut return value
-        list.peek_mut().map(|value| *value = 42);
+        if let Some(value) = list.peek_mut() {
+            *value = 42;
+        }
 
         assert_eq!(list.peek(), Some(&42));
         assert_eq!(list.pop(), Some(42));
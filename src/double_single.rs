@@ -1,9 +1,33 @@
 //! # Double Singly-Linked List
 //!
 //! We smash our list into two halves: one going to the left, and one going to the right
+//!
+//! Think of it as a list with a finger in it. The finger sits in the gap between the two
+//! stacks: the top of `left` is the element just left of the finger and the top of `right`
+//! is the element just right of it. Reading the list in order means reading `left` from the
+//! bottom up and then `right` from the top down.
+//!
+//! ```text
+//! logical:   [a, b, c, | d, e]
+//! left:      c -> b -> a
+//! right:     d -> e
+//! ```
+//!
+//! The element just right of the finger is the *focus*, the one the finger is pointing at,
+//! like a block cursor in a text editor. With the finger at the very end there is no focus.
+//!
+//! Everything we do at the finger is O(1), because it only ever touches the heads of the
+//! two stacks. Moving the finger just pops a node off one stack and pushes it onto the other.
+
+use std::fmt;
+use std::iter::Rev;
+use std::mem;
+use std::ops::Deref;
+use std::vec;
 
 pub struct Stack<T> {
     head: Link<T>,
+    len: usize,
 }
 
 type Link<T> = Option<Box<Node<T>>>;
@@ -15,14 +39,12 @@ struct Node<T> {
 
 impl<T> Stack<T> {
     pub fn new() -> Self {
-        Stack { head: None }
+        Stack { head: None, len: 0 }
     }
 
+    /// Pushes an element onto the top of the stack.
     pub fn push(&mut self, elem: T) {
-        let new_node = Box::new(Node {
-            elem: elem,
-            next: None,
-        });
+        let new_node = Box::new(Node { elem, next: None });
 
         self.push_node(new_node);
     }
@@ -30,8 +52,10 @@ impl<T> Stack<T> {
     fn push_node(&mut self, mut node: Box<Node<T>>) {
         node.next = self.head.take();
         self.head = Some(node);
+        self.len += 1;
     }
 
+    /// Removes and returns the top element.
     pub fn pop(&mut self) -> Option<T> {
         self.pop_node().map(|node| node.elem)
     }
@@ -39,19 +63,53 @@ impl<T> Stack<T> {
     fn pop_node(&mut self) -> Option<Box<Node<T>>> {
         self.head.take().map(|mut node| {
             self.head = node.next.take();
+            self.len -= 1;
             node
         })
     }
 
+    /// Borrows the top element.
     pub fn peek(&self) -> Option<&T> {
         self.head.as_ref().map(|node| &node.elem)
     }
 
+    /// Mutably borrows the top element.
     pub fn peek_mut(&mut self) -> Option<&mut T> {
         self.head.as_mut().map(|node| &mut node.elem)
     }
+
+    /// Returns the number of elements. Kept as a running count, so this is O(1).
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.head.is_none()
+    }
+
+    /// Iterates from the top of the stack down.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            next: self.head.as_deref(),
+        }
+    }
+
+    /// Mutably iterates from the top of the stack down.
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        IterMut {
+            next: self.head.as_deref_mut(),
+        }
+    }
+}
+
+impl<T> Default for Stack<T> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
+/// Hoists each node out of its box before dropping it, so a long stack doesn't recurse once
+/// per node on the way down.
 impl<T> Drop for Stack<T> {
     fn drop(&mut self) {
         let mut cur_link = self.head.take();
@@ -61,6 +119,56 @@ impl<T> Drop for Stack<T> {
     }
 }
 
+pub struct Iter<'a, T> {
+    next: Option<&'a Node<T>>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next.map(|node| {
+            self.next = node.next.as_deref();
+            &node.elem
+        })
+    }
+}
+
+pub struct IterMut<'a, T> {
+    next: Option<&'a mut Node<T>>,
+}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next.take().map(|node| {
+            self.next = node.next.as_deref_mut();
+            &mut node.elem
+        })
+    }
+}
+
+/// Pops elements off the top until the stack is empty.
+pub struct IntoIter<T>(Stack<T>);
+
+impl<T> IntoIterator for Stack<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter(self)
+    }
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.pop()
+    }
+}
+
 /// A general purpose list.
 /// We can grow the list leftwards or rightwards by pushing onto either stack.
 pub struct List<T> {
@@ -77,32 +185,86 @@ impl<T> List<T> {
         }
     }
 
+    /// Inserts an element just left of the finger.
     pub fn push_left(&mut self, elem: T) {
         self.left.push(elem)
     }
+    /// Inserts an element just right of the finger.
     pub fn push_right(&mut self, elem: T) {
         self.right.push(elem)
     }
+    /// Removes the element just left of the finger.
     pub fn pop_left(&mut self) -> Option<T> {
         self.left.pop()
     }
+    /// Removes the element just right of the finger.
     pub fn pop_right(&mut self) -> Option<T> {
         self.right.pop()
     }
+    /// Borrows the element just left of the finger.
     pub fn peek_left(&self) -> Option<&T> {
         self.left.peek()
     }
+    /// Borrows the element just right of the finger.
     pub fn peek_right(&self) -> Option<&T> {
         self.right.peek()
     }
+    /// Mutably borrows the element just left of the finger.
     pub fn peek_left_mut(&mut self) -> Option<&mut T> {
         self.left.peek_mut()
     }
+    /// Mutably borrows the element just right of the finger.
     pub fn peek_right_mut(&mut self) -> Option<&mut T> {
         self.right.peek_mut()
     }
+    /// Borrows the focus. This is the same element as `peek_right`, named for what it means
+    /// to a caller using the list as a cursor.
+    pub fn peek_focus(&self) -> Option<&T> {
+        self.right.peek()
+    }
+    /// Mutably borrows the focus.
+    pub fn peek_focus_mut(&mut self) -> Option<&mut T> {
+        self.right.peek_mut()
+    }
+
+    /// Returns the total number of elements on both sides of the finger.
+    pub fn len(&self) -> usize {
+        self.left.len() + self.right.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.left.is_empty() && self.right.is_empty()
+    }
+
+    /// Returns the index of the focus, which is the number of elements left of the finger.
+    pub fn position(&self) -> usize {
+        self.left.len()
+    }
+
+    /// Iterates over the left half, starting next to the finger and moving away from it.
+    pub fn iter_left(&self) -> Iter<'_, T> {
+        self.left.iter()
+    }
+
+    /// Iterates over the right half, starting at the focus and moving away from the finger.
+    pub fn iter_right(&self) -> Iter<'_, T> {
+        self.right.iter()
+    }
+
+    /// Iterates over the whole list in logical (front to back) order, wherever the finger is.
+    ///
+    /// The left stack is stored back to front and a singly-linked stack can't be walked
+    /// backwards, so the left half is buffered before being handed out in reverse.
+    pub fn iter(&self) -> ListIter<'_, T> {
+        let left: Vec<&T> = self.left.iter().collect();
+        ListIter {
+            left: left.into_iter().rev(),
+            right: self.right.iter(),
+        }
+    }
 
     /// We can also "walk" along the list by popping values off one end and onto the other.
+    /// Returns false if the finger is already at the front.
     pub fn go_left(&mut self) -> bool {
         self.left
             .pop_node()
@@ -112,6 +274,7 @@ impl<T> List<T> {
             .is_some()
     }
 
+    /// Moves the finger one step right. Returns false if it is already at the back.
     pub fn go_right(&mut self) -> bool {
         self.right
             .pop_node()
@@ -120,11 +283,428 @@ impl<T> List<T> {
             })
             .is_some()
     }
+
+    /// Cuts the list at the finger and hands back both halves as independent stacks.
+    ///
+    /// Each stack is ordered from the finger outwards: the top of the left one is the element
+    /// just before the finger and the top of the right one is the focus.
+    pub fn split(self) -> (Stack<T>, Stack<T>) {
+        (self.left, self.right)
+    }
+
+    /// Glues two stacks back together with the finger between them. The inverse of `split`.
+    pub fn join(left: Stack<T>, right: Stack<T>) -> Self {
+        List { left, right }
+    }
+
+    /// Inserts an element before the focus. The focus stays where it is.
+    pub fn insert_before(&mut self, elem: T) {
+        self.left.push(elem);
+    }
+
+    /// Inserts an element after the focus. The focus stays where it is; if there is no focus
+    /// (the finger is at the back) the new element becomes the focus.
+    pub fn insert_after(&mut self, elem: T) {
+        match self.right.pop_node() {
+            Some(focus) => {
+                self.right.push(elem);
+                self.right.push_node(focus);
+            }
+            None => self.right.push(elem),
+        }
+    }
+
+    /// Swaps the focus for `elem`, returning the old focus. If there is no focus, `elem`
+    /// becomes the focus and we return `None`.
+    pub fn replace(&mut self, elem: T) -> Option<T> {
+        match self.right.peek_mut() {
+            Some(focus) => Some(mem::replace(focus, elem)),
+            None => {
+                self.right.push(elem);
+                None
+            }
+        }
+    }
+
+    /// Transforms the focus in place by value. The node is reused, so unlike a pop/push pair
+    /// this doesn't allocate. Returns false if there is no focus.
+    pub fn map_focus<F>(&mut self, f: F) -> bool
+    where
+        F: FnOnce(T) -> T,
+    {
+        match self.right.pop_node() {
+            Some(mut node) => {
+                node.elem = f(node.elem);
+                self.right.push_node(node);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes and returns the focus. The element after it becomes the new focus.
+    pub fn remove_at_focus(&mut self) -> Option<T> {
+        self.right.pop()
+    }
+
+    /// Moves the finger all the way to the front.
+    pub fn seek_to_front(&mut self) {
+        while self.go_left() {}
+    }
+
+    /// Moves the finger all the way to the back.
+    pub fn seek_to_back(&mut self) {
+        while self.go_right() {}
+    }
+
+    /// Moves the finger so that exactly `n` elements are left of it, making the element at
+    /// index `n` the focus. If the list is shorter than that the finger stops at the back and
+    /// we return false.
+    pub fn seek(&mut self, n: usize) -> bool {
+        let mut position = self.position();
+        while position > n {
+            self.go_left();
+            position -= 1;
+        }
+        while position < n {
+            if !self.go_right() {
+                return false;
+            }
+            position += 1;
+        }
+        true
+    }
+}
+
+pub struct ListIter<'a, T> {
+    left: Rev<vec::IntoIter<&'a T>>,
+    right: Iter<'a, T>,
+}
+
+impl<'a, T> Iterator for ListIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.left.next().or_else(|| self.right.next())
+    }
+}
+
+impl<T> Default for List<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// # Edit history
+///
+/// Wraps a `List` and records every edit and finger move so it can be undone and redone.
+///
+/// The trick is that each recorded `Edit` is a tiny instruction whose application hands back
+/// the instruction that reverses it (`PushLeft(x)` gives `PopLeft`, `PopLeft` gives back
+/// `PushLeft(x)`, and so on). Undo applies the top of the undo stack and parks the result on
+/// the redo stack; redo does the opposite. Both logs are just the `Stack` from this module.
+///
+/// Reading goes straight through to the list via `Deref`.
+pub struct History<T> {
+    list: List<T>,
+    undo: Stack<Edit<T>>,
+    redo: Stack<Edit<T>>,
+}
+
+enum Edit<T> {
+    PushLeft(T),
+    PopLeft,
+    PushRight(T),
+    PopRight,
+    InsertAfter(T),
+    RemoveAfter,
+    Replace(T),
+    GoLeft,
+    GoRight,
+}
+
+impl<T> Edit<T> {
+    /// Performs the edit and returns the edit that undoes it.
+    fn apply(self, list: &mut List<T>) -> Edit<T> {
+        match self {
+            Edit::PushLeft(elem) => {
+                list.push_left(elem);
+                Edit::PopLeft
+            }
+            Edit::PopLeft => Edit::PushLeft(list.pop_left().unwrap()),
+            Edit::PushRight(elem) => {
+                list.push_right(elem);
+                Edit::PopRight
+            }
+            Edit::PopRight => Edit::PushRight(list.pop_right().unwrap()),
+            Edit::InsertAfter(elem) => {
+                list.insert_after(elem);
+                Edit::RemoveAfter
+            }
+            Edit::RemoveAfter => {
+                let focus = list.right.pop_node().unwrap();
+                let after = list.right.pop().unwrap();
+                list.right.push_node(focus);
+                Edit::InsertAfter(after)
+            }
+            Edit::Replace(elem) => Edit::Replace(list.replace(elem).unwrap()),
+            Edit::GoLeft => {
+                list.go_left();
+                Edit::GoRight
+            }
+            Edit::GoRight => {
+                list.go_right();
+                Edit::GoLeft
+            }
+        }
+    }
+}
+
+impl<T> History<T> {
+    /// Starts recording edits to `list`.
+    pub fn new(list: List<T>) -> Self {
+        History {
+            list,
+            undo: Stack::new(),
+            redo: Stack::new(),
+        }
+    }
+
+    /// Stops recording and hands back the list.
+    pub fn into_inner(self) -> List<T> {
+        self.list
+    }
+
+    /// Applies a fresh edit. Anything that was undone can no longer be redone.
+    fn record(&mut self, edit: Edit<T>) {
+        let inverse = edit.apply(&mut self.list);
+        self.undo.push(inverse);
+        self.redo = Stack::new();
+    }
+
+    /// Same as `List::insert_before`, but recorded.
+    pub fn insert_before(&mut self, elem: T) {
+        self.record(Edit::PushLeft(elem));
+    }
+
+    /// Same as `List::insert_after`, but recorded.
+    pub fn insert_after(&mut self, elem: T) {
+        if self.list.peek_focus().is_some() {
+            self.record(Edit::InsertAfter(elem));
+        } else {
+            self.record(Edit::PushRight(elem));
+        }
+    }
+
+    /// Removes the focus, keeping it in the history so an undo can put it back.
+    /// Returns false if there was no focus.
+    pub fn remove_at_focus(&mut self) -> bool {
+        if self.list.peek_focus().is_none() {
+            return false;
+        }
+        self.record(Edit::PopRight);
+        true
+    }
+
+    /// Same as `List::replace`, except the old focus is kept in the history.
+    pub fn replace(&mut self, elem: T) {
+        if self.list.peek_focus().is_some() {
+            self.record(Edit::Replace(elem));
+        } else {
+            self.record(Edit::PushRight(elem));
+        }
+    }
+
+    /// Moves the finger left, recording the move. Returns false at the front.
+    pub fn go_left(&mut self) -> bool {
+        if self.list.position() == 0 {
+            return false;
+        }
+        self.record(Edit::GoLeft);
+        true
+    }
+
+    /// Moves the finger right, recording the move. Returns false at the back.
+    pub fn go_right(&mut self) -> bool {
+        if self.list.peek_focus().is_none() {
+            return false;
+        }
+        self.record(Edit::GoRight);
+        true
+    }
+
+    /// Reverts the most recent edit. Returns false if there is nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        match self.undo.pop() {
+            Some(edit) => {
+                let inverse = edit.apply(&mut self.list);
+                self.redo.push(inverse);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Re-applies the most recently undone edit. Returns false if there is nothing to redo.
+    pub fn redo(&mut self) -> bool {
+        match self.redo.pop() {
+            Some(edit) => {
+                let inverse = edit.apply(&mut self.list);
+                self.undo.push(inverse);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl<T> Deref for History<T> {
+    type Target = List<T>;
+
+    fn deref(&self) -> &List<T> {
+        &self.list
+    }
+}
+
+/// # Text zipper
+///
+/// A gap buffer for text made out of the list above: the finger is the cursor and the gap
+/// between the two stacks is where typing happens. Typing and deleting next to the cursor
+/// only touch the stack heads, so they're O(1) no matter how big the buffer is. Moving the
+/// cursor by `n` costs O(n).
+pub struct TextZipper {
+    chars: List<char>,
+}
+
+impl TextZipper {
+    /// Creates an empty TextZipper.
+    pub fn new() -> Self {
+        TextZipper { chars: List::new() }
+    }
+
+    /// Types a character at the cursor, leaving the cursor after it.
+    pub fn insert_char(&mut self, c: char) {
+        self.chars.push_left(c);
+    }
+
+    /// Types a whole string at the cursor.
+    pub fn insert_str(&mut self, s: &str) {
+        for c in s.chars() {
+            self.insert_char(c);
+        }
+    }
+
+    /// Deletes the character before the cursor, like backspace.
+    pub fn delete_backward(&mut self) -> Option<char> {
+        self.chars.pop_left()
+    }
+
+    /// Deletes the character after the cursor, like the delete key.
+    pub fn delete_forward(&mut self) -> Option<char> {
+        self.chars.pop_right()
+    }
+
+    /// Moves the cursor `n` characters, right for positive and left for negative values.
+    /// Stops at either end of the buffer, returning false if it couldn't go all the way.
+    pub fn move_cursor(&mut self, n: isize) -> bool {
+        let steps = n.unsigned_abs();
+        (0..steps).all(|_| {
+            if n < 0 {
+                self.chars.go_left()
+            } else {
+                self.chars.go_right()
+            }
+        })
+    }
+
+    /// Returns the cursor position in characters.
+    pub fn cursor(&self) -> usize {
+        self.chars.position()
+    }
+
+    /// Returns the length of the buffer in characters.
+    pub fn len(&self) -> usize {
+        self.chars.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chars.is_empty()
+    }
+}
+
+impl Default for TextZipper {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for TextZipper {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in self.chars.iter() {
+            write!(f, "{}", c)?;
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use super::List;
+    use super::{History, List, Stack, TextZipper};
+
+    #[test]
+    fn stack_basics() {
+        let mut stack = Stack::new();
+        assert_eq!(stack.pop(), None);
+        assert_eq!(stack.peek(), None);
+        assert!(stack.is_empty());
+
+        stack.push(1);
+        stack.push(2);
+        stack.push(3);
+        assert_eq!(stack.len(), 3);
+        assert_eq!(stack.peek(), Some(&3));
+
+        if let Some(value) = stack.peek_mut() {
+            *value = 30;
+        }
+        assert_eq!(stack.pop(), Some(30));
+        assert_eq!(stack.pop(), Some(2));
+
+        stack.push(4);
+        assert_eq!(stack.pop(), Some(4));
+        assert_eq!(stack.pop(), Some(1));
+        assert_eq!(stack.pop(), None);
+        assert_eq!(stack.len(), 0);
+    }
+
+    #[test]
+    fn stack_iters() {
+        let mut stack = Stack::new();
+        stack.push(1);
+        stack.push(2);
+        stack.push(3);
+
+        assert_eq!(stack.iter().collect::<Vec<_>>(), vec![&3, &2, &1]);
+
+        for value in stack.iter_mut() {
+            *value *= 10;
+        }
+        assert_eq!(stack.iter().collect::<Vec<_>>(), vec![&30, &20, &10]);
+
+        let mut iter = stack.into_iter();
+        assert_eq!(iter.next(), Some(30));
+        assert_eq!(iter.next(), Some(20));
+        assert_eq!(iter.next(), Some(10));
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn long_stack_drop() {
+        let mut stack = Stack::new();
+        for i in 0..100_000 {
+            stack.push(i);
+        }
+    }
 
     #[test]
     fn walk_around() {
@@ -154,4 +734,319 @@ mod test {
         assert_eq!(list.pop_right(), None);
         assert_eq!(list.pop_left(), None);
     }
+
+    #[test]
+    fn walk_right() {
+        let mut list = List::new();
+
+        // Build [1, 2, 3] with the finger at the front
+        list.push_right(3);
+        list.push_right(2);
+        list.push_right(1);
+        assert_eq!(list.peek_left(), None);
+
+        assert!(list.go_right());
+        assert_eq!(list.peek_left(), Some(&1));
+        assert_eq!(list.peek_right(), Some(&2));
+
+        assert!(list.go_right());
+        assert!(list.go_right());
+        assert!(!list.go_right());
+        assert_eq!(list.peek_left(), Some(&3));
+        assert_eq!(list.peek_right(), None);
+
+        // Walking back restores the order
+        assert!(list.go_left());
+        assert_eq!(list.pop_right(), Some(3));
+        assert_eq!(list.pop_left(), Some(2));
+        assert_eq!(list.pop_left(), Some(1));
+        assert!(!list.go_left());
+    }
+
+    #[test]
+    fn empty() {
+        let mut list: List<i32> = List::new();
+        assert!(!list.go_left());
+        assert!(!list.go_right());
+        assert_eq!(list.pop_left(), None);
+        assert_eq!(list.pop_right(), None);
+        assert_eq!(list.peek_left(), None);
+        assert_eq!(list.peek_right(), None);
+    }
+
+    #[test]
+    fn long_list_drop() {
+        // Dropping must not recurse once per node
+        let mut list = List::new();
+        for i in 0..100_000 {
+            list.push_left(i);
+            list.push_right(i);
+        }
+    }
+
+    #[test]
+    fn peek() {
+        let mut list = List::new();
+        assert_eq!(list.peek_focus(), None);
+        assert_eq!(list.peek_focus_mut(), None);
+
+        // [1, | 2, 3]
+        list.push_right(3);
+        list.push_right(2);
+        list.push_left(1);
+        assert_eq!(list.peek_left(), Some(&1));
+        assert_eq!(list.peek_right(), Some(&2));
+        assert_eq!(list.peek_focus(), Some(&2));
+
+        if let Some(value) = list.peek_focus_mut() {
+            *value = 20;
+        }
+        if let Some(value) = list.peek_left_mut() {
+            *value = 10;
+        }
+        assert_eq!(list.peek_focus(), Some(&20));
+        assert_eq!(list.peek_left(), Some(&10));
+
+        // At the back there is no focus
+        list.go_right();
+        list.go_right();
+        assert_eq!(list.peek_focus(), None);
+        assert_eq!(list.peek_left(), Some(&3));
+    }
+
+    #[test]
+    fn iter() {
+        let mut list = List::new();
+
+        // [1, 2, 3, | 4, 5]
+        list.push_left(1);
+        list.push_left(2);
+        list.push_left(3);
+        list.push_right(5);
+        list.push_right(4);
+
+        assert_eq!(list.iter_left().collect::<Vec<_>>(), vec![&3, &2, &1]);
+        assert_eq!(list.iter_right().collect::<Vec<_>>(), vec![&4, &5]);
+        assert_eq!(list.iter().collect::<Vec<_>>(), vec![&1, &2, &3, &4, &5]);
+
+        // The logical order doesn't depend on the finger
+        while list.go_left() {}
+        assert_eq!(list.iter_left().next(), None);
+        assert_eq!(list.iter().collect::<Vec<_>>(), vec![&1, &2, &3, &4, &5]);
+        while list.go_right() {}
+        assert_eq!(list.iter_right().next(), None);
+        assert_eq!(list.iter().collect::<Vec<_>>(), vec![&1, &2, &3, &4, &5]);
+    }
+
+    #[test]
+    fn seek() {
+        let mut list = List::new();
+        for i in (0..5).rev() {
+            list.push_right(i);
+        }
+
+        list.seek_to_back();
+        assert_eq!(list.peek_left(), Some(&4));
+        assert_eq!(list.peek_focus(), None);
+
+        list.seek_to_front();
+        assert_eq!(list.peek_left(), None);
+        assert_eq!(list.peek_focus(), Some(&0));
+
+        assert!(list.seek(3));
+        assert_eq!(list.peek_focus(), Some(&3));
+        assert!(list.seek(1));
+        assert_eq!(list.peek_focus(), Some(&1));
+
+        // Seeking onto the end is fine, past it is not
+        assert!(list.seek(5));
+        assert_eq!(list.peek_focus(), None);
+        assert!(!list.seek(7));
+        assert_eq!(list.peek_left(), Some(&4));
+        assert_eq!(list.iter().collect::<Vec<_>>(), vec![&0, &1, &2, &3, &4]);
+    }
+
+    #[test]
+    fn edit_at_focus() {
+        let mut list = List::new();
+
+        // No focus yet, so insert_after creates one
+        list.insert_after(2);
+        assert_eq!(list.peek_focus(), Some(&2));
+
+        list.insert_before(1);
+        list.insert_after(4);
+        list.insert_after(3);
+        assert_eq!(list.peek_focus(), Some(&2));
+        assert_eq!(list.iter().collect::<Vec<_>>(), vec![&1, &2, &3, &4]);
+
+        assert_eq!(list.remove_at_focus(), Some(2));
+        assert_eq!(list.peek_focus(), Some(&3));
+        assert_eq!(list.iter().collect::<Vec<_>>(), vec![&1, &3, &4]);
+
+        list.seek_to_back();
+        assert_eq!(list.remove_at_focus(), None);
+        assert_eq!(list.iter().collect::<Vec<_>>(), vec![&1, &3, &4]);
+    }
+
+    #[test]
+    fn lengths() {
+        let mut list = List::new();
+        assert!(list.is_empty());
+        assert_eq!(list.len(), 0);
+        assert_eq!(list.position(), 0);
+
+        list.push_left(1);
+        list.push_left(2);
+        list.push_right(3);
+        assert!(!list.is_empty());
+        assert_eq!(list.len(), 3);
+        assert_eq!(list.position(), 2);
+
+        list.go_left();
+        assert_eq!(list.position(), 1);
+        list.insert_after(4);
+        list.insert_before(5);
+        assert_eq!(list.len(), 5);
+        assert_eq!(list.position(), 2);
+
+        list.seek(4);
+        assert_eq!(list.position(), 4);
+        list.remove_at_focus();
+        list.pop_left();
+        assert_eq!(list.len(), 3);
+        assert_eq!(list.position(), 3);
+
+        list.seek_to_front();
+        assert_eq!(list.position(), 0);
+        assert_eq!(list.len(), 3);
+    }
+
+    #[test]
+    fn text_zipper() {
+        let mut text = TextZipper::new();
+        assert!(text.is_empty());
+
+        text.insert_str("helo world");
+        assert_eq!(text.to_string(), "helo world");
+        assert_eq!(text.cursor(), 10);
+
+        // Fix the typo
+        assert!(text.move_cursor(-7));
+        text.insert_char('l');
+        assert_eq!(text.to_string(), "hello world");
+        assert_eq!(text.cursor(), 4);
+
+        // Backspace and delete
+        assert!(text.move_cursor(2));
+        assert_eq!(text.delete_backward(), Some(' '));
+        assert_eq!(text.delete_forward(), Some('w'));
+        text.insert_str(", W");
+        assert_eq!(text.to_string(), "hello, World");
+        assert_eq!(text.len(), 12);
+
+        // Moving past the ends stops there
+        assert!(!text.move_cursor(-100));
+        assert_eq!(text.cursor(), 0);
+        assert_eq!(text.delete_backward(), None);
+        assert!(!text.move_cursor(100));
+        assert_eq!(text.cursor(), 12);
+        assert_eq!(text.delete_forward(), None);
+    }
+
+    #[test]
+    fn split_join() {
+        let mut list = List::new();
+        for i in (0..5).rev() {
+            list.push_right(i);
+        }
+        list.seek(2);
+
+        let (left, right) = list.split();
+        assert_eq!(left.iter().collect::<Vec<_>>(), vec![&1, &0]);
+        assert_eq!(right.iter().collect::<Vec<_>>(), vec![&2, &3, &4]);
+
+        // Each half is a list of its own
+        let tail = List::join(Stack::new(), right);
+        assert_eq!(tail.position(), 0);
+        assert_eq!(tail.iter().collect::<Vec<_>>(), vec![&2, &3, &4]);
+
+        // Putting the halves back together restores the list and the finger
+        let (_, right) = tail.split();
+        let list = List::join(left, right);
+        assert_eq!(list.position(), 2);
+        assert_eq!(list.len(), 5);
+        assert_eq!(list.iter().collect::<Vec<_>>(), vec![&0, &1, &2, &3, &4]);
+
+        let list: List<i32> = List::join(Stack::new(), Stack::new());
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    fn replace_and_map_focus() {
+        let mut list = List::new();
+        assert!(!list.map_focus(|x: i32| x + 1));
+
+        // No focus, so replace inserts one
+        assert_eq!(list.replace(1), None);
+        assert_eq!(list.peek_focus(), Some(&1));
+
+        list.push_right(0);
+        assert_eq!(list.replace(10), Some(0));
+        assert!(list.map_focus(|x| x * 2));
+        assert_eq!(list.iter().collect::<Vec<_>>(), vec![&20, &1]);
+        assert_eq!(list.len(), 2);
+
+        // Works on non-Copy types that have to be moved through the closure
+        let mut words = List::new();
+        words.push_right(String::from("foo"));
+        assert!(words.map_focus(|s| s + "bar"));
+        assert_eq!(words.peek_focus().map(|s| s.as_str()), Some("foobar"));
+    }
+
+    #[test]
+    fn undo_redo() {
+        let mut history = History::new(List::new());
+        assert!(!history.undo());
+        assert!(!history.redo());
+
+        history.insert_after(2);
+        history.insert_before(1);
+        history.insert_after(3);
+        history.go_right();
+        history.replace(30);
+        assert_eq!(history.iter().collect::<Vec<_>>(), vec![&1, &2, &30]);
+        assert_eq!(history.position(), 2);
+
+        // Unwind everything, one edit at a time
+        assert!(history.undo());
+        assert_eq!(history.iter().collect::<Vec<_>>(), vec![&1, &2, &3]);
+        assert!(history.undo());
+        assert_eq!(history.position(), 1);
+        assert!(history.undo());
+        assert_eq!(history.iter().collect::<Vec<_>>(), vec![&1, &2]);
+        assert!(history.undo());
+        assert!(history.undo());
+        assert!(history.is_empty());
+        assert!(!history.undo());
+
+        // And wind it back up
+        while history.redo() {}
+        assert_eq!(history.iter().collect::<Vec<_>>(), vec![&1, &2, &30]);
+        assert_eq!(history.position(), 2);
+
+        // Removing keeps the element around for undo
+        assert!(history.remove_at_focus());
+        assert_eq!(history.iter().collect::<Vec<_>>(), vec![&1, &2]);
+        assert!(!history.remove_at_focus());
+        assert!(history.undo());
+        assert_eq!(history.peek_focus(), Some(&30));
+
+        // A fresh edit discards the redo log
+        history.insert_before(0);
+        assert!(!history.redo());
+
+        let list = history.into_inner();
+        assert_eq!(list.iter().collect::<Vec<_>>(), vec![&1, &2, &0, &30]);
+    }
 }
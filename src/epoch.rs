@@ -0,0 +1,416 @@
+//! # Epoch-based reclamation
+//!
+//! Lock-free structures unlink nodes while other threads may still be reading them, so the
+//! thread that unlinks a node can't free it right away. Epoch-based reclamation answers
+//! "when is it safe?" with a global counter that only moves forward when everyone agrees.
+//!
+//! - Before touching shared pointers a thread **pins** itself, announcing the global epoch it
+//!   saw. While pinned it may hold pointers into the structure.
+//! - Unlinked nodes are **deferred**: stashed in a bag together with the epoch they were
+//!   retired in, instead of being freed.
+//! - The global epoch can only go from `e` to `e + 1` once every pinned thread has announced
+//!   `e`. So by the time it reaches `e + 2`, every thread that was pinned when a node was
+//!   retired in `e` has unpinned, and nobody can reach the node any more. That's when we
+//!   **collect** it.
+//!
+//! Each thread keeps a small local bag so deferring is usually just a `Vec::push`. Full bags
+//! are sealed with the current epoch and handed to a global list, which is also where
+//! collection happens. That list sits behind a mutex, but we only take it once per bag,
+//! never on the pin or defer fast path.
+//!
+//! Threads register themselves the first time they pin, in a lock-free list of participants
+//! that is only ever pushed to. When a thread exits its slot is marked free for reuse.
+
+use std::cell::{Cell, RefCell};
+use std::marker::PhantomData;
+use std::mem;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering::SeqCst};
+use std::sync::Mutex;
+
+/// How many deferred functions a thread collects before sealing its bag.
+const BAG_SIZE: usize = 64;
+
+/// Participant epochs are stored shifted left by one, with the low bit meaning "pinned".
+const PINNED: usize = 1;
+
+static GLOBAL: Global = Global {
+    epoch: AtomicUsize::new(0),
+    participants: AtomicPtr::new(ptr::null_mut()),
+    garbage: Mutex::new(Vec::new()),
+};
+
+thread_local! {
+    static LOCAL: Local = Local::register();
+}
+
+struct Global {
+    epoch: AtomicUsize,
+    participants: AtomicPtr<Participant>,
+    garbage: Mutex<Vec<SealedBag>>,
+}
+
+struct Participant {
+    epoch: AtomicUsize,
+    in_use: AtomicBool,
+    next: *mut Participant,
+}
+
+struct Local {
+    participant: &'static Participant,
+    bag: RefCell<Vec<Deferred>>,
+    guards: Cell<usize>,
+}
+
+struct SealedBag {
+    epoch: usize,
+    deferred: Vec<Deferred>,
+}
+
+/// A type-erased function to run later.
+struct Deferred {
+    data: *mut (),
+    call: unsafe fn(*mut ()),
+}
+
+// A deferred function is only ever run once, by whichever thread collects it. Whoever
+// created it promised (via `defer` or `defer_destroy`) that this is fine.
+unsafe impl Send for Deferred {}
+
+impl Deferred {
+    fn run(self) {
+        unsafe { (self.call)(self.data) }
+    }
+}
+
+unsafe fn call_boxed<F: FnOnce()>(data: *mut ()) {
+    let f = Box::from_raw(data as *mut F);
+    f();
+}
+
+unsafe fn drop_boxed<T>(data: *mut ()) {
+    drop(Box::from_raw(data as *mut T));
+}
+
+impl Global {
+    /// Moves the epoch forward if every pinned participant has caught up with it.
+    fn try_advance(&self) -> usize {
+        let epoch = self.epoch.load(SeqCst);
+        let mut participant = self.participants.load(SeqCst);
+
+        while let Some(p) = unsafe { participant.as_ref() } {
+            let announced = p.epoch.load(SeqCst);
+            if announced & PINNED != 0 && announced >> 1 != epoch {
+                return epoch;
+            }
+            participant = p.next;
+        }
+
+        match self
+            .epoch
+            .compare_exchange(epoch, epoch + 1, SeqCst, SeqCst)
+        {
+            Ok(_) => epoch + 1,
+            Err(current) => current,
+        }
+    }
+
+    /// Runs everything that was retired at least two epochs ago.
+    fn collect(&self) {
+        let epoch = self.try_advance();
+
+        let ready = {
+            let mut garbage = self.garbage.lock().unwrap();
+            let (ready, waiting) = mem::take(&mut *garbage)
+                .into_iter()
+                .partition(|bag: &SealedBag| bag.epoch + 2 <= epoch);
+            *garbage = waiting;
+            ready
+        };
+
+        // Run the functions outside the lock, they might take a while
+        for bag in ready {
+            for deferred in bag.deferred {
+                deferred.run();
+            }
+        }
+    }
+
+    fn push_bag(&self, deferred: Vec<Deferred>) {
+        if deferred.is_empty() {
+            return;
+        }
+        let bag = SealedBag {
+            epoch: self.epoch.load(SeqCst),
+            deferred,
+        };
+        self.garbage.lock().unwrap().push(bag);
+    }
+}
+
+impl Local {
+    /// Claims a free participant slot, or adds a new one to the list.
+    fn register() -> Self {
+        let mut participant = GLOBAL.participants.load(SeqCst);
+        while let Some(p) = unsafe { participant.as_ref() } {
+            if p.in_use
+                .compare_exchange(false, true, SeqCst, SeqCst)
+                .is_ok()
+            {
+                return Local::new(p);
+            }
+            participant = p.next;
+        }
+
+        let new = Box::into_raw(Box::new(Participant {
+            epoch: AtomicUsize::new(0),
+            in_use: AtomicBool::new(true),
+            next: ptr::null_mut(),
+        }));
+        let mut head = GLOBAL.participants.load(SeqCst);
+        loop {
+            unsafe { (*new).next = head };
+            match GLOBAL
+                .participants
+                .compare_exchange(head, new, SeqCst, SeqCst)
+            {
+                Ok(_) => break,
+                Err(current) => head = current,
+            }
+        }
+        // Participants are never freed, so this lives for the rest of the program
+        Local::new(unsafe { &*new })
+    }
+
+    fn new(participant: &'static Participant) -> Self {
+        Local {
+            participant,
+            bag: RefCell::new(Vec::new()),
+            guards: Cell::new(0),
+        }
+    }
+
+    fn pin(&self) {
+        let guards = self.guards.get();
+        self.guards.set(guards + 1);
+        if guards == 0 {
+            // Announce the epoch we saw. SeqCst keeps our later pointer loads from being
+            // reordered before this store.
+            let epoch = GLOBAL.epoch.load(SeqCst);
+            self.participant.epoch.store(epoch << 1 | PINNED, SeqCst);
+        }
+    }
+
+    fn unpin(&self) {
+        let guards = self.guards.get() - 1;
+        self.guards.set(guards);
+        if guards == 0 {
+            let epoch = self.participant.epoch.load(SeqCst);
+            self.participant.epoch.store(epoch & !PINNED, SeqCst);
+        }
+    }
+
+    fn defer(&self, deferred: Deferred) {
+        let full = {
+            let mut bag = self.bag.borrow_mut();
+            bag.push(deferred);
+            bag.len() >= BAG_SIZE
+        };
+        if full {
+            self.flush();
+        }
+    }
+
+    fn flush(&self) {
+        let bag = mem::take(&mut *self.bag.borrow_mut());
+        GLOBAL.push_bag(bag);
+        GLOBAL.collect();
+    }
+}
+
+impl Drop for Local {
+    fn drop(&mut self) {
+        // Leave our garbage for someone else, and give the slot back
+        GLOBAL.push_bag(mem::take(self.bag.get_mut()));
+        self.participant.epoch.store(0, SeqCst);
+        self.participant.in_use.store(false, SeqCst);
+    }
+}
+
+/// Proof that the current thread is pinned. Pointers loaded from a lock-free structure stay
+/// valid for as long as the guard is alive.
+///
+/// Guards are tied to the thread that created them, so they can't be sent elsewhere.
+pub struct Guard {
+    _not_send: PhantomData<*mut ()>,
+}
+
+/// Pins the current thread until the returned guard is dropped. Pinning again while already
+/// pinned is cheap and just nests.
+pub fn pin() -> Guard {
+    LOCAL.with(|local| local.pin());
+    Guard {
+        _not_send: PhantomData,
+    }
+}
+
+impl Guard {
+    /// Runs `f` once no thread that is currently pinned can still be pinned.
+    pub fn defer<F>(&self, f: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let data = Box::into_raw(Box::new(f)) as *mut ();
+        LOCAL.with(|local| {
+            local.defer(Deferred {
+                data,
+                call: call_boxed::<F>,
+            })
+        });
+    }
+
+    /// Frees a `Box`-allocated pointer once no thread can still be reading it.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must have come from `Box::into_raw`, must already be unreachable for any thread
+    /// that pins after this call, and must not be freed by anyone else. Dropping the `T` may
+    /// happen on another thread, arbitrarily later.
+    pub unsafe fn defer_destroy<T>(&self, ptr: *mut T) {
+        LOCAL.with(|local| {
+            local.defer(Deferred {
+                data: ptr as *mut (),
+                call: drop_boxed::<T>,
+            })
+        });
+    }
+
+    /// Hands this thread's deferred functions to the global list and tries to collect.
+    pub fn flush(&self) {
+        LOCAL.with(|local| local.flush());
+    }
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        // During thread teardown the thread local may already be gone; it unpinned for us
+        let _ = LOCAL.try_with(|local| local.unpin());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::pin;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::mpsc;
+    use std::sync::Arc;
+    use std::thread;
+
+    /// Pins and flushes until `done` says so, giving up after a while.
+    fn collect_until(done: impl Fn() -> bool) -> bool {
+        for _ in 0..1_000 {
+            if done() {
+                return true;
+            }
+            pin().flush();
+            thread::yield_now();
+        }
+        done()
+    }
+
+    #[test]
+    fn defer_runs_eventually() {
+        let count = Arc::new(AtomicUsize::new(0));
+        {
+            let guard = pin();
+            for _ in 0..10 {
+                let count = count.clone();
+                guard.defer(move || {
+                    count.fetch_add(1, Ordering::SeqCst);
+                });
+            }
+        }
+        assert!(collect_until(|| count.load(Ordering::SeqCst) == 10));
+    }
+
+    #[test]
+    fn pinned_thread_blocks_collection() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let (pinned_tx, pinned_rx) = mpsc::channel();
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+
+        // Another thread pins and stays pinned until we say so
+        let reader = thread::spawn(move || {
+            let _guard = pin();
+            pinned_tx.send(()).unwrap();
+            release_rx.recv().unwrap();
+        });
+        pinned_rx.recv().unwrap();
+
+        {
+            let guard = pin();
+            let count = count.clone();
+            guard.defer(move || {
+                count.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+
+        // However hard we try, the reader might still see the object
+        for _ in 0..100 {
+            pin().flush();
+        }
+        assert_eq!(count.load(Ordering::SeqCst), 0);
+
+        release_tx.send(()).unwrap();
+        reader.join().unwrap();
+        assert!(collect_until(|| count.load(Ordering::SeqCst) == 1));
+    }
+
+    #[test]
+    fn nested_pins() {
+        let outer = pin();
+        let inner = pin();
+        drop(outer);
+        // Still pinned through `inner`, deferring works as usual
+        let count = Arc::new(AtomicUsize::new(0));
+        let counted = count.clone();
+        inner.defer(move || {
+            counted.fetch_add(1, Ordering::SeqCst);
+        });
+        drop(inner);
+        assert!(collect_until(|| count.load(Ordering::SeqCst) == 1));
+    }
+
+    #[test]
+    fn destroy_from_many_threads() {
+        struct Counted(Arc<AtomicUsize>);
+        impl Drop for Counted {
+            fn drop(&mut self) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let per_thread = if cfg!(miri) { 20 } else { 1_000 };
+        let drops = Arc::new(AtomicUsize::new(0));
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let drops = drops.clone();
+                thread::spawn(move || {
+                    for _ in 0..per_thread {
+                        let guard = pin();
+                        let ptr = Box::into_raw(Box::new(Counted(drops.clone())));
+                        unsafe { guard.defer_destroy(ptr) };
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        // Exited threads left their garbage behind for us
+        assert!(collect_until(
+            || drops.load(Ordering::SeqCst) == 4 * per_thread
+        ));
+    }
+}
@@ -0,0 +1,637 @@
+//! # x-fast and y-fast tries
+//!
+//! Ordered structures for `u64` keys that beat comparison-based trees by looking at the
+//! keys' bits. A balanced tree takes O(log n) comparisons to find a key's predecessor. These
+//! take O(log log U), where U = 2^64 is the size of the universe, so about six steps no
+//! matter how many keys there are (Willard, 1983).
+//!
+//! ## x-fast trie
+//!
+//! Picture a binary trie over all 64 bits of every key, with one hash table per level
+//! holding the prefixes that exist there. Every key's prefixes are in the tables, so the
+//! longest prefix of a query `x` that's present can be found by *binary searching the
+//! levels*: 6 hash lookups for 64 levels.
+//!
+//! ```text
+//! level 0       ""                   each internal node stores the min and max leaf
+//!              /   \                 below it, and the leaves form a sorted linked list
+//! level 1     0     1
+//!            / \     \               x's longest present prefix has only one child. If x
+//! level 2   00  01    11             would go right from there, the node's max is x's
+//!            |   |     |             predecessor; if left, its min is x's successor. The
+//!   ...     ...  ...   ...           other neighbour is one step along the leaf list.
+//! leaves    a <-> b <-> c
+//! ```
+//!
+//! Queries are fast, but every key sits in all 64 levels, so inserting or removing one
+//! costs O(log U) and the trie takes O(n log U) space.
+//!
+//! ## y-fast trie
+//!
+//! The y-fast trie fixes both. It cuts the sorted keys into buckets of about log U = 64
+//! consecutive keys, each an ordinary balanced tree. Only one *representative* per bucket
+//! (here, its smallest key) goes into an x-fast trie. A query finds its bucket through the
+//! x-fast trie in O(log log U), then searches a bucket of O(log U) keys, again in
+//! O(log log U). Buckets are split when they grow past 2 * 64 keys and merged with a
+//! neighbour when they shrink below 64 / 2. The x-fast trie only changes when that
+//! happens, or when a bucket's smallest key changes. Space drops to O(n).
+
+use std::collections::btree_map::{self, BTreeMap};
+use std::collections::HashMap;
+use std::ops::Bound::{Excluded, Unbounded};
+
+const BITS: usize = 64;
+
+// Buckets hold between BUCKET / 2 and 2 * BUCKET keys, apart from a lone last bucket
+const BUCKET: usize = BITS;
+
+/// The top `level` bits of `key`.
+fn prefix(key: u64, level: usize) -> u64 {
+    key.checked_shr((BITS - level) as u32).unwrap_or(0)
+}
+
+/// An x-fast trie: a set of `u64` with O(log log U) predecessor and successor queries.
+pub struct XFastTrie {
+    // levels[l] maps each present prefix of length l (l < 64) to the smallest and
+    // largest key below it
+    levels: Vec<HashMap<u64, (u64, u64)>>,
+    // The keys themselves, with their neighbours in sorted order
+    leaves: HashMap<u64, Links>,
+}
+
+#[derive(Clone, Copy)]
+struct Links {
+    prev: Option<u64>,
+    next: Option<u64>,
+}
+
+impl XFastTrie {
+    /// Creates an empty XFastTrie.
+    pub fn new() -> Self {
+        XFastTrie {
+            levels: (0..BITS).map(|_| HashMap::new()).collect(),
+            leaves: HashMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    pub fn contains(&self, key: u64) -> bool {
+        self.leaves.contains_key(&key)
+    }
+
+    pub fn min(&self) -> Option<u64> {
+        self.levels[0].get(&0).map(|&(min, _)| min)
+    }
+
+    pub fn max(&self) -> Option<u64> {
+        self.levels[0].get(&0).map(|&(_, max)| max)
+    }
+
+    /// The smallest and largest key under `prefix` at `level`, if there are any.
+    fn bounds(&self, level: usize, prefix: u64) -> Option<(u64, u64)> {
+        if level == BITS {
+            self.leaves.get(&prefix).map(|_| (prefix, prefix))
+        } else {
+            self.levels[level].get(&prefix).copied()
+        }
+    }
+
+    /// Finds the nearest keys below and above `key`, which must not be in the trie.
+    fn neighbours_of_missing(&self, key: u64) -> (Option<u64>, Option<u64>) {
+        if self.is_empty() {
+            return (None, None);
+        }
+        // Binary search for the longest prefix of `key` that exists. Level 0 always does.
+        let (mut lo, mut hi) = (0, BITS - 1);
+        while lo < hi {
+            let mid = (lo + hi).div_ceil(2);
+            if self.levels[mid].contains_key(&prefix(key, mid)) {
+                lo = mid;
+            } else {
+                hi = mid - 1;
+            }
+        }
+
+        let (min, max) = self.levels[lo][&prefix(key, lo)];
+        let goes_right = (key >> (BITS - 1 - lo)) & 1 == 1;
+        if goes_right {
+            // Everything under this node is in its left child, so below `key`
+            (Some(max), self.leaves[&max].next)
+        } else {
+            (self.leaves[&min].prev, Some(min))
+        }
+    }
+
+    /// Returns the largest key strictly less than `key`.
+    pub fn predecessor(&self, key: u64) -> Option<u64> {
+        match self.leaves.get(&key) {
+            Some(links) => links.prev,
+            None => self.neighbours_of_missing(key).0,
+        }
+    }
+
+    /// Returns the smallest key strictly greater than `key`.
+    pub fn successor(&self, key: u64) -> Option<u64> {
+        match self.leaves.get(&key) {
+            Some(links) => links.next,
+            None => self.neighbours_of_missing(key).1,
+        }
+    }
+
+    /// Returns the largest key less than or equal to `key`.
+    pub fn floor(&self, key: u64) -> Option<u64> {
+        if self.contains(key) {
+            Some(key)
+        } else {
+            self.neighbours_of_missing(key).0
+        }
+    }
+
+    /// Adds a key, returning whether it was new.
+    pub fn insert(&mut self, key: u64) -> bool {
+        if self.contains(key) {
+            return false;
+        }
+
+        let (prev, next) = self.neighbours_of_missing(key);
+        self.leaves.insert(key, Links { prev, next });
+        if let Some(prev) = prev {
+            self.leaves.get_mut(&prev).unwrap().next = Some(key);
+        }
+        if let Some(next) = next {
+            self.leaves.get_mut(&next).unwrap().prev = Some(key);
+        }
+
+        for (level, nodes) in self.levels.iter_mut().enumerate() {
+            let bounds = nodes.entry(prefix(key, level)).or_insert((key, key));
+            bounds.0 = bounds.0.min(key);
+            bounds.1 = bounds.1.max(key);
+        }
+        true
+    }
+
+    /// Removes a key, returning whether it was there.
+    pub fn remove(&mut self, key: u64) -> bool {
+        let Links { prev, next } = match self.leaves.remove(&key) {
+            Some(links) => links,
+            None => return false,
+        };
+        if let Some(prev) = prev {
+            self.leaves.get_mut(&prev).unwrap().next = next;
+        }
+        if let Some(next) = next {
+            self.leaves.get_mut(&next).unwrap().prev = prev;
+        }
+
+        // Recompute the bounds bottom-up from each node's two children
+        for level in (0..BITS).rev() {
+            let prefix = prefix(key, level);
+            let left = self.bounds(level + 1, prefix << 1);
+            let right = self.bounds(level + 1, (prefix << 1) | 1);
+            let bounds = match (left, right) {
+                (None, None) => {
+                    self.levels[level].remove(&prefix);
+                    continue;
+                }
+                (Some(only), None) | (None, Some(only)) => only,
+                (Some((min, _)), Some((_, max))) => (min, max),
+            };
+            self.levels[level].insert(prefix, bounds);
+        }
+        true
+    }
+
+    /// Iterates over the keys in increasing order.
+    pub fn iter(&self) -> impl Iterator<Item = u64> + '_ {
+        let mut key = self.min();
+        std::iter::from_fn(move || {
+            let current = key?;
+            key = self.leaves[&current].next;
+            Some(current)
+        })
+    }
+}
+
+impl Default for XFastTrie {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A y-fast trie: a map from `u64` with O(log log U) lookups, predecessor and successor
+/// queries, and amortized O(log log U) updates, in O(n) space.
+pub struct YFastTrie<V> {
+    // Holds the smallest key of every bucket
+    representatives: XFastTrie,
+    buckets: HashMap<u64, BTreeMap<u64, V>>,
+    len: usize,
+}
+
+impl<V> YFastTrie<V> {
+    /// Creates an empty YFastTrie.
+    pub fn new() -> Self {
+        YFastTrie {
+            representatives: XFastTrie::new(),
+            buckets: HashMap::new(),
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The bucket that would hold `key`: the one with the largest representative at or
+    /// below it.
+    fn bucket_of(&self, key: u64) -> Option<(u64, &BTreeMap<u64, V>)> {
+        let representative = self.representatives.floor(key)?;
+        Some((representative, &self.buckets[&representative]))
+    }
+
+    pub fn get(&self, key: u64) -> Option<&V> {
+        self.bucket_of(key)?.1.get(&key)
+    }
+
+    pub fn get_mut(&mut self, key: u64) -> Option<&mut V> {
+        let representative = self.representatives.floor(key)?;
+        self.buckets.get_mut(&representative)?.get_mut(&key)
+    }
+
+    pub fn contains_key(&self, key: u64) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Returns the entry with the smallest key.
+    pub fn first_key_value(&self) -> Option<(u64, &V)> {
+        let min = self.representatives.min()?;
+        self.buckets[&min].iter().next().map(|(&k, v)| (k, v))
+    }
+
+    /// Returns the entry with the largest key.
+    pub fn last_key_value(&self) -> Option<(u64, &V)> {
+        let max = self.representatives.max()?;
+        self.buckets[&max].iter().next_back().map(|(&k, v)| (k, v))
+    }
+
+    /// Returns the entry with the largest key strictly less than `key`.
+    pub fn predecessor(&self, key: u64) -> Option<(u64, &V)> {
+        let (representative, bucket) = self.bucket_of(key)?;
+        if let Some((&k, v)) = bucket.range(..key).next_back() {
+            return Some((k, v));
+        }
+        // Everything in this bucket is at or above `key`, so it's the previous bucket's max
+        let previous = self.representatives.predecessor(representative)?;
+        self.buckets[&previous]
+            .iter()
+            .next_back()
+            .map(|(&k, v)| (k, v))
+    }
+
+    /// Returns the entry with the smallest key strictly greater than `key`.
+    pub fn successor(&self, key: u64) -> Option<(u64, &V)> {
+        let next_bucket = match self.bucket_of(key) {
+            Some((representative, bucket)) => {
+                if let Some((&k, v)) = bucket.range((Excluded(key), Unbounded)).next() {
+                    return Some((k, v));
+                }
+                self.representatives.successor(representative)?
+            }
+            // `key` is below every bucket
+            None => self.representatives.min()?,
+        };
+        self.buckets[&next_bucket]
+            .iter()
+            .next()
+            .map(|(&k, v)| (k, v))
+    }
+
+    /// Inserts a key-value pair, returning the old value if the key was already there.
+    pub fn insert(&mut self, key: u64, value: V) -> Option<V> {
+        let representative = match self.representatives.floor(key) {
+            Some(representative) => representative,
+            None => match self.representatives.min() {
+                // Below every bucket: it joins the first one and becomes its representative
+                Some(first) => {
+                    self.rename_bucket(first, key);
+                    key
+                }
+                None => {
+                    self.representatives.insert(key);
+                    self.buckets.insert(key, BTreeMap::new());
+                    key
+                }
+            },
+        };
+
+        let bucket = self.buckets.get_mut(&representative).unwrap();
+        let old = bucket.insert(key, value);
+        if old.is_none() {
+            self.len += 1;
+            if bucket.len() > 2 * BUCKET {
+                self.split_bucket(representative);
+            }
+        }
+        old
+    }
+
+    /// Removes a key, returning its value if it was there.
+    pub fn remove(&mut self, key: u64) -> Option<V> {
+        let representative = self.representatives.floor(key)?;
+        let bucket = self.buckets.get_mut(&representative).unwrap();
+        let value = bucket.remove(&key)?;
+        self.len -= 1;
+
+        if bucket.is_empty() {
+            self.buckets.remove(&representative);
+            self.representatives.remove(representative);
+            return Some(value);
+        }
+        let mut representative = representative;
+        if key == representative {
+            let min = *bucket.keys().next().unwrap();
+            self.rename_bucket(representative, min);
+            representative = min;
+        }
+        if self.buckets[&representative].len() < BUCKET / 2 {
+            self.merge_bucket(representative);
+        }
+        Some(value)
+    }
+
+    fn rename_bucket(&mut self, from: u64, to: u64) {
+        let bucket = self.buckets.remove(&from).unwrap();
+        self.representatives.remove(from);
+        self.representatives.insert(to);
+        self.buckets.insert(to, bucket);
+    }
+
+    /// Moves the upper half of an overfull bucket into a bucket of its own.
+    fn split_bucket(&mut self, representative: u64) {
+        let bucket = self.buckets.get_mut(&representative).unwrap();
+        let middle = *bucket.keys().nth(BUCKET).unwrap();
+        let upper = bucket.split_off(&middle);
+        self.representatives.insert(middle);
+        self.buckets.insert(middle, upper);
+    }
+
+    /// Merges an underfull bucket with a neighbour, splitting the result again if it's now
+    /// too big.
+    fn merge_bucket(&mut self, representative: u64) {
+        let (lower, upper) = match self.representatives.successor(representative) {
+            Some(next) => (representative, next),
+            None => match self.representatives.predecessor(representative) {
+                Some(previous) => (previous, representative),
+                // It's the only bucket, so it can be as small as it likes
+                None => return,
+            },
+        };
+        let mut upper_bucket = self.buckets.remove(&upper).unwrap();
+        self.representatives.remove(upper);
+        let bucket = self.buckets.get_mut(&lower).unwrap();
+        bucket.append(&mut upper_bucket);
+        if bucket.len() > 2 * BUCKET {
+            self.split_bucket(lower);
+        }
+    }
+
+    /// Iterates over the entries in increasing key order.
+    pub fn iter(&self) -> Iter<'_, V> {
+        Iter {
+            trie: self,
+            next_bucket: self.representatives.min(),
+            current: None,
+            remaining: self.len,
+        }
+    }
+}
+
+impl<V> Default for YFastTrie<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct Iter<'a, V> {
+    trie: &'a YFastTrie<V>,
+    next_bucket: Option<u64>,
+    current: Option<btree_map::Iter<'a, u64, V>>,
+    remaining: usize,
+}
+
+impl<'a, V> Iterator for Iter<'a, V> {
+    type Item = (u64, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((&k, v)) = self.current.as_mut().and_then(|bucket| bucket.next()) {
+                self.remaining -= 1;
+                return Some((k, v));
+            }
+            let representative = self.next_bucket?;
+            self.current = Some(self.trie.buckets[&representative].iter());
+            self.next_bucket = self.trie.representatives.successor(representative);
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<V> ExactSizeIterator for Iter<'_, V> {}
+
+impl<'a, V> IntoIterator for &'a YFastTrie<V> {
+    type Item = (u64, &'a V);
+    type IntoIter = Iter<'a, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{XFastTrie, YFastTrie, BUCKET};
+    use std::collections::{BTreeMap, BTreeSet};
+
+    fn lcg(x: &mut u64) -> u64 {
+        *x = x
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        *x
+    }
+
+    // Buckets are within bounds and keyed by their smallest key
+    fn check<V>(trie: &YFastTrie<V>) {
+        assert_eq!(trie.representatives.len(), trie.buckets.len());
+        let many = trie.buckets.len() > 1;
+        for (&representative, bucket) in &trie.buckets {
+            assert_eq!(bucket.keys().next(), Some(&representative));
+            assert!(bucket.len() <= 2 * BUCKET);
+            // Only a lone bucket may run low
+            assert!(!many || bucket.len() >= BUCKET / 2);
+        }
+        assert_eq!(
+            trie.buckets.values().map(|b| b.len()).sum::<usize>(),
+            trie.len()
+        );
+    }
+
+    #[test]
+    fn xfast_basics() {
+        let mut trie = XFastTrie::new();
+
+        // Check empty trie behaves right
+        assert!(trie.is_empty());
+        assert_eq!(trie.predecessor(5), None);
+        assert_eq!(trie.successor(5), None);
+        assert_eq!(trie.min(), None);
+        assert!(!trie.remove(5));
+
+        for key in [10, 3, 0, u64::MAX, 1 << 40, 7] {
+            assert!(trie.insert(key));
+        }
+        assert!(!trie.insert(7));
+        assert_eq!(trie.len(), 6);
+        assert_eq!(trie.min(), Some(0));
+        assert_eq!(trie.max(), Some(u64::MAX));
+
+        assert_eq!(trie.predecessor(7), Some(3));
+        assert_eq!(trie.predecessor(8), Some(7));
+        assert_eq!(trie.successor(8), Some(10));
+        assert_eq!(trie.successor(11), Some(1 << 40));
+        assert_eq!(trie.successor(1 << 40), Some(u64::MAX));
+        assert_eq!(trie.successor(u64::MAX), None);
+        assert_eq!(trie.predecessor(0), None);
+        assert_eq!(trie.floor(9), Some(7));
+        assert_eq!(trie.floor(10), Some(10));
+
+        assert!(trie.remove(0));
+        assert!(trie.remove(u64::MAX));
+        assert_eq!(trie.min(), Some(3));
+        assert_eq!(trie.max(), Some(1 << 40));
+        assert_eq!(trie.iter().collect::<Vec<_>>(), vec![3, 7, 10, 1 << 40]);
+    }
+
+    #[test]
+    fn xfast_against_btreeset() {
+        let mut trie = XFastTrie::new();
+        let mut model = BTreeSet::new();
+        let mut x = 3;
+        for i in 0..2_000 {
+            // Keys clustered under a few high prefixes, so the trie has real structure
+            let key = (lcg(&mut x) >> 62 << 62) | (lcg(&mut x) >> 54);
+            if i % 3 == 2 {
+                assert_eq!(trie.remove(key), model.remove(&key));
+            } else {
+                assert_eq!(trie.insert(key), model.insert(key));
+            }
+            let probe = lcg(&mut x);
+            assert_eq!(
+                trie.predecessor(probe),
+                model.range(..probe).next_back().copied()
+            );
+            assert_eq!(
+                trie.successor(probe),
+                model.range(probe + 1..).next().copied()
+            );
+        }
+        assert!(trie.iter().eq(model.iter().copied()));
+        assert_eq!(trie.min(), model.iter().next().copied());
+    }
+
+    #[test]
+    fn yfast_basics() {
+        let mut trie = YFastTrie::new();
+
+        // Check empty trie behaves right
+        assert!(trie.is_empty());
+        assert_eq!(trie.get(1), None);
+        assert_eq!(trie.remove(1), None);
+        assert_eq!(trie.predecessor(1), None);
+        assert_eq!(trie.successor(1), None);
+
+        assert_eq!(trie.insert(50, "fifty"), None);
+        assert_eq!(trie.insert(10, "ten"), None);
+        assert_eq!(trie.insert(90, "ninety"), None);
+        assert_eq!(trie.insert(10, "TEN"), Some("ten"));
+        assert_eq!(trie.len(), 3);
+        assert_eq!(trie.get(10), Some(&"TEN"));
+        *trie.get_mut(90).unwrap() = "NINETY";
+
+        assert_eq!(trie.predecessor(50), Some((10, &"TEN")));
+        assert_eq!(trie.successor(50), Some((90, &"NINETY")));
+        assert_eq!(trie.successor(0), Some((10, &"TEN")));
+        assert_eq!(trie.predecessor(10), None);
+        assert_eq!(trie.first_key_value(), Some((10, &"TEN")));
+        assert_eq!(trie.last_key_value(), Some((90, &"NINETY")));
+
+        assert_eq!(trie.remove(10), Some("TEN"));
+        assert!(!trie.contains_key(10));
+        assert_eq!(trie.first_key_value(), Some((50, &"fifty")));
+        check(&trie);
+    }
+
+    #[test]
+    fn buckets_split_and_merge() {
+        let mut trie = YFastTrie::new();
+        let n = 20 * BUCKET as u64;
+        for key in 0..n {
+            trie.insert(key * 3, key);
+        }
+        check(&trie);
+        assert!(trie.buckets.len() >= 10);
+        assert_eq!(trie.predecessor(301), Some((300, &100)));
+        assert_eq!(trie.successor(301), Some((303, &101)));
+
+        // Inserting below everything moves the first representative
+        trie.insert(0, 0);
+        for key in (0..n).filter(|k| k % 5 != 0) {
+            trie.remove(key * 3);
+            check(&trie);
+        }
+        assert_eq!(trie.len(), n as usize / 5);
+        let keys: Vec<_> = trie.iter().map(|(k, _)| k).collect();
+        assert_eq!(keys, (0..n).step_by(5).map(|k| k * 3).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn yfast_against_btreemap() {
+        let mut trie = YFastTrie::new();
+        let mut model = BTreeMap::new();
+        let mut x = 42;
+        for i in 0..20_000 {
+            let key = lcg(&mut x) >> 50;
+            match i % 5 {
+                0 | 1 => assert_eq!(trie.remove(key), model.remove(&key)),
+                _ => assert_eq!(trie.insert(key, i), model.insert(key, i)),
+            }
+            let probe = lcg(&mut x) >> 50;
+            assert_eq!(
+                trie.predecessor(probe),
+                model.range(..probe).next_back().map(|(&k, v)| (k, v))
+            );
+            assert_eq!(
+                trie.successor(probe),
+                model.range(probe + 1..).next().map(|(&k, v)| (k, v))
+            );
+            if i % 1_000 == 0 {
+                check(&trie);
+            }
+        }
+        check(&trie);
+        assert_eq!(trie.len(), model.len());
+        assert!(trie.iter().eq(model.iter().map(|(&k, v)| (k, v))));
+    }
+}
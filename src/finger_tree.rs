@@ -0,0 +1,1071 @@
+//! # Finger tree
+//!
+//! The 2-3 finger tree of Hinze and Paterson (2006): a persistent sequence with cheap
+//! access at both ends, cheap concatenation and splitting, and a general-purpose way to
+//! search it. Like [`crate::persistent`], every operation takes `&self` and returns a new
+//! tree, sharing most of the old one.
+//!
+//! A tree keeps one to four elements at each end, its *digits*, and puts everything in
+//! between into a tree of 2-3 nodes one level down. Each level down holds nodes of the
+//! level above's elements, so the depth is logarithmic, and the ends, the *fingers*, are
+//! always right at the top:
+//!
+//! ```text
+//!   Deep [a b] ------------------------------------------ [h i j]
+//!              \                                         /
+//!               Deep [(c d e)] ------------- [(f g)]
+//!                              \            /
+//!                               Empty
+//! ```
+//!
+//! The interesting part is what every node caches: a *measure*, from any [`Monoid`] the
+//! elements know how to map themselves into, combined over everything under the node. A
+//! size measure turns the tree into an indexed sequence, [`Seq`], where splitting at "the
+//! first point the running size passes i" is `split_at(i)`. A max measure turns it into a
+//! priority queue, [`PriorityQueue`], where the same split finds the largest element. Both
+//! searches are walks down the cached measures, O(log n) either way.
+//!
+//! Pushing and popping are amortized O(1), and concatenation is O(log min(m, n)). The
+//! amortized bounds are for a tree used in the ordinary way: persistence lets someone pop
+//! the same unlucky version over and over, and without Haskell's laziness each of those
+//! times pays the O(log n) again.
+
+use std::fmt;
+use std::iter::FromIterator;
+use std::sync::Arc;
+
+/// A measure: something with an associative way to combine two values, and a value that
+/// combining with changes nothing.
+pub trait Monoid: Clone {
+    fn identity() -> Self;
+
+    fn combine(&self, other: &Self) -> Self;
+}
+
+/// An element that can be measured, for a [`FingerTree`] to cache and search by.
+pub trait Measured {
+    type Measure: Monoid;
+
+    fn measure(&self) -> Self::Measure;
+}
+
+/// Counts elements.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Size(pub usize);
+
+impl Monoid for Size {
+    fn identity() -> Self {
+        Size(0)
+    }
+
+    fn combine(&self, other: &Self) -> Self {
+        Size(self.0 + other.0)
+    }
+}
+
+/// Keeps the largest element, if there's any.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Max<T>(pub Option<T>);
+
+impl<T: Ord + Clone> Monoid for Max<T> {
+    fn identity() -> Self {
+        Max(None)
+    }
+
+    fn combine(&self, other: &Self) -> Self {
+        Max(self.0.clone().max(other.0.clone()))
+    }
+}
+
+/// An element, or a 2-3 node of the level below's elements.
+#[derive(Clone)]
+enum Node<T: Measured> {
+    Leaf(T),
+    Branch(T::Measure, Arc<Vec<Node<T>>>),
+}
+
+#[derive(Clone)]
+enum Tree<T: Measured> {
+    Empty,
+    Single(Node<T>),
+    Deep {
+        measure: T::Measure,
+        // One to four nodes at either end
+        prefix: Vec<Node<T>>,
+        middle: Arc<Tree<T>>,
+        suffix: Vec<Node<T>>,
+    },
+}
+
+fn measure_all<T: Measured>(nodes: &[Node<T>]) -> T::Measure {
+    nodes.iter().fold(T::Measure::identity(), |acc, node| {
+        acc.combine(&node.measure())
+    })
+}
+
+impl<T: Measured> Node<T> {
+    fn measure(&self) -> T::Measure {
+        match self {
+            Node::Leaf(elem) => elem.measure(),
+            Node::Branch(measure, _) => measure.clone(),
+        }
+    }
+
+    fn branch(children: Vec<Node<T>>) -> Self {
+        Node::Branch(measure_all(&children), Arc::new(children))
+    }
+
+    fn leaf(&self) -> &T {
+        match self {
+            Node::Leaf(elem) => elem,
+            Node::Branch(..) => unreachable!("elements are at the top level"),
+        }
+    }
+
+    /// The first element under this node for which `pred` holds of everything up to and
+    /// including it, `acc` being everything before the node.
+    fn find<F: Fn(&T::Measure) -> bool>(&self, pred: &F, acc: T::Measure) -> &T {
+        match self {
+            Node::Leaf(elem) => elem,
+            Node::Branch(_, children) => find_nodes(children, pred, acc),
+        }
+    }
+}
+
+impl<T: Measured + Clone> Node<T> {
+    fn children(&self) -> Vec<Node<T>> {
+        match self {
+            Node::Branch(_, children) => children.to_vec(),
+            Node::Leaf(_) => unreachable!("leaves have no children"),
+        }
+    }
+}
+
+fn find_nodes<'a, T, F>(nodes: &'a [Node<T>], pred: &F, mut acc: T::Measure) -> &'a T
+where
+    T: Measured,
+    F: Fn(&T::Measure) -> bool,
+{
+    for (i, node) in nodes.iter().enumerate() {
+        let next = acc.combine(&node.measure());
+        if pred(&next) || i + 1 == nodes.len() {
+            return node.find(pred, acc);
+        }
+        acc = next;
+    }
+    unreachable!("digits aren't empty")
+}
+
+/// Splits around the first node at which `pred` starts to hold, or the last node.
+fn split_nodes<T, F>(
+    nodes: &[Node<T>],
+    pred: &F,
+    acc: &T::Measure,
+) -> (Vec<Node<T>>, Node<T>, Vec<Node<T>>)
+where
+    T: Measured + Clone,
+    F: Fn(&T::Measure) -> bool,
+{
+    let mut acc = acc.clone();
+    for (i, node) in nodes.iter().enumerate() {
+        acc = acc.combine(&node.measure());
+        if pred(&acc) || i + 1 == nodes.len() {
+            return (nodes[..i].to_vec(), node.clone(), nodes[i + 1..].to_vec());
+        }
+    }
+    unreachable!("digits aren't empty")
+}
+
+/// Groups two or more nodes into 2-3 nodes.
+fn group<T: Measured + Clone>(nodes: Vec<Node<T>>) -> Vec<Node<T>> {
+    let mut groups = Vec::with_capacity(nodes.len() / 3 + 1);
+    let mut rest = &nodes[..];
+    while rest.len() > 4 {
+        groups.push(Node::branch(rest[..3].to_vec()));
+        rest = &rest[3..];
+    }
+    if rest.len() == 4 {
+        groups.push(Node::branch(rest[..2].to_vec()));
+        rest = &rest[2..];
+    }
+    groups.push(Node::branch(rest.to_vec()));
+    groups
+}
+
+impl<T: Measured> Tree<T> {
+    fn measure(&self) -> T::Measure {
+        match self {
+            Tree::Empty => T::Measure::identity(),
+            Tree::Single(node) => node.measure(),
+            Tree::Deep { measure, .. } => measure.clone(),
+        }
+    }
+
+    fn find<F: Fn(&T::Measure) -> bool>(&self, pred: &F, acc: T::Measure) -> &T {
+        match self {
+            Tree::Empty => unreachable!("searching an empty tree"),
+            Tree::Single(node) => node.find(pred, acc),
+            Tree::Deep {
+                prefix,
+                middle,
+                suffix,
+                ..
+            } => {
+                let after_prefix = acc.combine(&measure_all(prefix));
+                if pred(&after_prefix) {
+                    return find_nodes(prefix, pred, acc);
+                }
+                let after_middle = after_prefix.combine(&middle.measure());
+                if pred(&after_middle) {
+                    return middle.find(pred, after_prefix);
+                }
+                find_nodes(suffix, pred, after_middle)
+            }
+        }
+    }
+}
+
+impl<T: Measured + Clone> Tree<T> {
+    fn deep(prefix: Vec<Node<T>>, middle: Arc<Tree<T>>, suffix: Vec<Node<T>>) -> Self {
+        let measure = measure_all(&prefix)
+            .combine(&middle.measure())
+            .combine(&measure_all(&suffix));
+        Tree::Deep {
+            measure,
+            prefix,
+            middle,
+            suffix,
+        }
+    }
+
+    fn from_nodes(nodes: Vec<Node<T>>) -> Self {
+        nodes
+            .into_iter()
+            .fold(Tree::Empty, |tree, node| tree.push_back(node))
+    }
+
+    /// A deep tree whose prefix may have run out, refilled from the middle.
+    fn deep_left(prefix: Vec<Node<T>>, middle: &Arc<Tree<T>>, suffix: Vec<Node<T>>) -> Self {
+        if !prefix.is_empty() {
+            return Tree::deep(prefix, middle.clone(), suffix);
+        }
+        match middle.pop_front() {
+            None => Tree::from_nodes(suffix),
+            Some((node, rest)) => Tree::deep(node.children(), Arc::new(rest), suffix),
+        }
+    }
+
+    /// A deep tree whose suffix may have run out, refilled from the middle.
+    fn deep_right(prefix: Vec<Node<T>>, middle: &Arc<Tree<T>>, suffix: Vec<Node<T>>) -> Self {
+        if !suffix.is_empty() {
+            return Tree::deep(prefix, middle.clone(), suffix);
+        }
+        match middle.pop_back() {
+            None => Tree::from_nodes(prefix),
+            Some((node, rest)) => Tree::deep(prefix, Arc::new(rest), node.children()),
+        }
+    }
+
+    fn push_front(&self, node: Node<T>) -> Self {
+        match self {
+            Tree::Empty => Tree::Single(node),
+            Tree::Single(only) => Tree::deep(vec![node], Arc::new(Tree::Empty), vec![only.clone()]),
+            Tree::Deep {
+                prefix,
+                middle,
+                suffix,
+                ..
+            } if prefix.len() == 4 => {
+                // A full digit keeps one and sends the other three down a level
+                let middle = middle.push_front(Node::branch(prefix[1..].to_vec()));
+                let prefix = vec![node, prefix[0].clone()];
+                Tree::deep(prefix, Arc::new(middle), suffix.clone())
+            }
+            Tree::Deep {
+                prefix,
+                middle,
+                suffix,
+                ..
+            } => {
+                let mut grown = Vec::with_capacity(prefix.len() + 1);
+                grown.push(node);
+                grown.extend(prefix.iter().cloned());
+                Tree::deep(grown, middle.clone(), suffix.clone())
+            }
+        }
+    }
+
+    fn push_back(&self, node: Node<T>) -> Self {
+        match self {
+            Tree::Empty => Tree::Single(node),
+            Tree::Single(only) => Tree::deep(vec![only.clone()], Arc::new(Tree::Empty), vec![node]),
+            Tree::Deep {
+                prefix,
+                middle,
+                suffix,
+                ..
+            } if suffix.len() == 4 => {
+                let middle = middle.push_back(Node::branch(suffix[..3].to_vec()));
+                let suffix = vec![suffix[3].clone(), node];
+                Tree::deep(prefix.clone(), Arc::new(middle), suffix)
+            }
+            Tree::Deep {
+                prefix,
+                middle,
+                suffix,
+                ..
+            } => {
+                let mut grown = suffix.clone();
+                grown.push(node);
+                Tree::deep(prefix.clone(), middle.clone(), grown)
+            }
+        }
+    }
+
+    fn pop_front(&self) -> Option<(Node<T>, Self)> {
+        match self {
+            Tree::Empty => None,
+            Tree::Single(only) => Some((only.clone(), Tree::Empty)),
+            Tree::Deep {
+                prefix,
+                middle,
+                suffix,
+                ..
+            } => {
+                let rest = Tree::deep_left(prefix[1..].to_vec(), middle, suffix.clone());
+                Some((prefix[0].clone(), rest))
+            }
+        }
+    }
+
+    fn pop_back(&self) -> Option<(Node<T>, Self)> {
+        match self {
+            Tree::Empty => None,
+            Tree::Single(only) => Some((only.clone(), Tree::Empty)),
+            Tree::Deep {
+                prefix,
+                middle,
+                suffix,
+                ..
+            } => {
+                let last = suffix.len() - 1;
+                let rest = Tree::deep_right(prefix.clone(), middle, suffix[..last].to_vec());
+                Some((suffix[last].clone(), rest))
+            }
+        }
+    }
+
+    /// Joins `left`, then `nodes`, then `right`. The nodes in between are how the digits
+    /// at the seam get passed down a level.
+    fn concat(left: &Self, nodes: Vec<Node<T>>, right: &Self) -> Self {
+        match (left, right) {
+            (Tree::Empty, _) => nodes
+                .into_iter()
+                .rev()
+                .fold(right.clone(), |tree, node| tree.push_front(node)),
+            (_, Tree::Empty) => nodes
+                .into_iter()
+                .fold(left.clone(), |tree, node| tree.push_back(node)),
+            (Tree::Single(only), _) => {
+                Tree::concat(&Tree::Empty, nodes, right).push_front(only.clone())
+            }
+            (_, Tree::Single(only)) => {
+                Tree::concat(left, nodes, &Tree::Empty).push_back(only.clone())
+            }
+            (
+                Tree::Deep {
+                    prefix,
+                    middle: left_middle,
+                    suffix: left_suffix,
+                    ..
+                },
+                Tree::Deep {
+                    prefix: right_prefix,
+                    middle: right_middle,
+                    suffix,
+                    ..
+                },
+            ) => {
+                let mut seam = left_suffix.clone();
+                seam.extend(nodes);
+                seam.extend(right_prefix.iter().cloned());
+                let middle = Tree::concat(left_middle, group(seam), right_middle);
+                Tree::deep(prefix.clone(), Arc::new(middle), suffix.clone())
+            }
+        }
+    }
+
+    /// Splits a non-empty tree around the first node at which `pred` starts to hold of
+    /// `acc` and everything up to it, or around the last node if it never does.
+    fn split<F: Fn(&T::Measure) -> bool>(
+        &self,
+        pred: &F,
+        acc: &T::Measure,
+    ) -> (Self, Node<T>, Self) {
+        match self {
+            Tree::Empty => unreachable!("splitting an empty tree"),
+            Tree::Single(only) => (Tree::Empty, only.clone(), Tree::Empty),
+            Tree::Deep {
+                prefix,
+                middle,
+                suffix,
+                ..
+            } => {
+                let after_prefix = acc.combine(&measure_all(prefix));
+                if pred(&after_prefix) {
+                    let (left, node, right) = split_nodes(prefix, pred, acc);
+                    let right = Tree::deep_left(right, middle, suffix.clone());
+                    return (Tree::from_nodes(left), node, right);
+                }
+                let after_middle = after_prefix.combine(&middle.measure());
+                if pred(&after_middle) {
+                    // Split the middle, then the node it split around
+                    let (middle_left, branch, middle_right) = middle.split(pred, &after_prefix);
+                    let acc = after_prefix.combine(&middle_left.measure());
+                    let (left, node, right) = split_nodes(&branch.children(), pred, &acc);
+                    let left = Tree::deep_right(prefix.clone(), &Arc::new(middle_left), left);
+                    let right = Tree::deep_left(right, &Arc::new(middle_right), suffix.clone());
+                    return (left, node, right);
+                }
+                let (left, node, right) = split_nodes(suffix, pred, &after_middle);
+                let left = Tree::deep_right(prefix.clone(), middle, left);
+                (left, node, Tree::from_nodes(right))
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct FingerTree<T: Measured> {
+    tree: Tree<T>,
+}
+
+impl<T: Measured> FingerTree<T> {
+    /// Creates an empty FingerTree.
+    pub fn new() -> Self {
+        FingerTree { tree: Tree::Empty }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        matches!(self.tree, Tree::Empty)
+    }
+
+    /// Returns the measure of all the elements together. O(1).
+    pub fn measure(&self) -> T::Measure {
+        self.tree.measure()
+    }
+
+    pub fn front(&self) -> Option<&T> {
+        match &self.tree {
+            Tree::Empty => None,
+            Tree::Single(only) => Some(only.leaf()),
+            Tree::Deep { prefix, .. } => Some(prefix[0].leaf()),
+        }
+    }
+
+    pub fn back(&self) -> Option<&T> {
+        match &self.tree {
+            Tree::Empty => None,
+            Tree::Single(only) => Some(only.leaf()),
+            Tree::Deep { suffix, .. } => suffix.last().map(Node::leaf),
+        }
+    }
+
+    /// Returns the first element at which `pred`, given the measure of everything up to
+    /// and including that element, holds. `pred` must be monotonic: false up to some
+    /// point and true from then on.
+    pub fn find<F: Fn(&T::Measure) -> bool>(&self, pred: F) -> Option<&T> {
+        if self.is_empty() || !pred(&self.measure()) {
+            return None;
+        }
+        Some(self.tree.find(&pred, T::Measure::identity()))
+    }
+
+    /// Iterates over the elements, front to back.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            stack: vec![Work::Tree(&self.tree)],
+        }
+    }
+}
+
+impl<T: Measured + Clone> FingerTree<T> {
+    /// Returns a new tree with `elem` in front.
+    pub fn push_front(&self, elem: T) -> Self {
+        FingerTree {
+            tree: self.tree.push_front(Node::Leaf(elem)),
+        }
+    }
+
+    /// Returns a new tree with `elem` at the back.
+    pub fn push_back(&self, elem: T) -> Self {
+        FingerTree {
+            tree: self.tree.push_back(Node::Leaf(elem)),
+        }
+    }
+
+    /// Returns a new tree without the front element. Popping an empty tree gives an
+    /// empty tree.
+    pub fn pop_front(&self) -> Self {
+        self.tree
+            .pop_front()
+            .map_or_else(Self::new, |(_, tree)| FingerTree { tree })
+    }
+
+    /// Returns a new tree without the back element.
+    pub fn pop_back(&self) -> Self {
+        self.tree
+            .pop_back()
+            .map_or_else(Self::new, |(_, tree)| FingerTree { tree })
+    }
+
+    /// Returns a new tree with the elements of this one followed by those of `other`.
+    pub fn concat(&self, other: &Self) -> Self {
+        FingerTree {
+            tree: Tree::concat(&self.tree, Vec::new(), &other.tree),
+        }
+    }
+
+    /// Splits at the first element at which `pred` holds, as for [`find`](Self::find):
+    /// the elements before it, and the elements from it on. If `pred` never holds, the
+    /// second tree is empty.
+    pub fn split<F: Fn(&T::Measure) -> bool>(&self, pred: F) -> (Self, Self) {
+        if self.is_empty() || !pred(&self.measure()) {
+            return (self.clone(), Self::new());
+        }
+        let (left, node, right) = self.tree.split(&pred, &T::Measure::identity());
+        (
+            FingerTree { tree: left },
+            FingerTree {
+                tree: right.push_front(node),
+            },
+        )
+    }
+}
+
+impl<T: Measured> Default for FingerTree<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Measured + Clone> FromIterator<T> for FingerTree<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut tree = FingerTree::new();
+        for elem in iter {
+            tree = tree.push_back(elem);
+        }
+        tree
+    }
+}
+
+impl<T: Measured + fmt::Debug> fmt::Debug for FingerTree<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+enum Work<'a, T: Measured> {
+    Node(&'a Node<T>),
+    Tree(&'a Tree<T>),
+}
+
+/// Iterator over the elements of a [`FingerTree`], front to back.
+pub struct Iter<'a, T: Measured> {
+    // What's still to visit, the next thing on top
+    stack: Vec<Work<'a, T>>,
+}
+
+impl<'a, T: Measured> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.stack.pop()? {
+                Work::Node(Node::Leaf(elem)) => return Some(elem),
+                Work::Node(Node::Branch(_, children)) => {
+                    self.stack.extend(children.iter().rev().map(Work::Node))
+                }
+                Work::Tree(Tree::Empty) => {}
+                Work::Tree(Tree::Single(only)) => self.stack.push(Work::Node(only)),
+                Work::Tree(Tree::Deep {
+                    prefix,
+                    middle,
+                    suffix,
+                    ..
+                }) => {
+                    self.stack.extend(suffix.iter().rev().map(Work::Node));
+                    self.stack.push(Work::Tree(middle));
+                    self.stack.extend(prefix.iter().rev().map(Work::Node));
+                }
+            }
+        }
+    }
+}
+
+impl<'a, T: Measured> IntoIterator for &'a FingerTree<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[derive(Clone)]
+struct Counted<T>(T);
+
+impl<T> Measured for Counted<T> {
+    type Measure = Size;
+
+    fn measure(&self) -> Size {
+        Size(1)
+    }
+}
+
+/// A persistent sequence over a finger tree measured by size: O(1) ends, O(log n) indexing,
+/// splitting and concatenation.
+#[derive(Clone)]
+pub struct Seq<T> {
+    tree: FingerTree<Counted<T>>,
+}
+
+impl<T> Seq<T> {
+    /// Creates an empty Seq.
+    pub fn new() -> Self {
+        Seq {
+            tree: FingerTree::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.measure().0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.tree.find(|size| size.0 > index).map(|elem| &elem.0)
+    }
+
+    pub fn front(&self) -> Option<&T> {
+        self.tree.front().map(|elem| &elem.0)
+    }
+
+    pub fn back(&self) -> Option<&T> {
+        self.tree.back().map(|elem| &elem.0)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.tree.iter().map(|elem| &elem.0)
+    }
+}
+
+impl<T: Clone> Seq<T> {
+    pub fn push_front(&self, elem: T) -> Self {
+        Seq {
+            tree: self.tree.push_front(Counted(elem)),
+        }
+    }
+
+    pub fn push_back(&self, elem: T) -> Self {
+        Seq {
+            tree: self.tree.push_back(Counted(elem)),
+        }
+    }
+
+    pub fn pop_front(&self) -> Self {
+        Seq {
+            tree: self.tree.pop_front(),
+        }
+    }
+
+    pub fn pop_back(&self) -> Self {
+        Seq {
+            tree: self.tree.pop_back(),
+        }
+    }
+
+    pub fn concat(&self, other: &Self) -> Self {
+        Seq {
+            tree: self.tree.concat(&other.tree),
+        }
+    }
+
+    /// Splits into the first `index` elements and the rest.
+    pub fn split_at(&self, index: usize) -> (Self, Self) {
+        let (left, right) = self.tree.split(|size| size.0 > index);
+        (Seq { tree: left }, Seq { tree: right })
+    }
+}
+
+impl<T> Default for Seq<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Clone> FromIterator<T> for Seq<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Seq {
+            tree: iter.into_iter().map(Counted).collect(),
+        }
+    }
+}
+
+impl<T: PartialEq> PartialEq for Seq<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Seq<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+#[derive(Clone)]
+struct Prioritized<T>(T);
+
+impl<T: Ord + Clone> Measured for Prioritized<T> {
+    type Measure = Max<T>;
+
+    fn measure(&self) -> Max<T> {
+        Max(Some(self.0.clone()))
+    }
+}
+
+/// A persistent max-priority queue over a finger tree measured by max: O(1) peek and
+/// amortized O(1) push, O(log n) pop and merge.
+#[derive(Clone)]
+pub struct PriorityQueue<T: Ord + Clone> {
+    tree: FingerTree<Prioritized<T>>,
+    len: usize,
+}
+
+impl<T: Ord + Clone> PriorityQueue<T> {
+    /// Creates an empty PriorityQueue.
+    pub fn new() -> Self {
+        PriorityQueue {
+            tree: FingerTree::new(),
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the largest element. It's the measure of the whole tree, so there's no
+    /// searching.
+    pub fn peek(&self) -> Option<T> {
+        self.tree.measure().0
+    }
+
+    pub fn push(&self, elem: T) -> Self {
+        PriorityQueue {
+            tree: self.tree.push_back(Prioritized(elem)),
+            len: self.len + 1,
+        }
+    }
+
+    /// Returns a new queue without the largest element, or the first of them if there
+    /// are several.
+    pub fn pop(&self) -> Self {
+        let max = self.tree.measure();
+        if max.0.is_none() {
+            return self.clone();
+        }
+        // The running max first reaches the overall max at the element to take out
+        let (left, right) = self.tree.split(|m| *m == max);
+        PriorityQueue {
+            tree: left.concat(&right.pop_front()),
+            len: self.len - 1,
+        }
+    }
+
+    /// Returns a new queue holding the elements of both.
+    pub fn merge(&self, other: &Self) -> Self {
+        PriorityQueue {
+            tree: self.tree.concat(&other.tree),
+            len: self.len + other.len,
+        }
+    }
+
+    /// Iterates over the elements in the order they were pushed.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.tree.iter().map(|elem| &elem.0)
+    }
+}
+
+impl<T: Ord + Clone> Default for PriorityQueue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Ord + Clone> FromIterator<T> for PriorityQueue<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut queue = PriorityQueue::new();
+        for elem in iter {
+            queue = queue.push(elem);
+        }
+        queue
+    }
+}
+
+impl<T: Ord + Clone + fmt::Debug> fmt::Debug for PriorityQueue<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{measure_all, FingerTree, Measured, Monoid, Node, PriorityQueue, Seq, Tree};
+    use std::collections::BinaryHeap;
+
+    /// Checks digit sizes, node arities and depths, and every cached measure, returning the
+    /// number of elements.
+    fn check<T: Measured>(tree: &Tree<T>, depth: usize) -> usize
+    where
+        T::Measure: PartialEq + std::fmt::Debug,
+    {
+        fn check_node<T: Measured>(node: &Node<T>, depth: usize) -> usize
+        where
+            T::Measure: PartialEq + std::fmt::Debug,
+        {
+            match node {
+                Node::Leaf(_) => {
+                    assert_eq!(depth, 0);
+                    1
+                }
+                Node::Branch(measure, children) => {
+                    assert!(depth > 0 && (2..=3).contains(&children.len()));
+                    assert_eq!(*measure, measure_all(children));
+                    children
+                        .iter()
+                        .map(|child| check_node(child, depth - 1))
+                        .sum()
+                }
+            }
+        }
+        match tree {
+            Tree::Empty => 0,
+            Tree::Single(only) => check_node(only, depth),
+            Tree::Deep {
+                measure,
+                prefix,
+                middle,
+                suffix,
+            } => {
+                assert!((1..=4).contains(&prefix.len()) && (1..=4).contains(&suffix.len()));
+                let all = measure_all(prefix)
+                    .combine(&middle.measure())
+                    .combine(&measure_all(suffix));
+                assert_eq!(*measure, all);
+                let ends: usize = prefix
+                    .iter()
+                    .chain(suffix)
+                    .map(|n| check_node(n, depth))
+                    .sum();
+                ends + check(middle, depth + 1)
+            }
+        }
+    }
+
+    /// Sums the weights before it, to find elements by cumulative weight.
+    #[derive(Clone, Debug, PartialEq)]
+    struct Weight(u32);
+
+    impl Monoid for Weight {
+        fn identity() -> Self {
+            Weight(0)
+        }
+
+        fn combine(&self, other: &Self) -> Self {
+            Weight(self.0 + other.0)
+        }
+    }
+
+    impl Measured for Weight {
+        type Measure = Weight;
+
+        fn measure(&self) -> Weight {
+            self.clone()
+        }
+    }
+
+    #[test]
+    fn basics() {
+        let seq = Seq::new();
+
+        // Check empty seq behaves right
+        assert_eq!(seq.front(), None);
+        assert_eq!(seq.get(0), None);
+        assert!(seq.pop_back().is_empty());
+
+        // Populate seq
+        let seq = seq.push_back(2).push_back(3).push_front(1);
+        assert_eq!(seq.len(), 3);
+        assert_eq!((seq.front(), seq.back()), (Some(&1), Some(&3)));
+        assert_eq!(seq.get(1), Some(&2));
+        assert_eq!(seq.get(3), None);
+
+        // Check normal removal
+        let seq = seq.pop_front();
+        assert_eq!(seq.front(), Some(&2));
+        let seq = seq.pop_back();
+        assert_eq!(format!("{:?}", seq), "[2]");
+
+        // Push some more just to make sure nothing's corrupted
+        let seq = (3..100).fold(seq, |seq, i| seq.push_back(i));
+        check(&seq.tree.tree, 0);
+        assert_eq!(seq.len(), 98);
+        assert!((0..98).all(|i| seq.get(i) == Some(&(i + 2))));
+
+        // Check exhaustion
+        let seq = (0..98).fold(seq, |seq, _| seq.pop_front());
+        assert!(seq.is_empty());
+        assert_eq!(seq.back(), None);
+    }
+
+    #[test]
+    fn concat_and_split() {
+        let a: Seq<u32> = (0..100).collect();
+        let b: Seq<u32> = (100..250).collect();
+        let joined = a.concat(&b);
+        check(&joined.tree.tree, 0);
+        assert!(joined.iter().copied().eq(0..250));
+        assert_eq!(joined.get(180), Some(&180));
+
+        for at in [0, 1, 99, 100, 137, 249, 250, 300] {
+            let (left, right) = joined.split_at(at);
+            check(&left.tree.tree, 0);
+            check(&right.tree.tree, 0);
+            let at = at.min(250);
+            assert!(left.iter().copied().eq(0..at as u32));
+            assert!(right.iter().copied().eq(at as u32..250));
+            assert_eq!(left.concat(&right), joined);
+        }
+
+        // The originals are untouched
+        assert_eq!(a.len(), 100);
+        assert!(b.iter().copied().eq(100..250));
+    }
+
+    #[test]
+    fn custom_measure() {
+        let tree: FingerTree<Weight> = (1..=10).map(Weight).collect();
+        assert_eq!(tree.measure(), Weight(55));
+
+        // The first element where the running total passes 20 is 6 (1 + ... + 6 = 21)
+        assert_eq!(tree.find(|w| w.0 > 20), Some(&Weight(6)));
+        assert_eq!(tree.find(|w| w.0 > 55), None);
+        let (light, heavy) = tree.split(|w| w.0 > 20);
+        assert_eq!(light.measure(), Weight(15));
+        assert_eq!(heavy.front(), Some(&Weight(6)));
+    }
+
+    #[test]
+    fn priority_queue() {
+        let queue: PriorityQueue<u32> = [5, 1, 8, 3, 8, 2].iter().copied().collect();
+        assert_eq!(queue.peek(), Some(8));
+        let mut drained = Vec::new();
+        let mut rest = queue.clone();
+        while let Some(max) = rest.peek() {
+            drained.push(max);
+            rest = rest.pop();
+        }
+        assert_eq!(drained, [8, 8, 5, 3, 2, 1]);
+        assert!(rest.pop().is_empty());
+
+        // Pops take out just the one element and keep the others in push order
+        assert!(queue.pop().iter().copied().eq([5, 1, 3, 8, 2]));
+        let merged = queue.merge(&[9].iter().copied().collect());
+        assert_eq!((merged.len(), merged.peek()), (7, Some(9)));
+        assert_eq!(queue.len(), 6);
+    }
+
+    #[test]
+    fn against_vec() {
+        // Random operations on random versions, each checked against its own model
+        let mut versions = vec![(Seq::new(), Vec::new())];
+        let mut x: u32 = 1;
+        let n = if cfg!(miri) { 300 } else { 5_000 };
+        for _ in 0..n {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let (seq, mut model) = versions[(x >> 4) as usize % versions.len()].clone();
+            let other = &versions[(x >> 12) as usize % versions.len()];
+            let next = match x >> 29 {
+                0 => {
+                    model.pop();
+                    seq.pop_back()
+                }
+                1 => {
+                    if !model.is_empty() {
+                        model.remove(0);
+                    }
+                    seq.pop_front()
+                }
+                // Concatenating versions doubles sizes fast, so cap them
+                2 if model.len() + other.1.len() < 1000 => {
+                    model.extend(other.1.iter().copied());
+                    seq.concat(&other.0)
+                }
+                3 => {
+                    let at = (x >> 8) as usize % (model.len() + 1);
+                    let (left, right) = seq.split_at(at);
+                    let tail = model.split_off(at);
+                    versions.push((right, tail));
+                    left
+                }
+                4 | 5 => {
+                    model.insert(0, x);
+                    seq.push_front(x)
+                }
+                _ => {
+                    model.push(x);
+                    seq.push_back(x)
+                }
+            };
+            assert_eq!(next.len(), model.len());
+            assert_eq!(next.front(), model.first());
+            assert_eq!(next.back(), model.last());
+            versions.push((next, model));
+        }
+        for (seq, model) in versions.iter().step_by(50) {
+            assert_eq!(check(&seq.tree.tree, 0), model.len());
+            assert!(seq.iter().eq(model.iter()));
+            assert!((0..model.len()).all(|i| seq.get(i) == model.get(i)));
+        }
+    }
+
+    #[test]
+    fn against_binary_heap() {
+        let mut queue = PriorityQueue::new();
+        let mut model = BinaryHeap::new();
+        let mut x: u32 = 1;
+        let n = if cfg!(miri) { 300 } else { 5_000 };
+        for _ in 0..n {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            if x >> 30 == 0 {
+                model.pop();
+                queue = queue.pop();
+            } else {
+                model.push(x % 100);
+                queue = queue.push(x % 100);
+            }
+            assert_eq!(queue.len(), model.len());
+            assert_eq!(queue.peek(), model.peek().copied());
+        }
+        assert_eq!(check(&queue.tree.tree, 0), model.len());
+    }
+}
@@ -0,0 +1,571 @@
+//! # Graph
+//!
+//! A graph with a weight on every node and every edge, kept as adjacency lists. Nodes and
+//! edges live in two [`SlotMap`]s, and each node keeps the ids of the edges leaving it
+//! (and, in a directed graph, of those coming into it):
+//!
+//! ```text
+//!   nodes:  a: out [e0 e1]  in []          edges:  e0: a -> b
+//!           b: out [e2]     in [e0]                e1: a -> c
+//!           c: out []       in [e1 e2]             e2: b -> c
+//! ```
+//!
+//! Ids are slot map keys, so they're stable: removing a node or an edge doesn't move
+//! anything else, and an id for something that's been removed just stops working instead
+//! of quietly pointing at whatever took its slot. Removing a node removes its edges too.
+//!
+//! Whether the graph is directed is a type parameter, [`Directed`] or [`Undirected`]. An
+//! undirected edge is in the out list of both its ends and is followed either way.
+//!
+//! Adding a node or an edge is O(1), visiting a node's edges is O(degree), and removing
+//! an edge is O(degree) of its ends, to take it out of their lists. Finding whether there's
+//! an edge between two nodes is a walk down one of their lists, too.
+
+use crate::slot_map::{Key, SlotMap};
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::{Index, IndexMut};
+
+/// Whether a [`Graph`]'s edges have a direction.
+pub trait Direction {
+    const DIRECTED: bool;
+}
+
+/// Edges go from their source to their target only.
+pub enum Directed {}
+
+/// Edges go both ways.
+pub enum Undirected {}
+
+impl Direction for Directed {
+    const DIRECTED: bool = true;
+}
+
+impl Direction for Undirected {
+    const DIRECTED: bool = false;
+}
+
+/// A handle to a node of a [`Graph`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(Key);
+
+/// A handle to an edge of a [`Graph`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct EdgeId(Key);
+
+impl NodeId {
+    /// Slot the node is in, below [`Graph::node_bound`]. Slots are reused, so this alone
+    /// doesn't identify a node, but it's unique among the nodes a graph has at a time.
+    pub fn index(&self) -> usize {
+        self.0.index()
+    }
+}
+
+impl EdgeId {
+    /// Slot the edge is in, like [`NodeId::index`].
+    pub fn index(&self) -> usize {
+        self.0.index()
+    }
+}
+
+/// An edge, as seen from its source. For an undirected edge met going from a node, the
+/// source is that node and the target is the other end.
+#[derive(Debug)]
+pub struct EdgeRef<'a, E> {
+    pub id: EdgeId,
+    pub source: NodeId,
+    pub target: NodeId,
+    pub weight: &'a E,
+}
+
+impl<E> Clone for EdgeRef<'_, E> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<E> Copy for EdgeRef<'_, E> {}
+
+#[derive(Clone)]
+struct Node<N> {
+    weight: N,
+    out: Vec<EdgeId>,
+    // Always empty in an undirected graph
+    incoming: Vec<EdgeId>,
+}
+
+#[derive(Clone)]
+struct Edge<E> {
+    weight: E,
+    source: NodeId,
+    target: NodeId,
+}
+
+pub struct Graph<N, E, D: Direction = Directed> {
+    nodes: SlotMap<Node<N>>,
+    edges: SlotMap<Edge<E>>,
+    node_bound: usize,
+    direction: PhantomData<D>,
+}
+
+pub type DiGraph<N, E> = Graph<N, E, Directed>;
+pub type UnGraph<N, E> = Graph<N, E, Undirected>;
+
+impl<N, E, D: Direction> Graph<N, E, D> {
+    /// Creates an empty Graph.
+    pub fn new() -> Self {
+        Graph {
+            nodes: SlotMap::new(),
+            edges: SlotMap::new(),
+            node_bound: 0,
+            direction: PhantomData,
+        }
+    }
+
+    pub fn is_directed(&self) -> bool {
+        D::DIRECTED
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.edges.len()
+    }
+
+    /// One more than the largest [`NodeId::index`] there's been, for keeping data about
+    /// each node in a `Vec` or a [`BitSet`](crate::bit_set::BitSet) indexed by it.
+    pub fn node_bound(&self) -> usize {
+        self.node_bound
+    }
+
+    pub fn add_node(&mut self, weight: N) -> NodeId {
+        let id = NodeId(self.nodes.insert(Node {
+            weight,
+            out: Vec::new(),
+            incoming: Vec::new(),
+        }));
+        self.node_bound = self.node_bound.max(id.index() + 1);
+        id
+    }
+
+    /// Adds an edge from `source` to `target`. Parallel edges and loops are allowed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either node isn't in the graph.
+    pub fn add_edge(&mut self, source: NodeId, target: NodeId, weight: E) -> EdgeId {
+        assert!(
+            self.contains_node(source) && self.contains_node(target),
+            "stale or foreign node"
+        );
+        let id = EdgeId(self.edges.insert(Edge {
+            weight,
+            source,
+            target,
+        }));
+        self.nodes[source.0].out.push(id);
+        if D::DIRECTED {
+            self.nodes[target.0].incoming.push(id);
+        } else if source != target {
+            self.nodes[target.0].out.push(id);
+        }
+        id
+    }
+
+    /// Removes a node along with every edge it's on, and returns its weight.
+    pub fn remove_node(&mut self, id: NodeId) -> Option<N> {
+        let node = self.nodes.get(id.0)?;
+        let mut edges: Vec<_> = node.out.iter().chain(&node.incoming).copied().collect();
+        // A directed loop is in both lists
+        edges.sort_unstable();
+        edges.dedup();
+        for edge in edges {
+            self.remove_edge(edge);
+        }
+        self.nodes.remove(id.0).map(|node| node.weight)
+    }
+
+    /// Removes an edge and returns its weight.
+    pub fn remove_edge(&mut self, id: EdgeId) -> Option<E> {
+        let edge = self.edges.remove(id.0)?;
+        fn unlink(list: &mut Vec<EdgeId>, id: EdgeId) {
+            if let Some(i) = list.iter().position(|&e| e == id) {
+                list.swap_remove(i);
+            }
+        }
+        unlink(&mut self.nodes[edge.source.0].out, id);
+        if D::DIRECTED {
+            unlink(&mut self.nodes[edge.target.0].incoming, id);
+        } else if edge.source != edge.target {
+            unlink(&mut self.nodes[edge.target.0].out, id);
+        }
+        Some(edge.weight)
+    }
+
+    /// Removes every node and edge. Every id handed out so far goes stale.
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.edges.clear();
+    }
+
+    pub fn contains_node(&self, id: NodeId) -> bool {
+        self.nodes.contains_key(id.0)
+    }
+
+    pub fn contains_edge(&self, id: EdgeId) -> bool {
+        self.edges.contains_key(id.0)
+    }
+
+    pub fn node(&self, id: NodeId) -> Option<&N> {
+        self.nodes.get(id.0).map(|node| &node.weight)
+    }
+
+    pub fn node_mut(&mut self, id: NodeId) -> Option<&mut N> {
+        self.nodes.get_mut(id.0).map(|node| &mut node.weight)
+    }
+
+    pub fn edge(&self, id: EdgeId) -> Option<&E> {
+        self.edges.get(id.0).map(|edge| &edge.weight)
+    }
+
+    pub fn edge_mut(&mut self, id: EdgeId) -> Option<&mut E> {
+        self.edges.get_mut(id.0).map(|edge| &mut edge.weight)
+    }
+
+    /// Returns the source and target of an edge.
+    pub fn endpoints(&self, id: EdgeId) -> Option<(NodeId, NodeId)> {
+        self.edges.get(id.0).map(|edge| (edge.source, edge.target))
+    }
+
+    /// Returns an edge from `source` to `target`, if there's any. In an undirected graph,
+    /// one between them either way round.
+    pub fn find_edge(&self, source: NodeId, target: NodeId) -> Option<EdgeId> {
+        self.edges_of(source)
+            .find(|edge| edge.target == target)
+            .map(|edge| edge.id)
+    }
+
+    /// Iterates over the nodes and their weights, in slot order.
+    pub fn nodes(&self) -> impl Iterator<Item = (NodeId, &N)> + '_ {
+        self.nodes
+            .iter()
+            .map(|(key, node)| (NodeId(key), &node.weight))
+    }
+
+    pub fn node_ids(&self) -> impl Iterator<Item = NodeId> + '_ {
+        self.nodes.keys().map(NodeId)
+    }
+
+    /// Iterates over every edge once, from its source as it was added.
+    pub fn edges(&self) -> impl Iterator<Item = EdgeRef<'_, E>> + '_ {
+        self.edges.iter().map(|(key, edge)| EdgeRef {
+            id: EdgeId(key),
+            source: edge.source,
+            target: edge.target,
+            weight: &edge.weight,
+        })
+    }
+
+    /// Looks at an edge from the end `from`.
+    fn edge_from(&self, id: EdgeId, from: NodeId) -> EdgeRef<'_, E> {
+        let edge = &self.edges[id.0];
+        let target = if edge.source == from {
+            edge.target
+        } else {
+            edge.source
+        };
+        EdgeRef {
+            id,
+            source: from,
+            target,
+            weight: &edge.weight,
+        }
+    }
+
+    /// Iterates over the edges leaving a node, or in an undirected graph, all its edges.
+    /// A stale id has none.
+    pub fn edges_of(&self, id: NodeId) -> impl Iterator<Item = EdgeRef<'_, E>> + '_ {
+        let out = self.nodes.get(id.0).map_or(&[][..], |node| &node.out);
+        out.iter().map(move |&edge| self.edge_from(edge, id))
+    }
+
+    /// Iterates over the edges coming into a node, each seen from the node it comes from.
+    /// In an undirected graph that's all its edges again.
+    pub fn incoming(&self, id: NodeId) -> impl Iterator<Item = EdgeRef<'_, E>> + '_ {
+        let node = self.nodes.get(id.0);
+        let list = node.map_or(&[][..], |node| {
+            if D::DIRECTED {
+                &node.incoming
+            } else {
+                &node.out
+            }
+        });
+        list.iter().map(move |&edge| {
+            let edge = self.edge_from(edge, id);
+            EdgeRef {
+                source: edge.target,
+                target: edge.source,
+                ..edge
+            }
+        })
+    }
+
+    /// Iterates over the nodes a node has an edge to, once per edge.
+    pub fn neighbors(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        self.edges_of(id).map(|edge| edge.target)
+    }
+
+    /// How many edges leave a node, or in an undirected graph, how many it's on. A loop
+    /// counts once.
+    pub fn degree(&self, id: NodeId) -> usize {
+        self.nodes.get(id.0).map_or(0, |node| node.out.len())
+    }
+}
+
+impl<N: Clone, E: Clone, D: Direction> Clone for Graph<N, E, D> {
+    fn clone(&self) -> Self {
+        Graph {
+            nodes: self.nodes.clone(),
+            edges: self.edges.clone(),
+            node_bound: self.node_bound,
+            direction: PhantomData,
+        }
+    }
+}
+
+impl<N, E, D: Direction> Default for Graph<N, E, D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<N, E, D: Direction> Index<NodeId> for Graph<N, E, D> {
+    type Output = N;
+
+    fn index(&self, id: NodeId) -> &N {
+        &self.nodes[id.0].weight
+    }
+}
+
+impl<N, E, D: Direction> IndexMut<NodeId> for Graph<N, E, D> {
+    fn index_mut(&mut self, id: NodeId) -> &mut N {
+        &mut self.nodes[id.0].weight
+    }
+}
+
+impl<N, E, D: Direction> Index<EdgeId> for Graph<N, E, D> {
+    type Output = E;
+
+    fn index(&self, id: EdgeId) -> &E {
+        &self.edges[id.0].weight
+    }
+}
+
+impl<N, E, D: Direction> IndexMut<EdgeId> for Graph<N, E, D> {
+    fn index_mut(&mut self, id: EdgeId) -> &mut E {
+        &mut self.edges[id.0].weight
+    }
+}
+
+impl<N: fmt::Debug, E: fmt::Debug, D: Direction> fmt::Debug for Graph<N, E, D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let nodes: Vec<_> = self.nodes().collect();
+        let edges: Vec<_> = self
+            .edges()
+            .map(|edge| (edge.source, edge.target, edge.weight))
+            .collect();
+        f.debug_struct("Graph")
+            .field("directed", &D::DIRECTED)
+            .field("nodes", &nodes)
+            .field("edges", &edges)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{DiGraph, Direction, Graph, NodeId, UnGraph};
+    use std::collections::HashMap;
+
+    /// Checks every edge is in exactly the right lists, and nothing else is.
+    fn check<N, E, D: Direction>(graph: &Graph<N, E, D>) {
+        let mut expected = HashMap::new();
+        for edge in graph.edges() {
+            *expected.entry((edge.source, true)).or_insert(0) += 1;
+            if D::DIRECTED {
+                *expected.entry((edge.target, false)).or_insert(0) += 1;
+            } else if edge.source != edge.target {
+                *expected.entry((edge.target, true)).or_insert(0) += 1;
+            }
+        }
+        for (key, node) in graph.nodes.iter() {
+            let id = NodeId(key);
+            assert!(id.index() < graph.node_bound());
+            assert_eq!(
+                node.out.len(),
+                expected.get(&(id, true)).copied().unwrap_or(0)
+            );
+            assert_eq!(
+                node.incoming.len(),
+                expected.get(&(id, false)).copied().unwrap_or(0)
+            );
+            for edge in node.out.iter().chain(&node.incoming) {
+                let (source, target) = graph.endpoints(*edge).unwrap();
+                assert!(source == id || target == id);
+            }
+        }
+    }
+
+    #[test]
+    fn basics() {
+        let mut graph = DiGraph::new();
+
+        // Check empty graph behaves right
+        assert_eq!(graph.node_count(), 0);
+        assert_eq!(graph.edges().count(), 0);
+
+        // Populate graph
+        let a = graph.add_node("a");
+        let b = graph.add_node("b");
+        let c = graph.add_node("c");
+        let ab = graph.add_edge(a, b, 1);
+        graph.add_edge(a, c, 2);
+        let bc = graph.add_edge(b, c, 3);
+        check(&graph);
+        assert_eq!(graph.edge_count(), 3);
+        assert_eq!(graph[b], "b");
+        assert_eq!(graph[bc], 3);
+        assert_eq!(graph.neighbors(a).collect::<Vec<_>>(), [b, c]);
+        assert_eq!(graph.neighbors(c).count(), 0);
+        let into_c: Vec<_> = graph.incoming(c).map(|edge| edge.source).collect();
+        assert_eq!(into_c, [a, b]);
+        assert_eq!(graph.find_edge(a, b), Some(ab));
+        assert_eq!(graph.find_edge(b, a), None);
+
+        // Check normal removal
+        assert_eq!(graph.remove_edge(ab), Some(1));
+        assert_eq!(graph.remove_edge(ab), None);
+        assert_eq!(graph.neighbors(a).collect::<Vec<_>>(), [c]);
+        assert_eq!(graph.remove_node(b), Some("b"));
+        assert!(!graph.contains_edge(bc));
+        assert_eq!(graph.incoming(c).count(), 1);
+        check(&graph);
+
+        // Push some more just to make sure nothing's corrupted
+        let d = graph.add_node("d");
+        graph.add_edge(d, d, 4);
+        graph.add_edge(c, d, 5);
+        graph[a] = "A";
+        check(&graph);
+        assert_eq!(graph.neighbors(d).collect::<Vec<_>>(), [d]);
+        assert_eq!(graph.incoming(d).count(), 2);
+
+        // Check exhaustion
+        for node in [a, c, d].iter() {
+            graph.remove_node(*node);
+        }
+        check(&graph);
+        assert_eq!(graph.node_count(), 0);
+        assert_eq!(graph.edge_count(), 0);
+    }
+
+    #[test]
+    fn stale_ids() {
+        let mut graph: DiGraph<u32, ()> = Graph::new();
+        let a = graph.add_node(1);
+        graph.remove_node(a);
+
+        // The new node gets the same slot but not the same id
+        let b = graph.add_node(2);
+        assert_eq!(a.index(), b.index());
+        assert_eq!(graph.node(a), None);
+        assert_eq!(graph.neighbors(a).count(), 0);
+        assert_eq!(graph.node(b), Some(&2));
+        assert_eq!(graph.node_bound(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "stale or foreign node")]
+    fn edge_to_stale_node() {
+        let mut graph = DiGraph::new();
+        let a = graph.add_node(());
+        let b = graph.add_node(());
+        graph.remove_node(b);
+        graph.add_edge(a, b, ());
+    }
+
+    #[test]
+    fn undirected() {
+        let mut graph = UnGraph::new();
+        let a = graph.add_node(());
+        let b = graph.add_node(());
+        let ab = graph.add_edge(a, b, "ab");
+        graph.add_edge(b, b, "loop");
+        check(&graph);
+        assert!(!graph.is_directed());
+
+        // An edge is seen from whichever end it's looked at
+        let from_b: Vec<_> = graph.edges_of(b).map(|e| (e.source, e.target)).collect();
+        assert_eq!(from_b, [(b, a), (b, b)]);
+        assert_eq!(graph.find_edge(b, a), Some(ab));
+        assert_eq!(graph.degree(b), 2);
+        assert_eq!(graph.incoming(a).map(|e| e.source).collect::<Vec<_>>(), [b]);
+
+        graph.remove_edge(ab);
+        check(&graph);
+        assert_eq!(graph.neighbors(a).count(), 0);
+        assert_eq!(graph.neighbors(b).collect::<Vec<_>>(), [b]);
+        graph.remove_node(b);
+        assert_eq!(graph.edge_count(), 0);
+    }
+
+    #[test]
+    fn against_edge_list() {
+        // Random additions and removals, checked against a plain list of live edges
+        let mut graph = UnGraph::new();
+        let mut nodes = Vec::new();
+        let mut edges = Vec::new();
+        let mut x: u32 = 1;
+        let n = if cfg!(miri) { 500 } else { 5_000 };
+        for _ in 0..n {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let pick = (x >> 8) as usize;
+            match x >> 29 {
+                0 | 1 => nodes.push(graph.add_node(x)),
+                2..=4 if !nodes.is_empty() => {
+                    let (a, b) = (nodes[pick % nodes.len()], nodes[(pick >> 8) % nodes.len()]);
+                    edges.push((graph.add_edge(a, b, x), a, b));
+                }
+                5 if !nodes.is_empty() => {
+                    let node = nodes.swap_remove(pick % nodes.len());
+                    graph.remove_node(node);
+                    edges.retain(|&(_, a, b)| a != node && b != node);
+                }
+                _ if !edges.is_empty() => {
+                    let (edge, ..) = edges.swap_remove(pick % edges.len());
+                    assert!(graph.remove_edge(edge).is_some());
+                }
+                _ => {}
+            }
+            assert_eq!(graph.node_count(), nodes.len());
+            assert_eq!(graph.edge_count(), edges.len());
+        }
+        check(&graph);
+        for &node in &nodes {
+            let mut expected: Vec<_> = edges
+                .iter()
+                .filter_map(|&(_, a, b)| match (a == node, b == node) {
+                    (true, _) => Some(b),
+                    (_, true) => Some(a),
+                    _ => None,
+                })
+                .collect();
+            let mut actual: Vec<_> = graph.neighbors(node).collect();
+            expected.sort();
+            actual.sort();
+            assert_eq!(actual, expected);
+        }
+    }
+}
@@ -0,0 +1,757 @@
+//! # Hash array mapped trie
+//!
+//! An immutable hash map in the style of [`crate::persistent`]: `insert` and `remove` take
+//! `&self` and return a new map, and the old one stays valid and unchanged. It's Bagwell's
+//! HAMT (2001), the map behind Clojure's and Scala's immutable hash maps.
+//!
+//! The map is a trie on the bits of each key's hash, five at a time: the root picks one of
+//! 32 children by the lowest five bits, the next level by the five after that, and so on.
+//! A node doesn't keep 32 slots, most of them empty. It keeps a 32-bit bitmap of which
+//! children it has and a packed array of just those, and finds child `i` at the position
+//! given by the number of bits set below bit `i`:
+//!
+//! ```text
+//!   bitmap    bits 1, 4, 7 and 10 set
+//!   children  [c1, c4, c7, c10]
+//!
+//!   child 7: bit 7 is set, and 2 bits below it, so children[2]
+//! ```
+//!
+//! A child is either a key-value pair, stored right there, or another node one level down.
+//! Two keys only push each other down a level while their hashes agree so far, so the
+//! trie is about log32 n deep. Keys whose whole hashes are equal share a collision node, a
+//! plain list.
+//!
+//! Changing the map copies the nodes on the path to the key and shares everything else,
+//! through `make_mut` on the node pointers, which copies a node only if another version
+//! also holds it. Removing tidies up on the way back: a node left holding a single pair
+//! collapses into its parent, so no path is left leading down to a lone pair.
+//!
+//! The pointers are `Rc` by default, which is cheaper and fine for versions that stay on
+//! one thread. [`SyncHamtMap`] is the same map over `Arc`, and is `Send` and `Sync`
+//! whenever its keys and values are, so versions can be handed between threads.
+
+use std::borrow::Borrow;
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+use std::iter::FromIterator;
+use std::mem;
+use std::ops::Deref;
+use std::rc::Rc;
+use std::slice;
+use std::sync::Arc;
+
+const BITS: u32 = 5;
+const MASK: u64 = (1 << BITS) - 1;
+
+/// The reference-counted pointer a [`HamtMap`] builds its nodes from.
+pub trait PointerKind {
+    type Pointer<T>: Deref<Target = T> + Clone;
+
+    fn new<T>(value: T) -> Self::Pointer<T>;
+
+    /// Returns the value to change in place, first copying it if it's shared.
+    fn make_mut<T: Clone>(this: &mut Self::Pointer<T>) -> &mut T;
+}
+
+/// Nodes behind `Rc`, for maps that stay on one thread.
+pub enum RcPointer {}
+
+/// Nodes behind `Arc`, for maps shared between threads.
+pub enum ArcPointer {}
+
+impl PointerKind for RcPointer {
+    type Pointer<T> = Rc<T>;
+
+    fn new<T>(value: T) -> Rc<T> {
+        Rc::new(value)
+    }
+
+    fn make_mut<T: Clone>(this: &mut Rc<T>) -> &mut T {
+        Rc::make_mut(this)
+    }
+}
+
+impl PointerKind for ArcPointer {
+    type Pointer<T> = Arc<T>;
+
+    fn new<T>(value: T) -> Arc<T> {
+        Arc::new(value)
+    }
+
+    fn make_mut<T: Clone>(this: &mut Arc<T>) -> &mut T {
+        Arc::make_mut(this)
+    }
+}
+
+enum Node<K, V, P: PointerKind> {
+    Branch {
+        bitmap: u32,
+        children: Vec<Child<K, V, P>>,
+    },
+    // Keys whose hashes are equal all the way down
+    Collision {
+        hash: u64,
+        entries: Vec<(K, V)>,
+    },
+}
+
+enum Child<K, V, P: PointerKind> {
+    Leaf { hash: u64, key: K, value: V },
+    Node(P::Pointer<Node<K, V, P>>),
+}
+
+impl<K: Clone, V: Clone, P: PointerKind> Clone for Node<K, V, P> {
+    fn clone(&self) -> Self {
+        match self {
+            Node::Branch { bitmap, children } => Node::Branch {
+                bitmap: *bitmap,
+                children: children.clone(),
+            },
+            Node::Collision { hash, entries } => Node::Collision {
+                hash: *hash,
+                entries: entries.clone(),
+            },
+        }
+    }
+}
+
+impl<K: Clone, V: Clone, P: PointerKind> Clone for Child<K, V, P> {
+    fn clone(&self) -> Self {
+        match self {
+            Child::Leaf { hash, key, value } => Child::Leaf {
+                hash: *hash,
+                key: key.clone(),
+                value: value.clone(),
+            },
+            Child::Node(node) => Child::Node(node.clone()),
+        }
+    }
+}
+
+/// The bit for `hash` in the bitmap of a node at `shift`.
+fn bit(hash: u64, shift: u32) -> u32 {
+    1 << ((hash >> shift) & MASK)
+}
+
+/// Where the child for `bit` is, or would go, in the packed array.
+fn position(bitmap: u32, bit: u32) -> usize {
+    (bitmap & (bit - 1)).count_ones() as usize
+}
+
+fn leaf_hash<K, V, P: PointerKind>(child: &Child<K, V, P>) -> u64 {
+    match child {
+        Child::Leaf { hash, .. } => *hash,
+        Child::Node(_) => unreachable!("pairing a node"),
+    }
+}
+
+/// A node at `shift` holding two pairs with different keys.
+fn pair<K, V, P: PointerKind>(shift: u32, a: Child<K, V, P>, b: Child<K, V, P>) -> Node<K, V, P> {
+    let (hash_a, hash_b) = (leaf_hash(&a), leaf_hash(&b));
+    if hash_a == hash_b {
+        if let (
+            Child::Leaf {
+                key: key_a,
+                value: value_a,
+                ..
+            },
+            Child::Leaf {
+                key: key_b,
+                value: value_b,
+                ..
+            },
+        ) = (a, b)
+        {
+            return Node::Collision {
+                hash: hash_a,
+                entries: vec![(key_a, value_a), (key_b, value_b)],
+            };
+        }
+        unreachable!();
+    }
+    let (bit_a, bit_b) = (bit(hash_a, shift), bit(hash_b, shift));
+    if bit_a == bit_b {
+        // Same five bits here, so they part ways further down
+        let below = pair(shift + BITS, a, b);
+        return Node::Branch {
+            bitmap: bit_a,
+            children: vec![Child::Node(P::new(below))],
+        };
+    }
+    let children = if bit_a < bit_b {
+        vec![a, b]
+    } else {
+        vec![b, a]
+    };
+    Node::Branch {
+        bitmap: bit_a | bit_b,
+        children,
+    }
+}
+
+fn insert<K, V, P>(node: &mut Node<K, V, P>, shift: u32, hash: u64, key: K, value: V) -> Option<V>
+where
+    K: Eq + Clone,
+    V: Clone,
+    P: PointerKind,
+{
+    if let Node::Collision { hash: shared, .. } = node {
+        if *shared != hash {
+            // A new hash has reached this collision node, so it needs a branch above it
+            let bitmap = bit(*shared, shift);
+            let collision = mem::replace(
+                node,
+                Node::Branch {
+                    bitmap,
+                    children: Vec::with_capacity(2),
+                },
+            );
+            if let Node::Branch { children, .. } = node {
+                children.push(Child::Node(P::new(collision)));
+            }
+        }
+    }
+    match node {
+        Node::Collision { entries, .. } => {
+            if let Some((_, old)) = entries.iter_mut().find(|(k, _)| *k == key) {
+                return Some(mem::replace(old, value));
+            }
+            entries.push((key, value));
+            None
+        }
+        Node::Branch { bitmap, children } => {
+            let bit = bit(hash, shift);
+            let at = position(*bitmap, bit);
+            if *bitmap & bit == 0 {
+                *bitmap |= bit;
+                children.insert(at, Child::Leaf { hash, key, value });
+                return None;
+            }
+            match &mut children[at] {
+                Child::Node(child) => insert(P::make_mut(child), shift + BITS, hash, key, value),
+                Child::Leaf {
+                    hash: h,
+                    key: k,
+                    value: v,
+                } if *h == hash && *k == key => Some(mem::replace(v, value)),
+                Child::Leaf { .. } => {
+                    let old = children.remove(at);
+                    let new = Child::Leaf { hash, key, value };
+                    let below = pair(shift + BITS, old, new);
+                    children.insert(at, Child::Node(P::new(below)));
+                    None
+                }
+            }
+        }
+    }
+}
+
+/// Removes `key`, which must be in the trie under `node`.
+fn remove<K, V, P, Q>(node: &mut Node<K, V, P>, shift: u32, hash: u64, key: &Q) -> V
+where
+    K: Borrow<Q> + Clone,
+    V: Clone,
+    P: PointerKind,
+    Q: Eq + ?Sized,
+{
+    match node {
+        Node::Collision { entries, .. } => {
+            let at = entries.iter().position(|(k, _)| k.borrow() == key).unwrap();
+            entries.swap_remove(at).1
+        }
+        Node::Branch { bitmap, children } => {
+            let bit = bit(hash, shift);
+            let at = position(*bitmap, bit);
+            let child = match &mut children[at] {
+                Child::Node(child) => P::make_mut(child),
+                Child::Leaf { .. } => {
+                    *bitmap &= !bit;
+                    match children.remove(at) {
+                        Child::Leaf { value, .. } => return value,
+                        Child::Node(_) => unreachable!(),
+                    }
+                }
+            };
+            let value = remove(child, shift + BITS, hash, key);
+            // A node down to one pair collapses into that pair
+            if let Some(leaf) = take_lone_leaf(child) {
+                children[at] = leaf;
+            }
+            value
+        }
+    }
+}
+
+/// Takes the pair out of a node holding just one pair and no nodes.
+fn take_lone_leaf<K, V, P: PointerKind>(node: &mut Node<K, V, P>) -> Option<Child<K, V, P>> {
+    match node {
+        Node::Branch { children, .. } if children.len() == 1 => match children[0] {
+            Child::Leaf { .. } => children.pop(),
+            Child::Node(_) => None,
+        },
+        Node::Collision { hash, entries } if entries.len() == 1 => {
+            let (key, value) = entries.pop()?;
+            Some(Child::Leaf {
+                hash: *hash,
+                key,
+                value,
+            })
+        }
+        _ => None,
+    }
+}
+
+pub struct HamtMap<K, V, P: PointerKind = RcPointer, S = RandomState> {
+    root: P::Pointer<Node<K, V, P>>,
+    len: usize,
+    hasher: S,
+}
+
+/// A [`HamtMap`] over `Arc`, to share between threads.
+pub type SyncHamtMap<K, V, S = RandomState> = HamtMap<K, V, ArcPointer, S>;
+
+impl<K, V, P: PointerKind> HamtMap<K, V, P, RandomState> {
+    /// Creates an empty HamtMap.
+    pub fn new() -> Self {
+        Self::with_hasher(RandomState::new())
+    }
+}
+
+impl<K, V, P: PointerKind, S> HamtMap<K, V, P, S> {
+    /// Creates an empty map that hashes keys with `hasher`. Every version made from it
+    /// shares a copy of the hasher.
+    pub fn with_hasher(hasher: S) -> Self {
+        HamtMap {
+            root: P::new(Node::Branch {
+                bitmap: 0,
+                children: Vec::new(),
+            }),
+            len: 0,
+            hasher,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Iterates over the entries, in the order of their hashes' bits.
+    pub fn iter(&self) -> Iter<'_, K, V, P> {
+        let mut iter = Iter {
+            stack: Vec::new(),
+            collision: [].iter(),
+            left: self.len,
+        };
+        iter.descend(&self.root);
+        iter
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.iter().map(|(k, _)| k)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.iter().map(|(_, v)| v)
+    }
+}
+
+impl<K, V, P, S> HamtMap<K, V, P, S>
+where
+    K: Eq + Hash,
+    P: PointerKind,
+    S: BuildHasher,
+{
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let hash = self.hasher.hash_one(key);
+        let mut node: &Node<K, V, P> = &self.root;
+        let mut shift = 0;
+        loop {
+            match node {
+                Node::Collision { entries, .. } => {
+                    return entries
+                        .iter()
+                        .find(|(k, _)| k.borrow() == key)
+                        .map(|(_, v)| v);
+                }
+                Node::Branch { bitmap, children } => {
+                    let bit = bit(hash, shift);
+                    if bitmap & bit == 0 {
+                        return None;
+                    }
+                    match &children[position(*bitmap, bit)] {
+                        Child::Node(child) => node = child,
+                        Child::Leaf {
+                            hash: h,
+                            key: k,
+                            value,
+                        } => {
+                            return (*h == hash && k.borrow() == key).then_some(value);
+                        }
+                    }
+                }
+            }
+            shift += BITS;
+        }
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+}
+
+impl<K, V, P, S> HamtMap<K, V, P, S>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+    P: PointerKind,
+    S: BuildHasher + Clone,
+{
+    /// Returns a new map with `key` mapped to `value`, replacing any value it had.
+    pub fn insert(&self, key: K, value: V) -> Self {
+        let mut map = self.clone();
+        map.insert_mut(key, value);
+        map
+    }
+
+    /// Returns a new map without `key`. If it wasn't there, that's just a copy of this
+    /// one, sharing everything.
+    pub fn remove<Q>(&self, key: &Q) -> Self
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let mut map = self.clone();
+        if self.contains_key(key) {
+            let hash = self.hasher.hash_one(key);
+            remove(P::make_mut(&mut map.root), 0, hash, key);
+            map.len -= 1;
+        }
+        map
+    }
+
+    fn insert_mut(&mut self, key: K, value: V) {
+        let hash = self.hasher.hash_one(&key);
+        if insert(P::make_mut(&mut self.root), 0, hash, key, value).is_none() {
+            self.len += 1;
+        }
+    }
+}
+
+impl<K, V, P: PointerKind, S: Clone> Clone for HamtMap<K, V, P, S> {
+    fn clone(&self) -> Self {
+        HamtMap {
+            root: self.root.clone(),
+            len: self.len,
+            hasher: self.hasher.clone(),
+        }
+    }
+}
+
+impl<K, V, P: PointerKind, S: Default> Default for HamtMap<K, V, P, S> {
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+impl<K, V, P, S> FromIterator<(K, V)> for HamtMap<K, V, P, S>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+    P: PointerKind,
+    S: BuildHasher + Clone + Default,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        // Nothing else holds this version's nodes, so they're built in place
+        let mut map = Self::default();
+        for (key, value) in iter {
+            map.insert_mut(key, value);
+        }
+        map
+    }
+}
+
+impl<K, V, P, S> PartialEq for HamtMap<K, V, P, S>
+where
+    K: Eq + Hash,
+    V: PartialEq,
+    P: PointerKind,
+    S: BuildHasher,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.iter().all(|(k, v)| other.get(k) == Some(v))
+    }
+}
+
+impl<K, V, P, S> Eq for HamtMap<K, V, P, S>
+where
+    K: Eq + Hash,
+    V: Eq,
+    P: PointerKind,
+    S: BuildHasher,
+{
+}
+
+impl<K: fmt::Debug, V: fmt::Debug, P: PointerKind, S> fmt::Debug for HamtMap<K, V, P, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// Iterator over the entries of a [`HamtMap`].
+pub struct Iter<'a, K, V, P: PointerKind> {
+    // The children left to visit at each level on the way down
+    stack: Vec<slice::Iter<'a, Child<K, V, P>>>,
+    collision: slice::Iter<'a, (K, V)>,
+    left: usize,
+}
+
+impl<'a, K, V, P: PointerKind> Iter<'a, K, V, P> {
+    fn descend(&mut self, node: &'a Node<K, V, P>) {
+        match node {
+            Node::Branch { children, .. } => self.stack.push(children.iter()),
+            Node::Collision { entries, .. } => self.collision = entries.iter(),
+        }
+    }
+}
+
+impl<'a, K, V, P: PointerKind> Iterator for Iter<'a, K, V, P> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((key, value)) = self.collision.next() {
+                self.left -= 1;
+                return Some((key, value));
+            }
+            match self.stack.last_mut()?.next() {
+                None => {
+                    self.stack.pop();
+                }
+                Some(Child::Leaf { key, value, .. }) => {
+                    self.left -= 1;
+                    return Some((key, value));
+                }
+                Some(Child::Node(node)) => self.descend(node),
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.left, Some(self.left))
+    }
+}
+
+impl<'a, K, V, P: PointerKind> ExactSizeIterator for Iter<'a, K, V, P> {}
+
+impl<'a, K, V, P: PointerKind, S> IntoIterator for &'a HamtMap<K, V, P, S> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V, P>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Child, HamtMap, Node, RcPointer, SyncHamtMap};
+    use std::collections::HashMap;
+    use std::hash::{BuildHasher, Hasher};
+    use std::rc::Rc;
+    use std::sync::Arc;
+    use std::thread;
+
+    /// Hashes everything into a handful of values, so keys collide all the way down.
+    #[derive(Clone, Default)]
+    struct Colliding;
+
+    struct CollidingHasher(u64);
+
+    impl BuildHasher for Colliding {
+        type Hasher = CollidingHasher;
+
+        fn build_hasher(&self) -> CollidingHasher {
+            CollidingHasher(0)
+        }
+    }
+
+    impl Hasher for CollidingHasher {
+        fn finish(&self) -> u64 {
+            // Varies in the top bits only, making for deep tries too
+            (self.0 % 4) << 62
+        }
+
+        fn write(&mut self, bytes: &[u8]) {
+            for &b in bytes {
+                self.0 = self.0.wrapping_mul(31).wrapping_add(b as u64);
+            }
+        }
+    }
+
+    #[test]
+    fn basics() {
+        let map: HamtMap<String, u32> = HamtMap::new();
+
+        // Check empty map behaves right
+        assert_eq!(map.get("a"), None);
+        assert!(map.remove("a").is_empty());
+
+        // Populate map
+        let map = map.insert("a".to_string(), 1).insert("b".to_string(), 2);
+        let map = map.insert("a".to_string(), 3);
+        assert_eq!(map.len(), 2);
+        assert_eq!((map.get("a"), map.get("b")), (Some(&3), Some(&2)));
+
+        // Check normal removal
+        let map = map.remove("a");
+        assert!(!map.contains_key("a"));
+        assert_eq!(map.remove("a").len(), 1);
+        assert_eq!(format!("{:?}", map), r#"{"b": 2}"#);
+
+        // Push some more just to make sure nothing's corrupted
+        let map = (0..100).fold(map, |map, i| map.insert(i.to_string(), i));
+        assert_eq!(map.len(), 101);
+        assert_eq!(map.iter().len(), 101);
+        assert_eq!(map.get("42"), Some(&42));
+
+        // Check exhaustion, down to a bare root
+        let map = (0..100).fold(map.remove("b"), |map, i| map.remove(&i.to_string()));
+        assert!(map.is_empty());
+        assert!(matches!(&*map.root, Node::Branch { children, .. } if children.is_empty()));
+    }
+
+    #[test]
+    fn persistence() {
+        let base: HamtMap<u32, u32> = (0..1000).map(|i| (i, i)).collect();
+        let inserted = base.insert(1000, 1000).insert(5, 50);
+        let removed = base.remove(&500);
+
+        // Every version is still just what it was
+        assert_eq!(base.len(), 1000);
+        assert_eq!((base.get(&5), inserted.get(&5)), (Some(&5), Some(&50)));
+        assert_eq!((base.get(&1000), inserted.get(&1000)), (None, Some(&1000)));
+        assert_eq!((base.get(&500), removed.get(&500)), (Some(&500), None));
+        assert_eq!(removed.len(), 999);
+        assert_eq!(base, base.remove(&5000));
+        assert_ne!(base, removed);
+    }
+
+    #[test]
+    fn sharing() {
+        let base: HamtMap<u32, u32> = (0..1000).map(|i| (i, i)).collect();
+        let updated = base.insert(0, 1);
+
+        // Only the path to key 0 was copied, so most of the root's children are shared
+        let nodes = |map: &HamtMap<u32, u32>| match &*map.root {
+            Node::Branch { children, .. } => children
+                .iter()
+                .filter_map(|child| match child {
+                    Child::Node(node) => Some(node.clone()),
+                    Child::Leaf { .. } => None,
+                })
+                .collect::<Vec<Rc<Node<u32, u32, RcPointer>>>>(),
+            Node::Collision { .. } => panic!("root is a branch"),
+        };
+        let (old, new) = (nodes(&base), nodes(&updated));
+        assert_eq!(old.len(), 32);
+        let shared = old
+            .iter()
+            .zip(&new)
+            .filter(|(a, b)| Rc::ptr_eq(a, b))
+            .count();
+        assert_eq!(shared, 31);
+    }
+
+    #[test]
+    fn collisions() {
+        let mut map: HamtMap<u32, u32, RcPointer, Colliding> = HamtMap::with_hasher(Colliding);
+        for i in 0..50 {
+            map = map.insert(i, i * 2);
+        }
+        assert_eq!(map.len(), 50);
+        assert!((0..50).all(|i| map.get(&i) == Some(&(i * 2))));
+        assert_eq!(map.get(&50), None);
+
+        let mut left = map.clone();
+        for i in (0..50).step_by(2) {
+            left = left.remove(&i);
+        }
+        assert_eq!(left.len(), 25);
+        assert!((0..50).all(|i| left.get(&i).is_some() == (i % 2 == 1)));
+        let mut emptied = left;
+        for i in 0..50 {
+            emptied = emptied.remove(&i);
+        }
+        assert!(emptied.is_empty());
+        assert_eq!(map.len(), 50);
+    }
+
+    #[test]
+    fn shared_between_threads() {
+        fn assert_send_sync<T: Send + Sync>(_: &T) {}
+
+        let base: SyncHamtMap<u32, u32> = (0..1000).map(|i| (i, i)).collect();
+        assert_send_sync(&base);
+        let base = Arc::new(base);
+        let handles: Vec<_> = (0..4)
+            .map(|t| {
+                let base = base.clone();
+                thread::spawn(move || {
+                    // Each thread makes its own version from the shared one
+                    let mine = base.insert(t, 100 + t).remove(&(500 + t));
+                    assert_eq!(mine.get(&t), Some(&(100 + t)));
+                    assert_eq!(base.get(&t), Some(&t));
+                    mine.len()
+                })
+            })
+            .collect();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), 999);
+        }
+        assert_eq!(base.len(), 1000);
+    }
+
+    #[test]
+    fn against_hash_map() {
+        // Random operations on random versions, each checked against its own model
+        let mut versions = vec![(HamtMap::<u32, u32>::new(), HashMap::new())];
+        let mut x: u32 = 1;
+        let n = if cfg!(miri) { 500 } else { 10_000 };
+        for _ in 0..n {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let (map, mut model) = versions[(x >> 4) as usize % versions.len()].clone();
+            let key = (x >> 8) % 500;
+            let next = if x >> 30 == 0 {
+                model.remove(&key);
+                map.remove(&key)
+            } else {
+                model.insert(key, x);
+                map.insert(key, x)
+            };
+            assert_eq!(next.len(), model.len());
+            assert_eq!(next.get(&key), model.get(&key));
+            versions.push((next, model));
+        }
+        for (map, model) in versions.iter().step_by(100) {
+            let mut entries: Vec<_> = map.iter().map(|(&k, &v)| (k, v)).collect();
+            entries.sort_unstable();
+            let mut expected: Vec<_> = model.iter().map(|(&k, &v)| (k, v)).collect();
+            expected.sort_unstable();
+            assert_eq!(entries, expected);
+        }
+    }
+}
@@ -0,0 +1,351 @@
+//! # Hazard pointers
+//!
+//! Another answer to "when can a lock-free structure free an unlinked node?", and a useful
+//! contrast with [`crate::epoch`]. Instead of announcing *when* it started reading, a thread
+//! announces exactly *which* node it's about to read, by publishing the pointer in a shared
+//! slot: a hazard pointer.
+//!
+//! - To read through a shared `AtomicPtr`, load it, publish it in a hazard slot, then load it
+//!   again. If it hasn't changed, the node was still reachable after it was published, so
+//!   nobody will free it until the slot is cleared.
+//! - Unlinked nodes are **retired** onto a per-thread list. Once that list gets long enough
+//!   the thread **scans**: it reads every hazard slot and frees each retired node nobody has
+//!   published. The rest wait for the next scan.
+//!
+//! Compared with epochs, a stalled reader only pins the handful of nodes it's protecting
+//! rather than everything retired since it started, so memory stays bounded. The price is a
+//! store and a full fence on every pointer we protect, and a bit more care at each use site.
+//!
+//! Slots are registered in a lock-free list that is never shrunk; dropping a `HazardPointer`
+//! clears its slot and leaves it free for the next one. When a thread exits, anything it
+//! couldn't free yet is left on a shared orphan list that the next scan picks up.
+
+use std::cell::RefCell;
+use std::mem;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicPtr, Ordering::SeqCst};
+use std::sync::Mutex;
+
+/// How many retired nodes a thread collects before scanning.
+const SCAN_THRESHOLD: usize = 64;
+
+static DOMAIN: Domain = Domain {
+    slots: AtomicPtr::new(ptr::null_mut()),
+    orphans: Mutex::new(Vec::new()),
+};
+
+thread_local! {
+    static RETIRED: Local = const {
+        Local {
+            retired: RefCell::new(Vec::new()),
+        }
+    };
+}
+
+struct Domain {
+    slots: AtomicPtr<Slot>,
+    orphans: Mutex<Vec<Retired>>,
+}
+
+struct Slot {
+    hazard: AtomicPtr<()>,
+    in_use: AtomicBool,
+    next: *mut Slot,
+}
+
+struct Local {
+    retired: RefCell<Vec<Retired>>,
+}
+
+/// A type-erased pointer waiting to be freed.
+struct Retired {
+    ptr: *mut (),
+    drop: unsafe fn(*mut ()),
+}
+
+// Whoever retired the pointer promised it may be freed from any thread.
+unsafe impl Send for Retired {}
+
+unsafe fn drop_boxed<T>(ptr: *mut ()) {
+    drop(Box::from_raw(ptr as *mut T));
+}
+
+impl Domain {
+    /// Claims a free slot, or adds a new one to the list.
+    fn acquire(&self) -> &'static Slot {
+        let mut slot = self.slots.load(SeqCst);
+        while let Some(s) = unsafe { slot.as_ref() } {
+            if s.in_use
+                .compare_exchange(false, true, SeqCst, SeqCst)
+                .is_ok()
+            {
+                return s;
+            }
+            slot = s.next;
+        }
+
+        let new = Box::into_raw(Box::new(Slot {
+            hazard: AtomicPtr::new(ptr::null_mut()),
+            in_use: AtomicBool::new(true),
+            next: ptr::null_mut(),
+        }));
+        let mut head = self.slots.load(SeqCst);
+        loop {
+            unsafe { (*new).next = head };
+            match self.slots.compare_exchange(head, new, SeqCst, SeqCst) {
+                Ok(_) => break,
+                Err(current) => head = current,
+            }
+        }
+        // Slots are never freed, so this lives for the rest of the program
+        unsafe { &*new }
+    }
+
+    /// Returns every pointer currently published in a slot, sorted.
+    fn hazards(&self) -> Vec<*mut ()> {
+        let mut hazards = Vec::new();
+        let mut slot = self.slots.load(SeqCst);
+        while let Some(s) = unsafe { slot.as_ref() } {
+            let hazard = s.hazard.load(SeqCst);
+            if !hazard.is_null() {
+                hazards.push(hazard);
+            }
+            slot = s.next;
+        }
+        hazards.sort_unstable();
+        hazards
+    }
+
+    /// Frees everything in `retired` that isn't protected, and returns the rest. Orphans
+    /// left behind by exited threads are checked too; any still protected go back.
+    fn scan(&self, retired: Vec<Retired>) -> Vec<Retired> {
+        let orphans = mem::take(&mut *self.orphans.lock().unwrap());
+        let hazards = self.hazards();
+        let is_protected = |r: &Retired| hazards.binary_search(&r.ptr).is_ok();
+
+        let (kept, free): (Vec<_>, Vec<_>) = retired.into_iter().partition(is_protected);
+        let (orphans, free_orphans): (Vec<_>, Vec<_>) = orphans.into_iter().partition(is_protected);
+        self.orphans.lock().unwrap().extend(orphans);
+
+        for r in free.into_iter().chain(free_orphans) {
+            unsafe { (r.drop)(r.ptr) };
+        }
+        kept
+    }
+}
+
+impl Local {
+    fn retire(&self, retired: Retired) {
+        let full = {
+            let mut list = self.retired.borrow_mut();
+            list.push(retired);
+            list.len() >= SCAN_THRESHOLD
+        };
+        if full {
+            self.reclaim();
+        }
+    }
+
+    fn reclaim(&self) {
+        let list = mem::take(&mut *self.retired.borrow_mut());
+        let kept = DOMAIN.scan(list);
+        self.retired.borrow_mut().extend(kept);
+    }
+}
+
+impl Drop for Local {
+    fn drop(&mut self) {
+        // Free what we can, and leave the rest for whoever scans next
+        let kept = DOMAIN.scan(mem::take(self.retired.get_mut()));
+        DOMAIN.orphans.lock().unwrap().extend(kept);
+    }
+}
+
+/// One published slot. Whatever pointer it protects won't be freed until it's reset,
+/// re-pointed, or dropped.
+pub struct HazardPointer {
+    slot: &'static Slot,
+}
+
+impl HazardPointer {
+    /// Creates a HazardPointer, protecting nothing.
+    pub fn new() -> Self {
+        HazardPointer {
+            slot: DOMAIN.acquire(),
+        }
+    }
+
+    /// Loads `src` and protects whatever it points to. The returned pointer stays valid until
+    /// this hazard pointer is reset or reused, as long as the structure only frees nodes it
+    /// has unlinked from `src` through [`retire`].
+    pub fn protect<T>(&mut self, src: &AtomicPtr<T>) -> *mut T {
+        let mut ptr = src.load(SeqCst);
+        loop {
+            self.slot.hazard.store(ptr as *mut (), SeqCst);
+            // Still there after we published it? Then no scan can have missed us.
+            let again = src.load(SeqCst);
+            if again == ptr {
+                return ptr;
+            }
+            ptr = again;
+        }
+    }
+
+    /// Publishes `ptr` without validating it. The caller has to check afterwards that the
+    /// node was still reachable, usually by re-reading whatever pointed to it.
+    pub fn set<T>(&mut self, ptr: *mut T) {
+        self.slot.hazard.store(ptr as *mut (), SeqCst);
+    }
+
+    /// Stops protecting anything.
+    pub fn reset(&mut self) {
+        self.slot.hazard.store(ptr::null_mut(), SeqCst);
+    }
+}
+
+impl Default for HazardPointer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for HazardPointer {
+    fn drop(&mut self) {
+        self.reset();
+        self.slot.in_use.store(false, SeqCst);
+    }
+}
+
+/// Frees a `Box`-allocated pointer once no hazard pointer protects it.
+///
+/// # Safety
+///
+/// `ptr` must have come from `Box::into_raw`, must already be unlinked so that no new hazard
+/// pointer can validate it, and must not be freed by anyone else. Dropping the `T` may happen
+/// on another thread, arbitrarily later.
+pub unsafe fn retire<T>(ptr: *mut T) {
+    let ptr = ptr as *mut ();
+    let retired = || Retired {
+        ptr,
+        drop: drop_boxed::<T>,
+    };
+    if RETIRED.try_with(|local| local.retire(retired())).is_err() {
+        // Retiring during thread teardown: hand it straight to the orphans
+        DOMAIN.orphans.lock().unwrap().push(retired());
+    }
+}
+
+/// Scans right away, freeing every pointer this thread retired that isn't protected.
+pub fn reclaim() {
+    RETIRED.with(|local| local.reclaim());
+}
+
+#[cfg(test)]
+mod test {
+    use super::{reclaim, retire, HazardPointer};
+    use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    struct Counted(Arc<AtomicUsize>);
+    impl Drop for Counted {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn protected_survives_scan() {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let shared = AtomicPtr::new(Box::into_raw(Box::new(Counted(drops.clone()))));
+
+        let mut hazard = HazardPointer::new();
+        let node = hazard.protect(&shared);
+
+        // Unlink and retire it while we still hold it
+        shared.store(std::ptr::null_mut(), Ordering::SeqCst);
+        unsafe { retire(node) };
+        reclaim();
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+        assert_eq!(unsafe { Arc::strong_count(&(*node).0) }, 2);
+
+        hazard.reset();
+        reclaim();
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn unprotected_freed() {
+        let drops = Arc::new(AtomicUsize::new(0));
+        for _ in 0..10 {
+            unsafe { retire(Box::into_raw(Box::new(Counted(drops.clone())))) };
+        }
+        reclaim();
+        assert_eq!(drops.load(Ordering::SeqCst), 10);
+    }
+
+    #[test]
+    fn protect_follows_changes() {
+        let a = Box::into_raw(Box::new(1));
+        let b = Box::into_raw(Box::new(2));
+        let shared = AtomicPtr::new(a);
+
+        let mut hazard = HazardPointer::new();
+        assert_eq!(hazard.protect(&shared), a);
+        shared.store(b, Ordering::SeqCst);
+        assert_eq!(hazard.protect(&shared), b);
+        drop(hazard);
+
+        unsafe {
+            retire(a);
+            retire(b);
+        }
+        reclaim();
+    }
+
+    #[test]
+    fn threads_swap_and_retire() {
+        let rounds = if cfg!(miri) { 20 } else { 5_000 };
+        let drops = Arc::new(AtomicUsize::new(0));
+        let first = Box::into_raw(Box::new(Counted(drops.clone())));
+        let shared = Arc::new(AtomicPtr::new(first));
+
+        // Writers keep replacing the node; readers keep protecting and reading it
+        let handles: Vec<_> = (0..4)
+            .map(|t| {
+                let shared = shared.clone();
+                let drops = drops.clone();
+                thread::spawn(move || {
+                    let mut hazard = HazardPointer::new();
+                    for _ in 0..rounds {
+                        if t % 2 == 0 {
+                            let new = Box::into_raw(Box::new(Counted(drops.clone())));
+                            let old = shared.swap(new, Ordering::SeqCst);
+                            unsafe { retire(old) };
+                        } else {
+                            let node = hazard.protect(&shared);
+                            assert!(Arc::strong_count(unsafe { &(*node).0 }) >= 1);
+                            hazard.reset();
+                        }
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        // Every node got retired, and with all readers gone all of it can be freed. Another
+        // test's scan may be holding the orphans for a moment, so give it a few tries.
+        let last = shared.load(Ordering::SeqCst);
+        unsafe { retire(last) };
+        for _ in 0..1_000 {
+            reclaim();
+            if drops.load(Ordering::SeqCst) == 2 * rounds + 1 {
+                break;
+            }
+            thread::yield_now();
+        }
+        assert_eq!(drops.load(Ordering::SeqCst), 2 * rounds + 1);
+    }
+}
@@ -0,0 +1,275 @@
+//! # Binary heap
+//!
+//! A complete binary tree stored in a `Vec`, level by level, with every node at least as
+//! large as its children. No pointers are needed: the children of slot `i` are at
+//! `2i + 1` and `2i + 2`, and its parent is at `(i - 1) / 2`.
+//!
+//! ```text
+//!             9                    slot:  0  1  2  3  4  5
+//!           /   \                  data:  9  7  8  2  5  6
+//!          7     8
+//!         / \   /
+//!        2   5 6
+//! ```
+//!
+//! A push goes on the end and *sifts up*, swapping with its parent while it's larger. A pop
+//! takes the root, moves the last element into its place and *sifts down*, swapping with
+//! its larger child while one is larger than it. Both touch one path, so both are O(log n).
+//!
+//! Building a heap from a `Vec` by pushing each element would be O(n log n). Sifting down
+//! every internal node from the bottom up is O(n) instead (Floyd, 1964): half the nodes
+//! are leaves and need no work, a quarter move at most one level, and so on.
+//!
+//! This is a max-heap. For a min-heap, wrap the elements in [`std::cmp::Reverse`].
+
+use std::fmt;
+use std::iter::FromIterator;
+
+#[derive(Clone)]
+pub struct BinaryHeap<T> {
+    data: Vec<T>,
+}
+
+impl<T: Ord> BinaryHeap<T> {
+    /// Creates an empty BinaryHeap.
+    pub fn new() -> Self {
+        BinaryHeap { data: Vec::new() }
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        BinaryHeap {
+            data: Vec::with_capacity(capacity),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.data.clear();
+    }
+
+    /// Returns the largest element.
+    pub fn peek(&self) -> Option<&T> {
+        self.data.first()
+    }
+
+    pub fn push(&mut self, elem: T) {
+        self.data.push(elem);
+        self.sift_up(self.data.len() - 1);
+    }
+
+    /// Removes and returns the largest element.
+    pub fn pop(&mut self) -> Option<T> {
+        let last = self.data.pop()?;
+        if self.data.is_empty() {
+            return Some(last);
+        }
+        let top = std::mem::replace(&mut self.data[0], last);
+        self.sift_down(0, self.data.len());
+        Some(top)
+    }
+
+    /// Moves the element at `i` up until its parent is no smaller.
+    fn sift_up(&mut self, mut i: usize) {
+        while i > 0 {
+            let parent = (i - 1) / 2;
+            if self.data[i] <= self.data[parent] {
+                break;
+            }
+            self.data.swap(i, parent);
+            i = parent;
+        }
+    }
+
+    /// Moves the element at `i` down until no child is larger, looking only at the first
+    /// `end` slots.
+    fn sift_down(&mut self, mut i: usize, end: usize) {
+        loop {
+            let left = 2 * i + 1;
+            if left >= end {
+                break;
+            }
+            let right = left + 1;
+            let larger = if right < end && self.data[right] > self.data[left] {
+                right
+            } else {
+                left
+            };
+            if self.data[larger] <= self.data[i] {
+                break;
+            }
+            self.data.swap(i, larger);
+            i = larger;
+        }
+    }
+
+    /// Returns the elements in heap order, which is not sorted.
+    pub fn into_vec(self) -> Vec<T> {
+        self.data
+    }
+
+    /// Returns the elements in ascending order, sorting in place (heapsort).
+    pub fn into_sorted_vec(mut self) -> Vec<T> {
+        // Swap the largest to the end of the part still a heap, then shrink it by one
+        for end in (1..self.data.len()).rev() {
+            self.data.swap(0, end);
+            self.sift_down(0, end);
+        }
+        self.data
+    }
+
+    /// Iterates over the elements in heap order, which is not sorted.
+    pub fn iter(&self) -> std::slice::Iter<'_, T> {
+        self.data.iter()
+    }
+}
+
+impl<T: Ord> Default for BinaryHeap<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Ord> From<Vec<T>> for BinaryHeap<T> {
+    /// Heapifies the vector in place, in O(n).
+    fn from(data: Vec<T>) -> Self {
+        let mut heap = BinaryHeap { data };
+        let len = heap.data.len();
+        // Leaves are already heaps; fix up each internal node, deepest first
+        for i in (0..len / 2).rev() {
+            heap.sift_down(i, len);
+        }
+        heap
+    }
+}
+
+impl<T: Ord> FromIterator<T> for BinaryHeap<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        BinaryHeap::from(iter.into_iter().collect::<Vec<_>>())
+    }
+}
+
+impl<T: Ord> Extend<T> for BinaryHeap<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for elem in iter {
+            self.push(elem);
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for BinaryHeap<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.data.iter()).finish()
+    }
+}
+
+impl<'a, T: Ord> IntoIterator for &'a BinaryHeap<T> {
+    type Item = &'a T;
+    type IntoIter = std::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::BinaryHeap;
+    use std::cmp::Reverse;
+
+    fn check_heap<T: Ord>(heap: &BinaryHeap<T>) {
+        for i in 1..heap.data.len() {
+            assert!(heap.data[i] <= heap.data[(i - 1) / 2]);
+        }
+    }
+
+    #[test]
+    fn basics() {
+        let mut heap = BinaryHeap::new();
+
+        // Check empty heap behaves right
+        assert_eq!(heap.pop(), None);
+        assert_eq!(heap.peek(), None);
+
+        // Populate heap
+        for x in [2, 7, 9, 5, 8, 6] {
+            heap.push(x);
+        }
+        check_heap(&heap);
+        assert_eq!(heap.len(), 6);
+        assert_eq!(heap.peek(), Some(&9));
+        assert_eq!(format!("{:?}", heap), "[9, 8, 7, 2, 5, 6]");
+
+        // Check normal removal
+        assert_eq!(heap.pop(), Some(9));
+        assert_eq!(heap.pop(), Some(8));
+
+        // Push some more just to make sure nothing's corrupted
+        heap.push(1);
+        heap.push(10);
+        check_heap(&heap);
+        assert_eq!(heap.pop(), Some(10));
+
+        // Check exhaustion
+        assert_eq!(heap.pop(), Some(7));
+        assert_eq!(heap.pop(), Some(6));
+        assert_eq!(heap.pop(), Some(5));
+        assert_eq!(heap.pop(), Some(2));
+        assert_eq!(heap.pop(), Some(1));
+        assert_eq!(heap.pop(), None);
+        assert!(heap.is_empty());
+    }
+
+    #[test]
+    fn min_heap() {
+        let mut heap: BinaryHeap<_> = [5, 1, 4, 2, 3].iter().map(|&x| Reverse(x)).collect();
+        assert_eq!(heap.peek(), Some(&Reverse(1)));
+        let order: Vec<_> = std::iter::from_fn(|| heap.pop().map(|Reverse(x)| x)).collect();
+        assert_eq!(order, vec![1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn heapify_and_sort() {
+        let mut x: u32 = 7;
+        for len in 0..100 {
+            let data: Vec<u32> = (0..len)
+                .map(|_| {
+                    x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                    (x >> 16) % 50
+                })
+                .collect();
+            let heap = BinaryHeap::from(data.clone());
+            check_heap(&heap);
+            assert_eq!(heap.peek(), data.iter().max());
+
+            let mut sorted = data.clone();
+            sorted.sort_unstable();
+            assert_eq!(heap.into_sorted_vec(), sorted);
+        }
+    }
+
+    #[test]
+    fn against_std() {
+        let mut heap = BinaryHeap::new();
+        let mut model = std::collections::BinaryHeap::new();
+        let mut x: u32 = 1;
+        for _ in 0..10_000 {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            if x >> 30 == 0 {
+                assert_eq!(heap.pop(), model.pop());
+            } else {
+                heap.push(x % 1000);
+                model.push(x % 1000);
+            }
+            assert_eq!(heap.peek(), model.peek());
+        }
+        assert_eq!(heap.len(), model.len());
+        assert_eq!(heap.into_sorted_vec(), model.into_sorted_vec());
+    }
+}
@@ -0,0 +1,384 @@
+//! # Huffman coding
+//!
+//! The shortest prefix code for bytes with known frequencies (Huffman, 1952). Every byte
+//! that occurs gets a string of bits, common bytes shorter ones, and no byte's code starts
+//! another's, so a stream of codes reads back without anything between them.
+//!
+//! The code is a binary tree with a byte at each leaf, and the path to a leaf, 0 for left
+//! and 1 for right, is its code. Building it starts with a leaf per byte in a min-heap by
+//! frequency, a [`crate::heap::BinaryHeap`] of `Reverse`d weights, and keeps taking out
+//! the two lightest trees and putting back a branch over both, weighing as much as the two
+//! together, until there's only one:
+//!
+//! ```text
+//!   a:45 b:13 c:12 d:16 e:9 f:5          a 0      c 100    b 101
+//!                                        f 1100   e 1101   d 111
+//!           100
+//!          /    \                        45·1 + (12 + 13 + 16)·3
+//!        a:45    55                        + (5 + 9)·4 = 224 bits
+//!               /    \
+//!             25      30
+//!            /  \    /  \
+//!          c:12 b:13 14  d:16
+//!                   /  \
+//!                 f:5  e:9
+//! ```
+//!
+//! The two lightest always end up deepest, as siblings, which is why it's optimal. Ties
+//! between equal weights go to whichever tree was made first, so the same frequencies
+//! always make the same code. A single byte on its own still gets a code of one bit.
+//!
+//! Decoding walks down the tree from the root, a bit at a time, and starts over at the
+//! root on reaching a leaf. The bits go in a [`BitStream`].
+
+use crate::heap::BinaryHeap;
+use std::cmp::Reverse;
+use std::fmt;
+
+const BITS: usize = 64;
+
+/// A growable string of bits, packed into words, the first bit lowest.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct BitStream {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl BitStream {
+    /// Creates an empty BitStream.
+    pub fn new() -> Self {
+        BitStream {
+            words: Vec::new(),
+            len: 0,
+        }
+    }
+
+    /// Returns the stream of the first `len` bits of `words`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `words` is too short for `len` bits.
+    pub fn from_words(mut words: Vec<u64>, len: usize) -> Self {
+        assert!(len <= words.len() * BITS, "too few words for {} bits", len);
+        words.truncate(len.div_ceil(BITS));
+        if !len.is_multiple_of(BITS) {
+            *words.last_mut().unwrap() &= (1 << (len % BITS)) - 1;
+        }
+        BitStream { words, len }
+    }
+
+    /// Returns the packed words, and the number of bits in them.
+    pub fn into_words(self) -> (Vec<u64>, usize) {
+        (self.words, self.len)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn push(&mut self, bit: bool) {
+        if self.len.is_multiple_of(BITS) {
+            self.words.push(0);
+        }
+        *self.words.last_mut().unwrap() |= (bit as u64) << (self.len % BITS);
+        self.len += 1;
+    }
+
+    /// Returns bit `i`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `i` is out of bounds.
+    pub fn get(&self, i: usize) -> bool {
+        assert!(
+            i < self.len,
+            "index {} out of bounds for length {}",
+            i,
+            self.len
+        );
+        self.words[i / BITS] >> (i % BITS) & 1 == 1
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = bool> + '_ {
+        (0..self.len).map(move |i| self.get(i))
+    }
+}
+
+impl fmt::Debug for BitStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for bit in self.iter() {
+            f.write_str(if bit { "1" } else { "0" })?;
+        }
+        Ok(())
+    }
+}
+
+enum Node {
+    Leaf(u8),
+    Branch(usize, usize),
+}
+
+/// A Huffman code for bytes: the tree, and each byte's path through it.
+pub struct HuffmanCode {
+    // Children link by index; the root is last
+    nodes: Vec<Node>,
+    // Each byte's code, left as 0 and right as 1, empty for bytes that don't occur
+    codes: Vec<Vec<bool>>,
+}
+
+impl HuffmanCode {
+    /// Builds the code for bytes occurring `frequencies[b]` times each. Bytes with no
+    /// occurrences get no code.
+    pub fn from_frequencies(frequencies: &[u64; 256]) -> Self {
+        let mut nodes = Vec::new();
+        let mut leaves = Vec::new();
+        for (byte, &weight) in frequencies.iter().enumerate() {
+            if weight > 0 {
+                leaves.push(Reverse((weight, nodes.len())));
+                nodes.push(Node::Leaf(byte as u8));
+            }
+        }
+        // The index of each node doubles as the tie-break: older trees first
+        let mut heap = BinaryHeap::from(leaves);
+        while heap.len() > 1 {
+            let Reverse((a, left)) = heap.pop().unwrap();
+            let Reverse((b, right)) = heap.pop().unwrap();
+            heap.push(Reverse((a + b, nodes.len())));
+            nodes.push(Node::Branch(left, right));
+        }
+
+        let mut codes = vec![Vec::new(); 256];
+        let mut stack = Vec::new();
+        if !nodes.is_empty() {
+            stack.push((nodes.len() - 1, Vec::new()));
+        }
+        while let Some((at, path)) = stack.pop() {
+            match nodes[at] {
+                // A lone leaf is the root, and gets a bit all the same
+                Node::Leaf(byte) if path.is_empty() => codes[byte as usize] = vec![false],
+                Node::Leaf(byte) => codes[byte as usize] = path,
+                Node::Branch(left, right) => {
+                    let mut right_path = path.clone();
+                    right_path.push(true);
+                    stack.push((right, right_path));
+                    let mut left_path = path;
+                    left_path.push(false);
+                    stack.push((left, left_path));
+                }
+            }
+        }
+        HuffmanCode { nodes, codes }
+    }
+
+    /// Builds the code for the frequencies of the bytes in `data`.
+    pub fn from_data(data: &[u8]) -> Self {
+        let mut frequencies = [0; 256];
+        for &byte in data {
+            frequencies[byte as usize] += 1;
+        }
+        Self::from_frequencies(&frequencies)
+    }
+
+    /// Returns the code for `byte`, or `None` if it had no occurrences.
+    pub fn code(&self, byte: u8) -> Option<&[bool]> {
+        let code = &self.codes[byte as usize];
+        (!code.is_empty()).then_some(&code[..])
+    }
+
+    /// Encodes `data` as the codes of its bytes, one after another.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a byte of `data` has no code.
+    pub fn encode(&self, data: &[u8]) -> BitStream {
+        let mut bits = BitStream::new();
+        for &byte in data {
+            let code = self
+                .code(byte)
+                .unwrap_or_else(|| panic!("byte {} has no code", byte));
+            for &bit in code {
+                bits.push(bit);
+            }
+        }
+        bits
+    }
+
+    /// Decodes a stream of codes back into bytes. Returns `None` if the bits end partway
+    /// through a code, or are no code at all.
+    pub fn decode(&self, bits: &BitStream) -> Option<Vec<u8>> {
+        let root = match self.nodes.len() {
+            0 => return bits.is_empty().then(Vec::new),
+            n => n - 1,
+        };
+        if let Node::Leaf(byte) = self.nodes[root] {
+            // A lone byte's code is a single 0
+            return bits.iter().all(|bit| !bit).then(|| vec![byte; bits.len()]);
+        }
+        let mut out = Vec::new();
+        let mut at = root;
+        for bit in bits.iter() {
+            at = match self.nodes[at] {
+                Node::Branch(left, right) => {
+                    if bit {
+                        right
+                    } else {
+                        left
+                    }
+                }
+                Node::Leaf(_) => unreachable!(),
+            };
+            if let Node::Leaf(byte) = self.nodes[at] {
+                out.push(byte);
+                at = root;
+            }
+        }
+        // Anywhere but back at the root is partway through a code
+        (at == root).then_some(out)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{BitStream, HuffmanCode};
+
+    fn bits(s: &str) -> Vec<bool> {
+        s.chars().map(|c| c == '1').collect()
+    }
+
+    /// Checks no code is a prefix of another, and that the code meets Kraft's inequality
+    /// with equality, as a full tree does.
+    fn check(code: &HuffmanCode) {
+        let codes: Vec<_> = (0..=255).filter_map(|b| code.code(b)).collect();
+        for (i, a) in codes.iter().enumerate() {
+            for (j, b) in codes.iter().enumerate() {
+                assert!(i == j || !b.starts_with(a));
+            }
+        }
+        if codes.len() > 1 {
+            let kraft: f64 = codes.iter().map(|c| 0.5f64.powi(c.len() as i32)).sum();
+            assert!((kraft - 1.0).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn textbook() {
+        // The frequencies in the module docs
+        let mut frequencies = [0; 256];
+        for &(byte, weight) in &[
+            (b'a', 45),
+            (b'b', 13),
+            (b'c', 12),
+            (b'd', 16),
+            (b'e', 9),
+            (b'f', 5),
+        ] {
+            frequencies[byte as usize] = weight;
+        }
+        let code = HuffmanCode::from_frequencies(&frequencies);
+        check(&code);
+        for &(byte, expected) in &[
+            (b'a', "0"),
+            (b'c', "100"),
+            (b'b', "101"),
+            (b'f', "1100"),
+            (b'e', "1101"),
+            (b'd', "111"),
+        ] {
+            assert_eq!(code.code(byte), Some(&bits(expected)[..]));
+        }
+        assert_eq!(code.code(b'z'), None);
+        let total: usize = (0..=255u8)
+            .filter_map(|b| {
+                code.code(b)
+                    .map(|c| c.len() * frequencies[b as usize] as usize)
+            })
+            .sum();
+        assert_eq!(total, 224);
+
+        let encoded = code.encode(b"face");
+        assert_eq!(format!("{:?}", encoded), "110001001101");
+        assert_eq!(code.decode(&encoded).unwrap(), b"face");
+    }
+
+    #[test]
+    fn edge_cases() {
+        // Nothing at all
+        let code = HuffmanCode::from_data(b"");
+        assert_eq!(code.decode(&BitStream::new()), Some(vec![]));
+        assert_eq!(code.encode(b""), BitStream::new());
+        assert_eq!(code.decode(&BitStream::from_words(vec![1], 1)), None);
+
+        // One byte, over and over
+        let code = HuffmanCode::from_data(b"zzzz");
+        assert_eq!(code.code(b'z'), Some(&[false][..]));
+        let encoded = code.encode(b"zzz");
+        assert_eq!(encoded.len(), 3);
+        assert_eq!(code.decode(&encoded).unwrap(), b"zzz");
+        assert_eq!(code.decode(&BitStream::from_words(vec![0b010], 3)), None);
+
+        // Cut off partway through a code
+        let code = HuffmanCode::from_data(b"aaaabbc");
+        let encoded = code.encode(b"cab");
+        let (words, len) = encoded.into_words();
+        assert_eq!(
+            code.decode(&BitStream::from_words(words.clone(), len))
+                .unwrap(),
+            b"cab"
+        );
+        assert_eq!(code.decode(&BitStream::from_words(words, len - 1)), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "byte 113 has no code")]
+    fn encode_unknown() {
+        HuffmanCode::from_data(b"abc").encode(b"q");
+    }
+
+    #[test]
+    fn bit_stream() {
+        let mut stream = BitStream::new();
+        assert!(stream.is_empty());
+        for i in 0..200 {
+            stream.push(i % 3 == 0);
+        }
+        assert_eq!(stream.len(), 200);
+        assert!(stream.get(129) && !stream.get(130));
+        let (words, len) = stream.clone().into_words();
+        assert_eq!(words.len(), 4);
+        assert_eq!(BitStream::from_words(words, len), stream);
+
+        // Bits past the end are dropped
+        assert_eq!(
+            BitStream::from_words(vec![u64::MAX], 3),
+            BitStream::from_words(vec![7], 3)
+        );
+    }
+
+    #[test]
+    fn round_trips() {
+        // Random texts with skewed byte frequencies, from few distinct bytes to all of them
+        let mut x: u32 = 1;
+        let n = if cfg!(miri) { 500 } else { 20_000 };
+        for &spread in &[2u32, 5, 40, 256] {
+            let data: Vec<u8> = (0..n)
+                .map(|_| {
+                    x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                    // Squaring skews towards small bytes
+                    let r = (x >> 8) % (spread * spread);
+                    (spread - 1 - ((r as f64).sqrt() as u32).min(spread - 1)) as u8
+                })
+                .collect();
+            let code = HuffmanCode::from_data(&data);
+            check(&code);
+            let encoded = code.encode(&data);
+            assert_eq!(code.decode(&encoded).unwrap(), data);
+            if spread < 256 {
+                assert!(encoded.len() < 8 * data.len());
+            }
+        }
+    }
+}
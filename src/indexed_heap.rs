@@ -0,0 +1,349 @@
+//! # Indexed priority queue
+//!
+//! A binary heap of keys ordered by priority, plus a hash map from each key to its slot
+//! in the heap. The map is what a plain heap lacks: to change a key's priority it has to
+//! find the key first, and scanning for it is O(n). With the map the key is found in O(1)
+//! and then sifted up or down into place, so [`IndexedHeap::change_priority`] and
+//! [`IndexedHeap::remove`] both run in O(log n).
+//!
+//! ```text
+//!   heap:   slot 0      1      2      3            positions:  a → 2
+//!          (c, 1) (d, 4) (a, 5) (b, 7)                         b → 3
+//!                                                              c → 0
+//!   change_priority(a, 0):                                     d → 1
+//!     positions[a] = 2, set (a, 0), sift up
+//!          (a, 0) (d, 4) (c, 1) (b, 7)                         a → 0, c → 2
+//! ```
+//!
+//! Every swap in the heap updates the two keys' entries in the map, so they never drift.
+//!
+//! Unlike [`crate::heap::BinaryHeap`], this queue gives the *smallest* priority first.
+//! That's what the algorithms needing decrease-key want: Dijkstra's shortest paths and
+//! Prim's spanning tree both keep pulling out the closest vertex, and lower a vertex's
+//! distance whenever they find a shorter way there.
+
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::iter::FromIterator;
+
+pub struct IndexedHeap<K, P> {
+    heap: Vec<(K, P)>,
+    // Slot in `heap` of each key
+    positions: HashMap<K, usize>,
+}
+
+impl<K: Hash + Eq + Clone, P: Ord> IndexedHeap<K, P> {
+    /// Creates an empty IndexedHeap.
+    pub fn new() -> Self {
+        IndexedHeap {
+            heap: Vec::new(),
+            positions: HashMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.heap.clear();
+        self.positions.clear();
+    }
+
+    /// Inserts a key with a priority. If the key was already there, its priority is
+    /// changed instead and the old one returned.
+    pub fn push(&mut self, key: K, priority: P) -> Option<P> {
+        if let Some(&i) = self.positions.get(&key) {
+            return Some(self.set_priority(i, priority));
+        }
+        self.positions.insert(key.clone(), self.heap.len());
+        self.heap.push((key, priority));
+        self.sift_up(self.heap.len() - 1);
+        None
+    }
+
+    /// Returns the key with the smallest priority, and that priority.
+    pub fn peek(&self) -> Option<(&K, &P)> {
+        self.heap.first().map(|(key, priority)| (key, priority))
+    }
+
+    /// Removes and returns the key with the smallest priority, and that priority.
+    pub fn pop(&mut self) -> Option<(K, P)> {
+        if self.heap.is_empty() {
+            return None;
+        }
+        Some(self.remove_at(0))
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&P>
+    where
+        Q: Hash + Eq + ?Sized,
+        K: Borrow<Q>,
+    {
+        let &i = self.positions.get(key)?;
+        Some(&self.heap[i].1)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        Q: Hash + Eq + ?Sized,
+        K: Borrow<Q>,
+    {
+        self.positions.contains_key(key)
+    }
+
+    /// Gives a key a new priority, higher or lower, and returns the old one. Returns
+    /// `None`, and changes nothing, if the key isn't there.
+    pub fn change_priority<Q>(&mut self, key: &Q, priority: P) -> Option<P>
+    where
+        Q: Hash + Eq + ?Sized,
+        K: Borrow<Q>,
+    {
+        let &i = self.positions.get(key)?;
+        Some(self.set_priority(i, priority))
+    }
+
+    /// Lowers a key's priority if `priority` is smaller than its current one, and
+    /// returns whether it did. This is the relaxation step of Dijkstra's algorithm.
+    pub fn decrease_priority<Q>(&mut self, key: &Q, priority: P) -> bool
+    where
+        Q: Hash + Eq + ?Sized,
+        K: Borrow<Q>,
+    {
+        match self.positions.get(key) {
+            Some(&i) if priority < self.heap[i].1 => {
+                self.set_priority(i, priority);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Removes a key from anywhere in the queue, returning its priority.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<P>
+    where
+        Q: Hash + Eq + ?Sized,
+        K: Borrow<Q>,
+    {
+        let &i = self.positions.get(key)?;
+        Some(self.remove_at(i).1)
+    }
+
+    /// Iterates over the keys and priorities in heap order, which is not sorted.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &P)> {
+        self.heap.iter().map(|(key, priority)| (key, priority))
+    }
+
+    fn set_priority(&mut self, i: usize, priority: P) -> P {
+        let old = std::mem::replace(&mut self.heap[i].1, priority);
+        self.sift(i);
+        old
+    }
+
+    fn remove_at(&mut self, i: usize) -> (K, P) {
+        let (key, priority) = self.heap.swap_remove(i);
+        self.positions.remove(&key);
+        if i < self.heap.len() {
+            // The last element moved into the hole, and may belong above or below it
+            self.positions.insert(self.heap[i].0.clone(), i);
+            self.sift(i);
+        }
+        (key, priority)
+    }
+
+    /// Swaps two slots, keeping the positions of their keys up to date.
+    fn swap(&mut self, i: usize, j: usize) {
+        self.heap.swap(i, j);
+        *self.positions.get_mut(&self.heap[i].0).unwrap() = i;
+        *self.positions.get_mut(&self.heap[j].0).unwrap() = j;
+    }
+
+    /// Moves the element at `i` to wherever it belongs, up or down.
+    fn sift(&mut self, i: usize) {
+        if i > 0 && self.heap[i].1 < self.heap[(i - 1) / 2].1 {
+            self.sift_up(i);
+        } else {
+            self.sift_down(i);
+        }
+    }
+
+    fn sift_up(&mut self, mut i: usize) {
+        while i > 0 {
+            let parent = (i - 1) / 2;
+            if self.heap[parent].1 <= self.heap[i].1 {
+                break;
+            }
+            self.swap(i, parent);
+            i = parent;
+        }
+    }
+
+    fn sift_down(&mut self, mut i: usize) {
+        let len = self.heap.len();
+        loop {
+            let left = 2 * i + 1;
+            if left >= len {
+                break;
+            }
+            let right = left + 1;
+            let smaller = if right < len && self.heap[right].1 < self.heap[left].1 {
+                right
+            } else {
+                left
+            };
+            if self.heap[i].1 <= self.heap[smaller].1 {
+                break;
+            }
+            self.swap(i, smaller);
+            i = smaller;
+        }
+    }
+}
+
+impl<K: Hash + Eq + Clone, P: Ord> Default for IndexedHeap<K, P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Hash + Eq + Clone, P: Ord> FromIterator<(K, P)> for IndexedHeap<K, P> {
+    fn from_iter<I: IntoIterator<Item = (K, P)>>(iter: I) -> Self {
+        let mut heap = IndexedHeap::new();
+        heap.extend(iter);
+        heap
+    }
+}
+
+impl<K: Hash + Eq + Clone, P: Ord> Extend<(K, P)> for IndexedHeap<K, P> {
+    fn extend<I: IntoIterator<Item = (K, P)>>(&mut self, iter: I) {
+        for (key, priority) in iter {
+            self.push(key, priority);
+        }
+    }
+}
+
+impl<K: fmt::Debug, P: fmt::Debug> fmt::Debug for IndexedHeap<K, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(self.heap.iter().map(|(key, priority)| (key, priority)))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::IndexedHeap;
+
+    fn check_heap(heap: &IndexedHeap<u32, u32>) {
+        assert_eq!(heap.heap.len(), heap.positions.len());
+        for (i, (key, priority)) in heap.heap.iter().enumerate() {
+            assert_eq!(heap.positions[key], i);
+            if i > 0 {
+                assert!(heap.heap[(i - 1) / 2].1 <= *priority);
+            }
+        }
+    }
+
+    #[test]
+    fn basics() {
+        let mut heap = IndexedHeap::new();
+
+        // Check empty queue behaves right
+        assert_eq!(heap.pop(), None);
+        assert_eq!(heap.change_priority("a", 1), None);
+        assert_eq!(heap.remove("a"), None);
+
+        // Populate queue
+        heap.push("a", 5);
+        heap.push("b", 7);
+        heap.push("c", 1);
+        heap.push("d", 4);
+        assert_eq!(heap.len(), 4);
+        assert_eq!(heap.peek(), Some((&"c", &1)));
+        assert_eq!(heap.get("a"), Some(&5));
+
+        // Re-pushing a key changes its priority rather than adding it twice
+        assert_eq!(heap.push("b", 3), Some(7));
+        assert_eq!(heap.len(), 4);
+
+        // Both directions
+        assert_eq!(heap.change_priority("a", 0), Some(5));
+        assert_eq!(heap.peek(), Some((&"a", &0)));
+        assert_eq!(heap.change_priority("a", 10), Some(0));
+        assert_eq!(heap.peek(), Some((&"c", &1)));
+
+        assert!(heap.decrease_priority("a", 9));
+        assert!(!heap.decrease_priority("a", 9));
+        assert!(!heap.decrease_priority("z", 0));
+
+        assert_eq!(heap.remove("d"), Some(4));
+        assert!(!heap.contains_key("d"));
+
+        // Check exhaustion
+        assert_eq!(heap.pop(), Some(("c", 1)));
+        assert_eq!(heap.pop(), Some(("b", 3)));
+        assert_eq!(heap.pop(), Some(("a", 9)));
+        assert_eq!(heap.pop(), None);
+        assert!(heap.is_empty());
+    }
+
+    #[test]
+    fn dijkstra() {
+        // Weighted edges of a small directed graph
+        let edges: &[&[(usize, u32)]] = &[
+            &[(1, 7), (2, 9), (5, 14)],
+            &[(0, 7), (2, 10), (3, 15)],
+            &[(0, 9), (1, 10), (3, 11), (5, 2)],
+            &[(1, 15), (2, 11), (4, 6)],
+            &[(3, 6), (5, 9)],
+            &[(0, 14), (2, 2), (4, 9)],
+        ];
+        let mut dist = vec![u32::MAX; edges.len()];
+        let mut queue: IndexedHeap<_, _> = (0..edges.len()).map(|v| (v, u32::MAX)).collect();
+        queue.change_priority(&0, 0);
+        while let Some((v, d)) = queue.pop() {
+            dist[v] = d;
+            for &(w, weight) in edges[v] {
+                queue.decrease_priority(&w, d + weight);
+            }
+        }
+        assert_eq!(dist, vec![0, 7, 9, 20, 20, 11]);
+    }
+
+    #[test]
+    fn against_brute_force() {
+        use std::collections::HashMap;
+        let mut heap = IndexedHeap::new();
+        let mut model: HashMap<u32, u32> = HashMap::new();
+        let mut x: u32 = 1;
+        for _ in 0..5_000 {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let key = (x >> 8) % 64;
+            let priority = (x >> 16) % 100;
+            match x >> 30 {
+                0 => assert_eq!(heap.push(key, priority), model.insert(key, priority)),
+                1 => {
+                    let expected = model.get_mut(&key).map(|p| std::mem::replace(p, priority));
+                    assert_eq!(heap.change_priority(&key, priority), expected);
+                }
+                2 => assert_eq!(heap.remove(&key), model.remove(&key)),
+                _ => {
+                    let popped = heap.pop();
+                    let least = model.values().min().copied();
+                    assert_eq!(popped.map(|(_, p)| p), least);
+                    if let Some((key, _)) = popped {
+                        model.remove(&key);
+                    }
+                }
+            }
+            check_heap(&heap);
+        }
+        assert_eq!(heap.len(), model.len());
+    }
+}
@@ -0,0 +1,549 @@
+//! # Interval tree
+//!
+//! Stores half-open ranges `start..end`, each with a value, and finds every stored range
+//! that contains a point or overlaps another range. It's an augmented [`crate::avl`] tree:
+//! ranges are ordered by `(start, end)`, and every node also remembers the largest `end`
+//! anywhere in its subtree.
+//!
+//! ```text
+//!                    [15, 20) max 30
+//!                   /               \
+//!       [5, 10) max 12             [17, 30) max 30
+//!       /          \                    \
+//!  [4, 8) max 8  [6, 12) max 12       [25, 26) max 26
+//! ```
+//!
+//! That one number is what makes the queries fast. A query can skip a whole subtree when
+//! its largest end is at or before the query's start, because then nothing in it reaches
+//! the query. Ordering by start lets it stop altogether at the first node that starts
+//! after the query ends, since everything after that starts later still. A query visits
+//! O(log n) nodes plus the ones it reports, and reports them sorted by start.
+//!
+//! The maximum is recomputed wherever the AVL code recomputes a height, including in
+//! rotations, so it never goes stale.
+
+use std::cmp::{self, Ordering};
+use std::fmt;
+use std::iter::FromIterator;
+use std::mem;
+use std::ops::Range;
+
+pub struct IntervalTree<K, V> {
+    root: Link<K, V>,
+    len: usize,
+}
+
+type Link<K, V> = Option<Box<Node<K, V>>>;
+
+struct Node<K, V> {
+    range: Range<K>,
+    value: V,
+    height: usize,
+    // The largest `end` of any range in this subtree
+    max_end: K,
+    left: Link<K, V>,
+    right: Link<K, V>,
+}
+
+/// Orders ranges by start, then end.
+fn compare<K: Ord>(a: &Range<K>, b: &Range<K>) -> Ordering {
+    (&a.start, &a.end).cmp(&(&b.start, &b.end))
+}
+
+fn height<K, V>(link: &Link<K, V>) -> usize {
+    link.as_ref().map_or(0, |node| node.height)
+}
+
+impl<K: Ord + Clone, V> Node<K, V> {
+    fn update(&mut self) {
+        self.height = 1 + cmp::max(height(&self.left), height(&self.right));
+        let mut max_end = &self.range.end;
+        for child in [&self.left, &self.right].iter().copied().flatten() {
+            max_end = cmp::max(max_end, &child.max_end);
+        }
+        self.max_end = max_end.clone();
+    }
+
+    fn balance_factor(&self) -> isize {
+        height(&self.left) as isize - height(&self.right) as isize
+    }
+}
+
+fn rotate_right<K: Ord + Clone, V>(mut node: Box<Node<K, V>>) -> Box<Node<K, V>> {
+    let mut left = node.left.take().expect("rotate_right needs a left child");
+    node.left = left.right.take();
+    node.update();
+    left.right = Some(node);
+    left.update();
+    left
+}
+
+fn rotate_left<K: Ord + Clone, V>(mut node: Box<Node<K, V>>) -> Box<Node<K, V>> {
+    let mut right = node.right.take().expect("rotate_left needs a right child");
+    node.right = right.left.take();
+    node.update();
+    right.left = Some(node);
+    right.update();
+    right
+}
+
+fn rebalance<K: Ord + Clone, V>(mut node: Box<Node<K, V>>) -> Box<Node<K, V>> {
+    node.update();
+    let balance = node.balance_factor();
+    if balance > 1 {
+        if node.left.as_ref().unwrap().balance_factor() < 0 {
+            node.left = node.left.take().map(rotate_left);
+        }
+        rotate_right(node)
+    } else if balance < -1 {
+        if node.right.as_ref().unwrap().balance_factor() > 0 {
+            node.right = node.right.take().map(rotate_right);
+        }
+        rotate_left(node)
+    } else {
+        node
+    }
+}
+
+fn insert<K: Ord + Clone, V>(link: &mut Link<K, V>, range: Range<K>, value: V) -> Option<V> {
+    let node = match link {
+        Some(node) => node,
+        None => {
+            *link = Some(Box::new(Node {
+                max_end: range.end.clone(),
+                range,
+                value,
+                height: 1,
+                left: None,
+                right: None,
+            }));
+            return None;
+        }
+    };
+    let old = match compare(&range, &node.range) {
+        Ordering::Less => insert(&mut node.left, range, value),
+        Ordering::Greater => insert(&mut node.right, range, value),
+        Ordering::Equal => return Some(mem::replace(&mut node.value, value)),
+    };
+    *link = link.take().map(rebalance);
+    old
+}
+
+fn remove<K: Ord + Clone, V>(link: &mut Link<K, V>, range: &Range<K>) -> Option<V> {
+    let node = link.as_mut()?;
+    let removed = match compare(range, &node.range) {
+        Ordering::Less => remove(&mut node.left, range),
+        Ordering::Greater => remove(&mut node.right, range),
+        Ordering::Equal => {
+            let mut node = link.take().unwrap();
+            *link = match (node.left.take(), node.right.take()) {
+                (None, right) => right,
+                (left, None) => left,
+                (Some(left), Some(right)) => {
+                    let (mut successor, rest) = take_min(right);
+                    successor.left = Some(left);
+                    successor.right = rest;
+                    Some(rebalance(successor))
+                }
+            };
+            return Some(node.value);
+        }
+    };
+    if removed.is_some() {
+        *link = link.take().map(rebalance);
+    }
+    removed
+}
+
+fn take_min<K: Ord + Clone, V>(mut node: Box<Node<K, V>>) -> (Box<Node<K, V>>, Link<K, V>) {
+    match node.left.take() {
+        None => {
+            let rest = node.right.take();
+            (node, rest)
+        }
+        Some(left) => {
+            let (min, rest) = take_min(left);
+            node.left = rest;
+            (min, Some(rebalance(node)))
+        }
+    }
+}
+
+impl<K, V> IntervalTree<K, V> {
+    /// Creates an empty IntervalTree.
+    pub fn new() -> Self {
+        IntervalTree { root: None, len: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    pub fn clear(&mut self) {
+        self.root = None;
+        self.len = 0;
+    }
+
+    /// Iterates over every range, sorted by start and then end.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        let mut iter = Iter {
+            stack: Vec::new(),
+            remaining: self.len,
+        };
+        iter.push_left_spine(self.root.as_deref());
+        iter
+    }
+}
+
+impl<K: Ord + Clone, V> IntervalTree<K, V> {
+    /// Inserts a range with its value. The range is the key, so inserting the same range
+    /// again replaces the value and returns the old one.
+    pub fn insert(&mut self, range: Range<K>, value: V) -> Option<V> {
+        let old = insert(&mut self.root, range, value);
+        if old.is_none() {
+            self.len += 1;
+        }
+        old
+    }
+
+    /// Returns the value stored for exactly this range.
+    pub fn get(&self, range: &Range<K>) -> Option<&V> {
+        let mut link = self.root.as_deref();
+        while let Some(node) = link {
+            link = match compare(range, &node.range) {
+                Ordering::Less => node.left.as_deref(),
+                Ordering::Greater => node.right.as_deref(),
+                Ordering::Equal => return Some(&node.value),
+            };
+        }
+        None
+    }
+
+    /// Removes exactly this range, returning its value if it was there.
+    pub fn remove(&mut self, range: &Range<K>) -> Option<V> {
+        let removed = remove(&mut self.root, range);
+        if removed.is_some() {
+            self.len -= 1;
+        }
+        removed
+    }
+
+    /// Iterates over the ranges that contain `point`, sorted by start.
+    pub fn query_point<'a>(&'a self, point: &'a K) -> Overlapping<'a, K, V> {
+        Overlapping::new(self, Query::Point(point))
+    }
+
+    /// Iterates over the ranges that share at least one point with `range`, sorted by
+    /// start. Ranges that merely touch, like `0..5` and `5..10`, don't overlap.
+    pub fn query_overlapping<'a>(&'a self, range: &'a Range<K>) -> Overlapping<'a, K, V> {
+        Overlapping::new(self, Query::Range(range))
+    }
+}
+
+impl<K, V> Default for IntervalTree<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord + Clone, V> FromIterator<(Range<K>, V)> for IntervalTree<K, V> {
+    fn from_iter<I: IntoIterator<Item = (Range<K>, V)>>(iter: I) -> Self {
+        let mut tree = IntervalTree::new();
+        tree.extend(iter);
+        tree
+    }
+}
+
+impl<K: Ord + Clone, V> Extend<(Range<K>, V)> for IntervalTree<K, V> {
+    fn extend<I: IntoIterator<Item = (Range<K>, V)>>(&mut self, iter: I) {
+        for (range, value) in iter {
+            self.insert(range, value);
+        }
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug> fmt::Debug for IntervalTree<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// Iterates over all ranges in order. The stack holds the nodes whose left subtree we've
+/// started but who haven't been yielded yet.
+pub struct Iter<'a, K, V> {
+    stack: Vec<&'a Node<K, V>>,
+    remaining: usize,
+}
+
+impl<'a, K, V> Iter<'a, K, V> {
+    fn push_left_spine(&mut self, mut link: Option<&'a Node<K, V>>) {
+        while let Some(node) = link {
+            self.stack.push(node);
+            link = node.left.as_deref();
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a Range<K>, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        self.push_left_spine(node.right.as_deref());
+        self.remaining -= 1;
+        Some((&node.range, &node.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+
+impl<'a, K, V> IntoIterator for &'a IntervalTree<K, V> {
+    type Item = (&'a Range<K>, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+enum Query<'a, K> {
+    Point(&'a K),
+    Range(&'a Range<K>),
+}
+
+impl<K: Ord> Query<'_, K> {
+    fn hits(&self, range: &Range<K>) -> bool {
+        match self {
+            Query::Point(point) => range.start <= **point && **point < range.end,
+            // Empty ranges on either side have no point to share
+            Query::Range(query) => {
+                query.start < query.end
+                    && range.start < range.end
+                    && range.start < query.end
+                    && query.start < range.end
+            }
+        }
+    }
+
+    /// Whether a subtree whose ranges end by `max_end` can reach the query at all.
+    fn reaches(&self, max_end: &K) -> bool {
+        match self {
+            Query::Point(point) => max_end > *point,
+            Query::Range(query) => *max_end > query.start,
+        }
+    }
+
+    /// Whether a range starting at `start`, and every range after it, begins too late.
+    fn is_past(&self, start: &K) -> bool {
+        match self {
+            Query::Point(point) => start > *point,
+            Query::Range(query) => *start >= query.end,
+        }
+    }
+}
+
+/// Iterator over the ranges matching a query, returned by
+/// [`IntervalTree::query_point`] and [`IntervalTree::query_overlapping`].
+pub struct Overlapping<'a, K, V> {
+    stack: Vec<&'a Node<K, V>>,
+    query: Query<'a, K>,
+}
+
+impl<'a, K: Ord, V> Overlapping<'a, K, V> {
+    fn new(tree: &'a IntervalTree<K, V>, query: Query<'a, K>) -> Self {
+        let mut iter = Overlapping {
+            stack: Vec::new(),
+            query,
+        };
+        iter.push_left_spine(tree.root.as_deref());
+        iter
+    }
+
+    /// Like an in-order walk, but without descending into subtrees that end too early.
+    fn push_left_spine(&mut self, mut link: Option<&'a Node<K, V>>) {
+        while let Some(node) = link {
+            if !self.query.reaches(&node.max_end) {
+                break;
+            }
+            self.stack.push(node);
+            link = node.left.as_deref();
+        }
+    }
+}
+
+impl<'a, K: Ord, V> Iterator for Overlapping<'a, K, V> {
+    type Item = (&'a Range<K>, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(node) = self.stack.pop() {
+            if self.query.is_past(&node.range.start) {
+                // Everything left on the stack starts later still
+                self.stack.clear();
+                return None;
+            }
+            self.push_left_spine(node.right.as_deref());
+            if self.query.hits(&node.range) {
+                return Some((&node.range, &node.value));
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{IntervalTree, Link};
+    use std::ops::Range;
+
+    // Checks ordering, balance and the max-end augmentation; returns the subtree height
+    fn check<V>(link: &Link<i32, V>) -> usize {
+        let node = match link {
+            Some(node) => node,
+            None => return 0,
+        };
+        let (left, right) = (check(&node.left), check(&node.right));
+        assert!((left as isize - right as isize).abs() <= 1);
+        assert_eq!(node.height, 1 + left.max(right));
+        let mut max_end = node.range.end;
+        for child in [&node.left, &node.right].iter().copied().flatten() {
+            max_end = max_end.max(child.max_end);
+        }
+        assert_eq!(node.max_end, max_end);
+        node.height
+    }
+
+    fn ranges<'a, I>(iter: I) -> Vec<Range<i32>>
+    where
+        I: Iterator<Item = (&'a Range<i32>, &'a ())>,
+    {
+        iter.map(|(range, _)| range.clone()).collect()
+    }
+
+    #[test]
+    fn basics() {
+        let mut tree = IntervalTree::new();
+
+        // Check empty tree behaves right
+        assert!(tree.is_empty());
+        assert_eq!(tree.query_point(&3).next(), None);
+        assert_eq!(tree.remove(&(0..1)), None);
+
+        assert_eq!(tree.insert(15..20, "a"), None);
+        assert_eq!(tree.insert(5..10, "b"), None);
+        assert_eq!(tree.insert(17..30, "c"), None);
+        assert_eq!(tree.insert(5..10, "B"), Some("b"));
+        assert_eq!(tree.len(), 3);
+        assert_eq!(tree.get(&(5..10)), Some(&"B"));
+        assert_eq!(tree.get(&(5..11)), None);
+
+        assert_eq!(tree.remove(&(15..20)), Some("a"));
+        assert_eq!(tree.remove(&(15..20)), None);
+        assert_eq!(tree.len(), 2);
+        check(&tree.root);
+        assert_eq!(format!("{:?}", tree), r#"{5..10: "B", 17..30: "c"}"#);
+    }
+
+    #[test]
+    fn point_queries() {
+        let tree: IntervalTree<_, _> = vec![15..20, 5..10, 17..30, 4..8, 6..12, 25..26]
+            .into_iter()
+            .map(|r| (r, ()))
+            .collect();
+        check(&tree.root);
+
+        assert_eq!(ranges(tree.query_point(&7)), vec![4..8, 5..10, 6..12]);
+        assert_eq!(ranges(tree.query_point(&18)), vec![15..20, 17..30]);
+        assert_eq!(ranges(tree.query_point(&25)), vec![17..30, 25..26]);
+        // Ends are exclusive
+        assert_eq!(ranges(tree.query_point(&12)), vec![]);
+        assert_eq!(ranges(tree.query_point(&30)), vec![]);
+        assert_eq!(ranges(tree.query_point(&3)), vec![]);
+    }
+
+    #[test]
+    fn overlap_queries() {
+        let tree: IntervalTree<_, _> = vec![0..5, 5..10, 3..7, 20..25, 8..21]
+            .into_iter()
+            .map(|r| (r, ()))
+            .collect();
+
+        assert_eq!(
+            ranges(tree.query_overlapping(&(4..6))),
+            vec![0..5, 3..7, 5..10]
+        );
+        assert_eq!(ranges(tree.query_overlapping(&(21..100))), vec![20..25]);
+        // Touching isn't overlapping
+        assert_eq!(ranges(tree.query_overlapping(&(25..30))), vec![]);
+        // An empty query overlaps nothing
+        assert_eq!(ranges(tree.query_overlapping(&(6..6))), vec![]);
+        assert_eq!(tree.query_overlapping(&(-100..100)).count(), 5);
+    }
+
+    #[test]
+    fn empty_ranges() {
+        let mut tree = IntervalTree::new();
+        tree.insert(0..5, ());
+        tree.insert(3..3, ());
+        tree.insert(7..7, ());
+
+        // Stored, but with no point in them, they overlap nothing and hold no point
+        assert_eq!(tree.len(), 3);
+        assert_eq!(ranges(tree.query_overlapping(&(0..5))), vec![0..5]);
+        assert_eq!(ranges(tree.query_overlapping(&(2..10))), vec![0..5]);
+        assert_eq!(ranges(tree.query_overlapping(&(7..8))), vec![]);
+        assert_eq!(ranges(tree.query_point(&3)), vec![0..5]);
+        assert_eq!(ranges(tree.query_point(&7)), vec![]);
+    }
+
+    #[test]
+    fn against_brute_force() {
+        let mut tree = IntervalTree::new();
+        let mut model: Vec<Range<i32>> = Vec::new();
+        let mut x: u32 = 17;
+        let mut next = || {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (x >> 16) as i32
+        };
+
+        for i in 0..3_000 {
+            let start = next() % 1_000;
+            let range = start..start + 1 + next() % 50;
+            if i % 4 == 3 && !model.is_empty() {
+                let victim = model.swap_remove(next() as usize % model.len());
+                assert_eq!(tree.remove(&victim), Some(()));
+            } else if tree.insert(range.clone(), ()).is_none() {
+                model.push(range);
+            }
+
+            if i % 50 == 0 {
+                check(&tree.root);
+                let point = next() % 1_000;
+                let mut expected: Vec<_> = model
+                    .iter()
+                    .filter(|r| r.contains(&point))
+                    .cloned()
+                    .collect();
+                expected.sort_by_key(|r| (r.start, r.end));
+                assert_eq!(ranges(tree.query_point(&point)), expected);
+
+                let query = point..point + 1 + next() % 30;
+                let mut expected: Vec<_> = model
+                    .iter()
+                    .filter(|r| r.start < query.end && query.start < r.end)
+                    .cloned()
+                    .collect();
+                expected.sort_by_key(|r| (r.start, r.end));
+                assert_eq!(ranges(tree.query_overlapping(&query)), expected);
+            }
+        }
+        assert_eq!(tree.len(), model.len());
+    }
+}
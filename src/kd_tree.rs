@@ -0,0 +1,385 @@
+//! # k-d tree
+//!
+//! Points in `K` dimensions, each with a value, in a binary tree where every node splits
+//! space in two along one axis at its own point (Bentley, 1975): the points on its left
+//! are no further along that axis, those on its right no nearer. The axis goes round in
+//! turn with the depth.
+//!
+//! ```text
+//!             (7,2)              split on x
+//!            /     \
+//!        (5,4)     (9,6)         split on y
+//!        /   \     /
+//!    (2,3) (4,7) (8,1)           split on x
+//! ```
+//!
+//! Built from a set of points all at once, each node takes the median of its points along
+//! its axis, found with a selection rather than a sort, so the tree is balanced and
+//! building it O(n log n). Points inserted later go in at a leaf, which can leave the
+//! tree lopsided, but never wrong; building again balances it.
+//!
+//! A nearest-neighbor search goes down towards the target first and then back up, only
+//! crossing a split if the best found so far is further away than the splitting plane.
+//! For the `k` nearest it's the same, against the furthest of the best `k` so far. An
+//! axis-aligned range search only crosses the splits the range straddles. Searches go by
+//! an explicit stack, so a lopsided tree doesn't overflow the call stack. Distances are
+//! Euclidean, and compared squared.
+
+use std::cmp::Ordering;
+use std::iter::FromIterator;
+
+struct Node<T, const K: usize> {
+    point: [f64; K],
+    value: T,
+    axis: usize,
+    left: Option<usize>,
+    right: Option<usize>,
+}
+
+pub struct KdTree<T, const K: usize> {
+    // Nodes link to each other by index
+    nodes: Vec<Node<T, K>>,
+    root: Option<usize>,
+}
+
+fn distance_squared<const K: usize>(a: &[f64; K], b: &[f64; K]) -> f64 {
+    a.iter().zip(b).map(|(a, b)| (a - b) * (a - b)).sum()
+}
+
+/// Makes the nodes of `nodes`, which start at `offset` in the whole, into a balanced
+/// tree, and returns the index of its root.
+fn build<T, const K: usize>(
+    nodes: &mut [Node<T, K>],
+    offset: usize,
+    depth: usize,
+) -> Option<usize> {
+    if nodes.is_empty() {
+        return None;
+    }
+    let axis = depth % K;
+    let mid = nodes.len() / 2;
+    nodes.select_nth_unstable_by(mid, |a, b| a.point[axis].total_cmp(&b.point[axis]));
+    let (left, rest) = nodes.split_at_mut(mid);
+    let (node, right) = rest.split_first_mut().unwrap();
+    node.axis = axis;
+    node.left = build(left, offset, depth + 1);
+    node.right = build(right, offset + mid + 1, depth + 1);
+    Some(offset + mid)
+}
+
+impl<T, const K: usize> KdTree<T, K> {
+    /// Creates an empty KdTree.
+    pub fn new() -> Self {
+        assert!(K > 0, "a k-d tree needs at least one dimension");
+        KdTree {
+            nodes: Vec::new(),
+            root: None,
+        }
+    }
+
+    /// Builds a balanced tree from a set of points and their values.
+    pub fn build(points: impl IntoIterator<Item = ([f64; K], T)>) -> Self {
+        let mut tree = Self::new();
+        tree.nodes = points
+            .into_iter()
+            .map(|(point, value)| Node {
+                point,
+                value,
+                axis: 0,
+                left: None,
+                right: None,
+            })
+            .collect();
+        tree.root = build(&mut tree.nodes, 0, 0);
+        tree
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Adds a point and its value at a leaf, without rebalancing.
+    pub fn insert(&mut self, point: [f64; K], value: T) {
+        let index = self.nodes.len();
+        let mut axis = 0;
+        if let Some(mut at) = self.root {
+            loop {
+                let node = &mut self.nodes[at];
+                let next = match point[node.axis].total_cmp(&node.point[node.axis]) {
+                    Ordering::Less => &mut node.left,
+                    _ => &mut node.right,
+                };
+                match *next {
+                    Some(next) => at = next,
+                    None => {
+                        *next = Some(index);
+                        axis = (node.axis + 1) % K;
+                        break;
+                    }
+                }
+            }
+        } else {
+            self.root = Some(index);
+        }
+        self.nodes.push(Node {
+            point,
+            value,
+            axis,
+            left: None,
+            right: None,
+        });
+    }
+
+    /// Returns the point closest to `target`, and its value. Of points equally close, any
+    /// one might be returned.
+    pub fn nearest(&self, target: &[f64; K]) -> Option<(&[f64; K], &T)> {
+        self.k_nearest(target, 1).pop()
+    }
+
+    /// Returns the `k` points closest to `target`, or all of them if there are fewer, with
+    /// their values, closest first.
+    pub fn k_nearest(&self, target: &[f64; K], k: usize) -> Vec<(&[f64; K], &T)> {
+        // The best so far, closest first, as distances and node indices
+        let mut best: Vec<(f64, usize)> = Vec::with_capacity(k + 1);
+        // Nodes still to look at, and how far away their side of the split is at least
+        let mut stack: Vec<(usize, f64)> = self.root.into_iter().map(|r| (r, 0.0)).collect();
+        while let Some((at, bound)) = stack.pop() {
+            if k == 0 || (best.len() == k && bound >= best[k - 1].0) {
+                continue;
+            }
+            let node = &self.nodes[at];
+            let d = distance_squared(&node.point, target);
+            if best.len() < k || d < best[k - 1].0 {
+                let i = best.partition_point(|&(b, _)| b <= d);
+                best.insert(i, (d, at));
+                best.truncate(k);
+            }
+            let diff = target[node.axis] - node.point[node.axis];
+            let (near, far) = match diff < 0.0 {
+                true => (node.left, node.right),
+                false => (node.right, node.left),
+            };
+            // The far side first, so that the near side comes off the stack first
+            if let Some(far) = far {
+                stack.push((far, bound.max(diff * diff)));
+            }
+            if let Some(near) = near {
+                stack.push((near, bound));
+            }
+        }
+        best.into_iter()
+            .map(|(_, at)| (&self.nodes[at].point, &self.nodes[at].value))
+            .collect()
+    }
+
+    /// Iterates over the points with every coordinate between `min` and `max`, both
+    /// included, in no particular order.
+    pub fn range(&self, min: [f64; K], max: [f64; K]) -> Range<'_, T, K> {
+        Range {
+            tree: self,
+            min,
+            max,
+            stack: self.root.into_iter().collect(),
+        }
+    }
+
+    /// Iterates over every point, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (&[f64; K], &T)> + '_ {
+        self.nodes.iter().map(|node| (&node.point, &node.value))
+    }
+}
+
+impl<T, const K: usize> Default for KdTree<T, K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const K: usize> FromIterator<([f64; K], T)> for KdTree<T, K> {
+    fn from_iter<I: IntoIterator<Item = ([f64; K], T)>>(iter: I) -> Self {
+        Self::build(iter)
+    }
+}
+
+/// Iterator over the points in an axis-aligned box, from [`KdTree::range`].
+pub struct Range<'a, T, const K: usize> {
+    tree: &'a KdTree<T, K>,
+    min: [f64; K],
+    max: [f64; K],
+    stack: Vec<usize>,
+}
+
+impl<'a, T, const K: usize> Iterator for Range<'a, T, K> {
+    type Item = (&'a [f64; K], &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(at) = self.stack.pop() {
+            let node = &self.tree.nodes[at];
+            let split = node.point[node.axis];
+            // Ties can be on either side of a split made by building
+            if let Some(left) = node.left.filter(|_| self.min[node.axis] <= split) {
+                self.stack.push(left);
+            }
+            if let Some(right) = node.right.filter(|_| self.max[node.axis] >= split) {
+                self.stack.push(right);
+            }
+            let inside =
+                (0..K).all(|i| self.min[i] <= node.point[i] && node.point[i] <= self.max[i]);
+            if inside {
+                return Some((&node.point, &node.value));
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::KdTree;
+
+    /// Checks every node is within the bounds its ancestors' splits leave it, and that
+    /// every node is in the tree once. Returns the depth.
+    fn check<T, const K: usize>(tree: &KdTree<T, K>) -> usize {
+        let mut seen = vec![false; tree.len()];
+        let mut deepest = 0;
+        let everywhere = ([f64::NEG_INFINITY; K], [f64::INFINITY; K]);
+        let mut stack: Vec<_> = tree.root.into_iter().map(|r| (r, 1, everywhere)).collect();
+        while let Some((at, depth, (lo, hi))) = stack.pop() {
+            assert!(!seen[at]);
+            seen[at] = true;
+            deepest = deepest.max(depth);
+            let node = &tree.nodes[at];
+            assert!((0..K).all(|i| lo[i] <= node.point[i] && node.point[i] <= hi[i]));
+            let (mut left_hi, mut right_lo) = (hi, lo);
+            left_hi[node.axis] = node.point[node.axis];
+            right_lo[node.axis] = node.point[node.axis];
+            stack.extend(node.left.map(|c| (c, depth + 1, (lo, left_hi))));
+            stack.extend(node.right.map(|c| (c, depth + 1, (right_lo, hi))));
+        }
+        assert!(seen.iter().all(|&s| s));
+        deepest
+    }
+
+    #[test]
+    fn basics() {
+        let mut tree: KdTree<&str, 2> = KdTree::new();
+
+        // Check empty tree behaves right
+        assert!(tree.is_empty());
+        assert_eq!(tree.nearest(&[0.0, 0.0]), None);
+        assert!(tree.k_nearest(&[0.0, 0.0], 3).is_empty());
+        assert_eq!(tree.range([0.0; 2], [10.0; 2]).count(), 0);
+
+        // The points in the module docs
+        let points = [
+            [2.0, 3.0],
+            [5.0, 4.0],
+            [9.0, 6.0],
+            [4.0, 7.0],
+            [8.0, 1.0],
+            [7.0, 2.0],
+        ];
+        let names = ["a", "b", "c", "d", "e", "f"];
+        tree = points.iter().copied().zip(names.iter().copied()).collect();
+        assert_eq!(tree.len(), 6);
+        assert_eq!(check(&tree), 3);
+        assert_eq!(tree.nodes[tree.root.unwrap()].point, [7.0, 2.0]);
+
+        assert_eq!(tree.nearest(&[9.0, 2.0]), Some((&[8.0, 1.0], &"e")));
+        let near: Vec<_> = tree
+            .k_nearest(&[4.0, 5.0], 3)
+            .into_iter()
+            .map(|(_, &n)| n)
+            .collect();
+        assert_eq!(near, ["b", "d", "a"]);
+        assert_eq!(tree.k_nearest(&[5.0, 5.0], 10).len(), 6);
+        assert!(tree.k_nearest(&[5.0, 5.0], 0).is_empty());
+
+        let mut inside: Vec<_> = tree
+            .range([4.0, 2.0], [8.0, 7.0])
+            .map(|(_, &n)| n)
+            .collect();
+        inside.sort();
+        assert_eq!(inside, ["b", "d", "f"]);
+
+        // Inserting after building
+        tree.insert([5.0, 5.0], "g");
+        check(&tree);
+        assert_eq!(tree.nearest(&[5.1, 5.1]), Some((&[5.0, 5.0], &"g")));
+        assert_eq!(tree.iter().count(), 7);
+    }
+
+    #[test]
+    fn lopsided() {
+        // Points inserted in order make a long chain, which the searches don't recurse down
+        let n = if cfg!(miri) { 500 } else { 5_000 };
+        let mut tree = KdTree::new();
+        for i in 0..n {
+            tree.insert([i as f64], i);
+        }
+        assert_eq!(check(&tree), n);
+        assert_eq!(
+            tree.nearest(&[n as f64 * 2.0]),
+            Some((&[(n - 1) as f64], &(n - 1)))
+        );
+        assert_eq!(tree.range([10.0], [19.5]).count(), 10);
+
+        // Building from the same points balances them
+        let balanced: KdTree<_, 1> = tree.iter().map(|(&p, &v)| (p, v)).collect();
+        assert!(check(&balanced) <= 13);
+    }
+
+    #[test]
+    fn against_brute_force() {
+        // Random points in three dimensions, some built and some inserted, with repeats
+        let mut x: u32 = 1;
+        let mut next = || {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            ((x >> 8) % 64) as f64
+        };
+        let n = if cfg!(miri) { 60 } else { 1_500 };
+        let mut model = Vec::new();
+        for i in 0..n {
+            model.push(([next(), next(), next()], i));
+        }
+        let mut tree: KdTree<_, 3> = model.iter().copied().take(n / 2).collect();
+        for &(point, i) in &model[n / 2..] {
+            tree.insert(point, i);
+        }
+        check(&tree);
+
+        for _ in 0..100 {
+            let target = [next(), next(), next()];
+            let mut distances: Vec<_> = model
+                .iter()
+                .map(|(p, _)| super::distance_squared(p, &target))
+                .collect();
+            distances.sort_by(f64::total_cmp);
+            let near = tree.k_nearest(&target, 5);
+            let found: Vec<_> = near
+                .iter()
+                .map(|(p, &i)| {
+                    assert_eq!(**p, model[i].0);
+                    super::distance_squared(p, &target)
+                })
+                .collect();
+            assert_eq!(found, distances[..5]);
+
+            let other = [next(), next(), next()];
+            let min = [0, 1, 2].map(|i| target[i].min(other[i]));
+            let max = [0, 1, 2].map(|i| target[i].max(other[i]));
+            let mut inside: Vec<_> = tree.range(min, max).map(|(_, &i)| i).collect();
+            inside.sort();
+            let expected: Vec<_> = model
+                .iter()
+                .filter(|(p, _)| (0..3).all(|i| min[i] <= p[i] && p[i] <= max[i]))
+                .map(|&(_, i)| i)
+                .collect();
+            assert_eq!(inside, expected);
+        }
+    }
+}
@@ -0,0 +1,506 @@
+//! # Leftist and skew heaps
+//!
+//! Two binary-tree heaps with a single real operation, merge. Push merges in a one-node
+//! heap, and pop merges the root's two subtrees. Only the right spines get walked:
+//! merging two heaps zips their right spines together in sorted order, and whatever
+//! hangs to the left of each spine node comes along untouched.
+//!
+//! ```text
+//!        1              2                 1
+//!       / \            / \               / \
+//!      4   3    +     5   6     ->      4   2          zip the right spines 1-3 and
+//!         /                                / \         2-6 in order; each node keeps
+//!        7                                5   3        its left subtree
+//!                                            / \
+//!                                           7   6
+//! ```
+//!
+//! Each heap then restores its own shape rule along the new spine. That rule is what
+//! keeps right spines short, and the two heaps go about it in different ways:
+//!
+//! - [`LeftistHeap`] (Crane, 1972) stores each node's *rank*, the length of its right
+//!   spine, and keeps the higher-ranked child on the left. A tree whose right spine is `r`
+//!   long holds at least `2^r - 1` nodes, so spines are O(log n) and so is every merge.
+//! - [`SkewHeap`] (Sleator and Tarjan, 1986) stores nothing and simply swaps the children
+//!   of every node along the merged spine. A single merge can take linear time, but they
+//!   average out to O(log n) amortized, the way splay trees do.
+//!
+//! Both are min-heaps. A left-leaning path can still get long, so both free their nodes
+//! with a loop instead of recursion, and the skew heap merges with a loop as well.
+
+use std::fmt;
+use std::iter::FromIterator;
+use std::mem;
+
+type Link<N> = Option<Box<N>>;
+
+pub struct LeftistHeap<T> {
+    root: Link<LeftistNode<T>>,
+    len: usize,
+}
+
+struct LeftistNode<T> {
+    elem: T,
+    // Length of the right spine from here, counting this node
+    rank: usize,
+    left: Link<LeftistNode<T>>,
+    right: Link<LeftistNode<T>>,
+}
+
+fn rank<T>(link: &Link<LeftistNode<T>>) -> usize {
+    link.as_ref().map_or(0, |node| node.rank)
+}
+
+/// Merges two leftist trees. Recursion only follows right spines, so its depth is
+/// O(log n).
+fn merge_leftist<T: Ord>(a: Link<LeftistNode<T>>, b: Link<LeftistNode<T>>) -> Link<LeftistNode<T>> {
+    let (mut a, mut b) = match (a, b) {
+        (Some(a), Some(b)) => (a, b),
+        (a, b) => return a.or(b),
+    };
+    if b.elem < a.elem {
+        mem::swap(&mut a, &mut b);
+    }
+    a.right = merge_leftist(a.right.take(), Some(b));
+    if rank(&a.left) < rank(&a.right) {
+        mem::swap(&mut a.left, &mut a.right);
+    }
+    a.rank = rank(&a.right) + 1;
+    Some(a)
+}
+
+impl<T: Ord> LeftistHeap<T> {
+    /// Creates an empty LeftistHeap.
+    pub fn new() -> Self {
+        LeftistHeap { root: None, len: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        *self = LeftistHeap::new();
+    }
+
+    /// Returns the smallest element.
+    pub fn peek(&self) -> Option<&T> {
+        self.root.as_ref().map(|node| &node.elem)
+    }
+
+    pub fn push(&mut self, elem: T) {
+        let node = Box::new(LeftistNode {
+            elem,
+            rank: 1,
+            left: None,
+            right: None,
+        });
+        self.root = merge_leftist(self.root.take(), Some(node));
+        self.len += 1;
+    }
+
+    /// Removes and returns the smallest element.
+    pub fn pop(&mut self) -> Option<T> {
+        self.root.take().map(|mut node| {
+            self.root = merge_leftist(node.left.take(), node.right.take());
+            self.len -= 1;
+            node.elem
+        })
+    }
+
+    /// Moves every element of `other` into this heap, in O(log n).
+    pub fn merge(&mut self, mut other: LeftistHeap<T>) {
+        self.root = merge_leftist(self.root.take(), other.root.take());
+        self.len += mem::take(&mut other.len);
+    }
+
+    /// Iterates over the elements in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        let mut stack: Vec<&LeftistNode<T>> = self.root.as_deref().into_iter().collect();
+        std::iter::from_fn(move || {
+            let node = stack.pop()?;
+            stack.extend(node.left.as_deref());
+            stack.extend(node.right.as_deref());
+            Some(&node.elem)
+        })
+    }
+}
+
+impl<T> Drop for LeftistHeap<T> {
+    fn drop(&mut self) {
+        let mut stack: Vec<_> = self.root.take().into_iter().collect();
+        while let Some(mut node) = stack.pop() {
+            stack.extend(node.left.take());
+            stack.extend(node.right.take());
+        }
+    }
+}
+
+pub struct SkewHeap<T> {
+    root: Link<SkewNode<T>>,
+    len: usize,
+}
+
+struct SkewNode<T> {
+    elem: T,
+    left: Link<SkewNode<T>>,
+    right: Link<SkewNode<T>>,
+}
+
+/// Merges two skew trees top-down. A merge can walk a linear-length spine, so this is
+/// a loop rather than recursion.
+fn merge_skew<T: Ord>(mut a: Link<SkewNode<T>>, mut b: Link<SkewNode<T>>) -> Link<SkewNode<T>> {
+    let mut root = None;
+    let mut cursor = &mut root;
+    loop {
+        let (mut x, mut y) = match (a, b) {
+            (Some(x), Some(y)) => (x, y),
+            (rest, None) | (None, rest) => {
+                *cursor = rest;
+                return root;
+            }
+        };
+        if y.elem < x.elem {
+            mem::swap(&mut x, &mut y);
+        }
+        // The rest of x's right spine merges with y, and ends up on x's left
+        a = x.right.take();
+        b = Some(y);
+        x.right = x.left.take();
+        cursor = &mut cursor.insert(x).left;
+    }
+}
+
+impl<T: Ord> SkewHeap<T> {
+    /// Creates an empty SkewHeap.
+    pub fn new() -> Self {
+        SkewHeap { root: None, len: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        *self = SkewHeap::new();
+    }
+
+    /// Returns the smallest element.
+    pub fn peek(&self) -> Option<&T> {
+        self.root.as_ref().map(|node| &node.elem)
+    }
+
+    pub fn push(&mut self, elem: T) {
+        let node = Box::new(SkewNode {
+            elem,
+            left: None,
+            right: None,
+        });
+        self.root = merge_skew(self.root.take(), Some(node));
+        self.len += 1;
+    }
+
+    /// Removes and returns the smallest element.
+    pub fn pop(&mut self) -> Option<T> {
+        self.root.take().map(|mut node| {
+            self.root = merge_skew(node.left.take(), node.right.take());
+            self.len -= 1;
+            node.elem
+        })
+    }
+
+    /// Moves every element of `other` into this heap, in O(log n) amortized.
+    pub fn merge(&mut self, mut other: SkewHeap<T>) {
+        self.root = merge_skew(self.root.take(), other.root.take());
+        self.len += mem::take(&mut other.len);
+    }
+
+    /// Iterates over the elements in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        let mut stack: Vec<&SkewNode<T>> = self.root.as_deref().into_iter().collect();
+        std::iter::from_fn(move || {
+            let node = stack.pop()?;
+            stack.extend(node.left.as_deref());
+            stack.extend(node.right.as_deref());
+            Some(&node.elem)
+        })
+    }
+}
+
+impl<T> Drop for SkewHeap<T> {
+    fn drop(&mut self) {
+        let mut stack: Vec<_> = self.root.take().into_iter().collect();
+        while let Some(mut node) = stack.pop() {
+            stack.extend(node.left.take());
+            stack.extend(node.right.take());
+        }
+    }
+}
+
+impl<T: Ord> Default for LeftistHeap<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Ord> Default for SkewHeap<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Ord> FromIterator<T> for LeftistHeap<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut heap = LeftistHeap::new();
+        heap.extend(iter);
+        heap
+    }
+}
+
+impl<T: Ord> FromIterator<T> for SkewHeap<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut heap = SkewHeap::new();
+        heap.extend(iter);
+        heap
+    }
+}
+
+impl<T: Ord> Extend<T> for LeftistHeap<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for elem in iter {
+            self.push(elem);
+        }
+    }
+}
+
+impl<T: Ord> Extend<T> for SkewHeap<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for elem in iter {
+            self.push(elem);
+        }
+    }
+}
+
+impl<T: Ord + fmt::Debug> fmt::Debug for LeftistHeap<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T: Ord + fmt::Debug> fmt::Debug for SkewHeap<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{rank, LeftistHeap, LeftistNode, SkewHeap, SkewNode};
+    use crate::heap::BinaryHeap;
+    use std::cmp::Reverse;
+
+    /// What the property tests need from either heap.
+    trait Meldable: Default {
+        fn push(&mut self, elem: u32);
+        fn pop(&mut self) -> Option<u32>;
+        fn peek(&self) -> Option<&u32>;
+        fn len(&self) -> usize;
+        fn merge(&mut self, other: Self);
+        fn check(&self);
+    }
+
+    fn check_leftist(node: &LeftistNode<u32>) {
+        assert_eq!(node.rank, rank(&node.right) + 1);
+        assert!(rank(&node.left) >= rank(&node.right));
+        for child in node.left.iter().chain(&node.right) {
+            assert!(node.elem <= child.elem);
+            check_leftist(child);
+        }
+    }
+
+    fn check_skew(node: &SkewNode<u32>) {
+        // Iteratively, since this tree may be deep
+        let mut stack = vec![node];
+        while let Some(node) = stack.pop() {
+            for child in node.left.iter().chain(&node.right) {
+                assert!(node.elem <= child.elem);
+                stack.push(child);
+            }
+        }
+    }
+
+    impl Meldable for LeftistHeap<u32> {
+        fn push(&mut self, elem: u32) {
+            self.push(elem)
+        }
+        fn pop(&mut self) -> Option<u32> {
+            self.pop()
+        }
+        fn peek(&self) -> Option<&u32> {
+            self.peek()
+        }
+        fn len(&self) -> usize {
+            self.len()
+        }
+        fn merge(&mut self, other: Self) {
+            self.merge(other)
+        }
+        fn check(&self) {
+            if let Some(root) = &self.root {
+                check_leftist(root);
+            }
+            assert_eq!(self.iter().count(), self.len);
+        }
+    }
+
+    impl Meldable for SkewHeap<u32> {
+        fn push(&mut self, elem: u32) {
+            self.push(elem)
+        }
+        fn pop(&mut self) -> Option<u32> {
+            self.pop()
+        }
+        fn peek(&self) -> Option<&u32> {
+            self.peek()
+        }
+        fn len(&self) -> usize {
+            self.len()
+        }
+        fn merge(&mut self, other: Self) {
+            self.merge(other)
+        }
+        fn check(&self) {
+            if let Some(root) = &self.root {
+                check_skew(root);
+            }
+            assert_eq!(self.iter().count(), self.len);
+        }
+    }
+
+    fn basics<H: Meldable>() {
+        let mut heap = H::default();
+
+        // Check empty heap behaves right
+        assert_eq!(heap.pop(), None);
+        assert_eq!(heap.peek(), None);
+
+        // Populate heap
+        for x in [5, 3, 8, 1, 9, 2] {
+            heap.push(x);
+        }
+        heap.check();
+        assert_eq!(heap.len(), 6);
+        assert_eq!(heap.peek(), Some(&1));
+
+        // Check normal removal
+        assert_eq!(heap.pop(), Some(1));
+        assert_eq!(heap.pop(), Some(2));
+
+        // Merge in another heap, and an empty one
+        let mut other = H::default();
+        other.push(0);
+        other.push(4);
+        heap.merge(other);
+        heap.merge(H::default());
+        heap.check();
+
+        // Check exhaustion
+        let popped: Vec<_> = std::iter::from_fn(|| heap.pop()).collect();
+        assert_eq!(popped, vec![0, 3, 4, 5, 8, 9]);
+        assert_eq!(heap.len(), 0);
+    }
+
+    /// Random pushes, pops and merges against the binary heap.
+    fn against_binary_heap<H: Meldable>() {
+        let mut heap = H::default();
+        let mut model = BinaryHeap::new();
+        let mut x: u32 = 1;
+        for i in 0..10_000 {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            match x >> 30 {
+                0 => assert_eq!(heap.pop(), model.pop().map(|Reverse(v)| v)),
+                1 => {
+                    let mut other = H::default();
+                    for j in 0..(x >> 8) % 20 {
+                        other.push((x >> 4) % 1000 + j);
+                        model.push(Reverse((x >> 4) % 1000 + j));
+                    }
+                    heap.merge(other);
+                }
+                _ => {
+                    heap.push(x % 1000);
+                    model.push(Reverse(x % 1000));
+                }
+            }
+            assert_eq!(heap.len(), model.len());
+            assert_eq!(heap.peek(), model.peek().map(|Reverse(v)| v));
+            if i % 500 == 0 {
+                heap.check();
+            }
+        }
+        let popped: Vec<_> = std::iter::from_fn(|| heap.pop()).collect();
+        let expected: Vec<_> = std::iter::from_fn(|| model.pop().map(|Reverse(v)| v)).collect();
+        assert_eq!(popped, expected);
+    }
+
+    /// Sorted pushes make the longest paths either heap can get.
+    fn sorted_input<H: Meldable>() {
+        let n = if cfg!(miri) { 1_000 } else { 100_000 };
+        let mut heap = H::default();
+        for x in (0..n).rev() {
+            heap.push(x);
+        }
+        for x in 0..n / 2 {
+            assert_eq!(heap.pop(), Some(x));
+        }
+        for x in 0..n {
+            heap.push(x);
+        }
+        assert_eq!(heap.len(), n as usize + n as usize / 2);
+    }
+
+    #[test]
+    fn leftist_basics() {
+        basics::<LeftistHeap<u32>>();
+    }
+
+    #[test]
+    fn skew_basics() {
+        basics::<SkewHeap<u32>>();
+    }
+
+    #[test]
+    fn leftist_against_binary_heap() {
+        against_binary_heap::<LeftistHeap<u32>>();
+    }
+
+    #[test]
+    fn skew_against_binary_heap() {
+        against_binary_heap::<SkewHeap<u32>>();
+    }
+
+    #[test]
+    fn leftist_sorted_input() {
+        sorted_input::<LeftistHeap<u32>>();
+    }
+
+    #[test]
+    fn skew_sorted_input() {
+        sorted_input::<SkewHeap<u32>>();
+    }
+
+    #[test]
+    fn leftist_spine_stays_short() {
+        let heap: LeftistHeap<u32> = (0..1 << 12).rev().collect();
+        // A right spine r long needs at least 2^r - 1 nodes
+        assert!(heap.root.as_ref().unwrap().rank <= 13);
+    }
+}
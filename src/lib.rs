@@ -1,6 +1,89 @@
+pub mod aho_corasick;
+pub mod alias;
+pub mod arena_list;
+pub mod avl;
+pub mod bimap;
+pub mod binomial_heap;
+pub mod bit_set;
+pub mod bloom;
+pub mod bst;
+pub mod btree;
+pub mod cache;
+pub mod catenable_deque;
+pub mod count_min;
+pub mod counter;
+pub mod cow_vec;
+pub mod cuckoo_map;
+pub mod dary_heap;
 pub mod decent;
 pub mod deque;
 pub mod double_single;
+pub mod epoch;
+pub mod fast_trie;
+pub mod finger_tree;
+pub mod flat_combining;
+pub mod graph;
+pub mod hamt;
+pub mod hazard;
+pub mod heap;
+pub mod huffman;
+pub mod indexed_heap;
+pub mod interval_tree;
+pub mod kd_tree;
+pub mod leftist_heap;
+pub mod linked_hash_map;
+pub mod linked_list;
+pub mod max_flow;
+pub mod merkle;
+pub mod min_max_heap;
 pub mod minimal;
+pub mod morton;
+pub mod mpmc;
+pub mod ms_queue;
+pub mod multi_map;
+pub mod order_stat;
+pub mod pairing_heap;
 pub mod persistent;
+pub mod persistent_heap;
+pub mod persistent_map;
+pub mod persistent_vec;
+pub mod piece_table;
+pub mod pool;
+pub mod quadtree;
+pub mod quantile;
+pub mod queue;
+pub mod r_tree;
+pub mod radix_trie;
+pub mod range_set;
+pub mod rank_select;
+pub mod rcu;
+pub mod real_time_queue;
+pub mod reservoir;
+pub mod ring_buffer;
+mod rng;
+pub mod robin_hood;
+pub mod rope;
+pub mod shard_map;
+pub mod shortest_path;
 pub mod singly_queue;
+pub mod skip_map;
+pub mod skiplist;
+pub mod slot_map;
+pub mod small_list;
+pub mod spanning_tree;
+pub mod sparse_table;
+pub mod splay;
+pub mod spsc;
+pub mod stack;
+pub mod suffix_array;
+pub mod toposort;
+pub mod traversal;
+pub mod treap;
+pub mod treiber;
+pub mod trie;
+pub mod ttl_cache;
+pub mod union_find;
+pub mod vec_deque;
+pub mod wavelet_tree;
+pub mod work_stealing;
+pub mod xor_list;
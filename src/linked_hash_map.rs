@@ -0,0 +1,374 @@
+//! # Linked hash map
+//!
+//! A hash map that remembers the order its entries went in. Lookups are a hash map's, O(1),
+//! while iteration runs from the oldest entry to the newest instead of in whatever order the
+//! hashes fall.
+//!
+//! It's the same pair of structures as [`crate::cache::LruCache`]: a [`LinkedList`] holds
+//! the entries in order, and a `HashMap` points each key at its node, so an entry can be
+//! unlinked from the middle in O(1) on removal. The list runs newest first, since
+//! `push_front_node` is what hands back a node to remember, and iteration walks it from
+//! the back:
+//!
+//! ```text
+//!   map:   "c" ──┐      "b" ──┐      "a" ──┐
+//!                v            v            v
+//!   list:  front ("c", 3) <-> ("b", 2) <-> ("a", 1) back
+//!
+//!   iter:  ("a", 1), ("b", 2), ("c", 3)
+//! ```
+//!
+//! Inserting a key that's already there replaces its value but keeps its place. In
+//! access-order mode, from [`LinkedHashMap::access_order`], every `insert`, `get` and
+//! `get_mut` moves the entry to the newest end instead, so the oldest entry is the least
+//! recently used. That's an LRU cache without the size limit, for callers who want to
+//! decide themselves when to `pop_front`.
+//!
+//! Keys are stored in both the map and the list, so they must be `Clone`. The nodes are
+//! reached through raw pointers, so run the tests under Miri too:
+//!
+//! ```text
+//! cargo +nightly miri test linked_hash_map
+//! ```
+
+use crate::linked_list::{LinkedList, Node};
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::iter::FromIterator;
+use std::ptr::NonNull;
+
+pub struct LinkedHashMap<K, V> {
+    map: HashMap<K, NonNull<Node<(K, V)>>>,
+    // Newest at the front
+    order: LinkedList<(K, V)>,
+    access_order: bool,
+}
+
+// The pointers are into `order`, which the map owns outright
+unsafe impl<K: Send, V: Send> Send for LinkedHashMap<K, V> {}
+unsafe impl<K: Sync, V: Sync> Sync for LinkedHashMap<K, V> {}
+
+impl<K: Hash + Eq + Clone, V> LinkedHashMap<K, V> {
+    /// Creates an empty LinkedHashMap in insertion order.
+    pub fn new() -> Self {
+        LinkedHashMap {
+            map: HashMap::new(),
+            order: LinkedList::new(),
+            access_order: false,
+        }
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        LinkedHashMap {
+            map: HashMap::with_capacity(capacity),
+            ..Self::new()
+        }
+    }
+
+    /// Switches the map to access order, where `insert`, `get` and `get_mut` each move
+    /// their entry to the newest end. Entries already in the map keep their places.
+    pub fn access_order(mut self) -> Self {
+        self.access_order = true;
+        self
+    }
+
+    pub fn is_access_order(&self) -> bool {
+        self.access_order
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.map.clear();
+        self.order.clear();
+    }
+
+    /// Moves `node` to the newest end, if the map is in access order.
+    fn touch(&mut self, node: NonNull<Node<(K, V)>>) {
+        if self.access_order {
+            unsafe { self.order.move_to_front(node) }
+        }
+    }
+
+    /// Inserts a key-value pair, returning the old value if the key was already there. A
+    /// new key goes in as the newest entry; an old one keeps its place, unless the map is
+    /// in access order.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        if let Some(&node) = self.map.get(&key) {
+            self.touch(node);
+            unsafe {
+                return Some(std::mem::replace(&mut (*node.as_ptr()).elem.1, value));
+            }
+        }
+        let node = self.order.push_front_node((key.clone(), value));
+        self.map.insert(key, node);
+        None
+    }
+
+    /// Returns the value under `key`, moving it to the newest end if the map is in access
+    /// order.
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        Q: Hash + Eq + ?Sized,
+        K: Borrow<Q>,
+    {
+        self.get_mut(key).map(|value| &*value)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        Q: Hash + Eq + ?Sized,
+        K: Borrow<Q>,
+    {
+        let node = *self.map.get(key)?;
+        self.touch(node);
+        unsafe { Some(&mut (*node.as_ptr()).elem.1) }
+    }
+
+    /// Returns the value under `key` without counting it as an access.
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+    where
+        Q: Hash + Eq + ?Sized,
+        K: Borrow<Q>,
+    {
+        let node = self.map.get(key)?;
+        unsafe { Some(&(*node.as_ptr()).elem.1) }
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        Q: Hash + Eq + ?Sized,
+        K: Borrow<Q>,
+    {
+        self.map.contains_key(key)
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        Q: Hash + Eq + ?Sized,
+        K: Borrow<Q>,
+    {
+        let node = self.map.remove(key)?;
+        unsafe { Some(self.order.unlink(node).1) }
+    }
+
+    /// Returns the oldest entry, the first `iter` yields.
+    pub fn front(&self) -> Option<(&K, &V)> {
+        self.order.back().map(|(key, value)| (key, value))
+    }
+
+    /// Returns the newest entry.
+    pub fn back(&self) -> Option<(&K, &V)> {
+        self.order.front().map(|(key, value)| (key, value))
+    }
+
+    /// Removes and returns the oldest entry.
+    pub fn pop_front(&mut self) -> Option<(K, V)> {
+        let (key, value) = self.order.pop_back()?;
+        self.map.remove(&key);
+        Some((key, value))
+    }
+
+    /// Removes and returns the newest entry.
+    pub fn pop_back(&mut self) -> Option<(K, V)> {
+        let (key, value) = self.order.pop_front()?;
+        self.map.remove(&key);
+        Some((key, value))
+    }
+
+    /// Iterates over the entries from oldest to newest, without counting any of them as
+    /// an access.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (&K, &V)> + ExactSizeIterator + '_ {
+        self.order.iter().rev().map(|(key, value)| (key, value))
+    }
+
+    pub fn iter_mut(
+        &mut self,
+    ) -> impl DoubleEndedIterator<Item = (&K, &mut V)> + ExactSizeIterator + '_ {
+        self.order
+            .iter_mut()
+            .rev()
+            .map(|(key, value)| (&*key, value))
+    }
+
+    pub fn keys(&self) -> impl DoubleEndedIterator<Item = &K> + ExactSizeIterator + '_ {
+        self.iter().map(|(key, _)| key)
+    }
+
+    pub fn values(&self) -> impl DoubleEndedIterator<Item = &V> + ExactSizeIterator + '_ {
+        self.iter().map(|(_, value)| value)
+    }
+}
+
+impl<K: Hash + Eq + Clone, V> Default for LinkedHashMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> Clone for LinkedHashMap<K, V> {
+    fn clone(&self) -> Self {
+        let mut map = LinkedHashMap::with_capacity(self.len());
+        map.access_order = self.access_order;
+        map.extend(self.iter().map(|(key, value)| (key.clone(), value.clone())));
+        map
+    }
+}
+
+impl<K: Hash + Eq + Clone + fmt::Debug, V: fmt::Debug> fmt::Debug for LinkedHashMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K: Hash + Eq + Clone, V> Extend<(K, V)> for LinkedHashMap<K, V> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (key, value) in iter {
+            self.insert(key, value);
+        }
+    }
+}
+
+impl<K: Hash + Eq + Clone, V> FromIterator<(K, V)> for LinkedHashMap<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = Self::new();
+        map.extend(iter);
+        map
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::LinkedHashMap;
+
+    fn entries(map: &LinkedHashMap<u32, u32>) -> Vec<(u32, u32)> {
+        map.iter().map(|(&k, &v)| (k, v)).collect()
+    }
+
+    #[test]
+    fn basics() {
+        let mut map = LinkedHashMap::new();
+
+        // Check empty map behaves right
+        assert_eq!(map.get("a"), None);
+        assert_eq!(map.remove("a"), None);
+        assert_eq!(map.pop_front(), None);
+        assert_eq!(map.front(), None);
+
+        // Populate map
+        assert_eq!(map.insert("c".to_string(), 3), None);
+        assert_eq!(map.insert("a".to_string(), 1), None);
+        assert_eq!(map.insert("b".to_string(), 2), None);
+        // Replacing keeps the old place
+        assert_eq!(map.insert("c".to_string(), 30), Some(3));
+        *map.get_mut("a").unwrap() += 10;
+        assert_eq!(map.len(), 3);
+        assert_eq!(format!("{:?}", map), r#"{"c": 30, "a": 11, "b": 2}"#);
+        assert_eq!(map.front(), Some((&"c".to_string(), &30)));
+        assert_eq!(map.back(), Some((&"b".to_string(), &2)));
+        let keys: Vec<_> = map.keys().rev().map(String::as_str).collect();
+        assert_eq!(keys, ["b", "a", "c"]);
+
+        // Check normal removal
+        assert_eq!(map.remove("a"), Some(11));
+        assert!(!map.contains_key("a"));
+        assert_eq!(map.pop_front(), Some(("c".to_string(), 30)));
+
+        // Push some more just to make sure nothing's corrupted
+        map.insert("d".to_string(), 4);
+        map.insert("a".to_string(), 1);
+        assert_eq!(format!("{:?}", map.clone()), r#"{"b": 2, "d": 4, "a": 1}"#);
+
+        // Check exhaustion
+        assert_eq!(map.pop_back(), Some(("a".to_string(), 1)));
+        assert_eq!(map.pop_back(), Some(("d".to_string(), 4)));
+        assert_eq!(map.pop_front(), Some(("b".to_string(), 2)));
+        assert_eq!(map.pop_front(), None);
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn access_order() {
+        let mut map: LinkedHashMap<u32, u32> = (1..=4).map(|i| (i, i * 10)).collect();
+        assert!(!map.is_access_order());
+        map.get(&1);
+        assert_eq!(entries(&map), [(1, 10), (2, 20), (3, 30), (4, 40)]);
+
+        let mut map = map.access_order();
+        assert!(map.is_access_order());
+        map.get(&1);
+        map.insert(2, 21);
+        // Peeking and iterating don't count
+        map.peek(&3);
+        assert_eq!(entries(&map), [(3, 30), (4, 40), (1, 10), (2, 21)]);
+        // So the front is the least recently used
+        assert_eq!(map.pop_front(), Some((3, 30)));
+        assert!(map.clone().is_access_order());
+    }
+
+    #[test]
+    fn against_vec() {
+        // A Vec of entries in order is a slow but obvious model
+        for access_order in [false, true] {
+            let mut map = LinkedHashMap::new();
+            if access_order {
+                map = map.access_order();
+            }
+            let mut model: Vec<(u32, u32)> = Vec::new();
+            let n = if cfg!(miri) { 500 } else { 20_000 };
+            let mut x: u32 = 1;
+            for i in 0..n {
+                x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                let key = (x >> 8) % 64;
+                let at = model.iter().position(|&(k, _)| k == key);
+                match x >> 29 {
+                    0 | 1 => {
+                        let expected = at.map(|at| model.remove(at).1);
+                        assert_eq!(map.remove(&key), expected);
+                    }
+                    2 | 3 => {
+                        let expected = at.map(|at| model[at].1);
+                        if let (Some(at), true) = (at, access_order) {
+                            let entry = model.remove(at);
+                            model.push(entry);
+                        }
+                        assert_eq!(map.get(&key).copied(), expected);
+                    }
+                    4 => assert_eq!(
+                        map.pop_front(),
+                        (!model.is_empty()).then(|| model.remove(0))
+                    ),
+                    _ => {
+                        let old = match at {
+                            Some(at) if access_order => {
+                                let (_, old) = model.remove(at);
+                                model.push((key, i));
+                                Some(old)
+                            }
+                            Some(at) => Some(std::mem::replace(&mut model[at].1, i)),
+                            None => {
+                                model.push((key, i));
+                                None
+                            }
+                        };
+                        assert_eq!(map.insert(key, i), old);
+                    }
+                }
+                assert_eq!(map.len(), model.len());
+            }
+            assert_eq!(entries(&map), model);
+            for (_, value) in map.iter_mut() {
+                *value += 1;
+            }
+            assert!(map.values().zip(&model).all(|(v, (_, m))| *v == m + 1));
+        }
+    }
+}
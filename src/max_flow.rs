@@ -0,0 +1,388 @@
+//! # Maximum flow
+//!
+//! A flow network, directed edges with capacities, and Dinic's algorithm (1970) for the
+//! most that can flow through it from a source to a sink.
+//!
+//! Every edge is stored next to a reverse edge that starts with no capacity. Pushing flow
+//! along an edge takes that much off what's left of it, its *residual* capacity, and adds
+//! it to the reverse edge's, so that a later path can push the flow back and send it some
+//! other way. A path from source to sink through edges with capacity left is an
+//! *augmenting path*, and the flow is as big as it gets exactly when there's none.
+//!
+//! Dinic's algorithm works in phases. Each starts with a breadth-first search from the
+//! source, giving every node a *level*, its distance in edges with capacity left. Then it
+//! only follows edges from one level to the next, as a depth-first search that pushes as
+//! much as fits down each path it finds to the sink, until there's no such path left: a
+//! *blocking flow*. Each node keeps a pointer to the first of its edges still worth trying,
+//! so no dead end is gone down twice in a phase. The sink's level goes up every phase,
+//! which bounds the phases by V and the whole thing by O(V² E).
+//!
+//! ```text
+//!   level 0     1        2        3
+//!
+//!           +-> a ---+-> c ---+
+//!     s ----+        |        +--> t
+//!           +-> b ---+-> d ---+
+//! ```
+//!
+//! When it's done, the nodes the source can still reach through edges with capacity left
+//! are one side of a *minimum cut*: the edges from them to the rest are full, and their
+//! capacities add up to the flow (Ford and Fulkerson, 1956). Nodes are numbered `0..n`, as
+//! for [`crate::union_find::UnionFind`], and edges by the order they were added.
+
+use std::collections::VecDeque;
+
+struct Arc {
+    to: usize,
+    residual: u64,
+}
+
+pub struct FlowNetwork {
+    // Edge i is arcs 2i and 2i + 1, forwards and backwards
+    arcs: Vec<Arc>,
+    capacity: Vec<u64>,
+    // The arcs leaving each node
+    adjacent: Vec<Vec<usize>>,
+}
+
+impl FlowNetwork {
+    /// Creates a FlowNetwork of `n` nodes and no edges.
+    pub fn new(n: usize) -> Self {
+        FlowNetwork {
+            arcs: Vec::new(),
+            capacity: Vec::new(),
+            adjacent: vec![Vec::new(); n],
+        }
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.adjacent.len()
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.capacity.len()
+    }
+
+    /// Adds a node and returns its number.
+    pub fn add_node(&mut self) -> usize {
+        self.adjacent.push(Vec::new());
+        self.adjacent.len() - 1
+    }
+
+    /// Adds an edge from `from` to `to` that can carry up to `capacity`, and returns its
+    /// number.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either node is out of bounds.
+    pub fn add_edge(&mut self, from: usize, to: usize, capacity: u64) -> usize {
+        assert!(
+            from < self.node_count() && to < self.node_count(),
+            "edge {} -> {} out of bounds for {} nodes",
+            from,
+            to,
+            self.node_count()
+        );
+        let edge = self.capacity.len();
+        self.adjacent[from].push(2 * edge);
+        self.adjacent[to].push(2 * edge + 1);
+        self.arcs.push(Arc {
+            to,
+            residual: capacity,
+        });
+        self.arcs.push(Arc {
+            to: from,
+            residual: 0,
+        });
+        self.capacity.push(capacity);
+        edge
+    }
+
+    pub fn capacity(&self, edge: usize) -> u64 {
+        self.capacity[edge]
+    }
+
+    /// How much is flowing along an edge.
+    pub fn flow(&self, edge: usize) -> u64 {
+        // All of it is on offer to go back
+        self.arcs[2 * edge + 1].residual
+    }
+
+    /// Returns every edge to carrying nothing.
+    pub fn reset(&mut self) {
+        for (edge, &capacity) in self.capacity.iter().enumerate() {
+            self.arcs[2 * edge].residual = capacity;
+            self.arcs[2 * edge + 1].residual = 0;
+        }
+    }
+
+    /// Pushes as much more flow from `source` to `sink` as the network can take, and
+    /// returns how much that was. On a network carrying nothing, that's the maximum flow.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either node is out of bounds.
+    pub fn max_flow(&mut self, source: usize, sink: usize) -> u64 {
+        assert!(source < self.node_count() && sink < self.node_count());
+        if source == sink {
+            return 0;
+        }
+        let mut total = 0;
+        while let Some(level) = self.levels(source, sink) {
+            total += self.blocking_flow(source, sink, &level);
+        }
+        total
+    }
+
+    /// Numbers every node by its distance from `source` through arcs with capacity left.
+    /// Returns `None` if that doesn't get to `sink`.
+    fn levels(&self, source: usize, sink: usize) -> Option<Vec<usize>> {
+        let mut level = vec![usize::MAX; self.node_count()];
+        level[source] = 0;
+        let mut queue = VecDeque::from(vec![source]);
+        while let Some(node) = queue.pop_front() {
+            for &arc in &self.adjacent[node] {
+                let Arc { to, residual } = self.arcs[arc];
+                if residual > 0 && level[to] == usize::MAX {
+                    level[to] = level[node] + 1;
+                    queue.push_back(to);
+                }
+            }
+        }
+        (level[sink] != usize::MAX).then_some(level)
+    }
+
+    /// Pushes flow along paths that go up a level at each step until there are none left.
+    fn blocking_flow(&mut self, source: usize, sink: usize, level: &[usize]) -> u64 {
+        // Each node's next arc to try
+        let mut next = vec![0; self.node_count()];
+        // The arcs of the path from the source so far
+        let mut path: Vec<usize> = Vec::new();
+        let mut total = 0;
+        loop {
+            let node = path.last().map_or(source, |&arc| self.arcs[arc].to);
+            if node == sink {
+                let pushed = path
+                    .iter()
+                    .map(|&arc| self.arcs[arc].residual)
+                    .min()
+                    .unwrap();
+                for &arc in &path {
+                    self.arcs[arc].residual -= pushed;
+                    self.arcs[arc ^ 1].residual += pushed;
+                }
+                total += pushed;
+                // Back up to just before the first arc that's now full
+                let full = path.iter().position(|&arc| self.arcs[arc].residual == 0);
+                path.truncate(full.unwrap());
+                continue;
+            }
+            let arcs = &self.adjacent[node];
+            while next[node] < arcs.len() {
+                let arc = &self.arcs[arcs[next[node]]];
+                if arc.residual > 0 && level[arc.to] == level[node] + 1 {
+                    break;
+                }
+                next[node] += 1;
+            }
+            if next[node] < arcs.len() {
+                path.push(arcs[next[node]]);
+                continue;
+            }
+            // A dead end: go back and have the node before try its next arc
+            match path.pop() {
+                Some(arc) => next[self.arcs[arc ^ 1].to] += 1,
+                None => return total,
+            }
+        }
+    }
+
+    /// Returns which nodes `source` can reach through edges with capacity left. After
+    /// [`max_flow`](Self::max_flow), that's the source's side of a minimum cut.
+    pub fn source_side(&self, source: usize) -> Vec<bool> {
+        let mut reached = vec![false; self.node_count()];
+        reached[source] = true;
+        let mut stack = vec![source];
+        while let Some(node) = stack.pop() {
+            for &arc in &self.adjacent[node] {
+                let Arc { to, residual } = self.arcs[arc];
+                if residual > 0 && !reached[to] {
+                    reached[to] = true;
+                    stack.push(to);
+                }
+            }
+        }
+        reached
+    }
+
+    /// Returns the edges of a minimum cut between `source` and the sink just maximized
+    /// for: the edges from the source's side to the other. Their capacities add up to the
+    /// maximum flow.
+    pub fn min_cut(&self, source: usize) -> Vec<usize> {
+        let side = self.source_side(source);
+        (0..self.edge_count())
+            .filter(|&edge| side[self.arcs[2 * edge + 1].to] && !side[self.arcs[2 * edge].to])
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::FlowNetwork;
+
+    /// Checks capacities are kept to and that flow is conserved everywhere but the source
+    /// and sink, which send and take `value`.
+    fn check(network: &FlowNetwork, source: usize, sink: usize, value: u64) {
+        let mut balance = vec![0i64; network.node_count()];
+        for edge in 0..network.edge_count() {
+            let flow = network.flow(edge);
+            assert!(flow <= network.capacity(edge));
+            balance[network.arcs[2 * edge + 1].to] -= flow as i64;
+            balance[network.arcs[2 * edge].to] += flow as i64;
+        }
+        for (node, &b) in balance.iter().enumerate() {
+            let expected = match node {
+                n if n == source => -(value as i64),
+                n if n == sink => value as i64,
+                _ => 0,
+            };
+            assert_eq!(b, expected);
+        }
+        let cut: u64 = network
+            .min_cut(source)
+            .iter()
+            .map(|&e| network.capacity(e))
+            .sum();
+        assert_eq!(cut, value);
+    }
+
+    #[test]
+    fn basics() {
+        // The network from Cormen et al.'s chapter on flows
+        let mut network = FlowNetwork::new(6);
+        let (s, v1, v2, v3, v4, t) = (0, 1, 2, 3, 4, 5);
+        for &(from, to, capacity) in &[
+            (s, v1, 16),
+            (s, v2, 13),
+            (v1, v3, 12),
+            (v2, v1, 4),
+            (v2, v4, 14),
+            (v3, v2, 9),
+            (v3, t, 20),
+            (v4, v3, 7),
+            (v4, t, 4),
+        ] {
+            network.add_edge(from, to, capacity);
+        }
+        assert_eq!(network.max_flow(s, t), 23);
+        check(&network, s, t, 23);
+        assert_eq!(network.max_flow(s, t), 0);
+        let side = network.source_side(s);
+        assert_eq!(side, [true, true, true, false, true, false]);
+
+        // More capacity into the sink lets more through, on top of what's there
+        network.add_edge(v4, t, 10);
+        assert_eq!(network.max_flow(s, t), 2);
+        check(&network, s, t, 25);
+
+        // And starting over gives the same total
+        network.reset();
+        assert_eq!(network.max_flow(s, t), 25);
+
+        // Nothing goes anywhere without edges
+        let mut network = FlowNetwork::new(2);
+        assert_eq!(network.max_flow(0, 1), 0);
+        assert_eq!(network.max_flow(0, 0), 0);
+        assert!(network.min_cut(0).is_empty());
+    }
+
+    #[test]
+    fn matching() {
+        // A bipartite matching: workers on the left, jobs they can do on the right
+        let can_do = [vec![0, 1], vec![0], vec![1, 2, 3], vec![3]];
+        let mut network = FlowNetwork::new(2);
+        let (source, sink) = (0, 1);
+        let workers: Vec<_> = can_do.iter().map(|_| network.add_node()).collect();
+        let jobs: Vec<_> = (0..4).map(|_| network.add_node()).collect();
+        let mut assignments = Vec::new();
+        for (w, jobs_for) in can_do.iter().enumerate() {
+            network.add_edge(source, workers[w], 1);
+            for &j in jobs_for {
+                assignments.push((network.add_edge(workers[w], jobs[j], 1), w, j));
+            }
+        }
+        for &job in &jobs {
+            network.add_edge(job, sink, 1);
+        }
+        assert_eq!(network.max_flow(source, sink), 4);
+        let mut matched: Vec<_> = assignments
+            .iter()
+            .filter(|&&(edge, ..)| network.flow(edge) == 1)
+            .map(|&(_, w, j)| (w, j))
+            .collect();
+        matched.sort();
+        assert_eq!(matched, [(0, 1), (1, 0), (2, 2), (3, 3)]);
+    }
+
+    #[test]
+    fn long_path() {
+        // A long chain doesn't overflow the call stack, there being none
+        let n = if cfg!(miri) { 1_000 } else { 100_000 };
+        let mut network = FlowNetwork::new(n);
+        for i in 1..n {
+            network.add_edge(i - 1, i, 5 + i as u64 % 7);
+        }
+        assert_eq!(network.max_flow(0, n - 1), 5);
+        assert_eq!(network.min_cut(0).len(), 1);
+    }
+
+    #[test]
+    fn against_edmonds_karp() {
+        // Random networks, against augmenting along one shortest path at a time
+        let mut x: u32 = 1;
+        let rounds = if cfg!(miri) { 3 } else { 50 };
+        let n = 12;
+        for _ in 0..rounds {
+            let mut network = FlowNetwork::new(n);
+            let mut residual = vec![vec![0u64; n]; n];
+            for _ in 0..40 {
+                x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                let (a, b) = ((x >> 8) as usize % n, (x >> 16) as usize % n);
+                let capacity = (x >> 24) as u64 % 10;
+                network.add_edge(a, b, capacity);
+                if a != b {
+                    residual[a][b] += capacity;
+                }
+            }
+            let mut expected = 0;
+            loop {
+                let mut prev = vec![None; n];
+                prev[0] = Some(0);
+                let mut queue = std::collections::VecDeque::from(vec![0]);
+                while let Some(a) = queue.pop_front() {
+                    for b in 0..n {
+                        if residual[a][b] > 0 && prev[b].is_none() {
+                            prev[b] = Some(a);
+                            queue.push_back(b);
+                        }
+                    }
+                }
+                if prev[n - 1].is_none() {
+                    break;
+                }
+                let mut path = vec![n - 1];
+                while *path.last().unwrap() != 0 {
+                    path.push(prev[*path.last().unwrap()].unwrap());
+                }
+                let pushed = path.windows(2).map(|w| residual[w[1]][w[0]]).min().unwrap();
+                for w in path.windows(2) {
+                    residual[w[1]][w[0]] -= pushed;
+                    residual[w[0]][w[1]] += pushed;
+                }
+                expected += pushed;
+            }
+            assert_eq!(network.max_flow(0, n - 1), expected);
+            check(&network, 0, n - 1, expected);
+        }
+    }
+}
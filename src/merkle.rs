@@ -0,0 +1,315 @@
+//! # Merkle tree
+//!
+//! A hash over a list of blocks that can vouch for any one block on its own (Merkle,
+//! 1979). Each block is hashed, then each pair of hashes is hashed together, then each pair
+//! of those, up to a single root:
+//!
+//! ```text
+//!                   root = h(h01, h2)
+//!                  /                \
+//!          h01 = h(h0, h1)           h2            an odd one out goes up
+//!          /            \             |            as it is, unpaired
+//!    h0 = h(a)     h1 = h(b)     h2 = h(c)
+//!        a             b             c
+//! ```
+//!
+//! To show that block `a` is in the list with a given root, it's enough to give the
+//! hashes it's paired with on the way up, here `h1` and `h2`: hashing `a` and then each of
+//! those in turn, on the side it was on, has to come out at the root. That's an
+//! *inclusion proof*, of about log₂ n hashes, and checking it needs nothing but the root.
+//!
+//! Leaves and inner nodes are hashed differently, so that an inner node can never pass
+//! for a block (RFC 6962 does the same). An odd node out at some level isn't paired with
+//! a copy of itself, which would let two different lists share a root, but carried up a
+//! level as it is.
+//!
+//! The hash is a [`Digest`]. Anything that's a `BuildHasher` is one, with 64-bit hashes,
+//! which is fine for spotting accidents. Against someone trying to forge a proof, it wants
+//! a cryptographic hash such as SHA-256 behind a `Digest` of its own.
+
+use std::collections::hash_map::DefaultHasher;
+use std::fmt;
+use std::hash::{BuildHasher, BuildHasherDefault, Hasher};
+
+/// A hash function for a Merkle tree: one way of hashing blocks, another of hashing two
+/// hashes together.
+pub trait Digest {
+    type Output: Clone + Eq + fmt::Debug;
+
+    fn leaf(&self, block: &[u8]) -> Self::Output;
+
+    fn node(&self, left: &Self::Output, right: &Self::Output) -> Self::Output;
+}
+
+impl<S: BuildHasher> Digest for S {
+    type Output = u64;
+
+    fn leaf(&self, block: &[u8]) -> u64 {
+        let mut hasher = self.build_hasher();
+        hasher.write_u8(0);
+        hasher.write(block);
+        hasher.finish()
+    }
+
+    fn node(&self, left: &u64, right: &u64) -> u64 {
+        let mut hasher = self.build_hasher();
+        hasher.write_u8(1);
+        hasher.write_u64(*left);
+        hasher.write_u64(*right);
+        hasher.finish()
+    }
+}
+
+/// The hasher a tree uses unless given one: std's SipHash, with fixed keys so that it
+/// comes out the same every time.
+pub type DefaultDigest = BuildHasherDefault<DefaultHasher>;
+
+/// Which side of the hash so far a proof's hash goes on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+/// The hashes a block is paired with on the way up to the root, from [`MerkleTree::proof`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Proof<O> {
+    pub index: usize,
+    pub path: Vec<(Side, O)>,
+}
+
+impl<O: Clone + Eq> Proof<O> {
+    /// Returns whether `block`, hashed with `digest` and then with the proof's hashes,
+    /// comes out at `root`.
+    pub fn verify<H: Digest<Output = O>>(&self, digest: &H, root: &O, block: &[u8]) -> bool {
+        let mut hash = digest.leaf(block);
+        for (side, sibling) in &self.path {
+            hash = match side {
+                Side::Left => digest.node(sibling, &hash),
+                Side::Right => digest.node(&hash, sibling),
+            };
+        }
+        hash == *root
+    }
+}
+
+pub struct MerkleTree<H: Digest = DefaultDigest> {
+    digest: H,
+    // Every level's hashes, the leaves first and the root last
+    levels: Vec<Vec<H::Output>>,
+}
+
+impl MerkleTree<DefaultDigest> {
+    /// Builds a tree over `blocks`, with the default hash.
+    pub fn new<B: AsRef<[u8]>>(blocks: impl IntoIterator<Item = B>) -> Self {
+        Self::with_digest(blocks, DefaultDigest::default())
+    }
+}
+
+impl<H: Digest> MerkleTree<H> {
+    /// Builds a tree over `blocks` that hashes with `digest`.
+    pub fn with_digest<B: AsRef<[u8]>>(blocks: impl IntoIterator<Item = B>, digest: H) -> Self {
+        let leaves: Vec<_> = blocks
+            .into_iter()
+            .map(|block| digest.leaf(block.as_ref()))
+            .collect();
+        let mut levels = vec![leaves];
+        while levels.last().unwrap().len() > 1 {
+            let level = levels.last().unwrap();
+            let next = level
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => digest.node(left, right),
+                    [odd] => odd.clone(),
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(next);
+        }
+        MerkleTree { digest, levels }
+    }
+
+    pub fn digest(&self) -> &H {
+        &self.digest
+    }
+
+    /// Returns the number of blocks.
+    pub fn len(&self) -> usize {
+        self.levels[0].len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the root hash, or `None` for a tree of no blocks.
+    pub fn root(&self) -> Option<&H::Output> {
+        self.levels.last().unwrap().first()
+    }
+
+    /// Returns the hash of block `index`.
+    pub fn leaf(&self, index: usize) -> Option<&H::Output> {
+        self.levels[0].get(index)
+    }
+
+    /// Returns the proof that block `index` is in the tree.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn proof(&self, index: usize) -> Proof<H::Output> {
+        assert!(
+            index < self.len(),
+            "index {} out of bounds for length {}",
+            index,
+            self.len()
+        );
+        let mut path = Vec::new();
+        let mut at = index;
+        for level in &self.levels[..self.levels.len() - 1] {
+            let sibling = at ^ 1;
+            if sibling < level.len() {
+                let side = if sibling < at {
+                    Side::Left
+                } else {
+                    Side::Right
+                };
+                path.push((side, level[sibling].clone()));
+            }
+            at /= 2;
+        }
+        Proof { index, path }
+    }
+
+    /// Returns whether `proof` shows that `block` is in this tree.
+    pub fn verify(&self, block: &[u8], proof: &Proof<H::Output>) -> bool {
+        match self.root() {
+            Some(root) => proof.verify(&self.digest, root, block),
+            None => false,
+        }
+    }
+}
+
+impl<H: Digest> fmt::Debug for MerkleTree<H> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MerkleTree")
+            .field("len", &self.len())
+            .field("root", &self.root())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Digest, MerkleTree, Proof, Side};
+
+    /// A "hash" that writes out the shape of the tree, to check it by eye.
+    struct Shape;
+
+    impl Digest for Shape {
+        type Output = String;
+
+        fn leaf(&self, block: &[u8]) -> String {
+            String::from_utf8(block.to_vec()).unwrap()
+        }
+
+        fn node(&self, left: &String, right: &String) -> String {
+            format!("({} {})", left, right)
+        }
+    }
+
+    #[test]
+    fn shape() {
+        let tree = MerkleTree::with_digest(["a", "b", "c"], Shape);
+        assert_eq!(tree.root().unwrap(), "((a b) c)");
+        let tree = MerkleTree::with_digest(["a", "b", "c", "d", "e"], Shape);
+        assert_eq!(tree.root().unwrap(), "(((a b) (c d)) e)");
+        assert_eq!(
+            tree.proof(2),
+            Proof {
+                index: 2,
+                path: vec![
+                    (Side::Right, "d".to_string()),
+                    (Side::Left, "(a b)".to_string()),
+                    (Side::Right, "e".to_string()),
+                ],
+            }
+        );
+        // The odd one out skips the levels it has no partner at
+        assert_eq!(
+            tree.proof(4).path,
+            [(Side::Left, "((a b) (c d))".to_string())]
+        );
+    }
+
+    #[test]
+    fn basics() {
+        // Check empty tree behaves right
+        let empty = MerkleTree::new(Vec::<&[u8]>::new());
+        assert!(empty.is_empty());
+        assert_eq!(empty.root(), None);
+
+        // One block is its own root
+        let one = MerkleTree::new([b"only"]);
+        assert_eq!(one.root(), one.leaf(0));
+        assert!(one.proof(0).path.is_empty());
+        assert!(one.verify(b"only", &one.proof(0)));
+        assert!(!empty.verify(b"only", &one.proof(0)));
+
+        let blocks = [&b"alpha"[..], b"beta", b"gamma", b"delta"];
+        let tree = MerkleTree::new(blocks);
+        assert_eq!(tree.len(), 4);
+        for (i, block) in blocks.iter().enumerate() {
+            let proof = tree.proof(i);
+            assert_eq!(proof.path.len(), 2);
+            assert!(tree.verify(block, &proof));
+            // Not for another block
+            assert!(!tree.verify(blocks[(i + 1) % 4], &proof));
+        }
+
+        // The same blocks hash the same, and any change changes the root
+        assert_eq!(MerkleTree::new(blocks).root(), tree.root());
+        let changed = MerkleTree::new([&b"alpha"[..], b"beta", b"gamma", b"delta!"]);
+        assert_ne!(changed.root(), tree.root());
+        assert!(!changed.verify(b"alpha", &tree.proof(0)));
+        let swapped = MerkleTree::new([&b"beta"[..], b"alpha", b"gamma", b"delta"]);
+        assert_ne!(swapped.root(), tree.root());
+    }
+
+    #[test]
+    fn no_second_preimage() {
+        // An inner node's children as one block don't hash to the inner node
+        let tree = MerkleTree::new([b"a", b"b"]);
+        let mut joined = tree.leaf(0).unwrap().to_ne_bytes().to_vec();
+        joined.extend(&tree.leaf(1).unwrap().to_ne_bytes());
+        assert_ne!(MerkleTree::new([joined]).root(), tree.root());
+
+        // Nor does repeating the odd one out give another list the same root
+        let three = MerkleTree::new([b"a", b"b", b"c"]);
+        let four = MerkleTree::new([b"a", b"b", b"c", b"c"]);
+        assert_ne!(three.root(), four.root());
+    }
+
+    #[test]
+    fn every_size() {
+        // Every proof of every tree up to a size verifies, and tampering with it doesn't
+        let n = if cfg!(miri) { 12 } else { 70 };
+        let blocks: Vec<_> = (0..n).map(|i: u32| i.to_le_bytes()).collect();
+        for size in 1..=n as usize {
+            let tree = MerkleTree::new(&blocks[..size]);
+            let depth = (size as f64).log2().ceil() as usize;
+            for (i, block) in blocks[..size].iter().enumerate() {
+                let mut proof = tree.proof(i);
+                assert!(proof.path.len() <= depth);
+                assert!(tree.verify(block, &proof));
+                if let Some((side, _)) = proof.path.first_mut() {
+                    *side = match side {
+                        Side::Left => Side::Right,
+                        Side::Right => Side::Left,
+                    };
+                    assert!(!tree.verify(block, &proof));
+                }
+            }
+        }
+    }
+}
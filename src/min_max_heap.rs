@@ -0,0 +1,370 @@
+//! # Min-max heap
+//!
+//! A double-ended priority queue: the array layout of [`crate::heap::BinaryHeap`], but
+//! with the levels taking turns (Atkinson, Sack, Santoro and Strothotte, 1986). A node on
+//! an even level is the smallest element of its subtree, and a node on an odd level is the
+//! largest:
+//!
+//! ```text
+//!   level 0 (min)                 1
+//!                             /       \
+//!   level 1 (max)           9           8
+//!                         /   \       /   \
+//!   level 2 (min)        3     2     4     5
+//!                       / \
+//!   level 3 (max)      7   6
+//! ```
+//!
+//! So the smallest element is the root and the largest is one of its two children, and
+//! both ends can be peeked at in O(1) and popped in O(log n). Sifting works like it does
+//! in a binary heap, only in steps of two levels, between a node and its grandparent or
+//! grandchildren. A push first checks its parent to see whether it belongs among the min
+//! levels or the max levels, then sifts up through those alone. A pop sifts down the same
+//! way, and at each step checks the level in between that it skipped.
+//!
+//! A bounded collection that must evict from one end while serving the other is what
+//! this is for. To keep the `k` largest of a stream, push each one and pop the minimum once
+//! there are more than `k`, while [`MinMaxHeap::peek_max`] still gives the best so far.
+
+use std::fmt;
+use std::iter::FromIterator;
+
+#[derive(Clone)]
+pub struct MinMaxHeap<T> {
+    data: Vec<T>,
+}
+
+/// Whether slot `i` is on an even level, where each node is the minimum of its subtree.
+fn is_min_level(i: usize) -> bool {
+    (i + 1).ilog2().is_multiple_of(2)
+}
+
+impl<T: Ord> MinMaxHeap<T> {
+    /// Creates an empty MinMaxHeap.
+    pub fn new() -> Self {
+        MinMaxHeap { data: Vec::new() }
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        MinMaxHeap {
+            data: Vec::with_capacity(capacity),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.data.clear();
+    }
+
+    pub fn peek_min(&self) -> Option<&T> {
+        self.data.first()
+    }
+
+    pub fn peek_max(&self) -> Option<&T> {
+        self.max_index().map(|i| &self.data[i])
+    }
+
+    /// Slot of the largest element: the root if it's alone, else its larger child.
+    fn max_index(&self) -> Option<usize> {
+        match self.data.len() {
+            0 => None,
+            1 => Some(0),
+            2 => Some(1),
+            _ if self.data[2] > self.data[1] => Some(2),
+            _ => Some(1),
+        }
+    }
+
+    pub fn push(&mut self, elem: T) {
+        self.data.push(elem);
+        let i = self.data.len() - 1;
+        if i == 0 {
+            return;
+        }
+        let parent = (i - 1) / 2;
+        // On the wrong side of its parent, it belongs to the other kind of level
+        if is_min_level(i) {
+            if self.data[i] > self.data[parent] {
+                self.data.swap(i, parent);
+                self.sift_up(parent, |a, b| a > b);
+            } else {
+                self.sift_up(i, |a, b| a < b);
+            }
+        } else if self.data[i] < self.data[parent] {
+            self.data.swap(i, parent);
+            self.sift_up(parent, |a, b| a < b);
+        } else {
+            self.sift_up(i, |a, b| a > b);
+        }
+    }
+
+    pub fn pop_min(&mut self) -> Option<T> {
+        self.remove_at(0)
+    }
+
+    pub fn pop_max(&mut self) -> Option<T> {
+        let i = self.max_index()?;
+        self.remove_at(i)
+    }
+
+    /// Removes the element at slot `i`, which is 0 or one of its children.
+    fn remove_at(&mut self, i: usize) -> Option<T> {
+        if i >= self.data.len() {
+            return None;
+        }
+        let elem = self.data.swap_remove(i);
+        if i < self.data.len() {
+            self.sift_down(i);
+        }
+        Some(elem)
+    }
+
+    /// Moves the element at `i` up through its grandparents while it comes `first` before
+    /// them.
+    fn sift_up(&mut self, mut i: usize, first: impl Fn(&T, &T) -> bool) {
+        while i >= 3 {
+            let grandparent = ((i - 1) / 2 - 1) / 2;
+            if !first(&self.data[i], &self.data[grandparent]) {
+                break;
+            }
+            self.data.swap(i, grandparent);
+            i = grandparent;
+        }
+    }
+
+    fn sift_down(&mut self, i: usize) {
+        if is_min_level(i) {
+            self.sift_down_by(i, |a, b| a < b);
+        } else {
+            self.sift_down_by(i, |a, b| a > b);
+        }
+    }
+
+    /// Moves the element at `i` down through its grandchildren while one of its children
+    /// or grandchildren comes `first` before it.
+    fn sift_down_by(&mut self, mut i: usize, first: impl Fn(&T, &T) -> bool) {
+        let len = self.data.len();
+        loop {
+            // The best of up to two children and four grandchildren
+            let left = 2 * i + 1;
+            let descendants = [
+                left,
+                left + 1,
+                2 * left + 1,
+                2 * left + 2,
+                2 * left + 3,
+                2 * left + 4,
+            ];
+            let best = descendants
+                .iter()
+                .copied()
+                .filter(|&j| j < len)
+                .reduce(|best, j| {
+                    if first(&self.data[j], &self.data[best]) {
+                        j
+                    } else {
+                        best
+                    }
+                });
+            let m = match best {
+                Some(m) if first(&self.data[m], &self.data[i]) => m,
+                _ => return,
+            };
+
+            self.data.swap(i, m);
+            if m <= left + 1 {
+                // It beat every grandchild, so nothing further down comes before it
+                return;
+            }
+            // The element that came down may be on the wrong side of its new parent
+            let parent = (m - 1) / 2;
+            if first(&self.data[parent], &self.data[m]) {
+                self.data.swap(m, parent);
+            }
+            i = m;
+        }
+    }
+
+    /// Returns the elements in heap order, which is not sorted.
+    pub fn into_vec(self) -> Vec<T> {
+        self.data
+    }
+
+    /// Iterates over the elements in heap order, which is not sorted.
+    pub fn iter(&self) -> std::slice::Iter<'_, T> {
+        self.data.iter()
+    }
+}
+
+impl<T: Ord> Default for MinMaxHeap<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Ord> From<Vec<T>> for MinMaxHeap<T> {
+    /// Heapifies the vector in place, in O(n).
+    fn from(data: Vec<T>) -> Self {
+        let mut heap = MinMaxHeap { data };
+        for i in (0..heap.data.len() / 2).rev() {
+            heap.sift_down(i);
+        }
+        heap
+    }
+}
+
+impl<T: Ord> FromIterator<T> for MinMaxHeap<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        MinMaxHeap::from(iter.into_iter().collect::<Vec<_>>())
+    }
+}
+
+impl<T: Ord> Extend<T> for MinMaxHeap<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for elem in iter {
+            self.push(elem);
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for MinMaxHeap<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.data.iter()).finish()
+    }
+}
+
+impl<'a, T: Ord> IntoIterator for &'a MinMaxHeap<T> {
+    type Item = &'a T;
+    type IntoIter = std::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{is_min_level, MinMaxHeap};
+
+    fn check_heap<T: Ord>(heap: &MinMaxHeap<T>) {
+        for i in 1..heap.data.len() {
+            let mut ancestor = i;
+            while ancestor > 0 {
+                ancestor = (ancestor - 1) / 2;
+                if is_min_level(ancestor) {
+                    assert!(heap.data[ancestor] <= heap.data[i]);
+                } else {
+                    assert!(heap.data[ancestor] >= heap.data[i]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn basics() {
+        let mut heap = MinMaxHeap::new();
+
+        // Check empty heap behaves right
+        assert_eq!(heap.pop_min(), None);
+        assert_eq!(heap.pop_max(), None);
+        assert_eq!(heap.peek_max(), None);
+
+        // Populate heap
+        heap.extend([3, 9, 1, 7, 2, 8, 4, 6, 5]);
+        check_heap(&heap);
+        assert_eq!(heap.len(), 9);
+        assert_eq!(heap.peek_min(), Some(&1));
+        assert_eq!(heap.peek_max(), Some(&9));
+
+        // Check removal from both ends
+        assert_eq!(heap.pop_max(), Some(9));
+        assert_eq!(heap.pop_min(), Some(1));
+        assert_eq!(heap.pop_max(), Some(8));
+        check_heap(&heap);
+
+        // Push some more just to make sure nothing's corrupted
+        heap.push(10);
+        heap.push(0);
+        assert_eq!(heap.pop_max(), Some(10));
+        assert_eq!(heap.pop_min(), Some(0));
+
+        // Check exhaustion
+        assert_eq!(heap.pop_min(), Some(2));
+        assert_eq!(heap.pop_max(), Some(7));
+        assert_eq!(heap.pop_min(), Some(3));
+        assert_eq!(heap.pop_max(), Some(6));
+        assert_eq!(heap.pop_max(), Some(5));
+        assert_eq!(heap.pop_min(), Some(4));
+        assert_eq!(heap.pop_min(), None);
+        assert!(heap.is_empty());
+    }
+
+    #[test]
+    fn top_k() {
+        // Keep the five largest of a stream, evicting the smallest whenever it overflows
+        let mut best = MinMaxHeap::with_capacity(6);
+        let mut x: u32 = 9;
+        let mut all = Vec::new();
+        for _ in 0..1000 {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            all.push(x >> 8);
+            best.push(x >> 8);
+            if best.len() > 5 {
+                best.pop_min();
+            }
+        }
+        all.sort_unstable();
+        assert_eq!(best.peek_max(), all.last());
+        let kept: Vec<_> = std::iter::from_fn(|| best.pop_max()).collect();
+        assert!(kept.iter().eq(all.iter().rev().take(5)));
+    }
+
+    #[test]
+    fn heapify() {
+        let mut x: u32 = 4;
+        for len in 0..100 {
+            let data: Vec<u32> = (0..len)
+                .map(|_| {
+                    x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                    (x >> 16) % 50
+                })
+                .collect();
+            let heap = MinMaxHeap::from(data.clone());
+            check_heap(&heap);
+            assert_eq!(heap.peek_min(), data.iter().min());
+            assert_eq!(heap.peek_max(), data.iter().max());
+        }
+    }
+
+    #[test]
+    fn against_sorted_vec() {
+        let mut heap = MinMaxHeap::new();
+        let mut model: Vec<u32> = Vec::new();
+        let mut x: u32 = 1;
+        for i in 0..10_000 {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            match x >> 30 {
+                0 => assert_eq!(heap.pop_min(), (!model.is_empty()).then(|| model.remove(0))),
+                1 => assert_eq!(heap.pop_max(), model.pop()),
+                _ => {
+                    let elem = (x >> 8) % 1000;
+                    heap.push(elem);
+                    let at = model.partition_point(|&e| e < elem);
+                    model.insert(at, elem);
+                }
+            }
+            assert_eq!(heap.peek_min(), model.first());
+            assert_eq!(heap.peek_max(), model.last());
+            if i % 100 == 0 {
+                check_heap(&heap);
+            }
+        }
+    }
+}
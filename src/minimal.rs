@@ -21,7 +21,7 @@ impl List {
 
     pub fn push(&mut self, elem: i32) {
         let new_node = Box::new(Node {
-            elem: elem,
+            elem,
             next: mem::replace(&mut self.head, Link::Empty),
         });
 
@@ -39,6 +39,12 @@ impl List {
     }
 }
 
+impl Default for List {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// We can't drop the contents of the `Box` _after_ deallocating, so there's no way to drop in a tail-recursive manner.
 /// Instead we're going to have to manually write an iterative drop for `List` that hoists nodes out of their boxes.
 impl Drop for List {
@@ -0,0 +1,296 @@
+//! # Z-order (Morton) index
+//!
+//! Points on a 2D grid of `u32` coordinates, each with a value, kept in one ordered map by
+//! their *Morton code* (Morton, 1966): the bits of `x` and `y` interleaved, `x` in the even
+//! places and `y` in the odd. Sorting by the code walks the grid along a Z-shaped curve,
+//! each square of four, then each square of four of those, and so on, so points near each
+//! other mostly end up near each other in the map:
+//!
+//! ```text
+//!   y
+//!   3 | 10  11  14  15        (2, 1):  x = 10, y = 01
+//!   2 |  8   9  12  13        bits     y1 x1 y0 x0
+//!   1 |  2   3   6   7                  0  1  1  0  = 6
+//!   0 |  0   1   4   5
+//!     +---------------- x
+//!        0   1   2   3
+//! ```
+//!
+//! Any square of side 2^k whose corner is a multiple of 2^k is one unbroken run of codes,
+//! so a rectangle query splits the rectangle into such squares, the largest that fit, like
+//! a quadtree would. Neighbouring squares whose runs meet merge into one interval, and
+//! each interval is a range over the map. A thin rectangle still takes many: about one per
+//! grid row or column it crosses, at worst.
+//!
+//! It's a way of getting a spatial index out of any ordered map, here
+//! `std::collections::BTreeMap`, which is what databases with nothing better do. Compare
+//! [`crate::quadtree`], which splits only where there are points.
+
+use std::collections::BTreeMap;
+use std::iter::FromIterator;
+use std::ops::RangeInclusive;
+
+/// Spreads the bits of `v` out to the even places of a `u64`.
+fn spread(v: u32) -> u64 {
+    let mut v = v as u64;
+    v = (v | v << 16) & 0x0000_ffff_0000_ffff;
+    v = (v | v << 8) & 0x00ff_00ff_00ff_00ff;
+    v = (v | v << 4) & 0x0f0f_0f0f_0f0f_0f0f;
+    v = (v | v << 2) & 0x3333_3333_3333_3333;
+    (v | v << 1) & 0x5555_5555_5555_5555
+}
+
+/// Gathers the bits in the even places of `v` back together.
+fn gather(v: u64) -> u32 {
+    let mut v = v & 0x5555_5555_5555_5555;
+    v = (v | v >> 1) & 0x3333_3333_3333_3333;
+    v = (v | v >> 2) & 0x0f0f_0f0f_0f0f_0f0f;
+    v = (v | v >> 4) & 0x00ff_00ff_00ff_00ff;
+    v = (v | v >> 8) & 0x0000_ffff_0000_ffff;
+    (v | v >> 16) as u32
+}
+
+/// Returns the Morton code of `(x, y)`.
+pub fn encode(x: u32, y: u32) -> u64 {
+    spread(x) | spread(y) << 1
+}
+
+/// Returns the point whose Morton code is `code`.
+pub fn decode(code: u64) -> (u32, u32) {
+    (gather(code), gather(code >> 1))
+}
+
+/// Splits the rectangle from `min` to `max`, both corners included, into the fewest
+/// intervals of Morton codes that cover exactly its points, in order.
+pub fn intervals(min: (u32, u32), max: (u32, u32)) -> Vec<RangeInclusive<u64>> {
+    let mut out: Vec<RangeInclusive<u64>> = Vec::new();
+    if min.0 > max.0 || min.1 > max.1 {
+        return out;
+    }
+    let (min, max) = ((min.0 as u64, min.1 as u64), (max.0 as u64, max.1 as u64));
+    // Squares still to look at: corner and side as a power of two
+    let mut stack = vec![(0u64, 0u64, 32)];
+    while let Some((x, y, level)) = stack.pop() {
+        let side = 1u64 << level;
+        let (far_x, far_y) = (x + side - 1, y + side - 1);
+        if far_x < min.0 || max.0 < x || far_y < min.1 || max.1 < y {
+            continue;
+        }
+        if min.0 <= x && far_x <= max.0 && min.1 <= y && far_y <= max.1 {
+            let start = encode(x as u32, y as u32);
+            let end = start + (((1u128 << (2 * level)) - 1) as u64);
+            match out.last_mut() {
+                Some(last) if *last.end() + 1 == start => *last = *last.start()..=end,
+                _ => out.push(start..=end),
+            }
+            continue;
+        }
+        // Backwards, so that the quarters come off in the order of their codes
+        let half = side / 2;
+        for i in (0..4).rev() {
+            stack.push((x + (i & 1) * half, y + (i >> 1) * half, level - 1));
+        }
+    }
+    out
+}
+
+/// A map from points to values, kept in Z-order.
+pub struct ZOrderMap<T> {
+    map: BTreeMap<u64, T>,
+}
+
+impl<T> ZOrderMap<T> {
+    /// Creates an empty ZOrderMap.
+    pub fn new() -> Self {
+        ZOrderMap {
+            map: BTreeMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.map.clear();
+    }
+
+    /// Sets the value at `(x, y)`, and returns the one that was there.
+    pub fn insert(&mut self, x: u32, y: u32, value: T) -> Option<T> {
+        self.map.insert(encode(x, y), value)
+    }
+
+    pub fn get(&self, x: u32, y: u32) -> Option<&T> {
+        self.map.get(&encode(x, y))
+    }
+
+    pub fn get_mut(&mut self, x: u32, y: u32) -> Option<&mut T> {
+        self.map.get_mut(&encode(x, y))
+    }
+
+    pub fn remove(&mut self, x: u32, y: u32) -> Option<T> {
+        self.map.remove(&encode(x, y))
+    }
+
+    /// Iterates over every point and its value, in Z-order.
+    pub fn iter(&self) -> impl Iterator<Item = ((u32, u32), &T)> + '_ {
+        self.map.iter().map(|(&code, value)| (decode(code), value))
+    }
+
+    /// Iterates over the points from `min` to `max`, both corners included, and their
+    /// values, in Z-order.
+    pub fn range(
+        &self,
+        min: (u32, u32),
+        max: (u32, u32),
+    ) -> impl Iterator<Item = ((u32, u32), &T)> + '_ {
+        intervals(min, max)
+            .into_iter()
+            .flat_map(move |codes| self.map.range(codes))
+            .map(|(&code, value)| (decode(code), value))
+    }
+}
+
+impl<T> Default for ZOrderMap<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> FromIterator<((u32, u32), T)> for ZOrderMap<T> {
+    fn from_iter<I: IntoIterator<Item = ((u32, u32), T)>>(iter: I) -> Self {
+        let mut map = ZOrderMap::new();
+        for ((x, y), value) in iter {
+            map.insert(x, y, value);
+        }
+        map
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{decode, encode, intervals, ZOrderMap};
+
+    #[test]
+    fn codes() {
+        // The grid in the module docs
+        assert_eq!(encode(0, 0), 0);
+        assert_eq!(encode(1, 0), 1);
+        assert_eq!(encode(0, 1), 2);
+        assert_eq!(encode(2, 1), 6);
+        assert_eq!(encode(3, 3), 15);
+        assert_eq!(encode(u32::MAX, u32::MAX), u64::MAX);
+        assert_eq!(encode(u32::MAX, 0), 0x5555_5555_5555_5555);
+
+        let mut x: u32 = 1;
+        for _ in 0..1000 {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let y = x.rotate_left(13) ^ 0x9e37_79b9;
+            assert_eq!(decode(encode(x, y)), (x, y));
+        }
+    }
+
+    #[test]
+    fn decomposition() {
+        // An aligned square is one interval, and so is the whole grid
+        assert_eq!(intervals((2, 0), (3, 1)), [4..=7]);
+        assert_eq!(intervals((0, 0), (u32::MAX, u32::MAX)), [0..=u64::MAX]);
+        assert_eq!(intervals((5, 5), (5, 5)), [51..=51]);
+
+        // The bottom two rows of the docs grid: two squares whose runs meet
+        assert_eq!(intervals((0, 0), (3, 1)), [0..=7]);
+
+        // A column of it doesn't merge
+        assert_eq!(intervals((1, 0), (1, 3)), [1..=1, 3..=3, 9..=9, 11..=11]);
+
+        // Nothing in an empty rectangle
+        assert!(intervals((3, 0), (2, 5)).is_empty());
+    }
+
+    #[test]
+    fn basics() {
+        let mut map = ZOrderMap::new();
+
+        // Check empty map behaves right
+        assert!(map.is_empty());
+        assert_eq!(map.get(0, 0), None);
+        assert_eq!(map.range((0, 0), (9, 9)).count(), 0);
+
+        // Populate map
+        for y in 0..4 {
+            for x in 0..4 {
+                assert_eq!(map.insert(x, y, x + 10 * y), None);
+            }
+        }
+        assert_eq!(map.len(), 16);
+        assert_eq!(map.insert(2, 1, 0), Some(12));
+        *map.get_mut(2, 1).unwrap() = 12;
+        assert_eq!(map.get(3, 2), Some(&23));
+
+        // In Z-order
+        let first: Vec<_> = map.iter().take(5).map(|(p, _)| p).collect();
+        assert_eq!(first, [(0, 0), (1, 0), (0, 1), (1, 1), (2, 0)]);
+        let found: Vec<_> = map.range((1, 1), (2, 2)).map(|(_, &v)| v).collect();
+        assert_eq!(found, [11, 12, 21, 22]);
+
+        // Check normal removal
+        assert_eq!(map.remove(1, 1), Some(11));
+        assert_eq!(map.remove(1, 1), None);
+        assert_eq!(map.range((1, 1), (2, 2)).count(), 3);
+
+        // Check exhaustion
+        map.clear();
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn against_brute_force() {
+        // Random points and rectangles, some near the edges of the grid
+        let mut x: u32 = 1;
+        let mut next = || {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            x >> 8
+        };
+        let n = if cfg!(miri) { 50 } else { 500 };
+        let mut points = Vec::new();
+        for i in 0..n {
+            let (px, py) = (next() % 256, next() % 256);
+            let p = match i % 5 {
+                0 => (u32::MAX - px, py),
+                _ => (px, py),
+            };
+            points.push(p);
+        }
+        let map: ZOrderMap<_> = points.iter().map(|&p| (p, p)).collect();
+        points.sort();
+        points.dedup();
+        assert_eq!(map.len(), points.len());
+
+        for round in 0..100 {
+            let (a, b) = ((next() % 256, next() % 256), (next() % 256, next() % 256));
+            let (mut min, mut max) = ((a.0.min(b.0), a.1.min(b.1)), (a.0.max(b.0), a.1.max(b.1)));
+            if round % 4 == 0 {
+                max.0 = u32::MAX;
+                min.0 = u32::MAX - min.0;
+            }
+            let codes = intervals(min, max);
+            assert!(codes.windows(2).all(|w| w[0].end() + 1 < *w[1].start()));
+
+            let found: Vec<_> = map.range(min, max).map(|(p, &v)| (p, v)).collect();
+            assert!(found.iter().all(|&(p, v)| p == v));
+            assert!(found
+                .windows(2)
+                .all(|w| encode(w[0].0 .0, w[0].0 .1) < encode(w[1].0 .0, w[1].0 .1)));
+            let mut found: Vec<_> = found.into_iter().map(|(p, _)| p).collect();
+            found.sort();
+            let inside =
+                |p: &&(u32, u32)| min.0 <= p.0 && p.0 <= max.0 && min.1 <= p.1 && p.1 <= max.1;
+            let expected: Vec<_> = points.iter().filter(inside).copied().collect();
+            assert_eq!(found, expected);
+        }
+    }
+}
@@ -0,0 +1,262 @@
+//! # Bounded MPMC array queue
+//!
+//! Dmitry Vyukov's bounded multi-producer multi-consumer queue. Everything lives in one array
+//! allocated up front, so pushing and popping never touch the allocator.
+//!
+//! Every slot carries a sequence number that says whose turn it is:
+//!
+//! - `seq == pos`: the slot is empty and waiting for the producer that claims position `pos`.
+//! - `seq == pos + 1`: the slot holds the value written at `pos`, waiting for its consumer.
+//!
+//! A producer reads `enqueue_pos`, checks the slot's sequence, and CASes the position forward
+//! to claim it. After writing it bumps the slot to `pos + 1`. A consumer does the mirror image
+//! on `dequeue_pos`, and after reading it sets the sequence to `pos + capacity`, which is the
+//! position the next producer to land on this slot will have claimed.
+//!
+//! Threads only contend on the position counters; the slot handoff itself is a plain
+//! release/acquire pair on the sequence number.
+
+use std::cell::UnsafeCell;
+use std::mem::MaybeUninit;
+use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+pub struct ArrayQueue<T> {
+    slots: Box<[Slot<T>]>,
+    mask: usize,
+    enqueue_pos: AtomicUsize,
+    dequeue_pos: AtomicUsize,
+}
+
+struct Slot<T> {
+    seq: AtomicUsize,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+impl<T> ArrayQueue<T> {
+    /// Creates a queue holding at least `capacity` elements, rounded up to a power of two,
+    /// and to two at the least: with a single slot, the sequence numbers `pos + 1` for
+    /// "full" and `pos + capacity` for "empty again" would be the same.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero. A capacity of one is rounded up to two.
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "capacity must be at least one");
+        let capacity = capacity.max(2).next_power_of_two();
+        let slots = (0..capacity)
+            .map(|i| Slot {
+                seq: AtomicUsize::new(i),
+                value: UnsafeCell::new(MaybeUninit::uninit()),
+            })
+            .collect();
+
+        ArrayQueue {
+            slots,
+            mask: capacity - 1,
+            enqueue_pos: AtomicUsize::new(0),
+            dequeue_pos: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Pushes an element, or hands it back if the queue is full.
+    pub fn try_push(&self, value: T) -> Result<(), T> {
+        let mut pos = self.enqueue_pos.load(Ordering::Relaxed);
+        loop {
+            let slot = &self.slots[pos & self.mask];
+            let seq = slot.seq.load(Ordering::Acquire);
+            let diff = seq.wrapping_sub(pos) as isize;
+
+            if diff == 0 {
+                // The slot is free for position `pos`, try to claim it
+                match self.enqueue_pos.compare_exchange_weak(
+                    pos,
+                    pos.wrapping_add(1),
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        unsafe { ptr::write(slot.value.get(), MaybeUninit::new(value)) };
+                        slot.seq.store(pos.wrapping_add(1), Ordering::Release);
+                        return Ok(());
+                    }
+                    Err(current) => pos = current,
+                }
+            } else if diff < 0 {
+                // The slot still holds the value from one lap ago: we're full
+                return Err(value);
+            } else {
+                // Another producer got here first, catch up
+                pos = self.enqueue_pos.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Pops the oldest element, if there is one.
+    pub fn try_pop(&self) -> Option<T> {
+        let mut pos = self.dequeue_pos.load(Ordering::Relaxed);
+        loop {
+            let slot = &self.slots[pos & self.mask];
+            let seq = slot.seq.load(Ordering::Acquire);
+            let diff = seq.wrapping_sub(pos.wrapping_add(1)) as isize;
+
+            if diff == 0 {
+                // The slot holds the value for position `pos`, try to claim it
+                match self.dequeue_pos.compare_exchange_weak(
+                    pos,
+                    pos.wrapping_add(1),
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        let value = unsafe { ptr::read(slot.value.get()).assume_init() };
+                        // Hand the slot to whoever claims it on the next lap
+                        slot.seq
+                            .store(pos.wrapping_add(self.mask + 1), Ordering::Release);
+                        return Some(value);
+                    }
+                    Err(current) => pos = current,
+                }
+            } else if diff < 0 {
+                // Nothing has been written here yet: we're empty
+                return None;
+            } else {
+                pos = self.dequeue_pos.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Returns roughly how many elements are queued. Exact when no one else is pushing or
+    /// popping.
+    pub fn len(&self) -> usize {
+        let tail = self.enqueue_pos.load(Ordering::SeqCst);
+        let head = self.dequeue_pos.load(Ordering::SeqCst);
+        tail.wrapping_sub(head).min(self.capacity())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T> Drop for ArrayQueue<T> {
+    fn drop(&mut self) {
+        while self.try_pop().is_some() {}
+    }
+}
+
+unsafe impl<T: Send> Send for ArrayQueue<T> {}
+unsafe impl<T: Send> Sync for ArrayQueue<T> {}
+
+#[cfg(test)]
+mod test {
+    use super::ArrayQueue;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn basics() {
+        let queue = ArrayQueue::new(3);
+        assert_eq!(queue.capacity(), 4);
+        assert_eq!(queue.try_pop(), None);
+        assert!(queue.is_empty());
+
+        for i in 0..4 {
+            assert_eq!(queue.try_push(i), Ok(()));
+        }
+        assert_eq!(queue.try_push(4), Err(4));
+        assert_eq!(queue.len(), 4);
+
+        assert_eq!(queue.try_pop(), Some(0));
+        assert_eq!(queue.try_push(4), Ok(()));
+
+        // Several laps around the buffer
+        for i in 1..100 {
+            assert_eq!(queue.try_pop(), Some(i));
+            assert_eq!(queue.try_push(i + 4), Ok(()));
+        }
+        assert_eq!(queue.len(), 4);
+    }
+
+    #[test]
+    fn capacity_one() {
+        // Rounded up to two slots, so a second value can't overwrite the first
+        let queue = ArrayQueue::new(1);
+        assert_eq!(queue.capacity(), 2);
+        assert_eq!(queue.try_push(1), Ok(()));
+        assert_eq!(queue.try_push(2), Ok(()));
+        assert_eq!(queue.try_push(3), Err(3));
+        assert_eq!(queue.try_pop(), Some(1));
+        assert_eq!(queue.try_pop(), Some(2));
+        assert_eq!(queue.try_pop(), None);
+    }
+
+    #[test]
+    fn drops_remaining() {
+        let value = Arc::new(());
+        let queue = ArrayQueue::new(8);
+        for _ in 0..5 {
+            queue.try_push(value.clone()).unwrap();
+        }
+        drop(queue.try_pop());
+        assert_eq!(Arc::strong_count(&value), 5);
+
+        drop(queue);
+        assert_eq!(Arc::strong_count(&value), 1);
+    }
+
+    #[test]
+    fn mpmc() {
+        let per_thread = if cfg!(miri) { 50 } else { 10_000 };
+        let producers = 4;
+        let consumers = 4;
+        let total = producers * per_thread;
+
+        let queue = Arc::new(ArrayQueue::new(16));
+        let received = Arc::new(AtomicUsize::new(0));
+        let sum = Arc::new(AtomicUsize::new(0));
+
+        let mut handles = Vec::new();
+        for p in 0..producers {
+            let queue = queue.clone();
+            handles.push(thread::spawn(move || {
+                for i in 0..per_thread {
+                    let mut value = p * per_thread + i;
+                    // Spin until there's room
+                    while let Err(back) = queue.try_push(value) {
+                        value = back;
+                        thread::yield_now();
+                    }
+                }
+            }));
+        }
+        for _ in 0..consumers {
+            let queue = queue.clone();
+            let received = received.clone();
+            let sum = sum.clone();
+            handles.push(thread::spawn(move || {
+                while received.load(Ordering::SeqCst) < total {
+                    match queue.try_pop() {
+                        Some(value) => {
+                            sum.fetch_add(value, Ordering::SeqCst);
+                            received.fetch_add(1, Ordering::SeqCst);
+                        }
+                        None => thread::yield_now(),
+                    }
+                }
+            }));
+        }
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(received.load(Ordering::SeqCst), total);
+        assert_eq!(sum.load(Ordering::SeqCst), total * (total - 1) / 2);
+        assert!(queue.is_empty());
+    }
+}
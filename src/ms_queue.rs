@@ -0,0 +1,465 @@
+//! # Michael–Scott lock-free queue
+//!
+//! The classic multi-producer multi-consumer queue from Michael and Scott's 1996 paper. It's
+//! a singly-linked list with two atomic pointers: producers swing `tail` forward with CAS and
+//! consumers swing `head` forward with CAS, so neither side ever takes a lock.
+//!
+//! The list always starts with a dummy node. `head` points at the dummy, and the first real
+//! element lives in the node after it. Dequeuing moves the value out of that second node and
+//! makes it the new dummy, which means `head` and `tail` never have to be updated together.
+//!
+//! ```text
+//! head                    tail
+//!  |                       |
+//!  v                       v
+//! [dummy] -> [a] -> [b] -> [c] -> null
+//! ```
+//!
+//! `tail` is allowed to lag one node behind the real end. Anyone who notices the lag helps
+//! push it forward before carrying on, which is what makes the queue lock-free rather than
+//! merely non-blocking for the lucky thread.
+//!
+//! ## Reclamation
+//!
+//! Once a dequeuer has swung `head` past the old dummy, other threads may still be halfway
+//! through reading it, so we can't free it on the spot. Every operation runs pinned, and the
+//! old dummy is handed to the crate's epoch collector (see [`crate::epoch`]), which frees it
+//! once every thread that might have seen it has unpinned.
+//!
+//! `HazardQueue` is the same algorithm reclaiming through [`crate::hazard`] instead, so the
+//! two strategies can be compared side by side. Each operation protects the node it's about
+//! to read (and, when dequeuing, the one after it) and re-checks that it's still linked
+//! before touching it.
+
+use crate::epoch;
+use crate::hazard::{self, HazardPointer};
+use std::mem::MaybeUninit;
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, Ordering::SeqCst};
+
+pub struct Queue<T> {
+    head: AtomicPtr<Node<T>>,
+    tail: AtomicPtr<Node<T>>,
+}
+
+struct Node<T> {
+    // Uninitialized in the dummy node, and again once a dequeuer has moved the value out
+    value: MaybeUninit<T>,
+    next: AtomicPtr<Node<T>>,
+}
+
+/// The same queue, reclaiming nodes with hazard pointers rather than epochs.
+pub struct HazardQueue<T> {
+    head: AtomicPtr<Node<T>>,
+    tail: AtomicPtr<Node<T>>,
+}
+
+impl<T> Node<T> {
+    fn new(value: MaybeUninit<T>) -> *mut Self {
+        Box::into_raw(Box::new(Node {
+            value,
+            next: AtomicPtr::new(ptr::null_mut()),
+        }))
+    }
+}
+
+impl<T> Queue<T> {
+    /// Creates an empty Queue.
+    pub fn new() -> Self {
+        let dummy = Node::new(MaybeUninit::uninit());
+        Queue {
+            head: AtomicPtr::new(dummy),
+            tail: AtomicPtr::new(dummy),
+        }
+    }
+
+    /// Adds an element at the back of the queue.
+    pub fn enqueue(&self, value: T) {
+        let node = Node::new(MaybeUninit::new(value));
+        let _guard = epoch::pin();
+
+        loop {
+            let tail = self.tail.load(SeqCst);
+            let next = unsafe { (*tail).next.load(SeqCst) };
+
+            // Make sure tail didn't move while we were reading its next
+            if tail != self.tail.load(SeqCst) {
+                continue;
+            }
+
+            if next.is_null() {
+                // We really are at the end: try to link our node in
+                let linked = unsafe {
+                    (*tail)
+                        .next
+                        .compare_exchange(ptr::null_mut(), node, SeqCst, SeqCst)
+                        .is_ok()
+                };
+                if linked {
+                    // Swing tail to our node. If this fails someone already helped us.
+                    let _ = self.tail.compare_exchange(tail, node, SeqCst, SeqCst);
+                    return;
+                }
+            } else {
+                // Tail is lagging behind, help it along and retry
+                let _ = self.tail.compare_exchange(tail, next, SeqCst, SeqCst);
+            }
+        }
+    }
+
+    /// Removes and returns the element at the front of the queue.
+    pub fn dequeue(&self) -> Option<T> {
+        let guard = epoch::pin();
+
+        loop {
+            let head = self.head.load(SeqCst);
+            let tail = self.tail.load(SeqCst);
+            let next = unsafe { (*head).next.load(SeqCst) };
+
+            if head != self.head.load(SeqCst) {
+                continue;
+            }
+
+            if head == tail {
+                if next.is_null() {
+                    return None;
+                }
+                // Something was enqueued but tail hasn't caught up yet
+                let _ = self.tail.compare_exchange(tail, next, SeqCst, SeqCst);
+            } else if self
+                .head
+                .compare_exchange(head, next, SeqCst, SeqCst)
+                .is_ok()
+            {
+                // We won `next`: its value is ours to move out, and it's the new dummy
+                let value = unsafe { ptr::read((*next).value.as_ptr()) };
+                // The old dummy's value is long gone, so freeing it later drops nothing
+                unsafe { guard.defer_destroy(head) };
+                return Some(value);
+            }
+        }
+    }
+
+    /// Returns true if the queue had no elements at the moment we looked.
+    pub fn is_empty(&self) -> bool {
+        let _guard = epoch::pin();
+        let head = self.head.load(SeqCst);
+        unsafe { (*head).next.load(SeqCst).is_null() }
+    }
+}
+
+impl<T> Default for Queue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for Queue<T> {
+    fn drop(&mut self) {
+        // `&mut self` means nobody else is around, so the last dummy can go right away
+        while self.dequeue().is_some() {}
+        unsafe { drop(Box::from_raw(*self.head.get_mut())) };
+    }
+}
+
+unsafe impl<T: Send> Send for Queue<T> {}
+unsafe impl<T: Send> Sync for Queue<T> {}
+
+impl<T> HazardQueue<T> {
+    /// Creates an empty HazardQueue.
+    pub fn new() -> Self {
+        let dummy = Node::new(MaybeUninit::uninit());
+        HazardQueue {
+            head: AtomicPtr::new(dummy),
+            tail: AtomicPtr::new(dummy),
+        }
+    }
+
+    /// Adds an element at the back of the queue.
+    pub fn enqueue(&self, value: T) {
+        let node = Node::new(MaybeUninit::new(value));
+        let mut hazard = HazardPointer::new();
+
+        loop {
+            // Protected, so it can't be freed under us even if a dequeuer unlinks it
+            let tail = hazard.protect(&self.tail);
+            let next = unsafe { (*tail).next.load(SeqCst) };
+
+            if next.is_null() {
+                let linked = unsafe {
+                    (*tail)
+                        .next
+                        .compare_exchange(ptr::null_mut(), node, SeqCst, SeqCst)
+                        .is_ok()
+                };
+                if linked {
+                    let _ = self.tail.compare_exchange(tail, node, SeqCst, SeqCst);
+                    return;
+                }
+            } else {
+                let _ = self.tail.compare_exchange(tail, next, SeqCst, SeqCst);
+            }
+        }
+    }
+
+    /// Removes and returns the element at the front of the queue.
+    pub fn dequeue(&self) -> Option<T> {
+        let mut hazard_head = HazardPointer::new();
+        let mut hazard_next = HazardPointer::new();
+
+        loop {
+            let head = hazard_head.protect(&self.head);
+            let tail = self.tail.load(SeqCst);
+            let next = unsafe { (*head).next.load(SeqCst) };
+
+            // `next` is only safe to read if it was still linked after we published it, and
+            // it was as long as `head` hasn't moved
+            hazard_next.set(next);
+            if head != self.head.load(SeqCst) {
+                continue;
+            }
+
+            if head == tail {
+                if next.is_null() {
+                    return None;
+                }
+                let _ = self.tail.compare_exchange(tail, next, SeqCst, SeqCst);
+            } else if self
+                .head
+                .compare_exchange(head, next, SeqCst, SeqCst)
+                .is_ok()
+            {
+                let value = unsafe { ptr::read((*next).value.as_ptr()) };
+                unsafe { hazard::retire(head) };
+                return Some(value);
+            }
+        }
+    }
+
+    /// Returns true if the queue had no elements at the moment we looked.
+    pub fn is_empty(&self) -> bool {
+        let mut hazard = HazardPointer::new();
+        let head = hazard.protect(&self.head);
+        unsafe { (*head).next.load(SeqCst).is_null() }
+    }
+}
+
+impl<T> Default for HazardQueue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for HazardQueue<T> {
+    fn drop(&mut self) {
+        while self.dequeue().is_some() {}
+        unsafe { drop(Box::from_raw(*self.head.get_mut())) };
+    }
+}
+
+unsafe impl<T: Send> Send for HazardQueue<T> {}
+unsafe impl<T: Send> Sync for HazardQueue<T> {}
+
+#[cfg(test)]
+mod test {
+    use super::{HazardQueue, Queue};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn basics() {
+        let queue = Queue::new();
+
+        // Check empty queue behaves right
+        assert_eq!(queue.dequeue(), None);
+        assert!(queue.is_empty());
+
+        // Populate queue
+        queue.enqueue(1);
+        queue.enqueue(2);
+        queue.enqueue(3);
+        assert!(!queue.is_empty());
+
+        // Check normal removal
+        assert_eq!(queue.dequeue(), Some(1));
+        assert_eq!(queue.dequeue(), Some(2));
+
+        // Push some more just to make sure nothing's corrupted
+        queue.enqueue(4);
+        queue.enqueue(5);
+
+        // Check normal removal
+        assert_eq!(queue.dequeue(), Some(3));
+        assert_eq!(queue.dequeue(), Some(4));
+
+        // Check exhaustion
+        assert_eq!(queue.dequeue(), Some(5));
+        assert_eq!(queue.dequeue(), None);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn drops_every_value_once() {
+        struct Counted(Arc<AtomicUsize>);
+        impl Drop for Counted {
+            fn drop(&mut self) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let drops = Arc::new(AtomicUsize::new(0));
+        let queue = Queue::new();
+        for _ in 0..10 {
+            queue.enqueue(Counted(drops.clone()));
+        }
+        for _ in 0..4 {
+            drop(queue.dequeue());
+        }
+        assert_eq!(drops.load(Ordering::SeqCst), 4);
+
+        // The rest are dropped with the queue
+        drop(queue);
+        assert_eq!(drops.load(Ordering::SeqCst), 10);
+    }
+
+    #[test]
+    fn mpmc() {
+        let per_thread = if cfg!(miri) { 50 } else { 10_000 };
+        let producers = 4;
+        let consumers = 4;
+
+        let queue = Arc::new(Queue::new());
+        let received = Arc::new(AtomicUsize::new(0));
+        let sum = Arc::new(AtomicUsize::new(0));
+
+        let mut handles = Vec::new();
+        for p in 0..producers {
+            let queue = queue.clone();
+            handles.push(thread::spawn(move || {
+                for i in 0..per_thread {
+                    queue.enqueue(p * per_thread + i);
+                }
+            }));
+        }
+        for _ in 0..consumers {
+            let queue = queue.clone();
+            let received = received.clone();
+            let sum = sum.clone();
+            handles.push(thread::spawn(move || {
+                while received.load(Ordering::SeqCst) < producers * per_thread {
+                    if let Some(value) = queue.dequeue() {
+                        sum.fetch_add(value, Ordering::SeqCst);
+                        received.fetch_add(1, Ordering::SeqCst);
+                    }
+                }
+            }));
+        }
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        // Every value came out exactly once
+        let n = producers * per_thread;
+        assert_eq!(received.load(Ordering::SeqCst), n);
+        assert_eq!(sum.load(Ordering::SeqCst), n * (n - 1) / 2);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn fifo_per_producer() {
+        let per_thread = if cfg!(miri) { 50 } else { 10_000 };
+        let queue = Arc::new(Queue::new());
+
+        let handles: Vec<_> = (0..2)
+            .map(|p| {
+                let queue = queue.clone();
+                thread::spawn(move || {
+                    for i in 0..per_thread {
+                        queue.enqueue((p, i));
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        // Each producer's values come out in the order they went in
+        let mut last = [None, None];
+        while let Some((p, i)) = queue.dequeue() {
+            assert!(last[p].is_none_or(|prev| prev < i));
+            last[p] = Some(i);
+        }
+        assert_eq!(last, [Some(per_thread - 1), Some(per_thread - 1)]);
+    }
+
+    #[test]
+    fn hazard_basics() {
+        let queue = HazardQueue::new();
+        assert_eq!(queue.dequeue(), None);
+        assert!(queue.is_empty());
+
+        queue.enqueue(1);
+        queue.enqueue(2);
+        assert_eq!(queue.dequeue(), Some(1));
+        queue.enqueue(3);
+        assert_eq!(queue.dequeue(), Some(2));
+        assert_eq!(queue.dequeue(), Some(3));
+        assert_eq!(queue.dequeue(), None);
+        assert!(queue.is_empty());
+
+        // Whatever's left is dropped with the queue
+        let value = Arc::new(());
+        let queue = HazardQueue::new();
+        queue.enqueue(value.clone());
+        queue.enqueue(value.clone());
+        drop(queue);
+        assert_eq!(Arc::strong_count(&value), 1);
+    }
+
+    #[test]
+    fn hazard_mpmc() {
+        let per_thread = if cfg!(miri) { 50 } else { 10_000 };
+        let producers = 4;
+        let consumers = 4;
+        let n = producers * per_thread;
+
+        let queue = Arc::new(HazardQueue::new());
+        let received = Arc::new(AtomicUsize::new(0));
+        let sum = Arc::new(AtomicUsize::new(0));
+
+        let mut handles = Vec::new();
+        for p in 0..producers {
+            let queue = queue.clone();
+            handles.push(thread::spawn(move || {
+                for i in 0..per_thread {
+                    queue.enqueue(p * per_thread + i);
+                }
+            }));
+        }
+        for _ in 0..consumers {
+            let queue = queue.clone();
+            let received = received.clone();
+            let sum = sum.clone();
+            handles.push(thread::spawn(move || {
+                while received.load(Ordering::SeqCst) < n {
+                    match queue.dequeue() {
+                        Some(value) => {
+                            sum.fetch_add(value, Ordering::SeqCst);
+                            received.fetch_add(1, Ordering::SeqCst);
+                        }
+                        None => thread::yield_now(),
+                    }
+                }
+            }));
+        }
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(received.load(Ordering::SeqCst), n);
+        assert_eq!(sum.load(Ordering::SeqCst), n * (n - 1) / 2);
+        assert!(queue.is_empty());
+    }
+}
@@ -0,0 +1,314 @@
+//! # Multimap
+//!
+//! A map that holds any number of values under each key, in the order they went in. It's a
+//! [`RobinHoodMap`] from each key to a `Vec` of its values, with the bookkeeping to keep
+//! that honest: a key is in the map exactly while it has at least one value, so there are
+//! never empty lists to trip over, and `len` counts values rather than keys.
+//!
+//! ```text
+//!   insert(a, 1), insert(b, 2), insert(a, 3)
+//!
+//!   a -> [1, 3]
+//!   b -> [2]          len 3, key_count 2
+//! ```
+//!
+//! `iter` yields every key-value pair, one key's values together; `groups` yields each key
+//! once with all its values as a slice.
+
+use crate::robin_hood::RobinHoodMap;
+use std::borrow::Borrow;
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+use std::iter::FromIterator;
+
+#[derive(Clone)]
+pub struct MultiMap<K, V, S = RandomState> {
+    map: RobinHoodMap<K, Vec<V>, S>,
+    // Values, not keys
+    len: usize,
+}
+
+impl<K, V> MultiMap<K, V, RandomState> {
+    /// Creates an empty MultiMap.
+    pub fn new() -> Self {
+        Self::with_hasher(RandomState::new())
+    }
+}
+
+impl<K, V, S> MultiMap<K, V, S> {
+    /// Creates an empty multimap that hashes keys with `hasher`.
+    pub fn with_hasher(hasher: S) -> Self {
+        MultiMap {
+            map: RobinHoodMap::with_hasher(hasher),
+            len: 0,
+        }
+    }
+
+    /// Returns the number of values, over all keys.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of distinct keys.
+    pub fn key_count(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.map.clear();
+        self.len = 0;
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.map.keys()
+    }
+
+    /// Iterates over each key once, with all its values in the order they went in.
+    pub fn groups(&self) -> impl Iterator<Item = (&K, &[V])> {
+        self.map
+            .iter()
+            .map(|(key, values)| (key, values.as_slice()))
+    }
+
+    /// Iterates over every key-value pair. The values under one key come out together,
+    /// in the order they went in.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.map
+            .iter()
+            .flat_map(|(key, values)| values.iter().map(move |value| (key, value)))
+    }
+}
+
+impl<K, V, S> MultiMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    /// Adds a value under `key`, after any already there.
+    pub fn insert(&mut self, key: K, value: V) {
+        self.map.entry(key).or_default().push(value);
+        self.len += 1;
+    }
+
+    /// Iterates over the values under `key`, oldest first. Empty if there are none.
+    pub fn get_all<Q>(&self, key: &Q) -> impl Iterator<Item = &V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.map.get(key).into_iter().flatten()
+    }
+
+    /// Returns the oldest value under `key`.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.map.get(key).and_then(|values| values.first())
+    }
+
+    /// Returns how many values are under `key`.
+    pub fn count<Q>(&self, key: &Q) -> usize
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.map.get(key).map_or(0, Vec::len)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.map.contains_key(key)
+    }
+
+    /// Removes the oldest value under `key` equal to `value`, keeping the order of the
+    /// rest. Returns whether there was one.
+    pub fn remove_one<Q>(&mut self, key: &Q, value: &V) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+        V: PartialEq,
+    {
+        let values = match self.map.get_mut(key) {
+            Some(values) => values,
+            None => return false,
+        };
+        let at = match values.iter().position(|v| v == value) {
+            Some(at) => at,
+            None => return false,
+        };
+        values.remove(at);
+        if values.is_empty() {
+            self.map.remove(key);
+        }
+        self.len -= 1;
+        true
+    }
+
+    /// Removes `key` and returns all its values, oldest first. Empty if there were none.
+    pub fn remove_all<Q>(&mut self, key: &Q) -> Vec<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let values = self.map.remove(key).unwrap_or_default();
+        self.len -= values.len();
+        values
+    }
+
+    /// Keeps only the pairs for which `f` returns true, dropping keys left with no values.
+    pub fn retain(&mut self, mut f: impl FnMut(&K, &V) -> bool) {
+        let mut len = 0;
+        self.map.retain(|key, values| {
+            values.retain(|value| f(key, value));
+            len += values.len();
+            !values.is_empty()
+        });
+        self.len = len;
+    }
+}
+
+impl<K, V, S: Default> Default for MultiMap<K, V, S> {
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug, S> fmt::Debug for MultiMap<K, V, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.groups()).finish()
+    }
+}
+
+impl<K, V, S> Extend<(K, V)> for MultiMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (key, value) in iter {
+            self.insert(key, value);
+        }
+    }
+}
+
+impl<K, V, S> FromIterator<(K, V)> for MultiMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher + Default,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = Self::default();
+        map.extend(iter);
+        map
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::MultiMap;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn basics() {
+        let mut map = MultiMap::new();
+
+        // Check empty multimap behaves right
+        assert_eq!(map.get_all("a").count(), 0);
+        assert_eq!(map.get("a"), None);
+        assert!(!map.remove_one("a", &1));
+        assert!(map.remove_all("a").is_empty());
+
+        // Populate multimap
+        map.insert("a".to_string(), 1);
+        map.insert("b".to_string(), 2);
+        map.insert("a".to_string(), 3);
+        map.insert("a".to_string(), 1);
+        assert_eq!((map.len(), map.key_count()), (4, 2));
+        assert_eq!(map.get_all("a").collect::<Vec<_>>(), [&1, &3, &1]);
+        assert_eq!(map.get("a"), Some(&1));
+        assert_eq!(map.count("a"), 3);
+
+        // Check normal removal
+        assert!(map.remove_one("a", &1));
+        assert!(!map.remove_one("a", &7));
+        assert_eq!(map.get_all("a").collect::<Vec<_>>(), [&3, &1]);
+        // Taking a key's last value takes the key too
+        assert!(map.remove_one("b", &2));
+        assert!(!map.contains_key("b"));
+        assert_eq!(map.len(), 2);
+        assert_eq!(format!("{:?}", map), r#"{"a": [3, 1]}"#);
+
+        // Push some more just to make sure nothing's corrupted
+        map.insert("b".to_string(), 4);
+        let mut pairs: Vec<_> = map.iter().map(|(k, &v)| (k.as_str(), v)).collect();
+        pairs.sort_unstable();
+        assert_eq!(pairs, [("a", 1), ("a", 3), ("b", 4)]);
+
+        // Check exhaustion
+        assert_eq!(map.remove_all("a"), [3, 1]);
+        assert_eq!(map.remove_all("b"), [4]);
+        assert!(map.is_empty());
+        assert_eq!(map.key_count(), 0);
+    }
+
+    #[test]
+    fn retain() {
+        let mut map: MultiMap<u32, u32> = (0..30).map(|i| (i % 3, i)).collect();
+        map.retain(|&k, &v| k != 0 && v % 2 == 0);
+        assert_eq!((map.len(), map.key_count()), (10, 2));
+        assert!(!map.contains_key(&0));
+        assert_eq!(
+            map.get_all(&1).collect::<Vec<_>>(),
+            [&4, &10, &16, &22, &28]
+        );
+    }
+
+    #[test]
+    fn against_btree_map() {
+        let mut map = MultiMap::new();
+        let mut model: BTreeMap<u32, Vec<u32>> = BTreeMap::new();
+        let n = if cfg!(miri) { 1_000 } else { 20_000 };
+        let mut x: u32 = 1;
+        for _ in 0..n {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let key = (x >> 8) % 50;
+            let value = (x >> 16) % 4;
+            match x >> 30 {
+                0 => {
+                    let values = model.get_mut(&key);
+                    let at = values
+                        .as_ref()
+                        .and_then(|values| values.iter().position(|&v| v == value));
+                    if let (Some(values), Some(at)) = (values, at) {
+                        values.remove(at);
+                    }
+                    model.retain(|_, values| !values.is_empty());
+                    assert_eq!(map.remove_one(&key, &value), at.is_some());
+                }
+                1 if value == 0 => {
+                    let expected = model.remove(&key).unwrap_or_default();
+                    assert_eq!(map.remove_all(&key), expected);
+                }
+                _ => {
+                    model.entry(key).or_default().push(value);
+                    map.insert(key, value);
+                }
+            }
+            assert_eq!(map.len(), model.values().map(Vec::len).sum::<usize>());
+            assert_eq!(map.key_count(), model.len());
+        }
+        let mut groups: Vec<_> = map.groups().map(|(&k, vs)| (k, vs.to_vec())).collect();
+        groups.sort_unstable();
+        assert_eq!(groups, model.into_iter().collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,535 @@
+//! # Order-statistics tree
+//!
+//! An ordered map that can also answer questions by *position*: what's the k-th smallest
+//! key, and how many keys are smaller than this one? It's the [`crate::avl`] tree with one
+//! more field per node, the size of its subtree. Sizes are cheap to keep right: a node's
+//! size is one plus the sizes of its children, recomputed wherever a height is, rotations
+//! included.
+//!
+//! With sizes, finding the k-th key is a single walk down. Compare k with the size of the
+//! left subtree: smaller means go left, equal means this node, larger means go right
+//! looking for the `k - left - 1`-th key there. Rank runs the same walk in reverse, adding
+//! up the left sizes it skips.
+//!
+//! ```text
+//!            (7) 40             select(4): left of 40 has 3 -> go right, k = 0
+//!           /      \            left of 60 has 1 -> go left, k = 0
+//!      (3) 20      (3) 60       50 has no left -> it's 50
+//!      /   \       /   \
+//!   (1)10 (1)30 (1)50 (1)70     rank(50) = 3 + 1 + 0 = 4
+//! ```
+//!
+//! All of it is O(log n). That makes the tree handy for keeping a running median or
+//! percentile over values that come and go, which a heap can't do once values need removing.
+
+use std::borrow::Borrow;
+use std::cmp::{self, Ordering};
+use std::fmt;
+use std::iter::FromIterator;
+use std::mem;
+
+pub struct Map<K, V> {
+    root: Link<K, V>,
+}
+
+type Link<K, V> = Option<Box<Node<K, V>>>;
+
+struct Node<K, V> {
+    key: K,
+    value: V,
+    height: usize,
+    // Number of nodes in the subtree rooted here
+    size: usize,
+    left: Link<K, V>,
+    right: Link<K, V>,
+}
+
+fn height<K, V>(link: &Link<K, V>) -> usize {
+    link.as_ref().map_or(0, |node| node.height)
+}
+
+fn size<K, V>(link: &Link<K, V>) -> usize {
+    link.as_ref().map_or(0, |node| node.size)
+}
+
+impl<K, V> Node<K, V> {
+    fn update(&mut self) {
+        self.height = 1 + cmp::max(height(&self.left), height(&self.right));
+        self.size = 1 + size(&self.left) + size(&self.right);
+    }
+
+    fn balance_factor(&self) -> isize {
+        height(&self.left) as isize - height(&self.right) as isize
+    }
+}
+
+fn rotate_right<K, V>(mut node: Box<Node<K, V>>) -> Box<Node<K, V>> {
+    let mut left = node.left.take().expect("rotate_right needs a left child");
+    node.left = left.right.take();
+    node.update();
+    left.right = Some(node);
+    left.update();
+    left
+}
+
+fn rotate_left<K, V>(mut node: Box<Node<K, V>>) -> Box<Node<K, V>> {
+    let mut right = node.right.take().expect("rotate_left needs a right child");
+    node.right = right.left.take();
+    node.update();
+    right.left = Some(node);
+    right.update();
+    right
+}
+
+fn rebalance<K, V>(mut node: Box<Node<K, V>>) -> Box<Node<K, V>> {
+    node.update();
+    let balance = node.balance_factor();
+    if balance > 1 {
+        if node.left.as_ref().unwrap().balance_factor() < 0 {
+            node.left = node.left.take().map(rotate_left);
+        }
+        rotate_right(node)
+    } else if balance < -1 {
+        if node.right.as_ref().unwrap().balance_factor() > 0 {
+            node.right = node.right.take().map(rotate_right);
+        }
+        rotate_left(node)
+    } else {
+        node
+    }
+}
+
+fn insert<K: Ord, V>(link: &mut Link<K, V>, key: K, value: V) -> Option<V> {
+    let node = match link {
+        Some(node) => node,
+        None => {
+            *link = Some(Box::new(Node {
+                key,
+                value,
+                height: 1,
+                size: 1,
+                left: None,
+                right: None,
+            }));
+            return None;
+        }
+    };
+    let old = match key.cmp(&node.key) {
+        Ordering::Less => insert(&mut node.left, key, value),
+        Ordering::Greater => insert(&mut node.right, key, value),
+        Ordering::Equal => return Some(mem::replace(&mut node.value, value)),
+    };
+    *link = link.take().map(rebalance);
+    old
+}
+
+/// Takes the node at `link` out of the tree, putting its successor in its place.
+fn unlink<K, V>(link: &mut Link<K, V>) -> (K, V) {
+    let mut node = link.take().unwrap();
+    *link = match (node.left.take(), node.right.take()) {
+        (None, right) => right,
+        (left, None) => left,
+        (Some(left), Some(right)) => {
+            let (mut successor, rest) = take_min(right);
+            successor.left = Some(left);
+            successor.right = rest;
+            Some(rebalance(successor))
+        }
+    };
+    (node.key, node.value)
+}
+
+fn take_min<K, V>(mut node: Box<Node<K, V>>) -> (Box<Node<K, V>>, Link<K, V>) {
+    match node.left.take() {
+        None => {
+            let rest = node.right.take();
+            (node, rest)
+        }
+        Some(left) => {
+            let (min, rest) = take_min(left);
+            node.left = rest;
+            (min, Some(rebalance(node)))
+        }
+    }
+}
+
+fn remove<K, V, Q>(link: &mut Link<K, V>, key: &Q) -> Option<(K, V)>
+where
+    K: Borrow<Q>,
+    Q: Ord + ?Sized,
+{
+    let node = link.as_mut()?;
+    let removed = match key.cmp(node.key.borrow()) {
+        Ordering::Less => remove(&mut node.left, key),
+        Ordering::Greater => remove(&mut node.right, key),
+        Ordering::Equal => return Some(unlink(link)),
+    };
+    if removed.is_some() {
+        *link = link.take().map(rebalance);
+    }
+    removed
+}
+
+/// Removes the `k`-th node of the subtree, which has to exist.
+fn remove_at<K, V>(link: &mut Link<K, V>, k: usize) -> (K, V) {
+    let node = link.as_mut().unwrap();
+    let left = size(&node.left);
+    let removed = match k.cmp(&left) {
+        Ordering::Less => remove_at(&mut node.left, k),
+        Ordering::Greater => remove_at(&mut node.right, k - left - 1),
+        Ordering::Equal => return unlink(link),
+    };
+    *link = link.take().map(rebalance);
+    removed
+}
+
+impl<K, V> Map<K, V> {
+    /// Creates an empty Map.
+    pub fn new() -> Self {
+        Map { root: None }
+    }
+
+    pub fn len(&self) -> usize {
+        size(&self.root)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    pub fn clear(&mut self) {
+        self.root = None;
+    }
+
+    /// Returns the entry with the `k`-th smallest key, counting from zero.
+    pub fn select(&self, k: usize) -> Option<(&K, &V)> {
+        let mut k = k;
+        let mut link = self.root.as_deref();
+        while let Some(node) = link {
+            let left = size(&node.left);
+            link = match k.cmp(&left) {
+                Ordering::Less => node.left.as_deref(),
+                Ordering::Equal => return Some((&node.key, &node.value)),
+                Ordering::Greater => {
+                    k -= left + 1;
+                    node.right.as_deref()
+                }
+            };
+        }
+        None
+    }
+
+    /// Removes the entry with the `k`-th smallest key, counting from zero.
+    pub fn remove_at(&mut self, k: usize) -> Option<(K, V)> {
+        if k >= self.len() {
+            return None;
+        }
+        Some(remove_at(&mut self.root, k))
+    }
+
+    /// Returns the lower median: the middle entry, or the lower of the middle two.
+    pub fn median(&self) -> Option<(&K, &V)> {
+        self.select(self.len().checked_sub(1)? / 2)
+    }
+
+    /// In-order iteration, so keys come out sorted.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        let mut iter = Iter {
+            stack: Vec::new(),
+            remaining: self.len(),
+        };
+        iter.push_left_spine(self.root.as_deref());
+        iter
+    }
+}
+
+impl<K: Ord, V> Map<K, V> {
+    /// Inserts a key-value pair, returning the old value if the key was already there.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        insert(&mut self.root, key, value)
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut link = self.root.as_deref();
+        while let Some(node) = link {
+            link = match key.cmp(node.key.borrow()) {
+                Ordering::Less => node.left.as_deref(),
+                Ordering::Greater => node.right.as_deref(),
+                Ordering::Equal => return Some(&node.value),
+            };
+        }
+        None
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    /// Returns the number of keys smaller than `key`, which is the position `key` has, or
+    /// would have, in sorted order.
+    pub fn rank<Q>(&self, key: &Q) -> usize
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut rank = 0;
+        let mut link = self.root.as_deref();
+        while let Some(node) = link {
+            link = match key.cmp(node.key.borrow()) {
+                Ordering::Less => node.left.as_deref(),
+                Ordering::Equal => return rank + size(&node.left),
+                Ordering::Greater => {
+                    rank += size(&node.left) + 1;
+                    node.right.as_deref()
+                }
+            };
+        }
+        rank
+    }
+
+    /// Removes a key, returning its value if it was there.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.remove_entry(key).map(|(_, v)| v)
+    }
+
+    /// Removes a key, returning the stored key and value if it was there.
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        remove(&mut self.root, key)
+    }
+
+    /// Walks the whole tree and panics if the keys are out of order, a stored size or
+    /// height is stale, or a node is out of balance. It's a hook for tests, and costs O(n).
+    pub fn check_invariants(&self) {
+        fn check<K: Ord, V>(link: &Link<K, V>, lower: Option<&K>, upper: Option<&K>) {
+            let node = match link {
+                Some(node) => node,
+                None => return,
+            };
+            assert!(
+                lower.is_none_or(|lower| *lower < node.key),
+                "keys out of order"
+            );
+            assert!(
+                upper.is_none_or(|upper| node.key < *upper),
+                "keys out of order"
+            );
+            assert_eq!(
+                node.size,
+                1 + size(&node.left) + size(&node.right),
+                "stale size"
+            );
+            assert_eq!(
+                node.height,
+                1 + cmp::max(height(&node.left), height(&node.right)),
+                "stale height"
+            );
+            assert!(node.balance_factor().abs() <= 1, "out of balance");
+            check(&node.left, lower, Some(&node.key));
+            check(&node.right, Some(&node.key), upper);
+        }
+        check(&self.root, None, None);
+    }
+}
+
+impl<K, V> Default for Map<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord, V> FromIterator<(K, V)> for Map<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = Map::new();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K: Ord, V> Extend<(K, V)> for Map<K, V> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug> fmt::Debug for Map<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// In-order iterator. The stack holds the nodes whose left subtree we've started but who
+/// haven't been yielded yet.
+pub struct Iter<'a, K, V> {
+    stack: Vec<&'a Node<K, V>>,
+    remaining: usize,
+}
+
+impl<'a, K, V> Iter<'a, K, V> {
+    fn push_left_spine(&mut self, mut link: Option<&'a Node<K, V>>) {
+        while let Some(node) = link {
+            self.stack.push(node);
+            link = node.left.as_deref();
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        self.push_left_spine(node.right.as_deref());
+        self.remaining -= 1;
+        Some((&node.key, &node.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+
+impl<'a, K, V> IntoIterator for &'a Map<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Map;
+
+    #[test]
+    fn basics() {
+        let mut map = Map::new();
+
+        // Check empty map behaves right
+        assert!(map.is_empty());
+        assert_eq!(map.select(0), None);
+        assert_eq!(map.rank(&5), 0);
+        assert_eq!(map.remove_at(0), None);
+        assert_eq!(map.median(), None);
+
+        // Populate map
+        for k in [40, 20, 60, 10, 30, 50, 70] {
+            assert_eq!(map.insert(k, k / 10), None);
+        }
+        assert_eq!(map.len(), 7);
+        assert_eq!(map.insert(30, 0), Some(3));
+        assert_eq!(map.len(), 7);
+        assert_eq!(map.get(&30), Some(&0));
+        assert!(map.contains_key(&70));
+
+        assert_eq!(map.remove(&30), Some(0));
+        assert_eq!(map.remove_entry(&10), Some((10, 1)));
+        assert_eq!(map.remove(&30), None);
+        assert_eq!(map.len(), 5);
+        map.check_invariants();
+        assert_eq!(format!("{:?}", map), "{20: 2, 40: 4, 50: 5, 60: 6, 70: 7}");
+    }
+
+    #[test]
+    fn rank_and_select() {
+        // Every third number, so ranks of missing keys are checked too
+        let map: Map<_, _> = (0..300).map(|i| (i * 3, i)).collect();
+        map.check_invariants();
+
+        for i in 0..300 {
+            assert_eq!(map.select(i), Some((&(i * 3), &i)));
+            assert_eq!(map.rank(&(i * 3)), i);
+            assert_eq!(map.rank(&(i * 3 + 1)), i + 1);
+        }
+        assert_eq!(map.select(300), None);
+        assert_eq!(map.rank(&10_000), 300);
+    }
+
+    #[test]
+    fn remove_at() {
+        let mut map: Map<_, _> = (0..10).map(|i| (i, ())).collect();
+        assert_eq!(map.remove_at(0), Some((0, ())));
+        assert_eq!(map.remove_at(8), Some((9, ())));
+        assert_eq!(map.remove_at(8), None);
+        assert_eq!(map.remove_at(3), Some((4, ())));
+        map.check_invariants();
+
+        let keys: Vec<_> = map.iter().map(|(k, _)| *k).collect();
+        assert_eq!(keys, vec![1, 2, 3, 5, 6, 7, 8]);
+        assert_eq!(map.iter().len(), 7);
+    }
+
+    #[test]
+    fn running_median() {
+        // Values come and go; the median is always one walk away
+        let mut map = Map::new();
+        let mut model: Vec<u32> = Vec::new();
+        let mut x: u32 = 5;
+        for i in 0..2_000 {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let value = (x >> 16) % 10_000;
+            if i % 3 == 2 && !model.is_empty() {
+                // Drop the oldest value still around
+                let oldest = model.remove(0);
+                assert!(map.remove(&oldest).is_some());
+            } else if !map.contains_key(&value) {
+                map.insert(value, ());
+                model.push(value);
+            }
+
+            let mut sorted = model.clone();
+            sorted.sort_unstable();
+            let expected = sorted.get(sorted.len().wrapping_sub(1) / 2);
+            assert_eq!(map.median().map(|(k, _)| k), expected);
+        }
+        map.check_invariants();
+    }
+
+    #[test]
+    fn against_btreemap() {
+        use std::collections::BTreeMap;
+        let mut map = Map::new();
+        let mut model = BTreeMap::new();
+        let mut x: u32 = 31;
+        for i in 0..5_000 {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let key = (x >> 16) % 500;
+            match x % 4 {
+                0 => assert_eq!(map.remove(&key), model.remove(&key)),
+                1 if !model.is_empty() => {
+                    let k = key as usize % model.len();
+                    let expected = model.keys().nth(k).copied().unwrap();
+                    assert_eq!(map.remove_at(k).map(|(k, _)| k), Some(expected));
+                    model.remove(&expected);
+                }
+                _ => assert_eq!(map.insert(key, x), model.insert(key, x)),
+            }
+            if i % 100 == 0 {
+                map.check_invariants();
+                assert_eq!(map.rank(&key), model.range(..key).count());
+            }
+        }
+        assert_eq!(map.len(), model.len());
+        assert!(map.iter().eq(model.iter()));
+    }
+}
@@ -0,0 +1,453 @@
+//! # Pairing heap
+//!
+//! A heap-ordered tree with any number of children per node, kept as linked nodes the
+//! way the unsafe [`crate::linked_list`] is (Fredman, Sedgewick, Sleator and Tarjan, 1986).
+//! Each node points at its first child and its next sibling, and back at whichever of the
+//! two points at it: its parent if it's a first child, its previous sibling otherwise.
+//!
+//! Everything is built from one step, *meld*: of two trees, the larger root becomes the
+//! first child of the smaller. That's O(1), and so are push (meld with a one-node tree)
+//! and [`PairingHeap::merge`].
+//!
+//! Pop is where the deferred work gets done. Taking the root leaves its children as a
+//! list of trees, which get melded in two passes: pairs from left to right, then the
+//! results from right to left into one tree.
+//!
+//! ```text
+//!   children of the popped root:   a  b  c  d  e
+//!   first pass, in pairs:          ab    cd    e
+//!   second pass, right to left:    ab + (cd + e)
+//! ```
+//!
+//! That's amortized O(log n). [`PairingHeap::decrease_key`] cuts a node out of its
+//! sibling list, with its subtree, and melds it with the root. Its exact amortized cost
+//! is still an open problem, somewhere between O(log log n) and O(log n).
+//!
+//! Asymptotics aren't everything, though. `benches/pairing_heap.rs` pits this against
+//! [`crate::heap::BinaryHeap`]: merging is far faster, but an allocation and a few cache
+//! misses per element mean the plain array wins at push and pop, and even at a
+//! Dijkstra-style workload where it just pushes duplicates instead of decreasing keys.
+//!
+//! This is a min-heap, since that's the side decrease-key works on. [`PairingHeap::push`]
+//! returns a [`Handle`] to the new element, and the handle is how you name that element
+//! later. The heap can't tell a live handle from one whose element is already gone, so
+//! `decrease_key` is `unsafe`.
+//!
+//! Run the tests under Miri to check the pointer juggling:
+//!
+//! ```text
+//! cargo +nightly miri test pairing_heap
+//! ```
+
+use std::fmt;
+use std::iter::FromIterator;
+use std::marker::PhantomData;
+use std::ptr::NonNull;
+
+pub struct PairingHeap<T> {
+    root: Link<T>,
+    len: usize,
+    _boo: PhantomData<T>,
+}
+
+type Link<T> = Option<NonNull<Node<T>>>;
+
+struct Node<T> {
+    elem: T,
+    child: Link<T>,
+    next: Link<T>,
+    // The parent if this is a first child, the previous sibling otherwise
+    prev: Link<T>,
+}
+
+/// Names an element in a [`PairingHeap`], for [`PairingHeap::decrease_key`].
+pub struct Handle<T> {
+    node: NonNull<Node<T>>,
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Handle<T> {}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.node == other.node
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Handle").field(&self.node).finish()
+    }
+}
+
+/// Makes the larger of two roots the first child of the smaller, and returns the new
+/// root.
+///
+/// # Safety
+///
+/// Both must be live roots, with no siblings and no parent.
+unsafe fn meld<T: Ord>(a: NonNull<Node<T>>, b: NonNull<Node<T>>) -> NonNull<Node<T>> {
+    let (parent, child) = if (*b.as_ptr()).elem < (*a.as_ptr()).elem {
+        (b, a)
+    } else {
+        (a, b)
+    };
+    let first = (*parent.as_ptr()).child;
+    (*child.as_ptr()).next = first;
+    if let Some(first) = first {
+        (*first.as_ptr()).prev = Some(child);
+    }
+    (*child.as_ptr()).prev = Some(parent);
+    (*parent.as_ptr()).child = Some(child);
+    parent
+}
+
+impl<T: Ord> PairingHeap<T> {
+    /// Creates an empty PairingHeap.
+    pub fn new() -> Self {
+        PairingHeap {
+            root: None,
+            len: 0,
+            _boo: PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        *self = PairingHeap::new();
+    }
+
+    /// Returns the smallest element.
+    pub fn peek(&self) -> Option<&T> {
+        unsafe { self.root.map(|node| &(*node.as_ptr()).elem) }
+    }
+
+    /// Adds an element and returns a handle to it.
+    pub fn push(&mut self, elem: T) -> Handle<T> {
+        unsafe {
+            let new = NonNull::new_unchecked(Box::into_raw(Box::new(Node {
+                elem,
+                child: None,
+                next: None,
+                prev: None,
+            })));
+            self.root = Some(match self.root {
+                Some(root) => meld(root, new),
+                None => new,
+            });
+            self.len += 1;
+            Handle { node: new }
+        }
+    }
+
+    /// Removes and returns the smallest element.
+    pub fn pop(&mut self) -> Option<T> {
+        unsafe {
+            self.root.map(|node| {
+                let boxed_node = Box::from_raw(node.as_ptr());
+
+                // First pass: meld the children in pairs, left to right
+                let mut pairs = Vec::new();
+                let mut next = boxed_node.child;
+                while let Some(a) = next {
+                    let b = (*a.as_ptr()).next;
+                    next = b.and_then(|b| (*b.as_ptr()).next);
+                    detach(a);
+                    pairs.push(match b {
+                        Some(b) => {
+                            detach(b);
+                            meld(a, b)
+                        }
+                        None => a,
+                    });
+                }
+
+                // Second pass: meld the pairs into one tree, right to left
+                self.root = pairs.into_iter().rev().reduce(|acc, tree| meld(tree, acc));
+                self.len -= 1;
+                boxed_node.elem
+            })
+        }
+    }
+
+    /// Moves every element of `other` into this heap, in O(1).
+    pub fn merge(&mut self, mut other: PairingHeap<T>) {
+        self.root = match (self.root, other.root.take()) {
+            (Some(a), Some(b)) => unsafe { Some(meld(a, b)) },
+            (a, b) => a.or(b),
+        };
+        self.len += std::mem::take(&mut other.len);
+    }
+
+    /// Replaces the element behind `handle` with a smaller or equal one, and moves it up
+    /// to where it now belongs.
+    ///
+    /// # Safety
+    ///
+    /// `handle` must have come from this heap (or one merged into it), and its element
+    /// must not have been popped since.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `elem` is greater than the element it replaces.
+    pub unsafe fn decrease_key(&mut self, handle: Handle<T>, elem: T) {
+        let node = handle.node;
+        assert!(
+            elem <= (*node.as_ptr()).elem,
+            "decrease_key would increase the key"
+        );
+        (*node.as_ptr()).elem = elem;
+        if self.root == Some(node) {
+            return;
+        }
+
+        // Cut the subtree out of its sibling list, then meld it with the root
+        let prev = (*node.as_ptr()).prev.unwrap();
+        let next = (*node.as_ptr()).next;
+        if (*prev.as_ptr()).child == Some(node) {
+            (*prev.as_ptr()).child = next;
+        } else {
+            (*prev.as_ptr()).next = next;
+        }
+        if let Some(next) = next {
+            (*next.as_ptr()).prev = Some(prev);
+        }
+        detach(node);
+        self.root = Some(meld(self.root.unwrap(), node));
+    }
+
+    /// Iterates over the elements in no particular order.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            stack: self.root.into_iter().collect(),
+            _boo: PhantomData,
+        }
+    }
+}
+
+/// Clears a node's sibling links, leaving it a root.
+///
+/// # Safety
+///
+/// `node` must be live.
+unsafe fn detach<T>(node: NonNull<Node<T>>) {
+    (*node.as_ptr()).next = None;
+    (*node.as_ptr()).prev = None;
+}
+
+impl<T> Drop for PairingHeap<T> {
+    fn drop(&mut self) {
+        // The tree can be a single long sibling list, so free it with a stack
+        let mut stack: Vec<_> = self.root.take().into_iter().collect();
+        while let Some(node) = stack.pop() {
+            unsafe {
+                let boxed_node = Box::from_raw(node.as_ptr());
+                stack.extend(boxed_node.child);
+                stack.extend(boxed_node.next);
+            }
+        }
+    }
+}
+
+unsafe impl<T: Send> Send for PairingHeap<T> {}
+unsafe impl<T: Sync> Sync for PairingHeap<T> {}
+
+impl<T: Ord> Default for PairingHeap<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Ord> FromIterator<T> for PairingHeap<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut heap = PairingHeap::new();
+        heap.extend(iter);
+        heap
+    }
+}
+
+impl<T: Ord> Extend<T> for PairingHeap<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for elem in iter {
+            self.push(elem);
+        }
+    }
+}
+
+impl<T: Ord + fmt::Debug> fmt::Debug for PairingHeap<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// Iterator over the elements of a [`PairingHeap`], in no particular order.
+pub struct Iter<'a, T> {
+    stack: Vec<NonNull<Node<T>>>,
+    _boo: PhantomData<&'a T>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.stack.pop().map(|node| unsafe {
+            let node = &*node.as_ptr();
+            self.stack.extend(node.child);
+            self.stack.extend(node.next);
+            &node.elem
+        })
+    }
+}
+
+impl<'a, T: Ord> IntoIterator for &'a PairingHeap<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::PairingHeap;
+
+    #[test]
+    fn basics() {
+        let mut heap = PairingHeap::new();
+
+        // Check empty heap behaves right
+        assert_eq!(heap.pop(), None);
+        assert_eq!(heap.peek(), None);
+
+        // Populate heap
+        heap.extend([5, 3, 8, 1, 9, 2]);
+        assert_eq!(heap.len(), 6);
+        assert_eq!(heap.peek(), Some(&1));
+        let mut all: Vec<_> = heap.iter().copied().collect();
+        all.sort_unstable();
+        assert_eq!(all, vec![1, 2, 3, 5, 8, 9]);
+
+        // Check normal removal
+        assert_eq!(heap.pop(), Some(1));
+        assert_eq!(heap.pop(), Some(2));
+
+        // Push some more just to make sure nothing's corrupted
+        heap.push(4);
+        heap.push(0);
+        assert_eq!(heap.pop(), Some(0));
+
+        // Check exhaustion
+        assert_eq!(heap.pop(), Some(3));
+        assert_eq!(heap.pop(), Some(4));
+        assert_eq!(heap.pop(), Some(5));
+        assert_eq!(heap.pop(), Some(8));
+        assert_eq!(heap.pop(), Some(9));
+        assert_eq!(heap.pop(), None);
+        assert!(heap.is_empty());
+    }
+
+    #[test]
+    fn merge() {
+        let mut evens: PairingHeap<_> = (0..10).step_by(2).collect();
+        let odds: PairingHeap<_> = (1..10).step_by(2).collect();
+        evens.merge(odds);
+        evens.merge(PairingHeap::new());
+        assert_eq!(evens.len(), 10);
+        let popped: Vec<_> = std::iter::from_fn(|| evens.pop()).collect();
+        assert_eq!(popped, (0..10).collect::<Vec<_>>());
+
+        let mut empty = PairingHeap::new();
+        empty.merge((0..3).collect());
+        assert_eq!(empty.peek(), Some(&0));
+    }
+
+    #[test]
+    fn decrease_key() {
+        let mut heap = PairingHeap::new();
+        let handles: Vec<_> = (0..10).map(|i| heap.push(10 * i + 100)).collect();
+        heap.pop();
+        unsafe {
+            // Wherever the pop left them: first children, later siblings, or equal keys
+            heap.decrease_key(handles[9], 5);
+            heap.decrease_key(handles[3], 1);
+            heap.decrease_key(handles[5], 150);
+            heap.decrease_key(handles[1], 1);
+        }
+        assert_eq!(heap.len(), 9);
+        let popped: Vec<_> = std::iter::from_fn(|| heap.pop()).collect();
+        assert_eq!(popped, vec![1, 1, 5, 120, 140, 150, 160, 170, 180]);
+    }
+
+    #[test]
+    #[should_panic(expected = "would increase")]
+    fn decrease_key_upwards() {
+        let mut heap = PairingHeap::new();
+        let handle = heap.push(1);
+        unsafe { heap.decrease_key(handle, 2) };
+    }
+
+    #[test]
+    fn long_sibling_list_drop() {
+        // Pushing in increasing order makes every element a child of the first
+        let n = if cfg!(miri) { 1_000 } else { 100_000 };
+        let heap: PairingHeap<_> = (0..n).collect();
+        assert_eq!(heap.len(), n);
+    }
+
+    #[test]
+    fn against_std() {
+        use std::cmp::Reverse;
+        let mut heap = PairingHeap::new();
+        let mut model = std::collections::BinaryHeap::new();
+        let mut live = Vec::new();
+        let mut x: u32 = 1;
+        let rounds = if cfg!(miri) { 500 } else { 20_000 };
+        for _ in 0..rounds {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            match x >> 30 {
+                0 => {
+                    let popped = heap.pop();
+                    assert_eq!(popped, model.pop().map(|Reverse(v)| v));
+                    // Forget the handle of whatever came out; keys are unique, so it's easy
+                    live.retain(|&(_, key)| Some(key) != popped);
+                }
+                1 if !live.is_empty() => {
+                    // Lower a random live key, and its twin in the model
+                    let i = (x >> 8) as usize % live.len();
+                    let (handle, key) = live[i];
+                    let lowered = key - key % 1000;
+                    unsafe { heap.decrease_key(handle, lowered) };
+                    let mut rest: Vec<_> = model.drain().filter(|&Reverse(k)| k != key).collect();
+                    rest.push(Reverse(lowered));
+                    model.extend(rest);
+                    live[i].1 = lowered;
+                }
+                _ => {
+                    // Unique keys, even once lowered, so handles and model entries pair up
+                    let key = (x >> 2) % 1_000_000 * 1000 + 999;
+                    if live.iter().all(|&(_, k)| k / 1000 != key / 1000) {
+                        live.push((heap.push(key), key));
+                        model.push(Reverse(key));
+                    }
+                }
+            }
+            assert_eq!(heap.len(), model.len());
+            assert_eq!(heap.peek(), model.peek().map(|Reverse(v)| v));
+        }
+    }
+}
@@ -44,7 +44,7 @@ impl<T> List<T> {
     pub fn append(&self, elem: T) -> List<T> {
         List {
             head: Some(Rc::new(Node {
-                elem: elem,
+                elem,
                 next: self.head.clone(),
             })),
         }
@@ -63,6 +63,150 @@ impl<T> List<T> {
     pub fn head(&self) -> Option<&T> {
         self.head.as_ref().map(|node| &node.elem)
     }
+
+    /// Returns true if the list has no elements.
+    pub fn is_empty(&self) -> bool {
+        self.head.is_none()
+    }
+
+    /// Returns the number of elements, walking the whole spine.
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    /// Compares two versions of a list by structure rather than by value.
+    ///
+    /// Lists derived from one another share their tails, so we line both spines up by length
+    /// and walk them in lockstep until they land on the *same node* (pointer equality). From
+    /// there on everything is shared; whatever we passed on the way is what changed.
+    pub fn diff<'a>(&'a self, other: &'a List<T>) -> Diff<'a, T> {
+        let mut removed = Vec::new();
+        let mut added = Vec::new();
+
+        let mut ours = self.head.as_ref();
+        let mut theirs = other.head.as_ref();
+        let (self_len, other_len) = (self.len(), other.len());
+
+        // Only the longer list can have nodes above the point where the spines could meet.
+        for _ in other_len..self_len {
+            let node = ours.unwrap();
+            removed.push(&node.elem);
+            ours = node.next.as_ref();
+        }
+        for _ in self_len..other_len {
+            let node = theirs.unwrap();
+            added.push(&node.elem);
+            theirs = node.next.as_ref();
+        }
+
+        while let (Some(a), Some(b)) = (ours, theirs) {
+            if Rc::ptr_eq(a, b) {
+                break;
+            }
+            removed.push(&a.elem);
+            added.push(&b.elem);
+            ours = a.next.as_ref();
+            theirs = b.next.as_ref();
+        }
+
+        Diff {
+            removed,
+            added,
+            shared: List {
+                head: ours.cloned(),
+            },
+        }
+    }
+}
+
+/// The result of [`List::diff`]: the prefixes that differ plus the suffix both lists share.
+pub struct Diff<'a, T> {
+    /// Elements only reachable from `self`, front to back.
+    pub removed: Vec<&'a T>,
+    /// Elements only reachable from `other`, front to back.
+    pub added: Vec<&'a T>,
+    /// The common tail, physically shared by both lists.
+    pub shared: List<T>,
+}
+
+impl<T: Clone> List<T> {
+    /// Returns a new list with the elements in the opposite order.
+    /// Nothing can be shared with the original spine, so every element is cloned.
+    pub fn reverse(&self) -> List<T> {
+        let mut reversed = List::new();
+        for elem in self.iter() {
+            reversed = reversed.append(elem.clone());
+        }
+        reversed
+    }
+
+    /// Returns a new list with all of our elements followed by the items of `iter`, in order.
+    ///
+    /// Nothing after the last element can be shared, so the original spine is copied. Rather
+    /// than concatenating one element at a time (quadratic), everything is pushed onto a
+    /// scratch list back-to-front and flipped over in a single pass at the end.
+    pub fn extend_with<I>(&self, iter: I) -> List<T>
+    where
+        I: IntoIterator<Item = T>,
+    {
+        let mut items = iter.into_iter().peekable();
+        if items.peek().is_none() {
+            return self.clone();
+        }
+
+        let mut scratch = List::new();
+        for elem in self.iter() {
+            scratch = scratch.append(elem.clone());
+        }
+        for elem in items {
+            scratch = scratch.append(elem);
+        }
+        scratch.into_reversed()
+    }
+
+    /// Reverses a list by value. Nodes we own outright are moved rather than cloned.
+    fn into_reversed(mut self) -> List<T> {
+        let mut reversed = List::new();
+        let mut next = self.head.take();
+
+        while let Some(node) = next {
+            let Node { elem, next: rest } = Rc::try_unwrap(node).unwrap_or_else(|shared| Node {
+                elem: shared.elem.clone(),
+                next: shared.next.clone(),
+            });
+            next = rest;
+            reversed = reversed.append(elem);
+        }
+        reversed
+    }
+}
+
+impl<A: Clone, B: Clone> List<(A, B)> {
+    /// Splits a list of pairs into a list of firsts and a list of seconds, keeping the order.
+    pub fn unzip(&self) -> (List<A>, List<B>) {
+        let mut lefts = ListBuilder::new();
+        let mut rights = ListBuilder::new();
+        for (a, b) in self.iter() {
+            lefts.push(a.clone());
+            rights.push(b.clone());
+        }
+        (lefts.finish(), rights.finish())
+    }
+}
+
+/// Cloning a list is just bumping the reference count on the head node.
+impl<T> Clone for List<T> {
+    fn clone(&self) -> Self {
+        List {
+            head: self.head.clone(),
+        }
+    }
+}
+
+impl<T> Default for List<T> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 pub struct Iter<'a, T> {
@@ -72,7 +216,7 @@ pub struct Iter<'a, T> {
 impl<T> List<T> {
     pub fn iter(&self) -> Iter<'_, T> {
         Iter {
-            next: self.head.as_ref().map(|node| &**node),
+            next: self.head.as_deref(),
         }
     }
 }
@@ -82,7 +226,7 @@ impl<'a, T> Iterator for Iter<'a, T> {
 
     fn next(&mut self) -> Option<Self::Item> {
         self.next.map(|node| {
-            self.next = node.next.as_ref().map(|node| &**node);
+            self.next = node.next.as_deref();
             &node.elem
         })
     }
@@ -102,9 +246,122 @@ impl<T> Drop for List<T> {
     }
 }
 
+/// Builds a list from elements given in the order they should appear.
+///
+/// `append` always puts the new element in front, so building `1 -> 2 -> 3` by hand means
+/// pushing 3, then 2, then 1. The builder buffers the elements and links them back-to-front
+/// in one O(n) pass when you call `finish`.
+pub struct ListBuilder<T> {
+    elems: Vec<T>,
+}
+
+impl<T> ListBuilder<T> {
+    /// Creates an empty ListBuilder.
+    pub fn new() -> Self {
+        ListBuilder { elems: Vec::new() }
+    }
+
+    /// Adds an element after everything pushed so far.
+    pub fn push(&mut self, elem: T) {
+        self.elems.push(elem);
+    }
+
+    /// Consumes the builder and returns the finished list.
+    pub fn finish(self) -> List<T> {
+        let mut list = List::new();
+        for elem in self.elems.into_iter().rev() {
+            list = list.append(elem);
+        }
+        list
+    }
+}
+
+impl<T> Default for ListBuilder<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// # Persistent FIFO queue (banker's queue)
+///
+/// A queue made out of two persistent lists: `front` holds the elements in dequeue order and
+/// `back` holds newly enqueued elements in reverse. When `front` runs dry we reverse `back`
+/// into it, so each element is moved exactly once and `enqueue`/`dequeue` are amortized O(1).
+///
+/// We keep the invariant that `front` is only empty when the whole queue is empty, which
+/// means `peek` never has to look at `back`.
+///
+/// Like the list, every operation returns a new queue and leaves the old one untouched.
+/// The amortized bound assumes each version is only dequeued from once; repeatedly
+/// dequeuing the same old version can pay for the same reversal over and over. The
+/// [real-time queue](crate::real_time_queue) spreads the reversal out so that can't happen.
+pub struct Queue<T> {
+    front: List<T>,
+    back: List<T>,
+}
+
+impl<T> Queue<T> {
+    /// Creates an empty Queue.
+    pub fn new() -> Self {
+        Queue {
+            front: List::new(),
+            back: List::new(),
+        }
+    }
+
+    /// Returns a reference to the element at the front of the queue.
+    pub fn peek(&self) -> Option<&T> {
+        self.front.head()
+    }
+
+    /// Returns true if the queue has no elements.
+    pub fn is_empty(&self) -> bool {
+        self.front.is_empty()
+    }
+}
+
+impl<T: Clone> Queue<T> {
+    /// Returns a new queue with `elem` added at the back.
+    pub fn enqueue(&self, elem: T) -> Queue<T> {
+        Queue::check(self.front.clone(), self.back.append(elem))
+    }
+
+    /// Returns a new queue with the front element removed.
+    pub fn dequeue(&self) -> Queue<T> {
+        Queue::check(self.front.tail(), self.back.clone())
+    }
+
+    /// Restores the invariant by moving `back` over once `front` is empty.
+    fn check(front: List<T>, back: List<T>) -> Queue<T> {
+        if front.is_empty() {
+            Queue {
+                front: back.reverse(),
+                back: List::new(),
+            }
+        } else {
+            Queue { front, back }
+        }
+    }
+}
+
+impl<T> Default for Queue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Clone for Queue<T> {
+    fn clone(&self) -> Self {
+        Queue {
+            front: self.front.clone(),
+            back: self.back.clone(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use super::List;
+    use super::{List, ListBuilder, Queue};
 
     #[test]
     fn basics() {
@@ -157,4 +414,154 @@ mod test {
         assert_eq!(iter.next(), Some(&2));
         assert_eq!(iter.next(), Some(&1));
     }
+
+    #[test]
+    fn reverse() {
+        let list = List::new().append(1).append(2).append(3);
+        let reversed = list.reverse();
+
+        let mut iter = reversed.iter();
+        assert_eq!(iter.next(), Some(&1));
+        assert_eq!(iter.next(), Some(&2));
+        assert_eq!(iter.next(), Some(&3));
+        assert_eq!(iter.next(), None);
+
+        // The original is untouched
+        assert_eq!(list.head(), Some(&3));
+    }
+
+    #[test]
+    fn queue_basics() {
+        let queue = Queue::new();
+        assert!(queue.is_empty());
+        assert_eq!(queue.peek(), None);
+
+        let queue = queue.enqueue(1).enqueue(2).enqueue(3);
+        assert_eq!(queue.peek(), Some(&1));
+
+        let queue = queue.dequeue();
+        assert_eq!(queue.peek(), Some(&2));
+
+        // Enqueue while the front still has elements
+        let queue = queue.enqueue(4);
+        assert_eq!(queue.peek(), Some(&2));
+
+        let queue = queue.dequeue();
+        assert_eq!(queue.peek(), Some(&3));
+        let queue = queue.dequeue();
+        assert_eq!(queue.peek(), Some(&4));
+        let queue = queue.dequeue();
+        assert_eq!(queue.peek(), None);
+        assert!(queue.is_empty());
+
+        // Make sure empty dequeue works
+        let queue = queue.dequeue();
+        assert_eq!(queue.peek(), None);
+    }
+
+    #[test]
+    fn queue_persistence() {
+        let queue1 = Queue::new().enqueue(1).enqueue(2);
+        let queue2 = queue1.dequeue();
+        let queue3 = queue1.enqueue(3);
+
+        // Old versions are still usable
+        assert_eq!(queue1.peek(), Some(&1));
+        assert_eq!(queue2.peek(), Some(&2));
+        assert_eq!(queue3.peek(), Some(&1));
+
+        let queue3 = queue3.dequeue().dequeue();
+        assert_eq!(queue3.peek(), Some(&3));
+        assert_eq!(queue2.dequeue().peek(), None);
+    }
+
+    #[test]
+    fn extend_with() {
+        let list = List::new().append(2).append(1);
+        let extended = list.extend_with(vec![3, 4, 5]);
+
+        let mut iter = extended.iter();
+        assert_eq!(iter.next(), Some(&1));
+        assert_eq!(iter.next(), Some(&2));
+        assert_eq!(iter.next(), Some(&3));
+        assert_eq!(iter.next(), Some(&4));
+        assert_eq!(iter.next(), Some(&5));
+        assert_eq!(iter.next(), None);
+
+        // The original is untouched
+        let mut iter = list.iter();
+        assert_eq!(iter.next(), Some(&1));
+        assert_eq!(iter.next(), Some(&2));
+        assert_eq!(iter.next(), None);
+
+        // Extending with nothing, or extending nothing
+        assert_eq!(list.extend_with(Vec::new()).head(), Some(&1));
+        assert_eq!(List::new().extend_with(vec![7]).head(), Some(&7));
+    }
+
+    #[test]
+    fn diff() {
+        let base = List::new().append(1).append(2);
+        let ours = base.append(3).append(4);
+        let theirs = base.tail().append(5);
+
+        let diff = ours.diff(&theirs);
+        assert_eq!(diff.removed, vec![&4, &3, &2]);
+        assert_eq!(diff.added, vec![&5]);
+        assert_eq!(diff.shared.head(), Some(&1));
+        assert_eq!(diff.shared.len(), 1);
+
+        // Equal values in separate nodes are still a difference
+        let copy = List::new().append(1);
+        let original = base.tail();
+        let diff = original.diff(&copy);
+        assert_eq!(diff.removed, vec![&1]);
+        assert_eq!(diff.added, vec![&1]);
+        assert!(diff.shared.is_empty());
+
+        // A list shares everything with itself
+        let diff = ours.diff(&ours);
+        assert!(diff.removed.is_empty());
+        assert!(diff.added.is_empty());
+        assert_eq!(diff.shared.len(), 4);
+    }
+
+    #[test]
+    fn builder() {
+        let mut builder = ListBuilder::new();
+        builder.push(1);
+        builder.push(2);
+        builder.push(3);
+        let list = builder.finish();
+
+        let mut iter = list.iter();
+        assert_eq!(iter.next(), Some(&1));
+        assert_eq!(iter.next(), Some(&2));
+        assert_eq!(iter.next(), Some(&3));
+        assert_eq!(iter.next(), None);
+
+        let empty: List<i32> = ListBuilder::new().finish();
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn unzip() {
+        let list = List::new()
+            .append((3, 'c'))
+            .append((2, 'b'))
+            .append((1, 'a'));
+        let (numbers, letters) = list.unzip();
+
+        let mut iter = numbers.iter();
+        assert_eq!(iter.next(), Some(&1));
+        assert_eq!(iter.next(), Some(&2));
+        assert_eq!(iter.next(), Some(&3));
+        assert_eq!(iter.next(), None);
+
+        let mut iter = letters.iter();
+        assert_eq!(iter.next(), Some(&'a'));
+        assert_eq!(iter.next(), Some(&'b'));
+        assert_eq!(iter.next(), Some(&'c'));
+        assert_eq!(iter.next(), None);
+    }
 }
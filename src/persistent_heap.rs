@@ -0,0 +1,336 @@
+//! # Persistent leftist heap
+//!
+//! An immutable priority queue in the style of [`crate::persistent`]: `push` and `pop` take
+//! `&self` and return a new heap, and the old one stays valid and unchanged.
+//!
+//! It's the leftist heap of [`crate::leftist_heap`] with `Arc` links. Everything there
+//! happens along right spines, and here those are the only nodes that get copied. A merge
+//! builds fresh nodes for the O(log n) spine nodes it passes through, and each new node
+//! points at the same left subtree as the one it copies:
+//!
+//! ```text
+//!   heap         =          1            heap.push(2)   =        1'
+//!                          / \                                  / \
+//!                         4   3                                4   2
+//!                        /   /                                /   /
+//!                       8   7                                8   3
+//!                                                               /
+//!   1' is a copy of 1 and 2 is new; 4, 8, 3 and 7 are shared   7
+//! ```
+//!
+//! So push, pop and merge are O(log n) in both time and new memory, and cloning a heap is
+//! O(1). Nodes are reference counted with `Arc` rather than the list's `Rc`, so whole
+//! versions can be handed between threads.
+
+use std::fmt;
+use std::iter::FromIterator;
+use std::sync::Arc;
+
+pub struct Heap<T> {
+    root: Link<T>,
+}
+
+type Link<T> = Option<Arc<Node<T>>>;
+
+struct Node<T> {
+    elem: T,
+    // Length of the right spine from here, counting this node
+    rank: usize,
+    len: usize,
+    left: Link<T>,
+    right: Link<T>,
+}
+
+fn rank<T>(link: &Link<T>) -> usize {
+    link.as_ref().map_or(0, |node| node.rank)
+}
+
+fn len<T>(link: &Link<T>) -> usize {
+    link.as_ref().map_or(0, |node| node.len)
+}
+
+/// Merges two heaps into a new one, copying only the nodes on the right spines it walks.
+fn merge<T: Ord + Clone>(a: &Link<T>, b: &Link<T>) -> Link<T> {
+    let (a, b) = match (a, b) {
+        (Some(a), Some(b)) => (a, b),
+        (a, b) => return a.clone().or_else(|| b.clone()),
+    };
+    let (small, large) = if b.elem < a.elem { (b, a) } else { (a, b) };
+    let merged = merge(&small.right, &Some(large.clone()));
+    let left = small.left.clone();
+    let (left, right) = if rank(&left) < rank(&merged) {
+        (merged, left)
+    } else {
+        (left, merged)
+    };
+    Some(Arc::new(Node {
+        elem: small.elem.clone(),
+        rank: rank(&right) + 1,
+        len: len(&left) + len(&right) + 1,
+        left,
+        right,
+    }))
+}
+
+impl<T> Heap<T> {
+    /// Creates an empty Heap.
+    pub fn new() -> Self {
+        Heap { root: None }
+    }
+
+    pub fn len(&self) -> usize {
+        len(&self.root)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    /// Returns the smallest element.
+    pub fn peek(&self) -> Option<&T> {
+        self.root.as_ref().map(|node| &node.elem)
+    }
+
+    /// Iterates over the elements in no particular order.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            stack: self.root.as_deref().into_iter().collect(),
+        }
+    }
+}
+
+impl<T: Ord + Clone> Heap<T> {
+    /// Returns a new heap with `elem` added.
+    pub fn push(&self, elem: T) -> Heap<T> {
+        let single = Some(Arc::new(Node {
+            elem,
+            rank: 1,
+            len: 1,
+            left: None,
+            right: None,
+        }));
+        Heap {
+            root: merge(&self.root, &single),
+        }
+    }
+
+    /// Returns a new heap without the smallest element. Popping an empty heap gives an
+    /// empty heap.
+    pub fn pop(&self) -> Heap<T> {
+        Heap {
+            root: self
+                .root
+                .as_ref()
+                .and_then(|node| merge(&node.left, &node.right)),
+        }
+    }
+
+    /// Returns a new heap holding the elements of both.
+    pub fn merge(&self, other: &Heap<T>) -> Heap<T> {
+        Heap {
+            root: merge(&self.root, &other.root),
+        }
+    }
+}
+
+impl<T> Clone for Heap<T> {
+    fn clone(&self) -> Self {
+        Heap {
+            root: self.root.clone(),
+        }
+    }
+}
+
+impl<T> Default for Heap<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for Heap<T> {
+    fn drop(&mut self) {
+        // Free the nodes only this version holds, and stop wherever another still shares one
+        let mut stack: Vec<_> = self.root.take().into_iter().collect();
+        while let Some(node) = stack.pop() {
+            if let Ok(mut node) = Arc::try_unwrap(node) {
+                stack.extend(node.left.take());
+                stack.extend(node.right.take());
+            }
+        }
+    }
+}
+
+impl<T: Ord + Clone> FromIterator<T> for Heap<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut heap = Heap::new();
+        for elem in iter {
+            heap = heap.push(elem);
+        }
+        heap
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Heap<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// Iterator over the elements of a [`Heap`], in no particular order.
+pub struct Iter<'a, T> {
+    stack: Vec<&'a Node<T>>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.stack.pop().map(|node| {
+            self.stack.extend(node.left.as_deref());
+            self.stack.extend(node.right.as_deref());
+            &node.elem
+        })
+    }
+}
+
+impl<'a, T> IntoIterator for &'a Heap<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Heap;
+    use std::sync::Arc;
+
+    /// Pops every element of a version, in order, leaving it untouched.
+    fn drain<T: Ord + Clone>(heap: &Heap<T>) -> Vec<T> {
+        let mut heap = heap.clone();
+        let mut out = Vec::new();
+        while let Some(elem) = heap.peek().cloned() {
+            out.push(elem);
+            heap = heap.pop();
+        }
+        out
+    }
+
+    #[test]
+    fn basics() {
+        let heap = Heap::new();
+
+        // Check empty heap behaves right
+        assert_eq!(heap.peek(), None);
+        assert!(heap.pop().is_empty());
+
+        // Populate heap
+        let heap = heap.push(5).push(3).push(8).push(1);
+        assert_eq!(heap.len(), 4);
+        assert_eq!(heap.peek(), Some(&1));
+
+        // Check normal removal
+        let heap = heap.pop();
+        assert_eq!(heap.peek(), Some(&3));
+        let heap = heap.pop().push(0);
+        assert_eq!(heap.peek(), Some(&0));
+
+        // Check exhaustion
+        assert_eq!(drain(&heap), vec![0, 5, 8]);
+        let heap = heap.pop().pop().pop();
+        assert!(heap.is_empty());
+        assert_eq!(heap.pop().peek(), None);
+    }
+
+    #[test]
+    fn persistence() {
+        let base: Heap<_> = [4, 8, 3, 7, 1].iter().copied().collect();
+        let pushed = base.push(2);
+        let popped = base.pop();
+        let merged = popped.merge(&pushed);
+
+        // Every version is still just what it was
+        assert_eq!(drain(&base), vec![1, 3, 4, 7, 8]);
+        assert_eq!(drain(&pushed), vec![1, 2, 3, 4, 7, 8]);
+        assert_eq!(drain(&popped), vec![3, 4, 7, 8]);
+        assert_eq!(drain(&merged), vec![1, 2, 3, 3, 4, 4, 7, 7, 8, 8]);
+        assert_eq!(merged.len(), 10);
+    }
+
+    #[test]
+    fn sharing() {
+        let base: Heap<_> = (0..100).collect();
+        let pushed = base.push(50);
+
+        // Only the right spine was copied; the root's left subtree is the very same node
+        let (old, new) = (base.root.as_ref().unwrap(), pushed.root.as_ref().unwrap());
+        assert!(!Arc::ptr_eq(old, new));
+        let shared = old.left.as_ref().unwrap();
+        assert!(Arc::ptr_eq(shared, new.left.as_ref().unwrap()));
+        assert_eq!(Arc::strong_count(shared), 2);
+
+        drop(base);
+        assert_eq!(
+            Arc::strong_count(pushed.root.as_ref().unwrap().left.as_ref().unwrap()),
+            1
+        );
+        assert_eq!(drain(&pushed).len(), 101);
+    }
+
+    #[test]
+    fn deep_left_path_drop() {
+        // Descending pushes build one long left path
+        let n = if cfg!(miri) { 1_000 } else { 100_000 };
+        let heap: Heap<_> = (0..n).rev().collect();
+        assert_eq!(heap.len(), n);
+        assert_eq!(heap.peek(), Some(&0));
+    }
+
+    #[test]
+    fn against_binary_heap() {
+        use crate::heap::BinaryHeap;
+        use std::cmp::Reverse;
+
+        // Random operations on random versions, each checked against its own model
+        let mut versions = vec![(Heap::new(), BinaryHeap::new())];
+        let mut x: u32 = 1;
+        for _ in 0..2_000 {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let (heap, model) = versions[(x >> 4) as usize % versions.len()].clone();
+            let other = versions[(x >> 12) as usize % versions.len()].clone();
+            let next = match x >> 30 {
+                0 => {
+                    let mut model = model;
+                    model.pop();
+                    (heap.pop(), model)
+                }
+                // Merging versions into each other doubles sizes fast, so cap them
+                1 if heap.len() + other.0.len() < 1000 => {
+                    let mut model = model;
+                    model.extend(other.1.into_vec());
+                    (heap.merge(&other.0), model)
+                }
+                _ => {
+                    let mut model = model;
+                    model.push(Reverse(x % 1000));
+                    (heap.push(x % 1000), model)
+                }
+            };
+            assert_eq!(next.0.len(), next.1.len());
+            assert_eq!(next.0.peek(), next.1.peek().map(|Reverse(v)| v));
+            versions.push(next);
+        }
+        for (heap, model) in versions.iter().step_by(50) {
+            let expected: Vec<_> = model
+                .clone()
+                .into_sorted_vec()
+                .into_iter()
+                .rev()
+                .map(|Reverse(v)| v)
+                .collect();
+            assert_eq!(drain(heap), expected);
+        }
+    }
+}
@@ -0,0 +1,579 @@
+//! # Persistent ordered map
+//!
+//! An immutable sorted map in the style of [`crate::persistent`]: `insert` and `remove` take
+//! `&self` and return a new map, and the old one stays valid and unchanged.
+//!
+//! It's a weight-balanced tree (Nievergelt and Reingold, 1972), the balanced tree behind
+//! Haskell's `Data.Map`, with the rebalancing parameters Hirai and Yamamoto (2011) proved
+//! correct. Every node keeps the size of its subtree, and a node is balanced when neither
+//! side outweighs the other more than three to one, counting one extra for each side so
+//! empty subtrees weigh something. An update that tips a node over that gets a single
+//! rotation, or a double one if the heavy side's inner grandchild is the heavy one:
+//!
+//! ```text
+//!        a                     b                   a                     c
+//!       / \                   / \                 / \                  /   \
+//!      x   b       -->       a   z               x   b       -->      a     b
+//!         / \               / \                     / \              / \   / \
+//!        y   z             x   y                   c   z            x  y1 y2  z
+//!                                                 / \
+//!   single                                       y1  y2     double
+//! ```
+//!
+//! The only nodes an update changes are the ones on the path to the key, plus the few a
+//! rotation moves, so those are all that get copied; every subtree off the path is shared
+//! with the old version. That's O(log n) time and new memory per update, and cloning a map
+//! is O(1). Nodes are reference counted with `Arc` rather than the list's `Rc`, so whole
+//! versions can be handed between threads.
+
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::fmt;
+use std::iter::FromIterator;
+use std::marker::PhantomData;
+use std::ops::{Bound, RangeBounds, RangeFull};
+use std::sync::Arc;
+
+// A side may weigh up to DELTA times the other; past that, a double rotation if the heavy
+// child's inner subtree weighs at least GAMMA times its outer one
+const DELTA: usize = 3;
+const GAMMA: usize = 2;
+
+pub struct Map<K, V> {
+    root: Link<K, V>,
+}
+
+type Link<K, V> = Option<Arc<Node<K, V>>>;
+
+struct Node<K, V> {
+    key: K,
+    value: V,
+    len: usize,
+    left: Link<K, V>,
+    right: Link<K, V>,
+}
+
+fn len<K, V>(link: &Link<K, V>) -> usize {
+    link.as_ref().map_or(0, |node| node.len)
+}
+
+fn weight<K, V>(link: &Link<K, V>) -> usize {
+    len(link) + 1
+}
+
+fn node<K, V>(key: K, value: V, left: Link<K, V>, right: Link<K, V>) -> Link<K, V> {
+    Some(Arc::new(Node {
+        key,
+        value,
+        len: len(&left) + len(&right) + 1,
+        left,
+        right,
+    }))
+}
+
+/// Builds a node whose sides were balanced until one of them changed by one element.
+fn balance<K: Clone, V: Clone>(
+    key: K,
+    value: V,
+    left: Link<K, V>,
+    right: Link<K, V>,
+) -> Link<K, V> {
+    if weight(&right) > DELTA * weight(&left) {
+        let heavy = right.unwrap();
+        if weight(&heavy.left) < GAMMA * weight(&heavy.right) {
+            let lower = node(key, value, left, heavy.left.clone());
+            node(
+                heavy.key.clone(),
+                heavy.value.clone(),
+                lower,
+                heavy.right.clone(),
+            )
+        } else {
+            let inner = heavy.left.as_ref().unwrap();
+            let lower_left = node(key, value, left, inner.left.clone());
+            let lower_right = node(
+                heavy.key.clone(),
+                heavy.value.clone(),
+                inner.right.clone(),
+                heavy.right.clone(),
+            );
+            node(
+                inner.key.clone(),
+                inner.value.clone(),
+                lower_left,
+                lower_right,
+            )
+        }
+    } else if weight(&left) > DELTA * weight(&right) {
+        let heavy = left.unwrap();
+        if weight(&heavy.right) < GAMMA * weight(&heavy.left) {
+            let lower = node(key, value, heavy.right.clone(), right);
+            node(
+                heavy.key.clone(),
+                heavy.value.clone(),
+                heavy.left.clone(),
+                lower,
+            )
+        } else {
+            let inner = heavy.right.as_ref().unwrap();
+            let lower_left = node(
+                heavy.key.clone(),
+                heavy.value.clone(),
+                heavy.left.clone(),
+                inner.left.clone(),
+            );
+            let lower_right = node(key, value, inner.right.clone(), right);
+            node(
+                inner.key.clone(),
+                inner.value.clone(),
+                lower_left,
+                lower_right,
+            )
+        }
+    } else {
+        node(key, value, left, right)
+    }
+}
+
+fn insert<K: Ord + Clone, V: Clone>(link: &Link<K, V>, key: K, value: V) -> Link<K, V> {
+    let n = match link {
+        Some(n) => n,
+        None => return node(key, value, None, None),
+    };
+    match key.cmp(&n.key) {
+        Ordering::Less => balance(
+            n.key.clone(),
+            n.value.clone(),
+            insert(&n.left, key, value),
+            n.right.clone(),
+        ),
+        Ordering::Greater => balance(
+            n.key.clone(),
+            n.value.clone(),
+            n.left.clone(),
+            insert(&n.right, key, value),
+        ),
+        // Same shape, so no rebalancing; the children are shared as they are
+        Ordering::Equal => node(key, value, n.left.clone(), n.right.clone()),
+    }
+}
+
+/// Removes `key`, which must be in the tree under `link`.
+fn remove<K, V, Q>(link: &Link<K, V>, key: &Q) -> Link<K, V>
+where
+    K: Borrow<Q> + Clone,
+    V: Clone,
+    Q: Ord + ?Sized,
+{
+    let n = link.as_ref().unwrap();
+    match key.cmp(n.key.borrow()) {
+        Ordering::Less => balance(
+            n.key.clone(),
+            n.value.clone(),
+            remove(&n.left, key),
+            n.right.clone(),
+        ),
+        Ordering::Greater => balance(
+            n.key.clone(),
+            n.value.clone(),
+            n.left.clone(),
+            remove(&n.right, key),
+        ),
+        Ordering::Equal => glue(&n.left, &n.right),
+    }
+}
+
+/// Joins the two balanced subtrees of a removed node, promoting the neighbour from the
+/// heavier side.
+fn glue<K: Clone, V: Clone>(left: &Link<K, V>, right: &Link<K, V>) -> Link<K, V> {
+    match (left, right) {
+        (None, other) | (other, None) => other.clone(),
+        _ if len(left) > len(right) => {
+            let (key, value, left) = remove_max(left.as_ref().unwrap());
+            balance(key, value, left, right.clone())
+        }
+        _ => {
+            let (key, value, right) = remove_min(right.as_ref().unwrap());
+            balance(key, value, left.clone(), right)
+        }
+    }
+}
+
+fn remove_min<K: Clone, V: Clone>(n: &Node<K, V>) -> (K, V, Link<K, V>) {
+    match &n.left {
+        None => (n.key.clone(), n.value.clone(), n.right.clone()),
+        Some(left) => {
+            let (key, value, left) = remove_min(left);
+            let rest = balance(n.key.clone(), n.value.clone(), left, n.right.clone());
+            (key, value, rest)
+        }
+    }
+}
+
+fn remove_max<K: Clone, V: Clone>(n: &Node<K, V>) -> (K, V, Link<K, V>) {
+    match &n.right {
+        None => (n.key.clone(), n.value.clone(), n.left.clone()),
+        Some(right) => {
+            let (key, value, right) = remove_max(right);
+            let rest = balance(n.key.clone(), n.value.clone(), n.left.clone(), right);
+            (key, value, rest)
+        }
+    }
+}
+
+impl<K, V> Map<K, V> {
+    /// Creates an empty Map.
+    pub fn new() -> Self {
+        Map { root: None }
+    }
+
+    pub fn len(&self) -> usize {
+        len(&self.root)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    /// Returns the entry with the smallest key.
+    pub fn first(&self) -> Option<(&K, &V)> {
+        let mut node = self.root.as_deref()?;
+        while let Some(left) = node.left.as_deref() {
+            node = left;
+        }
+        Some((&node.key, &node.value))
+    }
+
+    /// Returns the entry with the largest key.
+    pub fn last(&self) -> Option<(&K, &V)> {
+        let mut node = self.root.as_deref()?;
+        while let Some(right) = node.right.as_deref() {
+            node = right;
+        }
+        Some((&node.key, &node.value))
+    }
+}
+
+impl<K: Ord, V> Map<K, V> {
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut link = &self.root;
+        while let Some(node) = link {
+            link = match key.cmp(node.key.borrow()) {
+                Ordering::Less => &node.left,
+                Ordering::Greater => &node.right,
+                Ordering::Equal => return Some(&node.value),
+            };
+        }
+        None
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    /// Iterates over the entries with keys in `range`, in key order.
+    pub fn range<Q, R>(&self, range: R) -> Range<'_, K, V, Q, R>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        // Stack up the path to the first key in range, keeping the nodes we went left at
+        let mut stack = Vec::new();
+        let mut link = &self.root;
+        while let Some(node) = link {
+            let below_start = match range.start_bound() {
+                Bound::Included(start) => node.key.borrow() < start,
+                Bound::Excluded(start) => node.key.borrow() <= start,
+                Bound::Unbounded => false,
+            };
+            if below_start {
+                link = &node.right;
+            } else {
+                stack.push(&**node);
+                link = &node.left;
+            }
+        }
+        Range {
+            stack,
+            range,
+            _key: PhantomData,
+        }
+    }
+
+    /// Iterates over every entry in key order.
+    pub fn iter(&self) -> Range<'_, K, V, K, RangeFull> {
+        self.range(..)
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.iter().map(|(k, _)| k)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.iter().map(|(_, v)| v)
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> Map<K, V> {
+    /// Returns a new map with `key` mapped to `value`, replacing any value it had.
+    pub fn insert(&self, key: K, value: V) -> Map<K, V> {
+        Map {
+            root: insert(&self.root, key, value),
+        }
+    }
+
+    /// Returns a new map without `key`. If it wasn't there, that's just a copy of this
+    /// one, sharing everything.
+    pub fn remove<Q>(&self, key: &Q) -> Map<K, V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        if !self.contains_key(key) {
+            return self.clone();
+        }
+        Map {
+            root: remove(&self.root, key),
+        }
+    }
+}
+
+impl<K, V> Clone for Map<K, V> {
+    fn clone(&self) -> Self {
+        Map {
+            root: self.root.clone(),
+        }
+    }
+}
+
+impl<K, V> Default for Map<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> FromIterator<(K, V)> for Map<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = Map::new();
+        for (key, value) in iter {
+            map = map.insert(key, value);
+        }
+        map
+    }
+}
+
+impl<K: Ord, V: PartialEq> PartialEq for Map<K, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+impl<K: Ord, V: Eq> Eq for Map<K, V> {}
+
+impl<K: Ord + fmt::Debug, V: fmt::Debug> fmt::Debug for Map<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// An iterator over a range of a [`Map`], in key order. Created by [`Map::range`].
+pub struct Range<'a, K, V, Q: ?Sized, R> {
+    // Nodes still to yield, each followed in order by its right subtree
+    stack: Vec<&'a Node<K, V>>,
+    range: R,
+    _key: PhantomData<fn(&Q)>,
+}
+
+impl<'a, K, V, Q, R> Iterator for Range<'a, K, V, Q, R>
+where
+    K: Borrow<Q>,
+    Q: Ord + ?Sized,
+    R: RangeBounds<Q>,
+{
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        let past_end = match self.range.end_bound() {
+            Bound::Included(end) => node.key.borrow() > end,
+            Bound::Excluded(end) => node.key.borrow() >= end,
+            Bound::Unbounded => false,
+        };
+        if past_end {
+            self.stack.clear();
+            return None;
+        }
+        let mut link = &node.right;
+        while let Some(next) = link {
+            self.stack.push(next);
+            link = &next.left;
+        }
+        Some((&node.key, &node.value))
+    }
+}
+
+impl<'a, K: Ord, V> IntoIterator for &'a Map<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Range<'a, K, V, K, RangeFull>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{len, weight, Link, Map, Range, DELTA};
+    use std::collections::BTreeMap;
+    use std::ops::Bound::{Excluded, Included};
+    use std::ops::RangeBounds;
+    use std::sync::Arc;
+
+    /// Checks the sizes, the order and the weight balance of every node.
+    fn check<K: Ord, V>(link: &Link<K, V>) {
+        if let Some(node) = link {
+            assert_eq!(node.len, len(&node.left) + len(&node.right) + 1);
+            assert!(weight(&node.left) <= DELTA * weight(&node.right));
+            assert!(weight(&node.right) <= DELTA * weight(&node.left));
+            assert!(node.left.as_ref().is_none_or(|left| left.key < node.key));
+            assert!(node.right.as_ref().is_none_or(|right| right.key > node.key));
+            check(&node.left);
+            check(&node.right);
+        }
+    }
+
+    #[test]
+    fn basics() {
+        let map: Map<String, u32> = Map::new();
+
+        // Check empty map behaves right
+        assert_eq!(map.get("a"), None);
+        assert_eq!(map.first(), None);
+        assert!(map.remove("a").is_empty());
+
+        // Populate map
+        let map = map.insert("b".to_string(), 2).insert("a".to_string(), 1);
+        let map = map.insert("c".to_string(), 3).insert("a".to_string(), 4);
+        assert_eq!(map.len(), 3);
+        assert_eq!(map.get("a"), Some(&4));
+        assert_eq!(map.first().map(|(k, _)| k.as_str()), Some("a"));
+        assert_eq!(map.last().map(|(_, v)| v), Some(&3));
+        assert_eq!(format!("{:?}", map), r#"{"a": 4, "b": 2, "c": 3}"#);
+
+        // Check normal removal
+        let map = map.remove("b");
+        assert!(!map.contains_key("b"));
+        assert_eq!(map.remove("b").len(), 2);
+
+        // Push some more just to make sure nothing's corrupted
+        let map = (0..100).fold(map, |map, i| map.insert(format!("{:03}", i), i));
+        check(&map.root);
+        assert_eq!(map.len(), 102);
+        assert_eq!(map.keys().next().map(String::as_str), Some("000"));
+
+        // Check exhaustion
+        let map = (0..100).fold(map, |map, i| map.remove(&format!("{:03}", i)));
+        let map = map.remove("a").remove("c");
+        assert!(map.is_empty());
+        assert_eq!(map.iter().next(), None);
+    }
+
+    #[test]
+    fn persistence() {
+        let base: Map<u32, u32> = (0..100).map(|i| (i, i)).collect();
+        let inserted = base.insert(100, 100).insert(5, 50);
+        let removed = base.remove(&50);
+
+        // Every version is still just what it was
+        assert_eq!(base.len(), 100);
+        assert_eq!((base.get(&5), inserted.get(&5)), (Some(&5), Some(&50)));
+        assert_eq!(inserted.last(), Some((&100, &100)));
+        assert_eq!((base.get(&50), removed.get(&50)), (Some(&50), None));
+        assert_eq!(removed.len(), 99);
+        assert_eq!(base, base.remove(&500));
+        assert_ne!(base, removed);
+        assert!(base.iter().map(|(&k, _)| k).eq(0..100));
+    }
+
+    #[test]
+    fn sharing() {
+        let base: Map<u32, u32> = (0..1000).map(|i| (i, i)).collect();
+        let root = base.root.as_ref().unwrap();
+        let updated = base.insert(root.key, 0);
+
+        // Replacing the root's value copies just the root
+        let new = updated.root.as_ref().unwrap();
+        assert!(!Arc::ptr_eq(root, new));
+        assert!(Arc::ptr_eq(
+            root.left.as_ref().unwrap(),
+            new.left.as_ref().unwrap()
+        ));
+        assert!(Arc::ptr_eq(
+            root.right.as_ref().unwrap(),
+            new.right.as_ref().unwrap()
+        ));
+
+        // Touching the largest key leaves the whole left subtree shared
+        let bigger = base.insert(1000, 1000);
+        let left = base.root.as_ref().unwrap().left.as_ref().unwrap();
+        assert_eq!(Arc::strong_count(left), 3);
+        drop(bigger);
+        assert_eq!(Arc::strong_count(left), 2);
+    }
+
+    #[test]
+    fn range() {
+        let map: Map<u32, u32> = (0..50).map(|i| (i * 2, i)).collect();
+        fn keys<R: RangeBounds<u32>>(range: Range<'_, u32, u32, u32, R>) -> Vec<u32> {
+            range.map(|(&k, _)| k).collect()
+        }
+
+        assert_eq!(keys(map.range(10..16)), [10, 12, 14]);
+        assert_eq!(keys(map.range(9..=16)), [10, 12, 14, 16]);
+        assert_eq!(keys(map.range(95..)), [96, 98]);
+        assert_eq!(keys(map.range(..3)), [0, 2]);
+        assert_eq!(map.range(40..40).count(), 0);
+        assert_eq!(map.range(200..).count(), 0);
+        assert_eq!(map.range(..).count(), 50);
+        assert_eq!(map.range((Excluded(10), Included(14))).count(), 2);
+    }
+
+    #[test]
+    fn against_btree_map() {
+        // Random operations on random versions, each checked against its own model
+        let mut versions = vec![(Map::new(), BTreeMap::new())];
+        let mut x: u32 = 1;
+        let n = if cfg!(miri) { 500 } else { 10_000 };
+        for _ in 0..n {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let (map, mut model) = versions[(x >> 4) as usize % versions.len()].clone();
+            let key = (x >> 8) % 500;
+            let next = if x >> 30 == 0 {
+                model.remove(&key);
+                map.remove(&key)
+            } else {
+                model.insert(key, x);
+                map.insert(key, x)
+            };
+            assert_eq!(next.len(), model.len());
+            assert_eq!(next.get(&key), model.get(&key));
+            versions.push((next, model));
+        }
+        for (map, model) in versions.iter().step_by(100) {
+            check(&map.root);
+            assert!(map.iter().eq(model.iter()));
+            let (low, high) = (x % 250, x % 250 + 100);
+            assert!(map.range(low..high).eq(model.range(low..high)));
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+        }
+    }
+}
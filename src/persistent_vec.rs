@@ -0,0 +1,580 @@
+//! # Persistent vector
+//!
+//! An immutable vector in the style of [`crate::persistent`], after Clojure's (Hickey,
+//! 2007, from Bagwell's tries): `push_back`, `update` and `pop_back` take `&self` and
+//! return a new vector, and every older version stays valid and unchanged. Where the list
+//! can only share its tails, this shares almost everything, and indexes anywhere in
+//! O(log32 n), which is four steps for a million elements.
+//!
+//! The elements sit in the leaves of a tree where every node has up to 32 children, and
+//! the bits of an index, five at a time from the top, say which child to take:
+//!
+//! ```text
+//!   index 1000 = 11111 01000         root
+//!                                 /    |
+//!                                0 .. 31
+//!                                      |
+//!                                leaf: elements 992..1023, take number 8
+//! ```
+//!
+//! Changing an element copies the nodes on the path from the root to its leaf, at most 32
+//! pointers or elements each, and the new path points at all the same siblings as the old.
+//!
+//! One more trick makes `push_back` cheap: the last up to 32 elements aren't in the tree
+//! but in a separate *tail*. Pushing copies just the tail, and only once it's full does it
+//! move into the tree as a new leaf, so a push touches the tree once every 32 times.
+//!
+//! Both kinds of node are behind `Arc`, and every change goes through `Arc::make_mut`,
+//! which copies a node only if some other version also holds it. For a persistent
+//! operation that's the path copy above. A [`Transient`] is a version nobody else can see,
+//! for building or changing a vector in bulk: the first write to each node copies it, and
+//! from then on the node is the transient's alone and is changed in place. Turning it back
+//! with [`Transient::persistent`] is free.
+//!
+//! This is the plain bit-partitioned trie. The relaxed (RRB) variant adds O(log n)
+//! concatenation and splitting at the cost of a fiddlier index walk, and isn't here.
+
+use std::fmt;
+use std::iter::FromIterator;
+use std::mem;
+use std::ops::Index;
+use std::sync::Arc;
+
+const BITS: u32 = 5;
+const WIDTH: usize = 1 << BITS;
+const MASK: usize = WIDTH - 1;
+
+enum Node<T> {
+    Branch(Arc<Vec<Node<T>>>),
+    Leaf(Arc<Vec<T>>),
+}
+
+// Derive would want `T: Clone`, but cloning a node only bumps a count
+impl<T> Clone for Node<T> {
+    fn clone(&self) -> Self {
+        match self {
+            Node::Branch(children) => Node::Branch(children.clone()),
+            Node::Leaf(elems) => Node::Leaf(elems.clone()),
+        }
+    }
+}
+
+pub struct Vector<T> {
+    len: usize,
+    // Bits of the index the root's children are picked by
+    shift: u32,
+    root: Arc<Vec<Node<T>>>,
+    // The last 1 to 32 elements, or none if the vector is empty
+    tail: Arc<Vec<T>>,
+}
+
+impl<T> Vector<T> {
+    /// Creates an empty Vector.
+    pub fn new() -> Self {
+        Vector {
+            len: 0,
+            shift: BITS,
+            root: Arc::new(Vec::new()),
+            tail: Arc::new(Vec::new()),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Index of the first element in the tail.
+    fn tail_offset(&self) -> usize {
+        self.len - self.tail.len()
+    }
+
+    /// Returns the leaf holding element `i`, which must be in bounds.
+    fn leaf_for(&self, i: usize) -> &[T] {
+        if i >= self.tail_offset() {
+            return &self.tail;
+        }
+        let mut children = &self.root;
+        let mut level = self.shift;
+        loop {
+            match &children[(i >> level) & MASK] {
+                Node::Branch(next) => {
+                    children = next;
+                    level -= BITS;
+                }
+                Node::Leaf(elems) => return elems,
+            }
+        }
+    }
+
+    pub fn get(&self, i: usize) -> Option<&T> {
+        if i >= self.len {
+            return None;
+        }
+        Some(&self.leaf_for(i)[i & MASK])
+    }
+
+    pub fn first(&self) -> Option<&T> {
+        self.get(0)
+    }
+
+    pub fn last(&self) -> Option<&T> {
+        self.tail.last()
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            vec: self,
+            index: 0,
+            leaf: &[],
+        }
+    }
+
+    /// Returns a mutable version of this vector to change in bulk. Other versions don't
+    /// see its changes.
+    pub fn transient(&self) -> Transient<T> {
+        Transient { vec: self.clone() }
+    }
+}
+
+impl<T: Clone> Vector<T> {
+    /// Returns a new vector with `elem` on the end.
+    pub fn push_back(&self, elem: T) -> Vector<T> {
+        let mut vec = self.clone();
+        vec.push_mut(elem);
+        vec
+    }
+
+    /// Returns a new vector with element `i` replaced by `elem`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `i` is out of bounds.
+    pub fn update(&self, i: usize, elem: T) -> Vector<T> {
+        let mut vec = self.clone();
+        vec.set_mut(i, elem);
+        vec
+    }
+
+    /// Returns a new vector without the last element, or an empty one if this is empty.
+    pub fn pop_back(&self) -> Vector<T> {
+        let mut vec = self.clone();
+        vec.pop_mut();
+        vec
+    }
+
+    fn push_mut(&mut self, elem: T) {
+        if self.tail.len() < WIDTH {
+            Arc::make_mut(&mut self.tail).push(elem);
+            self.len += 1;
+            return;
+        }
+
+        // The tail is full, so it becomes a leaf in the tree and a new tail starts
+        let mut tail = Vec::with_capacity(WIDTH);
+        tail.push(elem);
+        let leaf = Node::Leaf(mem::replace(&mut self.tail, Arc::new(tail)));
+        let offset = self.len - WIDTH;
+        if offset == 1 << (self.shift + BITS) {
+            // No room under the root, so the tree grows a level
+            let old = mem::replace(&mut self.root, Arc::new(Vec::with_capacity(2)));
+            let root = Arc::make_mut(&mut self.root);
+            root.push(Node::Branch(old));
+            root.push(new_path(self.shift, leaf));
+            self.shift += BITS;
+        } else {
+            push_leaf(&mut self.root, self.shift, offset, leaf);
+        }
+        self.len += 1;
+    }
+
+    fn set_mut(&mut self, i: usize, elem: T) {
+        assert!(
+            i < self.len,
+            "index {} out of bounds for length {}",
+            i,
+            self.len
+        );
+        let offset = self.tail_offset();
+        if i >= offset {
+            Arc::make_mut(&mut self.tail)[i - offset] = elem;
+            return;
+        }
+        let mut children = Arc::make_mut(&mut self.root);
+        let mut level = self.shift;
+        loop {
+            match &mut children[(i >> level) & MASK] {
+                Node::Branch(next) => {
+                    children = Arc::make_mut(next);
+                    level -= BITS;
+                }
+                Node::Leaf(elems) => {
+                    Arc::make_mut(elems)[i & MASK] = elem;
+                    return;
+                }
+            }
+        }
+    }
+
+    fn pop_mut(&mut self) -> Option<T> {
+        let elem = Arc::make_mut(&mut self.tail).pop()?;
+        self.len -= 1;
+        if self.tail.is_empty() && self.len > 0 {
+            // Bring the last leaf back out of the tree as the tail
+            self.tail = pop_leaf(&mut self.root);
+            if self.shift > BITS && self.root.len() == 1 {
+                // A root with one child is a level too many
+                if let Node::Branch(only) = self.root[0].clone() {
+                    self.root = only;
+                    self.shift -= BITS;
+                }
+            }
+        }
+        Some(elem)
+    }
+}
+
+/// A chain of branches down from `level` with `leaf` at the bottom.
+fn new_path<T>(level: u32, leaf: Node<T>) -> Node<T> {
+    if level == 0 {
+        leaf
+    } else {
+        Node::Branch(Arc::new(vec![new_path(level - BITS, leaf)]))
+    }
+}
+
+/// Adds `leaf`, whose first element has index `offset`, as the last leaf under
+/// `children`, a branch at `level`.
+fn push_leaf<T>(children: &mut Arc<Vec<Node<T>>>, level: u32, offset: usize, leaf: Node<T>) {
+    let children = Arc::make_mut(children);
+    let sub = (offset >> level) & MASK;
+    if level == BITS {
+        children.push(leaf);
+    } else if sub < children.len() {
+        match &mut children[sub] {
+            Node::Branch(next) => push_leaf(next, level - BITS, offset, leaf),
+            Node::Leaf(_) => unreachable!("leaf above the bottom level"),
+        }
+    } else {
+        children.push(new_path(level - BITS, leaf));
+    }
+}
+
+/// Takes the last leaf out from under `children`. The tree is packed to the left, so
+/// that's down the last child every time. Branches left empty go too.
+fn pop_leaf<T>(children: &mut Arc<Vec<Node<T>>>) -> Arc<Vec<T>> {
+    let children = Arc::make_mut(children);
+    match children.last_mut() {
+        Some(Node::Leaf(_)) => match children.pop() {
+            Some(Node::Leaf(elems)) => elems,
+            _ => unreachable!(),
+        },
+        Some(Node::Branch(next)) => {
+            let leaf = pop_leaf(next);
+            if next.is_empty() {
+                children.pop();
+            }
+            leaf
+        }
+        None => unreachable!("popped a leaf from an empty branch"),
+    }
+}
+
+/// A version of a [`Vector`] that changes in place, made by [`Vector::transient`].
+pub struct Transient<T> {
+    vec: Vector<T>,
+}
+
+impl<T> Transient<T> {
+    pub fn len(&self) -> usize {
+        self.vec.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vec.is_empty()
+    }
+
+    pub fn get(&self, i: usize) -> Option<&T> {
+        self.vec.get(i)
+    }
+
+    /// Turns this back into an immutable vector, in O(1).
+    pub fn persistent(self) -> Vector<T> {
+        self.vec
+    }
+}
+
+impl<T: Clone> Transient<T> {
+    pub fn push(&mut self, elem: T) {
+        self.vec.push_mut(elem);
+    }
+
+    /// Replaces element `i` with `elem`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `i` is out of bounds.
+    pub fn set(&mut self, i: usize, elem: T) {
+        self.vec.set_mut(i, elem);
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        self.vec.pop_mut()
+    }
+}
+
+impl<T: Clone> Extend<T> for Transient<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for elem in iter {
+            self.push(elem);
+        }
+    }
+}
+
+impl<T> Clone for Vector<T> {
+    fn clone(&self) -> Self {
+        Vector {
+            len: self.len,
+            shift: self.shift,
+            root: self.root.clone(),
+            tail: self.tail.clone(),
+        }
+    }
+}
+
+impl<T> Default for Vector<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Clone> FromIterator<T> for Vector<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut transient = Vector::new().transient();
+        transient.extend(iter);
+        transient.persistent()
+    }
+}
+
+impl<T> Index<usize> for Vector<T> {
+    type Output = T;
+
+    fn index(&self, i: usize) -> &T {
+        match self.get(i) {
+            Some(elem) => elem,
+            None => panic!("index {} out of bounds for length {}", i, self.len),
+        }
+    }
+}
+
+impl<T: PartialEq> PartialEq for Vector<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.iter().eq(other.iter())
+    }
+}
+
+impl<T: Eq> Eq for Vector<T> {}
+
+impl<T: fmt::Debug> fmt::Debug for Vector<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// Iterator over a [`Vector`], a leaf at a time.
+pub struct Iter<'a, T> {
+    vec: &'a Vector<T>,
+    index: usize,
+    // What's left of the current leaf
+    leaf: &'a [T],
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        if self.leaf.is_empty() {
+            if self.index >= self.vec.len {
+                return None;
+            }
+            self.leaf = &self.vec.leaf_for(self.index)[self.index & MASK..];
+        }
+        let (elem, rest) = self.leaf.split_first()?;
+        self.leaf = rest;
+        self.index += 1;
+        Some(elem)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let left = self.vec.len - self.index;
+        (left, Some(left))
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Iter<'a, T> {}
+
+impl<'a, T> IntoIterator for &'a Vector<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Node, Vector, WIDTH};
+    use std::sync::Arc;
+
+    #[test]
+    fn basics() {
+        let vec = Vector::new();
+
+        // Check empty vector behaves right
+        assert_eq!(vec.get(0), None);
+        assert_eq!(vec.last(), None);
+        assert!(vec.pop_back().is_empty());
+
+        // Populate vector
+        let vec = vec.push_back(1).push_back(2).push_back(3);
+        assert_eq!(vec.len(), 3);
+        assert_eq!((vec[0], vec.first(), vec.last()), (1, Some(&1), Some(&3)));
+        let vec = vec.update(1, 20);
+        assert_eq!(format!("{:?}", vec), "[1, 20, 3]");
+
+        // Check normal removal
+        let vec = vec.pop_back();
+        assert_eq!(vec.iter().copied().collect::<Vec<_>>(), [1, 20]);
+
+        // Push some more just to make sure nothing's corrupted, into the tree
+        let vec = (0..100).fold(vec, |vec, i| vec.push_back(i));
+        assert_eq!(vec.len(), 102);
+        assert_eq!(vec[101], 99);
+        assert_eq!(vec.get(102), None);
+
+        // Check exhaustion
+        let vec = (0..102).fold(vec, |vec, _| vec.pop_back());
+        assert!(vec.is_empty());
+        assert_eq!(vec, Vector::new());
+    }
+
+    #[test]
+    #[should_panic(expected = "index 3 out of bounds for length 3")]
+    fn update_out_of_bounds() {
+        let vec: Vector<u32> = (0..3).collect();
+        vec.update(3, 0);
+    }
+
+    #[test]
+    fn persistence() {
+        let base: Vector<u32> = (0..1000).collect();
+        let updated = base.update(500, 0);
+        let pushed = base.push_back(1000);
+        let popped = base.pop_back();
+
+        // Every version is still just what it was
+        assert!(base.iter().copied().eq(0..1000));
+        assert_eq!((updated[500], base[500]), (0, 500));
+        assert!(pushed.iter().copied().eq(0..1001));
+        assert!(popped.iter().copied().eq(0..999));
+    }
+
+    #[test]
+    fn sharing() {
+        let base: Vector<u32> = (0..WIDTH as u32 * 4).collect();
+        let updated = base.update(0, 100);
+
+        // Only the path to leaf 0 was copied; the other leaves are the very same ones
+        let leaf = |vec: &Vector<u32>, i: usize| match &vec.root[i] {
+            Node::Leaf(elems) => elems.clone(),
+            Node::Branch(_) => panic!("expected a leaf"),
+        };
+        assert!(!Arc::ptr_eq(&leaf(&base, 0), &leaf(&updated, 0)));
+        assert!(Arc::ptr_eq(&leaf(&base, 1), &leaf(&updated, 1)));
+        assert!(Arc::ptr_eq(&base.tail, &updated.tail));
+    }
+
+    #[test]
+    fn transient() {
+        let base: Vector<u32> = (0..100).collect();
+        let mut transient = base.transient();
+        transient.set(0, 7);
+        transient.push(100);
+        // Once copied, the tail belongs to the transient and changes in place
+        let tail = Arc::as_ptr(&transient.vec.tail);
+        transient.push(101);
+        transient.set(transient.len() - 1, 1010);
+        assert_eq!(Arc::as_ptr(&transient.vec.tail), tail);
+        assert_eq!(transient.pop(), Some(1010));
+        assert_eq!(transient.get(0), Some(&7));
+
+        let changed = transient.persistent();
+        assert_eq!(changed.len(), 101);
+        assert_eq!((changed[0], changed[100]), (7, 100));
+        // The original never saw any of it
+        assert!(base.iter().copied().eq(0..100));
+    }
+
+    #[test]
+    fn deep() {
+        // Enough elements for a tree four levels deep, then all the way back down
+        let n = if cfg!(miri) { 2_000 } else { 40_000 };
+        let mut transient = Vector::new().transient();
+        transient.extend(0..n);
+        let vec = transient.persistent();
+        assert!(vec.iter().copied().eq(0..n));
+        assert_eq!(vec.iter().len(), n as usize);
+        for i in (0..n).step_by(997) {
+            assert_eq!(vec[i as usize], i);
+        }
+
+        let mut transient = vec.transient();
+        for i in (0..n).rev() {
+            assert_eq!(transient.pop(), Some(i));
+        }
+        assert!(transient.is_empty());
+        assert_eq!(transient.persistent().shift, super::BITS);
+        assert_eq!(vec.len(), n as usize);
+    }
+
+    #[test]
+    fn against_vec() {
+        // Random operations on random versions, each checked against its own model
+        let mut versions = vec![(Vector::new(), Vec::new())];
+        let mut x: u32 = 1;
+        let n = if cfg!(miri) { 500 } else { 5_000 };
+        for _ in 0..n {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let (vec, mut model) = versions[(x >> 4) as usize % versions.len()].clone();
+            let next = match x >> 30 {
+                0 => {
+                    model.pop();
+                    vec.pop_back()
+                }
+                1 if !model.is_empty() => {
+                    let i = (x >> 8) as usize % model.len();
+                    model[i] = x;
+                    vec.update(i, x)
+                }
+                _ => {
+                    // Pushes in runs, so versions get big enough for a deep tree
+                    let mut transient = vec.transient();
+                    for k in 0..(x >> 8) % 200 {
+                        model.push(k);
+                        transient.push(k);
+                    }
+                    transient.persistent()
+                }
+            };
+            assert_eq!(next.len(), model.len());
+            versions.push((next, model));
+        }
+        for (vec, model) in versions.iter().step_by(25) {
+            assert!(vec.iter().eq(model.iter()));
+        }
+    }
+}
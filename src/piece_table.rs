@@ -0,0 +1,378 @@
+//! # Piece table
+//!
+//! The piece table (Crowley, 1998) is what a lot of editors keep a document in. The text
+//! is never edited in place. There are two buffers: the *original* text the document was
+//! opened with, which never changes, and the *added* buffer, which every inserted text is
+//! appended to and which never shrinks. The document itself is a list of *pieces*, each a
+//! range of one of the two buffers, read one after another:
+//!
+//! ```text
+//!   original:  "the quick fox"          added:  "brown "
+//!
+//!   pieces:    original 0..10  added 0..6  original 10..13
+//!   document:  "the quick "    "brown "    "fox"
+//! ```
+//!
+//! Inserting appends to the added buffer and splits the piece at the insertion point
+//! around a new piece. Deleting splits the pieces at both ends and drops the ones between.
+//! Neither touches the text itself, only the list. Here the list is a [`FingerTree`]
+//! measured by how long each piece is, so finding the piece at an offset, and splitting the
+//! list there, is O(log p) for p pieces.
+//!
+//! Since the buffers only ever grow, every piece ever made stays valid, and snapshotting
+//! the document is just keeping a copy of the list. A finger tree is persistent, so that
+//! copy is O(1) and shares almost everything with the live list. Restoring a snapshot is
+//! putting the copy back, which makes undo cheap at any depth: text that's been deleted is
+//! still there in the buffers for the old pieces to point at.
+//!
+//! Compared to [`crate::rope::Rope`], the pieces are cheaper to edit, since none of the
+//! text gets moved or copied, and snapshots come for free. The rope keeps the text in
+//! order and indexes it by chars and lines as well, where this only knows byte offsets.
+
+use crate::finger_tree::{FingerTree, Measured, Size};
+use std::fmt;
+use std::ops::{Bound, RangeBounds};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Source {
+    Original,
+    Added,
+}
+
+/// A range of one of the buffers. Never empty.
+#[derive(Clone, Debug)]
+struct Piece {
+    source: Source,
+    start: usize,
+    len: usize,
+}
+
+impl Measured for Piece {
+    type Measure = Size;
+
+    fn measure(&self) -> Size {
+        Size(self.len)
+    }
+}
+
+pub struct PieceTable {
+    original: String,
+    added: String,
+    pieces: FingerTree<Piece>,
+}
+
+/// A saved state of a [`PieceTable`], to go back to with
+/// [`restore`](PieceTable::restore).
+#[derive(Clone)]
+pub struct Snapshot {
+    pieces: FingerTree<Piece>,
+}
+
+impl PieceTable {
+    /// Creates an empty PieceTable.
+    pub fn new() -> Self {
+        PieceTable::from(String::new())
+    }
+
+    /// The length of the document in bytes.
+    pub fn len(&self) -> usize {
+        self.pieces.measure().0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pieces.is_empty()
+    }
+
+    fn text(&self, piece: &Piece) -> &str {
+        let buffer = match piece.source {
+            Source::Original => &self.original,
+            Source::Added => &self.added,
+        };
+        &buffer[piece.start..piece.start + piece.len]
+    }
+
+    /// Splits the pieces into those before byte `offset` and those from it on, cutting
+    /// the piece that straddles it in two.
+    fn split_at(&self, offset: usize) -> (FingerTree<Piece>, FingerTree<Piece>) {
+        assert!(
+            offset <= self.len(),
+            "offset {} out of bounds for length {}",
+            offset,
+            self.len()
+        );
+        let (left, right) = self.pieces.split(|size| size.0 > offset);
+        let cut = offset - left.measure().0;
+        match right.front() {
+            Some(piece) if cut > 0 => {
+                assert!(
+                    self.text(piece).is_char_boundary(cut),
+                    "offset {} is not a char boundary",
+                    offset
+                );
+                let head = Piece { len: cut, ..*piece };
+                let tail = Piece {
+                    start: piece.start + cut,
+                    len: piece.len - cut,
+                    ..*piece
+                };
+                (left.push_back(head), right.pop_front().push_front(tail))
+            }
+            _ => (left, right),
+        }
+    }
+
+    /// Inserts `text` at byte `offset`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `offset` is past the end or not on a char boundary.
+    pub fn insert(&mut self, offset: usize, text: &str) {
+        let (left, right) = self.split_at(offset);
+        if text.is_empty() {
+            return;
+        }
+        let start = self.added.len();
+        self.added.push_str(text);
+        // Typing one char after another keeps growing the same piece
+        let left = match left.back() {
+            Some(last) if last.source == Source::Added && last.start + last.len == start => {
+                let last = Piece {
+                    len: last.len + text.len(),
+                    ..*last
+                };
+                left.pop_back().push_back(last)
+            }
+            _ => left.push_back(Piece {
+                source: Source::Added,
+                start,
+                len: text.len(),
+            }),
+        };
+        self.pieces = left.concat(&right);
+    }
+
+    /// Deletes the bytes in `range`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range starts after it ends, ends past the end of the document, or
+    /// either end isn't on a char boundary.
+    pub fn delete<R: RangeBounds<usize>>(&mut self, range: R) {
+        let start = match range.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&end) => end + 1,
+            Bound::Excluded(&end) => end,
+            Bound::Unbounded => self.len(),
+        };
+        assert!(
+            start <= end,
+            "range starts at {} but ends at {}",
+            start,
+            end
+        );
+        let (_, right) = self.split_at(end);
+        let (left, _) = self.split_at(start);
+        self.pieces = left.concat(&right);
+    }
+
+    /// Takes a snapshot of the document as it is now. O(1).
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            pieces: self.pieces.clone(),
+        }
+    }
+
+    /// Puts the document back the way it was when `snapshot` was taken. O(1). The
+    /// snapshot must come from this table: its pieces point into this table's buffers.
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.pieces = snapshot.pieces.clone();
+    }
+
+    /// Iterates over the pieces' texts, in order.
+    pub fn chunks(&self) -> impl Iterator<Item = &str> {
+        self.pieces.iter().map(move |piece| self.text(piece))
+    }
+}
+
+impl Default for PieceTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<String> for PieceTable {
+    fn from(original: String) -> Self {
+        let mut pieces = FingerTree::new();
+        if !original.is_empty() {
+            pieces = pieces.push_back(Piece {
+                source: Source::Original,
+                start: 0,
+                len: original.len(),
+            });
+        }
+        PieceTable {
+            original,
+            added: String::new(),
+            pieces,
+        }
+    }
+}
+
+impl From<&str> for PieceTable {
+    fn from(original: &str) -> Self {
+        PieceTable::from(original.to_owned())
+    }
+}
+
+impl fmt::Display for PieceTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.chunks().try_for_each(|chunk| f.write_str(chunk))
+    }
+}
+
+impl fmt::Debug for PieceTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.to_string(), f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::PieceTable;
+
+    /// Checks no piece is empty and the cached length adds up.
+    fn check(table: &PieceTable) {
+        assert!(table.pieces.iter().all(|piece| piece.len > 0));
+        assert_eq!(table.chunks().map(str::len).sum::<usize>(), table.len());
+    }
+
+    #[test]
+    fn basics() {
+        let mut table = PieceTable::new();
+
+        // Check empty table behaves right
+        assert!(table.is_empty());
+        assert_eq!(table.chunks().next(), None);
+        table.delete(..);
+        assert_eq!(table.to_string(), "");
+
+        // Populate table
+        let mut table = PieceTable::from("the quick fox");
+        table.insert(10, "brown ");
+        assert_eq!(table.to_string(), "the quick brown fox");
+        assert_eq!(
+            table.chunks().collect::<Vec<_>>(),
+            ["the quick ", "brown ", "fox"]
+        );
+
+        // Check normal removal
+        table.delete(4..10);
+        assert_eq!(table.to_string(), "the brown fox");
+        table.delete(..=3);
+        assert_eq!(table.to_string(), "brown fox");
+
+        // Push some more just to make sure nothing's corrupted
+        table.insert(9, " jumps");
+        table.insert(0, "a ");
+        check(&table);
+        assert_eq!(table.to_string(), "a brown fox jumps");
+        assert_eq!(table.len(), 17);
+
+        // Check exhaustion
+        table.delete(..);
+        assert!(table.is_empty());
+    }
+
+    #[test]
+    fn typing_grows_one_piece() {
+        let mut table = PieceTable::from("ab");
+        for (i, c) in ["x", "y", "z", "é"].iter().enumerate() {
+            table.insert(1 + i, c);
+        }
+        assert_eq!(table.to_string(), "axyzéb");
+        assert_eq!(table.pieces.iter().count(), 3);
+
+        // Moving somewhere else starts a new one
+        table.insert(0, "!");
+        assert_eq!(table.pieces.iter().count(), 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "not a char boundary")]
+    fn mid_char_offset() {
+        PieceTable::from("aé").insert(2, "x");
+    }
+
+    #[test]
+    fn snapshots() {
+        let mut table = PieceTable::from("hello");
+        let mut history = vec![table.snapshot()];
+        table.insert(5, " world");
+        history.push(table.snapshot());
+        table.delete(0..6);
+        history.push(table.snapshot());
+        table.insert(0, "big ");
+        assert_eq!(table.to_string(), "big world");
+
+        // Undo all the way, then redo, each a single O(1) restore
+        table.restore(&history[0]);
+        assert_eq!(table.to_string(), "hello");
+        table.restore(&history[2]);
+        assert_eq!(table.to_string(), "world");
+        table.restore(&history[1]);
+        assert_eq!(table.to_string(), "hello world");
+
+        // Editing after going back leaves the other snapshots alone
+        table.insert(5, ",");
+        assert_eq!(table.to_string(), "hello, world");
+        table.restore(&history[2]);
+        assert_eq!(table.to_string(), "world");
+    }
+
+    #[test]
+    fn against_string() {
+        // Random edits, with random restores of earlier snapshots and their own models
+        let alphabet = ["a", "b", " ", "\n", "é", "字"];
+        let mut table = PieceTable::new();
+        let mut model = String::new();
+        let mut history = vec![(table.snapshot(), model.clone())];
+        let mut x: u32 = 1;
+        let n = if cfg!(miri) { 200 } else { 5_000 };
+        for _ in 0..n {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            // A random char boundary in the model
+            let mut at = (x >> 8) as usize % (model.len() + 1);
+            while !model.is_char_boundary(at) {
+                at -= 1;
+            }
+            match x >> 29 {
+                0..=3 => {
+                    let text = alphabet[(x >> 4) as usize % 6].repeat(1 + (x >> 12) as usize % 16);
+                    table.insert(at, &text);
+                    model.insert_str(at, &text);
+                }
+                4 | 5 => {
+                    let mut end = (at + (x >> 3) as usize % 20).min(model.len());
+                    while !model.is_char_boundary(end) {
+                        end += 1;
+                    }
+                    table.delete(at..end);
+                    model.replace_range(at..end, "");
+                }
+                6 => history.push((table.snapshot(), model.clone())),
+                _ => {
+                    let (snapshot, old) = &history[(x >> 4) as usize % history.len()];
+                    table.restore(snapshot);
+                    model = old.clone();
+                }
+            }
+            check(&table);
+            assert_eq!(table.len(), model.len());
+            assert_eq!(table.to_string(), model);
+        }
+    }
+}
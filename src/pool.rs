@@ -0,0 +1,284 @@
+//! # Object pool
+//!
+//! Keeps objects that are expensive to build, such as buffers, connections or big
+//! structs, around for reuse instead of dropping them. Taking from the pool hands out a
+//! [`Pooled`] guard that derefs to the object, and dropping the guard puts the object back
+//! on the pool's free list for the next taker:
+//!
+//! ```text
+//!   free: [ a  b  c ]      let x = pool.try_get();   free: [ a  b ]     x -> c
+//!                          drop(x);                  free: [ a  b  c ]
+//! ```
+//!
+//! The free list is a stack, so the most recently returned object, the one most likely
+//! still in cache, is the next one out. It holds at most `max_size` objects. Anything
+//! returned to a full pool is dropped, so a burst of demand doesn't leave the pool holding
+//! on to memory forever.
+//!
+//! Objects come back as they were left. If they need resetting, clearing a buffer say, do
+//! that after taking one, or before dropping the guard.
+//!
+//! The free list sits behind a `Mutex`, so one pool can be shared by many threads.
+
+use std::fmt;
+use std::mem::ManuallyDrop;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Mutex, MutexGuard};
+
+pub struct Pool<T> {
+    free: Mutex<Vec<T>>,
+    max_size: usize,
+}
+
+impl<T> Pool<T> {
+    /// Creates an empty Pool that keeps at most `max_size`
+    /// objects for reuse.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_size` is 0.
+    pub fn new(max_size: usize) -> Self {
+        assert!(max_size > 0, "max size must be at least 1");
+        Pool {
+            free: Mutex::new(Vec::new()),
+            max_size,
+        }
+    }
+
+    fn free(&self) -> MutexGuard<'_, Vec<T>> {
+        // A panic while holding the lock can't leave the Vec half-updated, so carry on
+        self.free.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn max_size(&self) -> usize {
+        self.max_size
+    }
+
+    /// Returns the number of objects waiting on the free list.
+    pub fn available(&self) -> usize {
+        self.free().len()
+    }
+
+    /// Fills the free list up with `count` objects built by `init`, or as many as fit under
+    /// `max_size`. Returns how many were added.
+    pub fn populate_with(&self, count: usize, mut init: impl FnMut() -> T) -> usize {
+        let mut free = self.free();
+        let added = count.min(self.max_size - free.len());
+        free.extend((0..added).map(|_| init()));
+        added
+    }
+
+    /// Takes an object off the free list, if there is one.
+    pub fn try_get(&self) -> Option<Pooled<'_, T>> {
+        let obj = self.free().pop()?;
+        Some(Pooled::new(self, obj))
+    }
+
+    /// Takes an object off the free list, or builds a new one with `init` when it's empty.
+    /// Either way it goes back to this pool when the guard drops.
+    pub fn get_with(&self, init: impl FnOnce() -> T) -> Pooled<'_, T> {
+        let obj = self.free().pop().unwrap_or_else(init);
+        Pooled::new(self, obj)
+    }
+
+    /// Puts an object on the free list, or drops it if the pool is full. Returns whether
+    /// it was kept.
+    pub fn put(&self, obj: T) -> bool {
+        let mut free = self.free();
+        if free.len() < self.max_size {
+            free.push(obj);
+            true
+        } else {
+            // Drop it outside the lock, it may take a while
+            drop(free);
+            drop(obj);
+            false
+        }
+    }
+
+    /// Drops every object on the free list.
+    pub fn clear(&self) {
+        let objs = std::mem::take(&mut *self.free());
+        drop(objs);
+    }
+}
+
+impl<T: Default> Pool<T> {
+    /// Takes an object off the free list, or builds a default one when it's empty.
+    pub fn get(&self) -> Pooled<'_, T> {
+        self.get_with(T::default)
+    }
+}
+
+impl<T> fmt::Debug for Pool<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pool")
+            .field("available", &self.available())
+            .field("max_size", &self.max_size)
+            .finish()
+    }
+}
+
+/// An object on loan from a [`Pool`], which goes back when this drops.
+pub struct Pooled<'a, T> {
+    pool: &'a Pool<T>,
+    // Taken out in `drop` or `detach`, and never touched again after that
+    obj: ManuallyDrop<T>,
+}
+
+impl<'a, T> Pooled<'a, T> {
+    fn new(pool: &'a Pool<T>, obj: T) -> Self {
+        Pooled {
+            pool,
+            obj: ManuallyDrop::new(obj),
+        }
+    }
+
+    /// Takes the object out for good, so it never goes back to the pool.
+    pub fn detach(this: Self) -> T {
+        let mut this = ManuallyDrop::new(this);
+        // `this` is never dropped, so the object is taken exactly once
+        unsafe { ManuallyDrop::take(&mut this.obj) }
+    }
+}
+
+impl<T> Drop for Pooled<'_, T> {
+    fn drop(&mut self) {
+        // Dropping the guard is the last use of `obj`
+        let obj = unsafe { ManuallyDrop::take(&mut self.obj) };
+        self.pool.put(obj);
+    }
+}
+
+impl<T> Deref for Pooled<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.obj
+    }
+}
+
+impl<T> DerefMut for Pooled<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.obj
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Pooled<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Pool, Pooled};
+    use std::rc::Rc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn basics() {
+        let pool: Pool<Vec<u8>> = Pool::new(4);
+
+        // Check empty pool behaves right
+        assert!(pool.try_get().is_none());
+        assert_eq!(pool.available(), 0);
+
+        // Populate pool, only up to the max size
+        let mut built = 0;
+        let added = pool.populate_with(10, || {
+            built += 1;
+            Vec::with_capacity(64)
+        });
+        assert_eq!((added, built), (4, 4));
+        assert_eq!(pool.available(), 4);
+
+        // Check objects go back when their guards drop, as they were left
+        {
+            let mut a = pool.try_get().unwrap();
+            let b = pool.get();
+            assert_eq!(pool.available(), 2);
+            a.extend_from_slice(b"hello");
+            assert_eq!(b.capacity(), 64);
+        }
+        assert_eq!(pool.available(), 4);
+        let a = pool.try_get().unwrap();
+        assert_eq!(&a[..], b"hello");
+        drop(a);
+
+        // Check exhaustion, then new ones get built on demand
+        let taken: Vec<_> = (0..4).map(|_| pool.try_get().unwrap()).collect();
+        assert!(pool.try_get().is_none());
+        let fresh = pool.get_with(|| vec![7]);
+        assert_eq!(*fresh, [7]);
+        drop(taken);
+        assert_eq!(pool.available(), 4);
+
+        // The pool is full, so this one is dropped rather than kept
+        drop(fresh);
+        assert_eq!(pool.available(), 4);
+        pool.clear();
+        assert_eq!(pool.available(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "max size must be at least 1")]
+    fn zero_max_size() {
+        Pool::<u32>::new(0);
+    }
+
+    #[test]
+    fn detach_and_drops() {
+        let token = Rc::new(());
+        let pool = Pool::new(2);
+        pool.populate_with(2, || token.clone());
+        assert_eq!(Rc::strong_count(&token), 3);
+
+        // Detached objects leave the pool for good
+        let kept = Pooled::detach(pool.try_get().unwrap());
+        assert_eq!(pool.available(), 1);
+        drop(kept);
+        assert_eq!(Rc::strong_count(&token), 2);
+
+        // Surplus past the max size is dropped
+        assert!(pool.put(token.clone()));
+        assert!(!pool.put(token.clone()));
+        assert_eq!(Rc::strong_count(&token), 3);
+        drop(pool);
+        assert_eq!(Rc::strong_count(&token), 1);
+    }
+
+    #[test]
+    fn shared_between_threads() {
+        let per_thread = if cfg!(miri) { 50 } else { 10_000 };
+        let threads = 4;
+        let pool = Arc::new(Pool::new(threads));
+        let built = Arc::new(AtomicUsize::new(0));
+
+        let mut handles = Vec::new();
+        for _ in 0..threads {
+            let pool = pool.clone();
+            let built = built.clone();
+            handles.push(thread::spawn(move || {
+                for i in 0..per_thread {
+                    let mut buf = pool.get_with(|| {
+                        built.fetch_add(1, Ordering::SeqCst);
+                        Vec::new()
+                    });
+                    buf.clear();
+                    buf.push(i);
+                    assert_eq!(*buf, [i]);
+                }
+            }));
+        }
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        // Each thread holds one object at a time, so no more than that were ever built
+        assert!(built.load(Ordering::SeqCst) <= threads);
+        assert_eq!(pool.available(), built.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,468 @@
+//! # Quadtree
+//!
+//! Points in the plane, each with a value, in a square-ish region that's cut into four
+//! quarters wherever too many points fall, and the quarters into quarters, and so on
+//! (Finkel and Bentley, 1974). This is the bucketed, region-splitting kind: every leaf
+//! holds up to a bucket's worth of points, and a leaf that gets one more splits at the
+//! middle of its region, whatever the points are.
+//!
+//! ```text
+//!   +-----------+-----+-----+
+//!   |           |  .  |   . |
+//!   |     .     +-----+-----+        each leaf holds at most
+//!   |           | . . |     |        `bucket` points, here 2
+//!   +-----+-----+-----+-----+
+//!   |  .  |     |           |
+//!   +-----+-----+     .     |
+//!   | . . |  .  |           |
+//!   +-----+-----+-----------+
+//! ```
+//!
+//! A range query goes only into the quarters that overlap the rectangle asked about, and
+//! a nearest-neighbor search goes into the closest quarter first, then skips any that are
+//! further away than the best point found so far. Both are fast when the points are spread
+//! out, though neither has a worst-case bound: a tight cluster makes a deep tree. Many
+//! copies of one point would split forever, so there's a limit on the depth, below which
+//! leaves just grow.
+//!
+//! The region is fixed when the tree is made, and points outside it are turned away.
+
+use std::mem;
+
+/// A point in the plane.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Point {
+    pub fn new(x: f64, y: f64) -> Self {
+        Point { x, y }
+    }
+
+    pub fn distance_squared(&self, other: &Point) -> f64 {
+        let (dx, dy) = (self.x - other.x, self.y - other.y);
+        dx * dx + dy * dy
+    }
+}
+
+/// An axis-aligned rectangle, edges included.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Rect {
+    pub min: Point,
+    pub max: Point,
+}
+
+impl Rect {
+    pub fn new(min: Point, max: Point) -> Self {
+        Rect { min, max }
+    }
+
+    pub fn contains(&self, point: &Point) -> bool {
+        self.min.x <= point.x
+            && point.x <= self.max.x
+            && self.min.y <= point.y
+            && point.y <= self.max.y
+    }
+
+    pub fn intersects(&self, other: &Rect) -> bool {
+        self.min.x <= other.max.x
+            && other.min.x <= self.max.x
+            && self.min.y <= other.max.y
+            && other.min.y <= self.max.y
+    }
+
+    /// Returns whether all of `other` is inside this rectangle.
+    pub fn contains_rect(&self, other: &Rect) -> bool {
+        self.contains(&other.min) && self.contains(&other.max)
+    }
+
+    pub fn area(&self) -> f64 {
+        (self.max.x - self.min.x) * (self.max.y - self.min.y)
+    }
+
+    /// Returns the smallest rectangle that covers both.
+    pub fn union(&self, other: &Rect) -> Rect {
+        Rect::new(
+            Point::new(self.min.x.min(other.min.x), self.min.y.min(other.min.y)),
+            Point::new(self.max.x.max(other.max.x), self.max.y.max(other.max.y)),
+        )
+    }
+
+    /// Returns the squared distance from `point` to the nearest point of the rectangle,
+    /// zero if it's inside.
+    pub fn distance_squared(&self, point: &Point) -> f64 {
+        let dx = (self.min.x - point.x).max(point.x - self.max.x).max(0.0);
+        let dy = (self.min.y - point.y).max(point.y - self.max.y).max(0.0);
+        dx * dx + dy * dy
+    }
+
+    fn center(&self) -> Point {
+        Point::new(
+            (self.min.x + self.max.x) / 2.0,
+            (self.min.y + self.max.y) / 2.0,
+        )
+    }
+
+    /// Which quarter `point` is in: bit 0 for the right half, bit 1 for the top.
+    fn quarter_of(&self, point: &Point) -> usize {
+        let center = self.center();
+        (point.x >= center.x) as usize | ((point.y >= center.y) as usize) << 1
+    }
+
+    fn quarter(&self, i: usize) -> Rect {
+        let center = self.center();
+        let (min_x, max_x) = match i & 1 {
+            0 => (self.min.x, center.x),
+            _ => (center.x, self.max.x),
+        };
+        let (min_y, max_y) = match i & 2 {
+            0 => (self.min.y, center.y),
+            _ => (center.y, self.max.y),
+        };
+        Rect::new(Point::new(min_x, min_y), Point::new(max_x, max_y))
+    }
+}
+
+// Deep enough to tell apart any two points an f64 can, in a region of sensible size
+const MAX_DEPTH: usize = 48;
+
+const DEFAULT_BUCKET: usize = 8;
+
+enum Node<T> {
+    Leaf(Vec<(Point, T)>),
+    Branch(Box<[Node<T>; 4]>),
+}
+
+impl<T> Node<T> {
+    /// Turns a full leaf's points into a branch, splitting again any quarter that's still
+    /// too full, which is bounded by the depth limit.
+    fn split(items: Vec<(Point, T)>, bounds: &Rect, bucket: usize, depth: usize) -> Self {
+        if items.len() <= bucket || depth >= MAX_DEPTH {
+            return Node::Leaf(items);
+        }
+        let mut quarters = [(); 4].map(|_| Vec::new());
+        for (point, value) in items {
+            quarters[bounds.quarter_of(&point)].push((point, value));
+        }
+        let mut i = 0;
+        Node::Branch(Box::new(quarters.map(|items| {
+            let node = Node::split(items, &bounds.quarter(i), bucket, depth + 1);
+            i += 1;
+            node
+        })))
+    }
+}
+
+pub struct Quadtree<T> {
+    bounds: Rect,
+    bucket: usize,
+    root: Node<T>,
+    len: usize,
+}
+
+impl<T> Quadtree<T> {
+    /// Creates an empty Quadtree over `bounds`.
+    pub fn new(bounds: Rect) -> Self {
+        Self::with_bucket_size(bounds, DEFAULT_BUCKET)
+    }
+
+    /// Returns an empty tree over `bounds` whose leaves split once they hold more than
+    /// `bucket` points.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bucket` is zero.
+    pub fn with_bucket_size(bounds: Rect, bucket: usize) -> Self {
+        assert!(bucket > 0, "bucket size must be at least one");
+        Quadtree {
+            bounds,
+            bucket,
+            root: Node::Leaf(Vec::new()),
+            len: 0,
+        }
+    }
+
+    pub fn bounds(&self) -> Rect {
+        self.bounds
+    }
+
+    pub fn bucket_size(&self) -> usize {
+        self.bucket
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.root = Node::Leaf(Vec::new());
+        self.len = 0;
+    }
+
+    /// Adds a point with its value. A point outside the bounds, or with a NaN coordinate,
+    /// is handed back.
+    pub fn insert(&mut self, point: Point, value: T) -> Result<(), (Point, T)> {
+        if !self.bounds.contains(&point) {
+            return Err((point, value));
+        }
+        let mut node = &mut self.root;
+        let mut bounds = self.bounds;
+        let mut depth = 0;
+        while let Node::Branch(children) = node {
+            let i = bounds.quarter_of(&point);
+            bounds = bounds.quarter(i);
+            node = &mut children[i];
+            depth += 1;
+        }
+        if let Node::Leaf(items) = node {
+            items.push((point, value));
+            if items.len() > self.bucket {
+                let items = mem::take(items);
+                *node = Node::split(items, &bounds, self.bucket, depth);
+            }
+        }
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Iterates over the points inside `area`, edges included, in no particular order.
+    pub fn range(&self, area: Rect) -> Range<'_, T> {
+        Range {
+            area,
+            stack: vec![(&self.root, self.bounds)],
+            items: [].iter(),
+        }
+    }
+
+    /// Iterates over every point, in no particular order.
+    pub fn iter(&self) -> Range<'_, T> {
+        self.range(self.bounds)
+    }
+
+    /// Returns the point closest to `target`, and its value. `target` needn't be inside
+    /// the bounds. Of points equally close, any one might be returned.
+    pub fn nearest(&self, target: Point) -> Option<(Point, &T)> {
+        let mut best: Option<(f64, &(Point, T))> = None;
+        let mut stack = vec![(&self.root, self.bounds)];
+        while let Some((node, bounds)) = stack.pop() {
+            if best.is_some_and(|(d, _)| bounds.distance_squared(&target) >= d) {
+                continue;
+            }
+            match node {
+                Node::Leaf(items) => {
+                    for item in items {
+                        let d = item.0.distance_squared(&target);
+                        if best.is_none_or(|(b, _)| d < b) {
+                            best = Some((d, item));
+                        }
+                    }
+                }
+                Node::Branch(children) => {
+                    // Closest quarter last, so that it comes off the stack first
+                    let mut quarters: Vec<_> = children
+                        .iter()
+                        .enumerate()
+                        .map(|(i, child)| (child, bounds.quarter(i)))
+                        .collect();
+                    quarters.sort_by(|(_, a), (_, b)| {
+                        let (a, b) = (a.distance_squared(&target), b.distance_squared(&target));
+                        b.partial_cmp(&a).unwrap()
+                    });
+                    stack.extend(quarters);
+                }
+            }
+        }
+        best.map(|(_, (point, value))| (*point, value))
+    }
+}
+
+/// Iterator over the points in a rectangle, from [`Quadtree::range`].
+pub struct Range<'a, T> {
+    area: Rect,
+    // Nodes still to look in, and their regions
+    stack: Vec<(&'a Node<T>, Rect)>,
+    items: std::slice::Iter<'a, (Point, T)>,
+}
+
+impl<'a, T> Iterator for Range<'a, T> {
+    type Item = (Point, &'a T);
+
+    fn next(&mut self) -> Option<(Point, &'a T)> {
+        loop {
+            for (point, value) in self.items.by_ref() {
+                if self.area.contains(point) {
+                    return Some((*point, value));
+                }
+            }
+            let (node, bounds) = self.stack.pop()?;
+            match node {
+                Node::Leaf(items) => self.items = items.iter(),
+                Node::Branch(children) => {
+                    for (i, child) in children.iter().enumerate() {
+                        let quarter = bounds.quarter(i);
+                        if quarter.intersects(&self.area) {
+                            self.stack.push((child, quarter));
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Node, Point, Quadtree, Rect, MAX_DEPTH};
+
+    fn unit(size: f64) -> Rect {
+        Rect::new(Point::new(0.0, 0.0), Point::new(size, size))
+    }
+
+    /// Checks every point sits in its leaf's region, leaves hold no more than a bucket
+    /// unless at the depth limit, and the count matches. Returns the depth.
+    fn check<T>(tree: &Quadtree<T>) -> usize {
+        let mut stack = vec![(&tree.root, tree.bounds, 0)];
+        let (mut count, mut deepest) = (0, 0);
+        while let Some((node, bounds, depth)) = stack.pop() {
+            deepest = deepest.max(depth);
+            match node {
+                Node::Leaf(items) => {
+                    assert!(items.len() <= tree.bucket || depth == MAX_DEPTH);
+                    assert!(items.iter().all(|(p, _)| bounds.contains(p)));
+                    count += items.len();
+                }
+                Node::Branch(children) => {
+                    for (i, child) in children.iter().enumerate() {
+                        stack.push((child, bounds.quarter(i), depth + 1));
+                    }
+                }
+            }
+        }
+        assert_eq!(count, tree.len());
+        deepest
+    }
+
+    #[test]
+    fn basics() {
+        let mut tree = Quadtree::with_bucket_size(unit(100.0), 2);
+
+        // Check empty tree behaves right
+        assert!(tree.is_empty());
+        assert_eq!(tree.nearest(Point::new(5.0, 5.0)), None);
+        assert_eq!(tree.iter().count(), 0);
+
+        // Populate tree
+        let points = [
+            (10.0, 10.0),
+            (90.0, 90.0),
+            (15.0, 20.0),
+            (60.0, 30.0),
+            (100.0, 0.0),
+        ];
+        for (i, &(x, y)) in points.iter().enumerate() {
+            assert!(tree.insert(Point::new(x, y), i).is_ok());
+        }
+        assert_eq!(tree.len(), 5);
+        check(&tree);
+
+        // Outside, or not a number, is turned away
+        assert_eq!(
+            tree.insert(Point::new(-1.0, 5.0), 9),
+            Err((Point::new(-1.0, 5.0), 9))
+        );
+        assert!(tree.insert(Point::new(f64::NAN, 5.0), 9).is_err());
+        assert_eq!(tree.len(), 5);
+
+        // Ranges, edges included
+        let area = Rect::new(Point::new(0.0, 0.0), Point::new(60.0, 30.0));
+        let mut found: Vec<_> = tree.range(area).map(|(_, &i)| i).collect();
+        found.sort();
+        assert_eq!(found, [0, 2, 3]);
+        assert_eq!(tree.iter().count(), 5);
+
+        // Nearest, from inside and out
+        assert_eq!(
+            tree.nearest(Point::new(12.0, 14.0)),
+            Some((Point::new(10.0, 10.0), &0))
+        );
+        assert_eq!(
+            tree.nearest(Point::new(200.0, 200.0)).map(|(_, &i)| i),
+            Some(1)
+        );
+        assert_eq!(
+            tree.nearest(Point::new(99.0, -5.0)).map(|(_, &i)| i),
+            Some(4)
+        );
+
+        // Check exhaustion
+        tree.clear();
+        assert!(tree.is_empty());
+        assert_eq!(tree.nearest(Point::new(5.0, 5.0)), None);
+    }
+
+    #[test]
+    fn duplicates() {
+        // The same point over and over stops splitting at the depth limit
+        let mut tree = Quadtree::with_bucket_size(unit(1.0), 1);
+        for i in 0..100 {
+            tree.insert(Point::new(0.3, 0.3), i).unwrap();
+        }
+        assert_eq!(check(&tree), MAX_DEPTH);
+        assert_eq!(tree.range(unit(0.5)).count(), 100);
+        assert_eq!(
+            tree.nearest(Point::new(1.0, 1.0)).unwrap().0,
+            Point::new(0.3, 0.3)
+        );
+    }
+
+    #[test]
+    fn against_brute_force() {
+        // Random points, for several bucket sizes, against looking at every one
+        let mut x: u32 = 1;
+        let mut next = || {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (x >> 8) as f64 / (1 << 24) as f64 * 1000.0
+        };
+        let n = if cfg!(miri) { 100 } else { 2_000 };
+        for &bucket in &[1, 4, 16] {
+            let mut tree = Quadtree::with_bucket_size(unit(1000.0), bucket);
+            let mut model = Vec::new();
+            for i in 0..n {
+                // Some on a coarse grid, so that there are ties and repeats
+                let point = match i % 3 {
+                    0 => Point::new((next() / 100.0).floor() * 100.0, next()),
+                    _ => Point::new(next(), next()),
+                };
+                tree.insert(point, i).unwrap();
+                model.push(point);
+            }
+            check(&tree);
+
+            for _ in 0..50 {
+                let (a, b) = (Point::new(next(), next()), Point::new(next(), next()));
+                let area = Rect::new(
+                    Point::new(a.x.min(b.x), a.y.min(b.y)),
+                    Point::new(a.x.max(b.x), a.y.max(b.y)),
+                );
+                let mut found: Vec<_> = tree.range(area).map(|(_, &i)| i).collect();
+                found.sort();
+                let expected: Vec<_> = (0..n).filter(|&i| area.contains(&model[i])).collect();
+                assert_eq!(found, expected);
+
+                let (point, &i) = tree.nearest(a).unwrap();
+                assert_eq!(model[i], point);
+                let closest = model
+                    .iter()
+                    .map(|p| p.distance_squared(&a))
+                    .fold(f64::INFINITY, f64::min);
+                assert_eq!(point.distance_squared(&a), closest);
+            }
+        }
+    }
+}
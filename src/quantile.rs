@@ -0,0 +1,299 @@
+//! # Quantile sketch
+//!
+//! Answers "what's the median", "what's the 99th percentile" over a stream too long to keep,
+//! to within a chosen rank error `ε`: asked for quantile `q` of `n` values, it returns a
+//! value whose rank among them is within `εn` of `⌈qn⌉`. The algorithm is Greenwald and
+//! Khanna's (2001), which guarantees that bound for any input and any order, where
+//! sampling would only make it likely.
+//!
+//! It keeps a sorted list of some of the values seen, each with two numbers that pin down
+//! where it ranks among all of them:
+//!
+//! - `g`: its lowest possible rank, minus that of the entry before it;
+//! - `delta`: how much higher than the lowest its rank might be.
+//!
+//! ```text
+//!   value   3    9    14   20   31
+//!   g       1    2    3    1    2       rank of 14 is between 1 + 2 + 3 = 6
+//!   delta   0    1    2    1    0                         and 6 + 2 = 8
+//! ```
+//!
+//! A new value goes in at its sorted place with `g = 1`, and a `delta` as uncertain as the
+//! bound allows, `⌊2εn⌋ - 1`, since its `g` counts towards the bound too. Every so often,
+//! neighbouring entries whose combined uncertainty still fits in `2εn` are merged,
+//! forgetting the smaller value. A query walks the list adding up `g` until it finds an
+//! entry whose rank range is settled enough.
+//!
+//! The smallest and largest values are never merged away, so `min` and `max` are exact.
+//! Greenwald and Khanna bound the list at O((1 / ε) log(εn)) entries with a more careful
+//! merge order. This uses the simpler merge most implementations use, which keeps the
+//! error guarantee and stays small in practice without the proof.
+
+#[derive(Clone, Debug)]
+pub struct QuantileSketch<T> {
+    entries: Vec<Entry<T>>,
+    epsilon: f64,
+    len: usize,
+    // Inserts since the last compress
+    since_compress: usize,
+}
+
+#[derive(Clone, Debug)]
+struct Entry<T> {
+    value: T,
+    g: usize,
+    delta: usize,
+}
+
+impl<T: Ord> QuantileSketch<T> {
+    /// Creates an empty sketch whose answers rank within `epsilon * len` of the true ones.
+    ///
+    /// # Panics
+    ///
+    /// Panics unless `0 < epsilon < 1`.
+    pub fn new(epsilon: f64) -> Self {
+        assert!(
+            epsilon > 0.0 && epsilon < 1.0,
+            "epsilon must be between 0 and 1"
+        );
+        QuantileSketch {
+            entries: Vec::new(),
+            epsilon,
+            len: 0,
+            since_compress: 0,
+        }
+    }
+
+    pub fn epsilon(&self) -> f64 {
+        self.epsilon
+    }
+
+    /// Returns the number of values inserted.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of values the sketch is holding on to.
+    pub fn stored(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// How uncertain an entry's rank is allowed to get.
+    fn threshold(&self) -> usize {
+        (2.0 * self.epsilon * self.len as f64) as usize
+    }
+
+    pub fn insert(&mut self, value: T) {
+        let i = self.entries.partition_point(|e| e.value <= value);
+        // The new smallest or largest value knows its rank exactly
+        let delta = if i == 0 || i == self.entries.len() {
+            0
+        } else {
+            self.threshold().saturating_sub(1)
+        };
+        self.entries.insert(i, Entry { value, g: 1, delta });
+        self.len += 1;
+
+        self.since_compress += 1;
+        if self.since_compress as f64 >= 1.0 / (2.0 * self.epsilon) {
+            self.compress();
+            self.since_compress = 0;
+        }
+    }
+
+    /// Merges each entry into the one after it where their uncertainty allows. The first
+    /// entry stays, to keep the minimum.
+    fn compress(&mut self) {
+        if self.entries.len() < 3 {
+            return;
+        }
+        let threshold = self.threshold();
+        let mut entries = std::mem::take(&mut self.entries).into_iter().rev();
+        let mut kept = Vec::new();
+        let mut right = entries.next().unwrap();
+        let first_left = entries.len() - 1;
+        for (i, left) in entries.enumerate() {
+            if i < first_left && left.g + right.g + right.delta <= threshold {
+                right.g += left.g;
+            } else {
+                kept.push(right);
+                right = left;
+            }
+        }
+        kept.push(right);
+        kept.reverse();
+        self.entries = kept;
+    }
+
+    /// Returns a value whose rank is within `epsilon * len` of `⌈q * len⌉`, or `None` if
+    /// the sketch is empty. `quantile(0.5)` is the median.
+    ///
+    /// # Panics
+    ///
+    /// Panics unless `0 <= q <= 1`.
+    pub fn quantile(&self, q: f64) -> Option<&T> {
+        assert!((0.0..=1.0).contains(&q), "q must be between 0 and 1");
+        // The rank asked for, counting from 1 as the entries do
+        let rank = (q * self.len as f64).ceil();
+        let bound = self.epsilon * self.len as f64;
+        let mut min_rank = 0;
+        for (i, entry) in self.entries.iter().enumerate() {
+            min_rank += entry.g;
+            // This one might rank too high, so the one before must be close enough
+            if (min_rank + entry.delta) as f64 > rank + bound {
+                return Some(&self.entries[i.saturating_sub(1)].value);
+            }
+        }
+        self.entries.last().map(|e| &e.value)
+    }
+
+    pub fn min(&self) -> Option<&T> {
+        self.entries.first().map(|e| &e.value)
+    }
+
+    pub fn max(&self) -> Option<&T> {
+        self.entries.last().map(|e| &e.value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::QuantileSketch;
+
+    /// Checks every percentile of a sketch over a permutation of `0..n`, where each value
+    /// is its own rank.
+    fn check_ranks(sketch: &QuantileSketch<u32>, n: usize) {
+        let bound = sketch.epsilon() * n as f64;
+        for percent in 0..=100 {
+            let q = percent as f64 / 100.0;
+            let value = *sketch.quantile(q).unwrap() as f64;
+            let error = (value - q * n as f64).abs();
+            // One extra for the off-by-one between ranks 0..n and 1..=n
+            assert!(
+                error <= bound + 1.0,
+                "q = {}: {} is off by {}",
+                q,
+                value,
+                error
+            );
+        }
+        assert_eq!(sketch.min(), Some(&0));
+        assert_eq!(sketch.max(), Some(&(n as u32 - 1)));
+    }
+
+    #[test]
+    fn basics() {
+        let mut sketch = QuantileSketch::new(0.1);
+
+        // Check empty sketch behaves right
+        assert!(sketch.is_empty());
+        assert_eq!(sketch.quantile(0.5), None);
+        assert_eq!(sketch.min(), None);
+
+        // Small enough that nothing can be merged, so the answers are exact
+        for x in [5, 1, 4, 2, 3] {
+            sketch.insert(x);
+        }
+        assert_eq!(sketch.len(), 5);
+        assert_eq!(sketch.quantile(0.0), Some(&1));
+        assert_eq!(sketch.quantile(0.5), Some(&3));
+        assert_eq!(sketch.quantile(1.0), Some(&5));
+        assert_eq!((sketch.min(), sketch.max()), (Some(&1), Some(&5)));
+    }
+
+    #[test]
+    #[should_panic(expected = "q must be between 0 and 1")]
+    fn q_out_of_range() {
+        QuantileSketch::<u32>::new(0.01).quantile(1.5);
+    }
+
+    #[test]
+    fn random_order() {
+        let n = if cfg!(miri) { 2_000 } else { 100_000 };
+        let mut values: Vec<u32> = (0..n as u32).collect();
+        let mut x: u32 = 1;
+        for i in (1..n).rev() {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            values.swap(i, (x >> 8) as usize % (i + 1));
+        }
+
+        let mut sketch = QuantileSketch::new(0.01);
+        for &value in &values {
+            sketch.insert(value);
+        }
+        check_ranks(&sketch, n);
+        // A few hundred entries stand in for all of them
+        assert!(sketch.stored() < n / 20, "{} stored", sketch.stored());
+    }
+
+    #[test]
+    fn sorted_and_reversed() {
+        let n = if cfg!(miri) { 2_000 } else { 50_000 };
+        let mut ascending = QuantileSketch::new(0.005);
+        let mut descending = QuantileSketch::new(0.005);
+        for value in 0..n as u32 {
+            ascending.insert(value);
+            descending.insert(n as u32 - 1 - value);
+        }
+        check_ranks(&ascending, n);
+        check_ranks(&descending, n);
+    }
+
+    #[test]
+    fn duplicates() {
+        let mut sketch = QuantileSketch::new(0.01);
+        for i in 0..10_000 {
+            sketch.insert(i % 3);
+        }
+        assert_eq!(sketch.quantile(0.1), Some(&0));
+        assert_eq!(sketch.quantile(0.5), Some(&1));
+        assert_eq!(sketch.quantile(0.9), Some(&2));
+    }
+
+    #[test]
+    fn against_sorted() {
+        let n = if cfg!(miri) { 1_000 } else { 20_000 };
+        let mut x: u32 = 1;
+        // Distinct values, then lots of repeats
+        for &range in &[u32::MAX, 100] {
+            // In thousandths, so the bound can be checked exactly
+            for &epsilon in &[100, 10, 3] {
+                let mut sketch = QuantileSketch::new(epsilon as f64 / 1000.0);
+                let mut sorted = Vec::new();
+                for i in 1..=n {
+                    x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                    let value = (x >> 8) % range;
+                    sketch.insert(value);
+                    let at = sorted.partition_point(|&v| v < value);
+                    sorted.insert(at, value);
+
+                    if i % 97 != 0 && i != n {
+                        continue;
+                    }
+                    for percent in 0..=100 {
+                        let q = percent as f64 / 100.0;
+                        let value = *sketch.quantile(q).unwrap();
+                        // Every rank, from 1, that the answer holds, against the rank asked
+                        // for, ⌈qn⌉ (and the smallest for q = 0), all in thousandths
+                        let lo = 1000 * (sorted.partition_point(|&v| v < value) + 1) as i64;
+                        let hi = 1000 * sorted.partition_point(|&v| v <= value) as i64;
+                        let rank = 1000 * (q * i as f64).ceil().max(1.0) as i64;
+                        let error = (lo - rank).max(rank - hi).max(0);
+                        assert!(
+                            error <= epsilon * i as i64,
+                            "epsilon = {}/1000, n = {}, q = {}%: off by {}/1000",
+                            epsilon,
+                            i,
+                            percent,
+                            error
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
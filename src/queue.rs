@@ -0,0 +1,308 @@
+//! # Queue
+//!
+//! A singly-linked FIFO queue with a raw pointer to the tail, so we can `enqueue` at the back
+//! and `dequeue` at the front in O(1).
+//!
+//! `singly_queue` does the same thing with `Box` links plus a raw tail pointer. That mix is
+//! a trap: every time we touch the boxes through `&mut`, Miri's stacked borrows model
+//! invalidates the raw tail pointer we derived earlier, so using it afterwards is undefined
+//! behaviour even though it "works". Here the nodes are owned by raw pointers only, from
+//! `Box::into_raw` to `Box::from_raw`, and every access goes through them. Once raw, stay raw.
+//!
+//! All the `unsafe` stays inside this module; the public API is entirely safe.
+//!
+//! Run the tests under Miri to check the pointer juggling:
+//!
+//! ```text
+//! cargo +nightly miri test queue
+//! ```
+
+use std::ptr;
+
+pub struct Queue<T> {
+    head: Link<T>,
+    tail: Link<T>,
+}
+
+type Link<T> = *mut Node<T>;
+
+struct Node<T> {
+    elem: T,
+    next: Link<T>,
+}
+
+impl<T> Queue<T> {
+    /// Creates an empty Queue.
+    pub fn new() -> Self {
+        Queue {
+            head: ptr::null_mut(),
+            tail: ptr::null_mut(),
+        }
+    }
+
+    /// Adds an element at the back of the queue.
+    pub fn enqueue(&mut self, elem: T) {
+        unsafe {
+            let new_tail = Box::into_raw(Box::new(Node {
+                elem,
+                next: ptr::null_mut(),
+            }));
+
+            if !self.tail.is_null() {
+                // If the old tail existed, update it to point to the new tail
+                (*self.tail).next = new_tail;
+            } else {
+                // Otherwise, update the head to point to it
+                self.head = new_tail;
+            }
+
+            self.tail = new_tail;
+        }
+    }
+
+    /// Removes and returns the element at the front of the queue.
+    pub fn dequeue(&mut self) -> Option<T> {
+        unsafe {
+            if self.head.is_null() {
+                None
+            } else {
+                // Take ownership of the head node again so it gets freed
+                let head = Box::from_raw(self.head);
+                self.head = head.next;
+
+                // If we're out of `head`, make sure the tail doesn't dangle
+                if self.head.is_null() {
+                    self.tail = ptr::null_mut();
+                }
+
+                Some(head.elem)
+            }
+        }
+    }
+
+    /// Borrows the element at the front of the queue.
+    pub fn peek(&self) -> Option<&T> {
+        unsafe { self.head.as_ref().map(|node| &node.elem) }
+    }
+
+    /// Mutably borrows the element at the front of the queue.
+    pub fn peek_mut(&mut self) -> Option<&mut T> {
+        unsafe { self.head.as_mut().map(|node| &mut node.elem) }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.head.is_null()
+    }
+
+    /// Iterates from the front of the queue to the back.
+    pub fn iter(&self) -> Iter<'_, T> {
+        unsafe {
+            Iter {
+                next: self.head.as_ref(),
+            }
+        }
+    }
+
+    /// Mutably iterates from the front of the queue to the back.
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        unsafe {
+            IterMut {
+                next: self.head.as_mut(),
+            }
+        }
+    }
+}
+
+impl<T> Default for Queue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for Queue<T> {
+    fn drop(&mut self) {
+        while self.dequeue().is_some() {}
+    }
+}
+
+pub struct IntoIter<T>(Queue<T>);
+
+pub struct Iter<'a, T> {
+    next: Option<&'a Node<T>>,
+}
+
+pub struct IterMut<'a, T> {
+    next: Option<&'a mut Node<T>>,
+}
+
+impl<T> IntoIterator for Queue<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter(self)
+    }
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.dequeue()
+    }
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        unsafe {
+            self.next.map(|node| {
+                self.next = node.next.as_ref();
+                &node.elem
+            })
+        }
+    }
+}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        unsafe {
+            self.next.take().map(|node| {
+                self.next = node.next.as_mut();
+                &mut node.elem
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Queue;
+
+    #[test]
+    fn basics() {
+        let mut queue = Queue::new();
+
+        // Check empty queue behaves right
+        assert_eq!(queue.dequeue(), None);
+        assert!(queue.is_empty());
+
+        // Populate queue
+        queue.enqueue(1);
+        queue.enqueue(2);
+        queue.enqueue(3);
+
+        // Check normal removal
+        assert_eq!(queue.dequeue(), Some(1));
+        assert_eq!(queue.dequeue(), Some(2));
+
+        // Push some more just to make sure nothing's corrupted
+        queue.enqueue(4);
+        queue.enqueue(5);
+
+        // Check normal removal
+        assert_eq!(queue.dequeue(), Some(3));
+        assert_eq!(queue.dequeue(), Some(4));
+
+        // Check exhaustion
+        assert_eq!(queue.dequeue(), Some(5));
+        assert_eq!(queue.dequeue(), None);
+
+        // Check the exhaustion case fixed the pointer right
+        queue.enqueue(6);
+        queue.enqueue(7);
+
+        // Check normal removal
+        assert_eq!(queue.dequeue(), Some(6));
+        assert_eq!(queue.dequeue(), Some(7));
+        assert_eq!(queue.dequeue(), None);
+    }
+
+    #[test]
+    fn into_iter() {
+        let mut queue = Queue::new();
+        queue.enqueue(1);
+        queue.enqueue(2);
+        queue.enqueue(3);
+
+        let mut iter = queue.into_iter();
+        assert_eq!(iter.next(), Some(1));
+        assert_eq!(iter.next(), Some(2));
+        assert_eq!(iter.next(), Some(3));
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn iter() {
+        let mut queue = Queue::new();
+        queue.enqueue(1);
+        queue.enqueue(2);
+        queue.enqueue(3);
+
+        let mut iter = queue.iter();
+        assert_eq!(iter.next(), Some(&1));
+        assert_eq!(iter.next(), Some(&2));
+        assert_eq!(iter.next(), Some(&3));
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn iter_mut() {
+        let mut queue = Queue::new();
+        queue.enqueue(1);
+        queue.enqueue(2);
+        queue.enqueue(3);
+
+        let mut iter = queue.iter_mut();
+        assert_eq!(iter.next(), Some(&mut 1));
+        assert_eq!(iter.next(), Some(&mut 2));
+        assert_eq!(iter.next(), Some(&mut 3));
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn miri_food() {
+        // Interleave every kind of access so Miri gets to check the raw pointers against
+        // the references we hand out.
+        let mut queue = Queue::new();
+
+        queue.enqueue(1);
+        queue.enqueue(2);
+        queue.enqueue(3);
+
+        assert!(queue.dequeue() == Some(1));
+        queue.enqueue(4);
+        assert!(queue.dequeue() == Some(2));
+        queue.enqueue(5);
+
+        assert!(queue.peek() == Some(&3));
+        queue.enqueue(6);
+        if let Some(x) = queue.peek_mut() {
+            *x *= 10;
+        }
+        assert!(queue.peek() == Some(&30));
+        assert!(queue.dequeue() == Some(30));
+
+        for elem in queue.iter_mut() {
+            *elem *= 100;
+        }
+
+        let mut iter = queue.iter();
+        assert_eq!(iter.next(), Some(&400));
+        assert_eq!(iter.next(), Some(&500));
+        assert_eq!(iter.next(), Some(&600));
+        assert_eq!(iter.next(), None);
+
+        assert!(queue.dequeue() == Some(400));
+        if let Some(x) = queue.peek_mut() {
+            *x *= 10;
+        }
+        assert!(queue.peek() == Some(&5000));
+        queue.enqueue(7);
+
+        // Drop it on the ground and let the dtor exercise itself
+    }
+}
@@ -0,0 +1,579 @@
+//! # R-tree
+//!
+//! Rectangles, each with a value, in a balanced tree whose every node covers its entries
+//! with the smallest rectangle that holds them all (Guttman, 1984). It's the index for
+//! things that take up space rather than sit at a point, like the outlines of shapes on a
+//! map: a search goes into every child whose covering rectangle could matter, and skips
+//! the rest.
+//!
+//! ```text
+//!   +---------------+  +-----------+        root:   [ R1 | R2 ]
+//!   | R1  +--+      |  | R2        |                  /      \
+//!   |  +--|a |--+   |  |  +-----+  |         leaves: [a b c]  [d e]
+//!   |  |b +--+  |c  |  |  |  d  |  |
+//!   |  +--------+   |  |  +-----+ e|
+//!   +---------------+  +-----------+
+//! ```
+//!
+//! Every leaf is at the same depth, and every node but the root has between a minimum and
+//! a maximum number of entries. A new rectangle goes down into whichever child would have
+//! to grow least to cover it. A node with one too many splits in two by Guttman's
+//! quadratic split: the two entries that would waste the most space together start the
+//! two halves, and the rest go one at a time, the one that cares most about which half
+//! first, each to the half that grows least for it. A split can make the parent too full,
+//! up to the root, which splits into a new root and makes the tree one taller.
+//!
+//! Removing an entry can leave its node with too few. Rather than merge it with a
+//! neighbor, the node is taken out of the tree, and its entries put back in from the top,
+//! each at the height it came from. A root left with one child gives way to that child.
+//!
+//! Rectangles are a [`crate::quadtree::Rect`], edges included.
+
+use crate::quadtree::Rect;
+use std::cmp::Ordering;
+use std::mem;
+
+const DEFAULT_MAX: usize = 8;
+
+enum Node<T> {
+    Leaf(Vec<(Rect, T)>),
+    Branch(Vec<(Rect, Node<T>)>),
+}
+
+/// What goes into a node: a value into a leaf, or a subtree into a branch.
+enum Entry<T> {
+    Item(T),
+    Tree(Node<T>),
+}
+
+fn cover<E>(entries: &[(Rect, E)]) -> Rect {
+    entries
+        .iter()
+        .map(|e| e.0)
+        .reduce(|a, b| a.union(&b))
+        .unwrap()
+}
+
+/// How much bigger `rect` has to get to cover `other` as well.
+fn growth(rect: &Rect, other: &Rect) -> f64 {
+    rect.union(other).area() - rect.area()
+}
+
+/// Guttman's quadratic split: divides the entries into two groups of at least `min` each.
+fn quadratic_split<E>(mut entries: Vec<(Rect, E)>, min: usize) -> [Vec<(Rect, E)>; 2] {
+    // The pair that would waste the most area as one node start the two groups
+    let (mut seeds, mut worst) = ((0, 1), f64::NEG_INFINITY);
+    for i in 0..entries.len() {
+        for j in i + 1..entries.len() {
+            let (a, b) = (&entries[i].0, &entries[j].0);
+            let waste = a.union(b).area() - a.area() - b.area();
+            if waste > worst {
+                seeds = (i, j);
+                worst = waste;
+            }
+        }
+    }
+    // The later one first, so the earlier one's index still holds
+    let second = entries.swap_remove(seeds.1);
+    let first = entries.swap_remove(seeds.0);
+    let mut covers = [first.0, second.0];
+    let mut groups = [vec![first], vec![second]];
+    while !entries.is_empty() {
+        // A group that needs everything left to reach the minimum gets it
+        if let Some(g) = (0..2).find(|&g| groups[g].len() + entries.len() == min) {
+            groups[g].append(&mut entries);
+            break;
+        }
+        // Next, the entry with the strongest preference between the two
+        let i = (0..entries.len())
+            .max_by(|&i, &j| {
+                let preference = |k: usize| {
+                    let rect = &entries[k].0;
+                    (growth(&covers[0], rect) - growth(&covers[1], rect)).abs()
+                };
+                preference(i).total_cmp(&preference(j))
+            })
+            .unwrap();
+        let entry = entries.swap_remove(i);
+        let grow = [0, 1].map(|g| growth(&covers[g], &entry.0));
+        // Least growth, then smaller area, then fewer entries
+        let g = match grow[0].total_cmp(&grow[1]) {
+            Ordering::Equal => match covers[0].area().total_cmp(&covers[1].area()) {
+                Ordering::Equal => (groups[1].len() < groups[0].len()) as usize,
+                order => (order == Ordering::Greater) as usize,
+            },
+            order => (order == Ordering::Greater) as usize,
+        };
+        covers[g] = covers[g].union(&entry.0);
+        groups[g].push(entry);
+    }
+    groups
+}
+
+impl<T> Node<T> {
+    fn len(&self) -> usize {
+        match self {
+            Node::Leaf(items) => items.len(),
+            Node::Branch(children) => children.len(),
+        }
+    }
+
+    fn cover(&self) -> Rect {
+        match self {
+            Node::Leaf(items) => cover(items),
+            Node::Branch(children) => cover(children),
+        }
+    }
+
+    /// Puts an entry in at `level`, this node being at `height`, with leaves at zero, and
+    /// splits what gets too full on the way back up. Returns the half split off this node,
+    /// if it was.
+    fn insert(
+        &mut self,
+        height: usize,
+        rect: Rect,
+        entry: Entry<T>,
+        level: usize,
+        [min, max]: [usize; 2],
+    ) -> Option<Node<T>> {
+        match (self, entry) {
+            (Node::Leaf(items), Entry::Item(value)) => {
+                items.push((rect, value));
+                if items.len() <= max {
+                    return None;
+                }
+                let [keep, split] = quadratic_split(mem::take(items), min);
+                *items = keep;
+                Some(Node::Leaf(split))
+            }
+            (Node::Branch(children), entry) => {
+                if height == level {
+                    match entry {
+                        Entry::Tree(node) => children.push((rect, node)),
+                        Entry::Item(_) => unreachable!(),
+                    }
+                } else {
+                    // The child that has to grow least, then the smallest
+                    let i = (0..children.len())
+                        .min_by(|&i, &j| {
+                            let (a, b) = (&children[i].0, &children[j].0);
+                            growth(a, &rect)
+                                .total_cmp(&growth(b, &rect))
+                                .then(a.area().total_cmp(&b.area()))
+                        })
+                        .unwrap();
+                    let child = &mut children[i];
+                    child.0 = child.0.union(&rect);
+                    if let Some(split) = child.1.insert(height - 1, rect, entry, level, [min, max])
+                    {
+                        child.0 = child.1.cover();
+                        children.push((split.cover(), split));
+                    }
+                }
+                if children.len() <= max {
+                    return None;
+                }
+                let [keep, split] = quadratic_split(mem::take(children), min);
+                *children = keep;
+                Some(Node::Branch(split))
+            }
+            (Node::Leaf(_), Entry::Tree(_)) => unreachable!(),
+        }
+    }
+
+    /// Takes out an entry with exactly `rect`, this node being at `height`. Children left
+    /// with fewer than `min` entries are taken out too, and their entries added to
+    /// `orphans`, with the level each belongs at.
+    fn remove(
+        &mut self,
+        height: usize,
+        rect: &Rect,
+        min: usize,
+        orphans: &mut Vec<(Rect, Entry<T>, usize)>,
+    ) -> Option<T> {
+        let children = match self {
+            Node::Leaf(items) => {
+                let i = items.iter().position(|(r, _)| r == rect)?;
+                return Some(items.swap_remove(i).1);
+            }
+            Node::Branch(children) => children,
+        };
+        for i in 0..children.len() {
+            if !children[i].0.contains_rect(rect) {
+                continue;
+            }
+            let value = match children[i].1.remove(height - 1, rect, min, orphans) {
+                Some(value) => value,
+                None => continue,
+            };
+            if children[i].1.len() >= min {
+                children[i].0 = children[i].1.cover();
+                return Some(value);
+            }
+            match children.swap_remove(i).1 {
+                Node::Leaf(items) => {
+                    orphans.extend(items.into_iter().map(|(r, v)| (r, Entry::Item(v), 0)));
+                }
+                Node::Branch(nodes) => orphans.extend(
+                    nodes
+                        .into_iter()
+                        .map(|(r, n)| (r, Entry::Tree(n), height - 1)),
+                ),
+            }
+            return Some(value);
+        }
+        None
+    }
+}
+
+pub struct RTree<T> {
+    root: Node<T>,
+    // Leaves are at height zero
+    height: usize,
+    min: usize,
+    max: usize,
+    len: usize,
+}
+
+impl<T> RTree<T> {
+    /// Creates an empty RTree.
+    pub fn new() -> Self {
+        Self::with_node_size(DEFAULT_MAX)
+    }
+
+    /// Returns an empty tree whose nodes hold up to `max` entries, and, but for the root,
+    /// at least two fifths of that.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max` is less than two.
+    pub fn with_node_size(max: usize) -> Self {
+        assert!(max >= 2, "nodes must hold at least two entries");
+        RTree {
+            root: Node::Leaf(Vec::new()),
+            height: 0,
+            min: (max * 2 / 5).max(1),
+            max,
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.root = Node::Leaf(Vec::new());
+        self.height = 0;
+        self.len = 0;
+    }
+
+    pub fn insert(&mut self, rect: Rect, value: T) {
+        self.insert_at(rect, Entry::Item(value), 0);
+        self.len += 1;
+    }
+
+    fn insert_at(&mut self, rect: Rect, entry: Entry<T>, level: usize) {
+        let sizes = [self.min, self.max];
+        if let Some(split) = self.root.insert(self.height, rect, entry, level, sizes) {
+            let old = mem::replace(&mut self.root, Node::Leaf(Vec::new()));
+            self.root = Node::Branch(vec![(old.cover(), old), (split.cover(), split)]);
+            self.height += 1;
+        }
+    }
+
+    /// Removes an entry whose rectangle is exactly `rect`, and returns its value. If
+    /// there's more than one, which goes is unspecified.
+    pub fn remove(&mut self, rect: &Rect) -> Option<T> {
+        let mut orphans = Vec::new();
+        let value = self
+            .root
+            .remove(self.height, rect, self.min, &mut orphans)?;
+        for (rect, entry, level) in orphans {
+            self.insert_at(rect, entry, level);
+        }
+        while let Node::Branch(children) = &mut self.root {
+            if children.len() > 1 {
+                break;
+            }
+            self.root = children.pop().unwrap().1;
+            self.height -= 1;
+        }
+        self.len -= 1;
+        Some(value)
+    }
+
+    /// Iterates over the entries whose rectangles overlap `area`, edges included.
+    pub fn intersecting(&self, area: Rect) -> Search<'_, T> {
+        self.search(area, Query::Intersecting)
+    }
+
+    /// Iterates over the entries whose rectangles are inside `area`.
+    pub fn within(&self, area: Rect) -> Search<'_, T> {
+        self.search(area, Query::Within)
+    }
+
+    /// Iterates over the entries whose rectangles hold all of `area`.
+    pub fn containing(&self, area: Rect) -> Search<'_, T> {
+        self.search(area, Query::Containing)
+    }
+
+    fn search(&self, area: Rect, query: Query) -> Search<'_, T> {
+        Search {
+            area,
+            query,
+            stack: vec![&self.root],
+            items: [].iter(),
+        }
+    }
+
+    /// Iterates over every entry, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (Rect, &T)> + '_ {
+        self.search(Rect::default(), Query::All)
+    }
+}
+
+impl<T> Default for RTree<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Query {
+    All,
+    Intersecting,
+    Within,
+    Containing,
+}
+
+impl Query {
+    /// Whether an entry with `rect` can have anything that matches under it.
+    fn enter(self, rect: &Rect, area: &Rect) -> bool {
+        match self {
+            Query::All => true,
+            Query::Intersecting | Query::Within => rect.intersects(area),
+            Query::Containing => rect.contains_rect(area),
+        }
+    }
+
+    /// Whether an entry with `rect` itself matches.
+    fn matches(self, rect: &Rect, area: &Rect) -> bool {
+        match self {
+            Query::Within => area.contains_rect(rect),
+            _ => self.enter(rect, area),
+        }
+    }
+}
+
+/// Iterator over the entries that match a search, from [`RTree::intersecting`],
+/// [`RTree::within`] and [`RTree::containing`], in no particular order.
+pub struct Search<'a, T> {
+    area: Rect,
+    query: Query,
+    stack: Vec<&'a Node<T>>,
+    items: std::slice::Iter<'a, (Rect, T)>,
+}
+
+impl<'a, T> Iterator for Search<'a, T> {
+    type Item = (Rect, &'a T);
+
+    fn next(&mut self) -> Option<(Rect, &'a T)> {
+        loop {
+            for (rect, value) in self.items.by_ref() {
+                if self.query.matches(rect, &self.area) {
+                    return Some((*rect, value));
+                }
+            }
+            match self.stack.pop()? {
+                Node::Leaf(items) => self.items = items.iter(),
+                Node::Branch(children) => {
+                    let (query, area) = (self.query, &self.area);
+                    self.stack.extend(
+                        children
+                            .iter()
+                            .filter(|(rect, _)| query.enter(rect, area))
+                            .map(|(_, child)| child),
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Node, RTree};
+    use crate::quadtree::{Point, Rect};
+
+    fn rect(x0: f64, y0: f64, x1: f64, y1: f64) -> Rect {
+        Rect::new(Point::new(x0, y0), Point::new(x1, y1))
+    }
+
+    /// Checks every leaf is at the bottom, every branch's rectangles are their children's
+    /// exact covers, every node but the root is neither too full nor too empty, and the
+    /// count matches.
+    fn check<T>(tree: &RTree<T>) {
+        let mut stack = vec![(&tree.root, tree.height)];
+        let mut count = 0;
+        while let Some((node, height)) = stack.pop() {
+            assert!(node.len() <= tree.max);
+            if height != tree.height {
+                assert!(node.len() >= tree.min);
+            }
+            match node {
+                Node::Leaf(items) => {
+                    assert_eq!(height, 0);
+                    count += items.len();
+                }
+                Node::Branch(children) => {
+                    assert!(height > 0);
+                    for (rect, child) in children {
+                        assert_eq!(*rect, child.cover());
+                        stack.push((child, height - 1));
+                    }
+                }
+            }
+        }
+        assert_eq!(count, tree.len());
+    }
+
+    fn sorted<'a>(found: impl Iterator<Item = (Rect, &'a usize)>) -> Vec<usize> {
+        let mut found: Vec<_> = found.map(|(_, &i)| i).collect();
+        found.sort();
+        found
+    }
+
+    #[test]
+    fn basics() {
+        let mut tree = RTree::with_node_size(3);
+
+        // Check empty tree behaves right
+        assert!(tree.is_empty());
+        assert_eq!(tree.intersecting(rect(0.0, 0.0, 9.0, 9.0)).count(), 0);
+        assert_eq!(tree.remove(&rect(0.0, 0.0, 1.0, 1.0)), None);
+
+        // Populate tree
+        let rects = [
+            rect(0.0, 0.0, 2.0, 2.0),
+            rect(1.0, 1.0, 3.0, 3.0),
+            rect(5.0, 5.0, 6.0, 6.0),
+            rect(0.0, 5.0, 1.0, 9.0),
+            rect(8.0, 0.0, 9.0, 1.0),
+            rect(2.0, 2.0, 8.0, 8.0),
+        ];
+        for (i, &r) in rects.iter().enumerate() {
+            tree.insert(r, i);
+            check(&tree);
+        }
+        assert_eq!(tree.len(), 6);
+        assert!(tree.height > 0);
+
+        // Touching at a corner counts as overlapping
+        let area = rect(3.0, 3.0, 5.0, 5.0);
+        assert_eq!(sorted(tree.intersecting(area)), [1, 2, 5]);
+        assert_eq!(sorted(tree.within(rect(0.0, 0.0, 6.0, 6.0))), [0, 1, 2]);
+        assert_eq!(sorted(tree.containing(area)), [5]);
+        assert_eq!(sorted(tree.containing(rect(5.5, 5.5, 5.5, 5.5))), [2, 5]);
+        assert_eq!(tree.iter().count(), 6);
+
+        // Check normal removal
+        assert_eq!(tree.remove(&rects[5]), Some(5));
+        assert_eq!(tree.remove(&rects[5]), None);
+        check(&tree);
+        assert_eq!(sorted(tree.intersecting(area)), [1, 2]);
+
+        // Check exhaustion
+        for (i, r) in rects.iter().enumerate().take(5) {
+            assert_eq!(tree.remove(r), Some(i));
+            check(&tree);
+        }
+        assert!(tree.is_empty());
+        assert_eq!(tree.height, 0);
+        assert_eq!(tree.iter().count(), 0);
+    }
+
+    #[test]
+    fn grid() {
+        // A grid of unit squares, whose searches are easy to count
+        let mut tree = RTree::new();
+        for i in 0..400 {
+            let (x, y) = ((i % 20) as f64, (i / 20) as f64);
+            tree.insert(rect(x, y, x + 1.0, y + 1.0), i);
+        }
+        check(&tree);
+        assert_eq!(tree.within(rect(0.0, 0.0, 5.0, 5.0)).count(), 25);
+        assert_eq!(tree.intersecting(rect(0.5, 0.5, 1.5, 1.5)).count(), 4);
+        assert_eq!(tree.containing(rect(3.2, 4.2, 3.8, 4.8)).count(), 1);
+
+        // Emptying every other row condenses the tree without losing anything
+        for i in (0..400).filter(|i| (i / 20) % 2 == 0) {
+            let (x, y) = ((i % 20) as f64, (i / 20) as f64);
+            assert_eq!(tree.remove(&rect(x, y, x + 1.0, y + 1.0)), Some(i));
+        }
+        check(&tree);
+        assert_eq!(tree.len(), 200);
+        assert_eq!(tree.within(rect(0.0, 0.0, 5.0, 5.0)).count(), 10);
+    }
+
+    #[test]
+    fn against_brute_force() {
+        // A random mix of inserts and removals, with repeats, against a list
+        let mut x: u32 = 1;
+        let mut next = || {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            ((x >> 8) % 100) as f64
+        };
+        let steps = if cfg!(miri) { 200 } else { 3_000 };
+        for &size in &[2, 4, 9] {
+            let mut tree = RTree::with_node_size(size);
+            let mut model: Vec<(Rect, usize)> = Vec::new();
+            for i in 0..steps {
+                let (x0, y0) = (next(), next());
+                let r = rect(x0, y0, x0 + next() / 5.0, y0 + next() / 5.0);
+                if next() < 30.0 && !model.is_empty() {
+                    let (r, _) = model[i % model.len()];
+                    let value = tree.remove(&r).unwrap();
+                    let at = model.iter().position(|&m| m == (r, value)).unwrap();
+                    model.swap_remove(at);
+                } else {
+                    tree.insert(r, i);
+                    model.push((r, i));
+                }
+                if i % 100 == 0 {
+                    check(&tree);
+                }
+
+                let area = rect(x0, y0, x0 + 20.0, y0 + 20.0);
+                let expect = |f: &dyn Fn(&Rect) -> bool| {
+                    let mut found: Vec<_> = model
+                        .iter()
+                        .filter(|(r, _)| f(r))
+                        .map(|&(_, i)| i)
+                        .collect();
+                    found.sort();
+                    found
+                };
+                assert_eq!(
+                    sorted(tree.intersecting(area)),
+                    expect(&|r| r.intersects(&area))
+                );
+                assert_eq!(
+                    sorted(tree.within(area)),
+                    expect(&|r| area.contains_rect(r))
+                );
+                let small = rect(x0, y0, x0 + 1.0, y0 + 1.0);
+                assert_eq!(
+                    sorted(tree.containing(small)),
+                    expect(&|r| r.contains_rect(&small))
+                );
+            }
+            check(&tree);
+            assert_eq!(tree.len(), model.len());
+        }
+    }
+}
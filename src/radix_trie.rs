@@ -0,0 +1,545 @@
+//! # Radix trie
+//!
+//! A path-compressed [`crate::trie`] (also known as a PATRICIA trie, after Morrison, 1968).
+//! In a plain trie most nodes have a single child and no value; they only spell out one
+//! more byte. Here each edge carries a whole byte string instead, so those chains collapse
+//! into one node. Every node but the root either holds a value or branches.
+//!
+//! ```text
+//! keys: "romane", "romanus", "romulus", "rubens"
+//!
+//!   trie: 18 nodes, root included         radix trie: 8 nodes, likewise
+//!
+//!   r-o-m-a-n-e                                    r
+//!   |   |   |                                    /   \
+//!   |   |   u-s                                om     ubens
+//!   |   u-l-u-s                              /    \
+//!   u-b-e-n-s                              an      ulus
+//!                                         /  \
+//!                                        e    us
+//! ```
+//!
+//! A lookup still reads each byte of the key once, but it walks far fewer nodes and spends
+//! far less memory on them. The price is that updates change the shape. Inserting a key
+//! that diverges in the middle of an edge splits the edge in two. Removing a key can leave
+//! a node with one child and no value, and then it merges back into that child.
+//!
+//! The API is the same as the plain trie's, and [`RadixTrie::from`] converts one by
+//! compressing its chains in place.
+
+use crate::trie::{self, Trie};
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::iter::FromIterator;
+use std::mem;
+
+pub struct RadixTrie<V> {
+    root: Node<V>,
+    len: usize,
+}
+
+struct Node<V> {
+    // The bytes on the edge leading here; empty only for the root
+    label: Vec<u8>,
+    value: Option<V>,
+    // Keyed by the first byte of each child's label
+    children: BTreeMap<u8, Node<V>>,
+}
+
+impl<V> Node<V> {
+    fn new(label: Vec<u8>, value: Option<V>) -> Self {
+        Node {
+            label,
+            value,
+            children: BTreeMap::new(),
+        }
+    }
+
+    /// Removes `key`, given relative to this node, and tidies up the nodes it went through.
+    fn remove(&mut self, key: &[u8]) -> Option<V> {
+        let first = match key.first() {
+            Some(first) => first,
+            None => return self.value.take(),
+        };
+        let child = self.children.get_mut(first)?;
+        if !key.starts_with(&child.label) {
+            return None;
+        }
+        let skip = child.label.len();
+        let removed = child.remove(&key[skip..])?;
+
+        if child.value.is_none() {
+            match child.children.len() {
+                // Leads nowhere any more
+                0 => {
+                    self.children.remove(first);
+                }
+                // Only passes through now, so it merges with its one child
+                1 => {
+                    let (_, grandchild) = child.children.pop_first().unwrap();
+                    child.label.extend(grandchild.label);
+                    child.value = grandchild.value;
+                    child.children = grandchild.children;
+                }
+                _ => {}
+            }
+        }
+        Some(removed)
+    }
+}
+
+/// Length of the common prefix of two byte strings.
+fn common_prefix(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(x, y)| x == y).count()
+}
+
+impl<V> RadixTrie<V> {
+    /// Creates an empty RadixTrie.
+    pub fn new() -> Self {
+        RadixTrie {
+            root: Node::new(Vec::new(), None),
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        *self = RadixTrie::new();
+    }
+
+    /// Returns how many nodes the trie is made of, the root included.
+    pub fn node_count(&self) -> usize {
+        let mut count = 0;
+        let mut stack = vec![&self.root];
+        while let Some(node) = stack.pop() {
+            count += 1;
+            stack.extend(node.children.values());
+        }
+        count
+    }
+
+    /// Inserts a key-value pair, returning the old value if the key was already there.
+    pub fn insert<K>(&mut self, key: &K, value: V) -> Option<V>
+    where
+        K: AsRef<[u8]> + ?Sized,
+    {
+        let mut rest = key.as_ref();
+        let mut node = &mut self.root;
+        while let Some(&first) = rest.first() {
+            let child = match node.children.entry(first) {
+                Entry::Vacant(entry) => {
+                    entry.insert(Node::new(rest.to_vec(), Some(value)));
+                    self.len += 1;
+                    return None;
+                }
+                Entry::Occupied(entry) => entry.into_mut(),
+            };
+            let common = common_prefix(&child.label, rest);
+            if common < child.label.len() {
+                // The key leaves this edge partway along: split it where they part
+                let suffix = child.label.split_off(common);
+                let lower = Node {
+                    label: suffix,
+                    value: child.value.take(),
+                    children: mem::take(&mut child.children),
+                };
+                child.children.insert(lower.label[0], lower);
+            }
+            rest = &rest[common..];
+            node = child;
+        }
+
+        let old = node.value.replace(value);
+        if old.is_none() {
+            self.len += 1;
+        }
+        old
+    }
+
+    fn find(&self, key: &[u8]) -> Option<&Node<V>> {
+        let mut rest = key;
+        let mut node = &self.root;
+        while let Some(first) = rest.first() {
+            node = node.children.get(first)?;
+            rest = rest.strip_prefix(&node.label[..])?;
+        }
+        Some(node)
+    }
+
+    pub fn get<K>(&self, key: &K) -> Option<&V>
+    where
+        K: AsRef<[u8]> + ?Sized,
+    {
+        self.find(key.as_ref())?.value.as_ref()
+    }
+
+    pub fn get_mut<K>(&mut self, key: &K) -> Option<&mut V>
+    where
+        K: AsRef<[u8]> + ?Sized,
+    {
+        let mut rest = key.as_ref();
+        let mut node = &mut self.root;
+        while let Some(first) = rest.first() {
+            node = node.children.get_mut(first)?;
+            rest = rest.strip_prefix(&node.label[..])?;
+        }
+        node.value.as_mut()
+    }
+
+    pub fn contains_key<K>(&self, key: &K) -> bool
+    where
+        K: AsRef<[u8]> + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    /// Removes a key, returning its value if it was there. Nodes left with nothing to do
+    /// are removed or merged into their only child.
+    pub fn remove<K>(&mut self, key: &K) -> Option<V>
+    where
+        K: AsRef<[u8]> + ?Sized,
+    {
+        let removed = self.root.remove(key.as_ref());
+        if removed.is_some() {
+            self.len -= 1;
+        }
+        removed
+    }
+
+    /// Returns the longest prefix of `key` that is itself a key, along with its value.
+    pub fn longest_prefix_match<'k, K>(&self, key: &'k K) -> Option<(&'k [u8], &V)>
+    where
+        K: AsRef<[u8]> + ?Sized,
+    {
+        let key = key.as_ref();
+        let mut best = self.root.value.as_ref().map(|value| (0, value));
+        let mut consumed = 0;
+        let mut node = &self.root;
+        while let Some(first) = key.get(consumed) {
+            node = match node.children.get(first) {
+                Some(child) if key[consumed..].starts_with(&child.label) => child,
+                _ => break,
+            };
+            consumed += node.label.len();
+            if let Some(value) = &node.value {
+                best = Some((consumed, value));
+            }
+        }
+        best.map(|(len, value)| (&key[..len], value))
+    }
+
+    /// Iterates over every key starting with `prefix`, in lexicographic order.
+    pub fn iter_prefix<K>(&self, prefix: &K) -> Iter<'_, V>
+    where
+        K: AsRef<[u8]> + ?Sized,
+    {
+        let mut iter = Iter {
+            key: Vec::new(),
+            stack: Vec::new(),
+        };
+
+        // Walk down to the node covering the prefix, which may end partway along an edge
+        let mut rest = prefix.as_ref();
+        let mut node = &self.root;
+        while let Some(first) = rest.first() {
+            node = match node.children.get(first) {
+                Some(child) => child,
+                None => return iter,
+            };
+            if let Some(after) = rest.strip_prefix(&node.label[..]) {
+                iter.key.extend(&node.label);
+                rest = after;
+            } else if node.label.starts_with(rest) {
+                break;
+            } else {
+                return iter;
+            }
+        }
+        if rest.is_empty() {
+            // The node's own label is already in the key; start below it
+            let depth = iter.key.len() - node.label.len();
+            iter.key.truncate(depth);
+        }
+        iter.stack.push((iter.key.len(), node));
+        iter
+    }
+
+    /// Iterates over every key in lexicographic order.
+    pub fn iter(&self) -> Iter<'_, V> {
+        self.iter_prefix(&[])
+    }
+}
+
+impl<V> Default for RadixTrie<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V> From<Trie<V>> for RadixTrie<V> {
+    /// Compresses a plain trie's chains in place, without rebuilding it key by key.
+    fn from(mut trie: Trie<V>) -> Self {
+        fn compress<V>(mut label: Vec<u8>, mut node: trie::Node<V>) -> Node<V> {
+            while node.value.is_none() && node.children.len() == 1 {
+                let (byte, child) = node.children.pop_first().unwrap();
+                label.push(byte);
+                node = child;
+            }
+            Node {
+                label,
+                value: node.value,
+                children: node
+                    .children
+                    .into_iter()
+                    .map(|(byte, child)| (byte, compress(vec![byte], child)))
+                    .collect(),
+            }
+        }
+
+        let len = trie.len();
+        let root = mem::replace(&mut trie.root, trie::Node::new());
+        // The root is never merged into a child, whatever it looks like
+        let root = Node {
+            label: Vec::new(),
+            value: root.value,
+            children: root
+                .children
+                .into_iter()
+                .map(|(byte, child)| (byte, compress(vec![byte], child)))
+                .collect(),
+        };
+        RadixTrie { root, len }
+    }
+}
+
+impl<K: AsRef<[u8]>, V> FromIterator<(K, V)> for RadixTrie<V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut trie = RadixTrie::new();
+        trie.extend(iter);
+        trie
+    }
+}
+
+impl<K: AsRef<[u8]>, V> Extend<(K, V)> for RadixTrie<V> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (key, value) in iter {
+            self.insert(&key, value);
+        }
+    }
+}
+
+impl<V: fmt::Debug> fmt::Debug for RadixTrie<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(
+                self.iter()
+                    .map(|(key, value)| (String::from_utf8_lossy(&key).into_owned(), value)),
+            )
+            .finish()
+    }
+}
+
+/// Depth-first iterator over keys and values. Keys are built up as it goes, so it yields
+/// them owned.
+pub struct Iter<'a, V> {
+    // The key of the node visited last
+    key: Vec<u8>,
+    // Nodes still to visit, the next one on top, with the length of their parent's key
+    stack: Vec<(usize, &'a Node<V>)>,
+}
+
+impl<'a, V> Iterator for Iter<'a, V> {
+    type Item = (Vec<u8>, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((depth, node)) = self.stack.pop() {
+            self.key.truncate(depth);
+            self.key.extend(&node.label);
+            // Children go on in reverse, so the smallest byte comes off first
+            for child in node.children.values().rev() {
+                self.stack.push((self.key.len(), child));
+            }
+            if let Some(value) = &node.value {
+                return Some((self.key.clone(), value));
+            }
+        }
+        None
+    }
+}
+
+impl<'a, V> IntoIterator for &'a RadixTrie<V> {
+    type Item = (Vec<u8>, &'a V);
+    type IntoIter = Iter<'a, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<V> Drop for RadixTrie<V> {
+    fn drop(&mut self) {
+        // Nested keys like "a", "aa", "aaa", ... still make a deep chain of nodes
+        let mut stack = vec![mem::replace(&mut self.root, Node::new(Vec::new(), None))];
+        while let Some(mut node) = stack.pop() {
+            stack.extend(mem::take(&mut node.children).into_values());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::RadixTrie;
+    use crate::trie::Trie;
+
+    fn keys<V>(iter: super::Iter<'_, V>) -> Vec<String> {
+        iter.map(|(key, _)| String::from_utf8(key).unwrap())
+            .collect()
+    }
+
+    const ROMANS: [&str; 4] = ["romane", "romanus", "romulus", "rubens"];
+
+    #[test]
+    fn basics() {
+        let mut trie = RadixTrie::new();
+
+        // Check empty trie behaves right
+        assert!(trie.is_empty());
+        assert_eq!(trie.get("a"), None);
+        assert_eq!(trie.remove("a"), None);
+        assert_eq!(trie.node_count(), 1);
+
+        for (i, key) in ROMANS.iter().enumerate() {
+            assert_eq!(trie.insert(*key, i), None);
+        }
+        assert_eq!(trie.len(), 4);
+        assert_eq!(trie.node_count(), 8);
+        assert_eq!(trie.insert("romulus", 20), Some(2));
+        *trie.get_mut("rubens").unwrap() += 10;
+        assert_eq!(trie.get("rubens"), Some(&13));
+
+        // Neither partway along an edge nor past its end is a key
+        assert_eq!(trie.get("rom"), None);
+        assert_eq!(trie.get("roma"), None);
+        assert_eq!(trie.get("romanes"), None);
+        assert!(!trie.contains_key("r"));
+
+        // Inserting at a split point makes a new node, not a new edge
+        trie.insert("roman", 99);
+        assert_eq!(trie.node_count(), 8);
+        trie.insert("", 100);
+        assert_eq!(
+            format!("{:?}", trie),
+            r#"{"": 100, "roman": 99, "romane": 0, "romanus": 1, "romulus": 20, "rubens": 13}"#
+        );
+    }
+
+    #[test]
+    fn removal_merges() {
+        let mut trie: RadixTrie<_> = ROMANS.iter().map(|key| (key, ())).collect();
+
+        // "om" is left with one child, "an", so they become "oman"
+        assert_eq!(trie.remove("romulus"), Some(()));
+        assert_eq!(trie.node_count(), 6);
+        assert_eq!(trie.get("romane"), Some(&()));
+        assert_eq!(trie.remove("rom"), None);
+
+        // "r" and "oman" merge the same way, but leaving the root with one child is fine
+        assert_eq!(trie.remove("rubens"), Some(()));
+        assert_eq!(trie.node_count(), 4);
+        assert_eq!(trie.remove("romane"), Some(()));
+        assert_eq!(trie.node_count(), 2);
+        assert_eq!(keys(trie.iter()), vec!["romanus"]);
+        assert_eq!(trie.remove("romanus"), Some(()));
+        assert_eq!(trie.node_count(), 1);
+        assert!(trie.is_empty());
+    }
+
+    #[test]
+    fn prefix_queries() {
+        let trie: RadixTrie<_> = ["car", "card", "care", "careful", "cat", "dog"]
+            .iter()
+            .map(|word| (word, ()))
+            .collect();
+
+        assert_eq!(
+            keys(trie.iter_prefix("car")),
+            vec!["car", "card", "care", "careful"]
+        );
+        // Ending partway along the "ful" edge
+        assert_eq!(keys(trie.iter_prefix("caref")), vec!["careful"]);
+        assert_eq!(keys(trie.iter_prefix("d")), vec!["dog"]);
+        assert_eq!(keys(trie.iter_prefix("caz")), Vec::<String>::new());
+        assert_eq!(keys(trie.iter_prefix("dogs")), Vec::<String>::new());
+
+        let mut routes = RadixTrie::new();
+        routes.insert("/", "index");
+        routes.insert("/api/", "api");
+        routes.insert("/api/users/", "users");
+        let route = |path: &'static str| routes.longest_prefix_match(path).map(|(_, h)| *h);
+        assert_eq!(route("/api/users/42"), Some("users"));
+        assert_eq!(route("/api/use"), Some("api"));
+        assert_eq!(route("/about"), Some("index"));
+        assert_eq!(route("nope"), None);
+    }
+
+    #[test]
+    fn from_plain_trie() {
+        let words = [
+            "to", "tea", "ted", "ten", "i", "in", "inn", "romane", "romanus",
+        ];
+        let plain: Trie<_> = words.iter().enumerate().map(|(i, w)| (w, i)).collect();
+        let plain_nodes = plain.node_count();
+        let expected: Vec<_> = plain.iter().map(|(k, v)| (k, *v)).collect();
+
+        let radix = RadixTrie::from(plain);
+        assert_eq!(radix.len(), words.len());
+        assert_eq!((plain_nodes, radix.node_count()), (18, 13));
+        assert_eq!(
+            radix.iter().map(|(k, v)| (k, *v)).collect::<Vec<_>>(),
+            expected
+        );
+
+        // Building it directly gives the same shape
+        let direct: RadixTrie<_> = words.iter().enumerate().map(|(i, w)| (w, i)).collect();
+        assert_eq!(direct.node_count(), radix.node_count());
+    }
+
+    #[test]
+    fn against_btreemap() {
+        use std::collections::BTreeMap;
+        let mut trie = RadixTrie::new();
+        let mut model = BTreeMap::new();
+        let mut x: u32 = 1;
+        for i in 0..3_000 {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let len = (x >> 28) as usize % 8;
+            let key: Vec<u8> = (0..len)
+                .map(|j| b'a' + ((x >> (j * 3)) & 3) as u8)
+                .collect();
+            if i % 3 == 0 {
+                assert_eq!(trie.remove(&key), model.remove(&key));
+            } else {
+                assert_eq!(trie.insert(&key, i), model.insert(key.clone(), i));
+            }
+            assert_eq!(trie.get(&key), model.get(&key));
+        }
+        assert_eq!(trie.len(), model.len());
+        assert!(trie.iter().eq(model.iter().map(|(k, v)| (k.clone(), v))));
+        let expected: Vec<_> = model.keys().filter(|k| k.starts_with(b"ab")).collect();
+        let found: Vec<_> = trie.iter_prefix("ab").map(|(k, _)| k).collect();
+        assert_eq!(found.iter().collect::<Vec<_>>(), expected);
+
+        // Same keys through a plain trie convert to the same thing
+        let plain: Trie<_> = model.iter().map(|(k, v)| (k, *v)).collect();
+        let converted = RadixTrie::from(plain);
+        assert_eq!(converted.node_count(), trie.node_count());
+    }
+}
@@ -0,0 +1,387 @@
+//! # Range set
+//!
+//! A set of values kept as the runs they make up, half-open ranges that neither overlap
+//! nor touch. It's a set over a range of values too large to list one by one, like the
+//! free blocks of a disk or the byte ranges of a download that have arrived so far.
+//!
+//! The ranges sit in a `std::collections::BTreeMap` from start to end. Adding a range
+//! swallows every range it overlaps or touches, and the one before it if that one reaches
+//! it, so what's stored is always the fewest, longest ranges:
+//!
+//! ```text
+//!   {0..3, 5..7, 9..12}
+//!   insert 2..5      {0..7, 9..12}          0..3 and 5..7 both merge in
+//!   remove 4..10     {0..4, 10..12}         cutting one range short and another off
+//!   gaps(0..14)      2 gaps: 4..10, 12..14
+//! ```
+//!
+//! Taking a range out trims the ranges at either end and drops everything between, which
+//! can split one range into two. Either way, finding where to start is a lookup in the
+//! map, and each range swallowed or dropped costs one removal, so both are
+//! O((1 + k) log n) for `k` ranges touched. A lookup is one O(log n) search for the last
+//! range starting at or before the value.
+
+use std::cmp;
+use std::collections::btree_map::{self, BTreeMap};
+use std::fmt;
+use std::iter::FromIterator;
+use std::ops::Range;
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct RangeSet<T> {
+    // From each range's start to its end
+    ranges: BTreeMap<T, T>,
+}
+
+impl<T: Ord + Clone> RangeSet<T> {
+    /// Creates an empty RangeSet.
+    pub fn new() -> Self {
+        RangeSet {
+            ranges: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the number of ranges, not of values.
+    pub fn len(&self) -> usize {
+        self.ranges.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.ranges.clear();
+    }
+
+    /// Returns the range holding `value`, if any.
+    fn range_of(&self, value: &T) -> Option<(&T, &T)> {
+        let (start, end) = self.ranges.range(..=value).next_back()?;
+        (value < end).then_some((start, end))
+    }
+
+    pub fn contains(&self, value: &T) -> bool {
+        self.range_of(value).is_some()
+    }
+
+    /// Returns whether every value in `range` is in the set. An empty range always is.
+    pub fn contains_range(&self, range: &Range<T>) -> bool {
+        range.is_empty()
+            || self
+                .range_of(&range.start)
+                .is_some_and(|(_, end)| range.end <= *end)
+    }
+
+    /// Adds every value in `range`.
+    pub fn insert(&mut self, range: Range<T>) {
+        let Range { mut start, mut end } = range;
+        if start >= end {
+            return;
+        }
+        // The range before, if it reaches this one
+        if let Some((s, e)) = self.ranges.range(..=&start).next_back() {
+            if *e >= start {
+                start = s.clone();
+            }
+        }
+        // And every range that starts inside this one or right after it
+        let swallowed: Vec<T> = self
+            .ranges
+            .range(&start..=&end)
+            .map(|(s, _)| s.clone())
+            .collect();
+        for s in swallowed {
+            let e = self.ranges.remove(&s).unwrap();
+            end = cmp::max(end, e);
+        }
+        self.ranges.insert(start, end);
+    }
+
+    /// Takes out every value in `range`.
+    pub fn remove(&mut self, range: Range<T>) {
+        let Range { start, end } = range;
+        if start >= end {
+            return;
+        }
+        // The range before is cut short, and may carry on past this one
+        let mut rest = None;
+        if let Some((_, e)) = self.ranges.range_mut(..&start).next_back() {
+            if *e > start {
+                let old = std::mem::replace(e, start.clone());
+                if old > end {
+                    rest = Some(old);
+                }
+            }
+        }
+        // Every range starting inside this one goes, but for what's left past the end
+        let dropped: Vec<T> = self
+            .ranges
+            .range(&start..&end)
+            .map(|(s, _)| s.clone())
+            .collect();
+        for s in dropped {
+            let e = self.ranges.remove(&s).unwrap();
+            if e > end {
+                rest = Some(e);
+            }
+        }
+        if let Some(e) = rest {
+            self.ranges.insert(end, e);
+        }
+    }
+
+    /// Iterates over the ranges, in order.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            ranges: self.ranges.iter(),
+        }
+    }
+
+    /// Iterates over the ranges of values in `range` that aren't in the set, in order.
+    pub fn gaps(&self, range: Range<T>) -> Gaps<'_, T> {
+        // From the range holding the start, if there is one
+        let from = match self.range_of(&range.start) {
+            Some((s, _)) => s.clone(),
+            None => range.start.clone(),
+        };
+        Gaps {
+            ranges: self.ranges.range(from..),
+            at: range.start,
+            end: range.end,
+        }
+    }
+}
+
+impl<T: Ord + Clone> Default for RangeSet<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Ord + Clone> FromIterator<Range<T>> for RangeSet<T> {
+    fn from_iter<I: IntoIterator<Item = Range<T>>>(iter: I) -> Self {
+        let mut set = RangeSet::new();
+        set.extend(iter);
+        set
+    }
+}
+
+impl<T: Ord + Clone> Extend<Range<T>> for RangeSet<T> {
+    fn extend<I: IntoIterator<Item = Range<T>>>(&mut self, iter: I) {
+        for range in iter {
+            self.insert(range);
+        }
+    }
+}
+
+impl<'a, T: Ord + Clone> IntoIterator for &'a RangeSet<T> {
+    type Item = Range<T>;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+impl<T: Ord + Clone + fmt::Debug> fmt::Debug for RangeSet<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+/// Iterator over the ranges of a [`RangeSet`], in order.
+pub struct Iter<'a, T> {
+    ranges: btree_map::Iter<'a, T, T>,
+}
+
+impl<T: Clone> Iterator for Iter<'_, T> {
+    type Item = Range<T>;
+
+    fn next(&mut self) -> Option<Range<T>> {
+        self.ranges.next().map(|(s, e)| s.clone()..e.clone())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.ranges.size_hint()
+    }
+}
+
+impl<T: Clone> DoubleEndedIterator for Iter<'_, T> {
+    fn next_back(&mut self) -> Option<Range<T>> {
+        self.ranges.next_back().map(|(s, e)| s.clone()..e.clone())
+    }
+}
+
+impl<T: Clone> ExactSizeIterator for Iter<'_, T> {}
+
+/// Iterator over the gaps in a [`RangeSet`] within a range, from [`RangeSet::gaps`].
+pub struct Gaps<'a, T> {
+    ranges: btree_map::Range<'a, T, T>,
+    // Everything before here is done with
+    at: T,
+    end: T,
+}
+
+impl<T: Ord + Clone> Iterator for Gaps<'_, T> {
+    type Item = Range<T>;
+
+    fn next(&mut self) -> Option<Range<T>> {
+        while self.at < self.end {
+            let (start, end) = match self.ranges.next() {
+                Some(range) => range,
+                None => {
+                    let gap = self.at.clone()..self.end.clone();
+                    self.at = self.end.clone();
+                    return Some(gap);
+                }
+            };
+            let gap = self.at.clone()..cmp::min(start, &self.end).clone();
+            self.at = cmp::max(&self.at, end).clone();
+            if !gap.is_empty() {
+                return Some(gap);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::RangeSet;
+    use std::ops::Range;
+
+    /// Checks the ranges are each non-empty, in order, and neither overlap nor touch.
+    fn check<T: Ord + Clone>(set: &RangeSet<T>) {
+        let ranges: Vec<Range<T>> = set.iter().collect();
+        assert!(ranges.iter().all(|r| r.start < r.end));
+        assert!(ranges.windows(2).all(|w| w[0].end < w[1].start));
+    }
+
+    fn pairs(ranges: impl Iterator<Item = Range<i32>>) -> Vec<(i32, i32)> {
+        ranges.map(|r| (r.start, r.end)).collect()
+    }
+
+    #[test]
+    fn basics() {
+        let mut set = RangeSet::new();
+
+        // Check empty set behaves right
+        assert!(set.is_empty());
+        assert!(!set.contains(&0));
+        assert_eq!(pairs(set.gaps(0..5)), [(0, 5)]);
+
+        // Populate set: the example in the module docs
+        set.extend(vec![5..7, 0..3, 9..12]);
+        assert_eq!(set.len(), 3);
+        assert!(set.contains(&0) && set.contains(&2) && !set.contains(&3));
+        assert!(set.contains_range(&(9..12)));
+        assert!(!set.contains_range(&(2..6)));
+        assert!(set.contains_range(&(4..4)));
+
+        set.insert(2..5);
+        assert_eq!(pairs(set.iter()), [(0, 7), (9, 12)]);
+        set.remove(4..10);
+        assert_eq!(pairs(set.iter()), [(0, 4), (10, 12)]);
+        assert_eq!(pairs(set.gaps(0..14)), [(4, 10), (12, 14)]);
+        assert_eq!(format!("{:?}", set), "{0..4, 10..12}");
+
+        // Empty ranges change nothing
+        set.insert(20..20);
+        set.remove(Range { start: 3, end: 1 });
+        assert_eq!(set.len(), 2);
+
+        // Check exhaustion
+        set.remove(0..100);
+        assert!(set.is_empty());
+    }
+
+    #[test]
+    fn merging() {
+        let mut set = RangeSet::new();
+
+        // Touching merges, as the two together have no gap
+        set.insert(0..3);
+        set.insert(3..5);
+        set.insert(-2..0);
+        assert_eq!(pairs(set.iter()), [(-2, 5)]);
+
+        // Swallowing several
+        set.insert(10..12);
+        set.insert(14..15);
+        set.insert(20..30);
+        set.insert(8..21);
+        assert_eq!(pairs(set.iter()), [(-2, 5), (8, 30)]);
+
+        // Inside what's there already
+        set.insert(9..10);
+        assert_eq!(set.len(), 2);
+
+        // Splitting one in two, and the pieces left
+        set.remove(12..13);
+        assert_eq!(pairs(set.iter()), [(-2, 5), (8, 12), (13, 30)]);
+        assert!(set.contains(&11) && !set.contains(&12) && set.contains(&13));
+        assert_eq!(pairs(set.gaps(0..10)), [(5, 8)]);
+        assert_eq!(set.gaps(9..11).count(), 0);
+        check(&set);
+    }
+
+    #[test]
+    fn other_types() {
+        // Anything ordered will do, like strings, where no two values are next to each other
+        let mut set: RangeSet<String> = RangeSet::new();
+        set.insert("apple".to_string().."banana".to_string());
+        set.insert("cherry".to_string().."date".to_string());
+        assert!(set.contains(&"avocado".to_string()));
+        assert!(!set.contains(&"banana".to_string()));
+        let gaps: Vec<_> = set.gaps("a".to_string().."z".to_string()).collect();
+        assert_eq!(gaps.len(), 3);
+        assert_eq!(gaps[1], "banana".to_string().."cherry".to_string());
+    }
+
+    #[test]
+    fn against_bitmap() {
+        // Random inserts and removals over a small universe, against a flag per value
+        let mut x: u32 = 1;
+        let mut next = |n: u32| {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (x >> 8) % n
+        };
+        let n = 200;
+        let mut set = RangeSet::new();
+        let mut model = vec![false; n as usize];
+        let steps = if cfg!(miri) { 200 } else { 5_000 };
+        for _ in 0..steps {
+            let start = next(n);
+            let range = start..(start + next(30)).min(n);
+            let inserting = next(3) > 0;
+            for flag in &mut model[range.start as usize..range.end as usize] {
+                *flag = inserting;
+            }
+            if inserting {
+                set.insert(range);
+            } else {
+                set.remove(range);
+            }
+            check(&set);
+
+            for v in 0..n {
+                assert_eq!(set.contains(&v), model[v as usize]);
+            }
+            let (a, b) = (next(n + 1), next(n + 1));
+            let window = a.min(b)..a.max(b);
+            let mut uncovered = vec![false; n as usize];
+            for gap in set.gaps(window.clone()) {
+                assert!(!gap.is_empty());
+                for v in gap {
+                    uncovered[v as usize] = true;
+                }
+            }
+            for v in 0..n {
+                assert_eq!(
+                    uncovered[v as usize],
+                    window.contains(&v) && !model[v as usize]
+                );
+            }
+        }
+    }
+}
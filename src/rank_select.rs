@@ -0,0 +1,494 @@
+//! # Rank/select bit vector
+//!
+//! An immutable bit vector that also answers, in constant time:
+//!
+//! - `rank1(i)`: how many ones are in the first `i` bits;
+//! - `select1(k)`: where the `k`th one is, counting from 0. It is the inverse of rank:
+//!   `rank1(select1(k)) == k`.
+//!
+//! and the same for zeros. Together they let bit vectors stand in for pointers, which is
+//! what succinct structures are made of. The wavelet tree in [`crate::wavelet_tree`], for
+//! one, is a stack of these, walked with nothing but rank and select.
+//!
+//! Rank comes from a two-level directory of running totals (Jacobson, 1989). Every
+//! superblock of 2^16 bits records how many ones came before it, and every block of 512
+//! bits, 8 words, records in a `u16` how many came before it within its superblock. A rank
+//! is two lookups plus at most 8 `count_ones`:
+//!
+//! ```text
+//!   bits:    [ w0 ... w7 ][ w8 ... w15 ] ... [ w1024 ... ][ w1032 ... ] ...
+//!   blocks:  0            131                0            258           within the super
+//!   supers:  0                               31000                      ones before
+//!
+//!   rank1(1033 * 64 + 6) = supers[1] + blocks[129] + ones(w1032) + ones(w1033 below bit 6)
+//! ```
+//!
+//! Select follows Clark (1996). The ones are cut into groups of 8192, and each group
+//! records where its first one is. A group spread over 2^24 bits or more is rare enough to
+//! store the position of every one it holds outright. Any other group fits its offsets in
+//! a `u32`, and records the offset of every 512th one within it, splitting it further into
+//! minigroups. A minigroup spread over 2^17 bits or more again stores all its offsets. The
+//! rest are narrow enough that the one we want is within 256 blocks of the minigroup's
+//! first, and a binary search of the rank directory over just those finds it:
+//!
+//! ```text
+//!   group:     8192 ones  --  spread >= 2^24?  every position         (long)
+//!                         \-  otherwise        every 512th offset     (short)
+//!   minigroup:  512 ones  --  spread >= 2^17?  every offset           (sparse)
+//!                         \-  otherwise        search <= 256 blocks   (dense)
+//! ```
+//!
+//! No step depends on the length, so select is O(1) as well. The zeros get a select
+//! directory of their own, built the same way.
+//!
+//! The textbook sizes grow with log n, which is what makes the directories o(n); these are
+//! fixed around their values for 64-bit lengths. Long groups' positions cost at most 1/32
+//! of the bits they cover, and sparse minigroups' at most 1/8. All told, the directories
+//! come to around an eighth of the bits, against a quarter for a plain sampled select.
+
+use std::iter::FromIterator;
+
+const BITS: usize = 64;
+const WORDS_PER_BLOCK: usize = 8;
+const BLOCK_BITS: usize = BITS * WORDS_PER_BLOCK;
+const BLOCKS_PER_SUPER: usize = 128;
+// Small enough that a block's count within its superblock fits in a `u16`
+const SUPER_BITS: usize = BLOCK_BITS * BLOCKS_PER_SUPER;
+
+// Select's ones (or zeros) per group, and the spread past which a group stores them all
+const GROUP: usize = 8192;
+const LONG: usize = 1 << 24;
+// The same for minigroups, of which there're as many per group as `Group::sparse` has bits
+const MINI: usize = 512;
+const SPARSE: usize = 1 << 17;
+const MINIS_PER_GROUP: usize = GROUP / MINI;
+
+#[derive(Clone, Debug)]
+pub struct RankSelect {
+    words: Vec<u64>,
+    len: usize,
+    ones: usize,
+    // supers[s] is the number of ones before superblock s
+    supers: Vec<usize>,
+    // blocks[b] is the number of ones before block b, counting from the start of its superblock
+    blocks: Vec<u16>,
+    select_ones: Select,
+    select_zeros: Select,
+}
+
+/// Select's directory for one kind of bit.
+#[derive(Clone, Debug, Default)]
+struct Select {
+    groups: Vec<Group>,
+    // Every position in the long groups
+    long: Vec<usize>,
+    // Every offset in the sparse minigroups
+    sparse: Vec<u32>,
+}
+
+#[derive(Clone, Debug)]
+struct Group {
+    // Position of the group's first bit
+    start: usize,
+    long: bool,
+    // Where the group's positions start in `long`, or its sparse minigroups' in `sparse`
+    explicit: usize,
+    // Bit m is set if minigroup m is sparse
+    sparse: u16,
+    // Offset of each minigroup's first bit from `start`
+    minis: [u32; MINIS_PER_GROUP],
+}
+
+impl Select {
+    /// Builds the directory from the positions of every bit of one kind, in order.
+    fn new<I: Iterator<Item = usize>>(mut positions: I) -> Self {
+        let mut select = Select::default();
+        let mut group = Vec::with_capacity(GROUP);
+        loop {
+            group.clear();
+            group.extend(positions.by_ref().take(GROUP));
+            if group.is_empty() {
+                return select;
+            }
+            select.push_group(&group);
+        }
+    }
+
+    fn push_group(&mut self, positions: &[usize]) {
+        let start = positions[0];
+        let mut group = Group {
+            start,
+            long: positions[positions.len() - 1] - start >= LONG,
+            explicit: 0,
+            sparse: 0,
+            minis: [0; MINIS_PER_GROUP],
+        };
+        if group.long {
+            group.explicit = self.long.len();
+            self.long.extend_from_slice(positions);
+        } else {
+            group.explicit = self.sparse.len();
+            for (m, mini) in positions.chunks(MINI).enumerate() {
+                // Spreads under `LONG` fit in a `u32`
+                group.minis[m] = (mini[0] - start) as u32;
+                if mini[mini.len() - 1] - mini[0] >= SPARSE {
+                    group.sparse |= 1 << m;
+                    self.sparse.extend(mini.iter().map(|&i| (i - start) as u32));
+                }
+            }
+        }
+        self.groups.push(group);
+    }
+}
+
+impl RankSelect {
+    /// Builds the vector from `len` bits packed into `words`, least significant bit first.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `words` has too few bits for `len`.
+    pub fn from_words(mut words: Vec<u64>, len: usize) -> Self {
+        let needed = len.div_ceil(BITS);
+        assert!(words.len() >= needed, "too few words for {} bits", len);
+        words.truncate(needed);
+        // Bits past the end would throw off the counts
+        if !len.is_multiple_of(BITS) {
+            words[needed - 1] &= (1 << (len % BITS)) - 1;
+        }
+
+        let mut supers = Vec::with_capacity(needed.div_ceil(SUPER_BITS / BITS));
+        let mut blocks = Vec::with_capacity(needed.div_ceil(WORDS_PER_BLOCK));
+        let mut ones = 0;
+        for (b, chunk) in words.chunks(WORDS_PER_BLOCK).enumerate() {
+            if b % BLOCKS_PER_SUPER == 0 {
+                supers.push(ones);
+            }
+            blocks.push((ones - supers[supers.len() - 1]) as u16);
+            ones += chunk.iter().map(|w| w.count_ones() as usize).sum::<usize>();
+        }
+
+        let select_ones = Select::new(positions(&words, len, true));
+        let select_zeros = Select::new(positions(&words, len, false));
+        RankSelect {
+            words,
+            len,
+            ones,
+            supers,
+            blocks,
+            select_ones,
+            select_zeros,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn count_ones(&self) -> usize {
+        self.ones
+    }
+
+    pub fn count_zeros(&self) -> usize {
+        self.len - self.ones
+    }
+
+    /// Returns bit `i`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `i >= len`.
+    pub fn get(&self, i: usize) -> bool {
+        assert!(
+            i < self.len,
+            "index {} out of bounds for length {}",
+            i,
+            self.len
+        );
+        self.words[i / BITS] & (1 << (i % BITS)) != 0
+    }
+
+    /// Number of ones before block `b`, which must exist.
+    fn before_block(&self, b: usize) -> usize {
+        self.supers[b / BLOCKS_PER_SUPER] + self.blocks[b] as usize
+    }
+
+    /// Returns the number of ones in the first `i` bits.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `i > len`.
+    pub fn rank1(&self, i: usize) -> usize {
+        assert!(
+            i <= self.len,
+            "index {} out of bounds for length {}",
+            i,
+            self.len
+        );
+        // The end may be past the last block
+        if i == self.len {
+            return self.ones;
+        }
+        let word = i / BITS;
+        let first = word - word % WORDS_PER_BLOCK;
+        let mut rank = self.before_block(word / WORDS_PER_BLOCK);
+        rank += self.words[first..word]
+            .iter()
+            .map(|w| w.count_ones() as usize)
+            .sum::<usize>();
+        if !i.is_multiple_of(BITS) {
+            rank += (self.words[word] & ((1 << (i % BITS)) - 1)).count_ones() as usize;
+        }
+        rank
+    }
+
+    /// Returns the number of zeros in the first `i` bits.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `i > len`.
+    pub fn rank0(&self, i: usize) -> usize {
+        i - self.rank1(i)
+    }
+
+    /// Returns the position of the `k`th one, counting from 0, if there are that many.
+    pub fn select1(&self, k: usize) -> Option<usize> {
+        (k < self.count_ones()).then(|| self.select(k, true))
+    }
+
+    /// Returns the position of the `k`th zero, counting from 0, if there are that many.
+    pub fn select0(&self, k: usize) -> Option<usize> {
+        (k < self.count_zeros()).then(|| self.select(k, false))
+    }
+
+    /// Position of the `k`th `bit`, which must exist.
+    fn select(&self, k: usize, bit: bool) -> usize {
+        let select = if bit {
+            &self.select_ones
+        } else {
+            &self.select_zeros
+        };
+        let group = &select.groups[k / GROUP];
+        let i = k % GROUP;
+        if group.long {
+            return select.long[group.explicit + i];
+        }
+        let m = i / MINI;
+        if group.sparse & (1 << m) != 0 {
+            // Skip the sparse minigroups before this one
+            let before = (group.sparse & ((1 << m) - 1)).count_ones() as usize;
+            let offset = select.sparse[group.explicit + before * MINI + i % MINI];
+            return group.start + offset as usize;
+        }
+
+        // The minigroup is dense, so the answer is in the blocks up to `SPARSE` bits past its
+        // first bit. Find the last of them with at most k `bit`s before it.
+        let first = group.start + group.minis[m] as usize;
+        let before = |b: usize| {
+            let ones = self.before_block(b);
+            if bit {
+                ones
+            } else {
+                b * BLOCK_BITS - ones
+            }
+        };
+        let mut lo = first / BLOCK_BITS;
+        let mut hi = ((first + SPARSE - 1) / BLOCK_BITS).min(self.blocks.len() - 1);
+        while lo < hi {
+            let mid = lo + (hi - lo).div_ceil(2);
+            if before(mid) <= k {
+                lo = mid;
+            } else {
+                hi = mid - 1;
+            }
+        }
+
+        let mut left = k - before(lo);
+        let mut word = lo * WORDS_PER_BLOCK;
+        loop {
+            let w = if bit {
+                self.words[word]
+            } else {
+                !self.words[word]
+            };
+            let count = w.count_ones() as usize;
+            if left < count {
+                return word * BITS + select_in_word(w, left);
+            }
+            left -= count;
+            word += 1;
+        }
+    }
+}
+
+/// Positions of every `bit` in the first `len` bits of `words`, in order.
+fn positions(words: &[u64], len: usize, bit: bool) -> impl Iterator<Item = usize> + '_ {
+    words.iter().enumerate().flat_map(move |(i, &w)| {
+        let mut w = if bit { w } else { !w };
+        // Padding past the end reads as zeros, and mustn't be counted as any
+        if (i + 1) * BITS > len {
+            w &= (1 << (len % BITS)) - 1;
+        }
+        std::iter::from_fn(move || {
+            (w != 0).then(|| {
+                let j = w.trailing_zeros() as usize;
+                w &= w - 1;
+                i * BITS + j
+            })
+        })
+    })
+}
+
+/// Position of the `k`th set bit of `w`, which must have more than `k`.
+fn select_in_word(mut w: u64, k: usize) -> usize {
+    for _ in 0..k {
+        // Clear the lowest set bit
+        w &= w - 1;
+    }
+    w.trailing_zeros() as usize
+}
+
+impl FromIterator<bool> for RankSelect {
+    fn from_iter<I: IntoIterator<Item = bool>>(iter: I) -> Self {
+        let mut words = Vec::new();
+        let mut len = 0;
+        for bit in iter {
+            if len % BITS == 0 {
+                words.push(0);
+            }
+            if bit {
+                words[len / BITS] |= 1 << (len % BITS);
+            }
+            len += 1;
+        }
+        RankSelect::from_words(words, len)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::RankSelect;
+
+    /// Checks every rank and select against counting by hand.
+    fn check_against_naive(bits: &[bool]) {
+        let vector: RankSelect = bits.iter().copied().collect();
+        assert_eq!(vector.len(), bits.len());
+        let (mut ones, mut zeros) = (Vec::new(), Vec::new());
+        for (i, &bit) in bits.iter().enumerate() {
+            assert_eq!(vector.get(i), bit);
+            assert_eq!(vector.rank1(i), ones.len());
+            assert_eq!(vector.rank0(i), zeros.len());
+            if bit {
+                ones.push(i);
+            } else {
+                zeros.push(i);
+            }
+        }
+        assert_eq!(vector.rank1(bits.len()), ones.len());
+        assert_eq!(vector.count_ones(), ones.len());
+        assert_eq!(vector.count_zeros(), zeros.len());
+        for (k, &i) in ones.iter().enumerate() {
+            assert_eq!(vector.select1(k), Some(i));
+        }
+        for (k, &i) in zeros.iter().enumerate() {
+            assert_eq!(vector.select0(k), Some(i));
+        }
+        assert_eq!(vector.select1(ones.len()), None);
+        assert_eq!(vector.select0(zeros.len()), None);
+    }
+
+    #[test]
+    fn basics() {
+        // Check empty vector behaves right
+        let empty: RankSelect = std::iter::empty().collect();
+        assert!(empty.is_empty());
+        assert_eq!(empty.rank1(0), 0);
+        assert_eq!(empty.select1(0), None);
+        assert_eq!(empty.select0(0), None);
+
+        let bits = [true, false, false, true, true, false, true];
+        let vector: RankSelect = bits.iter().copied().collect();
+        assert_eq!(vector.rank1(4), 2);
+        assert_eq!(vector.rank0(4), 2);
+        assert_eq!(vector.select1(2), Some(4));
+        assert_eq!(vector.select0(2), Some(5));
+        assert_eq!(vector.select0(3), None);
+        check_against_naive(&bits);
+
+        // Bits past `len` in the words are ignored
+        let vector = RankSelect::from_words(vec![!0, !0], 70);
+        assert_eq!(vector.count_ones(), 70);
+        assert_eq!(vector.count_zeros(), 0);
+        assert_eq!(vector.select1(69), Some(69));
+        assert_eq!(vector.select0(0), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "index 8 out of bounds for length 7")]
+    fn rank_out_of_bounds() {
+        let vector: RankSelect = [true; 7].iter().copied().collect();
+        vector.rank1(8);
+    }
+
+    #[test]
+    fn uniform() {
+        // All ones, all zeros, and lengths around the word and superblock sizes
+        for &len in &[1, 63, 64, 65, 511, 512, 513, 5000, 70_000] {
+            check_against_naive(&vec![true; len]);
+            check_against_naive(&vec![false; len]);
+        }
+    }
+
+    #[test]
+    fn against_naive() {
+        let n = if cfg!(miri) { 2_000 } else { 50_000 };
+        let mut x: u32 = 1;
+        // From sparse to dense, plus long runs, which bunch the samples together
+        for density in [1, 16, 128, 250] {
+            let bits: Vec<bool> = (0..n)
+                .map(|i| {
+                    x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                    (x >> 24) < density || (i / 3000) % 4 == 1
+                })
+                .collect();
+            check_against_naive(&bits);
+        }
+    }
+
+    #[test]
+    fn spread_out() {
+        // A one every `gap` bits: 260 spreads each 512 ones over more than 2^17 bits, making
+        // sparse minigroups, and 4096 spreads each 8192 over more than 2^24, making long groups
+        for &(gap, len) in &[(260, 1 << 22), (4096, 1 << 26)] {
+            let len = if cfg!(miri) { 1 << 14 } else { len };
+            let mut words = vec![0; len / 64];
+            for i in (0..len).step_by(gap) {
+                words[i / 64] |= 1 << (i % 64);
+            }
+            let vector = RankSelect::from_words(words, len);
+            if !cfg!(miri) {
+                let ones = &vector.select_ones;
+                assert_eq!(!ones.sparse.is_empty(), gap == 260);
+                assert_eq!(!ones.long.is_empty(), gap == 4096);
+            }
+            let ones = len.div_ceil(gap);
+            assert_eq!(vector.count_ones(), ones);
+            for k in 0..ones {
+                assert_eq!(vector.select1(k), Some(k * gap));
+                assert_eq!(vector.rank1(k * gap), k);
+                assert_eq!(vector.rank1(k * gap + 1), k + 1);
+            }
+            assert_eq!(vector.select1(ones), None);
+
+            // The zeros are dense, so a sample of them is plenty
+            for k in (0..vector.count_zeros()).step_by(997) {
+                let i = k / (gap - 1) * gap + 1 + k % (gap - 1);
+                assert_eq!(vector.select0(k), Some(i));
+                assert_eq!(vector.rank0(i), k);
+            }
+        }
+    }
+}
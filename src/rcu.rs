@@ -0,0 +1,339 @@
+//! # RCU-style read-mostly list
+//!
+//! Read-copy-update in miniature. The list is persistent, like [`crate::persistent::List`],
+//! so a version never changes once built; all the shared state is a single pointer to the
+//! current version.
+//!
+//! - **Readers** pin, load the pointer and clone the list, which is one reference count
+//!   bump. They never lock or retry, and the snapshot they get stays exactly as it was no
+//!   matter what writers do afterwards.
+//! - **Writers** take a lock (so they don't lose each other's updates), build the new
+//!   version out of the old one, and swap the pointer. Persistence makes the copy cheap:
+//!   pushing to the front shares the whole old spine, and removing an element only copies
+//!   the nodes in front of it.
+//!
+//! ```text
+//! current --> [v2] X -> A -> B -> C        readers that loaded v1 keep it alive
+//!                        ^
+//!             [v1] ------+
+//! ```
+//!
+//! The old version can't be dropped the moment it's swapped out: a reader may have loaded
+//! the pointer and not yet bumped the count. That's the "grace period" of RCU, and here the
+//! epoch collector in [`crate::epoch`] provides it.
+//!
+//! Versions are handed between threads, so this list counts its nodes with `Arc` where the
+//! one in [`crate::persistent`] gets away with `Rc`. Otherwise it's the same list, cut down
+//! to what readers and writers need.
+
+use crate::epoch;
+use std::sync::atomic::{AtomicPtr, Ordering::SeqCst};
+use std::sync::{Arc, Mutex};
+
+/// A version of an [`RcuList`]: a persistent singly-linked list, shareable across threads.
+pub struct List<T> {
+    head: Link<T>,
+}
+
+type Link<T> = Option<Arc<Node<T>>>;
+
+struct Node<T> {
+    elem: T,
+    next: Link<T>,
+}
+
+impl<T> List<T> {
+    /// Creates an empty List.
+    pub fn new() -> Self {
+        List { head: None }
+    }
+
+    /// Returns a new list with `elem` in front of this one, sharing all of it.
+    pub fn append(&self, elem: T) -> List<T> {
+        List {
+            head: Some(Arc::new(Node {
+                elem,
+                next: self.head.clone(),
+            })),
+        }
+    }
+
+    /// Returns the list without its first element, sharing the rest.
+    pub fn tail(&self) -> List<T> {
+        List {
+            head: self.head.as_ref().and_then(|node| node.next.clone()),
+        }
+    }
+
+    /// Returns a reference to the first element
+    pub fn head(&self) -> Option<&T> {
+        self.head.as_ref().map(|node| &node.elem)
+    }
+
+    /// Returns true if the list has no elements.
+    pub fn is_empty(&self) -> bool {
+        self.head.is_none()
+    }
+
+    /// Returns the number of elements, walking the whole spine.
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            next: self.head.as_deref(),
+        }
+    }
+}
+
+/// Cloning a list is just bumping the reference count on the head node.
+impl<T> Clone for List<T> {
+    fn clone(&self) -> Self {
+        List {
+            head: self.head.clone(),
+        }
+    }
+}
+
+impl<T> Default for List<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for List<T> {
+    fn drop(&mut self) {
+        // Iteratively, and only as far as the nodes that are ours alone
+        let mut head = self.head.take();
+        while let Some(node) = head {
+            match Arc::try_unwrap(node) {
+                Ok(mut node) => head = node.next.take(),
+                Err(_) => break,
+            }
+        }
+    }
+}
+
+pub struct Iter<'a, T> {
+    next: Option<&'a Node<T>>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next.map(|node| {
+            self.next = node.next.as_deref();
+            &node.elem
+        })
+    }
+}
+
+pub struct RcuList<T> {
+    current: AtomicPtr<List<T>>,
+    writer: Mutex<()>,
+}
+
+impl<T> RcuList<T>
+where
+    T: Send + Sync + 'static,
+{
+    /// Creates an empty RcuList.
+    pub fn new() -> Self {
+        Self::from_list(List::new())
+    }
+
+    /// Starts from an existing version of a list.
+    pub fn from_list(list: List<T>) -> Self {
+        RcuList {
+            current: AtomicPtr::new(Box::into_raw(Box::new(list))),
+            writer: Mutex::new(()),
+        }
+    }
+
+    /// Returns the current version. Never blocks and never retries.
+    pub fn read(&self) -> List<T> {
+        let _guard = epoch::pin();
+        // The pinned guard keeps the pointee alive until we've taken our own reference
+        unsafe { (*self.current.load(SeqCst)).clone() }
+    }
+
+    /// Replaces the current version with `f(current)`. Writers run one at a time, each one
+    /// seeing the result of the last.
+    pub fn update<F>(&self, f: F)
+    where
+        F: FnOnce(&List<T>) -> List<T>,
+    {
+        let _writer = self.writer.lock().unwrap();
+        let guard = epoch::pin();
+
+        let old = self.current.load(SeqCst);
+        let new = Box::into_raw(Box::new(f(unsafe { &*old })));
+        self.current.store(new, SeqCst);
+        // Readers might still be about to clone the old version
+        unsafe { guard.defer_destroy(old) };
+    }
+
+    /// Pushes an element onto the front, sharing the entire old spine.
+    pub fn push_front(&self, elem: T) {
+        self.update(|list| list.append(elem));
+    }
+}
+
+impl<T> RcuList<T>
+where
+    T: Clone + Send + Sync + 'static,
+{
+    /// Removes the front element, returning a copy of it. The old versions still have it.
+    pub fn pop_front(&self) -> Option<T> {
+        let mut popped = None;
+        self.update(|list| {
+            popped = list.head().cloned();
+            list.tail()
+        });
+        popped
+    }
+
+    /// Removes the first element equal to `elem`. Only the nodes in front of it are copied;
+    /// everything after it is shared with the old version.
+    pub fn remove(&self, elem: &T) -> bool
+    where
+        T: PartialEq,
+    {
+        let mut removed = false;
+        self.update(|list| {
+            let mut prefix = Vec::new();
+            let mut rest = list.clone();
+            while let Some(head) = rest.head() {
+                if head == elem {
+                    removed = true;
+                    break;
+                }
+                prefix.push(head.clone());
+                rest = rest.tail();
+            }
+            if !removed {
+                return list.clone();
+            }
+
+            let mut new = rest.tail();
+            for elem in prefix.into_iter().rev() {
+                new = new.append(elem);
+            }
+            new
+        });
+        removed
+    }
+}
+
+impl<T> Default for RcuList<T>
+where
+    T: Send + Sync + 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for RcuList<T> {
+    fn drop(&mut self) {
+        // Older versions went through the collector; the current one is ours alone
+        unsafe { drop(Box::from_raw(*self.current.get_mut())) };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{List, RcuList};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    fn to_vec(list: &List<i32>) -> Vec<i32> {
+        list.iter().cloned().collect()
+    }
+
+    #[test]
+    fn basics() {
+        let list = RcuList::new();
+        assert!(list.read().is_empty());
+        assert_eq!(list.pop_front(), None);
+
+        list.push_front(3);
+        list.push_front(2);
+        list.push_front(1);
+        assert_eq!(to_vec(&list.read()), vec![1, 2, 3]);
+
+        assert_eq!(list.pop_front(), Some(1));
+        assert_eq!(to_vec(&list.read()), vec![2, 3]);
+
+        list.update(|l| l.append(10).append(20));
+        assert_eq!(to_vec(&list.read()), vec![20, 10, 2, 3]);
+    }
+
+    #[test]
+    fn snapshots_dont_change() {
+        let list = RcuList::from_list(List::new().append(3).append(2).append(1));
+        let before = list.read();
+
+        list.push_front(0);
+        assert!(list.remove(&2));
+        assert!(!list.remove(&42));
+
+        assert_eq!(to_vec(&before), vec![1, 2, 3]);
+        assert_eq!(to_vec(&list.read()), vec![0, 1, 3]);
+    }
+
+    #[test]
+    fn remove_shares_the_suffix() {
+        let list = RcuList::from_list(List::new().append(4).append(3).append(2).append(1));
+        let before = list.read();
+        assert!(list.remove(&2));
+        let after = list.read();
+
+        // 1 had to be copied, but 3 -> 4 is the very same nodes as before
+        assert_eq!(to_vec(&after), vec![1, 3, 4]);
+        let old = before.head.as_ref().unwrap();
+        let new = after.head.as_ref().unwrap();
+        assert!(!Arc::ptr_eq(old, new));
+        let old_three = old.next.as_ref().unwrap().next.as_ref().unwrap();
+        assert!(Arc::ptr_eq(old_three, new.next.as_ref().unwrap()));
+    }
+
+    #[test]
+    fn readers_see_consistent_versions() {
+        let n = if cfg!(miri) { 50 } else { 5_000 };
+        let list = Arc::new(RcuList::new());
+        let done = Arc::new(AtomicBool::new(false));
+
+        // The writer pushes 0, 1, 2, ... so every version is a countdown to zero
+        let readers: Vec<_> = (0..3)
+            .map(|_| {
+                let list = list.clone();
+                let done = done.clone();
+                thread::spawn(move || {
+                    while !done.load(Ordering::SeqCst) {
+                        let snapshot = list.read();
+                        let values = to_vec(&snapshot);
+                        let len = values.len() as i32;
+                        assert!(values.iter().rev().copied().eq(0..len));
+                        thread::yield_now();
+                    }
+                })
+            })
+            .collect();
+
+        for i in 0..n {
+            list.push_front(i);
+            if i % 10 == 0 {
+                thread::yield_now();
+            }
+        }
+        done.store(true, Ordering::SeqCst);
+        for reader in readers {
+            reader.join().unwrap();
+        }
+        assert_eq!(list.read().len(), n as usize);
+    }
+}
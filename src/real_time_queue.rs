@@ -0,0 +1,392 @@
+//! # Real-time persistent queue
+//!
+//! Okasaki's real-time queue (1995): a persistent FIFO queue where every operation is O(1)
+//! in the worst case, not just amortized, and so stays O(1) however the versions are
+//! reused.
+//!
+//! The [banker's queue](crate::persistent::Queue) reverses its whole back list into the
+//! front when the front runs out. That's O(n) once in a while, paid for by the O(1)
+//! operations before it, but only as long as nobody goes back to the version just before a
+//! reversal and dequeues from it again: each time, the full reversal happens again. This
+//! queue reverses the same way, but lazily and a step at a time, so no single operation
+//! ever does more than a step.
+//!
+//! The front is a lazy stream. When the back is about to outgrow the front, the queue
+//! replaces the front by a suspended *rotation*, `front ++ reverse(back)`, which produces
+//! one element each time it's forced. It also keeps a *schedule*, a pointer into the front
+//! at the first cell not yet forced, and every push and pop forces exactly one cell there
+//! before moving the schedule along:
+//!
+//! ```text
+//!   front     [1] [2] [3]  (4)  (5)  (6)        []  forced   ()  suspended
+//!                           ^
+//!   schedule ---------------+          back: 9 8 7
+//! ```
+//!
+//! The schedule is always as long as the front minus the back, so it's through the whole
+//! front by the time the back has caught up and the next rotation starts. Everything
+//! before the schedule is forced, and forcing the cell at it only looks at its already
+//! forced predecessor, so it's O(1). Forced cells are memoized and shared by every version
+//! pointing at them, which is what makes reusing a version cost nothing extra.
+
+use crate::persistent::List;
+use std::cell::{OnceCell, RefCell};
+use std::fmt;
+use std::iter::FromIterator;
+use std::rc::Rc;
+
+/// A lazy list: each cell is forced, at most once, when first looked at.
+struct Stream<T>(Rc<Susp<T>>);
+
+struct Susp<T> {
+    value: OnceCell<Option<(T, Stream<T>)>>,
+    // How to work out `value`, until it's been worked out
+    pending: RefCell<Option<Rotation<T>>>,
+}
+
+/// The suspended `front ++ reverse(rear) ++ acc`, for a rear one longer than the front.
+struct Rotation<T> {
+    front: Stream<T>,
+    rear: List<T>,
+    acc: Stream<T>,
+}
+
+impl<T> Clone for Stream<T> {
+    fn clone(&self) -> Self {
+        Stream(self.0.clone())
+    }
+}
+
+impl<T> Stream<T> {
+    fn forced(value: Option<(T, Stream<T>)>) -> Self {
+        Stream(Rc::new(Susp {
+            value: OnceCell::from(value),
+            pending: RefCell::new(None),
+        }))
+    }
+
+    fn nil() -> Self {
+        Stream::forced(None)
+    }
+
+    fn cons(elem: T, rest: Stream<T>) -> Self {
+        Stream::forced(Some((elem, rest)))
+    }
+
+    fn rotation(front: Stream<T>, rear: List<T>, acc: Stream<T>) -> Self {
+        Stream(Rc::new(Susp {
+            value: OnceCell::new(),
+            pending: RefCell::new(Some(Rotation { front, rear, acc })),
+        }))
+    }
+}
+
+impl<T: Clone> Stream<T> {
+    fn force(&self) -> Option<&(T, Stream<T>)> {
+        self.0
+            .value
+            .get_or_init(|| {
+                let rotation = self.0.pending.borrow_mut().take();
+                rotation.unwrap().step()
+            })
+            .as_ref()
+    }
+}
+
+impl<T: Clone> Rotation<T> {
+    /// Works out the first cell. The front's first cell is always forced already.
+    fn step(self) -> Option<(T, Stream<T>)> {
+        let last = self.rear.head().unwrap().clone();
+        match self.front.force() {
+            None => Some((last, self.acc)),
+            Some((elem, rest)) => {
+                let acc = Stream::cons(last, self.acc);
+                let rest = Stream::rotation(rest.clone(), self.rear.tail(), acc);
+                Some((elem.clone(), rest))
+            }
+        }
+    }
+}
+
+impl<T> Drop for Susp<T> {
+    fn drop(&mut self) {
+        // Unlink the forced cells only this stream holds one at a time, not recursively
+        let mut next = self.value.take().flatten().map(|(_, rest)| rest);
+        while let Some(Stream(susp)) = next {
+            next = match Rc::try_unwrap(susp) {
+                Ok(mut susp) => susp.value.take().flatten().map(|(_, rest)| rest),
+                Err(_) => None,
+            };
+        }
+    }
+}
+
+pub struct Queue<T> {
+    front: Stream<T>,
+    rear: List<T>,
+    // The first cell of `front` that hasn't been forced yet
+    schedule: Stream<T>,
+    len: usize,
+}
+
+impl<T> Queue<T> {
+    /// Creates an empty Queue.
+    pub fn new() -> Self {
+        let nil = Stream::nil();
+        Queue {
+            front: nil.clone(),
+            rear: List::new(),
+            schedule: nil,
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl<T: Clone> Queue<T> {
+    /// Returns a reference to the element at the front of the queue.
+    pub fn peek(&self) -> Option<&T> {
+        self.front.force().map(|(elem, _)| elem)
+    }
+
+    /// Returns a new queue with `elem` added at the back.
+    pub fn enqueue(&self, elem: T) -> Queue<T> {
+        Queue::exec(
+            self.front.clone(),
+            self.rear.append(elem),
+            &self.schedule,
+            self.len + 1,
+        )
+    }
+
+    /// Returns a new queue with the front element removed. Dequeuing an empty queue gives
+    /// an empty queue.
+    pub fn dequeue(&self) -> Queue<T> {
+        match self.front.force() {
+            None => Queue::new(),
+            Some((_, rest)) => Queue::exec(
+                rest.clone(),
+                self.rear.clone(),
+                &self.schedule,
+                self.len - 1,
+            ),
+        }
+    }
+
+    /// Forces one step of the schedule, or starts a rotation once it's run out.
+    fn exec(front: Stream<T>, rear: List<T>, schedule: &Stream<T>, len: usize) -> Queue<T> {
+        match schedule.force() {
+            Some((_, rest)) => Queue {
+                front,
+                rear,
+                schedule: rest.clone(),
+                len,
+            },
+            None => {
+                let front = Stream::rotation(front, rear, Stream::nil());
+                Queue {
+                    front: front.clone(),
+                    rear: List::new(),
+                    schedule: front,
+                    len,
+                }
+            }
+        }
+    }
+
+    /// Iterates over the elements, front to back.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            front: Some(&self.front),
+            rear: self.rear.iter().collect(),
+        }
+    }
+}
+
+impl<T> Clone for Queue<T> {
+    fn clone(&self) -> Self {
+        Queue {
+            front: self.front.clone(),
+            rear: self.rear.clone(),
+            schedule: self.schedule.clone(),
+            len: self.len,
+        }
+    }
+}
+
+impl<T> Default for Queue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Clone> FromIterator<T> for Queue<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut queue = Queue::new();
+        for elem in iter {
+            queue = queue.enqueue(elem);
+        }
+        queue
+    }
+}
+
+impl<T: Clone + fmt::Debug> fmt::Debug for Queue<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// Iterator over the elements of a [`Queue`], front to back.
+pub struct Iter<'a, T> {
+    front: Option<&'a Stream<T>>,
+    // The back list is newest first, so this pops oldest first
+    rear: Vec<&'a T>,
+}
+
+impl<'a, T: Clone> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(stream) = self.front {
+            match stream.force() {
+                Some((elem, rest)) => {
+                    self.front = Some(rest);
+                    return Some(elem);
+                }
+                None => self.front = None,
+            }
+        }
+        self.rear.pop()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Queue, Stream};
+    use crate::persistent;
+    use std::collections::VecDeque;
+    use std::rc::Rc;
+
+    /// Checks the schedule is the front's suffix as long as the front minus the back, and
+    /// that everything before it is forced. Counting the schedule forces it, too.
+    fn check<T: Clone>(queue: &Queue<T>) {
+        let rear = queue.rear.len();
+        let front = queue.len - rear;
+        let mut stream = queue.front.clone();
+        for _ in 0..front - (front - rear) {
+            assert!(stream.0.value.get().is_some());
+            stream = stream.force().unwrap().1.clone();
+        }
+        assert!(Rc::ptr_eq(&stream.0, &queue.schedule.0));
+        let mut scheduled = 0;
+        while let Some((_, rest)) = stream.force() {
+            scheduled += 1;
+            stream = rest.clone();
+        }
+        assert_eq!(scheduled, front - rear);
+    }
+
+    /// How many cells of a stream are forced, from the start.
+    fn forced<T>(stream: &Stream<T>) -> usize {
+        let mut count = 0;
+        let mut stream = stream.clone();
+        while let Some(Some((_, rest))) = stream.0.value.get() {
+            count += 1;
+            stream = rest.clone();
+        }
+        count
+    }
+
+    #[test]
+    fn basics() {
+        let queue = Queue::new();
+
+        // Check empty queue behaves right
+        assert_eq!(queue.peek(), None);
+        assert!(queue.dequeue().is_empty());
+
+        // Populate queue
+        let queue = queue.enqueue(1).enqueue(2).enqueue(3);
+        assert_eq!(queue.len(), 3);
+        assert_eq!(queue.peek(), Some(&1));
+
+        // Check normal removal
+        let queue = queue.dequeue();
+        assert_eq!(queue.peek(), Some(&2));
+        let queue = queue.enqueue(4);
+        assert_eq!(format!("{:?}", queue), "[2, 3, 4]");
+
+        // Push some more just to make sure nothing's corrupted
+        let queue = (5..100).fold(queue, |queue, i| queue.enqueue(i));
+        check(&queue);
+        assert!(queue.iter().copied().eq(2..100));
+
+        // Check exhaustion
+        let queue = (2..100).fold(queue, |queue, _| queue.dequeue());
+        assert!(queue.is_empty());
+        assert_eq!(queue.peek(), None);
+    }
+
+    #[test]
+    fn reused_version() {
+        // A version right after a rotation started, with the whole reversal still ahead
+        let n = 1023;
+        let queue: Queue<u32> = (0..n).collect();
+        assert_eq!(forced(&queue.front), 0);
+
+        // Dequeuing it over and over forces a single cell each time, and the same one at
+        // that, since every copy shares the memoized stream
+        for _ in 0..100 {
+            let next = queue.dequeue();
+            assert_eq!(next.peek(), Some(&1));
+            assert!(forced(&queue.front) <= 2);
+        }
+        check(&queue.dequeue());
+
+        // The banker's queue in the same spot reverses the whole back every time instead
+        let banker = (0..n).fold(persistent::Queue::new(), |queue, i| queue.enqueue(i));
+        assert_eq!(banker.dequeue().peek(), Some(&1));
+    }
+
+    #[test]
+    fn long_drop() {
+        let n = if cfg!(miri) { 1_000 } else { 200_000 };
+        let queue: Queue<u32> = (0..n).collect();
+        let queue = (0..n / 2).fold(queue, |queue, _| queue.dequeue());
+        assert_eq!(queue.peek(), Some(&(n / 2)));
+        drop(queue);
+    }
+
+    #[test]
+    fn against_vec_deque() {
+        // Random operations on random versions, each checked against its own model
+        let mut versions = vec![(Queue::new(), VecDeque::new())];
+        let mut x: u32 = 1;
+        let n = if cfg!(miri) { 500 } else { 10_000 };
+        for _ in 0..n {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let (queue, mut model) = versions[(x >> 4) as usize % versions.len()].clone();
+            let next = if x >> 30 == 0 {
+                model.pop_front();
+                queue.dequeue()
+            } else {
+                model.push_back(x);
+                queue.enqueue(x)
+            };
+            assert_eq!(next.len(), model.len());
+            assert_eq!(next.peek(), model.front());
+            check(&next);
+            versions.push((next, model));
+        }
+        for (queue, model) in versions.iter().step_by(100) {
+            assert!(queue.iter().eq(model.iter()));
+        }
+    }
+}
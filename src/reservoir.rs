@@ -0,0 +1,367 @@
+//! # Reservoir sampling
+//!
+//! Keeps a uniform random sample of `k` items from a stream of unknown length, in O(k)
+//! memory, without knowing how long the stream will run: at any point, every item seen
+//! so far is in the sample with the same probability, `k / seen`.
+//!
+//! The classic way (Algorithm R) keeps the first `k` items, then replaces a random one
+//! with item `i` with probability `k / i`. That's a random number per item, even once the
+//! sample hardly ever changes. [`Reservoir`] uses Li's Algorithm L (1994) instead, which
+//! works out how many items to skip before the next replacement, so it draws random
+//! numbers only O(k log(n / k)) times:
+//!
+//! ```text
+//!   stream:   a b c d e f g h i j k l m n o p ...
+//!   k = 3:    [a b c]
+//!             next replacement in 2 -> e      [a e c]
+//!             next replacement in 5 -> j      [j e c]
+//!             ...
+//! ```
+//!
+//! [`WeightedReservoir`] samples with weights instead (Efraimidis and Spirakis, 2006,
+//! their A-Res): each item gets the key `u^(1 / weight)` for a uniform `u`, and the
+//! sample is the `k` items with the largest keys, held in a min-heap so the smallest key
+//! is the one to beat. That's the same as drawing `k` items without replacement, each draw
+//! picking an item in proportion to its weight.
+
+use crate::heap::BinaryHeap;
+use crate::rng::XorShift;
+use std::cmp::{Ordering, Reverse};
+
+#[derive(Clone, Debug)]
+pub struct Reservoir<T> {
+    sample: Vec<T>,
+    capacity: usize,
+    seen: u64,
+    // Index of the next item to take, once the sample is full
+    next: u64,
+    // Algorithm L's running value, the largest of k uniform variables
+    w: f64,
+    rng: XorShift,
+}
+
+impl<T> Reservoir<T> {
+    /// Creates an empty reservoir that keeps `capacity` items.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is 0.
+    pub fn new(capacity: usize) -> Self {
+        Self::with_rng(capacity, XorShift::from_entropy())
+    }
+
+    /// Like `new`, but the same seed always picks the same sample from the same stream.
+    pub fn with_seed(capacity: usize, seed: u64) -> Self {
+        Self::with_rng(capacity, XorShift::new(seed))
+    }
+
+    fn with_rng(capacity: usize, rng: XorShift) -> Self {
+        assert!(capacity > 0, "capacity must be at least 1");
+        Reservoir {
+            sample: Vec::with_capacity(capacity),
+            capacity,
+            seen: 0,
+            next: 0,
+            w: 1.0,
+            rng,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the number of items pushed so far.
+    pub fn seen(&self) -> u64 {
+        self.seen
+    }
+
+    /// Returns the current sample, in no particular order.
+    pub fn sample(&self) -> &[T] {
+        &self.sample
+    }
+
+    pub fn into_sample(self) -> Vec<T> {
+        self.sample
+    }
+
+    /// A uniform random number in `(0, 1]`, safe to take the log of.
+    fn uniform(&mut self) -> f64 {
+        1.0 - self.rng.next_f64()
+    }
+
+    /// Moves `w` and `next` on to the next item that will go in the sample.
+    fn schedule(&mut self) {
+        let k = self.capacity as f64;
+        self.w *= (self.uniform().ln() / k).exp();
+        // How many items to pass over, geometric with probability w. `as` saturates, so
+        // a huge skip just means nothing more gets in.
+        let skip = (self.uniform().ln() / (1.0 - self.w).ln()).floor();
+        self.next = self.next.saturating_add(skip as u64 + 1);
+    }
+
+    /// Offers the next item of the stream to the sample.
+    pub fn push(&mut self, item: T) {
+        if self.sample.len() < self.capacity {
+            self.sample.push(item);
+            if self.sample.len() == self.capacity {
+                self.next = self.seen;
+                self.schedule();
+            }
+        } else if self.seen == self.next {
+            let i = self.rng.below(self.capacity as u64) as usize;
+            self.sample[i] = item;
+            self.schedule();
+        }
+        self.seen += 1;
+    }
+}
+
+impl<T> Extend<T> for Reservoir<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for item in iter {
+            self.push(item);
+        }
+    }
+}
+
+/// An item and its key, ordered by the key alone.
+#[derive(Clone, Debug)]
+struct Keyed<T> {
+    key: f64,
+    item: T,
+}
+
+impl<T> PartialEq for Keyed<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T> Eq for Keyed<T> {}
+
+impl<T> PartialOrd for Keyed<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Keyed<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key.total_cmp(&other.key)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct WeightedReservoir<T> {
+    // A min-heap on the keys, so the weakest member is at the top
+    heap: BinaryHeap<Reverse<Keyed<T>>>,
+    capacity: usize,
+    seen: u64,
+    rng: XorShift,
+}
+
+impl<T> WeightedReservoir<T> {
+    /// Creates an empty reservoir that keeps `capacity` items.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is 0.
+    pub fn new(capacity: usize) -> Self {
+        Self::with_rng(capacity, XorShift::from_entropy())
+    }
+
+    /// Like `new`, but the same seed always picks the same sample from the same stream.
+    pub fn with_seed(capacity: usize, seed: u64) -> Self {
+        Self::with_rng(capacity, XorShift::new(seed))
+    }
+
+    fn with_rng(capacity: usize, rng: XorShift) -> Self {
+        assert!(capacity > 0, "capacity must be at least 1");
+        WeightedReservoir {
+            heap: BinaryHeap::with_capacity(capacity),
+            capacity,
+            seen: 0,
+            rng,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the number of items pushed so far.
+    pub fn seen(&self) -> u64 {
+        self.seen
+    }
+
+    /// Returns the number of items in the sample.
+    pub fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+
+    /// Offers the next item of the stream to the sample, with the given weight. An item
+    /// of weight 0 is never picked.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the weight is negative, infinite or NaN.
+    pub fn push(&mut self, item: T, weight: f64) {
+        assert!(
+            weight >= 0.0 && weight.is_finite(),
+            "weight must be finite and not negative"
+        );
+        self.seen += 1;
+        if weight == 0.0 {
+            return;
+        }
+        // ln(u^(1 / weight)), which orders the same and doesn't underflow to 0
+        let key = (1.0 - self.rng.next_f64()).ln() / weight;
+        let beats_weakest = self
+            .heap
+            .peek()
+            .is_none_or(|Reverse(weakest)| key > weakest.key);
+        if self.heap.len() < self.capacity {
+            self.heap.push(Reverse(Keyed { key, item }));
+        } else if beats_weakest {
+            self.heap.pop();
+            self.heap.push(Reverse(Keyed { key, item }));
+        }
+    }
+
+    /// Returns the current sample, in no particular order.
+    pub fn sample(&self) -> impl Iterator<Item = &T> {
+        self.heap.iter().map(|Reverse(keyed)| &keyed.item)
+    }
+
+    pub fn into_sample(self) -> Vec<T> {
+        self.heap
+            .into_vec()
+            .into_iter()
+            .map(|Reverse(keyed)| keyed.item)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Reservoir, WeightedReservoir};
+
+    /// A well-spread seed for each run. Xorshift takes a few rounds to get going from small
+    /// seeds like 1, 2, 3, and runs in a row would start out alike.
+    fn seed(run: u64) -> u64 {
+        (run + 1).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+    }
+
+    #[test]
+    fn basics() {
+        let mut reservoir = Reservoir::with_seed(3, 7);
+
+        // Check empty reservoir behaves right
+        assert!(reservoir.sample().is_empty());
+
+        // Fewer items than the capacity are all kept
+        reservoir.extend(["a", "b"]);
+        assert_eq!(reservoir.sample(), ["a", "b"]);
+        reservoir.push("c");
+        assert_eq!(reservoir.sample(), ["a", "b", "c"]);
+
+        // After that, the sample stays full and only holds items from the stream
+        reservoir.extend(["d", "e", "f", "g", "h"]);
+        assert_eq!(reservoir.seen(), 8);
+        let mut sample = reservoir.into_sample();
+        sample.sort_unstable();
+        sample.dedup();
+        assert_eq!(sample.len(), 3);
+        assert!(sample.iter().all(|s| ("a"..="h").contains(s)));
+    }
+
+    #[test]
+    #[should_panic(expected = "capacity must be at least 1")]
+    fn zero_capacity() {
+        Reservoir::<u32>::new(0);
+    }
+
+    #[test]
+    fn uniform() {
+        // Every item should end up in the sample equally often
+        let (n, k) = (100, 10);
+        let runs = if cfg!(miri) { 200 } else { 20_000 };
+        let mut hits = vec![0u32; n];
+        for run in 0..runs {
+            let mut reservoir = Reservoir::with_seed(k, seed(run));
+            reservoir.extend(0..n);
+            for &i in reservoir.sample() {
+                hits[i] += 1;
+            }
+        }
+        let expected = (runs as usize * k / n) as f64;
+        let tolerance = if cfg!(miri) { 0.8 } else { 0.15 };
+        for &count in &hits {
+            let off = (count as f64 - expected).abs() / expected;
+            assert!(off < tolerance, "{} hits, expected {}", count, expected);
+        }
+    }
+
+    #[test]
+    fn long_streams_are_cheap() {
+        // Skipping means late items are mostly passed over without a random draw
+        let mut reservoir = Reservoir::with_seed(5, 3);
+        reservoir.extend(0..1_000_000u32);
+        assert_eq!(reservoir.seen(), 1_000_000);
+        // Something from the second half made it in, as it should in all but 1 in 32 runs
+        assert!(reservoir.sample().iter().any(|&i| i >= 500_000));
+    }
+
+    #[test]
+    fn weighted_basics() {
+        let mut reservoir = WeightedReservoir::with_seed(2, 11);
+        assert!(reservoir.is_empty());
+        reservoir.push("never", 0.0);
+        assert!(reservoir.is_empty());
+        reservoir.push("a", 1.0);
+        reservoir.push("b", 1.0);
+        reservoir.push("c", 1.0);
+        assert_eq!(reservoir.len(), 2);
+        assert_eq!(reservoir.seen(), 4);
+        assert!(reservoir.sample().all(|&s| s != "never"));
+        assert_eq!(reservoir.into_sample().len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "weight must be finite and not negative")]
+    fn negative_weight() {
+        WeightedReservoir::new(1).push(1, -1.0);
+    }
+
+    #[test]
+    fn weighted_proportions() {
+        // Picking one item, each should win in proportion to its weight
+        let weights = [1.0, 2.0, 3.0, 4.0];
+        let runs = if cfg!(miri) { 400 } else { 40_000 };
+        let mut wins = [0u32; 4];
+        for run in 0..runs {
+            let mut reservoir = WeightedReservoir::with_seed(1, seed(run));
+            for (i, &weight) in weights.iter().enumerate() {
+                reservoir.push(i, weight);
+            }
+            wins[reservoir.into_sample()[0]] += 1;
+        }
+        for (i, &count) in wins.iter().enumerate() {
+            let expected = runs as f64 * weights[i] / 10.0;
+            let tolerance = if cfg!(miri) { 0.5 } else { 0.1 };
+            let off = (count as f64 - expected).abs() / expected;
+            assert!(
+                off < tolerance,
+                "item {} won {} times, expected {}",
+                i,
+                count,
+                expected
+            );
+        }
+    }
+}
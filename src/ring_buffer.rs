@@ -0,0 +1,431 @@
+//! # Fixed-capacity ring buffer
+//!
+//! A deque of at most `N` elements stored inline, in an array that lives wherever the
+//! buffer does, so it never touches the heap. The elements occupy `len` slots starting at
+//! `head` and wrap around the end of the array:
+//!
+//! ```text
+//!            back          head = front
+//!              v               v
+//!   [ d  e  f  g  .  .  .  .  .  a  b  c ]      N = 12, len = 7
+//! ```
+//!
+//! Pushing or popping at either end moves `head` or `len` by one, in O(1). When the buffer
+//! is full, `push_back` and `push_front` hand the element back. The `_overwrite` versions
+//! drop the oldest element from the far end instead, which suits a fixed window over a
+//! stream such as "the last 100 samples".
+//!
+//! Because the elements wrap, they're in at most two contiguous runs, which
+//! [`RingBuffer::as_slices`] returns front first.
+//!
+//! Which slots are initialized is tracked by hand, so run the tests under Miri too:
+//!
+//! ```text
+//! cargo +nightly miri test ring_buffer
+//! ```
+
+use std::fmt;
+use std::mem::MaybeUninit;
+use std::ptr;
+use std::slice;
+
+pub struct RingBuffer<T, const N: usize> {
+    buf: [MaybeUninit<T>; N],
+    // Slot of the front element
+    head: usize,
+    len: usize,
+}
+
+impl<T, const N: usize> RingBuffer<T, N> {
+    /// Creates an empty RingBuffer.
+    pub const fn new() -> Self {
+        RingBuffer {
+            buf: [const { MaybeUninit::uninit() }; N],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    pub fn clear(&mut self) {
+        while self.pop_front().is_some() {}
+    }
+
+    /// Slot of the element `i` places from the front, which may be past the back.
+    fn slot(&self, i: usize) -> usize {
+        let slot = self.head + i;
+        if slot >= N {
+            slot - N
+        } else {
+            slot
+        }
+    }
+
+    /// Pushes an element onto the back, or hands it back if the buffer is full.
+    pub fn push_back(&mut self, elem: T) -> Result<(), T> {
+        if self.is_full() {
+            return Err(elem);
+        }
+        let slot = self.slot(self.len);
+        self.buf[slot] = MaybeUninit::new(elem);
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Pushes an element onto the front, or hands it back if the buffer is full.
+    pub fn push_front(&mut self, elem: T) -> Result<(), T> {
+        if self.is_full() {
+            return Err(elem);
+        }
+        self.head = if self.head == 0 { N - 1 } else { self.head - 1 };
+        self.buf[self.head] = MaybeUninit::new(elem);
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Pushes an element onto the back, first popping the front if the buffer is full.
+    /// Returns the element that made way, which with `N == 0` is `elem` itself.
+    pub fn push_back_overwrite(&mut self, elem: T) -> Option<T> {
+        let overwritten = if self.is_full() {
+            self.pop_front()
+        } else {
+            None
+        };
+        match self.push_back(elem) {
+            Ok(()) => overwritten,
+            Err(elem) => Some(elem),
+        }
+    }
+
+    /// Pushes an element onto the front, first popping the back if the buffer is full.
+    pub fn push_front_overwrite(&mut self, elem: T) -> Option<T> {
+        let overwritten = if self.is_full() {
+            self.pop_back()
+        } else {
+            None
+        };
+        match self.push_front(elem) {
+            Ok(()) => overwritten,
+            Err(elem) => Some(elem),
+        }
+    }
+
+    pub fn pop_front(&mut self) -> Option<T> {
+        if self.is_empty() {
+            return None;
+        }
+        let elem = unsafe { self.buf[self.head].assume_init_read() };
+        self.head = self.slot(1);
+        self.len -= 1;
+        Some(elem)
+    }
+
+    pub fn pop_back(&mut self) -> Option<T> {
+        if self.is_empty() {
+            return None;
+        }
+        self.len -= 1;
+        let slot = self.slot(self.len);
+        Some(unsafe { self.buf[slot].assume_init_read() })
+    }
+
+    /// Returns the element `i` places from the front.
+    pub fn get(&self, i: usize) -> Option<&T> {
+        if i >= self.len {
+            return None;
+        }
+        Some(unsafe { self.buf[self.slot(i)].assume_init_ref() })
+    }
+
+    pub fn get_mut(&mut self, i: usize) -> Option<&mut T> {
+        if i >= self.len {
+            return None;
+        }
+        let slot = self.slot(i);
+        Some(unsafe { self.buf[slot].assume_init_mut() })
+    }
+
+    pub fn front(&self) -> Option<&T> {
+        self.get(0)
+    }
+
+    pub fn back(&self) -> Option<&T> {
+        self.len.checked_sub(1).and_then(|i| self.get(i))
+    }
+
+    /// The two runs of slots the elements occupy, front first. The second is empty unless
+    /// they wrap around the end of the array.
+    fn runs(&self) -> (std::ops::Range<usize>, std::ops::Range<usize>) {
+        if self.head + self.len <= N {
+            (self.head..self.head + self.len, 0..0)
+        } else {
+            (self.head..N, 0..self.head + self.len - N)
+        }
+    }
+
+    /// Returns the elements as two slices, front first, which together are in order.
+    pub fn as_slices(&self) -> (&[T], &[T]) {
+        let (first, second) = self.runs();
+        // The slots in both runs are initialized, and MaybeUninit<T> has T's layout
+        unsafe {
+            let base = self.buf.as_ptr() as *const T;
+            (
+                slice::from_raw_parts(base.add(first.start), first.len()),
+                slice::from_raw_parts(base.add(second.start), second.len()),
+            )
+        }
+    }
+
+    pub fn as_mut_slices(&mut self) -> (&mut [T], &mut [T]) {
+        let (first, second) = self.runs();
+        // The runs don't overlap, so the two borrows don't either
+        unsafe {
+            let base = self.buf.as_mut_ptr() as *mut T;
+            (
+                slice::from_raw_parts_mut(base.add(first.start), first.len()),
+                slice::from_raw_parts_mut(base.add(second.start), second.len()),
+            )
+        }
+    }
+
+    /// Iterates from front to back.
+    pub fn iter(&self) -> Iter<'_, T> {
+        let (first, second) = self.as_slices();
+        Iter {
+            first: first.iter(),
+            second: second.iter(),
+        }
+    }
+}
+
+impl<T, const N: usize> Drop for RingBuffer<T, N> {
+    fn drop(&mut self) {
+        let (first, second) = self.as_mut_slices();
+        unsafe {
+            ptr::drop_in_place(first);
+            ptr::drop_in_place(second);
+        }
+    }
+}
+
+impl<T, const N: usize> Default for RingBuffer<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Clone, const N: usize> Clone for RingBuffer<T, N> {
+    fn clone(&self) -> Self {
+        let mut new = RingBuffer::new();
+        for elem in self.iter() {
+            // Can't fail, since it holds no more than we do
+            let _ = new.push_back(elem.clone());
+        }
+        new
+    }
+}
+
+impl<T: fmt::Debug, const N: usize> fmt::Debug for RingBuffer<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// Iterator over the elements of a [`RingBuffer`], front to back.
+pub struct Iter<'a, T> {
+    first: slice::Iter<'a, T>,
+    second: slice::Iter<'a, T>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.first.next().or_else(|| self.second.next())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.first.len() + self.second.len();
+        (len, Some(len))
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for Iter<'a, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.second.next_back().or_else(|| self.first.next_back())
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Iter<'a, T> {}
+
+impl<'a, T, const N: usize> IntoIterator for &'a RingBuffer<T, N> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::RingBuffer;
+    use std::collections::VecDeque;
+    use std::rc::Rc;
+
+    #[test]
+    fn basics() {
+        let mut ring: RingBuffer<i32, 4> = RingBuffer::new();
+
+        // Check empty ring behaves right
+        assert_eq!(ring.pop_front(), None);
+        assert_eq!(ring.pop_back(), None);
+        assert_eq!(ring.back(), None);
+        assert_eq!(ring.capacity(), 4);
+
+        // Populate ring
+        assert_eq!(ring.push_back(2), Ok(()));
+        assert_eq!(ring.push_back(3), Ok(()));
+        assert_eq!(ring.push_front(1), Ok(()));
+        assert_eq!(ring.push_front(0), Ok(()));
+        assert!(ring.is_full());
+        assert_eq!(ring.push_back(4), Err(4));
+        assert_eq!(format!("{:?}", ring), "[0, 1, 2, 3]");
+
+        // Check normal removal
+        assert_eq!(ring.pop_front(), Some(0));
+        assert_eq!(ring.pop_back(), Some(3));
+        assert_eq!(ring.front(), Some(&1));
+        assert_eq!(ring.back(), Some(&2));
+
+        // Push some more just to make sure nothing's corrupted
+        ring.push_back(5).unwrap();
+        ring.push_back(6).unwrap();
+        *ring.get_mut(0).unwrap() = 10;
+        assert_eq!(ring.get(3), Some(&6));
+        assert_eq!(ring.get(4), None);
+        assert!(ring.iter().rev().eq(&[6, 5, 2, 10]));
+
+        // Check exhaustion
+        assert_eq!(ring.pop_back(), Some(6));
+        assert_eq!(ring.pop_front(), Some(10));
+        assert_eq!(ring.pop_front(), Some(2));
+        assert_eq!(ring.pop_front(), Some(5));
+        assert_eq!(ring.pop_front(), None);
+        assert!(ring.is_empty());
+    }
+
+    #[test]
+    fn overwrite() {
+        // A window over the last three of a stream
+        let mut window: RingBuffer<u32, 3> = RingBuffer::new();
+        let evicted: Vec<_> = (0..6).map(|i| window.push_back_overwrite(i)).collect();
+        assert_eq!(evicted, vec![None, None, None, Some(0), Some(1), Some(2)]);
+        assert!(window.iter().eq(&[3, 4, 5]));
+
+        // From the other end, it's the back that makes way
+        assert_eq!(window.push_front_overwrite(9), Some(5));
+        assert!(window.iter().eq(&[9, 3, 4]));
+
+        // Nothing fits in nothing
+        let mut empty: RingBuffer<u32, 0> = RingBuffer::new();
+        assert_eq!(empty.push_back_overwrite(1), Some(1));
+        assert_eq!(empty.push_front(1), Err(1));
+        assert!(empty.is_full() && empty.is_empty());
+    }
+
+    #[test]
+    fn slices() {
+        let mut ring: RingBuffer<u32, 5> = RingBuffer::new();
+        ring.push_back(1).unwrap();
+        ring.push_back(2).unwrap();
+        let empty: &[u32] = &[];
+        assert_eq!(ring.as_slices(), (&[1, 2][..], empty));
+
+        // Pushing at the front from slot 0 wraps to the end of the array
+        ring.push_front(0).unwrap();
+        ring.push_front(9).unwrap();
+        assert_eq!(ring.head, 3);
+        assert_eq!(ring.as_slices(), (&[9, 0][..], &[1, 2][..]));
+
+        let (first, second) = ring.as_mut_slices();
+        first[0] = 8;
+        second[1] = 3;
+        assert!(ring.iter().eq(&[8, 0, 1, 3]));
+        assert_eq!(ring.iter().len(), 4);
+    }
+
+    #[test]
+    fn drops_what_it_holds() {
+        let token = Rc::new(());
+        {
+            let mut ring: RingBuffer<Rc<()>, 4> = RingBuffer::new();
+            for _ in 0..6 {
+                ring.push_back_overwrite(token.clone());
+            }
+            ring.push_front_overwrite(token.clone());
+            ring.pop_back();
+            assert_eq!(ring.len(), 3);
+            let copy = ring.clone();
+            assert_eq!(Rc::strong_count(&token), 7);
+            drop(copy);
+            assert_eq!(Rc::strong_count(&token), 4);
+        }
+        assert_eq!(Rc::strong_count(&token), 1);
+    }
+
+    #[test]
+    fn against_vec_deque() {
+        let mut ring: RingBuffer<u32, 16> = RingBuffer::new();
+        let mut model = VecDeque::new();
+        let mut x: u32 = 1;
+        for _ in 0..10_000 {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            match x >> 29 {
+                0 | 1 => assert_eq!(ring.pop_front(), model.pop_front()),
+                2 => assert_eq!(ring.pop_back(), model.pop_back()),
+                3 => {
+                    let pushed = ring.push_front(x);
+                    assert_eq!(pushed.is_ok(), model.len() < 16);
+                    if pushed.is_ok() {
+                        model.push_front(x);
+                    }
+                }
+                4 => {
+                    let evicted = if model.len() == 16 {
+                        model.pop_front()
+                    } else {
+                        None
+                    };
+                    model.push_back(x);
+                    assert_eq!(ring.push_back_overwrite(x), evicted);
+                }
+                _ => {
+                    let pushed = ring.push_back(x);
+                    assert_eq!(pushed.is_ok(), model.len() < 16);
+                    if pushed.is_ok() {
+                        model.push_back(x);
+                    }
+                }
+            }
+            let (a, b) = ring.as_slices();
+            let (c, d) = model.as_slices();
+            assert!(a.iter().chain(b).eq(c.iter().chain(d)));
+        }
+    }
+}
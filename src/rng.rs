@@ -0,0 +1,67 @@
+//! A tiny xorshift random number generator for the randomized structures in the crate
+//! (skip list heights, treap priorities, samplers). Fast and good enough for balancing, but
+//! nowhere near good enough for anything security related.
+
+use std::cell::Cell;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+#[derive(Debug, Clone)]
+pub(crate) struct XorShift {
+    state: u64,
+}
+
+impl XorShift {
+    /// Creates a generator from a fixed seed, which makes runs reproducible.
+    pub(crate) fn new(seed: u64) -> Self {
+        // Xorshift gets stuck on zero forever
+        XorShift {
+            state: if seed == 0 {
+                0x9E37_79B9_7F4A_7C15
+            } else {
+                seed
+            },
+        }
+    }
+
+    /// Creates a generator seeded from the standard library's per-process randomness.
+    pub(crate) fn from_entropy() -> Self {
+        XorShift::new(RandomState::new().build_hasher().finish())
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.state = x;
+        x
+    }
+
+    /// A random number in `0..n`, which must not be empty. Multiplying and keeping the
+    /// high half (Lemire, 2019) has a bias of at most `n / 2^64`, far too small to matter
+    /// here.
+    pub(crate) fn below(&mut self, n: u64) -> u64 {
+        debug_assert!(n > 0);
+        ((self.next_u64() as u128 * n as u128) >> 64) as u64
+    }
+
+    /// A random number in `[0, 1)`, from the top 53 bits.
+    pub(crate) fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+thread_local! {
+    static THREAD_RNG: Cell<u64> = Cell::new(XorShift::from_entropy().state);
+}
+
+/// A random number from a generator private to the current thread.
+pub(crate) fn next_u64() -> u64 {
+    THREAD_RNG.with(|state| {
+        let mut rng = XorShift { state: state.get() };
+        let x = rng.next_u64();
+        state.set(rng.state);
+        x
+    })
+}
@@ -0,0 +1,839 @@
+//! # Robin Hood hash map
+//!
+//! An open-addressing hash map: the entries live directly in one array of slots, and a key
+//! that finds its home slot taken probes forward, one slot at a time, until it finds room.
+//! No chains, no allocation per entry, and a probe walks consecutive memory.
+//!
+//! Plain linear probing suffers from clustering: runs of full slots grow, and a key hashing
+//! into one has to walk to its end. Robin Hood hashing (Celis, 1986) evens that out. Every
+//! entry knows its *distance*, how far it sits from its home slot, and an insert that meets
+//! an entry closer to home than itself takes that slot and carries on inserting the entry
+//! it displaced. Taking from the rich, giving to the poor:
+//!
+//! ```text
+//!   slot       0    1    2    3    4    5
+//!   entry      a    b    c    d    .    .      insert e, home 1
+//!   distance   0    0    1    2
+//!
+//!   slot 1: b is 0 from home, e is 0  -> keep going
+//!   slot 2: c is 1 from home, e is 1  -> keep going
+//!   slot 3: d is 2 from home, e is 2  -> keep going
+//!   slot 4: empty                     -> e goes here, 3 from home
+//! ```
+//!
+//! Distances along a run never jump by more than one, which keeps the variance of probe
+//! lengths low even at high load, and makes misses fast: a lookup can stop as soon as it
+//! meets an entry closer to home than it has come, because its key would have taken that
+//! slot.
+//!
+//! Removing uses backward-shift deletion. Rather than leave a tombstone, every entry after
+//! the removed one that isn't already home moves back one slot, until an empty slot or an
+//! entry at home. The table is then exactly as if the removed key had never been inserted.
+//!
+//! The capacity is a power of two, so the home slot is the low bits of the hash, and the
+//! table doubles once it's 7/8 full.
+
+use std::borrow::Borrow;
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+use std::iter::FromIterator;
+use std::mem;
+use std::ops::Index;
+
+const MIN_CAPACITY: usize = 8;
+
+#[derive(Clone)]
+struct Bucket<K, V> {
+    hash: u64,
+    key: K,
+    value: V,
+}
+
+#[derive(Clone)]
+pub struct RobinHoodMap<K, V, S = RandomState> {
+    slots: Vec<Option<Bucket<K, V>>>,
+    len: usize,
+    hasher: S,
+}
+
+impl<K, V> RobinHoodMap<K, V, RandomState> {
+    /// Creates an empty RobinHoodMap.
+    pub fn new() -> Self {
+        Self::with_hasher(RandomState::new())
+    }
+
+    /// Creates an empty map with room for at least `capacity` entries before it grows.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_and_hasher(capacity, RandomState::new())
+    }
+}
+
+impl<K, V, S> RobinHoodMap<K, V, S> {
+    /// Creates an empty map that hashes keys with `hasher`. It allocates nothing until the
+    /// first insert.
+    pub fn with_hasher(hasher: S) -> Self {
+        RobinHoodMap {
+            slots: Vec::new(),
+            len: 0,
+            hasher,
+        }
+    }
+
+    /// Creates an empty map with room for at least `capacity` entries, hashing keys with
+    /// `hasher`.
+    pub fn with_capacity_and_hasher(capacity: usize, hasher: S) -> Self {
+        let mut map = Self::with_hasher(hasher);
+        if capacity > 0 {
+            map.slots = empty_slots(slots_for(capacity));
+        }
+        map
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn hasher(&self) -> &S {
+        &self.hasher
+    }
+
+    /// Returns how many entries fit before the next resize.
+    pub fn capacity(&self) -> usize {
+        self.slots.len() / 8 * 7
+    }
+
+    /// Removes every entry, but keeps the slots.
+    pub fn clear(&mut self) {
+        self.slots.iter_mut().for_each(|slot| *slot = None);
+        self.len = 0;
+    }
+
+    fn mask(&self) -> usize {
+        self.slots.len() - 1
+    }
+
+    /// How far slot `i` is from the home slot of `hash`.
+    fn distance(&self, hash: u64, i: usize) -> usize {
+        i.wrapping_sub(hash as usize) & self.mask()
+    }
+
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            slots: self.slots.iter(),
+            left: self.len,
+        }
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        IterMut {
+            slots: self.slots.iter_mut(),
+            left: self.len,
+        }
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.iter().map(|(k, _)| k)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.iter().map(|(_, v)| v)
+    }
+}
+
+/// The number of slots, a power of two, that fits `capacity` entries under the load limit.
+fn slots_for(capacity: usize) -> usize {
+    (capacity * 8 / 7 + 1).next_power_of_two().max(MIN_CAPACITY)
+}
+
+fn empty_slots<K, V>(n: usize) -> Vec<Option<Bucket<K, V>>> {
+    (0..n).map(|_| None).collect()
+}
+
+impl<K, V, S> RobinHoodMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    fn hash<Q: Hash + ?Sized>(&self, key: &Q) -> u64 {
+        self.hasher.hash_one(key)
+    }
+
+    /// Returns the slot holding `key`, if it's there.
+    fn find<Q>(&self, hash: u64, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        if self.len == 0 {
+            return None;
+        }
+        let mut i = hash as usize & self.mask();
+        let mut distance = 0;
+        while let Some(bucket) = &self.slots[i] {
+            // Our key would have taken this slot from an entry this close to home
+            if self.distance(bucket.hash, i) < distance {
+                return None;
+            }
+            if bucket.hash == hash && bucket.key.borrow() == key {
+                return Some(i);
+            }
+            i = (i + 1) & self.mask();
+            distance += 1;
+        }
+        None
+    }
+
+    /// Makes room for one more entry, doubling the table if it would go over the limit.
+    fn reserve_one(&mut self) {
+        if self.len < self.capacity() {
+            return;
+        }
+        let slots = slots_for(self.len + 1).max(self.slots.len() * 2);
+        let old = mem::replace(&mut self.slots, empty_slots(slots));
+        for bucket in old.into_iter().flatten() {
+            self.place(bucket);
+        }
+    }
+
+    /// Robin Hood insertion of a key known not to be in the map, with room for it.
+    /// Returns the slot it ended up in.
+    fn place(&mut self, mut bucket: Bucket<K, V>) -> usize {
+        let mask = self.mask();
+        let mut i = bucket.hash as usize & mask;
+        let mut distance = 0;
+        let mut placed = None;
+        loop {
+            match &mut self.slots[i] {
+                slot @ None => {
+                    *slot = Some(bucket);
+                    return placed.unwrap_or(i);
+                }
+                Some(resident) => {
+                    let resident_distance = i.wrapping_sub(resident.hash as usize) & mask;
+                    if resident_distance < distance {
+                        // Take the slot, and carry on with the entry we displaced
+                        mem::swap(resident, &mut bucket);
+                        placed.get_or_insert(i);
+                        distance = resident_distance;
+                    }
+                }
+            }
+            i = (i + 1) & mask;
+            distance += 1;
+        }
+    }
+
+    /// Takes the entry out of slot `i`, shifting the entries after it back to fill the gap.
+    fn take(&mut self, mut i: usize) -> Bucket<K, V> {
+        let bucket = self.slots[i].take().unwrap();
+        self.len -= 1;
+        loop {
+            let next = (i + 1) & self.mask();
+            match &self.slots[next] {
+                Some(b) if self.distance(b.hash, next) > 0 => {
+                    self.slots[i] = self.slots[next].take();
+                    i = next;
+                }
+                _ => return bucket,
+            }
+        }
+    }
+
+    /// Inserts a key-value pair, returning the old value if the key was already there.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        match self.entry(key) {
+            Entry::Occupied(mut entry) => Some(entry.insert(value)),
+            Entry::Vacant(entry) => {
+                entry.insert(value);
+                None
+            }
+        }
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let i = self.find(self.hash(key), key)?;
+        self.slots[i].as_ref().map(|b| &b.value)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let i = self.find(self.hash(key), key)?;
+        self.slots[i].as_mut().map(|b| &mut b.value)
+    }
+
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let i = self.find(self.hash(key), key)?;
+        self.slots[i].as_ref().map(|b| (&b.key, &b.value))
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.find(self.hash(key), key).is_some()
+    }
+
+    /// Removes a key, returning its value if it was there.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.remove_entry(key).map(|(_, v)| v)
+    }
+
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let i = self.find(self.hash(key), key)?;
+        let bucket = self.take(i);
+        Some((bucket.key, bucket.value))
+    }
+
+    /// Returns the entry for `key`, to inspect or update in place. Makes room for one more
+    /// entry first, so this may grow the table even if the key is there.
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, S> {
+        self.reserve_one();
+        let hash = self.hash(&key);
+        match self.find(hash, &key) {
+            Some(index) => Entry::Occupied(OccupiedEntry { map: self, index }),
+            None => Entry::Vacant(VacantEntry {
+                map: self,
+                hash,
+                key,
+            }),
+        }
+    }
+
+    /// Keeps only the entries for which `f` returns true.
+    pub fn retain(&mut self, mut f: impl FnMut(&K, &mut V) -> bool) {
+        // Start after an empty slot, which the load limit guarantees. Shifts stop at empty
+        // slots, so none can wrap round and move an entry we've already been past.
+        let start = match self.slots.iter().position(Option::is_none) {
+            Some(empty) => empty,
+            None => return,
+        };
+        let mut step = 1;
+        while step <= self.slots.len() {
+            let i = (start + step) & self.mask();
+            let keep = match &mut self.slots[i] {
+                Some(b) => f(&b.key, &mut b.value),
+                None => true,
+            };
+            if keep {
+                step += 1;
+            } else {
+                // The shift may have moved a new entry into slot i, so look at it again
+                self.take(i);
+            }
+        }
+    }
+}
+
+impl<K, V, S: Default> Default for RobinHoodMap<K, V, S> {
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+impl<K, V, S> fmt::Debug for RobinHoodMap<K, V, S>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K, V, S> PartialEq for RobinHoodMap<K, V, S>
+where
+    K: Eq + Hash,
+    V: PartialEq,
+    S: BuildHasher,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.iter().all(|(k, v)| other.get(k) == Some(v))
+    }
+}
+
+impl<K: Eq + Hash, V: Eq, S: BuildHasher> Eq for RobinHoodMap<K, V, S> {}
+
+impl<K, V, S> Extend<(K, V)> for RobinHoodMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+impl<K, V, S> FromIterator<(K, V)> for RobinHoodMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher + Default,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = Self::default();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K, Q, V, S> Index<&Q> for RobinHoodMap<K, V, S>
+where
+    K: Eq + Hash + Borrow<Q>,
+    Q: Eq + Hash + ?Sized,
+    S: BuildHasher,
+{
+    type Output = V;
+
+    /// # Panics
+    ///
+    /// Panics if the key isn't in the map.
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("no entry for key")
+    }
+}
+
+/// A view into one entry of a [`RobinHoodMap`], from [`RobinHoodMap::entry`].
+pub enum Entry<'a, K, V, S> {
+    Occupied(OccupiedEntry<'a, K, V, S>),
+    Vacant(VacantEntry<'a, K, V, S>),
+}
+
+pub struct OccupiedEntry<'a, K, V, S> {
+    map: &'a mut RobinHoodMap<K, V, S>,
+    index: usize,
+}
+
+pub struct VacantEntry<'a, K, V, S> {
+    map: &'a mut RobinHoodMap<K, V, S>,
+    hash: u64,
+    key: K,
+}
+
+impl<'a, K, V, S> Entry<'a, K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => &entry.key,
+        }
+    }
+
+    /// Returns the value, inserting `default` first if there isn't one.
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        self.or_insert_with(|| default)
+    }
+
+    /// Returns the value, inserting `f()` first if there isn't one.
+    pub fn or_insert_with(self, f: impl FnOnce() -> V) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(f()),
+        }
+    }
+
+    pub fn or_default(self) -> &'a mut V
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+
+    /// Calls `f` on the value if there is one.
+    pub fn and_modify(mut self, f: impl FnOnce(&mut V)) -> Self {
+        if let Entry::Occupied(entry) = &mut self {
+            f(entry.get_mut());
+        }
+        self
+    }
+}
+
+impl<'a, K, V, S> OccupiedEntry<'a, K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    fn bucket(&self) -> &Bucket<K, V> {
+        self.map.slots[self.index].as_ref().unwrap()
+    }
+
+    fn bucket_mut(&mut self) -> &mut Bucket<K, V> {
+        self.map.slots[self.index].as_mut().unwrap()
+    }
+
+    pub fn key(&self) -> &K {
+        &self.bucket().key
+    }
+
+    pub fn get(&self) -> &V {
+        &self.bucket().value
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.bucket_mut().value
+    }
+
+    /// Returns the value, borrowed for as long as the map was.
+    pub fn into_mut(self) -> &'a mut V {
+        &mut self.map.slots[self.index].as_mut().unwrap().value
+    }
+
+    /// Replaces the value, returning the old one. The key stays as it was.
+    pub fn insert(&mut self, value: V) -> V {
+        mem::replace(self.get_mut(), value)
+    }
+
+    /// Removes the entry, returning its key and value.
+    pub fn remove_entry(self) -> (K, V) {
+        let bucket = self.map.take(self.index);
+        (bucket.key, bucket.value)
+    }
+
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+}
+
+impl<'a, K, V, S> VacantEntry<'a, K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    /// Inserts the value, returning it borrowed for as long as the map was.
+    pub fn insert(self, value: V) -> &'a mut V {
+        let map = self.map;
+        // `entry` already made room
+        let index = map.place(Bucket {
+            hash: self.hash,
+            key: self.key,
+            value,
+        });
+        map.len += 1;
+        &mut map.slots[index].as_mut().unwrap().value
+    }
+}
+
+/// Iterator over the entries of a [`RobinHoodMap`], in slot order.
+pub struct Iter<'a, K, V> {
+    slots: std::slice::Iter<'a, Option<Bucket<K, V>>>,
+    left: usize,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let bucket = self.slots.by_ref().flatten().next()?;
+        self.left -= 1;
+        Some((&bucket.key, &bucket.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.left, Some(self.left))
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for Iter<'a, K, V> {}
+
+pub struct IterMut<'a, K, V> {
+    slots: std::slice::IterMut<'a, Option<Bucket<K, V>>>,
+    left: usize,
+}
+
+impl<'a, K, V> Iterator for IterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let bucket = self.slots.by_ref().flatten().next()?;
+        self.left -= 1;
+        Some((&bucket.key, &mut bucket.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.left, Some(self.left))
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for IterMut<'a, K, V> {}
+
+impl<'a, K, V, S> IntoIterator for &'a RobinHoodMap<K, V, S> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Owning iterator over the entries of a [`RobinHoodMap`].
+pub struct IntoIter<K, V> {
+    slots: std::iter::Flatten<std::vec::IntoIter<Option<Bucket<K, V>>>>,
+    left: usize,
+}
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<(K, V)> {
+        let bucket = self.slots.next()?;
+        self.left -= 1;
+        Some((bucket.key, bucket.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.left, Some(self.left))
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoIter<K, V> {}
+
+impl<K, V, S> IntoIterator for RobinHoodMap<K, V, S> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            left: self.len,
+            slots: self.slots.into_iter().flatten(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Entry, RobinHoodMap};
+    use std::collections::HashMap;
+    use std::hash::{BuildHasher, Hasher};
+    use std::rc::Rc;
+
+    /// Checks every entry can be found, and distances never jump by more than one.
+    fn check<K, V, S>(map: &RobinHoodMap<K, V, S>)
+    where
+        K: Eq + std::hash::Hash,
+        S: BuildHasher,
+    {
+        let mut len = 0;
+        for (i, slot) in map.slots.iter().enumerate() {
+            let bucket = match slot {
+                Some(bucket) => bucket,
+                None => continue,
+            };
+            len += 1;
+            assert_eq!(bucket.hash, map.hash(&bucket.key));
+            assert_eq!(map.find(bucket.hash, &bucket.key), Some(i));
+            let next = &map.slots[(i + 1) & map.mask()];
+            if let Some(next) = next {
+                let d = map.distance(bucket.hash, i);
+                assert!(map.distance(next.hash, (i + 1) & map.mask()) <= d + 1);
+            }
+        }
+        assert_eq!(len, map.len());
+        assert!(map.len() <= map.capacity());
+    }
+
+    /// Hashes everything into a handful of values, to force long probe runs. They're at
+    /// the very end of any table, so the runs wrap round to the start.
+    #[derive(Clone, Default)]
+    struct Colliding;
+
+    struct CollidingHasher(u64);
+
+    impl BuildHasher for Colliding {
+        type Hasher = CollidingHasher;
+
+        fn build_hasher(&self) -> CollidingHasher {
+            CollidingHasher(0)
+        }
+    }
+
+    impl Hasher for CollidingHasher {
+        fn finish(&self) -> u64 {
+            u64::MAX - self.0 % 5
+        }
+
+        fn write(&mut self, bytes: &[u8]) {
+            for &b in bytes {
+                self.0 = self.0.wrapping_mul(31).wrapping_add(b as u64);
+            }
+        }
+    }
+
+    #[test]
+    fn basics() {
+        let mut map = RobinHoodMap::new();
+
+        // Check empty map behaves right
+        assert_eq!(map.get("a"), None);
+        assert_eq!(map.remove("a"), None);
+        assert_eq!(map.capacity(), 0);
+
+        // Populate map
+        assert_eq!(map.insert("a".to_string(), 1), None);
+        assert_eq!(map.insert("b".to_string(), 2), None);
+        assert_eq!(map.insert("a".to_string(), 3), Some(1));
+        assert_eq!(map.len(), 2);
+        assert_eq!(map["a"], 3);
+        assert_eq!(map.get_key_value("b"), Some((&"b".to_string(), &2)));
+        *map.get_mut("b").unwrap() += 10;
+        check(&map);
+
+        // Check normal removal
+        assert_eq!(map.remove("a"), Some(3));
+        assert_eq!(map.remove("a"), None);
+        assert!(!map.contains_key("a"));
+        assert_eq!(format!("{:?}", map), r#"{"b": 12}"#);
+
+        // Push some more just to make sure nothing's corrupted, through a few resizes
+        for i in 0..100 {
+            map.insert(i.to_string(), i);
+        }
+        assert_eq!(map.len(), 101);
+        assert!(map.capacity() >= 101);
+        check(&map);
+
+        // Check exhaustion
+        map.retain(|k, _| k == "b");
+        assert_eq!(map.len(), 1);
+        check(&map);
+        map.clear();
+        assert!(map.is_empty());
+        assert_eq!(map.get("b"), None);
+    }
+
+    #[test]
+    fn entry() {
+        let mut map: RobinHoodMap<&str, u32> = RobinHoodMap::with_capacity(4);
+        assert!(map.capacity() >= 4);
+        *map.entry("a").or_insert(0) += 1;
+        *map.entry("a").or_insert(0) += 1;
+        map.entry("b").and_modify(|v| *v = 100).or_default();
+        assert_eq!(map.entry("b").key(), &"b");
+        assert_eq!((map["a"], map["b"]), (2, 0));
+
+        match map.entry("a") {
+            Entry::Occupied(mut entry) => {
+                assert_eq!(entry.insert(7), 2);
+                assert_eq!(entry.remove_entry(), ("a", 7));
+            }
+            Entry::Vacant(_) => panic!("a should be there"),
+        }
+        match map.entry("c") {
+            Entry::Occupied(_) => panic!("c shouldn't be there"),
+            Entry::Vacant(entry) => assert_eq!(entry.into_key(), "c"),
+        }
+        assert_eq!(map.len(), 1);
+        check(&map);
+    }
+
+    #[test]
+    fn collisions() {
+        // Five hashes for everything, so entries pile up in long runs
+        let mut map = RobinHoodMap::with_hasher(Colliding);
+        for i in 0..200u32 {
+            map.insert(i, i * 2);
+        }
+        check(&map);
+        for i in (0..200).step_by(3) {
+            assert_eq!(map.remove(&i), Some(i * 2));
+        }
+        check(&map);
+        for i in 0..200 {
+            assert_eq!(map.get(&i).copied(), (i % 3 != 0).then_some(i * 2));
+        }
+
+        // Each entry is offered once, even as removals shift the run round the end
+        let mut offered = 0;
+        map.retain(|&k, _| {
+            offered += 1;
+            k % 2 == 0
+        });
+        assert_eq!(offered, 133);
+        assert_eq!(map.len(), 66);
+        check(&map);
+    }
+
+    #[test]
+    fn drops_what_it_holds() {
+        let token = Rc::new(());
+        {
+            let mut map = RobinHoodMap::new();
+            for i in 0..20 {
+                map.insert(i, token.clone());
+            }
+            map.insert(0, token.clone());
+            map.remove(&1);
+            assert_eq!(Rc::strong_count(&token), 20);
+            let mut iter = map.clone().into_iter();
+            assert_eq!(iter.len(), 19);
+            iter.next();
+            assert_eq!(Rc::strong_count(&token), 38);
+        }
+        assert_eq!(Rc::strong_count(&token), 1);
+    }
+
+    #[test]
+    fn against_hash_map() {
+        let mut map = RobinHoodMap::new();
+        let mut model = HashMap::new();
+        let n = if cfg!(miri) { 1_000 } else { 50_000 };
+        let mut x: u32 = 1;
+        for i in 0..n {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let key = (x >> 8) % 2_000;
+            match x >> 30 {
+                0 => assert_eq!(map.remove(&key), model.remove(&key)),
+                1 => assert_eq!(map.get(&key), model.get(&key)),
+                _ => assert_eq!(map.insert(key, i), model.insert(key, i)),
+            }
+            assert_eq!(map.len(), model.len());
+            if i % 1000 == 0 {
+                check(&map);
+            }
+        }
+        check(&map);
+        for (k, v) in map.iter_mut() {
+            *v += k;
+        }
+        let mut entries: Vec<_> = map.into_iter().collect();
+        entries.sort_unstable();
+        let mut expected: Vec<_> = model.into_iter().map(|(k, v)| (k, v + k)).collect();
+        expected.sort_unstable();
+        assert_eq!(entries, expected);
+    }
+}
@@ -0,0 +1,786 @@
+//! # Rope
+//!
+//! A rope (Boehm, Atkinson and Plass, 1995) holds a long text as a balanced binary tree whose
+//! leaves are short chunks of it. An edit in the middle of a `String` shifts everything
+//! after it. An edit in the middle of a rope only rebuilds the path down to one chunk.
+//!
+//! Every branch caches how many bytes, chars and newlines there are below it, so getting to
+//! a position walks down a single path, going left or subtracting the left side's count and
+//! going right:
+//!
+//! ```text
+//!                 [31 chars, 2 newlines]
+//!                 /                    \
+//!      [20 chars, 2 newlines]       "gets longer"
+//!         /              \
+//!   "A rope\nis "    "text that\n"
+//! ```
+//!
+//! The tree is kept balanced like an AVL tree. Everything is built on two operations,
+//! *join* and *split* (Blelloch, Ferizovic and Sun, 2016). Joining walks down the taller
+//! tree's spine until it reaches a subtree about as tall as the other tree, hangs the
+//! other tree there, and rebalances on the way back up: O(difference in heights).
+//! Splitting cuts along the path down to the index and joins the pieces hanging off it
+//! back into two trees, O(log n) altogether. Inserting is then a split and two joins, and
+//! deleting is two splits and a join. An insert that fits in the chunk it lands in skips
+//! all that and just updates the counts along the path.
+//!
+//! Positions are char indices, as with `str::chars`. Byte and line positions are converted
+//! to and from them in O(log n). For the cursor-based take on editing text, where edits at
+//! the cursor are O(1) but jumping somewhere is O(distance), see
+//! [`crate::double_single::TextZipper`].
+
+use std::fmt;
+use std::ops::{Bound, RangeBounds};
+
+/// The most bytes a chunk holds.
+const MAX_CHUNK: usize = 256;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Metrics {
+    bytes: usize,
+    chars: usize,
+    newlines: usize,
+}
+
+impl Metrics {
+    fn of(text: &str) -> Self {
+        Metrics {
+            bytes: text.len(),
+            chars: text.chars().count(),
+            newlines: text.bytes().filter(|&b| b == b'\n').count(),
+        }
+    }
+
+    fn add(self, other: Metrics) -> Self {
+        Metrics {
+            bytes: self.bytes + other.bytes,
+            chars: self.chars + other.chars,
+            newlines: self.newlines + other.newlines,
+        }
+    }
+}
+
+// Chunks are never empty: an empty rope has no root at all
+#[derive(Clone)]
+enum Node {
+    Leaf {
+        text: String,
+        metrics: Metrics,
+    },
+    Branch {
+        left: Box<Node>,
+        right: Box<Node>,
+        height: usize,
+        metrics: Metrics,
+    },
+}
+
+type Link = Option<Box<Node>>;
+
+impl Node {
+    fn leaf(text: String) -> Box<Node> {
+        let metrics = Metrics::of(&text);
+        Box::new(Node::Leaf { text, metrics })
+    }
+
+    fn branch(left: Box<Node>, right: Box<Node>) -> Box<Node> {
+        Box::new(Node::Branch {
+            height: 1 + left.height().max(right.height()),
+            metrics: left.metrics().add(right.metrics()),
+            left,
+            right,
+        })
+    }
+
+    fn height(&self) -> usize {
+        match self {
+            Node::Leaf { .. } => 0,
+            Node::Branch { height, .. } => *height,
+        }
+    }
+
+    fn metrics(&self) -> Metrics {
+        match self {
+            Node::Leaf { metrics, .. } | Node::Branch { metrics, .. } => *metrics,
+        }
+    }
+
+    fn children(self) -> (Box<Node>, Box<Node>) {
+        match self {
+            Node::Branch { left, right, .. } => (left, right),
+            Node::Leaf { .. } => unreachable!("a leaf has no children"),
+        }
+    }
+}
+
+/// Puts two trees whose heights differ by at most two under a new branch, rotating once
+/// or twice if they differ by two.
+fn balance(left: Box<Node>, right: Box<Node>) -> Box<Node> {
+    let (lh, rh) = (left.height(), right.height());
+    if lh > rh + 1 {
+        let (a, b) = (*left).children();
+        if b.height() > a.height() {
+            let (b1, b2) = (*b).children();
+            Node::branch(Node::branch(a, b1), Node::branch(b2, right))
+        } else {
+            Node::branch(a, Node::branch(b, right))
+        }
+    } else if rh > lh + 1 {
+        let (a, b) = (*right).children();
+        if a.height() > b.height() {
+            let (a1, a2) = (*a).children();
+            Node::branch(Node::branch(left, a1), Node::branch(a2, b))
+        } else {
+            Node::branch(Node::branch(left, a), b)
+        }
+    } else {
+        Node::branch(left, right)
+    }
+}
+
+/// Joins two trees, the left one's text first.
+fn join(left: Box<Node>, right: Box<Node>) -> Box<Node> {
+    let (lh, rh) = (left.height(), right.height());
+    if lh > rh + 1 {
+        let (a, b) = (*left).children();
+        balance(a, join(b, right))
+    } else if rh > lh + 1 {
+        let (a, b) = (*right).children();
+        balance(join(left, a), b)
+    } else {
+        match (*left, *right) {
+            // Two small chunks meeting at the seam become one
+            (Node::Leaf { text: mut l, .. }, Node::Leaf { text: r, .. })
+                if l.len() + r.len() <= MAX_CHUNK =>
+            {
+                l.push_str(&r);
+                Node::leaf(l)
+            }
+            (left, right) => Node::branch(Box::new(left), Box::new(right)),
+        }
+    }
+}
+
+fn concat(left: Link, right: Link) -> Link {
+    match (left, right) {
+        (Some(left), Some(right)) => Some(join(left, right)),
+        (left, None) => left,
+        (None, right) => right,
+    }
+}
+
+/// Splits a tree into the text before char `at` and the text from it on.
+fn split(node: Box<Node>, at: usize) -> (Link, Link) {
+    if at == 0 {
+        return (None, Some(node));
+    }
+    if at >= node.metrics().chars {
+        return (Some(node), None);
+    }
+    match *node {
+        Node::Leaf { mut text, .. } => {
+            let rest = text.split_off(byte_offset(&text, at));
+            (Some(Node::leaf(text)), Some(Node::leaf(rest)))
+        }
+        Node::Branch { left, right, .. } => {
+            let chars = left.metrics().chars;
+            if at <= chars {
+                let (a, b) = split(left, at);
+                (a, concat(b, Some(right)))
+            } else {
+                let (a, b) = split(right, at - chars);
+                (concat(Some(left), a), b)
+            }
+        }
+    }
+}
+
+/// Inserts into the chunk holding char `at` if it has room for the text, updating the
+/// counts on the way back up. Returns whether it did.
+fn insert_in_place(node: &mut Node, at: usize, text: &str, added: Metrics) -> bool {
+    match node {
+        Node::Leaf {
+            text: chunk,
+            metrics,
+        } => {
+            if chunk.len() + text.len() > MAX_CHUNK {
+                return false;
+            }
+            chunk.insert_str(byte_offset(chunk, at), text);
+            *metrics = metrics.add(added);
+            true
+        }
+        Node::Branch {
+            left,
+            right,
+            metrics,
+            ..
+        } => {
+            let chars = left.metrics().chars;
+            let done = if at <= chars {
+                insert_in_place(left, at, text, added)
+            } else {
+                insert_in_place(right, at - chars, text, added)
+            };
+            if done {
+                *metrics = metrics.add(added);
+            }
+            done
+        }
+    }
+}
+
+/// Cuts a text into chunks and builds a perfectly balanced tree over them.
+fn build(text: &str) -> Link {
+    let mut chunks = Vec::new();
+    let mut rest = text;
+    while !rest.is_empty() {
+        let mut end = rest.len().min(MAX_CHUNK);
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        chunks.push(Node::leaf(rest[..end].to_owned()));
+        rest = &rest[end..];
+    }
+    fn tree(chunks: &mut impl Iterator<Item = Box<Node>>, n: usize) -> Box<Node> {
+        if n == 1 {
+            chunks.next().unwrap()
+        } else {
+            let left = tree(chunks, n / 2);
+            Node::branch(left, tree(chunks, n - n / 2))
+        }
+    }
+    let n = chunks.len();
+    if n == 0 {
+        None
+    } else {
+        Some(tree(&mut chunks.into_iter(), n))
+    }
+}
+
+/// The byte offset of char `at` in `text`, or its length if `at` is the end.
+fn byte_offset(text: &str, at: usize) -> usize {
+    text.char_indices().nth(at).map_or(text.len(), |(i, _)| i)
+}
+
+#[derive(Clone, Default)]
+pub struct Rope {
+    root: Link,
+}
+
+impl Rope {
+    /// Creates an empty Rope.
+    pub fn new() -> Self {
+        Rope { root: None }
+    }
+
+    fn metrics(&self) -> Metrics {
+        self.root
+            .as_ref()
+            .map_or(Metrics::default(), |root| root.metrics())
+    }
+
+    pub fn len_bytes(&self) -> usize {
+        self.metrics().bytes
+    }
+
+    pub fn len_chars(&self) -> usize {
+        self.metrics().chars
+    }
+
+    /// The number of lines, which is always one more than the number of newlines: an
+    /// empty rope has one empty line, and so does the end of a text ending in a newline.
+    pub fn len_lines(&self) -> usize {
+        self.metrics().newlines + 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    /// Walks down to the chunk holding position `at`, as counted by `measure`. Returns the
+    /// chunk, how far into it `at` is, and the counts of everything before it.
+    fn descend(
+        &self,
+        mut at: usize,
+        measure: impl Fn(&Metrics) -> usize,
+    ) -> (&str, usize, Metrics) {
+        let mut node = self.root.as_deref().unwrap();
+        let mut before = Metrics::default();
+        loop {
+            match node {
+                Node::Leaf { text, .. } => return (text, at, before),
+                Node::Branch { left, right, .. } => {
+                    let metrics = left.metrics();
+                    if at < measure(&metrics) {
+                        node = left;
+                    } else {
+                        at -= measure(&metrics);
+                        before = before.add(metrics);
+                        node = right;
+                    }
+                }
+            }
+        }
+    }
+
+    fn check_char(&self, at: usize) {
+        assert!(
+            at <= self.len_chars(),
+            "char index {} out of bounds for length {}",
+            at,
+            self.len_chars()
+        );
+    }
+
+    /// Returns the char at char index `at`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `at` is out of bounds.
+    pub fn char(&self, at: usize) -> char {
+        assert!(
+            at < self.len_chars(),
+            "char index {} out of bounds for length {}",
+            at,
+            self.len_chars()
+        );
+        let (text, at, _) = self.descend(at, |m| m.chars);
+        text.chars().nth(at).unwrap()
+    }
+
+    /// Converts a char index to the byte index it starts at. The end converts to the end.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `at` is past the end.
+    pub fn char_to_byte(&self, at: usize) -> usize {
+        self.check_char(at);
+        if at == self.len_chars() {
+            return self.len_bytes();
+        }
+        let (text, at, before) = self.descend(at, |m| m.chars);
+        before.bytes + byte_offset(text, at)
+    }
+
+    /// Converts a byte index to the index of the char starting there. The end converts to
+    /// the end.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `at` is past the end or not at the start of a char.
+    pub fn byte_to_char(&self, at: usize) -> usize {
+        assert!(
+            at <= self.len_bytes(),
+            "byte index {} out of bounds for length {}",
+            at,
+            self.len_bytes()
+        );
+        if at == self.len_bytes() {
+            return self.len_chars();
+        }
+        let (text, offset, before) = self.descend(at, |m| m.bytes);
+        assert!(
+            text.is_char_boundary(offset),
+            "byte index {} is not a char boundary",
+            at
+        );
+        before.chars + text[..offset].chars().count()
+    }
+
+    /// Returns the line that char index `at` is on, counting from zero.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `at` is past the end.
+    pub fn char_to_line(&self, at: usize) -> usize {
+        self.check_char(at);
+        if at == self.len_chars() {
+            return self.metrics().newlines;
+        }
+        let (text, at, before) = self.descend(at, |m| m.chars);
+        let offset = byte_offset(text, at);
+        before.newlines + text[..offset].bytes().filter(|&b| b == b'\n').count()
+    }
+
+    /// Returns the char index line `line` starts at, counting from zero.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there's no line `line`.
+    pub fn line_to_char(&self, line: usize) -> usize {
+        assert!(
+            line < self.len_lines(),
+            "line {} out of bounds for {} lines",
+            line,
+            self.len_lines()
+        );
+        if line == 0 {
+            return 0;
+        }
+        // Line `line` starts just after newline number `line - 1`
+        let (text, nth, before) = self.descend(line - 1, |m| m.newlines);
+        let offset = text.match_indices('\n').nth(nth).unwrap().0;
+        before.chars + text[..offset].chars().count() + 1
+    }
+
+    /// Inserts `text` at char index `at`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `at` is past the end.
+    pub fn insert(&mut self, at: usize, text: &str) {
+        self.check_char(at);
+        if text.is_empty() {
+            return;
+        }
+        if let Some(root) = &mut self.root {
+            if insert_in_place(root, at, text, Metrics::of(text)) {
+                return;
+            }
+        }
+        let (left, right) = self
+            .root
+            .take()
+            .map_or((None, None), |root| split(root, at));
+        self.root = concat(concat(left, build(text)), right);
+    }
+
+    /// Deletes the chars in `range`, a range of char indices.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range starts after it ends or ends past the end of the rope.
+    pub fn delete<R: RangeBounds<usize>>(&mut self, range: R) {
+        let start = match range.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&end) => end + 1,
+            Bound::Excluded(&end) => end,
+            Bound::Unbounded => self.len_chars(),
+        };
+        assert!(
+            start <= end && end <= self.len_chars(),
+            "range {}..{} out of bounds for length {}",
+            start,
+            end,
+            self.len_chars()
+        );
+        if let Some(root) = self.root.take() {
+            let (rest, right) = split(root, end);
+            let (left, _) = rest.map_or((None, None), |rest| split(rest, start));
+            self.root = concat(left, right);
+        }
+    }
+
+    /// Splits the rope in two at char index `at`, keeping the text before it and returning
+    /// the rest.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `at` is past the end.
+    pub fn split_off(&mut self, at: usize) -> Rope {
+        self.check_char(at);
+        let (left, right) = self
+            .root
+            .take()
+            .map_or((None, None), |root| split(root, at));
+        self.root = left;
+        Rope { root: right }
+    }
+
+    /// Moves all of `other`'s text onto the end of this rope.
+    pub fn append(&mut self, other: Rope) {
+        self.root = concat(self.root.take(), other.root);
+    }
+
+    /// Iterates over the chunks the text is stored in, in order.
+    pub fn chunks(&self) -> Chunks<'_> {
+        Chunks {
+            stack: self.root.as_deref().into_iter().collect(),
+        }
+    }
+
+    pub fn chars(&self) -> impl Iterator<Item = char> + '_ {
+        self.chunks().flat_map(str::chars)
+    }
+
+    /// Iterates over the lines, without their newlines. There are always
+    /// [`len_lines`](Rope::len_lines) of them.
+    pub fn lines(&self) -> Lines<'_> {
+        Lines {
+            chunks: self.chunks(),
+            chunk: "",
+            done: false,
+        }
+    }
+}
+
+impl From<&str> for Rope {
+    fn from(text: &str) -> Self {
+        Rope { root: build(text) }
+    }
+}
+
+impl PartialEq for Rope {
+    fn eq(&self, other: &Self) -> bool {
+        // Chunk boundaries depend on the edit history, so compare the bytes
+        self.len_bytes() == other.len_bytes()
+            && self
+                .chunks()
+                .flat_map(str::bytes)
+                .eq(other.chunks().flat_map(str::bytes))
+    }
+}
+
+impl Eq for Rope {}
+
+impl PartialEq<str> for Rope {
+    fn eq(&self, other: &str) -> bool {
+        self.len_bytes() == other.len() && self.chunks().flat_map(str::bytes).eq(other.bytes())
+    }
+}
+
+impl fmt::Display for Rope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.chunks().try_for_each(|chunk| f.write_str(chunk))
+    }
+}
+
+impl fmt::Debug for Rope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.to_string(), f)
+    }
+}
+
+/// Iterator over the chunks of a [`Rope`].
+pub struct Chunks<'a> {
+    stack: Vec<&'a Node>,
+}
+
+impl<'a> Iterator for Chunks<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(node) = self.stack.pop() {
+            match node {
+                Node::Leaf { text, .. } => return Some(text),
+                Node::Branch { left, right, .. } => {
+                    self.stack.push(right);
+                    self.stack.push(left);
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Iterator over the lines of a [`Rope`]. A line can span chunks, so each one is copied
+/// out into a `String`.
+pub struct Lines<'a> {
+    chunks: Chunks<'a>,
+    // What's left of the current chunk
+    chunk: &'a str,
+    done: bool,
+}
+
+impl<'a> Iterator for Lines<'a> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let mut line = String::new();
+        loop {
+            if let Some(i) = self.chunk.find('\n') {
+                line.push_str(&self.chunk[..i]);
+                self.chunk = &self.chunk[i + 1..];
+                return Some(line);
+            }
+            line.push_str(self.chunk);
+            match self.chunks.next() {
+                Some(chunk) => self.chunk = chunk,
+                None => {
+                    self.done = true;
+                    return Some(line);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Metrics, Node, Rope, MAX_CHUNK};
+
+    /// Checks the tree is AVL-balanced, every count is right and no chunk is empty or too big.
+    fn check(rope: &Rope) {
+        fn check_node(node: &Node) -> (usize, Metrics) {
+            match node {
+                Node::Leaf { text, metrics } => {
+                    assert!(!text.is_empty() && text.len() <= MAX_CHUNK);
+                    assert_eq!(*metrics, Metrics::of(text));
+                    (0, *metrics)
+                }
+                Node::Branch {
+                    left,
+                    right,
+                    height,
+                    metrics,
+                } => {
+                    let (lh, lm) = check_node(left);
+                    let (rh, rm) = check_node(right);
+                    assert!(lh <= rh + 1 && rh <= lh + 1);
+                    assert_eq!(*height, 1 + lh.max(rh));
+                    assert_eq!(*metrics, lm.add(rm));
+                    (*height, *metrics)
+                }
+            }
+        }
+        if let Some(root) = &rope.root {
+            check_node(root);
+        }
+    }
+
+    #[test]
+    fn basics() {
+        let mut rope = Rope::new();
+
+        // Check empty rope behaves right
+        assert!(rope.is_empty());
+        assert_eq!(rope.len_chars(), 0);
+        assert_eq!(rope.len_lines(), 1);
+        assert_eq!(rope.chunks().next(), None);
+        assert_eq!(rope.lines().collect::<Vec<_>>(), [""]);
+
+        // Populate rope
+        rope.insert(0, "hello world");
+        rope.insert(5, ", big");
+        assert_eq!(rope, *"hello, big world");
+        assert_eq!(rope.char(7), 'b');
+
+        // Check normal removal
+        rope.delete(5..10);
+        assert_eq!(rope.to_string(), "hello world");
+        rope.delete(..=5);
+        assert_eq!(rope.to_string(), "world");
+
+        // Push some more just to make sure nothing's corrupted
+        let long = "abcdefghij".repeat(100);
+        rope.insert(2, &long);
+        check(&rope);
+        assert!(rope.chunks().count() > 1);
+        assert_eq!(rope.to_string(), format!("wo{}rld", long));
+        let rest = rope.split_off(500);
+        check(&rope);
+        check(&rest);
+        assert_eq!(rope.len_chars() + rest.len_chars(), 1005);
+        rope.append(rest);
+        assert_eq!(rope.to_string(), format!("wo{}rld", long));
+
+        // Check exhaustion
+        rope.delete(..);
+        assert!(rope.is_empty());
+        assert_eq!(rope, Rope::new());
+    }
+
+    #[test]
+    fn indices() {
+        // One, two, three and four byte chars, with chunk boundaries falling between them
+        let text = "aé字😀\n".repeat(100);
+        let rope = Rope::from(&text[..]);
+        check(&rope);
+        let mut line = 0;
+        for (c, (b, ch)) in text.char_indices().enumerate() {
+            assert_eq!(rope.char(c), ch);
+            assert_eq!(rope.char_to_byte(c), b);
+            assert_eq!(rope.byte_to_char(b), c);
+            assert_eq!(rope.char_to_line(c), line);
+            if ch == '\n' {
+                line += 1;
+                assert_eq!(rope.line_to_char(line), c + 1);
+            }
+        }
+        assert_eq!(rope.char_to_byte(rope.len_chars()), text.len());
+        assert_eq!(rope.byte_to_char(text.len()), rope.len_chars());
+        assert_eq!(rope.char_to_line(rope.len_chars()), 100);
+        assert_eq!(rope.len_lines(), 101);
+    }
+
+    #[test]
+    #[should_panic(expected = "not a char boundary")]
+    fn mid_char_byte() {
+        Rope::from("aé").byte_to_char(2);
+    }
+
+    #[test]
+    fn lines() {
+        let rope = Rope::from("a\nbc\n\nd");
+        assert_eq!(rope.lines().collect::<Vec<_>>(), ["a", "bc", "", "d"]);
+        assert_eq!(rope.line_to_char(3), 6);
+        let rope = Rope::from("x\n");
+        assert_eq!(rope.lines().collect::<Vec<_>>(), ["x", ""]);
+
+        // Lines spanning several chunks
+        let long = format!("{}\n{}", "a".repeat(1000), "b".repeat(600));
+        let rope = Rope::from(&long[..]);
+        assert!(rope.lines().eq(long.split('\n').map(String::from)));
+    }
+
+    #[test]
+    fn against_string() {
+        let alphabet = ['a', 'b', ' ', '\n', 'é', '字', '😀'];
+        let mut rope = Rope::new();
+        let mut model = String::new();
+        let mut x: u32 = 1;
+        let n = if cfg!(miri) { 200 } else { 3_000 };
+        for _ in 0..n {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let len = model.chars().count();
+            let at = (x >> 8) as usize % (len + 1);
+            let byte =
+                |model: &String, at| model.char_indices().nth(at).map_or(model.len(), |(i, _)| i);
+            match x >> 29 {
+                0..=4 if len < 10_000 => {
+                    // Mostly short inserts, sometimes long enough to need new chunks
+                    let size = if x & 0x380 != 0 {
+                        x as usize % 5
+                    } else {
+                        x as usize % 600
+                    };
+                    let text: String = (0..size)
+                        .map(|i| alphabet[((x as usize >> 3) + i * 5) % 7])
+                        .collect();
+                    rope.insert(at, &text);
+                    model.insert_str(byte(&model, at), &text);
+                }
+                0..=6 => {
+                    let end = (at + (x >> 3) as usize % 100).min(len);
+                    rope.delete(at..end);
+                    let (start, end) = (byte(&model, at), byte(&model, end));
+                    model.replace_range(start..end, "");
+                }
+                _ => {
+                    let rest = rope.split_off(at);
+                    assert_eq!(rest, model[byte(&model, at)..]);
+                    rope.append(rest);
+                }
+            }
+            check(&rope);
+            assert_eq!(rope, *model.as_str());
+            assert_eq!(rope.len_chars(), model.chars().count());
+            assert_eq!(rope.len_lines(), model.matches('\n').count() + 1);
+            if rope.len_chars() > 0 {
+                let at = at % rope.len_chars();
+                assert_eq!(rope.char_to_byte(at), byte(&model, at));
+                assert_eq!(rope.char(at), model.chars().nth(at).unwrap());
+            }
+        }
+        assert!(rope.lines().eq(model.split('\n').map(String::from)));
+    }
+}
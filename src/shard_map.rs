@@ -0,0 +1,316 @@
+//! # Sharded concurrent hash map
+//!
+//! The simplest way to make a hash map scale across threads: split it into several smaller
+//! maps ("shards"), each behind its own `RwLock`, and use the key's hash to pick a shard.
+//! Two threads only contend when their keys land in the same shard, and readers of the same
+//! shard don't block each other at all.
+//!
+//! ```text
+//! hash(key) -> top bits pick the shard
+//!
+//! [ RwLock<HashMap> ] [ RwLock<HashMap> ] [ RwLock<HashMap> ] [ RwLock<HashMap> ] ...
+//! ```
+//!
+//! The shard is picked from the *top* bits of the hash, because the inner maps use the same
+//! hasher and index their buckets by the low bits. Picking shards by the low bits too would
+//! leave every key in a shard sharing them, and pile them into a fraction of the buckets.
+//!
+//! Nothing can borrow into a shard once its lock is released, so lookups hand back clones.
+
+use std::borrow::Borrow;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hash};
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+const DEFAULT_SHARDS: usize = 16;
+
+pub struct ShardMap<K, V, S = RandomState> {
+    shards: Box<[RwLock<HashMap<K, V, S>>]>,
+    hasher: S,
+    shift: u32,
+}
+
+impl<K, V> ShardMap<K, V, RandomState>
+where
+    K: Eq + Hash,
+{
+    /// Creates an empty ShardMap.
+    pub fn new() -> Self {
+        Self::with_shards(DEFAULT_SHARDS)
+    }
+
+    /// Creates an empty map with at least `shards` shards, rounded up to a power of two.
+    pub fn with_shards(shards: usize) -> Self {
+        Self::with_shards_and_hasher(shards, RandomState::new())
+    }
+}
+
+impl<K, V, S> ShardMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher + Clone,
+{
+    /// Creates an empty map with at least `shards` shards that hashes keys with `hasher`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `shards` is zero.
+    pub fn with_shards_and_hasher(shards: usize, hasher: S) -> Self {
+        assert!(shards > 0, "need at least one shard");
+        let shards = shards.next_power_of_two();
+        ShardMap {
+            shards: (0..shards)
+                .map(|_| RwLock::new(HashMap::with_hasher(hasher.clone())))
+                .collect(),
+            hasher,
+            shift: 64 - shards.trailing_zeros(),
+        }
+    }
+
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    fn shard_index<Q>(&self, key: &Q) -> usize
+    where
+        Q: Hash + ?Sized,
+    {
+        // `checked_shr` because with a single shard we'd shift out all 64 bits
+        let hash = self.hasher.hash_one(key);
+        hash.checked_shr(self.shift).unwrap_or(0) as usize
+    }
+
+    fn read<Q>(&self, key: &Q) -> RwLockReadGuard<'_, HashMap<K, V, S>>
+    where
+        Q: Hash + ?Sized,
+    {
+        self.shards[self.shard_index(key)].read().unwrap()
+    }
+
+    fn write<Q>(&self, key: &Q) -> RwLockWriteGuard<'_, HashMap<K, V, S>>
+    where
+        Q: Hash + ?Sized,
+    {
+        self.shards[self.shard_index(key)].write().unwrap()
+    }
+
+    /// Returns a copy of the value for `key`.
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+        V: Clone,
+    {
+        self.read(key).get(key).cloned()
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.read(key).contains_key(key)
+    }
+
+    /// Inserts a key-value pair, returning the old value if the key was already there.
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        self.write(&key).insert(key, value)
+    }
+
+    /// Removes a key, returning its value if it was there.
+    pub fn remove<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.write(key).remove(key)
+    }
+
+    /// Returns a copy of the value for `key`, inserting `f()` first if there isn't one. The
+    /// check and the insert happen under one lock, so `f` runs at most once per key no
+    /// matter how many threads race for it.
+    pub fn entry_or_insert_with<F>(&self, key: K, f: F) -> V
+    where
+        F: FnOnce() -> V,
+        V: Clone,
+    {
+        self.write(&key).entry(key).or_insert_with(f).clone()
+    }
+
+    /// Calls `f` with the value for `key` while holding its shard's write lock, so
+    /// read-modify-write updates don't race. Returns whatever `f` returns.
+    pub fn modify<Q, F, R>(&self, key: &Q, f: F) -> Option<R>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+        F: FnOnce(&mut V) -> R,
+    {
+        self.write(key).get_mut(key).map(f)
+    }
+
+    /// Adds up the shards one at a time, so under concurrent writes it's only approximate.
+    pub fn len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| shard.read().unwrap().len())
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shards
+            .iter()
+            .all(|shard| shard.read().unwrap().is_empty())
+    }
+
+    /// Copies out every entry. All shards are read-locked together (always in index order,
+    /// so this can't deadlock with another snapshot), which makes the copy a consistent
+    /// point-in-time view; writers wait while it's being taken.
+    pub fn snapshot(&self) -> Vec<(K, V)>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        let guards: Vec<_> = self
+            .shards
+            .iter()
+            .map(|shard| shard.read().unwrap())
+            .collect();
+        guards
+            .iter()
+            .flat_map(|shard| shard.iter().map(|(k, v)| (k.clone(), v.clone())))
+            .collect()
+    }
+
+    /// Removes everything.
+    pub fn clear(&self) {
+        for shard in self.shards.iter() {
+            shard.write().unwrap().clear();
+        }
+    }
+}
+
+impl<K, V> Default for ShardMap<K, V, RandomState>
+where
+    K: Eq + Hash,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ShardMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn basics() {
+        let map = ShardMap::new();
+        assert!(map.is_empty());
+        assert_eq!(map.get(&1), None);
+
+        assert_eq!(map.insert(1, "a"), None);
+        assert_eq!(map.insert(2, "b"), None);
+        assert_eq!(map.insert(1, "A"), Some("a"));
+        assert_eq!(map.get(&1), Some("A"));
+        assert!(map.contains_key(&2));
+        assert_eq!(map.len(), 2);
+
+        assert_eq!(map.remove(&2), Some("b"));
+        assert_eq!(map.remove(&2), None);
+        assert_eq!(map.len(), 1);
+
+        map.clear();
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn shard_counts() {
+        let map: ShardMap<i32, i32> = ShardMap::with_shards(5);
+        assert_eq!(map.shard_count(), 8);
+
+        // A single shard still works
+        let map = ShardMap::with_shards(1);
+        for i in 0..100 {
+            map.insert(i, i);
+        }
+        assert_eq!(map.len(), 100);
+        assert_eq!(map.get(&42), Some(42));
+    }
+
+    #[test]
+    fn borrowed_keys() {
+        let map = ShardMap::new();
+        map.insert("one".to_string(), 1);
+        assert_eq!(map.get("one"), Some(1));
+        assert_eq!(
+            map.modify("one", |v| {
+                *v += 10;
+                *v
+            }),
+            Some(11)
+        );
+        assert_eq!(map.modify("two", |v| *v), None);
+        assert_eq!(map.remove("one"), Some(11));
+    }
+
+    #[test]
+    fn entry_or_insert_with() {
+        let map = ShardMap::new();
+        assert_eq!(map.entry_or_insert_with("k", || 1), 1);
+        // Already there, so the closure doesn't run
+        assert_eq!(map.entry_or_insert_with("k", || unreachable!()), 1);
+    }
+
+    #[test]
+    fn snapshot() {
+        let map = ShardMap::new();
+        for i in 0..50 {
+            map.insert(i, i * 2);
+        }
+        let mut entries = map.snapshot();
+        entries.sort();
+        assert_eq!(entries, (0..50).map(|i| (i, i * 2)).collect::<Vec<_>>());
+
+        // The snapshot is a copy, later writes don't show up in it
+        map.clear();
+        assert_eq!(entries.len(), 50);
+    }
+
+    #[test]
+    fn concurrent_counters() {
+        let per_thread = if cfg!(miri) { 50 } else { 5_000 };
+        let threads = 4;
+        let map = Arc::new(ShardMap::new());
+        let created = Arc::new(AtomicUsize::new(0));
+
+        // Every thread bumps the same 32 counters
+        let handles: Vec<_> = (0..threads)
+            .map(|_| {
+                let map = map.clone();
+                let created = created.clone();
+                thread::spawn(move || {
+                    for i in 0..per_thread {
+                        let key = i % 32;
+                        map.entry_or_insert_with(key, || {
+                            created.fetch_add(1, Ordering::SeqCst);
+                            0
+                        });
+                        map.modify(&key, |count| *count += 1);
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        // Each counter was created exactly once, and no increment got lost
+        assert_eq!(created.load(Ordering::SeqCst), 32);
+        let total: usize = map.snapshot().into_iter().map(|(_, count)| count).sum();
+        assert_eq!(total, threads * per_thread);
+    }
+}
@@ -0,0 +1,315 @@
+//! # Shortest paths
+//!
+//! Dijkstra's algorithm (1959) and A* (Hart, Nilsson and Raphael, 1968) over a [`Graph`],
+//! with non-negative edge costs.
+//!
+//! Dijkstra's algorithm settles nodes in order of distance from the start. It keeps every
+//! node it has found a way to in an [`IndexedHeap`] by the length of the best way so
+//! far, and keeps taking out the closest. That one's distance is final: any other way
+//! there would have to leave through some node still in the heap, which is no closer.
+//! Taking a node out *relaxes* its edges: a neighbor that this node gets to more cheaply
+//! than before has its distance lowered, in place in the heap.
+//!
+//! ```text
+//!          4            settled   heap
+//!     s ------> b       s 0       a 1, b 4
+//!     |         ^       a 1       c 2, b 4
+//!   1 |       1 |       c 2       b 3          (s a c b beats s b)
+//!     v    1    |       b 3
+//!     a ------> c
+//! ```
+//!
+//! A* is the same search towards one goal, with each node's place in the heap going by
+//! its distance *plus* a guess at how far it still is from the goal. A good guess steers
+//! the search straight at the goal instead of outwards in every direction. The guess must
+//! never be more than the real distance left, or the path found may not be the shortest.
+//! A node can come back into the heap if a guess that's low enough to be allowed, but
+//! uneven, got it settled too soon: what's settled for good is just the goal.
+//!
+//! Both remember which edge each node was last reached by, so the path back to the start
+//! is a walk along those. With a binary heap, they take O((V + E) log V).
+
+use crate::graph::{Direction, EdgeId, EdgeRef, Graph, NodeId};
+use crate::indexed_heap::IndexedHeap;
+use std::collections::HashMap;
+use std::ops::Add;
+
+/// An edge cost: something ordered with a zero that adding to changes nothing.
+pub trait Cost: Copy + Ord + Add<Output = Self> {
+    fn zero() -> Self;
+}
+
+macro_rules! impl_cost {
+    ($($t:ty)*) => {
+        $(impl Cost for $t {
+            fn zero() -> Self {
+                0
+            }
+        })*
+    };
+}
+
+impl_cost!(u8 u16 u32 u64 u128 usize i8 i16 i32 i64 i128 isize);
+
+/// The result of [`dijkstra`]: the distance from the start to every node it can reach,
+/// and a shortest path to each.
+pub struct ShortestPaths<C> {
+    start: NodeId,
+    // Best distance found for each node, and the node and edge it was reached by
+    best: HashMap<NodeId, (C, Option<(NodeId, EdgeId)>)>,
+}
+
+impl<C: Cost> ShortestPaths<C> {
+    pub fn start(&self) -> NodeId {
+        self.start
+    }
+
+    /// Returns the distance from the start to `node`, or `None` if it can't be reached.
+    pub fn distance(&self, node: NodeId) -> Option<C> {
+        self.best.get(&node).map(|&(cost, _)| cost)
+    }
+
+    /// Returns the node just before `node` on a shortest path to it, and the edge from
+    /// there. The start has none.
+    pub fn predecessor(&self, node: NodeId) -> Option<(NodeId, EdgeId)> {
+        self.best.get(&node).and_then(|&(_, prev)| prev)
+    }
+
+    /// Returns the nodes on a shortest path from the start to `node`, both included.
+    pub fn path_to(&self, node: NodeId) -> Option<Vec<NodeId>> {
+        self.best.get(&node)?;
+        let mut path = vec![node];
+        let mut at = node;
+        while let Some((prev, _)) = self.predecessor(at) {
+            path.push(prev);
+            at = prev;
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    /// Iterates over the nodes that can be reached and their distances, in no order.
+    pub fn iter(&self) -> impl Iterator<Item = (NodeId, C)> + '_ {
+        self.best.iter().map(|(&node, &(cost, _))| (node, cost))
+    }
+}
+
+/// The search behind both: settles nodes by distance so far plus `heuristic`, until the
+/// heap runs out or the goal is settled.
+fn search<N, E, D: Direction, C: Cost>(
+    graph: &Graph<N, E, D>,
+    start: NodeId,
+    goal: Option<NodeId>,
+    cost: impl Fn(EdgeRef<'_, E>) -> C,
+    heuristic: impl Fn(NodeId) -> C,
+) -> ShortestPaths<C> {
+    let mut best = HashMap::new();
+    let mut heap = IndexedHeap::new();
+    if graph.contains_node(start) {
+        best.insert(start, (C::zero(), None));
+        heap.push(start, heuristic(start));
+    }
+    while let Some((node, _)) = heap.pop() {
+        if Some(node) == goal {
+            break;
+        }
+        let (distance, _) = best[&node];
+        for edge in graph.edges_of(node) {
+            let step = cost(edge);
+            debug_assert!(step >= C::zero(), "negative edge cost");
+            let next = distance + step;
+            if best.get(&edge.target).is_none_or(|&(old, _)| next < old) {
+                best.insert(edge.target, (next, Some((node, edge.id))));
+                heap.push(edge.target, next + heuristic(edge.target));
+            }
+        }
+    }
+    ShortestPaths { start, best }
+}
+
+/// Finds the shortest paths from `start` to every node it can reach, `cost` giving each
+/// edge's length, which mustn't be negative.
+pub fn dijkstra<N, E, D: Direction, C: Cost>(
+    graph: &Graph<N, E, D>,
+    start: NodeId,
+    cost: impl Fn(EdgeRef<'_, E>) -> C,
+) -> ShortestPaths<C> {
+    search(graph, start, None, cost, |_| C::zero())
+}
+
+/// Finds a shortest path from `start` to `goal`, and its length. `heuristic` guesses the
+/// distance from a node to the goal and must never guess high.
+pub fn astar<N, E, D: Direction, C: Cost>(
+    graph: &Graph<N, E, D>,
+    start: NodeId,
+    goal: NodeId,
+    cost: impl Fn(EdgeRef<'_, E>) -> C,
+    heuristic: impl Fn(NodeId) -> C,
+) -> Option<(C, Vec<NodeId>)> {
+    let paths = search(graph, start, Some(goal), cost, heuristic);
+    Some((paths.distance(goal)?, paths.path_to(goal)?))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{astar, dijkstra};
+    use crate::graph::{DiGraph, NodeId, UnGraph};
+    use std::cell::Cell;
+
+    /// Checks a path starts and ends right and that its edges add up to `length`.
+    fn check_path(
+        graph: &DiGraph<(), u32>,
+        path: &[NodeId],
+        from: NodeId,
+        to: NodeId,
+        length: u32,
+    ) {
+        assert_eq!(path.first(), Some(&from));
+        assert_eq!(path.last(), Some(&to));
+        let total: u32 = path
+            .windows(2)
+            .map(|w| {
+                graph
+                    .edges_of(w[0])
+                    .filter(|edge| edge.target == w[1])
+                    .map(|edge| *edge.weight)
+                    .min()
+                    .unwrap()
+            })
+            .sum();
+        assert_eq!(total, length);
+    }
+
+    #[test]
+    fn basics() {
+        // The graph in the module docs
+        let mut graph = DiGraph::new();
+        let [s, a, b, c, lone] = [(); 5].map(|_| graph.add_node(()));
+        graph.add_edge(s, b, 4);
+        graph.add_edge(s, a, 1);
+        graph.add_edge(a, c, 1);
+        graph.add_edge(c, b, 1);
+        let paths = dijkstra(&graph, s, |edge| *edge.weight);
+        assert_eq!(paths.start(), s);
+        assert_eq!(paths.distance(s), Some(0));
+        assert_eq!(paths.distance(b), Some(3));
+        assert_eq!(paths.distance(c), Some(2));
+        assert_eq!(paths.path_to(b), Some(vec![s, a, c, b]));
+        assert_eq!(paths.path_to(s), Some(vec![s]));
+        assert_eq!(paths.predecessor(s), None);
+        assert_eq!(paths.iter().count(), 4);
+
+        // Unreachable
+        assert_eq!(paths.distance(lone), None);
+        assert_eq!(paths.path_to(lone), None);
+        let back = dijkstra(&graph, b, |edge| *edge.weight);
+        assert_eq!(back.distance(s), None);
+
+        // A cheaper direct edge wins, and A* agrees
+        graph.add_edge(s, b, 2);
+        let paths = dijkstra(&graph, s, |edge| *edge.weight);
+        assert_eq!(paths.path_to(b), Some(vec![s, b]));
+        assert_eq!(
+            astar(&graph, s, b, |e| *e.weight, |_| 0),
+            Some((2, vec![s, b]))
+        );
+        assert_eq!(astar(&graph, s, lone, |e| *e.weight, |_| 0), None);
+    }
+
+    #[test]
+    fn grid() {
+        // Walls down the middle of a grid, every step costing one, with a gap at the top;
+        // A* with the Manhattan distance settles far fewer nodes than Dijkstra
+        let n = 30;
+        let mut graph = UnGraph::new();
+        let nodes: Vec<_> = (0..n * n).map(|i| graph.add_node((i / n, i % n))).collect();
+        let wall = |r: usize, c: usize| c == n / 2 && r > 0;
+        for r in 0..n {
+            for c in 0..n {
+                if wall(r, c) {
+                    continue;
+                }
+                if c + 1 < n && !wall(r, c + 1) {
+                    graph.add_edge(nodes[r * n + c], nodes[r * n + c + 1], ());
+                }
+                if r + 1 < n && !wall(r + 1, c) {
+                    graph.add_edge(nodes[r * n + c], nodes[(r + 1) * n + c], ());
+                }
+            }
+        }
+        let (start, goal) = (nodes[(n - 1) * n], nodes[(n - 1) * n + n - 1]);
+        let paths = dijkstra(&graph, start, |_| 1usize);
+        let expected = 2 * (n - 1) + (n - 1);
+        assert_eq!(paths.distance(goal), Some(expected));
+
+        let looked_at = Cell::new(0);
+        let manhattan = |node: NodeId| {
+            looked_at.set(looked_at.get() + 1);
+            let (r, c) = graph[node];
+            let (gr, gc) = graph[goal];
+            r.max(gr) - r.min(gr) + c.max(gc) - c.min(gc)
+        };
+        let (length, path) = astar(&graph, start, goal, |_| 1, manhattan).unwrap();
+        assert_eq!(length, expected);
+        assert_eq!(path.len(), expected + 1);
+        assert!(looked_at.get() < paths.iter().count());
+    }
+
+    #[test]
+    fn against_floyd_warshall() {
+        // Random graphs, checked against all the distances worked out the slow way
+        let mut x: u32 = 1;
+        let rounds = if cfg!(miri) { 2 } else { 30 };
+        let n = 25;
+        for _ in 0..rounds {
+            let mut graph = DiGraph::new();
+            let nodes: Vec<_> = (0..n).map(|_| graph.add_node(())).collect();
+            for _ in 0..3 * n {
+                x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                let (a, b) = ((x >> 8) as usize % n, (x >> 16) as usize % n);
+                graph.add_edge(nodes[a], nodes[b], (x >> 24) % 20);
+            }
+            let mut model = vec![vec![None; n]; n];
+            for (i, row) in model.iter_mut().enumerate() {
+                row[i] = Some(0);
+            }
+            for edge in graph.edges() {
+                let (a, b) = (edge.source.index(), edge.target.index());
+                if model[a][b].is_none_or(|old| *edge.weight < old) {
+                    model[a][b] = Some(*edge.weight);
+                }
+            }
+            for k in 0..n {
+                for i in 0..n {
+                    for j in 0..n {
+                        if let (Some(a), Some(b)) = (model[i][k], model[k][j]) {
+                            if model[i][j].is_none_or(|old| a + b < old) {
+                                model[i][j] = Some(a + b);
+                            }
+                        }
+                    }
+                }
+            }
+
+            let start = nodes[0];
+            let paths = dijkstra(&graph, start, |edge| *edge.weight);
+            for (goal, &node) in nodes.iter().enumerate() {
+                assert_eq!(paths.distance(node), model[0][goal]);
+                let d = match model[0][goal] {
+                    Some(d) => d,
+                    None => continue,
+                };
+                check_path(&graph, &paths.path_to(node).unwrap(), start, node, d);
+
+                // Any guess up to the real distance left will do, however uneven
+                let uneven = |from: NodeId| match model[from.index()][goal] {
+                    Some(left) if from.index().is_multiple_of(2) => left,
+                    _ => 0,
+                };
+                let (length, path) = astar(&graph, start, node, |e| *e.weight, uneven).unwrap();
+                assert_eq!(length, d);
+                check_path(&graph, &path, start, node, d);
+            }
+        }
+    }
+}
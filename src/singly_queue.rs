@@ -23,7 +23,7 @@ impl<T> List<T> {
 
     pub fn push(&mut self, elem: T) {
         let mut new_tail = Box::new(Node {
-            elem: elem,
+            elem,
             // When you push onto the tail, your next is always None
             next: None,
         });
@@ -33,12 +33,12 @@ impl<T> List<T> {
         //     Some(old_tail) => {
         //         // If the old tail existed, update it to point to the new tail
         //         old_tail.next = Some(new_tail);
-        //         old_tail.next.as_mut().map(|node| &mut **node)
+        //         old_tail.next.as_deref_mut()
         //     }
         //     None => {
         //         // Otherwise, update the head to point to it
         //         self.head = Some(new_tail);
-        //         self.head.as_mut().map(|node| &mut **node)
+        //         self.head.as_deref_mut()
         //     }
         // };
 
@@ -82,25 +82,36 @@ impl<T> List<T> {
         self.head.as_mut().map(|node| &mut node.elem)
     }
 
-    pub fn into_iter(self) -> IntoIter<T> {
-        IntoIter(self)
-    }
-
     pub fn iter(&self) -> Iter<'_, T> {
         Iter {
-            next: self.head.as_ref().map(|node| &**node),
+            next: self.head.as_deref(),
         }
     }
 
     pub fn iter_mut(&mut self) -> IterMut<'_, T> {
         IterMut {
-            next: self.head.as_mut().map(|node| &mut **node),
+            next: self.head.as_deref_mut(),
         }
     }
 }
 
+impl<T> Default for List<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 pub struct IntoIter<T>(List<T>);
 
+impl<T> IntoIterator for List<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter(self)
+    }
+}
+
 pub struct Iter<'a, T> {
     next: Option<&'a Node<T>>,
 }
@@ -131,7 +142,7 @@ impl<'a, T> Iterator for Iter<'a, T> {
 
     fn next(&mut self) -> Option<Self::Item> {
         self.next.map(|node| {
-            self.next = node.next.as_ref().map(|node| &**node);
+            self.next = node.next.as_deref();
             &node.elem
         })
     }
@@ -142,7 +153,7 @@ impl<'a, T> Iterator for IterMut<'a, T> {
 
     fn next(&mut self) -> Option<Self::Item> {
         self.next.take().map(|node| {
-            self.next = node.next.as_mut().map(|node| &mut **node);
+            self.next = node.next.as_deref_mut();
             &mut node.elem
         })
     }
@@ -0,0 +1,601 @@
+//! # Concurrent skip-list map
+//!
+//! An ordered map many threads can read and write at once, built on the "lazy" skip list of
+//! Herlihy, Lev, Luchangco and Shavit. A skip list is a stack of sorted linked lists: every
+//! node is on the bottom level, and each level up holds roughly half of the nodes below it,
+//! so a search can skip ahead from the top and drop down as it closes in.
+//!
+//! ```text
+//! level 2: head --------------------> 30 ----------------> null
+//! level 1: head --------> 10 -------> 30 -------> 50 ----> null
+//! level 0: head -> 5 ---> 10 -> 20 -> 30 -> 40 -> 50 ----> null
+//! ```
+//!
+//! Readers never lock: `get` and range iteration just follow atomic pointers. Writers lock
+//! only the handful of nodes whose links they change, validate that those still look the
+//! way the search left them, and retry otherwise.
+//!
+//! Two flags per node keep readers honest:
+//!
+//! - `fully_linked` is set once an insert has linked the node on every level. Until then
+//!   it's invisible, so nobody sees a half-inserted key.
+//! - `marked` is set (under the node's lock) when a remove claims the node. From then on the
+//!   key counts as gone, even though the node is still unlinked level by level.
+//!
+//! Removed nodes may still be under a reader's feet, so they're freed through
+//! [`crate::epoch`]. Values live behind their own pointer, so replacing one swaps the
+//! pointer and retires the old value the same way.
+
+use crate::epoch::{self, Guard};
+use crate::rng;
+use std::borrow::Borrow;
+use std::ops::{Bound, RangeBounds};
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering::SeqCst};
+use std::sync::{Mutex, MutexGuard};
+use std::thread;
+
+const MAX_HEIGHT: usize = 16;
+
+pub struct SkipMap<K, V> {
+    head: *mut Node<K, V>,
+    len: AtomicUsize,
+}
+
+struct Node<K, V> {
+    // `None` only in the head sentinel
+    key: Option<K>,
+    value: AtomicPtr<V>,
+    next: Box<[AtomicPtr<Node<K, V>>]>,
+    lock: Mutex<()>,
+    marked: AtomicBool,
+    fully_linked: AtomicBool,
+}
+
+impl<K, V> Node<K, V> {
+    fn alloc(key: Option<K>, value: *mut V, height: usize) -> *mut Self {
+        let next = (0..height)
+            .map(|_| AtomicPtr::new(ptr::null_mut()))
+            .collect();
+        Box::into_raw(Box::new(Node {
+            key,
+            value: AtomicPtr::new(value),
+            next,
+            lock: Mutex::new(()),
+            marked: AtomicBool::new(false),
+            fully_linked: AtomicBool::new(false),
+        }))
+    }
+
+    fn height(&self) -> usize {
+        self.next.len()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ()> {
+        self.lock.lock().unwrap()
+    }
+}
+
+impl<K, V> Drop for Node<K, V> {
+    fn drop(&mut self) {
+        let value = *self.value.get_mut();
+        if !value.is_null() {
+            unsafe { drop(Box::from_raw(value)) };
+        }
+    }
+}
+
+/// Picks a tower height: 1 with probability 1/2, 2 with 1/4, and so on.
+fn random_height() -> usize {
+    (rng::next_u64().trailing_zeros() as usize + 1).min(MAX_HEIGHT)
+}
+
+/// Where a search for a key ended up on each level.
+struct Position<K, V> {
+    preds: [*mut Node<K, V>; MAX_HEIGHT],
+    succs: [*mut Node<K, V>; MAX_HEIGHT],
+    // The highest level the key was found on, if it was
+    found: Option<usize>,
+}
+
+impl<K, V> SkipMap<K, V>
+where
+    K: Ord + Send + 'static,
+    V: Send + 'static,
+{
+    /// Creates an empty SkipMap.
+    pub fn new() -> Self {
+        let head = Node::alloc(None, ptr::null_mut(), MAX_HEIGHT);
+        unsafe { (*head).fully_linked.store(true, SeqCst) };
+        SkipMap {
+            head,
+            len: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns how many keys there were at the moment we looked.
+    pub fn len(&self) -> usize {
+        self.len.load(SeqCst)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Walks down from the top, recording the last node before `key` and the first node at
+    /// or after it on every level. Must be called while pinned.
+    fn find<Q>(&self, key: &Q, _guard: &Guard) -> Position<K, V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut position = Position {
+            preds: [ptr::null_mut(); MAX_HEIGHT],
+            succs: [ptr::null_mut(); MAX_HEIGHT],
+            found: None,
+        };
+        let mut pred = self.head;
+
+        for level in (0..MAX_HEIGHT).rev() {
+            let mut curr = unsafe { (*pred).next[level].load(SeqCst) };
+            while let Some(node) = unsafe { curr.as_ref() } {
+                if node.key.as_ref().unwrap().borrow() >= key {
+                    break;
+                }
+                pred = curr;
+                curr = node.next[level].load(SeqCst);
+            }
+
+            if position.found.is_none() {
+                if let Some(node) = unsafe { curr.as_ref() } {
+                    if node.key.as_ref().unwrap().borrow() == key {
+                        position.found = Some(level);
+                    }
+                }
+            }
+            position.preds[level] = pred;
+            position.succs[level] = curr;
+        }
+        position
+    }
+
+    /// Inserts a key-value pair, returning the old value if the key was already there.
+    pub fn insert(&self, key: K, value: V) -> Option<V>
+    where
+        V: Clone,
+    {
+        let guard = epoch::pin();
+        let height = random_height();
+        let value = Box::into_raw(Box::new(value));
+
+        loop {
+            let position = self.find(&key, &guard);
+
+            if let Some(level) = position.found {
+                let node = unsafe { &*position.succs[level] };
+                if node.marked.load(SeqCst) {
+                    // It's on its way out; wait for the remove to finish and try again
+                    continue;
+                }
+                // Someone else's insert may still be linking it in
+                while !node.fully_linked.load(SeqCst) {
+                    thread::yield_now();
+                }
+
+                // Lock so a concurrent remove can't read the old value after we've replaced it
+                let _lock = node.lock();
+                if node.marked.load(SeqCst) {
+                    continue;
+                }
+                let old = node.value.swap(value, SeqCst);
+                let result = unsafe { (*old).clone() };
+                unsafe { guard.defer_destroy(old) };
+                return Some(result);
+            }
+
+            // Lock every distinct predecessor and check nothing changed since the search
+            let Some(_locks) = lock_preds(&position, height, |level, pred, succ| {
+                !pred.marked.load(SeqCst)
+                    && unsafe { succ.as_ref() }.is_none_or(|s| !s.marked.load(SeqCst))
+                    && pred.next[level].load(SeqCst) == succ
+            }) else {
+                continue;
+            };
+
+            let node = Node::alloc(Some(key), value, height);
+            for level in 0..height {
+                unsafe {
+                    (*node).next[level].store(position.succs[level], SeqCst);
+                    (*position.preds[level]).next[level].store(node, SeqCst);
+                }
+            }
+            unsafe { (*node).fully_linked.store(true, SeqCst) };
+            self.len.fetch_add(1, SeqCst);
+            return None;
+        }
+    }
+
+    /// Returns a copy of the value for `key`.
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        V: Clone,
+    {
+        let guard = epoch::pin();
+        let position = self.find(key, &guard);
+        let node = unsafe { &*position.succs[position.found?] };
+        if node.fully_linked.load(SeqCst) && !node.marked.load(SeqCst) {
+            Some(unsafe { (*node.value.load(SeqCst)).clone() })
+        } else {
+            None
+        }
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let guard = epoch::pin();
+        let position = self.find(key, &guard);
+        position.found.is_some_and(|level| {
+            let node = unsafe { &*position.succs[level] };
+            node.fully_linked.load(SeqCst) && !node.marked.load(SeqCst)
+        })
+    }
+
+    /// Removes a key, returning its value if it was there.
+    pub fn remove<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        V: Clone,
+    {
+        let guard = epoch::pin();
+        // Once we've marked the victim it's ours, and we keep its lock across retries
+        let mut victim: *mut Node<K, V> = ptr::null_mut();
+        let mut _victim_lock: Option<MutexGuard<'_, ()>> = None;
+
+        loop {
+            let position = self.find(key, &guard);
+
+            if victim.is_null() {
+                let level = position.found?;
+                let node = unsafe { &*position.succs[level] };
+                // Only remove nodes that are fully in, found on their top level
+                if !node.fully_linked.load(SeqCst)
+                    || node.height() - 1 != level
+                    || node.marked.load(SeqCst)
+                {
+                    return None;
+                }
+                let lock = node.lock();
+                if node.marked.load(SeqCst) {
+                    return None;
+                }
+                node.marked.store(true, SeqCst);
+                victim = position.succs[level];
+                _victim_lock = Some(lock);
+            }
+
+            let node = victim;
+            let height = unsafe { (*node).height() };
+
+            let Some(_locks) = lock_preds(&position, height, |level, pred, _| {
+                !pred.marked.load(SeqCst) && pred.next[level].load(SeqCst) == node
+            }) else {
+                continue;
+            };
+
+            for level in (0..height).rev() {
+                unsafe {
+                    let next = (*node).next[level].load(SeqCst);
+                    (*position.preds[level]).next[level].store(next, SeqCst);
+                }
+            }
+            self.len.fetch_sub(1, SeqCst);
+
+            let value = unsafe { (*(*node).value.load(SeqCst)).clone() };
+            // Readers that found it before the unlink may still be walking through it
+            unsafe { guard.defer_destroy(node) };
+            return Some(value);
+        }
+    }
+
+    /// Iterates over the entries in `range` in key order, yielding copies.
+    ///
+    /// The iterator sees a live view: entries inserted or removed while it's running may or
+    /// may not show up, but every entry it yields was in the map at some point during the
+    /// walk. It keeps the thread pinned, so don't hold on to it for long.
+    pub fn range<Q, R>(&self, range: R) -> Range<'_, K, V, Q, R>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        let guard = epoch::pin();
+        let start = match range.start_bound() {
+            Bound::Unbounded => unsafe { (*self.head).next[0].load(SeqCst) },
+            Bound::Included(key) | Bound::Excluded(key) => {
+                let position = self.find(key, &guard);
+                position.succs[0]
+            }
+        };
+        Range {
+            _map: self,
+            _guard: guard,
+            next: start,
+            range,
+            _key: std::marker::PhantomData,
+        }
+    }
+
+    /// Iterates over every entry in key order, yielding copies.
+    pub fn iter(&self) -> Range<'_, K, V, K, std::ops::RangeFull> {
+        self.range(..)
+    }
+}
+
+/// Locks the predecessors on levels `0..height` from the bottom up, skipping repeats, and
+/// checks `valid` on each level. Returns the locks if everything checked out.
+fn lock_preds<'a, K: 'a, V: 'a>(
+    position: &Position<K, V>,
+    height: usize,
+    valid: impl Fn(usize, &Node<K, V>, *mut Node<K, V>) -> bool,
+) -> Option<Vec<MutexGuard<'a, ()>>> {
+    let mut locks = Vec::with_capacity(height);
+    let mut last = ptr::null_mut();
+
+    for level in 0..height {
+        let pred = position.preds[level];
+        let node = unsafe { &*pred };
+        if pred != last {
+            locks.push(node.lock());
+            last = pred;
+        }
+        if !valid(level, node, position.succs[level]) {
+            return None;
+        }
+    }
+    Some(locks)
+}
+
+/// An iterator over a range of a `SkipMap`. Created by [`SkipMap::range`].
+pub struct Range<'a, K, V, Q: ?Sized, R> {
+    _map: &'a SkipMap<K, V>,
+    _guard: Guard,
+    next: *mut Node<K, V>,
+    range: R,
+    _key: std::marker::PhantomData<fn(&Q)>,
+}
+
+impl<K, V, Q, R> Iterator for Range<'_, K, V, Q, R>
+where
+    K: Borrow<Q> + Clone,
+    V: Clone,
+    Q: Ord + ?Sized,
+    R: RangeBounds<Q>,
+{
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // The guard keeps every node we can reach from here alive
+        while let Some(node) = unsafe { self.next.as_ref() } {
+            self.next = node.next[0].load(SeqCst);
+            let key = node.key.as_ref().unwrap();
+
+            let past_end = match self.range.end_bound() {
+                Bound::Included(end) => key.borrow() > end,
+                Bound::Excluded(end) => key.borrow() >= end,
+                Bound::Unbounded => false,
+            };
+            if past_end {
+                self.next = ptr::null_mut();
+                return None;
+            }
+            if !self.range.contains(key.borrow()) {
+                // Only the excluded start bound can get us here
+                continue;
+            }
+            if node.fully_linked.load(SeqCst) && !node.marked.load(SeqCst) {
+                let value = unsafe { (*node.value.load(SeqCst)).clone() };
+                return Some((key.clone(), value));
+            }
+        }
+        None
+    }
+}
+
+impl<K, V> Default for SkipMap<K, V>
+where
+    K: Ord + Send + 'static,
+    V: Send + 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V> Drop for SkipMap<K, V> {
+    fn drop(&mut self) {
+        // Nobody else can see the map now, so free every node still linked on the bottom
+        let mut curr = self.head;
+        while !curr.is_null() {
+            let node = unsafe { Box::from_raw(curr) };
+            curr = node.next[0].load(SeqCst);
+        }
+    }
+}
+
+unsafe impl<K: Send + Sync, V: Send + Sync> Send for SkipMap<K, V> {}
+unsafe impl<K: Send + Sync, V: Send + Sync> Sync for SkipMap<K, V> {}
+
+#[cfg(test)]
+mod test {
+    use super::SkipMap;
+    use crate::epoch;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn basics() {
+        let map = SkipMap::new();
+        assert!(map.is_empty());
+        assert_eq!(map.get(&1), None);
+        assert_eq!(map.remove(&1), None);
+
+        assert_eq!(map.insert(3, "c"), None);
+        assert_eq!(map.insert(1, "a"), None);
+        assert_eq!(map.insert(2, "b"), None);
+        assert_eq!(map.len(), 3);
+        assert_eq!(map.get(&2), Some("b"));
+        assert!(map.contains_key(&3));
+
+        // Replacing hands back the old value
+        assert_eq!(map.insert(2, "B"), Some("b"));
+        assert_eq!(map.get(&2), Some("B"));
+        assert_eq!(map.len(), 3);
+
+        assert_eq!(map.remove(&1), Some("a"));
+        assert_eq!(map.remove(&1), None);
+        assert!(!map.contains_key(&1));
+        assert_eq!(map.len(), 2);
+    }
+
+    #[test]
+    fn ordered_and_ranges() {
+        let map = SkipMap::new();
+        for i in [5, 1, 9, 3, 7, 2, 8, 4, 6, 0] {
+            map.insert(i, i * 10);
+        }
+
+        let keys: Vec<_> = map.iter().map(|(k, _)| k).collect();
+        assert_eq!(keys, (0..10).collect::<Vec<_>>());
+
+        let pairs: Vec<_> = map.range(3..6).collect();
+        assert_eq!(pairs, vec![(3, 30), (4, 40), (5, 50)]);
+
+        let keys: Vec<_> = map.range(7..).map(|(k, _)| k).collect();
+        assert_eq!(keys, vec![7, 8, 9]);
+
+        let keys: Vec<_> = map.range(..=2).map(|(k, _)| k).collect();
+        assert_eq!(keys, vec![0, 1, 2]);
+
+        use std::ops::Bound::{Excluded, Included};
+        let keys: Vec<_> = map
+            .range((Excluded(3), Included(5)))
+            .map(|(k, _)| k)
+            .collect();
+        assert_eq!(keys, vec![4, 5]);
+
+        assert_eq!(map.range(20..).next(), None);
+    }
+
+    #[test]
+    fn borrowed_keys() {
+        let map = SkipMap::new();
+        map.insert("b".to_string(), 2);
+        map.insert("a".to_string(), 1);
+        assert_eq!(map.get("a"), Some(1));
+        assert_eq!(map.remove("b"), Some(2));
+        assert_eq!(map.len(), 1);
+    }
+
+    #[test]
+    fn drops_everything() {
+        let value = Arc::new(());
+        {
+            let map = SkipMap::new();
+            for i in 0..10 {
+                map.insert(i, value.clone());
+            }
+            map.insert(0, value.clone());
+            map.remove(&5);
+        }
+        // Removed and replaced values go through the epoch collector, so they may take a
+        // few collections to show up
+        for _ in 0..1_000 {
+            if Arc::strong_count(&value) == 1 {
+                break;
+            }
+            epoch::pin().flush();
+            thread::yield_now();
+        }
+        assert_eq!(Arc::strong_count(&value), 1);
+    }
+
+    #[test]
+    fn concurrent_writers() {
+        let per_thread = if cfg!(miri) { 30 } else { 2_000 };
+        let threads = 4;
+        let map = Arc::new(SkipMap::new());
+
+        // Each thread inserts its own keys, removes the odd ones, and reads everyone's
+        let handles: Vec<_> = (0..threads)
+            .map(|t| {
+                let map = map.clone();
+                thread::spawn(move || {
+                    for i in 0..per_thread {
+                        let key = i * threads + t;
+                        assert_eq!(map.insert(key, key), None);
+                        assert_eq!(map.get(&key), Some(key));
+                    }
+                    for i in (1..per_thread).step_by(2) {
+                        let key = i * threads + t;
+                        assert_eq!(map.remove(&key), Some(key));
+                    }
+                    // Range scans while the others are still writing stay sorted
+                    let keys: Vec<_> = map.range(..).map(|(k, _)| k).collect();
+                    assert!(keys.windows(2).all(|w| w[0] < w[1]));
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let expected: Vec<_> = (0..per_thread * threads)
+            .filter(|key| (key / threads) % 2 == 0)
+            .collect();
+        let keys: Vec<_> = map.iter().map(|(k, _)| k).collect();
+        assert_eq!(keys, expected);
+        assert_eq!(map.len(), expected.len());
+    }
+
+    #[test]
+    fn racing_on_same_keys() {
+        let rounds = if cfg!(miri) { 20 } else { 2_000 };
+        let map = Arc::new(SkipMap::new());
+
+        // Everyone fights over the same few keys; the map should never lose track of them
+        let handles: Vec<_> = (0..4)
+            .map(|t| {
+                let map = map.clone();
+                thread::spawn(move || {
+                    for i in 0..rounds {
+                        let key = i % 8;
+                        if (i + t) % 2 == 0 {
+                            map.insert(key, t);
+                        } else {
+                            map.remove(&key);
+                        }
+                        if let Some(value) = map.get(&key) {
+                            assert!(value < 4);
+                        }
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(map.iter().count(), map.len());
+        for key in 0..8 {
+            map.remove(&key);
+        }
+        assert!(map.is_empty());
+        assert_eq!(map.iter().next(), None);
+    }
+}
@@ -0,0 +1,939 @@
+//! # Skip lists
+//!
+//! A skip list is a sorted linked list with express lanes. Every node is on the bottom
+//! level; each node also gets a random *tower* height, and on level `l` it links straight to
+//! the next node whose tower reaches that high. With heights drawn so that each level holds
+//! about half the nodes of the one below, a search starts at the top, runs along until the
+//! next step would overshoot, drops a level, and arrives in O(log n) expected steps.
+//!
+//! ```text
+//! level 2: head ------------------> 30 --------------------> end
+//! level 1: head --------> 10 -----> 30 --------> 50 -------> end
+//! level 0: head -> 5 ---> 10 -> 20 -> 30 -> 40 -> 50 -> 60 -> end
+//! ```
+//!
+//! There's no rebalancing at all: the randomness does the job rotations do in a balanced
+//! tree, which makes the invariants ("each level is a sorted sublist of the one below") about
+//! as simple as an ordered map's can get.
+//!
+//! Nodes live in a `Vec` and link to each other by index. Removing a node swaps the last
+//! one into its slot and re-points the links to it, so the storage never has holes.
+//!
+//! `Map` is the plain ordered map. `Set` additionally counts how far each link jumps, which
+//! buys O(log n) `rank` and `select`.
+
+use crate::rng::XorShift;
+use std::borrow::Borrow;
+use std::fmt;
+use std::iter::FromIterator;
+use std::marker::PhantomData;
+use std::ops::{Bound, RangeBounds};
+
+const MAX_HEIGHT: usize = 24;
+
+/// An index into `nodes`, or `None` for the end of a level. As a *position*, `None` means
+/// the head.
+type Link = Option<usize>;
+
+pub struct Map<K, V> {
+    nodes: Vec<Node<K, V>>,
+    head: [Link; MAX_HEIGHT],
+    height: usize,
+    rng: XorShift,
+}
+
+struct Node<K, V> {
+    key: K,
+    value: V,
+    next: Vec<Link>,
+}
+
+impl<K, V> Map<K, V> {
+    /// Creates an empty Map.
+    pub fn new() -> Self {
+        Map {
+            nodes: Vec::new(),
+            head: [None; MAX_HEIGHT],
+            height: 1,
+            rng: XorShift::from_entropy(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Removes everything.
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.head = [None; MAX_HEIGHT];
+        self.height = 1;
+    }
+
+    /// Where the link out of `at` on `level` goes.
+    fn link(&self, at: Link, level: usize) -> Link {
+        match at {
+            None => self.head[level],
+            Some(i) => self.nodes[i].next[level],
+        }
+    }
+
+    fn set_link(&mut self, at: Link, level: usize, to: Link) {
+        match at {
+            None => self.head[level] = to,
+            Some(i) => self.nodes[i].next[level] = to,
+        }
+    }
+
+    /// Picks a tower height: 1 with probability 1/2, 2 with 1/4, and so on.
+    fn random_height(&mut self) -> usize {
+        (self.rng.next_u64().trailing_zeros() as usize + 1).min(MAX_HEIGHT)
+    }
+
+    /// Returns the first and last entries.
+    pub fn first_key_value(&self) -> Option<(&K, &V)> {
+        self.head[0].map(|i| {
+            let node = &self.nodes[i];
+            (&node.key, &node.value)
+        })
+    }
+
+    pub fn last_key_value(&self) -> Option<(&K, &V)> {
+        // Run along each level as far as it goes, then drop down
+        let mut at = None;
+        for level in (0..self.height).rev() {
+            while let Some(next) = self.link(at, level) {
+                at = Some(next);
+            }
+        }
+        at.map(|i| {
+            let node = &self.nodes[i];
+            (&node.key, &node.value)
+        })
+    }
+
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            map: self,
+            next: self.head[0],
+            remaining: self.len(),
+        }
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.iter().map(|(k, _)| k)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.iter().map(|(_, v)| v)
+    }
+}
+
+impl<K: Ord, V> Map<K, V> {
+    /// For every level, the last node before `key` (or the head).
+    fn preds<Q>(&self, key: &Q) -> [Link; MAX_HEIGHT]
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut preds = [None; MAX_HEIGHT];
+        let mut at = None;
+        for level in (0..self.height).rev() {
+            while let Some(next) = self.link(at, level) {
+                if self.nodes[next].key.borrow() >= key {
+                    break;
+                }
+                at = Some(next);
+            }
+            preds[level] = at;
+        }
+        preds
+    }
+
+    /// The node holding `key`, if there is one.
+    fn find<Q>(&self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let preds = self.preds(key);
+        self.link(preds[0], 0)
+            .filter(|&i| self.nodes[i].key.borrow() == key)
+    }
+
+    /// Inserts a key-value pair, returning the old value if the key was already there.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let preds = self.preds(&key);
+        if let Some(i) = self.link(preds[0], 0) {
+            if self.nodes[i].key == key {
+                return Some(std::mem::replace(&mut self.nodes[i].value, value));
+            }
+        }
+
+        let height = self.random_height();
+        // Levels we're opening up for the first time start at the head, which `preds`
+        // already says for anything above the old height
+        self.height = self.height.max(height);
+
+        let index = self.nodes.len();
+        let next = (0..height)
+            .map(|level| self.link(preds[level], level))
+            .collect();
+        self.nodes.push(Node { key, value, next });
+        for (level, &pred) in preds.iter().enumerate().take(height) {
+            self.set_link(pred, level, Some(index));
+        }
+        None
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.find(key).map(|i| &self.nodes[i].value)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.find(key).map(move |i| &mut self.nodes[i].value)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.find(key).is_some()
+    }
+
+    /// Removes a key, returning its value if it was there.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.remove_entry(key).map(|(_, v)| v)
+    }
+
+    /// Removes a key, returning the stored key and value if it was there.
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let preds = self.preds(key);
+        let index = self
+            .link(preds[0], 0)
+            .filter(|&i| self.nodes[i].key.borrow() == key)?;
+
+        let height = self.nodes[index].next.len();
+        for (level, &pred) in preds.iter().enumerate().take(height) {
+            let next = self.nodes[index].next[level];
+            self.set_link(pred, level, next);
+        }
+
+        // Fill the hole with the last node, pointing its predecessors at the new slot
+        let last = self.nodes.len() - 1;
+        if index != last {
+            let preds = self.preds::<K>(&self.nodes[last].key);
+            let height = self.nodes[last].next.len();
+            for (level, &pred) in preds.iter().enumerate().take(height) {
+                self.set_link(pred, level, Some(index));
+            }
+        }
+        let node = self.nodes.swap_remove(index);
+
+        while self.height > 1 && self.head[self.height - 1].is_none() {
+            self.height -= 1;
+        }
+        Some((node.key, node.value))
+    }
+
+    /// Iterates over the entries in `range` in key order.
+    pub fn range<Q, R>(&self, range: R) -> Range<'_, K, V, Q, R>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        let mut next = match range.start_bound() {
+            Bound::Unbounded => self.head[0],
+            Bound::Included(start) | Bound::Excluded(start) => self.link(self.preds(start)[0], 0),
+        };
+        if let (Bound::Excluded(start), Some(i)) = (range.start_bound(), next) {
+            if self.nodes[i].key.borrow() == start {
+                next = self.nodes[i].next[0];
+            }
+        }
+        Range {
+            map: self,
+            next,
+            range,
+            _key: PhantomData,
+        }
+    }
+}
+
+impl<K, V> Default for Map<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord, V> FromIterator<(K, V)> for Map<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = Map::new();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K: Ord, V> Extend<(K, V)> for Map<K, V> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug> fmt::Debug for Map<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+pub struct Iter<'a, K, V> {
+    map: &'a Map<K, V>,
+    next: Link,
+    remaining: usize,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next.map(|i| {
+            let node = &self.map.nodes[i];
+            self.next = node.next[0];
+            self.remaining -= 1;
+            (&node.key, &node.value)
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+
+impl<'a, K, V> IntoIterator for &'a Map<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over a range of a `Map`. Created by [`Map::range`].
+pub struct Range<'a, K, V, Q: ?Sized, R> {
+    map: &'a Map<K, V>,
+    next: Link,
+    range: R,
+    _key: PhantomData<fn(&Q)>,
+}
+
+impl<'a, K, V, Q, R> Iterator for Range<'a, K, V, Q, R>
+where
+    K: Borrow<Q>,
+    Q: Ord + ?Sized,
+    R: RangeBounds<Q>,
+{
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = &self.map.nodes[self.next?];
+        let in_range = match self.range.end_bound() {
+            Bound::Included(end) => node.key.borrow() <= end,
+            Bound::Excluded(end) => node.key.borrow() < end,
+            Bound::Unbounded => true,
+        };
+        if in_range {
+            self.next = node.next[0];
+            Some((&node.key, &node.value))
+        } else {
+            self.next = None;
+            None
+        }
+    }
+}
+
+/// A sorted set that also answers "how many elements are smaller than x?" (`rank`) and
+/// "what's the k-th smallest?" (`select`) in O(log n).
+///
+/// Every link remembers its *span*: how many bottom-level steps it jumps over. Adding up the
+/// spans along a search path gives the position we've reached, and following spans down
+/// from the top finds a position. Links that run off the end keep the distance to the end,
+/// which is what lets a new node work out its spans when it's inserted beneath them.
+///
+/// ```text
+/// level 1: head ---(3)---> 30 ------(2)------> 50 -(1)-> end
+/// level 0: head -(1)-> 10 -(1)-> 20 -(1)-> 30 -(1)-> 40 -(1)-> 50 -(1)-> end
+/// ```
+pub struct Set<T> {
+    nodes: Vec<SetNode<T>>,
+    head: [Link; MAX_HEIGHT],
+    head_span: [usize; MAX_HEIGHT],
+    height: usize,
+    rng: XorShift,
+}
+
+struct SetNode<T> {
+    value: T,
+    next: Vec<Link>,
+    span: Vec<usize>,
+}
+
+impl<T> Set<T> {
+    /// Creates an empty Set.
+    pub fn new() -> Self {
+        Set {
+            nodes: Vec::new(),
+            head: [None; MAX_HEIGHT],
+            head_span: [0; MAX_HEIGHT],
+            height: 1,
+            rng: XorShift::from_entropy(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    fn link(&self, at: Link, level: usize) -> Link {
+        match at {
+            None => self.head[level],
+            Some(i) => self.nodes[i].next[level],
+        }
+    }
+
+    fn span(&self, at: Link, level: usize) -> usize {
+        match at {
+            None => self.head_span[level],
+            Some(i) => self.nodes[i].span[level],
+        }
+    }
+
+    fn set_link(&mut self, at: Link, level: usize, to: Link, span: usize) {
+        match at {
+            None => {
+                self.head[level] = to;
+                self.head_span[level] = span;
+            }
+            Some(i) => {
+                self.nodes[i].next[level] = to;
+                self.nodes[i].span[level] = span;
+            }
+        }
+    }
+
+    fn span_mut(&mut self, at: Link, level: usize) -> &mut usize {
+        match at {
+            None => &mut self.head_span[level],
+            Some(i) => &mut self.nodes[i].span[level],
+        }
+    }
+
+    /// Returns the `k`-th smallest element, counting from zero.
+    pub fn select(&self, k: usize) -> Option<&T> {
+        if k >= self.len() {
+            return None;
+        }
+        // Positions count from one, with the head at zero
+        let target = k + 1;
+        let mut at = None;
+        let mut position = 0;
+        for level in (0..self.height).rev() {
+            while let Some(next) = self.link(at, level) {
+                let span = self.span(at, level);
+                if position + span > target {
+                    break;
+                }
+                position += span;
+                at = Some(next);
+            }
+            if position == target {
+                break;
+            }
+        }
+        at.map(|i| &self.nodes[i].value)
+    }
+
+    pub fn first(&self) -> Option<&T> {
+        self.head[0].map(|i| &self.nodes[i].value)
+    }
+
+    pub fn last(&self) -> Option<&T> {
+        self.select(self.len().checked_sub(1)?)
+    }
+
+    pub fn iter(&self) -> SetIter<'_, T> {
+        SetIter {
+            set: self,
+            next: self.head[0],
+            remaining: self.len(),
+        }
+    }
+}
+
+impl<T: Ord> Set<T> {
+    /// For every level, the last node before `value` and its position.
+    fn preds<Q>(&self, value: &Q) -> ([Link; MAX_HEIGHT], [usize; MAX_HEIGHT])
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut preds = [None; MAX_HEIGHT];
+        let mut positions = [0; MAX_HEIGHT];
+        let mut at = None;
+        let mut position = 0;
+        for level in (0..self.height).rev() {
+            while let Some(next) = self.link(at, level) {
+                if self.nodes[next].value.borrow() >= value {
+                    break;
+                }
+                position += self.span(at, level);
+                at = Some(next);
+            }
+            preds[level] = at;
+            positions[level] = position;
+        }
+        (preds, positions)
+    }
+
+    fn find<Q>(&self, value: &Q) -> Option<usize>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let (preds, _) = self.preds(value);
+        self.link(preds[0], 0)
+            .filter(|&i| self.nodes[i].value.borrow() == value)
+    }
+
+    pub fn contains<Q>(&self, value: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.find(value).is_some()
+    }
+
+    /// Returns how many elements are smaller than `value`. If `value` is in the set, that's
+    /// its index in sorted order.
+    pub fn rank<Q>(&self, value: &Q) -> usize
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let (_, positions) = self.preds(value);
+        positions[0]
+    }
+
+    /// Adds a value. Returns false (and leaves the set alone) if it was already there.
+    pub fn insert(&mut self, value: T) -> bool {
+        let (mut preds, mut positions) = self.preds(&value);
+        if let Some(i) = self.link(preds[0], 0) {
+            if self.nodes[i].value == value {
+                return false;
+            }
+        }
+
+        let height = (self.rng.next_u64().trailing_zeros() as usize + 1).min(MAX_HEIGHT);
+        if height > self.height {
+            // New levels start out as one link from the head straight to the end
+            for level in self.height..height {
+                preds[level] = None;
+                positions[level] = 0;
+                self.head_span[level] = self.len();
+            }
+            self.height = height;
+        }
+
+        // Our position, counting the head as zero
+        let position = positions[0] + 1;
+        let index = self.nodes.len();
+        let mut next = Vec::with_capacity(height);
+        let mut span = Vec::with_capacity(height);
+        for level in 0..height {
+            let pred = preds[level];
+            next.push(self.link(pred, level));
+            // The pred's link used to jump to position `positions[level] + old span`; we now
+            // sit in between
+            span.push(positions[level] + self.span(pred, level) + 1 - position);
+            let pred_span = position - positions[level];
+            self.set_link(pred, level, Some(index), pred_span);
+        }
+        self.nodes.push(SetNode { value, next, span });
+
+        // Taller links that pass over us now have one more step to take
+        for (level, &pred) in preds.iter().enumerate().take(self.height).skip(height) {
+            *self.span_mut(pred, level) += 1;
+        }
+        true
+    }
+
+    /// Removes a value, returning it if it was there.
+    pub fn take<Q>(&mut self, value: &Q) -> Option<T>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let (preds, _) = self.preds(value);
+        let index = self
+            .link(preds[0], 0)
+            .filter(|&i| self.nodes[i].value.borrow() == value)?;
+        Some(self.unlink(&preds, index))
+    }
+
+    /// Removes a value. Returns true if it was there.
+    pub fn remove<Q>(&mut self, value: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.take(value).is_some()
+    }
+
+    /// Removes and returns the `k`-th smallest element, counting from zero.
+    pub fn remove_at(&mut self, k: usize) -> Option<T> {
+        if k >= self.len() {
+            return None;
+        }
+        // Same walk as `select`, but stopping one short on every level
+        let target = k + 1;
+        let mut preds = [None; MAX_HEIGHT];
+        let mut at = None;
+        let mut position = 0;
+        for level in (0..self.height).rev() {
+            while let Some(next) = self.link(at, level) {
+                let span = self.span(at, level);
+                if position + span >= target {
+                    break;
+                }
+                position += span;
+                at = Some(next);
+            }
+            preds[level] = at;
+        }
+        let index = self.link(preds[0], 0)?;
+        Some(self.unlink(&preds, index))
+    }
+
+    /// Unlinks node `index`, whose predecessors on every level are `preds`.
+    fn unlink(&mut self, preds: &[Link; MAX_HEIGHT], index: usize) -> T {
+        for (level, &pred) in preds.iter().enumerate().take(self.height) {
+            if self.link(pred, level) == Some(index) {
+                let next = self.nodes[index].next[level];
+                let span = self.span(pred, level) + self.nodes[index].span[level] - 1;
+                self.set_link(pred, level, next, span);
+            } else {
+                // This link jumps over us, and now has one less step to take
+                *self.span_mut(pred, level) -= 1;
+            }
+        }
+
+        // Fill the hole with the last node. Nothing moves in sorted order, so spans stay put.
+        let last = self.nodes.len() - 1;
+        if index != last {
+            let (preds, _) = self.preds::<T>(&self.nodes[last].value);
+            let height = self.nodes[last].next.len();
+            for (level, &pred) in preds.iter().enumerate().take(height) {
+                let span = self.span(pred, level);
+                self.set_link(pred, level, Some(index), span);
+            }
+        }
+        let node = self.nodes.swap_remove(index);
+
+        while self.height > 1 && self.head[self.height - 1].is_none() {
+            self.height -= 1;
+        }
+        node.value
+    }
+}
+
+impl<T> Default for Set<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Ord> FromIterator<T> for Set<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut set = Set::new();
+        set.extend(iter);
+        set
+    }
+}
+
+impl<T: Ord> Extend<T> for Set<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for value in iter {
+            self.insert(value);
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Set<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+pub struct SetIter<'a, T> {
+    set: &'a Set<T>,
+    next: Link,
+    remaining: usize,
+}
+
+impl<'a, T> Iterator for SetIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next.map(|i| {
+            let node = &self.set.nodes[i];
+            self.next = node.next[0];
+            self.remaining -= 1;
+            &node.value
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T> ExactSizeIterator for SetIter<'_, T> {}
+
+impl<'a, T> IntoIterator for &'a Set<T> {
+    type Item = &'a T;
+    type IntoIter = SetIter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Map, Set};
+    use std::ops::Bound::{Excluded, Included, Unbounded};
+
+    #[test]
+    fn basics() {
+        let mut map = Map::new();
+
+        // Check empty map behaves right
+        assert!(map.is_empty());
+        assert_eq!(map.get(&1), None);
+        assert_eq!(map.remove(&1), None);
+        assert_eq!(map.first_key_value(), None);
+        assert_eq!(map.last_key_value(), None);
+
+        // Populate map
+        assert_eq!(map.insert(2, "b"), None);
+        assert_eq!(map.insert(1, "a"), None);
+        assert_eq!(map.insert(3, "c"), None);
+        assert_eq!(map.len(), 3);
+        assert_eq!(map.get(&2), Some(&"b"));
+        assert!(map.contains_key(&3));
+
+        // Replacing hands back the old value
+        assert_eq!(map.insert(2, "B"), Some("b"));
+        if let Some(value) = map.get_mut(&3) {
+            *value = "C";
+        }
+        assert_eq!(map.first_key_value(), Some((&1, &"a")));
+        assert_eq!(map.last_key_value(), Some((&3, &"C")));
+
+        // Check removal
+        assert_eq!(map.remove(&2), Some("B"));
+        assert_eq!(map.remove(&2), None);
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.remove_entry(&1), Some((1, "a")));
+        assert_eq!(map.iter().collect::<Vec<_>>(), vec![(&3, &"C")]);
+
+        map.clear();
+        assert!(map.is_empty());
+        assert_eq!(map.iter().next(), None);
+    }
+
+    #[test]
+    fn ordered_iteration() {
+        let map: Map<_, _> = [5, 1, 9, 3, 7, 2, 8, 4, 6, 0]
+            .iter()
+            .map(|&i| (i, i * 10))
+            .collect();
+        assert_eq!(
+            map.keys().copied().collect::<Vec<_>>(),
+            (0..10).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            map.values().copied().collect::<Vec<_>>(),
+            (0..100).step_by(10).collect::<Vec<_>>()
+        );
+        assert_eq!(map.iter().len(), 10);
+        assert_eq!(
+            format!(
+                "{:?}",
+                vec![(1, 'a'), (0, 'b')].into_iter().collect::<Map<_, _>>()
+            ),
+            "{0: 'b', 1: 'a'}"
+        );
+    }
+
+    #[test]
+    fn ranges() {
+        let map: Map<_, _> = (0..20).map(|i| (i * 2, ())).collect();
+        let keys = |r: Vec<(&i32, &())>| r.into_iter().map(|(k, _)| *k).collect::<Vec<_>>();
+
+        assert_eq!(keys(map.range(4..10).collect()), vec![4, 6, 8]);
+        assert_eq!(keys(map.range(5..=10).collect()), vec![6, 8, 10]);
+        assert_eq!(keys(map.range(..3).collect()), vec![0, 2]);
+        assert_eq!(keys(map.range(35..).collect()), vec![36, 38]);
+        assert_eq!(
+            keys(map.range((Excluded(4), Included(8))).collect()),
+            vec![6, 8]
+        );
+        assert_eq!(map.range((Excluded(5), Unbounded)).count(), 17);
+        assert_eq!(map.range(100..).next(), None);
+        assert_eq!(map.range(7..7).next(), None);
+    }
+
+    #[test]
+    fn borrowed_keys() {
+        let mut map = Map::new();
+        map.insert("b".to_string(), 2);
+        map.insert("a".to_string(), 1);
+        assert_eq!(map.get("a"), Some(&1));
+        assert_eq!(map.range::<str, _>((Included("b"), Unbounded)).count(), 1);
+        assert_eq!(map.remove("b"), Some(2));
+    }
+
+    #[test]
+    fn many_inserts_and_removes() {
+        // Compare against std's BTreeMap through a long mixed workload
+        use std::collections::BTreeMap;
+        let mut map = Map::new();
+        let mut model = BTreeMap::new();
+        let mut x: u32 = 12345;
+        for _ in 0..5_000 {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let key = (x >> 16) % 500;
+            if x.is_multiple_of(3) {
+                assert_eq!(map.remove(&key), model.remove(&key));
+            } else {
+                assert_eq!(map.insert(key, x), model.insert(key, x));
+            }
+        }
+        assert_eq!(map.len(), model.len());
+        assert!(map.iter().eq(model.iter()));
+        assert!(map.range(100..200).eq(model.range(100..200)));
+    }
+
+    #[test]
+    fn set_basics() {
+        let mut set = Set::new();
+        assert!(set.is_empty());
+        assert_eq!(set.select(0), None);
+        assert_eq!(set.rank(&5), 0);
+
+        assert!(set.insert(30));
+        assert!(set.insert(10));
+        assert!(set.insert(20));
+        assert!(!set.insert(20));
+        assert_eq!(set.len(), 3);
+        assert!(set.contains(&10));
+        assert_eq!(set.iter().copied().collect::<Vec<_>>(), vec![10, 20, 30]);
+        assert_eq!(set.first(), Some(&10));
+        assert_eq!(set.last(), Some(&30));
+
+        assert!(set.remove(&20));
+        assert!(!set.remove(&20));
+        assert_eq!(set.take(&10), Some(10));
+        assert_eq!(format!("{:?}", set), "{30}");
+    }
+
+    #[test]
+    fn rank_and_select() {
+        let set: Set<_> = (0..100).map(|i| i * 10).rev().collect();
+        for k in 0..100 {
+            assert_eq!(set.select(k), Some(&(k * 10)));
+            assert_eq!(set.rank(&(k * 10)), k);
+            // Values that aren't there rank where they would go
+            assert_eq!(set.rank(&(k * 10 + 5)), k + 1);
+        }
+        assert_eq!(set.select(100), None);
+        assert_eq!(set.rank(&2_000), 100);
+    }
+
+    #[test]
+    fn remove_at() {
+        let mut set: Set<_> = (0..10).collect();
+        assert_eq!(set.remove_at(3), Some(3));
+        assert_eq!(set.remove_at(0), Some(0));
+        assert_eq!(set.remove_at(7), Some(9));
+        assert_eq!(set.remove_at(7), None);
+        assert_eq!(
+            set.iter().copied().collect::<Vec<_>>(),
+            vec![1, 2, 4, 5, 6, 7, 8]
+        );
+        // The median of what's left
+        assert_eq!(set.select(set.len() / 2), Some(&5));
+    }
+
+    #[test]
+    fn set_ranks_survive_churn() {
+        // Spans are easy to get subtly wrong, so check every rank against a sorted Vec
+        use std::collections::BTreeSet;
+        let mut set = Set::new();
+        let mut model = BTreeSet::new();
+        let mut x: u32 = 777;
+        for step in 0..3_000 {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let value = (x >> 16) % 300;
+            match x % 4 {
+                0 => assert_eq!(set.remove(&value), model.remove(&value)),
+                1 if !model.is_empty() => {
+                    let k = value as usize % model.len();
+                    let expected = *model.iter().nth(k).unwrap();
+                    model.remove(&expected);
+                    assert_eq!(set.remove_at(k), Some(expected));
+                }
+                _ => assert_eq!(set.insert(value), model.insert(value)),
+            }
+
+            if step % 100 == 0 {
+                let sorted: Vec<_> = model.iter().copied().collect();
+                assert!(set.iter().copied().eq(sorted.iter().copied()));
+                for (k, value) in sorted.iter().enumerate() {
+                    assert_eq!(set.select(k), Some(value));
+                    assert_eq!(set.rank(value), k);
+                }
+            }
+        }
+    }
+}
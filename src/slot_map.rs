@@ -0,0 +1,508 @@
+//! # Slot map
+//!
+//! A `Vec` of slots that hands out a [`Key`] for each value inserted. Lookups via a key
+//! are O(1) like `Vec` indexing, but unlike an index, a key stays attached to its value:
+//! once the value is removed, the key stops working, even after its slot is reused.
+//!
+//! Each slot keeps a *generation* that goes up every time a value leaves it, and each key
+//! remembers the generation of the slot when it was handed out. A key only matches while
+//! the two agree:
+//!
+//! ```text
+//!   insert(a) -> Key { index: 0, generation: 0 }
+//!   remove(key_a)                                   slot 0 goes to generation 1
+//!   insert(b) -> Key { index: 0, generation: 1 }    same slot, new key
+//!   get(key_a) -> None                              0 != 1, so the stale key misses
+//! ```
+//!
+//! Vacant slots form a free list through the slots themselves, so insert and remove are
+//! O(1) and the vector never has holes that nobody will refill. A slot whose generation
+//! would wrap around is retired rather than freed, so a stale key can never come back to
+//! life.
+//!
+//! This is the usual way to hold a graph ([`crate::graph`] does), a scene or an entity
+//! system where things refer to each other: store keys rather than references, and the
+//! borrow checker has nothing to object to. A [`SecondaryMap`] attaches extra data to the
+//! keys of a primary map, as a column alongside it, and is just as careful about stale
+//! keys.
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::{Index, IndexMut};
+
+/// A handle to a value in a [`SlotMap`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Key {
+    index: u32,
+    generation: u32,
+}
+
+impl Key {
+    /// Slot the key points at. Slots are reused, so this alone doesn't identify a value.
+    pub fn index(&self) -> usize {
+        self.index as usize
+    }
+}
+
+#[derive(Clone)]
+pub struct SlotMap<V> {
+    slots: Vec<Slot<V>>,
+    // Head of the free list threaded through vacant slots
+    free: Option<u32>,
+    len: usize,
+}
+
+#[derive(Clone)]
+struct Slot<V> {
+    generation: u32,
+    content: Content<V>,
+}
+
+#[derive(Clone)]
+enum Content<V> {
+    Occupied(V),
+    Vacant { next_free: Option<u32> },
+}
+
+impl<V> SlotMap<V> {
+    /// Creates an empty SlotMap.
+    pub fn new() -> Self {
+        SlotMap {
+            slots: Vec::new(),
+            free: None,
+            len: 0,
+        }
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        SlotMap {
+            slots: Vec::with_capacity(capacity),
+            free: None,
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn insert(&mut self, value: V) -> Key {
+        self.insert_with_key(|_| value)
+    }
+
+    /// Inserts the value `f` makes from its own key, for values that need to know it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the map would need more than `u32::MAX` slots.
+    pub fn insert_with_key(&mut self, f: impl FnOnce(Key) -> V) -> Key {
+        self.len += 1;
+        if let Some(index) = self.free {
+            let slot = &mut self.slots[index as usize];
+            if let Content::Vacant { next_free } = slot.content {
+                self.free = next_free;
+            }
+            let key = Key {
+                index,
+                generation: slot.generation,
+            };
+            slot.content = Content::Occupied(f(key));
+            return key;
+        }
+        let index = u32::try_from(self.slots.len()).expect("too many slots");
+        let key = Key {
+            index,
+            generation: 0,
+        };
+        self.slots.push(Slot {
+            generation: 0,
+            content: Content::Occupied(f(key)),
+        });
+        key
+    }
+
+    fn slot(&self, key: Key) -> Option<&Slot<V>> {
+        self.slots
+            .get(key.index as usize)
+            .filter(|slot| slot.generation == key.generation)
+    }
+
+    pub fn contains_key(&self, key: Key) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub fn get(&self, key: Key) -> Option<&V> {
+        match &self.slot(key)?.content {
+            Content::Occupied(value) => Some(value),
+            Content::Vacant { .. } => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, key: Key) -> Option<&mut V> {
+        let slot = self.slots.get_mut(key.index as usize)?;
+        match &mut slot.content {
+            Content::Occupied(value) if slot.generation == key.generation => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Removes the value under `key`. The key, and any copy of it, is stale from then on.
+    pub fn remove(&mut self, key: Key) -> Option<V> {
+        self.get(key)?;
+        let slot = &mut self.slots[key.index as usize];
+        // Retire the slot for good rather than let its generation wrap
+        let (generation, next_free) = match slot.generation.checked_add(1) {
+            Some(generation) => (generation, self.free.replace(key.index)),
+            None => (slot.generation, None),
+        };
+        slot.generation = generation;
+        let old = std::mem::replace(&mut slot.content, Content::Vacant { next_free });
+        self.len -= 1;
+        match old {
+            Content::Occupied(value) => Some(value),
+            Content::Vacant { .. } => unreachable!(),
+        }
+    }
+
+    /// Keeps only the values `f` returns true for.
+    pub fn retain(&mut self, mut f: impl FnMut(Key, &mut V) -> bool) {
+        let doomed: Vec<_> = self
+            .iter_mut()
+            .filter_map(|(key, value)| (!f(key, value)).then_some(key))
+            .collect();
+        for key in doomed {
+            self.remove(key);
+        }
+    }
+
+    /// Removes every value. Every key handed out so far becomes stale.
+    pub fn clear(&mut self) {
+        self.retain(|_, _| false);
+    }
+
+    /// Iterates over the keys and values, in slot order.
+    pub fn iter(&self) -> impl Iterator<Item = (Key, &V)> + '_ {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter_map(|(i, slot)| match &slot.content {
+                Content::Occupied(value) => Some((
+                    Key {
+                        index: i as u32,
+                        generation: slot.generation,
+                    },
+                    value,
+                )),
+                Content::Vacant { .. } => None,
+            })
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Key, &mut V)> + '_ {
+        let slots = self.slots.iter_mut().enumerate();
+        slots.filter_map(|(i, slot)| match &mut slot.content {
+            Content::Occupied(value) => Some((
+                Key {
+                    index: i as u32,
+                    generation: slot.generation,
+                },
+                value,
+            )),
+            Content::Vacant { .. } => None,
+        })
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = Key> + '_ {
+        self.iter().map(|(key, _)| key)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> + '_ {
+        self.iter().map(|(_, value)| value)
+    }
+}
+
+impl<V> Default for SlotMap<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V> Index<Key> for SlotMap<V> {
+    type Output = V;
+
+    fn index(&self, key: Key) -> &V {
+        self.get(key).expect("stale or foreign key")
+    }
+}
+
+impl<V> IndexMut<Key> for SlotMap<V> {
+    fn index_mut(&mut self, key: Key) -> &mut V {
+        self.get_mut(key).expect("stale or foreign key")
+    }
+}
+
+impl<V: fmt::Debug> fmt::Debug for SlotMap<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// Extra data for the keys of a [`SlotMap`], stored by slot index.
+///
+/// It can't see the primary map, so it goes by generations alone. Inserting under a key
+/// replaces whatever an older key of the same slot left behind, and a key older than
+/// what's stored is turned away.
+pub struct SecondaryMap<V> {
+    slots: Vec<Option<(u32, V)>>,
+    len: usize,
+}
+
+impl<V> SecondaryMap<V> {
+    /// Creates an empty SecondaryMap.
+    pub fn new() -> Self {
+        SecondaryMap {
+            slots: Vec::new(),
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Attaches `value` to `key`, and returns the value that was already attached to the
+    /// same key. If `key` is older than what's there, nothing changes and `value` comes
+    /// back as the error.
+    pub fn insert(&mut self, key: Key, value: V) -> Result<Option<V>, V> {
+        let index = key.index as usize;
+        if index >= self.slots.len() {
+            self.slots.resize_with(index + 1, || None);
+        }
+        match &mut self.slots[index] {
+            Some((generation, _)) if *generation > key.generation => Err(value),
+            Some((generation, old)) => {
+                let same = *generation == key.generation;
+                *generation = key.generation;
+                let old = std::mem::replace(old, value);
+                Ok(same.then_some(old))
+            }
+            slot @ None => {
+                *slot = Some((key.generation, value));
+                self.len += 1;
+                Ok(None)
+            }
+        }
+    }
+
+    pub fn get(&self, key: Key) -> Option<&V> {
+        match self.slots.get(key.index as usize)? {
+            Some((generation, value)) if *generation == key.generation => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, key: Key) -> Option<&mut V> {
+        match self.slots.get_mut(key.index as usize)? {
+            Some((generation, value)) if *generation == key.generation => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn contains_key(&self, key: Key) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub fn remove(&mut self, key: Key) -> Option<V> {
+        let slot = self.slots.get_mut(key.index as usize)?;
+        match slot {
+            Some((generation, _)) if *generation == key.generation => {
+                self.len -= 1;
+                slot.take().map(|(_, value)| value)
+            }
+            _ => None,
+        }
+    }
+
+    /// Iterates over the keys and values, in slot order.
+    pub fn iter(&self) -> impl Iterator<Item = (Key, &V)> + '_ {
+        let slots = self.slots.iter().enumerate();
+        slots.filter_map(|(i, slot)| {
+            slot.as_ref().map(|(generation, value)| {
+                let key = Key {
+                    index: i as u32,
+                    generation: *generation,
+                };
+                (key, value)
+            })
+        })
+    }
+}
+
+impl<V> Default for SecondaryMap<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V: fmt::Debug> fmt::Debug for SecondaryMap<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Key, SecondaryMap, SlotMap};
+    use std::collections::HashMap;
+
+    #[test]
+    fn basics() {
+        let mut map = SlotMap::new();
+
+        // Check empty map behaves right
+        let foreign = Key {
+            index: 0,
+            generation: 0,
+        };
+        assert_eq!(map.get(foreign), None);
+        assert_eq!(map.remove(foreign), None);
+
+        // Populate map
+        let a = map.insert("a");
+        let b = map.insert("b");
+        let c = map.insert_with_key(|key| if key.index() == 2 { "c" } else { "?" });
+        assert_eq!(map.len(), 3);
+        assert_eq!(map[c], "c");
+
+        // Check normal removal
+        assert_eq!(map.remove(b), Some("b"));
+        assert_eq!(map.remove(b), None);
+        assert!(!map.contains_key(b));
+
+        // The freed slot is reused, under a new generation
+        let d = map.insert("d");
+        assert_eq!(d.index(), b.index());
+        assert_ne!(d, b);
+        assert_eq!(map.get(b), None);
+        map[d] = "dd";
+        assert_eq!(
+            format!("{:?}", map.values().collect::<Vec<_>>()),
+            r#"["a", "dd", "c"]"#
+        );
+
+        // Check exhaustion
+        map.clear();
+        assert!(map.is_empty());
+        for key in [a, c, d] {
+            assert_eq!(map.get(key), None);
+        }
+        // Three slots were freed, so refilling them grows nothing
+        for value in ["x", "y", "z"] {
+            map.insert(value);
+        }
+        assert_eq!(map.slots.len(), 3);
+    }
+
+    #[test]
+    fn retired_slots() {
+        let mut map = SlotMap::new();
+        let key = map.insert(1);
+        // Fast-forward to the last generation a slot can have
+        map.slots[0].generation = u32::MAX;
+        let last = Key {
+            index: key.index,
+            generation: u32::MAX,
+        };
+        assert_eq!(map.remove(last), Some(1));
+
+        // The slot isn't freed, so the next insert gets a fresh one
+        let next = map.insert(2);
+        assert_eq!(next.index(), 1);
+        assert_eq!(map.get(last), None);
+        assert_eq!(map.len(), 1);
+    }
+
+    #[test]
+    fn secondary() {
+        let mut map = SlotMap::new();
+        let mut names = SecondaryMap::new();
+        let a = map.insert(10);
+        let b = map.insert(20);
+        assert_eq!(names.insert(a, "a"), Ok(None));
+        assert_eq!(names.insert(b, "b"), Ok(None));
+        assert_eq!(names.insert(b, "bb"), Ok(Some("b")));
+        assert_eq!(names.len(), 2);
+
+        // Reusing b's slot doesn't inherit its name, and the new key takes the slot over
+        map.remove(b);
+        let c = map.insert(30);
+        assert_eq!(names.get(c), None);
+        assert_eq!(names.get(b), Some(&"bb"));
+        // Replacing an older key's value isn't replacing this key's
+        assert_eq!(names.insert(c, "c"), Ok(None));
+        assert_eq!(names.get(b), None);
+        assert_eq!(names.len(), 2);
+
+        // A stale key can't write over a newer one
+        assert_eq!(names.insert(b, "stale"), Err("stale"));
+        assert_eq!(names.get(c), Some(&"c"));
+        assert_eq!(names.insert(c, "c2"), Ok(Some("c")));
+        *names.get_mut(c).unwrap() = "cc";
+
+        assert_eq!(names.remove(b), None);
+        assert_eq!(names.remove(a), Some("a"));
+        assert_eq!(
+            format!("{:?}", names),
+            r#"{Key { index: 1, generation: 1 }: "cc"}"#
+        );
+        let joined: Vec<_> = map
+            .iter()
+            .filter_map(|(k, v)| Some((*v, *names.get(k)?)))
+            .collect();
+        assert_eq!(joined, vec![(30, "cc")]);
+    }
+
+    #[test]
+    fn against_hash_map() {
+        let mut map = SlotMap::new();
+        let mut model = HashMap::new();
+        let mut dead = Vec::new();
+        let mut keys: Vec<Key> = Vec::new();
+        let mut x: u32 = 1;
+        for i in 0..10_000 {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            match x >> 30 {
+                0 if !keys.is_empty() => {
+                    let key = keys.swap_remove((x >> 8) as usize % keys.len());
+                    assert_eq!(map.remove(key), model.remove(&key));
+                    dead.push(key);
+                }
+                1 if !dead.is_empty() => {
+                    // Stale keys never find anything, however their slots were reused
+                    let key = dead[(x >> 8) as usize % dead.len()];
+                    assert_eq!(map.get(key), None);
+                    assert_eq!(map.get_mut(key), None);
+                }
+                _ => {
+                    let key = map.insert(i);
+                    assert!(model.insert(key, i).is_none());
+                    keys.push(key);
+                }
+            }
+            assert_eq!(map.len(), model.len());
+        }
+        for (key, value) in map.iter() {
+            assert_eq!(model[&key], *value);
+        }
+        assert_eq!(map.iter().count(), model.len());
+        map.retain(|_, v| *v % 2 == 0);
+        assert!(map.values().all(|v| v % 2 == 0));
+    }
+}
@@ -0,0 +1,536 @@
+//! # Small list
+//!
+//! A growable sequence that keeps up to `N` elements inline, inside the value itself, and
+//! only moves them to the heap once there are more than that:
+//!
+//! ```text
+//!   SmallList<u8, 4>                      after pushing a fifth
+//!
+//!   Inline { len: 3,                      Heap(Vec) --> [ a  b  c  d  e  .  .  . ]
+//!            [ a  b  c  . ] }
+//! ```
+//!
+//! Most lists in most programs are short: the children of a tree node, the arguments of a
+//! call, the matches of a rare pattern. For those, skipping the allocation saves
+//! more than the extra branch on every access costs. The list *spills* to a `Vec` the
+//! moment a push would overflow the inline buffer, and stays there until
+//! [`SmallList::shrink_to_fit`] brings it back.
+//!
+//! It derefs to a slice either way, so indexing, slicing and iteration all come from
+//! `[T]`. The inline buffer is `MaybeUninit` with its live prefix tracked by hand, so run
+//! the tests under Miri too:
+//!
+//! ```text
+//! cargo +nightly miri test small_list
+//! ```
+
+use std::fmt;
+use std::iter::FromIterator;
+use std::mem::{self, MaybeUninit};
+use std::ops::{Deref, DerefMut};
+use std::ptr;
+use std::slice;
+
+pub struct SmallList<T, const N: usize> {
+    storage: Storage<T, N>,
+}
+
+enum Storage<T, const N: usize> {
+    // The first `len` slots are initialized
+    Inline {
+        buf: [MaybeUninit<T>; N],
+        len: usize,
+    },
+    Heap(Vec<T>),
+}
+
+impl<T, const N: usize> SmallList<T, N> {
+    /// Creates an empty SmallList.
+    pub const fn new() -> Self {
+        SmallList {
+            storage: Storage::Inline {
+                buf: [const { MaybeUninit::uninit() }; N],
+                len: 0,
+            },
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.storage {
+            Storage::Inline { len, .. } => *len,
+            Storage::Heap(vec) => vec.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether the elements have moved to the heap.
+    pub fn spilled(&self) -> bool {
+        matches!(self.storage, Storage::Heap(_))
+    }
+
+    pub fn capacity(&self) -> usize {
+        match &self.storage {
+            Storage::Inline { .. } => N,
+            Storage::Heap(vec) => vec.capacity(),
+        }
+    }
+
+    pub fn push(&mut self, elem: T) {
+        match &mut self.storage {
+            Storage::Inline { buf, len } if *len < N => {
+                buf[*len] = MaybeUninit::new(elem);
+                *len += 1;
+            }
+            Storage::Inline { .. } => self.spill(N + 1).push(elem),
+            Storage::Heap(vec) => vec.push(elem),
+        }
+    }
+
+    /// Moves the inline elements into a `Vec` with room for `capacity`, and returns it.
+    fn spill(&mut self, capacity: usize) -> &mut Vec<T> {
+        if let Storage::Inline { buf, len } = &mut self.storage {
+            let mut vec = Vec::with_capacity(capacity.max(*len));
+            // Bitwise moves: zeroing `len` first means the old slots are forgotten
+            let moved = mem::replace(len, 0);
+            unsafe {
+                ptr::copy_nonoverlapping(buf.as_ptr() as *const T, vec.as_mut_ptr(), moved);
+                vec.set_len(moved);
+            }
+            self.storage = Storage::Heap(vec);
+        }
+        match &mut self.storage {
+            Storage::Heap(vec) => vec,
+            Storage::Inline { .. } => unreachable!(),
+        }
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        match &mut self.storage {
+            Storage::Inline { buf, len } => {
+                if *len == 0 {
+                    return None;
+                }
+                *len -= 1;
+                Some(unsafe { buf[*len].assume_init_read() })
+            }
+            Storage::Heap(vec) => vec.pop(),
+        }
+    }
+
+    /// Inserts an element at `index`, shifting everything after it along.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index > len`.
+    pub fn insert(&mut self, index: usize, elem: T) {
+        let len = self.len();
+        assert!(index <= len, "insertion index out of bounds");
+        match &mut self.storage {
+            Storage::Inline { buf, len } if *len < N => unsafe {
+                let at = buf.as_mut_ptr().add(index);
+                ptr::copy(at, at.add(1), *len - index);
+                ptr::write(at, MaybeUninit::new(elem));
+                *len += 1;
+            },
+            Storage::Inline { .. } => self.spill(N + 1).insert(index, elem),
+            Storage::Heap(vec) => vec.insert(index, elem),
+        }
+    }
+
+    /// Removes and returns the element at `index`, shifting everything after it back.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index >= len`.
+    pub fn remove(&mut self, index: usize) -> T {
+        let len = self.len();
+        assert!(index < len, "removal index out of bounds");
+        match &mut self.storage {
+            Storage::Inline { buf, len } => unsafe {
+                let at = buf.as_mut_ptr().add(index);
+                let elem = ptr::read(at).assume_init();
+                ptr::copy(at.add(1), at, *len - index - 1);
+                *len -= 1;
+                elem
+            },
+            Storage::Heap(vec) => vec.remove(index),
+        }
+    }
+
+    /// Drops every element past the first `len`.
+    pub fn truncate(&mut self, len: usize) {
+        while self.len() > len {
+            self.pop();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    /// Moves the elements back inline if they fit, or else shrinks the heap buffer.
+    pub fn shrink_to_fit(&mut self) {
+        let vec = match &mut self.storage {
+            Storage::Heap(vec) if vec.len() <= N => mem::take(vec),
+            Storage::Heap(vec) => return vec.shrink_to_fit(),
+            Storage::Inline { .. } => return,
+        };
+        let mut inline = SmallList::new();
+        for elem in vec {
+            inline.push(elem);
+        }
+        *self = inline;
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        match &self.storage {
+            Storage::Inline { buf, len } => unsafe {
+                slice::from_raw_parts(buf.as_ptr() as *const T, *len)
+            },
+            Storage::Heap(vec) => vec,
+        }
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        match &mut self.storage {
+            Storage::Inline { buf, len } => unsafe {
+                slice::from_raw_parts_mut(buf.as_mut_ptr() as *mut T, *len)
+            },
+            Storage::Heap(vec) => vec,
+        }
+    }
+}
+
+impl<T, const N: usize> Drop for SmallList<T, N> {
+    fn drop(&mut self) {
+        // The Vec drops itself
+        if let Storage::Inline { .. } = self.storage {
+            unsafe { ptr::drop_in_place(self.as_mut_slice()) };
+        }
+    }
+}
+
+impl<T, const N: usize> Deref for SmallList<T, N> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        self.as_slice()
+    }
+}
+
+impl<T, const N: usize> DerefMut for SmallList<T, N> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        self.as_mut_slice()
+    }
+}
+
+impl<T, const N: usize> Default for SmallList<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Clone, const N: usize> Clone for SmallList<T, N> {
+    fn clone(&self) -> Self {
+        self.iter().cloned().collect()
+    }
+}
+
+impl<T, const N: usize> Extend<T> for SmallList<T, N> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        let wanted = self.len() + iter.size_hint().0;
+        if wanted > N {
+            self.spill(wanted);
+        }
+        for elem in iter {
+            self.push(elem);
+        }
+    }
+}
+
+impl<T, const N: usize> FromIterator<T> for SmallList<T, N> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut list = SmallList::new();
+        list.extend(iter);
+        list
+    }
+}
+
+impl<T: PartialEq, const N: usize> PartialEq for SmallList<T, N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+impl<T: Eq, const N: usize> Eq for SmallList<T, N> {}
+
+impl<T: fmt::Debug, const N: usize> fmt::Debug for SmallList<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<'a, T, const N: usize> IntoIterator for &'a SmallList<T, N> {
+    type Item = &'a T;
+    type IntoIter = slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, T, const N: usize> IntoIterator for &'a mut SmallList<T, N> {
+    type Item = &'a mut T;
+    type IntoIter = slice::IterMut<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+/// Owning iterator over a [`SmallList`]. An inline list stays inline while it's drained.
+pub struct IntoIter<T, const N: usize> {
+    inner: IntoIterInner<T, N>,
+}
+
+enum IntoIterInner<T, const N: usize> {
+    // Slots `front..back` are still initialized
+    Inline {
+        buf: [MaybeUninit<T>; N],
+        front: usize,
+        back: usize,
+    },
+    Heap(std::vec::IntoIter<T>),
+}
+
+impl<T, const N: usize> IntoIterator for SmallList<T, N> {
+    type Item = T;
+    type IntoIter = IntoIter<T, N>;
+
+    fn into_iter(mut self) -> Self::IntoIter {
+        let storage = mem::replace(&mut self.storage, Storage::Heap(Vec::new()));
+        let inner = match storage {
+            Storage::Inline { buf, len } => IntoIterInner::Inline {
+                buf,
+                front: 0,
+                back: len,
+            },
+            Storage::Heap(vec) => IntoIterInner::Heap(vec.into_iter()),
+        };
+        IntoIter { inner }
+    }
+}
+
+impl<T, const N: usize> Iterator for IntoIter<T, N> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        match &mut self.inner {
+            IntoIterInner::Inline { buf, front, back } => {
+                if front == back {
+                    return None;
+                }
+                *front += 1;
+                Some(unsafe { buf[*front - 1].assume_init_read() })
+            }
+            IntoIterInner::Heap(iter) => iter.next(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = match &self.inner {
+            IntoIterInner::Inline { front, back, .. } => back - front,
+            IntoIterInner::Heap(iter) => iter.len(),
+        };
+        (len, Some(len))
+    }
+}
+
+impl<T, const N: usize> DoubleEndedIterator for IntoIter<T, N> {
+    fn next_back(&mut self) -> Option<T> {
+        match &mut self.inner {
+            IntoIterInner::Inline { buf, front, back } => {
+                if front == back {
+                    return None;
+                }
+                *back -= 1;
+                Some(unsafe { buf[*back].assume_init_read() })
+            }
+            IntoIterInner::Heap(iter) => iter.next_back(),
+        }
+    }
+}
+
+impl<T, const N: usize> ExactSizeIterator for IntoIter<T, N> {}
+
+impl<T, const N: usize> Drop for IntoIter<T, N> {
+    fn drop(&mut self) {
+        if let IntoIterInner::Inline { buf, front, back } = &mut self.inner {
+            for slot in &mut buf[*front..*back] {
+                unsafe { slot.assume_init_drop() };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SmallList;
+    use std::rc::Rc;
+
+    #[test]
+    fn basics() {
+        let mut list: SmallList<i32, 4> = SmallList::new();
+
+        // Check empty list behaves right
+        assert_eq!(list.pop(), None);
+        assert_eq!(list.first(), None);
+        assert_eq!(list.capacity(), 4);
+
+        // Populate list
+        list.push(1);
+        list.push(3);
+        list.insert(1, 2);
+        list.insert(0, 0);
+        assert_eq!(list.as_slice(), &[0, 1, 2, 3]);
+        assert!(!list.spilled());
+
+        // Check normal removal
+        assert_eq!(list.pop(), Some(3));
+        assert_eq!(list.remove(0), 0);
+        assert_eq!(list.as_slice(), &[1, 2]);
+
+        // Push some more just to make sure nothing's corrupted
+        list.push(4);
+        list[0] = 10;
+        list.sort_unstable();
+        assert_eq!(format!("{:?}", list), "[2, 4, 10]");
+        for elem in &mut list {
+            *elem += 1;
+        }
+        assert!(list.iter().eq(&[3, 5, 11]));
+
+        // Check exhaustion
+        assert_eq!(list.pop(), Some(11));
+        assert_eq!(list.pop(), Some(5));
+        assert_eq!(list.pop(), Some(3));
+        assert_eq!(list.pop(), None);
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    fn spill() {
+        let mut list: SmallList<u32, 3> = (0..3).collect();
+        assert!(!list.spilled());
+        assert_eq!(list.capacity(), 3);
+
+        // The push that overflows moves everything to the heap, in order
+        list.push(3);
+        assert!(list.spilled());
+        assert!(list.capacity() >= 4);
+        assert_eq!(list.as_slice(), &[0, 1, 2, 3]);
+
+        // Shrinking below N doesn't bring it back on its own
+        list.pop();
+        list.pop();
+        assert!(list.spilled());
+        list.shrink_to_fit();
+        assert!(!list.spilled());
+        assert_eq!(list.as_slice(), &[0, 1]);
+
+        // Inserting into a full inline list spills too
+        list.push(2);
+        list.insert(0, 9);
+        assert!(list.spilled());
+        assert_eq!(list.as_slice(), &[9, 0, 1, 2]);
+
+        // And extending past N spills up front
+        let mut other: SmallList<u32, 3> = SmallList::new();
+        other.extend(0..10);
+        assert!(other.spilled());
+        assert_eq!(other.len(), 10);
+        other.truncate(3);
+        other.shrink_to_fit();
+        assert!(!other.spilled());
+        assert_eq!(other.as_slice(), &[0, 1, 2]);
+    }
+
+    #[test]
+    fn no_inline_room() {
+        let mut list: SmallList<u32, 0> = SmallList::new();
+        assert!(!list.spilled());
+        list.push(1);
+        assert!(list.spilled());
+        assert_eq!(list.pop(), Some(1));
+        list.shrink_to_fit();
+        assert!(!list.spilled());
+    }
+
+    #[test]
+    fn into_iter() {
+        let inline: SmallList<String, 4> = ["a", "b", "c"].iter().map(|s| s.to_string()).collect();
+        let mut iter = inline.into_iter();
+        assert_eq!(iter.len(), 3);
+        assert_eq!(iter.next_back().as_deref(), Some("c"));
+        assert_eq!(iter.next().as_deref(), Some("a"));
+        // "b" is dropped with the iterator
+
+        let spilled: SmallList<u32, 2> = (0..5).collect();
+        assert!(spilled.into_iter().rev().eq((0..5).rev()));
+    }
+
+    #[test]
+    fn drops_what_it_holds() {
+        let token = Rc::new(());
+        {
+            let inline: SmallList<_, 4> = (0..3).map(|_| token.clone()).collect();
+            let mut spilled: SmallList<_, 4> = inline.clone();
+            spilled.extend((0..3).map(|_| token.clone()));
+            assert_eq!(Rc::strong_count(&token), 10);
+
+            let mut iter = inline.into_iter();
+            iter.next();
+            assert_eq!(Rc::strong_count(&token), 9);
+            drop(iter);
+            assert_eq!(Rc::strong_count(&token), 7);
+            spilled.remove(2);
+            assert_eq!(Rc::strong_count(&token), 6);
+        }
+        assert_eq!(Rc::strong_count(&token), 1);
+    }
+
+    #[test]
+    fn against_vec() {
+        let mut list: SmallList<u32, 8> = SmallList::new();
+        let mut model = Vec::new();
+        let mut x: u32 = 1;
+        for _ in 0..10_000 {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            match x >> 29 {
+                0 | 1 => assert_eq!(list.pop(), model.pop()),
+                2 if !model.is_empty() => {
+                    let at = (x >> 8) as usize % model.len();
+                    assert_eq!(list.remove(at), model.remove(at));
+                }
+                3 => {
+                    let at = (x >> 8) as usize % (model.len() + 1);
+                    list.insert(at, x);
+                    model.insert(at, x);
+                }
+                4 => {
+                    list.shrink_to_fit();
+                    assert_eq!(list.spilled(), model.len() > 8);
+                }
+                _ => {
+                    list.push(x);
+                    model.push(x);
+                }
+            }
+            assert_eq!(list.as_slice(), &model[..]);
+        }
+    }
+}
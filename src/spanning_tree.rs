@@ -0,0 +1,195 @@
+//! # Minimum spanning tree
+//!
+//! The cheapest set of edges that connects every node of an undirected graph, two ways.
+//! Both rest on the *cut property*: whichever way the nodes are split in two, the cheapest
+//! edge across the split belongs to some minimum spanning tree.
+//!
+//! Kruskal's algorithm (1956) goes through the edges cheapest first and takes each one
+//! that joins two parts not yet joined, which a [`UnionFind`] over the nodes answers in
+//! effectively O(1). Sorting the edges dominates: O(E log E).
+//!
+//! Prim's algorithm (Jarník, 1930; Prim, 1957) grows one tree from a start, each step
+//! taking the cheapest edge from a node in the tree to a node outside it. The candidate
+//! edges wait in a [`BinaryHeap`], and an edge whose far end has joined the tree by the
+//! time it comes out is just dropped: O(E log E) again.
+//!
+//! ```text
+//!       7                  chosen:  a-c 1, c-d 2, d-b 3
+//!   a ----- b              total:   6
+//!   |       |
+//! 1 |       | 3
+//!   |   2   |
+//!   c ----- d
+//! ```
+//!
+//! A graph in several pieces gets a spanning tree for each piece, a minimum spanning
+//! *forest*, from either. Costs come from a closure over the edges, as for
+//! [`crate::shortest_path`], but here they may be negative.
+
+use crate::bit_set::BitSet;
+use crate::graph::{EdgeId, EdgeRef, NodeId, UnGraph};
+use crate::heap::BinaryHeap;
+use crate::shortest_path::Cost;
+use crate::union_find::UnionFind;
+use std::cmp::Reverse;
+
+/// Finds a minimum spanning forest with Kruskal's algorithm. Returns its edges, cheapest
+/// first, and their total cost.
+pub fn kruskal<N, E, C: Cost>(
+    graph: &UnGraph<N, E>,
+    cost: impl Fn(EdgeRef<'_, E>) -> C,
+) -> (Vec<EdgeId>, C) {
+    let mut edges: Vec<_> = graph.edges().map(|edge| (cost(edge), edge)).collect();
+    edges.sort_by_key(|&(cost, _)| cost);
+    let mut parts = UnionFind::new(graph.node_bound());
+    let mut chosen = Vec::new();
+    let mut total = C::zero();
+    for (cost, edge) in edges {
+        if parts.union(edge.source.index(), edge.target.index()) {
+            chosen.push(edge.id);
+            total = total + cost;
+        }
+    }
+    (chosen, total)
+}
+
+/// Finds a minimum spanning forest with Prim's algorithm, growing a tree from each node
+/// not yet in one. Returns its edges, in the order they joined, and their total cost.
+pub fn prim<N, E, C: Cost>(
+    graph: &UnGraph<N, E>,
+    cost: impl Fn(EdgeRef<'_, E>) -> C,
+) -> (Vec<EdgeId>, C) {
+    let mut in_tree = BitSet::with_capacity(graph.node_bound());
+    // Smallest first, by way of Reverse on the max-heap
+    let mut heap = BinaryHeap::new();
+    let mut chosen = Vec::new();
+    let mut total = C::zero();
+    let join = |node: NodeId, in_tree: &mut BitSet, heap: &mut BinaryHeap<_>| {
+        in_tree.insert(node.index());
+        for edge in graph.edges_of(node) {
+            if !in_tree.contains(edge.target.index()) {
+                heap.push(Reverse((cost(edge), edge.id, edge.target)));
+            }
+        }
+    };
+    for start in graph.node_ids() {
+        if in_tree.contains(start.index()) {
+            continue;
+        }
+        join(start, &mut in_tree, &mut heap);
+        while let Some(Reverse((cost, edge, target))) = heap.pop() {
+            if in_tree.contains(target.index()) {
+                continue;
+            }
+            chosen.push(edge);
+            total = total + cost;
+            join(target, &mut in_tree, &mut heap);
+        }
+    }
+    (chosen, total)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{kruskal, prim};
+    use crate::graph::{EdgeId, UnGraph};
+    use crate::union_find::UnionFind;
+
+    /// Checks the edges make a spanning forest: no cycles, and as many edges as it takes
+    /// to join everything that can be joined.
+    fn check_forest(graph: &UnGraph<(), i32>, edges: &[EdgeId]) {
+        let mut chosen = UnionFind::new(graph.node_bound());
+        for &edge in edges {
+            let (a, b) = graph.endpoints(edge).unwrap();
+            assert!(chosen.union(a.index(), b.index()));
+        }
+        let mut all = UnionFind::new(graph.node_bound());
+        for edge in graph.edges() {
+            all.union(edge.source.index(), edge.target.index());
+        }
+        assert_eq!(chosen.set_count(), all.set_count());
+    }
+
+    #[test]
+    fn basics() {
+        // The graph in the module docs
+        let mut graph = UnGraph::new();
+        let [a, b, c, d] = [(); 4].map(|_| graph.add_node(()));
+        graph.add_edge(a, b, 7);
+        let ac = graph.add_edge(a, c, 1);
+        let cd = graph.add_edge(c, d, 2);
+        let db = graph.add_edge(d, b, 3);
+        assert_eq!(kruskal(&graph, |e| *e.weight), (vec![ac, cd, db], 6));
+        assert_eq!(prim(&graph, |e| *e.weight), (vec![ac, cd, db], 6));
+
+        // Nothing to join
+        let empty: UnGraph<(), i32> = UnGraph::new();
+        assert_eq!(kruskal(&empty, |e| *e.weight), (vec![], 0));
+        assert_eq!(prim(&empty, |e| *e.weight), (vec![], 0));
+    }
+
+    #[test]
+    fn forest() {
+        // Two pieces, a loop and parallel edges, none of which trip it up
+        let mut graph = UnGraph::new();
+        let [a, b, c, d, e] = [(); 5].map(|_| graph.add_node(()));
+        graph.add_edge(a, a, -5);
+        graph.add_edge(a, b, 4);
+        let ab = graph.add_edge(a, b, 2);
+        let bc = graph.add_edge(b, c, -1);
+        let de = graph.add_edge(d, e, 9);
+        let (mut edges, total) = kruskal(&graph, |e| *e.weight);
+        assert_eq!(total, 10);
+        check_forest(&graph, &edges);
+        edges.sort();
+        let mut expected = vec![ab, bc, de];
+        expected.sort();
+        assert_eq!(edges, expected);
+        let (edges, total) = prim(&graph, |e| *e.weight);
+        assert_eq!(total, 10);
+        check_forest(&graph, &edges);
+    }
+
+    #[test]
+    fn against_brute_force() {
+        // Small random graphs, against the cheapest of every forest their edges make
+        let mut x: u32 = 1;
+        let rounds = if cfg!(miri) { 3 } else { 40 };
+        for _ in 0..rounds {
+            let mut graph = UnGraph::new();
+            let nodes: Vec<_> = (0..6).map(|_| graph.add_node(())).collect();
+            let mut edges = Vec::new();
+            for _ in 0..10 {
+                x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                let (a, b) = (nodes[(x >> 8) as usize % 6], nodes[(x >> 16) as usize % 6]);
+                edges.push(graph.add_edge(a, b, (x >> 24) as i32 % 10 - 3));
+            }
+
+            // The forests with the most edges are the spanning ones
+            let mut best = None;
+            for subset in 0..1u32 << edges.len() {
+                let mut parts = UnionFind::new(6);
+                let mut total = 0;
+                let mut forest = true;
+                for (i, &edge) in edges.iter().enumerate() {
+                    if subset & (1 << i) != 0 {
+                        let (a, b) = graph.endpoints(edge).unwrap();
+                        forest &= parts.union(a.index(), b.index());
+                        total += graph[edge];
+                    }
+                }
+                if forest {
+                    let key = (subset.count_ones(), -total);
+                    best = best.max(Some(key));
+                }
+            }
+            let expected = -best.unwrap().1;
+
+            for (chosen, total) in [kruskal(&graph, |e| *e.weight), prim(&graph, |e| *e.weight)] {
+                assert_eq!(total, expected);
+                assert_eq!(chosen.iter().map(|&e| graph[e]).sum::<i32>(), total);
+                check_forest(&graph, &chosen);
+            }
+        }
+    }
+}
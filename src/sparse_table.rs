@@ -0,0 +1,182 @@
+//! # Sparse table
+//!
+//! Answers range queries over data that never changes, in O(1) each after O(n log n)
+//! preprocessing. Level `k` of the table holds the answer for every window of length
+//! `2^k`, and each level is built from the one below it by combining two halves:
+//!
+//! ```text
+//! data     :  5  2  7  1  8  3        (range min)
+//! level 0  :  5  2  7  1  8  3        windows of 1
+//! level 1  :  2  2  1  1  3           windows of 2
+//! level 2  :  1  1  1                 windows of 4
+//!
+//! min(1..6) = min(level2[1], level2[2]) = min(1, 1)    two windows of 4 cover 1..6
+//! ```
+//!
+//! A query over any range takes the largest power of two that fits in it, and combines
+//! the window starting at the front of the range with the window ending at its back.
+//! The two windows usually overlap, which is fine for *idempotent* operations like min,
+//! max, gcd, and bitwise and/or, where counting an element twice doesn't change anything.
+//! It would be wrong for sums. Those, or data that changes, call for a segment tree or a
+//! Fenwick tree instead.
+
+use std::cmp;
+use std::ops::Range;
+
+pub struct SparseTable<T, F = fn(&T, &T) -> T> {
+    // levels[k][i] combines data[i..i + 2^k]
+    levels: Vec<Vec<T>>,
+    op: F,
+}
+
+impl<T, F> SparseTable<T, F>
+where
+    F: Fn(&T, &T) -> T,
+{
+    /// Builds a table over `data` for the operation `op`, which must be associative and
+    /// idempotent (`op(x, x) == x`), or queries will give wrong answers.
+    pub fn new(data: &[T], op: F) -> Self
+    where
+        T: Clone,
+    {
+        let mut levels = vec![data.to_vec()];
+        let mut width = 1;
+        while 2 * width <= data.len() {
+            let below = levels.last().unwrap();
+            let level = (0..=data.len() - 2 * width)
+                .map(|i| op(&below[i], &below[i + width]))
+                .collect();
+            levels.push(level);
+            width *= 2;
+        }
+        SparseTable { levels, op }
+    }
+
+    pub fn len(&self) -> usize {
+        self.levels[0].len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.levels[0].is_empty()
+    }
+
+    /// Combines every element in `range`, or returns `None` if the range is empty.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range goes past the end of the data.
+    pub fn query(&self, range: Range<usize>) -> Option<T> {
+        assert!(
+            range.end <= self.len(),
+            "range end {} out of bounds for length {}",
+            range.end,
+            self.len()
+        );
+        if range.start >= range.end {
+            return None;
+        }
+        // The largest power of two that fits, and a window from each end
+        let k = (range.end - range.start).ilog2() as usize;
+        let level = &self.levels[k];
+        Some((self.op)(&level[range.start], &level[range.end - (1 << k)]))
+    }
+}
+
+impl<T: Ord + Clone> SparseTable<T> {
+    /// Builds a table for range-minimum queries.
+    pub fn min(data: &[T]) -> Self {
+        SparseTable::new(data, |a, b| cmp::min(a, b).clone())
+    }
+
+    /// Builds a table for range-maximum queries.
+    pub fn max(data: &[T]) -> Self {
+        SparseTable::new(data, |a, b| cmp::max(a, b).clone())
+    }
+}
+
+impl SparseTable<u64> {
+    /// Builds a table for range-gcd queries.
+    pub fn gcd(data: &[u64]) -> Self {
+        SparseTable::new(data, |a, b| gcd(*a, *b))
+    }
+}
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        let r = a % b;
+        a = b;
+        b = r;
+    }
+    a
+}
+
+#[cfg(test)]
+mod test {
+    use super::SparseTable;
+
+    #[test]
+    fn basics() {
+        let table = SparseTable::min(&[5, 2, 7, 1, 8, 3]);
+        assert_eq!(table.len(), 6);
+        assert_eq!(table.query(0..6), Some(1));
+        assert_eq!(table.query(1..6), Some(1));
+        assert_eq!(table.query(0..2), Some(2));
+        assert_eq!(table.query(4..6), Some(3));
+        assert_eq!(table.query(2..3), Some(7));
+        assert_eq!(table.query(3..3), None);
+
+        // Check empty table behaves right
+        let empty: SparseTable<i32> = SparseTable::min(&[]);
+        assert!(empty.is_empty());
+        assert_eq!(empty.query(0..0), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "out of bounds")]
+    fn query_past_the_end() {
+        SparseTable::max(&[1, 2, 3]).query(1..4);
+    }
+
+    #[test]
+    fn against_brute_force() {
+        let mut x: u64 = 11;
+        let data: Vec<u64> = (0..70)
+            .map(|_| {
+                x = x.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1);
+                // Small multiples of a few primes, so the gcds are interesting
+                [2, 3, 4, 6, 10, 12, 15, 30][(x >> 61) as usize] * ((x >> 40) % 4 + 1)
+            })
+            .collect();
+
+        let min = SparseTable::min(&data);
+        let max = SparseTable::max(&data);
+        let gcd = SparseTable::gcd(&data);
+        let or = SparseTable::new(&data, |a, b| a | b);
+        for start in 0..data.len() {
+            for end in start + 1..=data.len() {
+                let window = &data[start..end];
+                assert_eq!(min.query(start..end), window.iter().min().copied());
+                assert_eq!(max.query(start..end), window.iter().max().copied());
+                assert_eq!(
+                    gcd.query(start..end),
+                    window.iter().copied().reduce(super::gcd)
+                );
+                assert_eq!(
+                    or.query(start..end),
+                    window.iter().copied().reduce(|a, b| a | b)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn non_copy_values() {
+        let words: Vec<String> = ["pear", "apple", "fig", "kiwi"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let table = SparseTable::min(&words);
+        assert_eq!(table.query(0..4).as_deref(), Some("apple"));
+        assert_eq!(table.query(2..4).as_deref(), Some("fig"));
+    }
+}
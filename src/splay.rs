@@ -0,0 +1,620 @@
+//! # Splay tree map
+//!
+//! A binary search tree that balances itself by *access* instead of by bookkeeping (Sleator
+//! and Tarjan, 1985). Every operation *splays* the key it touches: a series of rotations
+//! brings it to the root. Keys used often stay near the top, and any sequence of m
+//! operations costs O(m log n) in total, even though a single one can take O(n).
+//!
+//! Lookups restructure the tree as well, so [`Map::get`] takes `&mut self`.
+//!
+//! The splay here is the *top-down* version. It walks down from the root once, two levels
+//! at a time. A zig-zig step (both levels going the same way) rotates first, which halves
+//! the depth of the path. The nodes it passes get hung on a "lesser" and a "greater" side,
+//! and at the end those sides become the left and right subtrees of the target.
+//!
+//! ```text
+//! splay(1) on a zig-zig path:
+//!
+//!         5                             1
+//!        /                               \
+//!       4                                 4
+//!      /            -->                  / \
+//!     3                                 2   5
+//!    /                                   \
+//!   2                                     3
+//!  /
+//! 1
+//! ```
+//!
+//! Having the key at the root makes two operations trivial. [`Map::split`] cuts off the
+//! root's right subtree. [`Map::join`] splays the largest key of the left map to its root,
+//! where it has no right child, and hangs the right map there. Both are cheap, amortized
+//! like everything else.
+//!
+//! Every node stores the size of its subtree, so `split` knows how many entries each half
+//! got without counting them.
+
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::fmt;
+use std::iter::FromIterator;
+use std::mem;
+
+pub struct Map<K, V> {
+    root: Link<K, V>,
+}
+
+type Link<K, V> = Option<Box<Node<K, V>>>;
+
+struct Node<K, V> {
+    key: K,
+    value: V,
+    size: usize,
+    left: Link<K, V>,
+    right: Link<K, V>,
+}
+
+fn size<K, V>(link: &Link<K, V>) -> usize {
+    link.as_ref().map_or(0, |node| node.size)
+}
+
+impl<K, V> Node<K, V> {
+    fn update_size(&mut self) {
+        self.size = 1 + size(&self.left) + size(&self.right);
+    }
+}
+
+/// Splays the node that `target` points at to the root and returns it. `target` compares
+/// the sought key against a node's key. If the key isn't in the tree, the last node on the
+/// search path ends up at the root instead, which is its predecessor or successor.
+fn splay<K, V, F>(mut t: Box<Node<K, V>>, target: F) -> Box<Node<K, V>>
+where
+    F: Fn(&K) -> Ordering,
+{
+    // Nodes known to be smaller than the target, in increasing order, and nodes known to be
+    // larger, in decreasing order
+    let mut lesser = Vec::new();
+    let mut greater = Vec::new();
+
+    loop {
+        match target(&t.key) {
+            Ordering::Less => {
+                let mut left = match t.left.take() {
+                    Some(left) => left,
+                    None => break,
+                };
+                if target(&left.key) == Ordering::Less {
+                    // Zig-zig: rotate right before going on
+                    t.left = left.right.take();
+                    t.update_size();
+                    left.right = Some(t);
+                    t = left;
+                    left = match t.left.take() {
+                        Some(left) => left,
+                        None => break,
+                    };
+                }
+                greater.push(t);
+                t = left;
+            }
+            Ordering::Greater => {
+                let mut right = match t.right.take() {
+                    Some(right) => right,
+                    None => break,
+                };
+                if target(&right.key) == Ordering::Greater {
+                    // Zig-zig: rotate left before going on
+                    t.right = right.left.take();
+                    t.update_size();
+                    right.left = Some(t);
+                    t = right;
+                    right = match t.right.take() {
+                        Some(right) => right,
+                        None => break,
+                    };
+                }
+                lesser.push(t);
+                t = right;
+            }
+            Ordering::Equal => break,
+        }
+    }
+
+    // Reassemble: each lesser node's right child is the next one, with the target's old
+    // left subtree at the bottom, and the mirror image on the greater side
+    let mut left = t.left.take();
+    for mut node in lesser.into_iter().rev() {
+        node.right = left;
+        node.update_size();
+        left = Some(node);
+    }
+    let mut right = t.right.take();
+    for mut node in greater.into_iter().rev() {
+        node.left = right;
+        node.update_size();
+        right = Some(node);
+    }
+    t.left = left;
+    t.right = right;
+    t.update_size();
+    t
+}
+
+impl<K, V> Map<K, V> {
+    /// Creates an empty Map.
+    pub fn new() -> Self {
+        Map { root: None }
+    }
+
+    pub fn len(&self) -> usize {
+        size(&self.root)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    pub fn clear(&mut self) {
+        drop(mem::take(self));
+    }
+
+    /// Splays the largest key to the root, so its right subtree is empty.
+    fn splay_max(&mut self) {
+        self.root = self
+            .root
+            .take()
+            .map(|root| splay(root, |_| Ordering::Greater));
+    }
+
+    /// Splays the smallest key to the root, so its left subtree is empty.
+    fn splay_min(&mut self) {
+        self.root = self.root.take().map(|root| splay(root, |_| Ordering::Less));
+    }
+
+    /// Returns the entry with the smallest key, splaying it to the root.
+    pub fn first_key_value(&mut self) -> Option<(&K, &V)> {
+        self.splay_min();
+        self.root.as_deref().map(|node| (&node.key, &node.value))
+    }
+
+    /// Returns the entry with the largest key, splaying it to the root.
+    pub fn last_key_value(&mut self) -> Option<(&K, &V)> {
+        self.splay_max();
+        self.root.as_deref().map(|node| (&node.key, &node.value))
+    }
+
+    /// In-order iteration, so keys come out sorted. This is the one read that doesn't
+    /// splay.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        let mut iter = Iter {
+            stack: Vec::new(),
+            remaining: self.len(),
+        };
+        iter.push_left_spine(self.root.as_deref());
+        iter
+    }
+}
+
+impl<K: Ord, V> Map<K, V> {
+    /// Splays `key`, or its neighbour if it's missing, and tells whether the root now holds
+    /// exactly `key`.
+    fn splay_key<Q>(&mut self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.root = self
+            .root
+            .take()
+            .map(|root| splay(root, |k| key.cmp(k.borrow())));
+        self.root
+            .as_ref()
+            .is_some_and(|root| root.key.borrow() == key)
+    }
+
+    /// Inserts a key-value pair, returning the old value if the key was already there.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        if self.splay_key(&key) {
+            let root = self.root.as_mut().unwrap();
+            return Some(mem::replace(&mut root.value, value));
+        }
+
+        // The root is now the key's neighbour, so the new node goes on top of it and takes
+        // one of its subtrees
+        let mut node = Box::new(Node {
+            key,
+            value,
+            size: 1,
+            left: None,
+            right: None,
+        });
+        if let Some(mut root) = self.root.take() {
+            if node.key < root.key {
+                node.left = root.left.take();
+                root.update_size();
+                node.right = Some(root);
+            } else {
+                node.right = root.right.take();
+                root.update_size();
+                node.left = Some(root);
+            }
+            node.update_size();
+        }
+        self.root = Some(node);
+        None
+    }
+
+    /// Returns the value for `key`, splaying it to the root.
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        if self.splay_key(key) {
+            self.root.as_deref().map(|root| &root.value)
+        } else {
+            None
+        }
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        if self.splay_key(key) {
+            self.root.as_deref_mut().map(|root| &mut root.value)
+        } else {
+            None
+        }
+    }
+
+    pub fn contains_key<Q>(&mut self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.splay_key(key)
+    }
+
+    /// Removes a key, returning its value if it was there.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.remove_entry(key).map(|(_, v)| v)
+    }
+
+    /// Removes a key, returning the stored key and value if it was there.
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        if !self.splay_key(key) {
+            return None;
+        }
+        let mut root = self.root.take().unwrap();
+        let mut left = Map {
+            root: root.left.take(),
+        };
+        left.join(Map {
+            root: root.right.take(),
+        });
+        *self = left;
+        Some((root.key, root.value))
+    }
+
+    /// Moves every entry with a key greater than or equal to `key` into a new map and
+    /// returns it.
+    pub fn split<Q>(&mut self, key: &Q) -> Map<K, V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.splay_key(key);
+        let mut root = match self.root.take() {
+            Some(root) => root,
+            None => return Map::new(),
+        };
+        if root.key.borrow() < key {
+            // The root is the predecessor: it stays, its right subtree goes
+            let right = root.right.take();
+            root.update_size();
+            self.root = Some(root);
+            Map { root: right }
+        } else {
+            self.root = root.left.take();
+            root.update_size();
+            Map { root: Some(root) }
+        }
+    }
+
+    /// Appends `other`, whose keys must all be greater than the keys in `self`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a key in `other` isn't greater than every key in `self`.
+    pub fn join(&mut self, mut other: Map<K, V>) {
+        if other.is_empty() {
+            return;
+        }
+        self.splay_max();
+        let root = match self.root.as_mut() {
+            Some(root) => root,
+            None => {
+                *self = other;
+                return;
+            }
+        };
+        other.splay_min();
+        let other_root = other.root.take();
+        assert!(
+            other_root.as_ref().unwrap().key > root.key,
+            "joined map must only hold larger keys"
+        );
+        root.right = other_root;
+        root.update_size();
+    }
+}
+
+impl<K, V> Default for Map<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V> Drop for Map<K, V> {
+    fn drop(&mut self) {
+        // Splay trees can get as deep as they are long; don't let the default recursive
+        // drop blow the stack on one
+        let mut stack: Vec<_> = self.root.take().into_iter().collect();
+        while let Some(mut node) = stack.pop() {
+            stack.extend(node.left.take());
+            stack.extend(node.right.take());
+        }
+    }
+}
+
+impl<K: Ord, V> FromIterator<(K, V)> for Map<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = Map::new();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K: Ord, V> Extend<(K, V)> for Map<K, V> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug> fmt::Debug for Map<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// In-order iterator. The stack holds the nodes whose left subtree we've started but who
+/// haven't been yielded yet.
+pub struct Iter<'a, K, V> {
+    stack: Vec<&'a Node<K, V>>,
+    remaining: usize,
+}
+
+impl<'a, K, V> Iter<'a, K, V> {
+    fn push_left_spine(&mut self, mut link: Option<&'a Node<K, V>>) {
+        while let Some(node) = link {
+            self.stack.push(node);
+            link = node.left.as_deref();
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        self.push_left_spine(node.right.as_deref());
+        self.remaining -= 1;
+        Some((&node.key, &node.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+
+impl<'a, K, V> IntoIterator for &'a Map<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Link, Map};
+
+    fn root<V>(map: &Map<i32, V>) -> Option<i32> {
+        map.root.as_ref().map(|node| node.key)
+    }
+
+    fn keys<V>(map: &Map<i32, V>) -> Vec<i32> {
+        map.iter().map(|(k, _)| *k).collect()
+    }
+
+    // Checks the stored subtree sizes, without recursing on a possibly deep tree
+    fn check_sizes<V>(map: &Map<i32, V>) {
+        let mut stack: Vec<&Link<i32, V>> = vec![&map.root];
+        while let Some(link) = stack.pop() {
+            if let Some(node) = link {
+                assert_eq!(
+                    node.size,
+                    1 + super::size(&node.left) + super::size(&node.right)
+                );
+                stack.push(&node.left);
+                stack.push(&node.right);
+            }
+        }
+    }
+
+    #[test]
+    fn basics() {
+        let mut map = Map::new();
+
+        // Check empty map behaves right
+        assert!(map.is_empty());
+        assert_eq!(map.get(&1), None);
+        assert_eq!(map.remove(&1), None);
+        assert_eq!(map.first_key_value(), None);
+
+        // Populate map
+        for k in [5, 2, 8, 1, 9, 3] {
+            assert_eq!(map.insert(k, k * 10), None);
+        }
+        assert_eq!(map.len(), 6);
+        assert_eq!(map.insert(3, 0), Some(30));
+        assert_eq!(map.len(), 6);
+
+        if let Some(value) = map.get_mut(&3) {
+            *value = 33;
+        }
+        assert_eq!(map.get(&3), Some(&33));
+        assert!(map.contains_key(&9));
+        assert!(!map.contains_key(&4));
+
+        assert_eq!(map.first_key_value(), Some((&1, &10)));
+        assert_eq!(map.last_key_value(), Some((&9, &90)));
+
+        assert_eq!(map.remove(&5), Some(50));
+        assert_eq!(map.remove_entry(&1), Some((1, 10)));
+        assert_eq!(map.len(), 4);
+        assert_eq!(keys(&map), vec![2, 3, 8, 9]);
+        check_sizes(&map);
+    }
+
+    #[test]
+    fn access_splays_to_root() {
+        let mut map: Map<_, _> = (0..100).map(|i| (i, ())).collect();
+        map.get(&42);
+        assert_eq!(root(&map), Some(42));
+        map.get(&7);
+        assert_eq!(root(&map), Some(7));
+
+        // A miss splays the neighbour instead
+        map.get(&1000);
+        assert_eq!(root(&map), Some(99));
+        check_sizes(&map);
+        assert_eq!(keys(&map), (0..100).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn zig_zig_halves_the_path() {
+        // Ascending inserts leave a left-leaning path; splaying its bottom fixes that
+        let n = 1024;
+        let mut map: Map<_, _> = (0..n).map(|i| (i, ())).collect();
+        assert_eq!(depth(&map, 0), n as usize - 1);
+        map.get(&0);
+        assert!(depth(&map, n - 1) < (n / 2) as usize + 2);
+        check_sizes(&map);
+    }
+
+    fn depth<V>(map: &Map<i32, V>, key: i32) -> usize {
+        let mut depth = 0;
+        let mut link = map.root.as_deref();
+        while let Some(node) = link {
+            if node.key == key {
+                return depth;
+            }
+            link = if key < node.key {
+                node.left.as_deref()
+            } else {
+                node.right.as_deref()
+            };
+            depth += 1;
+        }
+        panic!("{} not in the map", key);
+    }
+
+    #[test]
+    fn split_and_join() {
+        let mut map: Map<_, _> = (0..20).map(|i| (i * 2, i)).collect();
+
+        // Splitting on a key that's there
+        let mut upper = map.split(&20);
+        assert_eq!(map.len(), 10);
+        assert_eq!(upper.len(), 10);
+        assert_eq!(keys(&map), (0..10).map(|i| i * 2).collect::<Vec<_>>());
+        assert_eq!(upper.first_key_value(), Some((&20, &10)));
+
+        // And on one that isn't
+        let top = upper.split(&31);
+        assert_eq!(keys(&upper), vec![20, 22, 24, 26, 28, 30]);
+        assert_eq!(keys(&top), vec![32, 34, 36, 38]);
+
+        // Splitting past either end
+        assert!(map.split(&100).is_empty());
+        let all = map.split(&-1);
+        assert!(map.is_empty());
+        map = all;
+
+        map.join(upper);
+        map.join(top);
+        map.join(Map::new());
+        assert_eq!(map.len(), 20);
+        assert_eq!(keys(&map), (0..20).map(|i| i * 2).collect::<Vec<_>>());
+        check_sizes(&map);
+
+        let mut empty = Map::new();
+        empty.join(map);
+        assert_eq!(empty.len(), 20);
+    }
+
+    #[test]
+    #[should_panic(expected = "larger keys")]
+    fn join_rejects_overlap() {
+        let mut low: Map<_, _> = (0..10).map(|i| (i, ())).collect();
+        let high: Map<_, _> = (5..15).map(|i| (i, ())).collect();
+        low.join(high);
+    }
+
+    #[test]
+    fn borrowed_keys() {
+        let mut map = Map::new();
+        map.insert("b".to_string(), 2);
+        map.insert("a".to_string(), 1);
+        assert_eq!(map.get("a"), Some(&1));
+        let upper = map.split("b");
+        assert_eq!(format!("{:?} {:?}", map, upper), r#"{"a": 1} {"b": 2}"#);
+    }
+
+    #[test]
+    fn against_btreemap() {
+        use std::collections::BTreeMap;
+        let mut map = Map::new();
+        let mut model = BTreeMap::new();
+        let mut x: u32 = 99;
+        for _ in 0..5_000 {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let key = ((x >> 16) % 500) as i32;
+            match x % 4 {
+                0 => assert_eq!(map.remove(&key), model.remove(&key)),
+                1 => assert_eq!(map.get(&key), model.get(&key)),
+                _ => assert_eq!(map.insert(key, x), model.insert(key, x)),
+            }
+        }
+        check_sizes(&map);
+        assert_eq!(map.len(), model.len());
+        assert!(map.iter().eq(model.iter()));
+    }
+}
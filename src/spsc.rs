@@ -0,0 +1,309 @@
+//! # SPSC ring buffer
+//!
+//! A bounded queue for exactly one producer thread and one consumer thread. With only one
+//! writer per index we don't need a single CAS: the producer owns `tail`, the consumer owns
+//! `head`, and each just publishes its own index with a release store for the other to read.
+//!
+//! ```text
+//!        head (consumer)         tail (producer)
+//!         |                       |
+//!         v                       v
+//! [ .  .  a  b  c  d  e  f  g  .  .  . ]   capacity a power of two
+//! ```
+//!
+//! Both indices count up forever (wrapping) and are masked down to a slot, so `tail - head`
+//! is always the number of elements, and a full buffer can be told apart from an empty one
+//! without wasting a slot.
+//!
+//! Each side also keeps a cached copy of the other side's index. It only re-reads the shared
+//! one when the cache says there isn't enough room (or data), which keeps the two threads
+//! from bouncing the same cache line back and forth on every operation.
+
+use std::cell::UnsafeCell;
+use std::mem::MaybeUninit;
+use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+struct Ring<T> {
+    slots: Box<[UnsafeCell<MaybeUninit<T>>]>,
+    mask: usize,
+    head: AtomicUsize,
+    tail: AtomicUsize,
+}
+
+impl<T> Ring<T> {
+    fn slot(&self, index: usize) -> *mut MaybeUninit<T> {
+        self.slots[index & self.mask].get()
+    }
+}
+
+/// The sending half. Only one exists per ring.
+pub struct Producer<T> {
+    ring: Arc<Ring<T>>,
+    tail: usize,
+    cached_head: usize,
+}
+
+/// The receiving half. Only one exists per ring.
+pub struct Consumer<T> {
+    ring: Arc<Ring<T>>,
+    head: usize,
+    cached_tail: usize,
+}
+
+/// Creates a ring holding at least `capacity` elements, rounded up to a power of two.
+///
+/// # Panics
+///
+/// Panics if `capacity` is zero.
+pub fn channel<T>(capacity: usize) -> (Producer<T>, Consumer<T>) {
+    assert!(capacity > 0, "capacity must be at least one");
+    let capacity = capacity.next_power_of_two();
+    let slots = (0..capacity)
+        .map(|_| UnsafeCell::new(MaybeUninit::uninit()))
+        .collect();
+
+    let ring = Arc::new(Ring {
+        slots,
+        mask: capacity - 1,
+        head: AtomicUsize::new(0),
+        tail: AtomicUsize::new(0),
+    });
+    (
+        Producer {
+            ring: ring.clone(),
+            tail: 0,
+            cached_head: 0,
+        },
+        Consumer {
+            ring,
+            head: 0,
+            cached_tail: 0,
+        },
+    )
+}
+
+impl<T> Producer<T> {
+    /// Returns the number of slots in the ring.
+    pub fn capacity(&self) -> usize {
+        self.ring.slots.len()
+    }
+
+    /// How many elements could be pushed right now without the buffer filling up. Only goes
+    /// to the shared index if the cached one says there's less room than we `want`.
+    fn free_slots(&mut self, want: usize) -> usize {
+        let capacity = self.capacity();
+        if capacity - self.tail.wrapping_sub(self.cached_head) < want {
+            self.cached_head = self.ring.head.load(Ordering::Acquire);
+        }
+        capacity - self.tail.wrapping_sub(self.cached_head)
+    }
+
+    /// Pushes an element, or hands it back if the buffer is full.
+    pub fn try_push(&mut self, value: T) -> Result<(), T> {
+        if self.free_slots(1) == 0 {
+            return Err(value);
+        }
+        unsafe { ptr::write(self.ring.slot(self.tail), MaybeUninit::new(value)) };
+        self.tail = self.tail.wrapping_add(1);
+        self.ring.tail.store(self.tail, Ordering::Release);
+        Ok(())
+    }
+
+    /// Pushes as many elements from `iter` as currently fit, publishing them all at once.
+    /// Returns how many were pushed; the rest are left in the iterator.
+    pub fn push_batch<I>(&mut self, iter: &mut I) -> usize
+    where
+        I: Iterator<Item = T>,
+    {
+        let free = self.free_slots(self.capacity());
+        let mut pushed = 0;
+        while pushed < free {
+            match iter.next() {
+                Some(value) => {
+                    let slot = self.ring.slot(self.tail.wrapping_add(pushed));
+                    unsafe { ptr::write(slot, MaybeUninit::new(value)) };
+                    pushed += 1;
+                }
+                None => break,
+            }
+        }
+        self.tail = self.tail.wrapping_add(pushed);
+        self.ring.tail.store(self.tail, Ordering::Release);
+        pushed
+    }
+}
+
+impl<T> Consumer<T> {
+    /// Returns the number of slots in the ring.
+    pub fn capacity(&self) -> usize {
+        self.ring.slots.len()
+    }
+
+    /// How many elements are ready to be popped right now. Only goes to the shared index if
+    /// the cached one says fewer than `want` are ready.
+    fn ready(&mut self, want: usize) -> usize {
+        if self.cached_tail.wrapping_sub(self.head) < want {
+            self.cached_tail = self.ring.tail.load(Ordering::Acquire);
+        }
+        self.cached_tail.wrapping_sub(self.head)
+    }
+
+    /// Pops the oldest element, if there is one.
+    pub fn try_pop(&mut self) -> Option<T> {
+        if self.ready(1) == 0 {
+            return None;
+        }
+        let value = unsafe { ptr::read(self.ring.slot(self.head)).assume_init() };
+        self.head = self.head.wrapping_add(1);
+        self.ring.head.store(self.head, Ordering::Release);
+        Some(value)
+    }
+
+    /// Pops up to `max` ready elements onto the end of `out`, freeing their slots all at once.
+    /// Returns how many were popped.
+    pub fn pop_batch(&mut self, out: &mut Vec<T>, max: usize) -> usize {
+        let count = self.ready(max).min(max);
+        out.reserve(count);
+        for i in 0..count {
+            let slot = self.ring.slot(self.head.wrapping_add(i));
+            out.push(unsafe { ptr::read(slot).assume_init() });
+        }
+        self.head = self.head.wrapping_add(count);
+        self.ring.head.store(self.head, Ordering::Release);
+        count
+    }
+
+    /// Returns true if nothing was ready at the moment we looked.
+    pub fn is_empty(&mut self) -> bool {
+        self.ready(1) == 0
+    }
+}
+
+impl<T> Drop for Ring<T> {
+    fn drop(&mut self) {
+        // Both halves are gone, so the indices are final
+        let head = *self.head.get_mut();
+        let tail = *self.tail.get_mut();
+        let mut index = head;
+        while index != tail {
+            unsafe { ptr::drop_in_place((*self.slot(index)).as_mut_ptr()) };
+            index = index.wrapping_add(1);
+        }
+    }
+}
+
+unsafe impl<T: Send> Send for Producer<T> {}
+unsafe impl<T: Send> Send for Consumer<T> {}
+
+#[cfg(test)]
+mod test {
+    use super::channel;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn basics() {
+        let (mut tx, mut rx) = channel(3);
+        assert_eq!(tx.capacity(), 4);
+        assert_eq!(rx.try_pop(), None);
+        assert!(rx.is_empty());
+
+        for i in 0..4 {
+            assert_eq!(tx.try_push(i), Ok(()));
+        }
+        assert_eq!(tx.try_push(4), Err(4));
+
+        assert_eq!(rx.try_pop(), Some(0));
+        assert_eq!(rx.try_pop(), Some(1));
+        assert_eq!(tx.try_push(4), Ok(()));
+        assert_eq!(tx.try_push(5), Ok(()));
+        assert_eq!(tx.try_push(6), Err(6));
+
+        // Wrapped around the end of the buffer
+        for i in 2..6 {
+            assert_eq!(rx.try_pop(), Some(i));
+        }
+        assert_eq!(rx.try_pop(), None);
+    }
+
+    #[test]
+    fn batches() {
+        let (mut tx, mut rx) = channel(8);
+
+        let mut source = 0..20;
+        assert_eq!(tx.push_batch(&mut source), 8);
+        assert_eq!(tx.push_batch(&mut source), 0);
+
+        let mut out = Vec::new();
+        assert_eq!(rx.pop_batch(&mut out, 5), 5);
+        assert_eq!(out, vec![0, 1, 2, 3, 4]);
+
+        assert_eq!(tx.push_batch(&mut source), 5);
+        assert_eq!(source.next(), Some(13));
+
+        out.clear();
+        assert_eq!(rx.pop_batch(&mut out, 100), 8);
+        assert_eq!(out, vec![5, 6, 7, 8, 9, 10, 11, 12]);
+        assert_eq!(rx.pop_batch(&mut out, 100), 0);
+    }
+
+    #[test]
+    fn drops_unread() {
+        let value = Arc::new(());
+        let (mut tx, mut rx) = channel(4);
+        for _ in 0..3 {
+            tx.try_push(value.clone()).unwrap();
+        }
+        drop(rx.try_pop());
+        assert_eq!(Arc::strong_count(&value), 3);
+
+        drop(tx);
+        drop(rx);
+        assert_eq!(Arc::strong_count(&value), 1);
+    }
+
+    #[test]
+    fn threaded() {
+        let n = if cfg!(miri) { 500 } else { 100_000 };
+        let (mut tx, mut rx) = channel(64);
+
+        let producer = thread::spawn(move || {
+            let mut i = 0;
+            while i < n {
+                let pushed = if i % 2 == 0 {
+                    tx.try_push(i).map_or(0, |_| 1)
+                } else {
+                    let mut batch = i..(i + 7).min(n);
+                    tx.push_batch(&mut batch)
+                };
+                if pushed == 0 {
+                    thread::yield_now();
+                }
+                i += pushed;
+            }
+        });
+
+        // Values have to come out in exactly the order they went in
+        let mut expected = 0;
+        let mut out = Vec::new();
+        while expected < n {
+            out.clear();
+            if expected % 3 == 0 {
+                out.extend(rx.try_pop());
+            } else {
+                rx.pop_batch(&mut out, 5);
+            }
+            if out.is_empty() {
+                thread::yield_now();
+            }
+            for value in out.drain(..) {
+                assert_eq!(value, expected);
+                expected += 1;
+            }
+        }
+        producer.join().unwrap();
+        assert_eq!(rx.try_pop(), None);
+    }
+}
@@ -0,0 +1,248 @@
+//! # Stack
+//!
+//! The classic safe singly-linked stack. Each node owns the next one through a `Box`, and the
+//! list only ever touches its head, so `push`, `pop` and `peek` are all O(1).
+//!
+//! ```text
+//! head -> 3 -> 2 -> 1 -> None
+//! ```
+//!
+//! This is the foundation the other lists in the crate grow out of: the queue adds a tail
+//! pointer, the deque adds back links, and the persistent list swaps `Box` for `Rc`.
+
+pub struct Stack<T> {
+    head: Link<T>,
+}
+
+type Link<T> = Option<Box<Node<T>>>;
+
+struct Node<T> {
+    elem: T,
+    next: Link<T>,
+}
+
+impl<T> Stack<T> {
+    /// Creates an empty Stack.
+    pub fn new() -> Self {
+        Stack { head: None }
+    }
+
+    /// Pushes an element onto the top of the stack.
+    pub fn push(&mut self, elem: T) {
+        let new_node = Box::new(Node {
+            elem,
+            next: self.head.take(),
+        });
+
+        self.head = Some(new_node);
+    }
+
+    /// Removes and returns the top element.
+    pub fn pop(&mut self) -> Option<T> {
+        self.head.take().map(|node| {
+            self.head = node.next;
+            node.elem
+        })
+    }
+
+    /// Borrows the top element.
+    pub fn peek(&self) -> Option<&T> {
+        self.head.as_ref().map(|node| &node.elem)
+    }
+
+    /// Mutably borrows the top element.
+    pub fn peek_mut(&mut self) -> Option<&mut T> {
+        self.head.as_mut().map(|node| &mut node.elem)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.head.is_none()
+    }
+
+    /// Iterates from the top of the stack down.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            next: self.head.as_deref(),
+        }
+    }
+
+    /// Mutably iterates from the top of the stack down.
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        IterMut {
+            next: self.head.as_deref_mut(),
+        }
+    }
+}
+
+impl<T> Default for Stack<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Dropping the head would drop its box, which drops the next box, and so on: one stack frame
+/// per node. Instead we hoist each node out of its box and cut its `next` link before it goes.
+impl<T> Drop for Stack<T> {
+    fn drop(&mut self) {
+        let mut cur_link = self.head.take();
+        while let Some(mut boxed_node) = cur_link {
+            cur_link = boxed_node.next.take();
+        }
+    }
+}
+
+pub struct IntoIter<T>(Stack<T>);
+
+pub struct Iter<'a, T> {
+    next: Option<&'a Node<T>>,
+}
+
+pub struct IterMut<'a, T> {
+    next: Option<&'a mut Node<T>>,
+}
+
+impl<T> IntoIterator for Stack<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter(self)
+    }
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.pop()
+    }
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next.map(|node| {
+            self.next = node.next.as_deref();
+            &node.elem
+        })
+    }
+}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // `take` the `&mut` out, since mutable references can't be copied
+        self.next.take().map(|node| {
+            self.next = node.next.as_deref_mut();
+            &mut node.elem
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Stack;
+
+    #[test]
+    fn basics() {
+        let mut stack = Stack::new();
+
+        // Check empty stack behaves right
+        assert_eq!(stack.pop(), None);
+        assert!(stack.is_empty());
+
+        // Populate stack
+        stack.push(1);
+        stack.push(2);
+        stack.push(3);
+
+        // Check normal removal
+        assert_eq!(stack.pop(), Some(3));
+        assert_eq!(stack.pop(), Some(2));
+
+        // Push some more just to make sure nothing's corrupted
+        stack.push(4);
+        stack.push(5);
+
+        // Check normal removal
+        assert_eq!(stack.pop(), Some(5));
+        assert_eq!(stack.pop(), Some(4));
+
+        // Check exhaustion
+        assert_eq!(stack.pop(), Some(1));
+        assert_eq!(stack.pop(), None);
+    }
+
+    #[test]
+    fn peek() {
+        let mut stack = Stack::new();
+        assert_eq!(stack.peek(), None);
+        assert_eq!(stack.peek_mut(), None);
+
+        stack.push(1);
+        stack.push(2);
+        assert_eq!(stack.peek(), Some(&2));
+
+        if let Some(value) = stack.peek_mut() {
+            *value = 42;
+        }
+        assert_eq!(stack.peek(), Some(&42));
+        assert_eq!(stack.pop(), Some(42));
+    }
+
+    #[test]
+    fn into_iter() {
+        let mut stack = Stack::new();
+        stack.push(1);
+        stack.push(2);
+        stack.push(3);
+
+        let mut iter = stack.into_iter();
+        assert_eq!(iter.next(), Some(3));
+        assert_eq!(iter.next(), Some(2));
+        assert_eq!(iter.next(), Some(1));
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn iter() {
+        let mut stack = Stack::new();
+        stack.push(1);
+        stack.push(2);
+        stack.push(3);
+
+        let mut iter = stack.iter();
+        assert_eq!(iter.next(), Some(&3));
+        assert_eq!(iter.next(), Some(&2));
+        assert_eq!(iter.next(), Some(&1));
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn iter_mut() {
+        let mut stack = Stack::new();
+        stack.push(1);
+        stack.push(2);
+        stack.push(3);
+
+        for value in stack.iter_mut() {
+            *value *= 10;
+        }
+
+        let mut iter = stack.iter_mut();
+        assert_eq!(iter.next(), Some(&mut 30));
+        assert_eq!(iter.next(), Some(&mut 20));
+        assert_eq!(iter.next(), Some(&mut 10));
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn long_drop() {
+        let mut stack = Stack::new();
+        for i in 0..100_000 {
+            stack.push(i);
+        }
+    }
+}
@@ -0,0 +1,252 @@
+//! # Suffix array
+//!
+//! Every suffix of a text, sorted. Storing the suffixes themselves would take quadratic
+//! space, so the array only holds where each one starts:
+//!
+//! ```text
+//! text: banana
+//!
+//!  i   sa[i]  suffix    lcp[i]
+//!  0     5    a           0
+//!  1     3    ana         1     ("a" shared with the one above)
+//!  2     1    anana       3     ("ana")
+//!  3     0    banana      0
+//!  4     4    na          0
+//!  5     2    nana        2     ("na")
+//! ```
+//!
+//! Every occurrence of a pattern is the start of a suffix beginning with it, and those
+//! suffixes sit next to each other in sorted order. So [`SuffixArray::find_all`] is two
+//! binary searches, O(m log n) for a pattern of length m, however many matches there are.
+//!
+//! The array is built by prefix doubling (Manber and Myers, 1990). Round k ranks the
+//! suffixes by their first 2^k bytes, and the rank of a 2^(k+1) prefix is the pair of ranks
+//! of its two halves, both already known. The rounds stop once every rank is distinct,
+//! so with sorting each round that's O(n log² n) at worst and much less on typical text.
+//!
+//! The LCP array holds the length of the longest common prefix of each suffix and the one
+//! before it. Comparing neighbours directly would be quadratic. Kasai's algorithm (2001)
+//! visits suffixes in text order instead. Dropping the first byte of a suffix loses at
+//! most one byte of its common prefix, so each comparison picks up where the last one
+//! left off, and the whole array takes O(n).
+
+pub struct SuffixArray {
+    text: Vec<u8>,
+    // Start of each suffix, in sorted order
+    suffixes: Vec<usize>,
+    // lcp[i] is the common prefix length of suffixes[i - 1] and suffixes[i]; lcp[0] is 0
+    lcp: Vec<usize>,
+}
+
+impl SuffixArray {
+    /// Builds the suffix and LCP arrays for `text`.
+    pub fn new<T>(text: &T) -> Self
+    where
+        T: AsRef<[u8]> + ?Sized,
+    {
+        let text = text.as_ref().to_vec();
+        let suffixes = build_suffixes(&text);
+        let lcp = build_lcp(&text, &suffixes);
+        SuffixArray {
+            text,
+            suffixes,
+            lcp,
+        }
+    }
+
+    pub fn text(&self) -> &[u8] {
+        &self.text
+    }
+
+    pub fn len(&self) -> usize {
+        self.text.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    /// Returns the start of every suffix, in sorted order.
+    pub fn suffixes(&self) -> &[usize] {
+        &self.suffixes
+    }
+
+    /// Returns the common prefix length of each sorted suffix with the one before it.
+    pub fn lcp(&self) -> &[usize] {
+        &self.lcp
+    }
+
+    /// Returns the range of sorted suffixes that start with `pattern`.
+    fn matching(&self, pattern: &[u8]) -> &[usize] {
+        let start = self
+            .suffixes
+            .partition_point(|&s| &self.text[s..] < pattern);
+        let len = self.suffixes[start..].partition_point(|&s| self.text[s..].starts_with(pattern));
+        &self.suffixes[start..start + len]
+    }
+
+    /// Returns where `pattern` occurs in the text, in increasing order. Occurrences
+    /// may overlap, and an empty pattern matches at every position.
+    pub fn find_all<P>(&self, pattern: &P) -> Vec<usize>
+    where
+        P: AsRef<[u8]> + ?Sized,
+    {
+        let mut found = self.matching(pattern.as_ref()).to_vec();
+        found.sort_unstable();
+        found
+    }
+
+    /// Returns how many times `pattern` occurs in the text, without listing them.
+    pub fn count<P>(&self, pattern: &P) -> usize
+    where
+        P: AsRef<[u8]> + ?Sized,
+    {
+        self.matching(pattern.as_ref()).len()
+    }
+
+    pub fn contains<P>(&self, pattern: &P) -> bool
+    where
+        P: AsRef<[u8]> + ?Sized,
+    {
+        self.count(pattern) > 0
+    }
+
+    /// Returns the longest substring that occurs at least twice, or `None` if no byte
+    /// repeats. It's the longest prefix shared by two suffixes, and those are neighbours.
+    pub fn longest_repeat(&self) -> Option<&[u8]> {
+        let (i, &len) = self.lcp.iter().enumerate().max_by_key(|&(_, len)| len)?;
+        if len == 0 {
+            return None;
+        }
+        let start = self.suffixes[i];
+        Some(&self.text[start..start + len])
+    }
+}
+
+fn build_suffixes(text: &[u8]) -> Vec<usize> {
+    let n = text.len();
+    let mut suffixes: Vec<usize> = (0..n).collect();
+    if n == 0 {
+        return suffixes;
+    }
+    let mut rank: Vec<usize> = text.iter().map(|&b| b as usize).collect();
+    let mut next = vec![0; n];
+
+    let mut width = 1;
+    loop {
+        // Sort by the first 2 * width bytes. A missing second half sorts before any byte,
+        // just as a shorter suffix sorts before a longer one it prefixes.
+        let key = |i: usize| (rank[i], rank.get(i + width).map_or(0, |r| r + 1));
+        suffixes.sort_unstable_by_key(|&i| key(i));
+
+        next[suffixes[0]] = 0;
+        for pair in suffixes.windows(2) {
+            let bump = (key(pair[0]) != key(pair[1])) as usize;
+            next[pair[1]] = next[pair[0]] + bump;
+        }
+        std::mem::swap(&mut rank, &mut next);
+
+        if rank[suffixes[n - 1]] == n - 1 || width >= n {
+            return suffixes;
+        }
+        width *= 2;
+    }
+}
+
+fn build_lcp(text: &[u8], suffixes: &[usize]) -> Vec<usize> {
+    let n = text.len();
+    let mut rank = vec![0; n];
+    for (i, &s) in suffixes.iter().enumerate() {
+        rank[s] = i;
+    }
+
+    let mut lcp = vec![0; n];
+    let mut h: usize = 0;
+    for start in 0..n {
+        if rank[start] == 0 {
+            h = 0;
+            continue;
+        }
+        let prev = suffixes[rank[start] - 1];
+        while start + h < n && prev + h < n && text[start + h] == text[prev + h] {
+            h += 1;
+        }
+        lcp[rank[start]] = h;
+        // The next suffix is this one minus its first byte
+        h = h.saturating_sub(1);
+    }
+    lcp
+}
+
+#[cfg(test)]
+mod test {
+    use super::SuffixArray;
+
+    #[test]
+    fn basics() {
+        let sa = SuffixArray::new("banana");
+        assert_eq!(sa.len(), 6);
+        assert_eq!(sa.suffixes(), &[5, 3, 1, 0, 4, 2]);
+        assert_eq!(sa.lcp(), &[0, 1, 3, 0, 0, 2]);
+
+        assert_eq!(sa.find_all("ana"), vec![1, 3]);
+        assert_eq!(sa.find_all("a"), vec![1, 3, 5]);
+        assert_eq!(sa.find_all("banana"), vec![0]);
+        assert_eq!(sa.find_all("bananas"), Vec::<usize>::new());
+        assert_eq!(sa.find_all("nab"), Vec::<usize>::new());
+        assert_eq!(sa.find_all(""), vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(sa.count("na"), 2);
+        assert!(sa.contains("nan"));
+        assert!(!sa.contains("x"));
+        assert_eq!(sa.longest_repeat(), Some(&b"ana"[..]));
+
+        // Check empty text behaves right
+        let empty = SuffixArray::new("");
+        assert!(empty.is_empty());
+        assert_eq!(empty.find_all("a"), Vec::<usize>::new());
+        assert_eq!(empty.longest_repeat(), None);
+        assert_eq!(SuffixArray::new("abc").longest_repeat(), None);
+    }
+
+    #[test]
+    fn runs_of_one_byte() {
+        // The worst case for doubling: every round is needed to tell the suffixes apart
+        let text = vec![b'a'; 1000];
+        let sa = SuffixArray::new(&text);
+        assert!(sa.suffixes().iter().copied().eq((0..1000).rev()));
+        assert!(sa.lcp().iter().copied().eq(0..1000));
+        assert_eq!(sa.count("aaa"), 998);
+        assert_eq!(sa.longest_repeat().map(|s| s.len()), Some(999));
+    }
+
+    #[test]
+    fn against_brute_force() {
+        let mut x: u32 = 3;
+        for len in 0..120 {
+            let text: Vec<u8> = (0..len)
+                .map(|_| {
+                    x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                    b"abc"[(x >> 28) as usize % 3]
+                })
+                .collect();
+            let sa = SuffixArray::new(&text);
+
+            let mut expected: Vec<usize> = (0..len).collect();
+            expected.sort_by_key(|&i| &text[i..]);
+            assert_eq!(sa.suffixes(), &expected[..]);
+
+            for i in 1..len {
+                let (a, b) = (&text[expected[i - 1]..], &text[expected[i]..]);
+                let common = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+                assert_eq!(sa.lcp()[i], common);
+            }
+
+            for pattern in [&b"a"[..], b"ab", b"cab", b"abca", b"bb"].iter() {
+                let naive: Vec<usize> = (0..len)
+                    .filter(|&i| text[i..].starts_with(pattern))
+                    .collect();
+                assert_eq!(sa.find_all(pattern), naive);
+            }
+        }
+    }
+}
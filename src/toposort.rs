@@ -0,0 +1,349 @@
+//! # Topological order and strongly connected components
+//!
+//! Two ways of looking at the order a directed graph's edges put its nodes in.
+//!
+//! A *topological order* lists the nodes so that every edge goes forwards. There's one
+//! exactly when there's no cycle. Kahn's algorithm (1962) builds it by repeatedly taking a
+//! node that nothing left points into and crossing off its outgoing edges. If it runs out
+//! of such nodes before it runs out of nodes, every node left has an edge coming in from
+//! another node left, so following those edges backwards has to come round in a circle,
+//! and [`toposort`] hands that cycle back instead.
+//!
+//! The *strongly connected components* are the groups of nodes that can all reach each
+//! other. Squashing each into a single node, the *condensation*, always leaves a graph
+//! without cycles:
+//!
+//! ```text
+//!   a <--> b ----> c <--> d           {a b} ----> {c d}
+//!          |              |             |           |
+//!          v              v             v           v
+//!          e ------------> f           {e} ------> {f}
+//! ```
+//!
+//! Tarjan's algorithm (1972) finds them in a single depth-first search. Each node gets a
+//! number in the order the search reaches it, and a *low link*: the smallest number it's
+//! found a way back up to, through edges to nodes still on a stack of unfinished ones. A
+//! node whose low link is its own number is the first of its component the search got to,
+//! and the component is everything above it on the stack. Components come out sinks
+//! first, so [`strongly_connected_components`] numbers them backwards, which makes the
+//! numbering a topological order of the condensation. Both algorithms are O(V + E), and
+//! the search keeps its own stack instead of recursing.
+
+use crate::bit_set::BitSet;
+use crate::graph::{DiGraph, NodeId};
+use std::collections::{HashMap, VecDeque};
+
+/// Returns the nodes in an order where every edge goes forwards, or, if there's no such
+/// order, the nodes of a cycle, each with an edge to the next and the last to the first.
+pub fn toposort<N, E>(graph: &DiGraph<N, E>) -> Result<Vec<NodeId>, Vec<NodeId>> {
+    let mut in_degree = vec![0; graph.node_bound()];
+    let mut ready = VecDeque::new();
+    for node in graph.node_ids() {
+        in_degree[node.index()] = graph.incoming(node).count();
+        if in_degree[node.index()] == 0 {
+            ready.push_back(node);
+        }
+    }
+    let mut order = Vec::with_capacity(graph.node_count());
+    while let Some(node) = ready.pop_front() {
+        order.push(node);
+        for next in graph.neighbors(node) {
+            in_degree[next.index()] -= 1;
+            if in_degree[next.index()] == 0 {
+                ready.push_back(next);
+            }
+        }
+    }
+    if order.len() == graph.node_count() {
+        return Ok(order);
+    }
+
+    // Walk backwards through the nodes left over until one comes round again
+    let left = |node: NodeId| in_degree[node.index()] > 0;
+    let mut node = graph.node_ids().find(|&node| left(node)).unwrap();
+    let mut walk = Vec::new();
+    let mut seen = HashMap::new();
+    while !seen.contains_key(&node) {
+        seen.insert(node, walk.len());
+        walk.push(node);
+        node = graph
+            .incoming(node)
+            .map(|edge| edge.source)
+            .find(|&n| left(n))
+            .unwrap();
+    }
+    let mut cycle = walk.split_off(seen[&node]);
+    cycle.reverse();
+    Err(cycle)
+}
+
+/// The strongly connected components of a graph, numbered from 0 in a topological order:
+/// an edge from one component to another always goes to a higher number.
+pub struct Components {
+    of: HashMap<NodeId, usize>,
+    members: Vec<Vec<NodeId>>,
+}
+
+impl Components {
+    /// How many components there are.
+    pub fn count(&self) -> usize {
+        self.members.len()
+    }
+
+    /// Returns the number of the component a node is in.
+    pub fn component(&self, node: NodeId) -> Option<usize> {
+        self.of.get(&node).copied()
+    }
+
+    /// Returns the nodes in a component.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there's no component `id`.
+    pub fn members(&self, id: usize) -> &[NodeId] {
+        &self.members[id]
+    }
+
+    /// Iterates over the components, in order.
+    pub fn iter(&self) -> impl Iterator<Item = &[NodeId]> + '_ {
+        self.members.iter().map(Vec::as_slice)
+    }
+
+    /// Builds the condensation of `graph`, the graph these components were found in: a
+    /// node for each component, in order, holding its members, and an edge from one to
+    /// another wherever `graph` has any edge between them.
+    pub fn condensation<N, E>(&self, graph: &DiGraph<N, E>) -> DiGraph<Vec<NodeId>, ()> {
+        let mut condensed = DiGraph::new();
+        let ids: Vec<_> = self
+            .members
+            .iter()
+            .map(|members| condensed.add_node(members.clone()))
+            .collect();
+        let mut edges: Vec<_> = graph
+            .edges()
+            .map(|edge| (self.of[&edge.source], self.of[&edge.target]))
+            .filter(|(from, to)| from != to)
+            .collect();
+        edges.sort_unstable();
+        edges.dedup();
+        for (from, to) in edges {
+            condensed.add_edge(ids[from], ids[to], ());
+        }
+        condensed
+    }
+}
+
+/// Finds the strongly connected components with Tarjan's algorithm.
+pub fn strongly_connected_components<N, E>(graph: &DiGraph<N, E>) -> Components {
+    const UNVISITED: usize = usize::MAX;
+    let mut number = vec![UNVISITED; graph.node_bound()];
+    let mut low = vec![0; graph.node_bound()];
+    let mut on_stack = BitSet::with_capacity(graph.node_bound());
+    let mut stack = Vec::new();
+    let mut found = Vec::new();
+    let mut count = 0;
+    for root in graph.node_ids() {
+        if number[root.index()] != UNVISITED {
+            continue;
+        }
+        number[root.index()] = count;
+        low[root.index()] = count;
+        count += 1;
+        stack.push(root);
+        on_stack.insert(root.index());
+        let mut path = vec![(root, graph.neighbors(root))];
+        while let Some((node, next)) = path.last_mut() {
+            let node = *node;
+            match next.next() {
+                Some(next) if number[next.index()] == UNVISITED => {
+                    number[next.index()] = count;
+                    low[next.index()] = count;
+                    count += 1;
+                    stack.push(next);
+                    on_stack.insert(next.index());
+                    path.push((next, graph.neighbors(next)));
+                }
+                Some(next) => {
+                    if on_stack.contains(next.index()) {
+                        low[node.index()] = low[node.index()].min(number[next.index()]);
+                    }
+                }
+                None => {
+                    path.pop();
+                    if let Some((parent, _)) = path.last() {
+                        low[parent.index()] = low[parent.index()].min(low[node.index()]);
+                    }
+                    if low[node.index()] == number[node.index()] {
+                        let mut members = Vec::new();
+                        loop {
+                            let member = stack.pop().unwrap();
+                            on_stack.remove(member.index());
+                            members.push(member);
+                            if member == node {
+                                break;
+                            }
+                        }
+                        found.push(members);
+                    }
+                }
+            }
+        }
+    }
+
+    // Found sinks first, so reverse for a topological order
+    found.reverse();
+    let mut of = HashMap::new();
+    for (id, members) in found.iter().enumerate() {
+        for &member in members {
+            of.insert(member, id);
+        }
+    }
+    Components { of, members: found }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{strongly_connected_components, toposort};
+    use crate::graph::{DiGraph, NodeId};
+    use crate::traversal::reachable;
+
+    /// Checks a cycle is real: distinct nodes, each with an edge to the next.
+    fn check_cycle(graph: &DiGraph<usize, ()>, cycle: &[NodeId]) {
+        let mut sorted = cycle.to_vec();
+        sorted.sort();
+        sorted.dedup();
+        assert_eq!(sorted.len(), cycle.len());
+        for (i, &node) in cycle.iter().enumerate() {
+            assert!(graph
+                .find_edge(node, cycle[(i + 1) % cycle.len()])
+                .is_some());
+        }
+    }
+
+    /// The graph in the module docs.
+    fn sample() -> (DiGraph<usize, ()>, Vec<NodeId>) {
+        let mut graph = DiGraph::new();
+        let nodes: Vec<_> = (0..6).map(|i| graph.add_node(i)).collect();
+        let [a, b, c, d, e, f] = [0, 1, 2, 3, 4, 5];
+        for &(from, to) in &[
+            (a, b),
+            (b, a),
+            (b, c),
+            (c, d),
+            (d, c),
+            (b, e),
+            (d, f),
+            (e, f),
+        ] {
+            graph.add_edge(nodes[from], nodes[to], ());
+        }
+        (graph, nodes)
+    }
+
+    #[test]
+    fn topological_order() {
+        let mut graph = DiGraph::new();
+        let nodes: Vec<_> = (0..6).map(|i| graph.add_node(i)).collect();
+        for &(from, to) in &[(5, 2), (5, 0), (4, 0), (4, 1), (2, 3), (3, 1)] {
+            graph.add_edge(nodes[from], nodes[to], ());
+        }
+        let order = toposort(&graph).unwrap();
+        assert_eq!(order.len(), 6);
+        let position = |node: NodeId| order.iter().position(|&n| n == node).unwrap();
+        for edge in graph.edges() {
+            assert!(position(edge.source) < position(edge.target));
+        }
+
+        // 1 -> 5 closes 5 -> 2 -> 3 -> 1
+        graph.add_edge(nodes[1], nodes[5], ());
+        let cycle = toposort(&graph).unwrap_err();
+        check_cycle(&graph, &cycle);
+        assert_eq!(cycle.len(), 4);
+
+        // A loop is a cycle of one
+        let mut graph = DiGraph::new();
+        let a = graph.add_node(0);
+        assert_eq!(toposort(&graph), Ok(vec![a]));
+        graph.add_edge(a, a, ());
+        assert_eq!(toposort(&graph), Err(vec![a]));
+        assert_eq!(toposort(&DiGraph::<(), ()>::new()), Ok(vec![]));
+    }
+
+    #[test]
+    fn components() {
+        let (graph, nodes) = sample();
+        let components = strongly_connected_components(&graph);
+        assert_eq!(components.count(), 4);
+        let of = |i: usize| components.component(nodes[i]).unwrap();
+        assert_eq!(of(0), of(1));
+        assert_eq!(of(2), of(3));
+        assert_eq!(of(0), 0);
+        assert_eq!(of(5), 3);
+        let mut first: Vec<_> = components.members(0).iter().map(|&n| graph[n]).collect();
+        first.sort();
+        assert_eq!(first, [0, 1]);
+
+        // The condensation is the picture on the right
+        let condensed = components.condensation(&graph);
+        let ids: Vec<_> = condensed.node_ids().collect();
+        assert_eq!(condensed.node_count(), 4);
+        assert_eq!(condensed.edge_count(), 4);
+        assert!(condensed.find_edge(ids[of(0)], ids[of(2)]).is_some());
+        assert!(condensed.find_edge(ids[of(4)], ids[of(5)]).is_some());
+        assert_eq!(toposort(&condensed), Ok(ids));
+    }
+
+    #[test]
+    fn long_cycle() {
+        // One component spanning a long ring, without recursing
+        let n = if cfg!(miri) { 1_000 } else { 100_000 };
+        let mut graph = DiGraph::new();
+        let nodes: Vec<_> = (0..n).map(|i| graph.add_node(i)).collect();
+        for i in 0..n {
+            graph.add_edge(nodes[i], nodes[(i + 1) % n], ());
+        }
+        assert_eq!(strongly_connected_components(&graph).count(), 1);
+        assert_eq!(toposort(&graph).unwrap_err().len(), n);
+    }
+
+    #[test]
+    fn against_reachability() {
+        // Random graphs: two nodes share a component exactly when each reaches the other
+        let mut x: u32 = 1;
+        let rounds = if cfg!(miri) { 2 } else { 30 };
+        let n = 20;
+        for round in 0..rounds {
+            let mut graph = DiGraph::new();
+            let nodes: Vec<_> = (0..n).map(|i| graph.add_node(i)).collect();
+            for _ in 0..round + 5 {
+                x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                graph.add_edge(
+                    nodes[(x >> 8) as usize % n],
+                    nodes[(x >> 16) as usize % n],
+                    (),
+                );
+            }
+            let components = strongly_connected_components(&graph);
+            for &a in &nodes {
+                for &b in &nodes {
+                    let together = reachable(&graph, a, b) && reachable(&graph, b, a);
+                    assert_eq!(components.component(a) == components.component(b), together);
+                }
+            }
+            assert_eq!(components.iter().map(<[_]>::len).sum::<usize>(), n);
+            for edge in graph.edges() {
+                assert!(components.component(edge.source) <= components.component(edge.target));
+            }
+
+            // Acyclic exactly when every component is a single node without a loop
+            let acyclic = components.count() == n && graph.edges().all(|e| e.source != e.target);
+            match toposort(&graph) {
+                Ok(order) => assert!(acyclic && order.len() == n),
+                Err(cycle) => {
+                    assert!(!acyclic);
+                    check_cycle(&graph, &cycle);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,299 @@
+//! # Graph traversal
+//!
+//! Breadth-first and depth-first search over a [`Graph`], as iterators over the nodes
+//! reachable from a start, in the order the search first gets to them:
+//!
+//! ```text
+//!       a ----> b ----> d          bfs from a:  a b c d e
+//!       |               ^          dfs from a:  a b d c e
+//!       v               |
+//!       c ----> e ------+
+//! ```
+//!
+//! Breadth-first search works through a queue, so it reaches nodes in order of how many
+//! edges away they are. Depth-first search works through a stack and follows each path as
+//! far as it goes before backing up. Both mark nodes as visited in a [`BitSet`] indexed by
+//! [`NodeId::index`], and both are O(V + E) for everything reachable.
+//!
+//! On top of those, [`reachable`] is the question of whether the search from one node ever
+//! gets to another, and [`find_cycle`] runs a depth-first search from every node, keeping
+//! track of which nodes are on the current path. An edge back to one of those closes a
+//! cycle. In an undirected graph, going back along the edge just taken doesn't count, but
+//! any other edge does, so two parallel edges make a cycle and so does a loop.
+
+use crate::bit_set::BitSet;
+use crate::graph::{Direction, EdgeId, Graph, NodeId};
+use std::collections::VecDeque;
+
+/// Breadth-first iterator over the nodes reachable from a start, the start first.
+pub struct Bfs<'a, N, E, D: Direction> {
+    graph: &'a Graph<N, E, D>,
+    visited: BitSet,
+    queue: VecDeque<NodeId>,
+}
+
+impl<'a, N, E, D: Direction> Bfs<'a, N, E, D> {
+    /// Starts a search at `start`. A stale `start` reaches nothing.
+    pub fn new(graph: &'a Graph<N, E, D>, start: NodeId) -> Self {
+        let mut visited = BitSet::with_capacity(graph.node_bound());
+        let mut queue = VecDeque::new();
+        if graph.contains_node(start) {
+            visited.insert(start.index());
+            queue.push_back(start);
+        }
+        Bfs {
+            graph,
+            visited,
+            queue,
+        }
+    }
+}
+
+impl<N, E, D: Direction> Iterator for Bfs<'_, N, E, D> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<NodeId> {
+        let node = self.queue.pop_front()?;
+        for next in self.graph.neighbors(node) {
+            if self.visited.insert(next.index()) {
+                self.queue.push_back(next);
+            }
+        }
+        Some(node)
+    }
+}
+
+/// Depth-first iterator over the nodes reachable from a start, in preorder.
+pub struct Dfs<'a, N, E, D: Direction> {
+    graph: &'a Graph<N, E, D>,
+    visited: BitSet,
+    // Nodes to go to next, the top first. A node can be on it more than once, and only the
+    // first time it comes off counts
+    stack: Vec<NodeId>,
+}
+
+impl<'a, N, E, D: Direction> Dfs<'a, N, E, D> {
+    /// Starts a search at `start`. A stale `start` reaches nothing.
+    pub fn new(graph: &'a Graph<N, E, D>, start: NodeId) -> Self {
+        let mut stack = Vec::new();
+        if graph.contains_node(start) {
+            stack.push(start);
+        }
+        Dfs {
+            graph,
+            visited: BitSet::with_capacity(graph.node_bound()),
+            stack,
+        }
+    }
+}
+
+impl<N, E, D: Direction> Iterator for Dfs<'_, N, E, D> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<NodeId> {
+        while let Some(node) = self.stack.pop() {
+            if !self.visited.insert(node.index()) {
+                continue;
+            }
+            // Backwards, so that the first neighbor comes off first
+            let (start, visited) = (self.stack.len(), &self.visited);
+            self.stack.extend(
+                self.graph
+                    .neighbors(node)
+                    .filter(|next| !visited.contains(next.index())),
+            );
+            self.stack[start..].reverse();
+            return Some(node);
+        }
+        None
+    }
+}
+
+/// Returns whether there's a path from `from` to `to`. Every node reaches itself.
+pub fn reachable<N, E, D: Direction>(graph: &Graph<N, E, D>, from: NodeId, to: NodeId) -> bool {
+    Bfs::new(graph, from).any(|node| node == to)
+}
+
+/// Returns whether the graph has a cycle anywhere.
+pub fn is_cyclic<N, E, D: Direction>(graph: &Graph<N, E, D>) -> bool {
+    find_cycle(graph).is_some()
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Color {
+    Unvisited,
+    OnPath,
+    Done,
+}
+
+/// Returns the nodes of some cycle, in order along it, if the graph has one. The last node
+/// has an edge back to the first.
+pub fn find_cycle<N, E, D: Direction>(graph: &Graph<N, E, D>) -> Option<Vec<NodeId>> {
+    let mut color = vec![Color::Unvisited; graph.node_bound()];
+    for start in graph.node_ids() {
+        if color[start.index()] != Color::Unvisited {
+            continue;
+        }
+        // The current path: each node, the edge it was reached by, and its edges still
+        // to look at
+        let mut path: Vec<(NodeId, Option<EdgeId>, _)> = vec![(start, None, graph.edges_of(start))];
+        color[start.index()] = Color::OnPath;
+        while let Some((node, arrived, edges)) = path.last_mut() {
+            let node = *node;
+            let edge = match edges.next() {
+                Some(edge) => edge,
+                None => {
+                    color[node.index()] = Color::Done;
+                    path.pop();
+                    continue;
+                }
+            };
+            if !D::DIRECTED && Some(edge.id) == *arrived {
+                continue;
+            }
+            match color[edge.target.index()] {
+                Color::Unvisited => {
+                    color[edge.target.index()] = Color::OnPath;
+                    path.push((edge.target, Some(edge.id), graph.edges_of(edge.target)));
+                }
+                Color::OnPath => {
+                    let at = path.iter().position(|(n, ..)| *n == edge.target).unwrap();
+                    return Some(path[at..].iter().map(|(n, ..)| *n).collect());
+                }
+                Color::Done => {}
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::{find_cycle, is_cyclic, reachable, Bfs, Dfs};
+    use crate::graph::{DiGraph, Direction, Graph, NodeId, UnGraph};
+
+    /// The graph in the module docs.
+    fn sample() -> (DiGraph<char, ()>, Vec<NodeId>) {
+        let mut graph = DiGraph::new();
+        let nodes: Vec<_> = "abcde".chars().map(|c| graph.add_node(c)).collect();
+        for &(from, to) in &[(0, 1), (0, 2), (1, 3), (2, 4), (4, 3)] {
+            graph.add_edge(nodes[from], nodes[to], ());
+        }
+        (graph, nodes)
+    }
+
+    fn names<N: Copy, E, D: Direction>(
+        graph: &Graph<N, E, D>,
+        nodes: impl Iterator<Item = NodeId>,
+    ) -> Vec<N> {
+        nodes.map(|node| graph[node]).collect()
+    }
+
+    /// Checks a cycle is real: distinct nodes, each with an edge to the next.
+    fn check_cycle<N, E, D: Direction>(graph: &Graph<N, E, D>, cycle: &[NodeId]) {
+        let mut sorted = cycle.to_vec();
+        sorted.sort();
+        sorted.dedup();
+        assert_eq!(sorted.len(), cycle.len());
+        for (i, &node) in cycle.iter().enumerate() {
+            let next = cycle[(i + 1) % cycle.len()];
+            assert!(graph.find_edge(node, next).is_some());
+        }
+    }
+
+    #[test]
+    fn orders() {
+        let (graph, nodes) = sample();
+        let bfs = names(&graph, Bfs::new(&graph, nodes[0]));
+        assert_eq!(bfs, ['a', 'b', 'c', 'd', 'e']);
+        let dfs = names(&graph, Dfs::new(&graph, nodes[0]));
+        assert_eq!(dfs, ['a', 'b', 'd', 'c', 'e']);
+
+        // Only what's reachable
+        let from_c = names(&graph, Dfs::new(&graph, nodes[2]));
+        assert_eq!(from_c, ['c', 'e', 'd']);
+        assert!(reachable(&graph, nodes[2], nodes[3]));
+        assert!(!reachable(&graph, nodes[3], nodes[2]));
+        assert!(reachable(&graph, nodes[3], nodes[3]));
+    }
+
+    #[test]
+    fn bfs_by_distance() {
+        // A grid, where breadth-first order is by Manhattan distance from the corner
+        let n = 8;
+        let mut graph = UnGraph::new();
+        let nodes: Vec<_> = (0..n * n).map(|i| graph.add_node((i / n, i % n))).collect();
+        for i in 0..n * n {
+            if i % n + 1 < n {
+                graph.add_edge(nodes[i], nodes[i + 1], ());
+            }
+            if i + n < n * n {
+                graph.add_edge(nodes[i], nodes[i + n], ());
+            }
+        }
+        let order = names(&graph, Bfs::new(&graph, nodes[0]));
+        assert_eq!(order.len(), n * n);
+        assert!(order.windows(2).all(|w| w[0].0 + w[0].1 <= w[1].0 + w[1].1));
+        assert_eq!(Dfs::new(&graph, nodes[0]).count(), n * n);
+    }
+
+    #[test]
+    fn deep_dfs() {
+        // A long path doesn't overflow the call stack, there being none
+        let n = if cfg!(miri) { 1_000 } else { 100_000 };
+        let mut graph = DiGraph::new();
+        let first = graph.add_node(0);
+        let mut last = first;
+        for i in 1..n {
+            let next = graph.add_node(i);
+            graph.add_edge(last, next, ());
+            last = next;
+        }
+        assert!(names(&graph, Dfs::new(&graph, last)) == [n - 1]);
+        assert!(find_cycle(&graph).is_none());
+        graph.add_edge(last, first, ());
+        assert_eq!(find_cycle(&graph).unwrap().len(), n);
+    }
+
+    #[test]
+    fn cycles() {
+        let (mut graph, nodes) = sample();
+        assert!(!is_cyclic(&graph));
+
+        // d -> c closes c -> e -> d
+        graph.add_edge(nodes[3], nodes[2], ());
+        let cycle = find_cycle(&graph).unwrap();
+        check_cycle(&graph, &cycle);
+        assert_eq!(cycle.len(), 3);
+
+        // A loop is a cycle of one
+        let mut graph = DiGraph::new();
+        let a = graph.add_node(());
+        assert!(!is_cyclic(&graph));
+        graph.add_edge(a, a, ());
+        assert_eq!(find_cycle(&graph), Some(vec![a]));
+    }
+
+    #[test]
+    fn undirected_cycles() {
+        // A tree has none: going back along an edge doesn't count
+        let mut graph = UnGraph::new();
+        let nodes: Vec<_> = (0..7).map(|i| graph.add_node(i)).collect();
+        for i in 1..7 {
+            graph.add_edge(nodes[(i - 1) / 2], nodes[i], ());
+        }
+        assert!(!is_cyclic(&graph));
+
+        // But a second edge between the same two nodes does
+        let extra = graph.add_edge(nodes[5], nodes[2], ());
+        let cycle = find_cycle(&graph).unwrap();
+        assert_eq!(cycle.len(), 2);
+        graph.remove_edge(extra);
+
+        // As does joining two leaves
+        graph.add_edge(nodes[3], nodes[6], ());
+        let cycle = find_cycle(&graph).unwrap();
+        check_cycle(&graph, &cycle);
+        assert_eq!(cycle.len(), 5);
+    }
+}
@@ -0,0 +1,724 @@
+//! # Treap map
+//!
+//! A treap (Seidel and Aragon, 1989) is a binary search tree on the keys and, at the same
+//! time, a heap on random *priorities*: every node gets a random priority when it's
+//! inserted, and no node has a higher priority than its parent. For a given set of keys and
+//! priorities there is exactly one such tree, and it's the tree you'd get by inserting the
+//! keys into a plain BST in priority order. Random priorities mean a random insertion
+//! order, so the expected depth is O(log n) no matter what order the keys really came in.
+//!
+//! Everything is built on two operations:
+//!
+//! - `split(t, key)` cuts a treap into the keys below `key` and the rest;
+//! - `merge(a, b)` glues two treaps back together when all of `a`'s keys are below `b`'s.
+//!   Whichever root has the higher priority stays on top.
+//!
+//! ```text
+//! insert(k):  split(t, k) -> (less, rest)     remove(k):  same splits, then
+//!             split(rest, >k) -> (eq, more)               merge(less, more)
+//!             merge(merge(less, eq or new node), more)
+//! ```
+//!
+//! They're also public as [`Map::split`] and [`Map::join`].
+//!
+//! ## Persistence
+//!
+//! How the nodes are linked is the map's third type parameter. A plain [`Map`] links them
+//! with `Box`es ([`Owned`]): every node has one owner, writes change it in place, and keys
+//! and values can be anything.
+//!
+//! A [`SharedMap`] links them with `Arc`s ([`Shared`]) and every write goes through
+//! `Arc::make_mut`, so a node that's shared gets copied on the way down and one that isn't
+//! is changed in place. Cloning the map (O(1)) takes a snapshot: the clone and the original
+//! share every node until one of them writes, and then only the touched path gets copied.
+//! The price is a reference count check on every node a write passes, and that keys and
+//! values have to be `Clone` before you can write at all.
+
+use crate::rng::XorShift;
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::fmt;
+use std::iter::FromIterator;
+use std::mem;
+use std::sync::Arc;
+
+mod links {
+    use std::ops::Deref;
+
+    /// How a mode points at a node.
+    pub trait Links {
+        type Ptr<T>: Deref<Target = T>;
+
+        fn new<T>(value: T) -> Self::Ptr<T>;
+    }
+
+    /// What writing through a link takes: for shared links, a node that can be copied.
+    pub trait LinksMut<T>: Links {
+        fn make_mut(ptr: &mut Self::Ptr<T>) -> &mut T;
+
+        fn into_inner(ptr: Self::Ptr<T>) -> T;
+    }
+
+    pub struct Node<K, V, M: Links> {
+        pub(in crate::treap) key: K,
+        pub(in crate::treap) value: V,
+        pub(in crate::treap) priority: u64,
+        pub(in crate::treap) left: Link<K, V, M>,
+        pub(in crate::treap) right: Link<K, V, M>,
+    }
+
+    pub type Link<K, V, M> = Option<<M as Links>::Ptr<Node<K, V, M>>>;
+}
+
+use links::{Link, Links, LinksMut, Node};
+
+/// How a treap links its nodes: [`Owned`] or [`Shared`].
+pub trait Mode: Links {}
+
+/// Nodes in `Box`es, each with one owner.
+pub struct Owned;
+
+/// Nodes in `Arc`s, shared between snapshots and copied on write.
+pub struct Shared;
+
+impl Links for Owned {
+    type Ptr<T> = Box<T>;
+
+    fn new<T>(value: T) -> Box<T> {
+        Box::new(value)
+    }
+}
+
+impl<T> LinksMut<T> for Owned {
+    fn make_mut(ptr: &mut Box<T>) -> &mut T {
+        ptr
+    }
+
+    fn into_inner(ptr: Box<T>) -> T {
+        *ptr
+    }
+}
+
+impl Links for Shared {
+    type Ptr<T> = Arc<T>;
+
+    fn new<T>(value: T) -> Arc<T> {
+        Arc::new(value)
+    }
+}
+
+impl<T: Clone> LinksMut<T> for Shared {
+    fn make_mut(ptr: &mut Arc<T>) -> &mut T {
+        Arc::make_mut(ptr)
+    }
+
+    fn into_inner(ptr: Arc<T>) -> T {
+        Arc::try_unwrap(ptr).unwrap_or_else(|shared| (*shared).clone())
+    }
+}
+
+impl Mode for Owned {}
+impl Mode for Shared {}
+
+/// Copying a shared node copies its links, so the children stay shared.
+impl<K: Clone, V: Clone> Clone for Node<K, V, Shared> {
+    fn clone(&self) -> Self {
+        Node {
+            key: self.key.clone(),
+            value: self.value.clone(),
+            priority: self.priority,
+            left: self.left.clone(),
+            right: self.right.clone(),
+        }
+    }
+}
+
+pub struct Map<K, V, M: Mode = Owned> {
+    root: Link<K, V, M>,
+    len: usize,
+    rng: XorShift,
+}
+
+/// A treap map whose clones are O(1) snapshots.
+pub type SharedMap<K, V> = Map<K, V, Shared>;
+
+/// Splits a treap into the keys that `goes_left` accepts and the rest. `goes_left` has to
+/// accept a prefix of the keys.
+fn split<K, V, M, F>(link: Link<K, V, M>, goes_left: &F) -> (Link<K, V, M>, Link<K, V, M>)
+where
+    M: LinksMut<Node<K, V, M>>,
+    F: Fn(&K) -> bool,
+{
+    let mut node = match link {
+        Some(node) => node,
+        None => return (None, None),
+    };
+    let n = M::make_mut(&mut node);
+    if goes_left(&n.key) {
+        // This node and its left subtree go left; the right subtree is split further
+        let (left, right) = split::<K, V, M, _>(n.right.take(), goes_left);
+        n.right = left;
+        (Some(node), right)
+    } else {
+        let (left, right) = split::<K, V, M, _>(n.left.take(), goes_left);
+        n.left = right;
+        (left, Some(node))
+    }
+}
+
+/// Joins two treaps where every key in `a` is smaller than every key in `b`.
+fn merge<K, V, M>(a: Link<K, V, M>, b: Link<K, V, M>) -> Link<K, V, M>
+where
+    M: LinksMut<Node<K, V, M>>,
+{
+    match (a, b) {
+        (None, b) => b,
+        (a, None) => a,
+        (Some(mut a), Some(mut b)) => {
+            if a.priority >= b.priority {
+                let n = M::make_mut(&mut a);
+                n.right = merge::<K, V, M>(n.right.take(), Some(b));
+                Some(a)
+            } else {
+                let n = M::make_mut(&mut b);
+                n.left = merge::<K, V, M>(Some(a), n.left.take());
+                Some(b)
+            }
+        }
+    }
+}
+
+fn get_mut<'a, K, V, M, Q>(link: &'a mut Link<K, V, M>, key: &Q) -> Option<&'a mut V>
+where
+    K: Borrow<Q> + 'a,
+    M: LinksMut<Node<K, V, M>> + 'a,
+    Q: Ord + ?Sized,
+{
+    let node = M::make_mut(link.as_mut()?);
+    match key.cmp(node.key.borrow()) {
+        Ordering::Less => get_mut::<K, V, M, Q>(&mut node.left, key),
+        Ordering::Greater => get_mut::<K, V, M, Q>(&mut node.right, key),
+        Ordering::Equal => Some(&mut node.value),
+    }
+}
+
+fn count<K, V, M: Links>(link: &Link<K, V, M>) -> usize {
+    link.as_ref().map_or(0, |node| {
+        1 + count::<K, V, M>(&node.left) + count::<K, V, M>(&node.right)
+    })
+}
+
+/// Copies an owned tree node for node, priorities and all.
+fn deep_clone<K: Clone, V: Clone>(link: &Link<K, V, Owned>) -> Link<K, V, Owned> {
+    link.as_ref().map(|node| {
+        Box::new(Node {
+            key: node.key.clone(),
+            value: node.value.clone(),
+            priority: node.priority,
+            left: deep_clone(&node.left),
+            right: deep_clone(&node.right),
+        })
+    })
+}
+
+impl<K, V> Map<K, V> {
+    /// Creates an empty Map. For one that takes snapshots, use `SharedMap::default()`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<K, V, M: Mode> Map<K, V, M> {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    pub fn clear(&mut self) {
+        self.root = None;
+        self.len = 0;
+    }
+
+    /// Returns the entry with the smallest key.
+    pub fn first_key_value(&self) -> Option<(&K, &V)> {
+        let mut node = self.root.as_deref()?;
+        while let Some(left) = node.left.as_deref() {
+            node = left;
+        }
+        Some((&node.key, &node.value))
+    }
+
+    /// Returns the entry with the largest key.
+    pub fn last_key_value(&self) -> Option<(&K, &V)> {
+        let mut node = self.root.as_deref()?;
+        while let Some(right) = node.right.as_deref() {
+            node = right;
+        }
+        Some((&node.key, &node.value))
+    }
+
+    /// In-order iteration, so keys come out sorted.
+    pub fn iter(&self) -> Iter<'_, K, V, M> {
+        let mut iter = Iter {
+            stack: Vec::new(),
+            remaining: self.len,
+        };
+        iter.push_left_spine(self.root.as_deref());
+        iter
+    }
+}
+
+impl<K: Ord, V, M: Mode> Map<K, V, M> {
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut link = self.root.as_deref();
+        while let Some(node) = link {
+            link = match key.cmp(node.key.borrow()) {
+                Ordering::Less => node.left.as_deref(),
+                Ordering::Greater => node.right.as_deref(),
+                Ordering::Equal => return Some(&node.value),
+            };
+        }
+        None
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+}
+
+impl<K, V, M> Map<K, V, M>
+where
+    K: Ord,
+    M: Mode + LinksMut<Node<K, V, M>>,
+{
+    /// Cuts the tree into the keys below `key`, the entry for `key` if there is one, and
+    /// the keys above it.
+    #[allow(clippy::type_complexity)]
+    fn split3<Q>(&mut self, key: &Q) -> (Link<K, V, M>, Link<K, V, M>, Link<K, V, M>)
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let (less, rest) = split::<K, V, M, _>(self.root.take(), &|k: &K| k.borrow() < key);
+        let (equal, more) = split::<K, V, M, _>(rest, &|k: &K| k.borrow() == key);
+        (less, equal, more)
+    }
+
+    /// Inserts a key-value pair, returning the old value if the key was already there.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let (less, equal, more) = self.split3(&key);
+        let (middle, old) = match equal {
+            Some(mut node) => {
+                let old = mem::replace(&mut M::make_mut(&mut node).value, value);
+                (node, Some(old))
+            }
+            None => {
+                self.len += 1;
+                let node = Node {
+                    key,
+                    value,
+                    priority: self.rng.next_u64(),
+                    left: None,
+                    right: None,
+                };
+                (M::new(node), None)
+            }
+        };
+        self.root = merge::<K, V, M>(merge::<K, V, M>(less, Some(middle)), more);
+        old
+    }
+
+    /// Returns a mutable reference to the value for `key`. In a shared map, the path to it
+    /// is copied first if it's shared with a snapshot.
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        get_mut::<K, V, M, Q>(&mut self.root, key)
+    }
+
+    /// Removes a key, returning its value if it was there.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.remove_entry(key).map(|(_, v)| v)
+    }
+
+    /// Removes a key, returning the stored key and value if it was there. If a snapshot
+    /// still shares the node, they're clones.
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let (less, equal, more) = self.split3(key);
+        self.root = merge::<K, V, M>(less, more);
+        let node = M::into_inner(equal?);
+        self.len -= 1;
+        Some((node.key, node.value))
+    }
+
+    /// Moves every entry with a key greater than or equal to `key` into a new map and
+    /// returns it. The split itself takes O(log n) expected time, but counting the moved
+    /// entries is linear in how many there are.
+    pub fn split<Q>(&mut self, key: &Q) -> Map<K, V, M>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let (less, more) = split::<K, V, M, _>(self.root.take(), &|k: &K| k.borrow() < key);
+        self.root = less;
+
+        // Nodes don't store subtree sizes, so the moved half has to be counted
+        let other = Map {
+            len: count::<K, V, M>(&more),
+            root: more,
+            rng: XorShift::new(self.rng.next_u64()),
+        };
+        self.len -= other.len;
+        other
+    }
+
+    /// Appends `other`, whose keys must all be greater than the keys in `self`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a key in `other` isn't greater than every key in `self`.
+    pub fn join(&mut self, mut other: Map<K, V, M>) {
+        if let (Some((max, _)), Some((min, _))) = (self.last_key_value(), other.first_key_value()) {
+            assert!(max < min, "joined map must only hold larger keys");
+        }
+        self.root = merge::<K, V, M>(self.root.take(), other.root.take());
+        self.len += other.len;
+    }
+}
+
+impl<K: Clone, V: Clone> Clone for Map<K, V> {
+    /// Copies every node, in O(n).
+    fn clone(&self) -> Self {
+        Map {
+            root: deep_clone(&self.root),
+            len: self.len,
+            rng: self.rng.clone(),
+        }
+    }
+}
+
+impl<K, V> Clone for SharedMap<K, V> {
+    /// Takes a snapshot in O(1). The two maps share all their nodes until one of them
+    /// writes.
+    fn clone(&self) -> Self {
+        Map {
+            root: self.root.clone(),
+            len: self.len,
+            rng: self.rng.clone(),
+        }
+    }
+}
+
+impl<K, V, M: Mode> Default for Map<K, V, M> {
+    fn default() -> Self {
+        Map {
+            root: None,
+            len: 0,
+            rng: XorShift::from_entropy(),
+        }
+    }
+}
+
+impl<K, V, M> FromIterator<(K, V)> for Map<K, V, M>
+where
+    K: Ord,
+    M: Mode + LinksMut<Node<K, V, M>>,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = Map::default();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K, V, M> Extend<(K, V)> for Map<K, V, M>
+where
+    K: Ord,
+    M: Mode + LinksMut<Node<K, V, M>>,
+{
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug, M: Mode> fmt::Debug for Map<K, V, M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// In-order iterator. The stack holds the nodes whose left subtree we've started but who
+/// haven't been yielded yet.
+pub struct Iter<'a, K, V, M: Mode = Owned> {
+    stack: Vec<&'a Node<K, V, M>>,
+    remaining: usize,
+}
+
+impl<'a, K, V, M: Mode> Iter<'a, K, V, M> {
+    fn push_left_spine(&mut self, mut link: Option<&'a Node<K, V, M>>) {
+        while let Some(node) = link {
+            self.stack.push(node);
+            link = node.left.as_deref();
+        }
+    }
+}
+
+impl<'a, K, V, M: Mode> Iterator for Iter<'a, K, V, M> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        self.push_left_spine(node.right.as_deref());
+        self.remaining -= 1;
+        Some((&node.key, &node.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V, M: Mode> ExactSizeIterator for Iter<'_, K, V, M> {}
+
+impl<'a, K, V, M: Mode> IntoIterator for &'a Map<K, V, M> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V, M>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::links::{LinksMut, Node};
+    use super::{Map, Mode, Owned, Shared, SharedMap};
+    use std::sync::Arc;
+
+    fn keys<V, M: Mode>(map: &Map<i32, V, M>) -> Vec<i32> {
+        map.iter().map(|(k, _)| *k).collect()
+    }
+
+    // Checks the heap order on priorities and returns the depth
+    fn check_heap<V, M: Mode>(node: Option<&Node<i32, V, M>>) -> usize {
+        match node {
+            None => 0,
+            Some(node) => {
+                for child in [&node.left, &node.right].iter().copied().flatten() {
+                    assert!(child.priority <= node.priority);
+                }
+                1 + check_heap(node.left.as_deref()).max(check_heap(node.right.as_deref()))
+            }
+        }
+    }
+
+    #[test]
+    fn basics() {
+        let mut map = Map::new();
+
+        // Check empty map behaves right
+        assert!(map.is_empty());
+        assert_eq!(map.get(&1), None);
+        assert_eq!(map.remove(&1), None);
+        assert_eq!(map.first_key_value(), None);
+
+        // Populate map
+        for k in [5, 2, 8, 1, 9, 3] {
+            assert_eq!(map.insert(k, k * 10), None);
+        }
+        assert_eq!(map.len(), 6);
+        assert_eq!(map.insert(3, 0), Some(30));
+        assert_eq!(map.len(), 6);
+
+        if let Some(value) = map.get_mut(&3) {
+            *value = 33;
+        }
+        assert_eq!(map.get(&3), Some(&33));
+        assert!(map.contains_key(&9));
+        assert!(!map.contains_key(&4));
+        assert_eq!(map.first_key_value(), Some((&1, &10)));
+        assert_eq!(map.last_key_value(), Some((&9, &90)));
+
+        assert_eq!(map.remove(&5), Some(50));
+        assert_eq!(map.remove_entry(&1), Some((1, 10)));
+        assert_eq!(map.len(), 4);
+        assert_eq!(keys(&map), vec![2, 3, 8, 9]);
+        check_heap(map.root.as_deref());
+    }
+
+    #[test]
+    fn sorted_input_stays_shallow() {
+        let n = if cfg!(miri) { 500 } else { 50_000 };
+        let map: Map<_, _> = (0..n).map(|i| (i, ())).collect();
+        // Expected depth is about 3 ln(n); a plain BST would be n deep
+        let depth = check_heap(map.root.as_deref());
+        assert!(depth < 100, "depth {}", depth);
+        assert_eq!(keys(&map), (0..n).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn split_and_join() {
+        let mut map: Map<_, _> = (0..20).map(|i| (i * 2, i)).collect();
+
+        let mut upper = map.split(&20);
+        assert_eq!(map.len(), 10);
+        assert_eq!(upper.len(), 10);
+        assert_eq!(keys(&map), (0..10).map(|i| i * 2).collect::<Vec<_>>());
+
+        let top = upper.split(&31);
+        assert_eq!(keys(&upper), vec![20, 22, 24, 26, 28, 30]);
+        assert_eq!(keys(&top), vec![32, 34, 36, 38]);
+        assert_eq!(top.len(), 4);
+
+        // Splitting past either end
+        assert!(map.split(&100).is_empty());
+        let all = map.split(&-1);
+        assert!(map.is_empty());
+        assert_eq!(map.len(), 0);
+        map = all;
+
+        map.join(upper);
+        map.join(top);
+        assert_eq!(map.len(), 20);
+        assert_eq!(keys(&map), (0..20).map(|i| i * 2).collect::<Vec<_>>());
+        check_heap(map.root.as_deref());
+    }
+
+    #[test]
+    #[should_panic(expected = "larger keys")]
+    fn join_rejects_overlap() {
+        let mut low: Map<_, _> = (0..10).map(|i| (i, ())).collect();
+        let high: Map<_, _> = (5..15).map(|i| (i, ())).collect();
+        low.join(high);
+    }
+
+    #[test]
+    fn values_need_not_clone() {
+        // The owned map moves nodes around and never copies them
+        struct Opaque(i32);
+        let mut map = Map::new();
+        for k in 0..10 {
+            map.insert(k, Opaque(k));
+        }
+        map.get_mut(&3).unwrap().0 = 33;
+        let upper = map.split(&5);
+        assert_eq!(map.remove(&3).map(|v| v.0), Some(33));
+        map.join(upper);
+        assert_eq!(map.len(), 9);
+        assert!(map.iter().all(|(k, v)| *k == v.0));
+    }
+
+    #[test]
+    fn owned_clone_is_deep() {
+        let map: Map<_, _> = (0..10).map(|i| (i, i)).collect();
+        let mut copy = map.clone();
+        *copy.get_mut(&4).unwrap() = -4;
+        copy.remove(&5);
+        assert_eq!(map.get(&4), Some(&4));
+        assert_eq!(map.len(), 10);
+        assert_eq!(keys(&copy), vec![0, 1, 2, 3, 4, 6, 7, 8, 9]);
+        assert_eq!(
+            check_heap(map.root.as_deref()),
+            check_heap(map.clone().root.as_deref())
+        );
+    }
+
+    #[test]
+    fn snapshots() {
+        let mut map: SharedMap<_, _> = (0..100).map(|i| (i, i)).collect();
+        let snapshot = map.clone();
+
+        map.insert(1000, 1000);
+        map.remove(&50);
+        *map.get_mut(&7).unwrap() = -7;
+
+        // The snapshot never sees any of it
+        assert_eq!(snapshot.len(), 100);
+        assert_eq!(snapshot.get(&50), Some(&50));
+        assert_eq!(snapshot.get(&7), Some(&7));
+        assert_eq!(snapshot.get(&1000), None);
+        assert_eq!(map.get(&7), Some(&-7));
+        assert_eq!(map.len(), 100);
+
+        // Only a few paths were copied; most nodes are still shared
+        let mut shared = 0;
+        let mut stack = vec![&map.root];
+        while let Some(link) = stack.pop() {
+            if let Some(node) = link {
+                if Arc::strong_count(node) > 1 {
+                    shared += 1;
+                    continue;
+                }
+                stack.push(&node.left);
+                stack.push(&node.right);
+            }
+        }
+        assert!(shared > 0);
+    }
+
+    #[test]
+    fn borrowed_keys() {
+        let mut map = Map::new();
+        map.insert("b".to_string(), 2);
+        map.insert("a".to_string(), 1);
+        assert_eq!(map.get("a"), Some(&1));
+        let upper = map.split("b");
+        assert_eq!(format!("{:?} {:?}", map, upper), r#"{"a": 1} {"b": 2}"#);
+    }
+
+    #[test]
+    fn against_btreemap() {
+        against_btreemap_in::<Owned>();
+        against_btreemap_in::<Shared>();
+    }
+
+    fn against_btreemap_in<M>()
+    where
+        M: Mode + LinksMut<Node<i32, u32, M>>,
+    {
+        use std::collections::BTreeMap;
+        let mut map = Map::<i32, u32, M>::default();
+        let mut model = BTreeMap::new();
+        let mut x: u32 = 7;
+        for _ in 0..5_000 {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let key = ((x >> 16) % 500) as i32;
+            if x.is_multiple_of(3) {
+                assert_eq!(map.remove(&key), model.remove(&key));
+            } else {
+                assert_eq!(map.insert(key, x), model.insert(key, x));
+            }
+            if x.is_multiple_of(7) {
+                // Cutting the tree apart and back together leaves the same entries
+                let upper = map.split(&key);
+                map.join(upper);
+            }
+        }
+        check_heap(map.root.as_deref());
+        assert_eq!(map.len(), model.len());
+        assert!(map.iter().eq(model.iter()));
+    }
+}
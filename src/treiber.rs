@@ -0,0 +1,266 @@
+//! # Treiber lock-free stack
+//!
+//! The lock-free stack from Treiber's 1986 report: a singly-linked list whose only shared
+//! state is the atomic `head` pointer. Pushing links a new node in front of the head we saw
+//! and CASes `head` over to it; popping CASes `head` over to the second node. Whoever loses a
+//! CAS just reads `head` again and retries.
+//!
+//! ```text
+//! head
+//!  |
+//!  v
+//! [c] -> [b] -> [a] -> null
+//! ```
+//!
+//! ## Reclamation
+//!
+//! A popper reads `(*head).next` before it knows whether its CAS will win, so the node it
+//! read may be popped, freed and (without care) reallocated as someone else's new head in
+//! the meantime. That's both a use-after-free and the ABA problem: the CAS would succeed on
+//! a recycled address and install a stale `next`. Running every operation pinned and
+//! handing popped nodes to the crate's epoch collector (see [`crate::epoch`]) rules out
+//! both, since no node is freed, and so no address reused, while anyone who might have
+//! read it is still pinned. [`crate::ms_queue`] reclaims its old dummies the same way.
+
+use crate::epoch;
+use std::mem::ManuallyDrop;
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, Ordering::SeqCst};
+
+pub struct Stack<T> {
+    head: AtomicPtr<Node<T>>,
+}
+
+struct Node<T> {
+    // Moved out by the popper that unlinks the node, so the deferred free mustn't drop it
+    elem: ManuallyDrop<T>,
+    next: *mut Node<T>,
+}
+
+impl<T> Stack<T> {
+    /// Creates an empty Stack.
+    pub fn new() -> Self {
+        Stack {
+            head: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    /// Pushes an element onto the top of the stack.
+    pub fn push(&self, elem: T) {
+        let node = Box::into_raw(Box::new(Node {
+            elem: ManuallyDrop::new(elem),
+            next: ptr::null_mut(),
+        }));
+        // We never read through another thread's node here, so there's nothing to pin for
+        loop {
+            let head = self.head.load(SeqCst);
+            unsafe { (*node).next = head };
+            if self
+                .head
+                .compare_exchange(head, node, SeqCst, SeqCst)
+                .is_ok()
+            {
+                return;
+            }
+        }
+    }
+
+    /// Removes and returns the top element.
+    pub fn pop(&self) -> Option<T> {
+        let guard = epoch::pin();
+
+        loop {
+            let head = self.head.load(SeqCst);
+            if head.is_null() {
+                return None;
+            }
+            // Safe to read while pinned, even if another popper unlinks it right now
+            let next = unsafe { (*head).next };
+
+            if self
+                .head
+                .compare_exchange(head, next, SeqCst, SeqCst)
+                .is_ok()
+            {
+                // We won `head`: its element is ours, and the node goes once nobody can see it
+                let elem = unsafe { ptr::read(&*(*head).elem) };
+                unsafe { guard.defer_destroy(head) };
+                return Some(elem);
+            }
+        }
+    }
+
+    /// Returns true if the stack had no elements at the moment we looked.
+    pub fn is_empty(&self) -> bool {
+        self.head.load(SeqCst).is_null()
+    }
+}
+
+impl<T> Default for Stack<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for Stack<T> {
+    fn drop(&mut self) {
+        // `&mut self` means nobody else is around, so the nodes can go right away
+        let mut cur = *self.head.get_mut();
+        while !cur.is_null() {
+            let mut node = unsafe { Box::from_raw(cur) };
+            unsafe { ManuallyDrop::drop(&mut node.elem) };
+            cur = node.next;
+        }
+    }
+}
+
+unsafe impl<T: Send> Send for Stack<T> {}
+unsafe impl<T: Send> Sync for Stack<T> {}
+
+#[cfg(test)]
+mod test {
+    use super::Stack;
+    use crate::epoch;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    struct Counted(Arc<AtomicUsize>);
+
+    impl Drop for Counted {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn basics() {
+        let stack = Stack::new();
+
+        // Check empty stack behaves right
+        assert_eq!(stack.pop(), None);
+        assert!(stack.is_empty());
+
+        // Populate stack
+        stack.push(1);
+        stack.push(2);
+        stack.push(3);
+        assert!(!stack.is_empty());
+
+        // Check normal removal
+        assert_eq!(stack.pop(), Some(3));
+        assert_eq!(stack.pop(), Some(2));
+
+        // Push some more just to make sure nothing's corrupted
+        stack.push(4);
+        stack.push(5);
+
+        // Check normal removal
+        assert_eq!(stack.pop(), Some(5));
+        assert_eq!(stack.pop(), Some(4));
+
+        // Check exhaustion
+        assert_eq!(stack.pop(), Some(1));
+        assert_eq!(stack.pop(), None);
+        assert!(stack.is_empty());
+    }
+
+    #[test]
+    fn drops_every_value_once() {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let stack = Stack::new();
+        for _ in 0..10 {
+            stack.push(Counted(drops.clone()));
+        }
+        for _ in 0..4 {
+            drop(stack.pop());
+        }
+        assert_eq!(drops.load(Ordering::SeqCst), 4);
+
+        // The rest are dropped with the stack
+        drop(stack);
+        assert_eq!(drops.load(Ordering::SeqCst), 10);
+    }
+
+    #[test]
+    fn concurrent_drops_every_value_once() {
+        let per_thread = if cfg!(miri) { 50 } else { 10_000 };
+        let threads = 4;
+        let drops = Arc::new(AtomicUsize::new(0));
+        let stack = Arc::new(Stack::new());
+
+        // Every thread pushes and pops at once, so nodes are retired while others read them
+        let handles: Vec<_> = (0..threads)
+            .map(|_| {
+                let stack = stack.clone();
+                let drops = drops.clone();
+                thread::spawn(move || {
+                    for i in 0..per_thread {
+                        stack.push(Counted(drops.clone()));
+                        if i % 2 == 0 {
+                            drop(stack.pop());
+                        }
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(drops.load(Ordering::SeqCst), threads * per_thread / 2);
+
+        // Whatever's left is dropped exactly once with the stack, and freeing the popped
+        // nodes through the collector drops nothing more
+        drop(Arc::try_unwrap(stack).ok().unwrap());
+        for _ in 0..100 {
+            epoch::pin().flush();
+        }
+        assert_eq!(drops.load(Ordering::SeqCst), threads * per_thread);
+    }
+
+    #[test]
+    fn mpmc() {
+        let per_thread = if cfg!(miri) { 50 } else { 10_000 };
+        let producers = 4;
+        let consumers = 4;
+        let n = producers * per_thread;
+
+        let stack = Arc::new(Stack::new());
+        let received = Arc::new(AtomicUsize::new(0));
+        let sum = Arc::new(AtomicUsize::new(0));
+
+        let mut handles = Vec::new();
+        for p in 0..producers {
+            let stack = stack.clone();
+            handles.push(thread::spawn(move || {
+                for i in 0..per_thread {
+                    stack.push(p * per_thread + i);
+                }
+            }));
+        }
+        for _ in 0..consumers {
+            let stack = stack.clone();
+            let received = received.clone();
+            let sum = sum.clone();
+            handles.push(thread::spawn(move || {
+                while received.load(Ordering::SeqCst) < n {
+                    match stack.pop() {
+                        Some(value) => {
+                            sum.fetch_add(value, Ordering::SeqCst);
+                            received.fetch_add(1, Ordering::SeqCst);
+                        }
+                        None => thread::yield_now(),
+                    }
+                }
+            }));
+        }
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        // Every value came out exactly once
+        assert_eq!(received.load(Ordering::SeqCst), n);
+        assert_eq!(sum.load(Ordering::SeqCst), n * (n - 1) / 2);
+        assert!(stack.is_empty());
+    }
+}
@@ -0,0 +1,440 @@
+//! # Trie
+//!
+//! A prefix tree keyed by byte strings. Every node stands for the prefix spelled out by the
+//! path from the root to it, with one child per possible next byte. A node holds a value
+//! when that prefix is a key in its own right.
+//!
+//! ```text
+//! keys: "to", "tea", "ten", "in", "inn"
+//!
+//!            (root)
+//!           /      \
+//!          t        i
+//!         / \        \
+//!       [o]  e       [n]          [ ] marks a node holding a value
+//!           / \        \
+//!         [a] [n]      [n]
+//! ```
+//!
+//! A lookup costs one step per byte of the key, however many keys there are. Keys that
+//! share a prefix share its nodes. All keys under a prefix sit in one subtree, which makes
+//! autocompletion ([`Trie::iter_prefix`]) and routing-table lookups
+//! ([`Trie::longest_prefix_match`]) straightforward. Children are kept sorted, so
+//! iteration goes in lexicographic order.
+//!
+//! The price is one node per byte, most of them with a single child. [`crate::radix_trie`]
+//! collapses those chains into single edges.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::iter::FromIterator;
+use std::mem;
+
+pub struct Trie<V> {
+    pub(crate) root: Node<V>,
+    len: usize,
+}
+
+pub(crate) struct Node<V> {
+    pub(crate) value: Option<V>,
+    pub(crate) children: BTreeMap<u8, Node<V>>,
+}
+
+impl<V> Node<V> {
+    pub(crate) fn new() -> Self {
+        Node {
+            value: None,
+            children: BTreeMap::new(),
+        }
+    }
+}
+
+impl<V> Trie<V> {
+    /// Creates an empty Trie.
+    pub fn new() -> Self {
+        Trie {
+            root: Node::new(),
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        *self = Trie::new();
+    }
+
+    /// Returns how many nodes the trie is made of, the root included.
+    pub fn node_count(&self) -> usize {
+        let mut count = 0;
+        let mut stack = vec![&self.root];
+        while let Some(node) = stack.pop() {
+            count += 1;
+            stack.extend(node.children.values());
+        }
+        count
+    }
+
+    fn find(&self, key: &[u8]) -> Option<&Node<V>> {
+        let mut node = &self.root;
+        for byte in key {
+            node = node.children.get(byte)?;
+        }
+        Some(node)
+    }
+
+    /// Inserts a key-value pair, returning the old value if the key was already there.
+    pub fn insert<K>(&mut self, key: &K, value: V) -> Option<V>
+    where
+        K: AsRef<[u8]> + ?Sized,
+    {
+        let mut node = &mut self.root;
+        for &byte in key.as_ref() {
+            node = node.children.entry(byte).or_insert_with(Node::new);
+        }
+        let old = node.value.replace(value);
+        if old.is_none() {
+            self.len += 1;
+        }
+        old
+    }
+
+    pub fn get<K>(&self, key: &K) -> Option<&V>
+    where
+        K: AsRef<[u8]> + ?Sized,
+    {
+        self.find(key.as_ref())?.value.as_ref()
+    }
+
+    pub fn get_mut<K>(&mut self, key: &K) -> Option<&mut V>
+    where
+        K: AsRef<[u8]> + ?Sized,
+    {
+        let mut node = &mut self.root;
+        for byte in key.as_ref() {
+            node = node.children.get_mut(byte)?;
+        }
+        node.value.as_mut()
+    }
+
+    pub fn contains_key<K>(&self, key: &K) -> bool
+    where
+        K: AsRef<[u8]> + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    /// Removes a key, returning its value if it was there. Nodes that no longer lead to any
+    /// key are freed.
+    pub fn remove<K>(&mut self, key: &K) -> Option<V>
+    where
+        K: AsRef<[u8]> + ?Sized,
+    {
+        let key = key.as_ref();
+
+        // Find where the branch leading only to this key starts: below the last node on
+        // the way that holds a value of its own or leads somewhere else too
+        let mut cut = 0;
+        let mut node = &self.root;
+        for (depth, byte) in key.iter().enumerate() {
+            if node.value.is_some() || node.children.len() > 1 {
+                cut = depth;
+            }
+            node = node.children.get(byte)?;
+        }
+        node.value.as_ref()?;
+        self.len -= 1;
+
+        if key.is_empty() || !node.children.is_empty() {
+            // Other keys go through this node, so only the value goes
+            let mut node = &mut self.root;
+            for byte in key {
+                node = node.children.get_mut(byte).unwrap();
+            }
+            return node.value.take();
+        }
+
+        // Unhook the branch, then take it apart one node at a time
+        let mut node = &mut self.root;
+        for byte in &key[..cut] {
+            node = node.children.get_mut(byte).unwrap();
+        }
+        let mut branch = node.children.remove(&key[cut]).unwrap();
+        for byte in &key[cut + 1..] {
+            branch = branch.children.remove(byte).unwrap();
+        }
+        branch.value.take()
+    }
+
+    /// Returns the longest prefix of `key` that is itself a key, along with its value. This
+    /// is the lookup a router does to pick the most specific route for an address.
+    pub fn longest_prefix_match<'k, K>(&self, key: &'k K) -> Option<(&'k [u8], &V)>
+    where
+        K: AsRef<[u8]> + ?Sized,
+    {
+        let key = key.as_ref();
+        let mut best = self.root.value.as_ref().map(|value| (0, value));
+        let mut node = &self.root;
+        for (i, byte) in key.iter().enumerate() {
+            node = match node.children.get(byte) {
+                Some(child) => child,
+                None => break,
+            };
+            if let Some(value) = &node.value {
+                best = Some((i + 1, value));
+            }
+        }
+        best.map(|(len, value)| (&key[..len], value))
+    }
+
+    /// Iterates over every key starting with `prefix`, in lexicographic order.
+    pub fn iter_prefix<K>(&self, prefix: &K) -> Iter<'_, V>
+    where
+        K: AsRef<[u8]> + ?Sized,
+    {
+        let prefix = prefix.as_ref();
+        Iter {
+            key: prefix.to_vec(),
+            stack: self
+                .find(prefix)
+                .map(|node| (prefix.len(), None, node))
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    /// Iterates over every key in lexicographic order.
+    pub fn iter(&self) -> Iter<'_, V> {
+        self.iter_prefix(&[])
+    }
+}
+
+impl<V> Default for Trie<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: AsRef<[u8]>, V> FromIterator<(K, V)> for Trie<V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut trie = Trie::new();
+        trie.extend(iter);
+        trie
+    }
+}
+
+impl<K: AsRef<[u8]>, V> Extend<(K, V)> for Trie<V> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (key, value) in iter {
+            self.insert(&key, value);
+        }
+    }
+}
+
+impl<V: fmt::Debug> fmt::Debug for Trie<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(
+                self.iter()
+                    .map(|(key, value)| (String::from_utf8_lossy(&key).into_owned(), value)),
+            )
+            .finish()
+    }
+}
+
+/// Depth-first iterator over keys and values. Keys are built up as it goes, so it yields
+/// them owned.
+pub struct Iter<'a, V> {
+    // The key of the node visited last
+    key: Vec<u8>,
+    // Nodes still to visit, the next one on top, with the length of their parent's key
+    // and the byte that leads to them
+    stack: Vec<(usize, Option<u8>, &'a Node<V>)>,
+}
+
+impl<'a, V> Iterator for Iter<'a, V> {
+    type Item = (Vec<u8>, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((depth, byte, node)) = self.stack.pop() {
+            self.key.truncate(depth);
+            self.key.extend(byte);
+            // Children go on in reverse, so the smallest byte comes off first
+            for (&byte, child) in node.children.iter().rev() {
+                self.stack.push((self.key.len(), Some(byte), child));
+            }
+            if let Some(value) = &node.value {
+                return Some((self.key.clone(), value));
+            }
+        }
+        None
+    }
+}
+
+impl<'a, V> IntoIterator for &'a Trie<V> {
+    type Item = (Vec<u8>, &'a V);
+    type IntoIter = Iter<'a, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<V> Drop for Trie<V> {
+    fn drop(&mut self) {
+        // One node per byte makes long keys deep chains; free them without recursing
+        let mut stack = vec![mem::replace(&mut self.root, Node::new())];
+        while let Some(mut node) = stack.pop() {
+            stack.extend(mem::take(&mut node.children).into_values());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Trie;
+
+    fn keys<V>(iter: super::Iter<'_, V>) -> Vec<String> {
+        iter.map(|(key, _)| String::from_utf8(key).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn basics() {
+        let mut trie = Trie::new();
+
+        // Check empty trie behaves right
+        assert!(trie.is_empty());
+        assert_eq!(trie.get("a"), None);
+        assert_eq!(trie.remove("a"), None);
+        assert_eq!(trie.node_count(), 1);
+
+        // Populate trie
+        for (i, key) in ["to", "tea", "ten", "in", "inn"].iter().enumerate() {
+            assert_eq!(trie.insert(*key, i), None);
+        }
+        assert_eq!(trie.len(), 5);
+        assert_eq!(trie.node_count(), 9);
+        assert_eq!(trie.insert("ten", 20), Some(2));
+        assert_eq!(trie.get("ten"), Some(&20));
+        *trie.get_mut("to").unwrap() += 10;
+        assert_eq!(trie.get("to"), Some(&10));
+
+        // Prefixes of keys aren't keys themselves
+        assert_eq!(trie.get("te"), None);
+        assert!(!trie.contains_key("t"));
+        assert!(trie.contains_key(&b"inn"[..]));
+        assert!(trie.contains_key(&String::from("in")));
+
+        // The empty string is a key like any other
+        trie.insert("", 99);
+        assert_eq!(trie.get(""), Some(&99));
+        assert_eq!(trie.len(), 6);
+        assert_eq!(
+            format!("{:?}", trie),
+            r#"{"": 99, "in": 3, "inn": 4, "tea": 1, "ten": 20, "to": 10}"#
+        );
+    }
+
+    #[test]
+    fn remove_prunes() {
+        let mut trie = Trie::new();
+        trie.insert("tea", 1);
+        trie.insert("ten", 2);
+        trie.insert("te", 3);
+        assert_eq!(trie.node_count(), 5);
+
+        // "te" still leads to "ten", so only the value goes
+        assert_eq!(trie.remove("te"), Some(3));
+        assert_eq!(trie.node_count(), 5);
+        assert_eq!(trie.remove("tea"), Some(1));
+        assert_eq!(trie.node_count(), 4);
+        assert_eq!(trie.remove("tea"), None);
+        assert_eq!(trie.remove("ten"), Some(2));
+        assert_eq!(trie.node_count(), 1);
+        assert!(trie.is_empty());
+    }
+
+    #[test]
+    fn autocomplete() {
+        let trie: Trie<_> = ["car", "card", "care", "careful", "cat", "dog", "ca"]
+            .iter()
+            .map(|word| (word, ()))
+            .collect();
+
+        assert_eq!(
+            keys(trie.iter_prefix("car")),
+            vec!["car", "card", "care", "careful"]
+        );
+        assert_eq!(keys(trie.iter_prefix("care")), vec!["care", "careful"]);
+        assert_eq!(keys(trie.iter_prefix("cx")), Vec::<String>::new());
+        assert_eq!(trie.iter().count(), 7);
+        assert_eq!(keys(trie.iter())[..3], ["ca", "car", "card"]);
+    }
+
+    #[test]
+    fn routing_table() {
+        let mut routes = Trie::new();
+        routes.insert("/", "index");
+        routes.insert("/api/", "api");
+        routes.insert("/api/users/", "users");
+
+        let route = |path: &'static str| {
+            routes
+                .longest_prefix_match(path)
+                .map(|(prefix, handler)| (std::str::from_utf8(prefix).unwrap(), *handler))
+        };
+        assert_eq!(route("/api/users/42"), Some(("/api/users/", "users")));
+        assert_eq!(route("/api/orders"), Some(("/api/", "api")));
+        assert_eq!(route("/about"), Some(("/", "index")));
+        assert_eq!(route("nope"), None);
+        assert_eq!(route("/api/"), Some(("/api/", "api")));
+    }
+
+    #[test]
+    fn long_keys_drop() {
+        let mut trie = Trie::new();
+        let n = if cfg!(miri) { 1_000 } else { 100_000 };
+        trie.insert(&vec![b'x'; n], ());
+        trie.insert(&vec![b'x'; n + 1], ());
+        assert_eq!(trie.node_count(), n + 2);
+        assert_eq!(
+            trie.iter().map(|(key, _)| key.len()).sum::<usize>(),
+            2 * n + 1
+        );
+        assert_eq!(trie.remove(&vec![b'x'; n + 1]), Some(()));
+        assert_eq!(trie.node_count(), n + 1);
+    }
+
+    #[test]
+    fn against_btreemap() {
+        use std::collections::BTreeMap;
+        let mut trie = Trie::new();
+        let mut model = BTreeMap::new();
+        let mut x: u32 = 1;
+        for i in 0..3_000 {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            // Short keys over a tiny alphabet, so they share plenty of prefixes
+            let len = (x >> 28) as usize % 6;
+            let key: Vec<u8> = (0..len)
+                .map(|j| b'a' + ((x >> (j * 3)) & 3) as u8)
+                .collect();
+            if i % 3 == 0 {
+                assert_eq!(trie.remove(&key), model.remove(&key));
+            } else {
+                assert_eq!(trie.insert(&key, i), model.insert(key, i));
+            }
+        }
+        assert_eq!(trie.len(), model.len());
+        assert!(trie.iter().eq(model.iter().map(|(k, v)| (k.clone(), v))));
+        let expected: Vec<_> = model.keys().filter(|k| k.starts_with(b"ab")).collect();
+        let found: Vec<_> = trie.iter_prefix("ab").map(|(k, _)| k).collect();
+        assert_eq!(found.iter().collect::<Vec<_>>(), expected);
+    }
+}
@@ -0,0 +1,365 @@
+//! # TTL cache
+//!
+//! A map whose entries expire a fixed time after they're put. Time is whatever `u64` clock
+//! the caller keeps, milliseconds since start say, and is passed in to every call, so
+//! nothing here reads a clock or sleeps.
+//!
+//! Checking an entry's deadline when it's looked up is enough for `get`, but expired
+//! entries nobody asks about again would stay forever. [`TtlCache::evict_expired`] finds
+//! them without scanning every entry, using a hierarchical timing wheel (Varghese and
+//! Lauck, 1987). Each level of the wheel is 64 slots, and each slot is a [`List`] deque of
+//! timers. A slot on level 0 is one tick wide, a slot on level 1 is 64 ticks, a slot on
+//! level 2 is 4096, and so on. Eleven levels cover every `u64` deadline:
+//!
+//! ```text
+//!   level 2   [    0    |  4096   |  8192   | ... ]     64 slots of 4096 ticks
+//!   level 1   [  0  | 64  | 128 | ... ]                 64 slots of 64 ticks
+//!   level 0   [0|1|2|3| ... ]                           64 slots of 1 tick
+//! ```
+//!
+//! A timer goes on the lowest level whose slots can still tell its deadline apart from
+//! now. That level is given by the highest bit where the two differ. When time reaches a
+//! level 0 slot, its timers have expired. When it reaches a slot on a higher level, that
+//! slot's timers are due within its width, and they *cascade*: each moves down to a finer
+//! level. Every timer moves down at most once per level, and a per-level bitmap of
+//! nonempty slots lets time jump straight to the next one that needs attention.
+//!
+//! Replacing or removing an entry leaves its old timer in the wheel. Each timer carries
+//! the id of the put that made it, and when it comes due, it's dropped unless it still
+//! matches its entry.
+
+use crate::deque::List;
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+const SLOT_BITS: u32 = 6;
+const SLOTS: usize = 1 << SLOT_BITS;
+const LEVELS: usize = 11;
+
+pub struct TtlCache<K, V> {
+    map: HashMap<K, Entry<V>>,
+    wheel: Vec<Level<K>>,
+    // The time the wheel has been advanced to
+    now: u64,
+    ttl: u64,
+    next_id: u64,
+}
+
+struct Entry<V> {
+    value: V,
+    expires: u64,
+    id: u64,
+}
+
+struct Timer<K> {
+    key: K,
+    expires: u64,
+    id: u64,
+}
+
+struct Level<K> {
+    slots: Vec<List<Timer<K>>>,
+    // Bit s is set when slot s may hold timers
+    occupied: u64,
+}
+
+/// The level for a timer due at `expires`: the one holding the highest bit where it and
+/// `now` differ.
+fn level_for(now: u64, expires: u64) -> usize {
+    let masked = (now ^ expires) | (SLOTS as u64 - 1);
+    let significant = 63 - masked.leading_zeros();
+    (significant / SLOT_BITS) as usize
+}
+
+fn slot_for(expires: u64, level: usize) -> usize {
+    (expires >> (level as u32 * SLOT_BITS)) as usize & (SLOTS - 1)
+}
+
+/// The time slot `slot` of `level` comes due, given the wheel is at `now`.
+fn slot_start(now: u64, level: usize, slot: usize) -> u64 {
+    let width = level as u32 * SLOT_BITS;
+    let above = width + SLOT_BITS;
+    let base = if above >= 64 {
+        0
+    } else {
+        now >> above << above
+    };
+    base | (slot as u64) << width
+}
+
+impl<K: Hash + Eq + Clone, V> TtlCache<K, V> {
+    /// Creates an empty TtlCache whose entries live for `ttl`
+    /// ticks.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `ttl` is zero.
+    pub fn new(ttl: u64) -> Self {
+        assert!(ttl > 0, "ttl must be at least 1");
+        TtlCache {
+            map: HashMap::new(),
+            wheel: (0..LEVELS)
+                .map(|_| Level {
+                    slots: (0..SLOTS).map(|_| List::new()).collect(),
+                    occupied: 0,
+                })
+                .collect(),
+            now: 0,
+            ttl,
+            next_id: 0,
+        }
+    }
+
+    pub fn ttl(&self) -> u64 {
+        self.ttl
+    }
+
+    /// Number of entries, counting expired ones not evicted yet.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Inserts an entry that expires `ttl` ticks after `now`. Returns the old value under
+    /// the same key, unless it had expired.
+    pub fn put(&mut self, key: K, value: V, now: u64) -> Option<V> {
+        self.put_with_ttl(key, value, self.ttl, now)
+    }
+
+    /// Inserts an entry that expires `ttl` ticks after `now`, whatever the cache's own
+    /// `ttl`.
+    pub fn put_with_ttl(&mut self, key: K, value: V, ttl: u64, now: u64) -> Option<V> {
+        // A clock that went backwards counts as standing still
+        let now = now.max(self.now);
+        let expires = now.saturating_add(ttl.max(1));
+        let id = self.next_id;
+        self.next_id += 1;
+        self.schedule(Timer {
+            key: key.clone(),
+            expires,
+            id,
+        });
+        let old = self.map.insert(key, Entry { value, expires, id })?;
+        (old.expires > now).then_some(old.value)
+    }
+
+    fn schedule(&mut self, timer: Timer<K>) {
+        let level = level_for(self.now, timer.expires);
+        let slot = slot_for(timer.expires, level);
+        self.wheel[level].slots[slot].push_back(timer);
+        self.wheel[level].occupied |= 1 << slot;
+    }
+
+    /// Returns the value under `key` if it hasn't expired by `now`. An expired one is
+    /// evicted there and then.
+    pub fn get<Q>(&mut self, key: &Q, now: u64) -> Option<&V>
+    where
+        Q: Hash + Eq + ?Sized,
+        K: Borrow<Q>,
+    {
+        if self.map.get(key)?.expires <= now {
+            self.map.remove(key);
+            return None;
+        }
+        self.map.get(key).map(|entry| &entry.value)
+    }
+
+    /// Returns when the entry under `key` expires, expired or not.
+    pub fn expires_at<Q>(&self, key: &Q) -> Option<u64>
+    where
+        Q: Hash + Eq + ?Sized,
+        K: Borrow<Q>,
+    {
+        self.map.get(key).map(|entry| entry.expires)
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        Q: Hash + Eq + ?Sized,
+        K: Borrow<Q>,
+    {
+        self.map.remove(key).map(|entry| entry.value)
+    }
+
+    /// Advances the wheel to `now` and removes every entry that has expired by then,
+    /// returning them in order of expiry.
+    pub fn evict_expired(&mut self, now: u64) -> Vec<(K, V)> {
+        let mut expired = Vec::new();
+        while let Some((level, slot)) = self.next_slot() {
+            let start = slot_start(self.now, level, slot);
+            if start > now {
+                break;
+            }
+            self.now = start;
+            self.wheel[level].occupied &= !(1 << slot);
+            let timers = std::mem::take(&mut self.wheel[level].slots[slot]);
+            for timer in timers {
+                let live = self.map.get(&timer.key).is_some_and(|e| e.id == timer.id);
+                if !live {
+                    // Replaced or removed since
+                    continue;
+                }
+                if timer.expires <= self.now {
+                    let entry = self.map.remove(&timer.key).unwrap();
+                    expired.push((timer.key, entry.value));
+                } else {
+                    self.schedule(timer);
+                }
+            }
+        }
+        self.now = self.now.max(now);
+        expired
+    }
+
+    /// The first nonempty slot of the lowest level that has one, which is the next to
+    /// come due.
+    fn next_slot(&self) -> Option<(usize, usize)> {
+        self.wheel
+            .iter()
+            .position(|level| level.occupied != 0)
+            .map(|level| (level, self.wheel[level].occupied.trailing_zeros() as usize))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{TtlCache, LEVELS, SLOTS};
+    use std::collections::HashMap;
+
+    /// Checks every timer sits in the slot its deadline maps to, and the bitmaps agree.
+    fn check<K: std::hash::Hash + Eq + Clone, V>(cache: &mut TtlCache<K, V>) {
+        for level in 0..LEVELS {
+            for slot in 0..SLOTS {
+                let timers: Vec<_> = std::mem::take(&mut cache.wheel[level].slots[slot])
+                    .into_iter()
+                    .collect();
+                if !timers.is_empty() {
+                    assert!(cache.wheel[level].occupied >> slot & 1 == 1);
+                }
+                for timer in timers {
+                    assert!(timer.expires > cache.now);
+                    assert_eq!(super::level_for(cache.now, timer.expires), level);
+                    assert_eq!(super::slot_for(timer.expires, level), slot);
+                    cache.wheel[level].slots[slot].push_back(timer);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn basics() {
+        let mut cache = TtlCache::new(10);
+
+        // Check empty cache behaves right
+        assert_eq!(cache.get("a", 0), None);
+        assert!(cache.evict_expired(100).is_empty());
+
+        // Populate cache
+        assert_eq!(cache.put("a", 1, 100), None);
+        assert_eq!(cache.put("b", 2, 105), None);
+        assert_eq!(cache.put_with_ttl("c", 3, 1_000, 105), None);
+        assert_eq!(cache.expires_at("b"), Some(115));
+        check(&mut cache);
+
+        // Alive up to its deadline, and gone from it on
+        assert_eq!(cache.get("a", 109), Some(&1));
+        assert_eq!(cache.get("a", 110), None);
+        assert_eq!(cache.len(), 2);
+
+        // Nobody looks at "b" again, so the wheel finds it
+        assert!(cache.evict_expired(114).is_empty());
+        assert_eq!(cache.evict_expired(115), vec![("b", 2)]);
+        check(&mut cache);
+
+        // Putting again moves the deadline, and leaves the old timer to be skipped
+        assert_eq!(cache.put("c", 30, 500), Some(3));
+        assert!(cache.evict_expired(509).is_empty());
+        assert_eq!(cache.evict_expired(1_105), vec![("c", 30)]);
+        assert_eq!(cache.put("c", 300, 2_000), None);
+
+        // Check exhaustion
+        assert_eq!(cache.evict_expired(2_009), vec![]);
+        assert_eq!(cache.evict_expired(2_010), vec![("c", 300)]);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "ttl must be at least 1")]
+    fn zero_ttl() {
+        TtlCache::<u32, u32>::new(0);
+    }
+
+    #[test]
+    fn cascades() {
+        // Deadlines far apart land on high levels and have to work their way down
+        let mut cache = TtlCache::new(1);
+        let deadlines = [3, 64, 100, 4_096, 5_000, 300_000, 1 << 40, u64::MAX];
+        for (i, &at) in deadlines.iter().enumerate() {
+            cache.put_with_ttl(i, at, at, 0);
+        }
+        check(&mut cache);
+        for &at in &deadlines {
+            assert!(cache.evict_expired(at - 1).iter().all(|&(_, v)| v < at));
+            check(&mut cache);
+            assert_eq!(cache.evict_expired(at).last().map(|&(_, v)| v), Some(at));
+        }
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn against_model() {
+        let mut cache = TtlCache::new(500);
+        let mut model: HashMap<u32, (u32, u64)> = HashMap::new();
+        let mut now = 0;
+        let mut x: u32 = 1;
+        for i in 0..10_000 {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let key = (x >> 8) % 100;
+            // Mostly small steps, now and then a long one
+            now += match x % 100 {
+                0 => 10_000,
+                1..=9 => 300,
+                _ => (x >> 20) as u64 % 5,
+            };
+            match x >> 30 {
+                0 => {
+                    let deadlines = model.clone();
+                    let mut expected: Vec<_> = model
+                        .iter()
+                        .filter(|&(_, &(_, expires))| expires <= now)
+                        .map(|(&k, &(v, _))| (k, v))
+                        .collect();
+                    model.retain(|_, &mut (_, expires)| expires > now);
+                    let mut evicted = cache.evict_expired(now);
+                    // In order of expiry
+                    let order: Vec<_> = evicted.iter().map(|(k, _)| deadlines[k].1).collect();
+                    assert!(order.windows(2).all(|w| w[0] <= w[1]));
+                    expected.sort_unstable();
+                    evicted.sort_unstable();
+                    assert_eq!(evicted, expected);
+                }
+                1 => {
+                    let alive = model.get(&key).filter(|&&(_, expires)| expires > now);
+                    assert_eq!(cache.get(&key, now), alive.map(|(v, _)| v));
+                    if alive.is_none() {
+                        model.remove(&key);
+                    }
+                }
+                _ => {
+                    let ttl = if i % 7 == 0 { 5_000 } else { 500 };
+                    let old = model.insert(key, (i, now + ttl));
+                    let old = old.filter(|&(_, expires)| expires > now).map(|(v, _)| v);
+                    assert_eq!(cache.put_with_ttl(key, i, ttl, now), old);
+                }
+            }
+            assert_eq!(cache.len(), model.len());
+            if i % 500 == 0 {
+                check(&mut cache);
+            }
+        }
+    }
+}
@@ -0,0 +1,320 @@
+//! # Union-find
+//!
+//! A disjoint-set forest (Galler and Fischer, 1964) over the elements `0..n`. Each set is a
+//! tree whose root names it, and each element only knows its parent. `find` walks up to
+//! the root and `union` hangs one root under the other:
+//!
+//! ```text
+//!   {0, 1, 2, 3}  {4, 5}         union(2, 5)          0
+//!                                                   / | \
+//!        0          4                              1  2  4
+//!       / \         |                                 |  |
+//!      1   2        5                                 3  5
+//!          |
+//!          3
+//! ```
+//!
+//! Two tricks keep the trees flat. Union by size hangs the smaller tree under the larger,
+//! so no element is more than log n steps from its root. Path compression points every
+//! element a `find` passes straight at the root. Together they make both operations
+//! O(α(n)) amortized, which is no more than 4 for any `n` that fits in memory (Tarjan,
+//! 1975).
+//!
+//! [`WeightedUnionFind`] also records how the elements of a set relate. Each element keeps
+//! its *potential* relative to its parent, and summing them up the path gives its
+//! potential relative to the root. `union(a, b, d)` says that `b` is `d` more than `a`, and
+//! from then on the difference between any two elements of the merged set is known.
+//! Compressing a path folds the offsets it skips into the ones that remain.
+
+/// A partition of `0..n` into disjoint sets.
+#[derive(Clone, Debug)]
+pub struct UnionFind {
+    parent: Vec<usize>,
+    // Only meaningful at roots
+    size: Vec<usize>,
+    sets: usize,
+}
+
+impl UnionFind {
+    /// Creates a UnionFind of `n` singleton sets.
+    pub fn new(n: usize) -> Self {
+        UnionFind {
+            parent: (0..n).collect(),
+            size: vec![1; n],
+            sets: n,
+        }
+    }
+
+    /// Number of elements.
+    pub fn len(&self) -> usize {
+        self.parent.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parent.is_empty()
+    }
+
+    /// Number of disjoint sets.
+    pub fn set_count(&self) -> usize {
+        self.sets
+    }
+
+    /// Returns the root of the set holding `x`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `x` is out of bounds.
+    pub fn find(&mut self, mut x: usize) -> usize {
+        let mut root = x;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        while self.parent[x] != root {
+            x = std::mem::replace(&mut self.parent[x], root);
+        }
+        root
+    }
+
+    /// Merges the sets holding `a` and `b`. Returns false if they were already one.
+    pub fn union(&mut self, a: usize, b: usize) -> bool {
+        let (a, b) = (self.find(a), self.find(b));
+        if a == b {
+            return false;
+        }
+        let (small, large) = if self.size[a] < self.size[b] {
+            (a, b)
+        } else {
+            (b, a)
+        };
+        self.parent[small] = large;
+        self.size[large] += self.size[small];
+        self.sets -= 1;
+        true
+    }
+
+    /// Whether `a` and `b` are in the same set.
+    pub fn same(&mut self, a: usize, b: usize) -> bool {
+        self.find(a) == self.find(b)
+    }
+
+    /// Number of elements in the set holding `x`.
+    pub fn set_size(&mut self, x: usize) -> usize {
+        let root = self.find(x);
+        self.size[root]
+    }
+}
+
+/// A union-find that also knows the difference between any two elements of a set.
+#[derive(Clone, Debug)]
+pub struct WeightedUnionFind {
+    parent: Vec<usize>,
+    size: Vec<usize>,
+    // Potential of each element minus that of its parent
+    offset: Vec<i64>,
+    sets: usize,
+}
+
+impl WeightedUnionFind {
+    /// Creates a WeightedUnionFind of `n` singleton sets.
+    pub fn new(n: usize) -> Self {
+        WeightedUnionFind {
+            parent: (0..n).collect(),
+            size: vec![1; n],
+            offset: vec![0; n],
+            sets: n,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.parent.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parent.is_empty()
+    }
+
+    pub fn set_count(&self) -> usize {
+        self.sets
+    }
+
+    /// Returns the root of the set holding `x`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `x` is out of bounds.
+    pub fn find(&mut self, x: usize) -> usize {
+        let mut path = Vec::new();
+        let mut root = x;
+        while self.parent[root] != root {
+            path.push(root);
+            root = self.parent[root];
+        }
+        // Nearest the root first, so each offset is already relative to the root when the
+        // next one down adds it in
+        for &node in path.iter().rev() {
+            let parent = self.parent[node];
+            if parent != root {
+                self.offset[node] += self.offset[parent];
+                self.parent[node] = root;
+            }
+        }
+        root
+    }
+
+    /// Potential of `x` relative to the root of its set.
+    fn potential(&mut self, x: usize) -> i64 {
+        self.find(x);
+        if self.parent[x] == x {
+            0
+        } else {
+            self.offset[x]
+        }
+    }
+
+    /// Records that `b` is `diff` more than `a`, merging their sets. Returns `Ok(false)`
+    /// if that was already known, or `Err` with the known difference if it contradicts it.
+    pub fn union(&mut self, a: usize, b: usize, diff: i64) -> Result<bool, i64> {
+        let (pa, pb) = (self.potential(a), self.potential(b));
+        let (ra, rb) = (self.find(a), self.find(b));
+        if ra == rb {
+            let known = pb - pa;
+            return if known == diff { Ok(false) } else { Err(known) };
+        }
+        // potential(rb) - potential(ra), from b - a = diff
+        let roots = pa + diff - pb;
+        if self.size[ra] < self.size[rb] {
+            self.parent[ra] = rb;
+            self.offset[ra] = -roots;
+            self.size[rb] += self.size[ra];
+        } else {
+            self.parent[rb] = ra;
+            self.offset[rb] = roots;
+            self.size[ra] += self.size[rb];
+        }
+        self.sets -= 1;
+        Ok(true)
+    }
+
+    /// Returns how much more `b` is than `a`, if they're in the same set.
+    pub fn diff(&mut self, a: usize, b: usize) -> Option<i64> {
+        if self.find(a) != self.find(b) {
+            return None;
+        }
+        Some(self.potential(b) - self.potential(a))
+    }
+
+    pub fn same(&mut self, a: usize, b: usize) -> bool {
+        self.find(a) == self.find(b)
+    }
+
+    pub fn set_size(&mut self, x: usize) -> usize {
+        let root = self.find(x);
+        self.size[root]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{UnionFind, WeightedUnionFind};
+
+    #[test]
+    fn basics() {
+        let mut sets = UnionFind::new(6);
+
+        // Check singletons behave right
+        assert_eq!(sets.len(), 6);
+        assert_eq!(sets.set_count(), 6);
+        assert!(!sets.same(0, 1));
+        assert_eq!(sets.set_size(3), 1);
+
+        // Populate sets
+        assert!(sets.union(0, 1));
+        assert!(sets.union(2, 3));
+        assert!(sets.union(0, 2));
+        assert!(sets.union(4, 5));
+        assert!(!sets.union(1, 3));
+        assert_eq!(sets.set_count(), 2);
+        assert!(sets.same(1, 3));
+        assert!(!sets.same(3, 4));
+        assert_eq!(sets.set_size(3), 4);
+        assert_eq!(sets.set_size(5), 2);
+
+        // Check everything ends up together
+        assert!(sets.union(5, 1));
+        assert_eq!(sets.set_count(), 1);
+        assert_eq!(sets.set_size(0), 6);
+    }
+
+    #[test]
+    fn long_chain() {
+        // Union by size keeps paths short, and compression flattens what's left
+        let n = if cfg!(miri) { 1_000 } else { 100_000 };
+        let mut sets = UnionFind::new(n);
+        for i in 1..n {
+            sets.union(i - 1, i);
+        }
+        assert_eq!(sets.set_count(), 1);
+        let root = sets.find(0);
+        for i in 0..n {
+            assert_eq!(sets.find(i), root);
+        }
+        assert!((0..n).all(|i| sets.parent[i] == root));
+    }
+
+    #[test]
+    fn weighted() {
+        let mut sets = WeightedUnionFind::new(5);
+
+        // Check unrelated elements behave right
+        assert_eq!(sets.diff(0, 1), None);
+        assert_eq!(sets.diff(2, 2), Some(0));
+
+        // 1 = 0 + 3, 2 = 1 + 4, 4 = 3 - 2
+        assert_eq!(sets.union(0, 1, 3), Ok(true));
+        assert_eq!(sets.union(1, 2, 4), Ok(true));
+        assert_eq!(sets.union(3, 4, -2), Ok(true));
+        assert_eq!(sets.diff(0, 2), Some(7));
+        assert_eq!(sets.diff(2, 0), Some(-7));
+        assert_eq!(sets.diff(0, 4), None);
+
+        // Known facts agree, contradictions report what's known
+        assert_eq!(sets.union(0, 2, 7), Ok(false));
+        assert_eq!(sets.union(2, 0, 1), Err(-7));
+
+        // Bridge the sets: 3 = 2 + 10
+        assert_eq!(sets.union(2, 3, 10), Ok(true));
+        assert_eq!(sets.set_count(), 1);
+        assert_eq!(sets.diff(0, 4), Some(15));
+        assert_eq!(sets.diff(4, 1), Some(-12));
+        assert_eq!(sets.set_size(4), 5);
+    }
+
+    #[test]
+    fn weighted_against_hidden_values() {
+        // Each element has a hidden value, and unions reveal differences between them
+        let n = 200;
+        let mut x: u32 = 1;
+        let mut next = || {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            x >> 8
+        };
+        let values: Vec<i64> = (0..n).map(|_| (next() % 1000) as i64 - 500).collect();
+        let mut sets = WeightedUnionFind::new(n);
+        let mut plain = UnionFind::new(n);
+        for _ in 0..2_000 {
+            let (a, b) = (next() as usize % n, next() as usize % n);
+            if next() % 3 == 0 {
+                let truth = values[b] - values[a];
+                assert!(sets.union(a, b, truth).is_ok());
+                plain.union(a, b);
+                // A wrong difference is always refused once they're joined
+                assert_eq!(sets.union(a, b, truth + 1), Err(truth));
+            } else {
+                let known = sets.diff(a, b);
+                assert_eq!(known.is_some(), plain.same(a, b));
+                assert!(known.is_none_or(|d| d == values[b] - values[a]));
+            }
+            assert_eq!(sets.set_count(), plain.set_count());
+        }
+    }
+}
@@ -0,0 +1,531 @@
+//! # Growable ring buffer
+//!
+//! The array-based counterpart to [`crate::deque`]: a double-ended queue whose elements
+//! sit in one heap buffer rather than a node each. `head` is the slot of the front
+//! element, and the back is `len` slots on, wrapping around the end of the buffer:
+//!
+//! ```text
+//!             tail  head
+//!              v     v
+//!   [ e  f  g  .  a  b  c  d ]      head = 4, len = 7
+//!
+//!   push_back(h), push_back(i): full, so grow
+//!
+//!   [ a  b  c  d  e  f  g  h  i  .  .  .  .  .  .  . ]      head = 0, len = 9
+//! ```
+//!
+//! Pushing at either end just writes the slot next to `head` or `tail`, so it's O(1)
+//! until the buffer is full. Then it grows to twice the size, and the elements, which may
+//! wrap, are copied into the new buffer in order starting from slot 0. Doubling means any
+//! element has been copied O(1) times on average, so pushes are amortized O(1) at both
+//! ends.
+//!
+//! Capacities are powers of two, so wrapping an index is a mask rather than a division.
+//! Slots are `MaybeUninit` and which are live is tracked by hand, like in
+//! [`crate::ring_buffer`], so run the tests under Miri too:
+//!
+//! ```text
+//! cargo +nightly miri test vec_deque
+//! ```
+
+use std::fmt;
+use std::iter::FromIterator;
+use std::mem::MaybeUninit;
+use std::ops::{Index, IndexMut};
+use std::ptr;
+use std::slice;
+
+pub struct VecDeque<T> {
+    buf: Box<[MaybeUninit<T>]>,
+    // Slot of the front element
+    head: usize,
+    len: usize,
+}
+
+impl<T> VecDeque<T> {
+    /// Creates an empty VecDeque. It doesn't allocate until the
+    /// first push.
+    pub fn new() -> Self {
+        VecDeque {
+            buf: Box::new([]),
+            head: 0,
+            len: 0,
+        }
+    }
+
+    /// Creates an empty deque with room for at least `capacity` elements.
+    pub fn with_capacity(capacity: usize) -> Self {
+        let mut deque = VecDeque::new();
+        deque.reserve(capacity);
+        deque
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.buf.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        while self.pop_front().is_some() {}
+        self.head = 0;
+    }
+
+    /// Slot `i` places on from `head`, wrapped.
+    fn slot(&self, i: usize) -> usize {
+        (self.head + i) & (self.capacity().wrapping_sub(1))
+    }
+
+    /// Makes sure there's room for `additional` more elements, growing to the next power
+    /// of two that fits.
+    pub fn reserve(&mut self, additional: usize) {
+        let needed = self.len.checked_add(additional).expect("capacity overflow");
+        if needed > self.capacity() {
+            self.grow_to(needed.max(4).next_power_of_two());
+        }
+    }
+
+    /// Moves the elements to the start of a new buffer of `capacity` slots.
+    fn grow_to(&mut self, capacity: usize) {
+        let mut buf: Box<[MaybeUninit<T>]> = (0..capacity).map(|_| MaybeUninit::uninit()).collect();
+        let (first, second) = self.as_slices();
+        // Bitwise moves: the old buffer's slots are forgotten, not dropped, since
+        // MaybeUninit never drops what it holds
+        unsafe {
+            let dst = buf.as_mut_ptr() as *mut T;
+            ptr::copy_nonoverlapping(first.as_ptr(), dst, first.len());
+            ptr::copy_nonoverlapping(second.as_ptr(), dst.add(first.len()), second.len());
+        }
+        self.buf = buf;
+        self.head = 0;
+    }
+
+    fn grow_if_full(&mut self) {
+        if self.len == self.capacity() {
+            self.grow_to((self.capacity() * 2).max(4));
+        }
+    }
+
+    pub fn push_back(&mut self, elem: T) {
+        self.grow_if_full();
+        let slot = self.slot(self.len);
+        self.buf[slot] = MaybeUninit::new(elem);
+        self.len += 1;
+    }
+
+    pub fn push_front(&mut self, elem: T) {
+        self.grow_if_full();
+        self.head = self.slot(self.capacity() - 1);
+        self.buf[self.head] = MaybeUninit::new(elem);
+        self.len += 1;
+    }
+
+    pub fn pop_front(&mut self) -> Option<T> {
+        if self.is_empty() {
+            return None;
+        }
+        let elem = unsafe { self.buf[self.head].assume_init_read() };
+        self.head = self.slot(1);
+        self.len -= 1;
+        Some(elem)
+    }
+
+    pub fn pop_back(&mut self) -> Option<T> {
+        if self.is_empty() {
+            return None;
+        }
+        self.len -= 1;
+        let slot = self.slot(self.len);
+        Some(unsafe { self.buf[slot].assume_init_read() })
+    }
+
+    /// Returns the element `i` places from the front.
+    pub fn get(&self, i: usize) -> Option<&T> {
+        if i >= self.len {
+            return None;
+        }
+        Some(unsafe { self.buf[self.slot(i)].assume_init_ref() })
+    }
+
+    pub fn get_mut(&mut self, i: usize) -> Option<&mut T> {
+        if i >= self.len {
+            return None;
+        }
+        let slot = self.slot(i);
+        Some(unsafe { self.buf[slot].assume_init_mut() })
+    }
+
+    pub fn front(&self) -> Option<&T> {
+        self.get(0)
+    }
+
+    pub fn front_mut(&mut self) -> Option<&mut T> {
+        self.get_mut(0)
+    }
+
+    pub fn back(&self) -> Option<&T> {
+        self.len.checked_sub(1).and_then(|i| self.get(i))
+    }
+
+    pub fn back_mut(&mut self) -> Option<&mut T> {
+        self.len.checked_sub(1).and_then(move |i| self.get_mut(i))
+    }
+
+    /// The two runs of slots the elements occupy, front first.
+    fn runs(&self) -> (std::ops::Range<usize>, std::ops::Range<usize>) {
+        let capacity = self.capacity();
+        if self.head + self.len <= capacity {
+            (self.head..self.head + self.len, 0..0)
+        } else {
+            (self.head..capacity, 0..self.head + self.len - capacity)
+        }
+    }
+
+    /// Returns the elements as two slices, front first, which together are in order.
+    pub fn as_slices(&self) -> (&[T], &[T]) {
+        let (first, second) = self.runs();
+        unsafe {
+            let base = self.buf.as_ptr() as *const T;
+            (
+                slice::from_raw_parts(base.add(first.start), first.len()),
+                slice::from_raw_parts(base.add(second.start), second.len()),
+            )
+        }
+    }
+
+    pub fn as_mut_slices(&mut self) -> (&mut [T], &mut [T]) {
+        let (first, second) = self.runs();
+        unsafe {
+            let base = self.buf.as_mut_ptr() as *mut T;
+            (
+                slice::from_raw_parts_mut(base.add(first.start), first.len()),
+                slice::from_raw_parts_mut(base.add(second.start), second.len()),
+            )
+        }
+    }
+
+    /// Iterates from front to back.
+    pub fn iter(&self) -> Iter<'_, T> {
+        let (first, second) = self.as_slices();
+        Iter {
+            first: first.iter(),
+            second: second.iter(),
+        }
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        let (first, second) = self.as_mut_slices();
+        IterMut {
+            first: first.iter_mut(),
+            second: second.iter_mut(),
+        }
+    }
+}
+
+impl<T> Drop for VecDeque<T> {
+    fn drop(&mut self) {
+        let (first, second) = self.as_mut_slices();
+        unsafe {
+            ptr::drop_in_place(first);
+            ptr::drop_in_place(second);
+        }
+    }
+}
+
+impl<T> Default for VecDeque<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Clone> Clone for VecDeque<T> {
+    fn clone(&self) -> Self {
+        self.iter().cloned().collect()
+    }
+}
+
+impl<T> Index<usize> for VecDeque<T> {
+    type Output = T;
+
+    fn index(&self, i: usize) -> &T {
+        self.get(i).expect("index out of bounds")
+    }
+}
+
+impl<T> IndexMut<usize> for VecDeque<T> {
+    fn index_mut(&mut self, i: usize) -> &mut T {
+        self.get_mut(i).expect("index out of bounds")
+    }
+}
+
+impl<T> Extend<T> for VecDeque<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        for elem in iter {
+            self.push_back(elem);
+        }
+    }
+}
+
+impl<T> FromIterator<T> for VecDeque<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut deque = VecDeque::new();
+        deque.extend(iter);
+        deque
+    }
+}
+
+impl<T: PartialEq> PartialEq for VecDeque<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.iter().eq(other)
+    }
+}
+
+impl<T: Eq> Eq for VecDeque<T> {}
+
+impl<T: fmt::Debug> fmt::Debug for VecDeque<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// Iterator over the elements of a [`VecDeque`], front to back.
+pub struct Iter<'a, T> {
+    first: slice::Iter<'a, T>,
+    second: slice::Iter<'a, T>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.first.next().or_else(|| self.second.next())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.first.len() + self.second.len();
+        (len, Some(len))
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for Iter<'a, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.second.next_back().or_else(|| self.first.next_back())
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Iter<'a, T> {}
+
+impl<'a, T> IntoIterator for &'a VecDeque<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+pub struct IterMut<'a, T> {
+    first: slice::IterMut<'a, T>,
+    second: slice::IterMut<'a, T>,
+}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.first.next().or_else(|| self.second.next())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.first.len() + self.second.len();
+        (len, Some(len))
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for IterMut<'a, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.second.next_back().or_else(|| self.first.next_back())
+    }
+}
+
+impl<'a, T> ExactSizeIterator for IterMut<'a, T> {}
+
+impl<'a, T> IntoIterator for &'a mut VecDeque<T> {
+    type Item = &'a mut T;
+    type IntoIter = IterMut<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+/// Owning iterator, front to back.
+pub struct IntoIter<T>(VecDeque<T>);
+
+impl<T> IntoIterator for VecDeque<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter(self)
+    }
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.0.pop_front()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.0.len, Some(self.0.len))
+    }
+}
+
+impl<T> DoubleEndedIterator for IntoIter<T> {
+    fn next_back(&mut self) -> Option<T> {
+        self.0.pop_back()
+    }
+}
+
+impl<T> ExactSizeIterator for IntoIter<T> {}
+
+#[cfg(test)]
+mod test {
+    use super::VecDeque;
+    use std::rc::Rc;
+
+    #[test]
+    fn basics() {
+        let mut deque = VecDeque::new();
+
+        // Check empty deque behaves right
+        assert_eq!(deque.pop_front(), None);
+        assert_eq!(deque.pop_back(), None);
+        assert_eq!(deque.capacity(), 0);
+
+        // Populate deque
+        deque.push_back(2);
+        deque.push_back(3);
+        deque.push_front(1);
+        deque.push_front(0);
+        assert_eq!(deque.len(), 4);
+        assert_eq!(deque.capacity(), 4);
+        assert_eq!(format!("{:?}", deque), "[0, 1, 2, 3]");
+
+        // Check normal removal
+        assert_eq!(deque.pop_front(), Some(0));
+        assert_eq!(deque.pop_back(), Some(3));
+
+        // Push some more just to make sure nothing's corrupted
+        deque.push_back(4);
+        deque.push_back(5);
+        deque.push_front(0);
+        assert_eq!(deque.capacity(), 8);
+        deque[1] = 10;
+        *deque.back_mut().unwrap() += 1;
+        assert!(deque.iter().eq(&[0, 10, 2, 4, 6]));
+        assert!(deque.iter().rev().eq(&[6, 4, 2, 10, 0]));
+
+        // Check exhaustion
+        assert_eq!(deque.pop_back(), Some(6));
+        assert_eq!(deque.pop_front(), Some(0));
+        assert_eq!(deque.pop_front(), Some(10));
+        assert_eq!(deque.pop_back(), Some(4));
+        assert_eq!(deque.pop_front(), Some(2));
+        assert_eq!(deque.pop_front(), None);
+        assert!(deque.is_empty());
+    }
+
+    #[test]
+    fn grows_while_wrapped() {
+        let mut deque = VecDeque::with_capacity(4);
+        assert_eq!(deque.capacity(), 4);
+        deque.extend([2, 3]);
+        deque.push_front(1);
+        deque.push_front(0);
+
+        // The front run is at the end of the buffer, which growing has to straighten out
+        assert_eq!(deque.as_slices(), (&[0, 1][..], &[2, 3][..]));
+        deque.push_back(4);
+        assert_eq!(deque.capacity(), 8);
+        assert_eq!(deque.head, 0);
+        let empty: &[i32] = &[];
+        assert_eq!(deque.as_slices(), (&[0, 1, 2, 3, 4][..], empty));
+
+        for elem in &mut deque {
+            *elem *= 10;
+        }
+        let all: Vec<_> = deque.clone().into_iter().collect();
+        assert_eq!(all, vec![0, 10, 20, 30, 40]);
+        assert_eq!(deque.into_iter().next_back(), Some(40));
+    }
+
+    #[test]
+    fn drops_what_it_holds() {
+        let token = Rc::new(());
+        {
+            let mut deque = VecDeque::new();
+            for i in 0..10 {
+                if i % 2 == 0 {
+                    deque.push_front(token.clone());
+                } else {
+                    deque.push_back(token.clone());
+                }
+            }
+            deque.pop_front();
+            assert_eq!(Rc::strong_count(&token), 10);
+            let mut iter = deque.clone().into_iter();
+            iter.next();
+            assert_eq!(Rc::strong_count(&token), 18);
+        }
+        assert_eq!(Rc::strong_count(&token), 1);
+    }
+
+    #[test]
+    fn zero_sized() {
+        let mut deque = VecDeque::new();
+        for _ in 0..100 {
+            deque.push_front(());
+        }
+        assert_eq!(deque.len(), 100);
+        assert_eq!(deque.iter().count(), 100);
+    }
+
+    #[test]
+    fn against_std() {
+        let mut deque = VecDeque::new();
+        let mut model = std::collections::VecDeque::new();
+        let mut x: u32 = 1;
+        for _ in 0..10_000 {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            match x >> 29 {
+                0 => assert_eq!(deque.pop_front(), model.pop_front()),
+                1 => assert_eq!(deque.pop_back(), model.pop_back()),
+                2..=4 => {
+                    deque.push_front(x);
+                    model.push_front(x);
+                }
+                _ => {
+                    deque.push_back(x);
+                    model.push_back(x);
+                }
+            }
+            assert_eq!(deque.len(), model.len());
+            assert_eq!(deque.front(), model.front());
+            assert_eq!(deque.back(), model.back());
+        }
+        assert!(deque.iter().eq(model.iter()));
+        let i = model.len() / 2;
+        assert_eq!(deque.get(i), model.get(i));
+    }
+}
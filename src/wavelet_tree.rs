@@ -0,0 +1,292 @@
+//! # Wavelet tree
+//!
+//! Answers questions about a static sequence of integers that a plain array can only
+//! answer by scanning, each in O(log σ) for symbols below σ (Grossi, Gupta and Vitter,
+//! 2003):
+//!
+//! - `rank(symbol, i)`: how many times `symbol` appears in the first `i` elements;
+//! - `select(symbol, k)`: where the `k`th `symbol` is, counting from 0;
+//! - `quantile(range, k)`: the `k`th smallest element in `range`, so the median of any
+//!   range is `quantile(range, range.len() / 2)`.
+//!
+//! The tree splits the symbols on their bits, most significant first. The root records
+//! one bit per element, its top bit, then sends the elements with a 0 to its left child
+//! and those with a 1 to its right, each keeping their order, and so on down:
+//!
+//! ```text
+//!   sequence 3 1 6 2 7 4 (3 bits)
+//!
+//!   level 0:  3 1 6 2 7 4     bits 0 0 1 0 1 1
+//!   level 1:  3 1 2 | 6 7 4   bits 1 0 1 | 1 1 0
+//!   level 2:  1 | 3 2 | 4 | 6 7
+//! ```
+//!
+//! A node's bits are a [`RankSelect`] vector, and with rank an element's position in the
+//! child follows from its position in the parent: the ones before it in the parent are the
+//! elements ahead of it on the right. No pointers needed.
+//!
+//! This one is laid out as a wavelet matrix (Claude, Navarro and Ordóñez, 2012): rather
+//! than one bit vector per node, each level is a single bit vector over all the elements,
+//! and every level sends all its zeros before all its ones into the level below. The
+//! nodes end up in a different order, but the same walks answer the same queries, and
+//! there are only log σ bit vectors, however many distinct symbols there are.
+//!
+//! [`RankSelect`]: crate::rank_select::RankSelect
+
+use crate::rank_select::RankSelect;
+use std::ops::Range;
+
+#[derive(Clone, Debug)]
+pub struct WaveletTree {
+    // levels[l] holds bit (bits - 1 - l) of every element, in that level's order
+    levels: Vec<RankSelect>,
+    len: usize,
+}
+
+impl WaveletTree {
+    /// Builds the tree over `data`, with as many levels as the largest value has bits.
+    pub fn new(data: &[u64]) -> Self {
+        let max = data.iter().copied().max().unwrap_or(0);
+        let bits = (u64::BITS - max.leading_zeros()) as usize;
+        let mut levels = Vec::with_capacity(bits);
+        let mut order = data.to_vec();
+        for l in 0..bits {
+            let shift = bits - 1 - l;
+            let level: RankSelect = order.iter().map(|&x| (x >> shift) & 1 == 1).collect();
+            // Zeros first, then ones, each keeping their order
+            let (mut zeros, ones): (Vec<u64>, Vec<u64>) =
+                order.iter().partition(|&&x| (x >> shift) & 1 == 0);
+            zeros.extend(ones);
+            order = zeros;
+            levels.push(level);
+        }
+        WaveletTree {
+            levels,
+            len: data.len(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn bits(&self) -> usize {
+        self.levels.len()
+    }
+
+    /// Whether `symbol` has more bits than the tree, so can't be in the sequence.
+    fn too_wide(&self, symbol: u64) -> bool {
+        self.bits() < 64 && symbol >> self.bits() != 0
+    }
+
+    /// Follows position `i` of `level` into the level below, down the side of `bit`.
+    fn descend(&self, level: &RankSelect, i: usize, bit: bool) -> usize {
+        if bit {
+            level.count_zeros() + level.rank1(i)
+        } else {
+            level.rank0(i)
+        }
+    }
+
+    fn bit(&self, symbol: u64, level: usize) -> bool {
+        (symbol >> (self.bits() - 1 - level)) & 1 == 1
+    }
+
+    /// Returns element `i`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `i >= len`.
+    pub fn get(&self, mut i: usize) -> u64 {
+        assert!(
+            i < self.len,
+            "index {} out of bounds for length {}",
+            i,
+            self.len
+        );
+        let mut symbol = 0;
+        for level in &self.levels {
+            let bit = level.get(i);
+            symbol = (symbol << 1) | bit as u64;
+            i = self.descend(level, i, bit);
+        }
+        symbol
+    }
+
+    /// Returns how many times `symbol` appears in the first `i` elements.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `i > len`.
+    pub fn rank(&self, symbol: u64, i: usize) -> usize {
+        assert!(
+            i <= self.len,
+            "index {} out of bounds for length {}",
+            i,
+            self.len
+        );
+        if self.too_wide(symbol) {
+            return 0;
+        }
+        // Where the first `i` elements land, against where the symbol's run starts
+        let (mut start, mut end) = (0, i);
+        for (l, level) in self.levels.iter().enumerate() {
+            let bit = self.bit(symbol, l);
+            start = self.descend(level, start, bit);
+            end = self.descend(level, end, bit);
+        }
+        end - start
+    }
+
+    /// Returns the position of the `k`th `symbol`, counting from 0, if there are that many.
+    pub fn select(&self, symbol: u64, k: usize) -> Option<usize> {
+        if self.too_wide(symbol) || k >= self.rank(symbol, self.len) {
+            return None;
+        }
+        // Down to the start of the symbol's run at the bottom, then back up from k past it
+        let mut start = 0;
+        for (l, level) in self.levels.iter().enumerate() {
+            start = self.descend(level, start, self.bit(symbol, l));
+        }
+        let mut i = start + k;
+        for (l, level) in self.levels.iter().enumerate().rev() {
+            i = if self.bit(symbol, l) {
+                level.select1(i - level.count_zeros()).unwrap()
+            } else {
+                level.select0(i).unwrap()
+            };
+        }
+        Some(i)
+    }
+
+    /// Returns the `k`th smallest element in `range`, counting from 0, or `None` if the
+    /// range has no more than `k` elements.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range goes past the end of the sequence.
+    pub fn quantile(&self, range: Range<usize>, mut k: usize) -> Option<u64> {
+        assert!(
+            range.end <= self.len,
+            "range end {} out of bounds for length {}",
+            range.end,
+            self.len
+        );
+        if k >= range.end.saturating_sub(range.start) {
+            return None;
+        }
+        let (mut start, mut end) = (range.start, range.end);
+        let mut symbol = 0;
+        for level in &self.levels {
+            // The range's elements with a 0 here are all smaller than the ones with a 1
+            let zeros = level.rank0(end) - level.rank0(start);
+            let bit = k >= zeros;
+            if bit {
+                k -= zeros;
+            }
+            symbol = (symbol << 1) | bit as u64;
+            start = self.descend(level, start, bit);
+            end = self.descend(level, end, bit);
+        }
+        Some(symbol)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::WaveletTree;
+
+    #[test]
+    fn basics() {
+        let data = [3, 1, 6, 2, 7, 4, 1, 3];
+        let tree = WaveletTree::new(&data);
+        assert_eq!(tree.len(), 8);
+        assert!((0..8).map(|i| tree.get(i)).eq(data.iter().copied()));
+
+        assert_eq!(tree.rank(3, 8), 2);
+        assert_eq!(tree.rank(3, 7), 1);
+        assert_eq!(tree.rank(1, 2), 1);
+        assert_eq!(tree.rank(5, 8), 0);
+        assert_eq!(tree.rank(100, 8), 0);
+
+        assert_eq!(tree.select(1, 0), Some(1));
+        assert_eq!(tree.select(1, 1), Some(6));
+        assert_eq!(tree.select(1, 2), None);
+        assert_eq!(tree.select(5, 0), None);
+
+        // Sorted, 1..6 is 1 2 4 6 7
+        assert_eq!(tree.quantile(1..6, 0), Some(1));
+        assert_eq!(tree.quantile(1..6, 2), Some(4));
+        assert_eq!(tree.quantile(1..6, 4), Some(7));
+        assert_eq!(tree.quantile(1..6, 5), None);
+        assert_eq!(tree.quantile(3..3, 0), None);
+
+        // Check empty and all-zero trees behave right
+        let empty = WaveletTree::new(&[]);
+        assert!(empty.is_empty());
+        assert_eq!(empty.rank(0, 0), 0);
+        assert_eq!(empty.select(0, 0), None);
+        let zeros = WaveletTree::new(&[0; 5]);
+        assert_eq!(zeros.get(4), 0);
+        assert_eq!(zeros.rank(0, 3), 3);
+        assert_eq!(zeros.rank(1, 3), 0);
+        assert_eq!(zeros.select(0, 4), Some(4));
+        assert_eq!(zeros.quantile(0..5, 2), Some(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "out of bounds")]
+    fn quantile_past_the_end() {
+        WaveletTree::new(&[1, 2, 3]).quantile(1..4, 0);
+    }
+
+    #[test]
+    fn full_width_symbols() {
+        let data = [u64::MAX, 0, 1 << 63, u64::MAX];
+        let tree = WaveletTree::new(&data);
+        assert_eq!(tree.get(2), 1 << 63);
+        assert_eq!(tree.rank(u64::MAX, 4), 2);
+        assert_eq!(tree.select(u64::MAX, 1), Some(3));
+        assert_eq!(tree.quantile(0..4, 1), Some(1 << 63));
+    }
+
+    #[test]
+    fn against_naive() {
+        let n = if cfg!(miri) { 300 } else { 3_000 };
+        let mut x: u32 = 1;
+        let mut next = |below: u32| {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            ((x >> 8) % below) as usize
+        };
+        for &sigma in &[2, 10, 1000] {
+            let data: Vec<u64> = (0..n).map(|_| next(sigma) as u64).collect();
+            let tree = WaveletTree::new(&data);
+            for _ in 0..500 {
+                let symbol = next(sigma + 1) as u64;
+                let i = next(n + 1);
+                let count = data[..i].iter().filter(|&&x| x == symbol).count();
+                assert_eq!(tree.rank(symbol, i), count);
+
+                let k = next(10);
+                let position = data
+                    .iter()
+                    .enumerate()
+                    .filter(|&(_, &x)| x == symbol)
+                    .nth(k)
+                    .map(|(i, _)| i);
+                assert_eq!(tree.select(symbol, k), position);
+
+                let (a, b) = (next(n + 1), next(n + 1));
+                let range = a.min(b)..a.max(b);
+                let mut sorted = data[range.clone()].to_vec();
+                sorted.sort_unstable();
+                let k = next(range.len() as u32 + 1);
+                assert_eq!(tree.quantile(range, k), sorted.get(k).copied());
+            }
+        }
+    }
+}
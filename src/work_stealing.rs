@@ -0,0 +1,367 @@
+//! # Chase–Lev work-stealing deque
+//!
+//! The deque behind most task schedulers. One owner thread holds the `Worker` and treats the
+//! deque as a stack: it pushes and pops at the *bottom*. Any number of other threads hold a
+//! `Stealer` and take work from the *top*, oldest first.
+//!
+//! ```text
+//!  top (stealers)                      bottom (owner)
+//!   |                                    |
+//!   v                                    v
+//! [ t | t+1 | ... |  ...  | b-1 | (free) ... ]  circular buffer, indices masked by capacity
+//! ```
+//!
+//! `top` and `bottom` only ever grow; a slot is `index & (capacity - 1)`. The owner is the only
+//! one who writes `bottom`, so push and pop are nearly free. Stealers race each other (and the
+//! owner, for the very last element) with a CAS on `top`.
+//!
+//! When the buffer fills up the owner copies the live range into one twice the size. A
+//! stealer may still be reading the old buffer at that point, so old buffers are kept around
+//! until the deque itself goes away. They only ever double, so this costs at most as much
+//! memory again as the largest buffer.
+//!
+//! Every atomic access is `SeqCst`. The paper by Lê et al. (PPoPP 2013) shows which of them
+//! can be relaxed; here we keep the simple, obviously-correct version.
+
+use std::cell::UnsafeCell;
+use std::mem::MaybeUninit;
+use std::ptr;
+use std::sync::atomic::{AtomicIsize, AtomicPtr, Ordering::SeqCst};
+use std::sync::Arc;
+
+const MIN_CAPACITY: usize = 16;
+
+/// The owner's end of the deque.
+pub struct Worker<T> {
+    inner: Arc<Inner<T>>,
+}
+
+/// A handle other threads use to take work from the top. Cheap to clone.
+pub struct Stealer<T> {
+    inner: Arc<Inner<T>>,
+}
+
+/// The outcome of a steal attempt.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Steal<T> {
+    /// The deque was empty.
+    Empty,
+    /// We got an element.
+    Success(T),
+    /// We lost a race with another thread; the deque may still have work.
+    Retry,
+}
+
+struct Inner<T> {
+    top: AtomicIsize,
+    bottom: AtomicIsize,
+    buffer: AtomicPtr<Buffer<T>>,
+    // Buffers we've grown out of. Only the worker touches this.
+    retired: UnsafeCell<Vec<*mut Buffer<T>>>,
+}
+
+struct Buffer<T> {
+    slots: Box<[UnsafeCell<MaybeUninit<T>>]>,
+}
+
+impl<T> Buffer<T> {
+    fn alloc(capacity: usize) -> *mut Self {
+        let slots = (0..capacity)
+            .map(|_| UnsafeCell::new(MaybeUninit::uninit()))
+            .collect();
+        Box::into_raw(Box::new(Buffer { slots }))
+    }
+
+    fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    fn slot(&self, index: isize) -> *mut MaybeUninit<T> {
+        self.slots[index as usize & (self.capacity() - 1)].get()
+    }
+
+    unsafe fn write(&self, index: isize, value: T) {
+        ptr::write(self.slot(index), MaybeUninit::new(value));
+    }
+
+    /// Copies the value out without claiming it. The caller decides whether it's theirs.
+    unsafe fn read(&self, index: isize) -> MaybeUninit<T> {
+        ptr::read_volatile(self.slot(index))
+    }
+}
+
+/// Creates a new deque, returning the owner's handle and a stealer.
+pub fn new<T>() -> (Worker<T>, Stealer<T>) {
+    let inner = Arc::new(Inner {
+        top: AtomicIsize::new(0),
+        bottom: AtomicIsize::new(0),
+        buffer: AtomicPtr::new(Buffer::alloc(MIN_CAPACITY)),
+        retired: UnsafeCell::new(Vec::new()),
+    });
+    (
+        Worker {
+            inner: inner.clone(),
+        },
+        Stealer { inner },
+    )
+}
+
+impl<T> Worker<T> {
+    /// Pushes work onto the bottom.
+    pub fn push(&self, value: T) {
+        let inner = &*self.inner;
+        let b = inner.bottom.load(SeqCst);
+        let t = inner.top.load(SeqCst);
+        let mut buffer = inner.buffer.load(SeqCst);
+
+        unsafe {
+            if b - t >= (*buffer).capacity() as isize {
+                buffer = self.grow(t, b);
+            }
+            (*buffer).write(b, value);
+        }
+        // Publishing the new bottom is what makes the element visible to stealers
+        inner.bottom.store(b + 1, SeqCst);
+    }
+
+    /// Pops the most recently pushed element off the bottom.
+    pub fn pop(&self) -> Option<T> {
+        let inner = &*self.inner;
+        let b = inner.bottom.load(SeqCst) - 1;
+        let buffer = inner.buffer.load(SeqCst);
+
+        // Claim the slot first, then see whether a stealer got there too
+        inner.bottom.store(b, SeqCst);
+        let t = inner.top.load(SeqCst);
+
+        if t > b {
+            // Empty: put bottom back where it was
+            inner.bottom.store(b + 1, SeqCst);
+            return None;
+        }
+
+        let value = unsafe { (*buffer).read(b) };
+        if t < b {
+            // More than one element left, no stealer can reach this one
+            return Some(unsafe { value.assume_init() });
+        }
+
+        // Last element: race the stealers for it
+        let won = inner.top.compare_exchange(t, t + 1, SeqCst, SeqCst).is_ok();
+        inner.bottom.store(b + 1, SeqCst);
+        if won {
+            Some(unsafe { value.assume_init() })
+        } else {
+            None
+        }
+    }
+
+    /// Returns true if there was no work at the moment we looked.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns how many elements were in the deque at the moment we looked.
+    pub fn len(&self) -> usize {
+        let b = self.inner.bottom.load(SeqCst);
+        let t = self.inner.top.load(SeqCst);
+        (b - t).max(0) as usize
+    }
+
+    /// Returns a new stealer for this deque.
+    pub fn stealer(&self) -> Stealer<T> {
+        Stealer {
+            inner: self.inner.clone(),
+        }
+    }
+
+    /// Moves the live range `t..b` into a buffer twice the size.
+    unsafe fn grow(&self, t: isize, b: isize) -> *mut Buffer<T> {
+        let inner = &*self.inner;
+        let old = inner.buffer.load(SeqCst);
+        let new = Buffer::alloc((*old).capacity() * 2);
+
+        for i in t..b {
+            ptr::write((*new).slot(i), (*old).read(i));
+        }
+        inner.buffer.store(new, SeqCst);
+        (*inner.retired.get()).push(old);
+        new
+    }
+}
+
+impl<T> Stealer<T> {
+    /// Tries to take the oldest element from the top.
+    pub fn steal(&self) -> Steal<T> {
+        let inner = &*self.inner;
+        let t = inner.top.load(SeqCst);
+        let b = inner.bottom.load(SeqCst);
+
+        if t >= b {
+            return Steal::Empty;
+        }
+
+        // Read before claiming: once top moves, the owner may overwrite the slot
+        let buffer = inner.buffer.load(SeqCst);
+        let value = unsafe { (*buffer).read(t) };
+
+        if inner.top.compare_exchange(t, t + 1, SeqCst, SeqCst).is_ok() {
+            Steal::Success(unsafe { value.assume_init() })
+        } else {
+            // Someone else claimed it; our copy is just bytes, so don't drop it
+            Steal::Retry
+        }
+    }
+
+    /// Returns true if there was no work at the moment we looked.
+    pub fn is_empty(&self) -> bool {
+        let t = self.inner.top.load(SeqCst);
+        let b = self.inner.bottom.load(SeqCst);
+        b <= t
+    }
+}
+
+impl<T> Clone for Stealer<T> {
+    fn clone(&self) -> Self {
+        Stealer {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Inner<T> {
+    fn drop(&mut self) {
+        let t = *self.top.get_mut();
+        let b = *self.bottom.get_mut();
+        let buffer = *self.buffer.get_mut();
+
+        unsafe {
+            // Whatever is still in the deque gets dropped
+            for i in t..b {
+                drop((*buffer).read(i).assume_init());
+            }
+            drop(Box::from_raw(buffer));
+
+            // Retired buffers only hold stale copies, free them without dropping anything
+            for old in self.retired.get_mut().drain(..) {
+                drop(Box::from_raw(old));
+            }
+        }
+    }
+}
+
+unsafe impl<T: Send> Send for Worker<T> {}
+unsafe impl<T: Send> Send for Stealer<T> {}
+unsafe impl<T: Send> Sync for Stealer<T> {}
+
+#[cfg(test)]
+mod test {
+    use super::{new, Steal};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn basics() {
+        let (worker, stealer) = new();
+        assert_eq!(worker.pop(), None);
+        assert_eq!(stealer.steal(), Steal::Empty);
+        assert!(worker.is_empty());
+
+        worker.push(1);
+        worker.push(2);
+        worker.push(3);
+        assert_eq!(worker.len(), 3);
+
+        // The owner works LIFO, the thief takes the oldest
+        assert_eq!(worker.pop(), Some(3));
+        assert_eq!(stealer.steal(), Steal::Success(1));
+        assert_eq!(worker.pop(), Some(2));
+        assert_eq!(worker.pop(), None);
+        assert_eq!(stealer.steal(), Steal::Empty);
+        assert!(stealer.is_empty());
+    }
+
+    #[test]
+    fn grow() {
+        let (worker, stealer) = new();
+        for i in 0..100 {
+            worker.push(i);
+        }
+        // Steal a few so the live range doesn't start at slot zero, then grow again
+        for i in 0..10 {
+            assert_eq!(stealer.steal(), Steal::Success(i));
+        }
+        for i in 100..200 {
+            worker.push(i);
+        }
+        assert_eq!(worker.len(), 190);
+        for i in (10..200).rev() {
+            assert_eq!(worker.pop(), Some(i));
+        }
+        assert_eq!(worker.pop(), None);
+    }
+
+    #[test]
+    fn drops_remaining() {
+        let (worker, stealer) = new();
+        let value = Arc::new(());
+        for _ in 0..40 {
+            worker.push(value.clone());
+        }
+        drop(worker.pop());
+        drop(stealer.steal());
+        assert_eq!(Arc::strong_count(&value), 39);
+
+        drop(worker);
+        drop(stealer);
+        assert_eq!(Arc::strong_count(&value), 1);
+    }
+
+    #[test]
+    fn concurrent_steal() {
+        let n = if cfg!(miri) { 200 } else { 50_000 };
+        let (worker, stealer) = new();
+        let sum = Arc::new(AtomicUsize::new(0));
+        let count = Arc::new(AtomicUsize::new(0));
+
+        let thieves: Vec<_> = (0..3)
+            .map(|_| {
+                let stealer = stealer.clone();
+                let sum = sum.clone();
+                let count = count.clone();
+                thread::spawn(move || {
+                    while count.load(Ordering::SeqCst) < n {
+                        if let Steal::Success(value) = stealer.steal() {
+                            sum.fetch_add(value, Ordering::SeqCst);
+                            count.fetch_add(1, Ordering::SeqCst);
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        // The owner keeps pushing and occasionally takes work back for itself
+        for i in 0..n {
+            worker.push(i);
+            if i % 3 == 0 {
+                if let Some(value) = worker.pop() {
+                    sum.fetch_add(value, Ordering::SeqCst);
+                    count.fetch_add(1, Ordering::SeqCst);
+                }
+            }
+        }
+        while let Some(value) = worker.pop() {
+            sum.fetch_add(value, Ordering::SeqCst);
+            count.fetch_add(1, Ordering::SeqCst);
+        }
+
+        for thief in thieves {
+            thief.join().unwrap();
+        }
+
+        // Every element was taken exactly once
+        assert_eq!(count.load(Ordering::SeqCst), n);
+        assert_eq!(sum.load(Ordering::SeqCst), n * (n - 1) / 2);
+    }
+}
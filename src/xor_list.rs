@@ -0,0 +1,509 @@
+//! # XOR linked list
+//!
+//! A doubly-linked list that stores one link per node instead of two: the XOR of the
+//! addresses of its neighbours, with a missing neighbour counted as 0.
+//!
+//! ```text
+//!   head                                          tail
+//!    v                                              v
+//!   [ a | 0 ^ B ] <-> [ b | A ^ C ] <-> [ c | B ^ D ] <-> [ d | C ^ 0 ]
+//!     at A              at B              at C              at D
+//! ```
+//!
+//! A node's link means nothing on its own. Walking needs the address of the node you came
+//! from: arriving at C from B, the next node is `link(C) ^ B = D`. From the head, the node
+//! before is 0, so either end is a place to start, and the same loop walks both ways.
+//!
+//! The trick is from the days when a pointer's worth of memory per node mattered (Sinha,
+//! 2004, has a readable account). Today it is mostly a curiosity, and it costs plenty:
+//!
+//! - Given a pointer to one node, you can't reach its neighbours, so there are no cursors
+//!   or O(1) removal from the middle. Everything starts from an end.
+//! - The links are integers, not pointers, which hides the nodes from debuggers, garbage
+//!   collectors, and the compiler's reasoning about pointers. Here that means using
+//!   `expose_provenance` and `with_exposed_provenance_mut`, so that turning an address
+//!   back into a pointer is allowed to pick up where the original pointer left off.
+//!
+//! One thing it does for free: `reverse` just swaps the head and the tail, in O(1), since
+//! the links read the same in both directions.
+//!
+//! Being nothing but pointer arithmetic, run the tests under Miri too:
+//!
+//! ```text
+//! cargo +nightly miri test xor_list
+//! ```
+
+use std::fmt;
+use std::iter::FromIterator;
+use std::marker::PhantomData;
+use std::ptr;
+
+pub struct XorList<T> {
+    head: *mut Node<T>,
+    tail: *mut Node<T>,
+    len: usize,
+    _boo: PhantomData<T>,
+}
+
+struct Node<T> {
+    elem: T,
+    // Address of the previous node XOR address of the next, with null counting as 0
+    link: usize,
+}
+
+fn addr<T>(node: *mut Node<T>) -> usize {
+    node.expose_provenance()
+}
+
+/// The neighbour of `node` on the side away from `from`.
+///
+/// # Safety
+///
+/// `node` must be a live node and `from` one of its neighbours, or null if it is an end.
+unsafe fn step<T>(from: *mut Node<T>, node: *mut Node<T>) -> *mut Node<T> {
+    ptr::with_exposed_provenance_mut((*node).link ^ addr(from))
+}
+
+// The list owns its nodes exclusively, like a `Box` would
+unsafe impl<T: Send> Send for XorList<T> {}
+unsafe impl<T: Sync> Sync for XorList<T> {}
+
+impl<T> XorList<T> {
+    /// Creates an empty XorList.
+    pub fn new() -> Self {
+        XorList {
+            head: ptr::null_mut(),
+            tail: ptr::null_mut(),
+            len: 0,
+            _boo: PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        while self.pop_front().is_some() {}
+    }
+
+    /// Pushes at the `head` end. `push_back` is this with the ends swapped.
+    fn push_at(head: &mut *mut Node<T>, tail: &mut *mut Node<T>, elem: T) {
+        let node = Box::into_raw(Box::new(Node {
+            elem,
+            link: addr(*head),
+        }));
+        if head.is_null() {
+            *tail = node;
+        } else {
+            // The old head's link was 0 ^ next, and now it's node ^ next
+            unsafe { (**head).link ^= addr(node) };
+        }
+        *head = node;
+    }
+
+    /// Pops from the `head` end. `pop_back` is this with the ends swapped.
+    fn pop_at(head: &mut *mut Node<T>, tail: &mut *mut Node<T>) -> Option<T> {
+        if head.is_null() {
+            return None;
+        }
+        unsafe {
+            let node = Box::from_raw(*head);
+            let next = step(ptr::null_mut(), *head);
+            if next.is_null() {
+                *tail = ptr::null_mut();
+            } else {
+                // The new head's link was node ^ next, and now it's 0 ^ next
+                (*next).link ^= addr(*head);
+            }
+            *head = next;
+            Some(node.elem)
+        }
+    }
+
+    pub fn push_front(&mut self, elem: T) {
+        Self::push_at(&mut self.head, &mut self.tail, elem);
+        self.len += 1;
+    }
+
+    pub fn push_back(&mut self, elem: T) {
+        Self::push_at(&mut self.tail, &mut self.head, elem);
+        self.len += 1;
+    }
+
+    pub fn pop_front(&mut self) -> Option<T> {
+        let elem = Self::pop_at(&mut self.head, &mut self.tail)?;
+        self.len -= 1;
+        Some(elem)
+    }
+
+    pub fn pop_back(&mut self) -> Option<T> {
+        let elem = Self::pop_at(&mut self.tail, &mut self.head)?;
+        self.len -= 1;
+        Some(elem)
+    }
+
+    pub fn front(&self) -> Option<&T> {
+        unsafe { self.head.as_ref().map(|node| &node.elem) }
+    }
+
+    pub fn front_mut(&mut self) -> Option<&mut T> {
+        unsafe { self.head.as_mut().map(|node| &mut node.elem) }
+    }
+
+    pub fn back(&self) -> Option<&T> {
+        unsafe { self.tail.as_ref().map(|node| &node.elem) }
+    }
+
+    pub fn back_mut(&mut self) -> Option<&mut T> {
+        unsafe { self.tail.as_mut().map(|node| &mut node.elem) }
+    }
+
+    /// Reverses the list in O(1).
+    pub fn reverse(&mut self) {
+        std::mem::swap(&mut self.head, &mut self.tail);
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            front: (ptr::null_mut(), self.head),
+            back: (ptr::null_mut(), self.tail),
+            len: self.len,
+            _boo: PhantomData,
+        }
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        IterMut {
+            front: (ptr::null_mut(), self.head),
+            back: (ptr::null_mut(), self.tail),
+            len: self.len,
+            _boo: PhantomData,
+        }
+    }
+}
+
+impl<T> Drop for XorList<T> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+impl<T> Default for XorList<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Clone> Clone for XorList<T> {
+    fn clone(&self) -> Self {
+        self.iter().cloned().collect()
+    }
+}
+
+impl<T> Extend<T> for XorList<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for elem in iter {
+            self.push_back(elem);
+        }
+    }
+}
+
+impl<T> FromIterator<T> for XorList<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut list = XorList::new();
+        list.extend(iter);
+        list
+    }
+}
+
+impl<T: PartialEq> PartialEq for XorList<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.iter().eq(other)
+    }
+}
+
+impl<T: Eq> Eq for XorList<T> {}
+
+impl<T: fmt::Debug> fmt::Debug for XorList<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// Moves `(from, node)` one step along, returning the node it was on.
+///
+/// # Safety
+///
+/// `node` must be live, and `from` its neighbour on the side already walked.
+unsafe fn advance<T>(pos: &mut (*mut Node<T>, *mut Node<T>)) -> *mut Node<T> {
+    let (from, node) = *pos;
+    *pos = (node, step(from, node));
+    node
+}
+
+/// Iterator over the elements of an [`XorList`]. Each end keeps the node it's on and the
+/// one it came from, which is what a step needs.
+pub struct Iter<'a, T> {
+    front: (*mut Node<T>, *mut Node<T>),
+    back: (*mut Node<T>, *mut Node<T>),
+    len: usize,
+    _boo: PhantomData<&'a T>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        // While len > 0, the front end is on a live node
+        unsafe { Some(&(*advance(&mut self.front)).elem) }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for Iter<'a, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        unsafe { Some(&(*advance(&mut self.back)).elem) }
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Iter<'a, T> {}
+
+impl<'a, T> IntoIterator for &'a XorList<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+pub struct IterMut<'a, T> {
+    front: (*mut Node<T>, *mut Node<T>),
+    back: (*mut Node<T>, *mut Node<T>),
+    len: usize,
+    _boo: PhantomData<&'a mut T>,
+}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        // The two ends never hand out the same node, since `len` stops them meeting
+        unsafe { Some(&mut (*advance(&mut self.front)).elem) }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for IterMut<'a, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        unsafe { Some(&mut (*advance(&mut self.back)).elem) }
+    }
+}
+
+impl<'a, T> ExactSizeIterator for IterMut<'a, T> {}
+
+impl<'a, T> IntoIterator for &'a mut XorList<T> {
+    type Item = &'a mut T;
+    type IntoIter = IterMut<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+pub struct IntoIter<T>(XorList<T>);
+
+impl<T> IntoIterator for XorList<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter(self)
+    }
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.0.pop_front()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.0.len, Some(self.0.len))
+    }
+}
+
+impl<T> DoubleEndedIterator for IntoIter<T> {
+    fn next_back(&mut self) -> Option<T> {
+        self.0.pop_back()
+    }
+}
+
+impl<T> ExactSizeIterator for IntoIter<T> {}
+
+#[cfg(test)]
+mod test {
+    use super::XorList;
+    use std::collections::VecDeque;
+    use std::rc::Rc;
+
+    #[test]
+    fn basics() {
+        let mut list = XorList::new();
+
+        // Check empty list behaves right
+        assert_eq!(list.pop_front(), None);
+        assert_eq!(list.pop_back(), None);
+        assert_eq!(list.front(), None);
+        assert_eq!(list.iter().next(), None);
+
+        // Populate list
+        list.push_back(2);
+        list.push_back(3);
+        list.push_front(1);
+        assert_eq!(format!("{:?}", list), "[1, 2, 3]");
+        assert_eq!(list.len(), 3);
+
+        // Check normal removal
+        assert_eq!(list.pop_front(), Some(1));
+        assert_eq!(list.pop_back(), Some(3));
+
+        // Push some more just to make sure nothing's corrupted
+        list.push_front(0);
+        list.push_back(4);
+        *list.front_mut().unwrap() -= 1;
+        *list.back_mut().unwrap() += 1;
+        assert!(list.iter().eq(&[-1, 2, 5]));
+        assert!(list.iter().rev().eq(&[5, 2, -1]));
+
+        // Check exhaustion
+        assert_eq!(list.pop_back(), Some(5));
+        assert_eq!(list.pop_back(), Some(2));
+        assert_eq!(list.pop_back(), Some(-1));
+        assert_eq!(list.pop_back(), None);
+        assert_eq!(list.back(), None);
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    fn iterators_meet_in_the_middle() {
+        for n in 0..6 {
+            let mut list: XorList<u32> = (0..n).collect();
+            for elem in list.iter_mut() {
+                *elem *= 10;
+            }
+
+            // Alternate ends: every element comes out exactly once
+            let mut iter = list.iter();
+            let mut seen = Vec::new();
+            assert_eq!(iter.len(), n as usize);
+            while let Some(&elem) = iter.next() {
+                seen.push(elem);
+                if let Some(&elem) = iter.next_back() {
+                    seen.push(elem);
+                }
+            }
+            seen.sort_unstable();
+            assert!(seen.iter().copied().eq((0..n).map(|i| i * 10)));
+        }
+    }
+
+    #[test]
+    fn reverse() {
+        let mut list: XorList<_> = (1..=4).collect();
+        list.reverse();
+        assert!(list.iter().eq(&[4, 3, 2, 1]));
+
+        // Pushing and popping still work from the new ends
+        list.push_front(5);
+        list.push_back(0);
+        assert_eq!(list.pop_back(), Some(0));
+        list.reverse();
+        assert!(list.clone().into_iter().eq(1..=5));
+        assert!(list.into_iter().rev().eq((1..=5).rev()));
+    }
+
+    #[test]
+    fn drops_what_it_holds() {
+        let token = Rc::new(());
+        {
+            let mut list = XorList::new();
+            for i in 0..10 {
+                if i % 2 == 0 {
+                    list.push_front(token.clone());
+                } else {
+                    list.push_back(token.clone());
+                }
+            }
+            list.pop_back();
+            assert_eq!(Rc::strong_count(&token), 10);
+            let mut iter = list.clone().into_iter();
+            iter.next_back();
+            assert_eq!(Rc::strong_count(&token), 18);
+        }
+        assert_eq!(Rc::strong_count(&token), 1);
+    }
+
+    #[test]
+    fn against_vec_deque() {
+        let mut list = XorList::new();
+        let mut model = VecDeque::new();
+        let n = if cfg!(miri) { 500 } else { 20_000 };
+        let mut x: u32 = 1;
+        for i in 0..n {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            match x >> 29 {
+                0 => assert_eq!(list.pop_front(), model.pop_front()),
+                1 => assert_eq!(list.pop_back(), model.pop_back()),
+                2 => {
+                    list.reverse();
+                    let reversed = model.drain(..).rev().collect();
+                    model = reversed;
+                }
+                3 | 4 => {
+                    list.push_front(x);
+                    model.push_front(x);
+                }
+                _ => {
+                    list.push_back(x);
+                    model.push_back(x);
+                }
+            }
+            assert_eq!(list.len(), model.len());
+            assert_eq!(list.front(), model.front());
+            assert_eq!(list.back(), model.back());
+            if i % 100 == 0 {
+                assert!(list.iter().eq(model.iter()));
+                assert!(list.iter().rev().eq(model.iter().rev()));
+            }
+        }
+    }
+}